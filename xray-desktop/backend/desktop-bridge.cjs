@@ -1,17 +1,701 @@
 #!/usr/bin/env node
 
+const { execFile } = require('node:child_process');
+const https = require('node:https');
+const net = require('node:net');
+const os = require('node:os');
+const readline = require('node:readline');
+const tls = require('node:tls');
 const { AgentCore, AgentError, formatAgentError } = require('xray-client-agent');
 
 function reply(payload) {
   process.stdout.write(`${JSON.stringify(payload)}\n`);
 }
 
-async function main() {
-  const action = process.argv[2];
-  const payloadRaw = process.argv[3] ?? '{}';
-  const payload = JSON.parse(payloadRaw);
-  const core = new AgentCore();
+// Read by the shell off stderr and forwarded as `vpn://connect-progress`.
+function progress(phase, detail) {
+  const report = detail == null ? { phase } : { phase, detail: String(detail) };
+  process.stderr.write(`@progress ${JSON.stringify(report)}\n`);
+}
+
+// Read by the shell off stderr and forwarded as `vpn://speedtest-progress`.
+function speedTestProgress(report) {
+  const phase = String(report?.phase ?? '');
+  if (!['latency', 'download', 'upload'].includes(phase)) return;
+  const line = { phase };
+  if (Number.isFinite(report.percent)) {
+    line.percent = Math.max(0, Math.min(100, Math.round(report.percent)));
+  }
+  if (Number.isFinite(report.mbps)) line.mbps = report.mbps;
+  process.stderr.write(`@speedtest ${JSON.stringify(line)}\n`);
+}
+
+function errorMessage(error) {
+  return error instanceof AgentError
+    ? formatAgentError(error)
+    : error instanceof Error
+      ? error.message
+      : String(error);
+}
+
+function errorCode(error) {
+  return error && typeof error.code === 'string' ? error.code : undefined;
+}
+
+// Set when a connect through this bridge succeeds; the agent does not expose a start time.
+let connectedAt = null;
+// Last `dns` servers applied through this bridge, for agents whose status doesn't report them.
+let appliedDns = [];
+// Set by `pause`, cleared by `resume`, `connect` and `disconnect`.
+let paused = false;
+// Last `ipv6` toggle, for agents whose status doesn't report it.
+let ipv6Enabled = true;
+// Last `mtu` applied through this bridge; `null` is the agent's default.
+let appliedMtu = null;
+// Last `transport` pinned through this bridge; `auto` leaves it to the config.
+let appliedTransport = 'auto';
+// Last `bandwidth` caps applied through this bridge; `null` means unlimited.
+let appliedBandwidth = { upKbps: null, downKbps: null };
+
+async function stats(core) {
+  const status = await core.status();
+  if (!status.connected) {
+    connectedAt = null;
+    return null;
+  }
+
+  // Byte counters need the xray stats API, which only newer agents expose.
+  const counters = typeof core.stats === 'function' ? await core.stats() : {};
+  // Link health for the quality indicator; `null` when the agent doesn't measure it.
+  const measured = (value) =>
+    value == null || !Number.isFinite(Number(value)) ? null : Number(value);
+  return {
+    uplinkBytes: Number(counters.uplinkBytes ?? 0),
+    downlinkBytes: Number(counters.downlinkBytes ?? 0),
+    uptimeSecs: connectedAt ? Math.floor((Date.now() - connectedAt) / 1000) : 0,
+    rttMs: measured(counters.rttMs),
+    lossPct: measured(counters.lossPct ?? counters.packetLossPct),
+  };
+}
+
+// Rejects ids the agent has no config for; `selectServer` and `connect` go through it.
+async function requireImportedServer(core, serverId) {
+  const config = (await core.status()).importedConfig;
+  if (!config || config.serverId !== serverId) {
+    throw new Error(`Server ${serverId} is not imported`);
+  }
+}
+
+// The agent holds one imported config, so the list has at most one entry for now.
+async function listServers(core) {
+  const config = (await core.status()).importedConfig;
+  if (!config) return [];
+
+  const { host, port } = config.server;
+  return [
+    {
+      id: config.serverId,
+      name: host,
+      country: null,
+      endpoint: `${host}:${port}`,
+      group: config.group ?? null,
+      transports: Array.isArray(config.transports) ? config.transports : [],
+    },
+  ];
+}
+
+// Only agents with firewall support can block traffic outside the tunnel.
+async function setKillSwitch(core, enabled) {
+  if (typeof core.setKillSwitch !== 'function') {
+    const error = new Error('Kill switch is not supported by this xray-client-agent version');
+    error.code = 'KILL_SWITCH_UNSUPPORTED';
+    throw error;
+  }
+  await core.setKillSwitch(enabled);
+}
+
+// Rules are `{ id, kind: 'app' | 'cidr', value }`, already validated by the shell.
+async function setSplitTunnel(core, rules) {
+  if (typeof core.setSplitTunnel !== 'function') {
+    const error = new Error('Split tunneling is not supported by this xray-client-agent version');
+    error.code = 'SPLIT_TUNNEL_UNSUPPORTED';
+    throw error;
+  }
+  await core.setSplitTunnel(rules);
+}
+
+async function setAppRules(core, rules) {
+  if (typeof core.setAppRules !== 'function') {
+    const error = new Error(
+      `Per-app routing is not available on ${process.platform} with this xray-client-agent version`,
+    );
+    error.code = 'APP_RULES_UNSUPPORTED';
+    throw error;
+  }
+  await core.setAppRules(rules);
+}
+
+async function setDns(core, servers) {
+  if (typeof core.setDns !== 'function') {
+    const error = new Error('Custom DNS is not supported by this xray-client-agent version');
+    error.code = 'DNS_UNSUPPORTED';
+    throw error;
+  }
+  await core.setDns(servers);
+  appliedDns = servers;
+}
+
+const RELEASES_URL = 'https://api.github.com/repos/slap-a-slut/vpnator/releases/latest';
+
+// Newest published desktop release; the shell compares versions.
+async function latestRelease() {
+  const response = await fetch(RELEASES_URL, {
+    headers: { Accept: 'application/vnd.github+json' },
+    signal: AbortSignal.timeout(10000),
+  });
+  if (!response.ok) {
+    throw new Error(`Release feed answered HTTP ${response.status}`);
+  }
+  const release = await response.json();
+  if (!release?.tag_name) throw new Error('Release feed returned no version');
+  return { version: String(release.tag_name), notesUrl: release.html_url ?? null };
+}
+
+// Disabling asks the agent to null-route IPv6; `fullyBlocked: false` means it could only
+// partly block it.
+async function setIpv6(core, enabled) {
+  if (typeof core.setIpv6 !== 'function') {
+    const error = new Error('IPv6 control is not supported by this xray-client-agent version');
+    error.code = 'IPV6_UNSUPPORTED';
+    throw error;
+  }
+  const result = await core.setIpv6(enabled);
+  ipv6Enabled = enabled;
+  return { fullyBlocked: enabled || result?.fullyBlocked !== false };
+}
+
+// The shell has already checked the range.
+async function setMtu(core, value) {
+  if (typeof core.setMtu !== 'function') {
+    const error = new Error('MTU configuration is not supported by this xray-client-agent version');
+    error.code = 'MTU_UNSUPPORTED';
+    throw error;
+  }
+  await core.setMtu(value);
+  appliedMtu = value;
+}
+
+const TRANSPORTS = new Set(['auto', 'reality', 'vless+ws', 'vless+grpc']);
+
+// Configs that list their transports are checked here too; the shell only knows the last
+// `listServers` answer.
+async function setTransport(core, transport) {
+  if (!TRANSPORTS.has(transport)) {
+    throw new Error(`Invalid transport. Expected one of ${[...TRANSPORTS].join(', ')}`);
+  }
+  if (typeof core.setTransport !== 'function') {
+    const error = new Error('Transport selection is not supported by this xray-client-agent version');
+    error.code = 'TRANSPORT_SELECTION_UNSUPPORTED';
+    throw error;
+  }
+  const config = (await core.status()).importedConfig;
+  if (
+    transport !== 'auto' &&
+    Array.isArray(config?.transports) &&
+    !config.transports.includes(transport)
+  ) {
+    const error = new Error(`Server ${config.serverId} does not offer ${transport}`);
+    error.code = 'TRANSPORT_NOT_OFFERED';
+    throw error;
+  }
+  await core.setTransport(transport);
+  appliedTransport = transport;
+}
+
+// Imported servers bring their own xray outbound; only agents that can run one connect them.
+async function useOutbound(core, outbound) {
+  if (typeof core.useOutbound !== 'function') {
+    const error = new Error('Imported servers are not supported by this xray-client-agent version');
+    error.code = 'OUTBOUND_UNSUPPORTED';
+    throw error;
+  }
+  await core.useOutbound(outbound);
+}
+
+// The shell has already rejected 0 and absurd values; `null` lifts that direction's cap.
+async function setBandwidth(core, limit) {
+  if (typeof core.setBandwidthLimit !== 'function') {
+    const error = new Error('Bandwidth limits are not supported by this xray-client-agent version');
+    error.code = 'BANDWIDTH_UNSUPPORTED';
+    throw error;
+  }
+  const normalized = {
+    upKbps: Number.isInteger(limit?.upKbps) ? limit.upKbps : null,
+    downKbps: Number.isInteger(limit?.downKbps) ? limit.downKbps : null,
+  };
+  await core.setBandwidthLimit(normalized);
+  appliedBandwidth = normalized;
+}
+
+// Either side may be missing on older installs; `null` rather than failing the whole call.
+async function versions(core) {
+  let bridge = null;
+  try {
+    bridge = require('xray-client-agent/package.json').version ?? null;
+  } catch {
+    // Packages with an `exports` map hide package.json.
+  }
+  const xray = typeof core.xrayVersion === 'function' ? await core.xrayVersion() : null;
+  return { bridge, xray: xray ?? null };
+}
+
+// What this agent version implements; the app ANDs it with what the OS build supports.
+function capabilities(core) {
+  const has = (name) => typeof core[name] === 'function';
+  return {
+    killSwitch: has('setKillSwitch'),
+    splitTunnel: has('setSplitTunnel'),
+    perApp: has('setAppRules'),
+    ipv6Control: has('setIpv6'),
+    mtu: has('setMtu'),
+    dns: has('setDns'),
+    bandwidthLimit: has('setBandwidthLimit'),
+    speedTest: has('speedTest'),
+    routes: has('routes'),
+    coreConfig: has('getCoreConfig') && has('setCoreConfig'),
+  };
+}
+
+// The agent resolves probe names and compares the answering resolvers with the tunnel's.
+async function dnsLeakTest(core) {
+  if (typeof core.dnsLeakTest !== 'function') {
+    const error = new Error('DNS leak test is not supported by this xray-client-agent version');
+    error.code = 'DNS_LEAK_TEST_UNSUPPORTED';
+    throw error;
+  }
+  const result = await core.dnsLeakTest();
+  return {
+    leaking: Boolean(result?.leaking),
+    resolverIps: Array.isArray(result?.resolverIps) ? result.resolverIps.map(String) : [],
+    throughTunnel: Boolean(result?.throughTunnel),
+  };
+}
+
+// The shell has already checked the tunnel is up.
+async function speedTest(core) {
+  if (typeof core.speedTest !== 'function') {
+    const error = new Error('Speed tests are not supported by this xray-client-agent version');
+    error.code = 'SPEED_TEST_UNSUPPORTED';
+    throw error;
+  }
+  const result = await core.speedTest({ onProgress: speedTestProgress });
+  return {
+    downMbps: Number(result?.downMbps ?? 0),
+    upMbps: Number(result?.upMbps ?? 0),
+    latencyMs: Math.max(0, Math.round(Number(result?.latencyMs ?? 0))),
+  };
+}
+
+function coreConfigUnsupported() {
+  const error = new Error('Core config editing is not supported by this xray-client-agent version');
+  error.code = 'CORE_CONFIG_UNSUPPORTED';
+  return error;
+}
+
+// Routes the agent installed for the tunnel, as `{ destination, via, interface }`; null when
+// there is no tunnel, so the shell shows an empty table.
+async function routes(core) {
+  if (!(await core.status()).connected) return null;
+  if (typeof core.routes !== 'function') {
+    const error = new Error('Route listing is not supported by this xray-client-agent version');
+    error.code = 'ROUTES_UNSUPPORTED';
+    throw error;
+  }
+  const table = await core.routes();
+  return Array.isArray(table) ? table : [];
+}
+
+// What an external service sees us as; through the tunnel when it is up.
+async function publicIp(core) {
+  if (typeof core.publicIp !== 'function') {
+    const error = new Error('Public IP lookup is not supported by this xray-client-agent version');
+    error.code = 'PUBLIC_IP_UNSUPPORTED';
+    throw error;
+  }
+  const result = await core.publicIp();
+  if (!result?.ip) throw new Error('Public IP lookup returned no address');
+  return { ip: String(result.ip), country: result.country ? String(result.country) : null };
+}
+
+// Addresses on interfaces that have a hardware address, i.e. Wi-Fi and Ethernet. Loopback and
+// TUN devices (ours included) have none, so bringing the tunnel up doesn't look like a change.
+function networkInterfaces() {
+  const addresses = [];
+  for (const [name, entries] of Object.entries(os.networkInterfaces())) {
+    for (const entry of entries ?? []) {
+      if (entry.internal || !entry.mac || /^(00:){5}00$/.test(entry.mac)) continue;
+      addresses.push({ name, address: entry.address });
+    }
+  }
+  return addresses;
+}
+
+// stdout of a system tool, or '' when it is missing or fails: detection is best effort.
+function runTool(command, args) {
+  return new Promise((resolve) => {
+    execFile(command, args, { timeout: 3000, windowsHide: true }, (error, stdout) => {
+      resolve(error ? '' : String(stdout));
+    });
+  });
+}
+
+function firstMatch(output, pattern) {
+  const match = output.match(pattern);
+  return match ? match[1].trim() || null : null;
+}
+
+const MAC_PATTERN = /((?:[0-9a-f]{1,2}[:-]){5}[0-9a-f]{1,2})/i;
+
+async function currentSsid() {
+  switch (process.platform) {
+    case 'linux':
+      return (
+        firstMatch(await runTool('iwgetid', ['-r']), /^(.+)$/m) ??
+        firstMatch(
+          await runTool('nmcli', ['-t', '-f', 'active,ssid', 'dev', 'wifi']),
+          /^yes:(.+)$/m,
+        )
+      );
+    case 'darwin':
+      return firstMatch(
+        await runTool('networksetup', ['-getairportnetwork', 'en0']),
+        /Current Wi-Fi Network: (.+)$/m,
+      );
+    case 'win32':
+      return firstMatch(
+        await runTool('netsh', ['wlan', 'show', 'interfaces']),
+        /^\s*SSID\s*:\s*(.+)$/m,
+      );
+    default:
+      return null;
+  }
+}
+
+async function defaultGateway() {
+  switch (process.platform) {
+    case 'linux':
+      return firstMatch(await runTool('ip', ['route', 'show', 'default']), /default via (\S+)/);
+    case 'darwin':
+      return firstMatch(await runTool('route', ['-n', 'get', 'default']), /gateway: (\S+)/);
+    case 'win32':
+      return firstMatch(
+        await runTool('powershell', [
+          '-NoProfile',
+          '-Command',
+          '(Get-NetRoute -DestinationPrefix 0.0.0.0/0 | Sort-Object RouteMetric | Select-Object -First 1).NextHop',
+        ]),
+        /^(\S+)$/m,
+      );
+    default:
+      return null;
+  }
+}
+
+async function gatewayMac() {
+  const gateway = await defaultGateway();
+  if (!gateway) return null;
+  const output =
+    process.platform === 'linux'
+      ? await runTool('ip', ['neigh', 'show', gateway])
+      : await runTool('arp', process.platform === 'win32' ? ['-a', gateway] : ['-n', gateway]);
+  return firstMatch(output, MAC_PATTERN);
+}
+
+// Raw material for the shell's conflict check: every interface name (TUN and TAP devices
+// included, unlike networkInterfaces) and the names of running executables.
+async function vpnInventory() {
+  const interfaces = Object.keys(os.networkInterfaces());
+  const processes =
+    process.platform === 'win32'
+      ? (await runTool('tasklist', ['/fo', 'csv', '/nh']))
+          .split(/\r?\n/)
+          .map((line) => firstMatch(line, /^"([^"]*)"/))
+      : (await runTool('ps', ['-A', '-o', 'comm='])).split('\n').map((line) => line.trim());
+  return { interfaces, processes: [...new Set(processes.filter(Boolean))] };
+}
+
+// What trusted networks are matched against: the Wi-Fi SSID and the default gateway's MAC,
+// which also tells wired networks apart. Either is null when it can't be read.
+async function networkIdentity() {
+  const [ssid, mac] = await Promise.all([currentSsid(), gatewayMac()]);
+  return { ssid, gatewayMac: mac };
+}
+
+// Codes the agent uses when the control server won't renew the token any more.
+const EXPIRED_CODES = new Set(['AUTH_EXPIRED', 'TOKEN_EXPIRED', 'UNAUTHORIZED']);
+// Codes for a token the control server rejects outright (revoked, or issued by another server).
+const INVALID_CODES = new Set(['AUTH_INVALID', 'TOKEN_INVALID', 'FORBIDDEN']);
+
+// The agent's auth failures as the two codes the shell maps to typed errors; others pass through.
+function asAuthError(error, label) {
+  const code = errorCode(error);
+  const authCode = EXPIRED_CODES.has(code)
+    ? 'AUTH_EXPIRED'
+    : INVALID_CODES.has(code)
+      ? 'AUTH_INVALID'
+      : null;
+  if (!authCode) return error;
+  const auth = new Error(`${label}: ${errorMessage(error)}`);
+  auth.code = authCode;
+  return auth;
+}
+
+async function refreshToken(core, baseUrl, token) {
+  if (typeof core.refreshToken !== 'function') {
+    const error = new Error('Token refresh is not supported by this xray-client-agent version');
+    error.code = 'TOKEN_REFRESH_UNSUPPORTED';
+    throw error;
+  }
+  if (!baseUrl) throw new Error('baseUrl is required');
+  if (!token) throw new Error('token is required');
+
+  let refreshed;
+  try {
+    refreshed = await core.refreshToken({ baseUrl, token });
+  } catch (error) {
+    if (EXPIRED_CODES.has(errorCode(error))) {
+      const expired = new Error(`Token refresh rejected: ${errorMessage(error)}`);
+      expired.code = 'AUTH_EXPIRED';
+      throw expired;
+    }
+    throw error;
+  }
+  // `expiresAt` is passed on as unix seconds whether the agent gives seconds or an ISO date.
+  const expiresAt =
+    typeof refreshed.expiresAt === 'string'
+      ? Math.floor(Date.parse(refreshed.expiresAt) / 1000)
+      : refreshed.expiresAt;
+  return {
+    token: String(refreshed.token),
+    expiresAt: Number.isFinite(expiresAt) ? expiresAt : null,
+  };
+}
+
+// Keeps the xray session but stops routing traffic through it.
+async function setPaused(core, value) {
+  const method = value ? 'pause' : 'resume';
+  if (typeof core[method] !== 'function') {
+    const error = new Error('Pausing is not supported by this xray-client-agent version');
+    error.code = 'PAUSE_UNSUPPORTED';
+    throw error;
+  }
+  if (value && !(await core.status()).connected) {
+    throw new Error('Not connected');
+  }
+  await core[method]();
+  paused = value;
+}
+
+const PING_TIMEOUT_MS = 3000;
+
+// TCP connect time to the server endpoint; `rttMs: null` when it can't be reached in time.
+async function ping(core, serverId) {
+  await requireImportedServer(core, serverId);
+  const { host, port } = (await core.status()).importedConfig.server;
+
+  const rttMs = await new Promise((resolve) => {
+    const started = process.hrtime.bigint();
+    const socket = net.connect({ host, port, timeout: PING_TIMEOUT_MS });
+    const finish = (value) => {
+      socket.destroy();
+      resolve(value);
+    };
+    socket.once('connect', () => finish(Number((process.hrtime.bigint() - started) / 1000000n)));
+    socket.once('timeout', () => finish(null));
+    socket.once('error', () => finish(null));
+  });
+  return { rttMs };
+}
+
+// Certificate problems mean the server answered; it just can't be trusted.
+const TLS_ERROR_CODES = new Set([
+  'CERT_HAS_EXPIRED',
+  'DEPTH_ZERO_SELF_SIGNED_CERT',
+  'SELF_SIGNED_CERT_IN_CHAIN',
+  'UNABLE_TO_VERIFY_LEAF_SIGNATURE',
+  'UNABLE_TO_GET_ISSUER_CERT_LOCALLY',
+  'ERR_TLS_CERT_ALTNAME_INVALID',
+]);
+
+const PROBE_TIMEOUT_MS = 5000;
+const PROBE_BODY_BYTES = 4096;
+
+// Hits the server the way a browser would, with the cover site's name and no token, and reports
+// what came back. The certificate isn't checked: REALITY forwards the cover's own, and what
+// matters here is the page. The shell judges whether it looks like the cover.
+async function probeDisguise(core, baseUrl, serverId) {
+  await requireImportedServer(core, serverId);
+  const config = (await core.status()).importedConfig;
+  if (baseUrl && config.baseUrl && new URL(config.baseUrl).origin !== new URL(baseUrl).origin) {
+    throw new Error(`Server ${serverId} is not from ${baseUrl}`);
+  }
+  const { host, port } = config.server;
+  const coverHost = config.reality?.serverName ?? null;
+  const name = coverHost ?? host;
+
+  return new Promise((resolve, reject) => {
+    const request = https.get(
+      {
+        host,
+        port,
+        path: '/',
+        servername: net.isIP(name) ? undefined : name,
+        rejectUnauthorized: false,
+        timeout: PROBE_TIMEOUT_MS,
+        headers: {
+          host: name,
+          'user-agent': 'Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36',
+          accept: 'text/html,application/xhtml+xml,*/*;q=0.8',
+        },
+      },
+      (response) => {
+        const chunks = [];
+        let size = 0;
+        const finish = () => {
+          request.destroy();
+          resolve({
+            statusCode: response.statusCode ?? 0,
+            contentType: response.headers['content-type'] ?? null,
+            location: response.headers.location ?? null,
+            body: Buffer.concat(chunks).subarray(0, PROBE_BODY_BYTES).toString('utf8'),
+            coverHost,
+          });
+        };
+        response.on('data', (chunk) => {
+          chunks.push(chunk);
+          size += chunk.length;
+          if (size >= PROBE_BODY_BYTES) finish();
+        });
+        response.once('end', finish);
+        response.once('error', finish);
+      },
+    );
+    request.once('timeout', () =>
+      request.destroy(new Error(`No answer within ${PROBE_TIMEOUT_MS} ms`)),
+    );
+    request.once('error', (error) => reject(error));
+  });
+}
 
+const FINGERPRINT_TIMEOUT_MS = 5000;
+
+// SHA-256 of the certificate the control server presents, as `AB:CD:...`. The CA chain isn't
+// checked here: a self-signed panel is exactly what a pin is for.
+function liveFingerprint(baseUrl) {
+  const url = new URL(baseUrl);
+  if (url.protocol !== 'https:') throw new Error(`${baseUrl} does not use TLS`);
+  const host = url.hostname.replace(/^\[|\]$/g, '');
+  return new Promise((resolve, reject) => {
+    const socket = tls.connect(
+      {
+        host,
+        port: Number(url.port || 443),
+        servername: net.isIP(host) ? undefined : host,
+        rejectUnauthorized: false,
+        timeout: FINGERPRINT_TIMEOUT_MS,
+      },
+      () => {
+        const fingerprint = socket.getPeerCertificate()?.fingerprint256;
+        socket.end();
+        if (fingerprint) resolve(fingerprint);
+        else reject(new Error(`${baseUrl} presented no certificate`));
+      },
+    );
+    socket.once('timeout', () =>
+      socket.destroy(new Error(`No TLS handshake within ${FINGERPRINT_TIMEOUT_MS} ms`)),
+    );
+    socket.once('error', (error) => reject(error));
+  });
+}
+
+function sameFingerprint(a, b) {
+  const bare = (value) => String(value).replace(/[^0-9a-f]/gi, '').toLowerCase();
+  return bare(a) === bare(b);
+}
+
+// The shell adds `pinnedFingerprint` to any request for a pinned `baseUrl`; nothing reaches the
+// server before its certificate matched.
+async function checkPin(payload) {
+  if (!payload.pinnedFingerprint || !payload.baseUrl) return;
+  const baseUrl = String(payload.baseUrl);
+  if (!sameFingerprint(await liveFingerprint(baseUrl), payload.pinnedFingerprint)) {
+    const error = new Error(`The certificate of ${baseUrl} does not match the pinned fingerprint`);
+    error.code = 'FINGERPRINT_MISMATCH';
+    throw error;
+  }
+}
+
+// Any HTTP answer counts, even an error status: the point is that the control server is there.
+async function pingBaseUrl(baseUrl) {
+  const https = baseUrl.startsWith('https:');
+  const started = process.hrtime.bigint();
+  try {
+    await fetch(baseUrl, { method: 'HEAD', signal: AbortSignal.timeout(PING_TIMEOUT_MS) });
+    return {
+      reachable: true,
+      rttMs: Number((process.hrtime.bigint() - started) / 1000000n),
+      tlsValid: https ? true : null,
+      error: null,
+    };
+  } catch (error) {
+    const cause = error?.cause ?? error;
+    const tlsInvalid = TLS_ERROR_CODES.has(cause?.code);
+    const timedOut = error?.name === 'TimeoutError';
+    return {
+      reachable: tlsInvalid,
+      rttMs: null,
+      tlsValid: tlsInvalid ? false : null,
+      error: timedOut ? `No answer within ${PING_TIMEOUT_MS} ms` : errorMessage(cause),
+    };
+  }
+}
+
+const SUBSCRIPTION_TIMEOUT_MS = 15000;
+
+// A subscription is JSON (an array of `{ name, baseUrl, token }`, or `{ entries: [...] }`) or
+// text with one `xraycp://import?...` link per line, possibly base64-encoded as a whole. Links
+// are passed through as strings for the shell to validate like deep links.
+function parseSubscription(text) {
+  const trimmed = text.trim();
+  try {
+    const body = JSON.parse(trimmed);
+    const entries = Array.isArray(body) ? body : body?.entries;
+    if (Array.isArray(entries)) return entries;
+  } catch {
+    // Not JSON, so a list of links.
+  }
+  const encoded = /^[A-Za-z0-9+/=\s]+$/.test(trimmed);
+  const lines = encoded ? Buffer.from(trimmed, 'base64').toString('utf8') : trimmed;
+  return lines
+    .split(/\r?\n/)
+    .map((line) => line.trim())
+    .filter(Boolean);
+}
+
+async function importSubscription(url) {
+  if (!url) throw new Error('url is required');
+  const response = await fetch(url, {
+    headers: { accept: 'application/json, text/plain' },
+    signal: AbortSignal.timeout(SUBSCRIPTION_TIMEOUT_MS),
+  });
+  if (!response.ok) {
+    throw new Error(`Subscription download failed: HTTP ${response.status}`);
+  }
+  return parseSubscription(await response.text());
+}
+
+async function dispatch(core, action, payload) {
+  await checkPin(payload);
   switch (action) {
     case 'importToken': {
       const baseUrl = String(payload.baseUrl ?? '');
@@ -20,7 +704,77 @@ async function main() {
       return null;
     }
     case 'connect': {
-      return core.connect();
+      progress('resolving_server', payload.serverId);
+      let status;
+      try {
+        // The shell passes the keyring token; re-import it if the agent lost its config.
+        if (payload.token) {
+          const current = await core.status();
+          if (!current.importedConfig) {
+            await core.importToken(String(payload.baseUrl ?? ''), String(payload.token));
+          }
+        }
+        if (payload.outbound) {
+          await useOutbound(core, payload.outbound);
+        } else if (payload.serverId) {
+          await requireImportedServer(core, String(payload.serverId));
+        }
+        if (payload.mode === 'proxy' || payload.mode === 'vpn') {
+          await core.setMode(payload.mode);
+        }
+        // Before connecting: the transport decides how the outbound is built.
+        if (payload.transport) {
+          await setTransport(core, String(payload.transport));
+        }
+        progress('spawning_xray');
+        // Agents that know their own phases report `configuring_tun` and `handshaking` here.
+        status = await core.connect({ onProgress: progress });
+      } catch (error) {
+        throw asAuthError(error, 'Connect rejected');
+      }
+      connectedAt = Date.now();
+      paused = false;
+      // A tunnel that was asked for a kill switch doesn't stay up without one.
+      if (payload.killSwitch) {
+        try {
+          await setKillSwitch(core, true);
+        } catch (error) {
+          await core.disconnect().catch(() => {});
+          connectedAt = null;
+          throw error;
+        }
+      }
+      // The rest is best-effort: the tunnel is up, and failing the connect over one of them
+      // would leave it running behind an error.
+      const reapply = async (setting, apply) => {
+        try {
+          await apply();
+        } catch (error) {
+          process.stderr.write(`warn: ${setting} not applied: ${errorMessage(error)}\n`);
+        }
+      };
+      if (Array.isArray(payload.dns)) {
+        await reapply('DNS servers', () => setDns(core, payload.dns.map(String)));
+      }
+      if (Array.isArray(payload.splitTunnel)) {
+        await reapply('split tunnel', () => setSplitTunnel(core, payload.splitTunnel));
+      }
+      if (Array.isArray(payload.appRules)) {
+        await reapply('app rules', () => setAppRules(core, payload.appRules));
+      }
+      if (Number.isInteger(payload.mtu)) {
+        await reapply('MTU', () => setMtu(core, payload.mtu));
+      }
+      if (payload.bandwidth) {
+        await reapply('bandwidth limit', () => setBandwidth(core, payload.bandwidth));
+      }
+      if (payload.ipv6 === false) {
+        await reapply('IPv6 block', async () => {
+          const { fullyBlocked } = await setIpv6(core, false);
+          if (!fullyBlocked) process.stderr.write('warn: IPv6 could not be fully blocked\n');
+        });
+      }
+      return status;
     }
     case 'setMode': {
       const mode = String(payload.mode ?? '');
@@ -31,6 +785,20 @@ async function main() {
     }
     case 'disconnect': {
       await core.disconnect();
+      connectedAt = null;
+      paused = false;
+      // A requested disconnect restores connectivity even with the kill switch on.
+      if (typeof core.setKillSwitch === 'function') {
+        await core.setKillSwitch(false);
+      }
+      return null;
+    }
+    case 'refreshToken': {
+      return refreshToken(core, String(payload.baseUrl ?? ''), String(payload.token ?? ''));
+    }
+    case 'pause':
+    case 'resume': {
+      await setPaused(core, action === 'pause');
       return null;
     }
     case 'updateDisguise': {
@@ -38,10 +806,142 @@ async function main() {
       const serverId = String(payload.serverId ?? '');
       const adminApiKey = String(payload.adminApiKey ?? '');
       const disguise = payload.disguise;
+      if (payload.dryRun) return previewDisguiseUpdate(baseUrl, serverId, adminApiKey, disguise);
       return updateDisguise(baseUrl, serverId, adminApiKey, disguise);
     }
+    case 'listDisguiseTemplates': {
+      return listDisguiseTemplates(String(payload.baseUrl ?? ''), String(payload.adminApiKey ?? ''));
+    }
+    case 'importSubscription': {
+      return importSubscription(String(payload.url ?? ''));
+    }
+    case 'rotateAdminKey': {
+      return rotateAdminKey(String(payload.baseUrl ?? ''), String(payload.adminApiKey ?? ''));
+    }
+    case 'verifyAdminKey': {
+      return verifyAdminKey(String(payload.baseUrl ?? ''), String(payload.adminApiKey ?? ''));
+    }
+    case 'fingerprint': {
+      return { fingerprint: await liveFingerprint(String(payload.baseUrl ?? '')) };
+    }
+    case 'probeDisguise': {
+      return probeDisguise(core, String(payload.baseUrl ?? ''), String(payload.serverId ?? ''));
+    }
+    case 'testDisguise': {
+      const baseUrl = String(payload.baseUrl ?? '');
+      const serverId = String(payload.serverId ?? '');
+      const adminApiKey = String(payload.adminApiKey ?? '');
+      return testDisguise(baseUrl, serverId, adminApiKey, payload.disguise);
+    }
     case 'status': {
-      return core.status();
+      const status = await core.status();
+      return {
+        ...status,
+        serverId: status.importedConfig?.serverId ?? null,
+        uptimeSecs:
+          status.connected && connectedAt ? Math.floor((Date.now() - connectedAt) / 1000) : null,
+        publicIp: status.publicIp ?? null,
+        killSwitchActive: Boolean(status.killSwitchActive),
+        dns: Array.isArray(status.dns) ? status.dns : appliedDns,
+        baseUrl: status.importedConfig?.baseUrl ?? null,
+        disguise: status.importedConfig?.reality?.serverName ?? null,
+        paused: typeof status.paused === 'boolean' ? status.paused : paused,
+        ipv6Enabled: typeof status.ipv6Enabled === 'boolean' ? status.ipv6Enabled : ipv6Enabled,
+        mtu: Number.isInteger(status.mtu) ? status.mtu : appliedMtu,
+        bandwidthLimit: status.bandwidthLimit ?? appliedBandwidth,
+        transport: status.connected ? (status.transport ?? appliedTransport) : null,
+      };
+    }
+    case 'stats': {
+      return stats(core);
+    }
+    case 'routes': {
+      return routes(core);
+    }
+    case 'capabilities': {
+      return capabilities(core);
+    }
+    case 'speedTest': {
+      return speedTest(core);
+    }
+    case 'coreConfig': {
+      if (typeof core.getCoreConfig !== 'function') throw coreConfigUnsupported();
+      return (await core.getCoreConfig()) ?? null;
+    }
+    // The shell has already checked the required keys.
+    case 'setCoreConfig': {
+      if (typeof core.setCoreConfig !== 'function') throw coreConfigUnsupported();
+      await core.setCoreConfig(payload.config);
+      return null;
+    }
+    case 'listServers': {
+      return listServers(core);
+    }
+    case 'ping': {
+      if (payload.baseUrl) return pingBaseUrl(String(payload.baseUrl));
+      // Without a target it is just a liveness probe, for `runSelfTest`.
+      if (!payload.serverId) return { pong: true };
+      return ping(core, String(payload.serverId));
+    }
+    case 'killSwitch': {
+      // Blocking while disconnected would cut the user off; `connect` arms it later.
+      const enabled = Boolean(payload.enabled);
+      const status = await core.status();
+      await setKillSwitch(core, enabled && Boolean(status.connected));
+      return null;
+    }
+    case 'dns': {
+      await setDns(core, Array.isArray(payload.servers) ? payload.servers.map(String) : []);
+      return null;
+    }
+    case 'transport': {
+      await setTransport(core, String(payload.transport ?? ''));
+      return null;
+    }
+    case 'mtu': {
+      await setMtu(core, Number(payload.value));
+      return null;
+    }
+    case 'bandwidth': {
+      await setBandwidth(core, payload);
+      return null;
+    }
+    case 'ipv6': {
+      return setIpv6(core, Boolean(payload.enabled));
+    }
+    case 'dnsLeakTest': {
+      return dnsLeakTest(core);
+    }
+    case 'latestRelease': {
+      return latestRelease();
+    }
+    case 'publicIp': {
+      return publicIp(core);
+    }
+    case 'networkInterfaces': {
+      return networkInterfaces();
+    }
+    case 'networkIdentity': {
+      return networkIdentity();
+    }
+    case 'vpnInventory': {
+      return vpnInventory();
+    }
+    case 'setLogLevel': {
+      return setLogLevel(core, String(payload.level ?? ''));
+    }
+    case 'setSplitTunnel': {
+      return setSplitTunnel(core, Array.isArray(payload.rules) ? payload.rules : []);
+    }
+    case 'appRules': {
+      return setAppRules(core, Array.isArray(payload.rules) ? payload.rules : []);
+    }
+    case 'version': {
+      return versions(core);
+    }
+    case 'selectServer': {
+      await requireImportedServer(core, String(payload.serverId ?? ''));
+      return null;
     }
     default:
       throw new Error(`Unsupported bridge action: ${action ?? 'undefined'}`);
@@ -49,20 +949,85 @@ async function main() {
 }
 
 async function updateDisguise(baseUrl, serverId, adminApiKey, disguise) {
+  return disguiseRequest('PATCH', '', 'Disguise update', baseUrl, serverId, adminApiKey, disguise);
+}
+
+// What `updateDisguise` would change, diffed per top-level key against the server's current
+// disguise; nothing is written. Keys come out sorted, absent ones as null.
+async function previewDisguiseUpdate(baseUrl, serverId, adminApiKey, disguise) {
+  if (!disguise || typeof disguise !== 'object') throw new Error('disguise payload is required');
+  const args = [baseUrl, serverId, adminApiKey];
+  const current = (await disguiseRequest('GET', '', 'Disguise lookup', ...args)) ?? {};
+  const fields = [...new Set([...Object.keys(current), ...Object.keys(disguise)])].sort();
+  const changedFields = fields
+    .filter((field) => JSON.stringify(current[field]) !== JSON.stringify(disguise[field]))
+    .map((field) => ({ field, from: current[field] ?? null, to: disguise[field] ?? null }));
+  return { changedFields, current, proposed: disguise };
+}
+
+// The panel validates and renders the disguise without saving it.
+async function testDisguise(baseUrl, serverId, adminApiKey, disguise) {
+  const args = [baseUrl, serverId, adminApiKey, disguise];
+  return disguiseRequest('POST', '/preview', 'Disguise preview', ...args);
+}
+
+// The panel invalidates `adminApiKey` once the new key is issued; the shell only saves the new
+// one after `verifyAdminKey` accepted it.
+async function rotateAdminKey(baseUrl, adminApiKey) {
+  if (!baseUrl) throw new Error('baseUrl is required');
+  if (!adminApiKey) throw new Error('adminApiKey is required');
+
+  const response = await fetch(new URL('/admin/api-key/rotate', baseUrl).toString(), {
+    method: 'POST',
+    headers: { authorization: `Bearer ${adminApiKey}`, accept: 'application/json' },
+  });
+  if (!response.ok) {
+    throw new Error(`Admin key rotation failed: HTTP ${response.status}`);
+  }
+  const body = await response.json();
+  return { adminApiKey: String(body?.adminApiKey ?? body?.key ?? '') };
+}
+
+// The template list is the cheapest call that needs admin rights and changes nothing.
+async function verifyAdminKey(baseUrl, adminApiKey) {
+  await listDisguiseTemplates(baseUrl, adminApiKey);
+  return { ok: true };
+}
+
+async function listDisguiseTemplates(baseUrl, adminApiKey) {
+  if (!baseUrl) throw new Error('baseUrl is required');
+  if (!adminApiKey) throw new Error('adminApiKey is required');
+
+  const response = await fetch(new URL('/xray-disguise/templates', baseUrl).toString(), {
+    headers: { authorization: `Bearer ${adminApiKey}`, accept: 'application/json' },
+  });
+  if (!response.ok) {
+    throw new Error(`Disguise template list failed: HTTP ${response.status}`);
+  }
+  const body = await response.json();
+  // Older panels wrap the list.
+  return Array.isArray(body) ? body : (body?.templates ?? []);
+}
+
+async function disguiseRequest(method, suffix, label, baseUrl, serverId, adminApiKey, disguise) {
   if (!baseUrl) throw new Error('baseUrl is required');
   if (!serverId) throw new Error('serverId is required');
   if (!adminApiKey) throw new Error('adminApiKey is required');
-  if (!disguise || typeof disguise !== 'object') throw new Error('disguise payload is required');
+  const reading = method === 'GET';
+  if (!reading && (!disguise || typeof disguise !== 'object')) {
+    throw new Error('disguise payload is required');
+  }
 
-  const target = new URL(`/servers/${encodeURIComponent(serverId)}/xray-disguise`, baseUrl).toString();
+  const path = `/servers/${encodeURIComponent(serverId)}/xray-disguise${suffix}`;
+  const target = new URL(path, baseUrl).toString();
   const response = await fetch(target, {
-    method: 'PATCH',
+    method,
     headers: {
       'content-type': 'application/json',
       authorization: `Bearer ${adminApiKey}`,
       accept: 'application/json',
     },
-    body: JSON.stringify(disguise),
+    body: reading ? undefined : JSON.stringify(disguise),
   });
 
   if (!response.ok) {
@@ -72,24 +1037,105 @@ async function updateDisguise(baseUrl, serverId, adminApiKey, disguise) {
     } catch {
       body = '';
     }
-    throw new Error(`Disguise update failed: HTTP ${response.status}${body ? ` ${body}` : ''}`);
+    throw new Error(`${label} failed: HTTP ${response.status}${body ? ` ${body}` : ''}`);
   }
 
   return response.json();
 }
 
-void main()
-  .then((data) => {
-    reply({ ok: true, data });
-  })
-  .catch((error) => {
-    const message =
-      error instanceof AgentError
-        ? formatAgentError(error)
-        : error instanceof Error
-          ? error.message
-          : String(error);
-
-    reply({ ok: false, error: message });
-    process.exitCode = 1;
+// A request with `stream: true` gets a list answer as one `{ id, record }` line per entry, as
+// they are produced when the agent hands out an async iterable, then a reply with `data: null`.
+async function streamRecords(id, data) {
+  if (data == null) return;
+  if (typeof data[Symbol.asyncIterator] !== 'function' && !Array.isArray(data)) {
+    throw new Error('This action does not answer with a list');
+  }
+  for await (const record of data) {
+    reply({ id, record });
+  }
+}
+
+const LOG_LEVELS = ['debug', 'info', 'warning', 'error', 'none'];
+// xray's loglevel: from VPNATOR_LOG_LEVEL at spawn, then setLogLevel. At 'debug' every request
+// is also traced to stderr, by action and payload field names only, never values.
+let logLevel = LOG_LEVELS.includes(process.env.VPNATOR_LOG_LEVEL)
+  ? process.env.VPNATOR_LOG_LEVEL
+  : 'warning';
+
+// Older agents keep their own level; the bridge's tracing follows either way.
+async function setLogLevel(core, level) {
+  if (!LOG_LEVELS.includes(level)) {
+    const error = new Error(`Unknown log level "${level}"`);
+    error.code = 'INVALID_LOG_LEVEL';
+    throw error;
+  }
+  if (typeof core.setLogLevel === 'function') await core.setLogLevel(level);
+  logLevel = level;
+  return { level };
+}
+
+function traceRequest(action, payload) {
+  if (logLevel !== 'debug') return;
+  const fields = payload && typeof payload === 'object' ? Object.keys(payload).sort() : [];
+  process.stderr.write(`debug: request ${action} fields [${fields.join(',')}]\n`);
+}
+
+// Long-lived mode used by the desktop shell: one JSON request per stdin line,
+// one `{ id, ok, data | error }` reply per stdout line.
+function serve() {
+  const core = new AgentCore();
+  if (logLevel !== 'warning' && typeof core.setLogLevel === 'function') {
+    void Promise.resolve(core.setLogLevel(logLevel)).catch(() => {});
+  }
+  const input = readline.createInterface({ input: process.stdin, crlfDelay: Infinity });
+
+  input.on('line', (line) => {
+    if (!line.trim()) return;
+
+    let request;
+    try {
+      request = JSON.parse(line);
+    } catch {
+      return;
+    }
+
+    const id = request.id;
+    traceRequest(String(request.action), request.payload);
+    void dispatch(core, request.action, request.payload ?? {})
+      .then(async (data) => {
+        if (request.stream === true) {
+          await streamRecords(id, data);
+          reply({ id, ok: true, data: null });
+        } else {
+          reply({ id, ok: true, data: data ?? null });
+        }
+      })
+      .catch((error) => {
+        reply({ id, ok: false, error: errorMessage(error), code: errorCode(error) });
+      });
+  });
+
+  input.on('close', () => {
+    process.exit(0);
   });
+}
+
+async function runOnce() {
+  const action = process.argv[2];
+  const payloadRaw = process.argv[3] ?? '{}';
+  const payload = JSON.parse(payloadRaw);
+  return dispatch(new AgentCore(), action, payload);
+}
+
+if (process.argv[2] === '--serve') {
+  serve();
+} else {
+  void runOnce()
+    .then((data) => {
+      reply({ ok: true, data });
+    })
+    .catch((error) => {
+      reply({ ok: false, error: errorMessage(error), code: errorCode(error) });
+      process.exitCode = 1;
+    });
+}
@@ -1,3 +1,15 @@
+use sha2::{Digest, Sha256};
+
+/// The script bundled as a resource; `bridge_integrity` checks it against this hash at launch.
+const BRIDGE_SCRIPT: &str = "../backend/desktop-bridge.cjs";
+
 fn main() {
+  println!("cargo:rerun-if-changed={BRIDGE_SCRIPT}");
+  let script = std::fs::read(BRIDGE_SCRIPT).expect("desktop-bridge.cjs is readable");
+  let hash: String = Sha256::digest(&script)
+    .iter()
+    .map(|byte| format!("{byte:02x}"))
+    .collect();
+  println!("cargo:rustc-env=BRIDGE_SHA256={hash}");
   tauri_build::build()
 }
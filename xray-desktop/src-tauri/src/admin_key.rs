@@ -0,0 +1,205 @@
+use crate::base_url::normalize_base_url;
+use crate::bridge::Bridge;
+use crate::config::Config;
+use crate::error::BridgeError;
+use crate::secrets::Secrets;
+use serde::Deserialize;
+use serde_json::json;
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Rotated {
+  admin_api_key: String,
+}
+
+/// Has the control plane issue a new admin key, then saves it, but only once an admin call made
+/// with it succeeded. Until then the keyring keeps the old key, so a key the panel handed out
+/// but doesn't accept yet can't lock the admin out.
+pub async fn rotate(
+  bridge: &Bridge,
+  secrets: &Secrets,
+  base_url: String,
+  current_key: String,
+) -> Result<(), BridgeError> {
+  if current_key.trim().is_empty() {
+    return Err(BridgeError::MissingAdminKey);
+  }
+  let reply = bridge
+    .call(
+      "rotateAdminKey",
+      json!({ "baseUrl": base_url, "adminApiKey": current_key }),
+    )
+    .await?;
+  let new_key = serde_json::from_value::<Rotated>(reply)
+    .map_err(|error| BridgeError::InvalidJson(error.to_string()))?
+    .admin_api_key;
+  if new_key.trim().is_empty() {
+    return Err(BridgeError::AdminKeyUnverified(
+      "the control plane returned an empty key".to_string(),
+    ));
+  }
+  bridge
+    .call(
+      "verifyAdminKey",
+      json!({ "baseUrl": base_url, "adminApiKey": new_key }),
+    )
+    .await
+    .map_err(|error| BridgeError::AdminKeyUnverified(error.to_string()))?;
+  secrets.store_admin_key(&base_url, &new_key)
+}
+
+#[allow(non_snake_case)]
+#[tauri::command]
+pub async fn rotateAdminKey(
+  bridge: tauri::State<'_, Bridge>,
+  secrets: tauri::State<'_, Secrets>,
+  config: tauri::State<'_, Config>,
+  baseUrl: String,
+  currentKey: String,
+) -> Result<(), BridgeError> {
+  let base_url = normalize_base_url(&baseUrl, config.get().allow_insecure_base_url)?;
+  rotate(&bridge, &secrets, base_url, currentKey).await
+}
+
+#[allow(non_snake_case)]
+#[tauri::command]
+pub fn storeAdminKey(
+  secrets: tauri::State<'_, Secrets>,
+  config: tauri::State<'_, Config>,
+  baseUrl: String,
+  adminApiKey: String,
+) -> Result<(), BridgeError> {
+  let base_url = normalize_base_url(&baseUrl, config.get().allow_insecure_base_url)?;
+  secrets.store_admin_key(&base_url, &adminApiKey)
+}
+
+#[allow(non_snake_case)]
+#[tauri::command]
+pub fn loadAdminKey(
+  secrets: tauri::State<'_, Secrets>,
+  config: tauri::State<'_, Config>,
+  baseUrl: String,
+) -> Result<Option<String>, BridgeError> {
+  let base_url = normalize_base_url(&baseUrl, config.get().allow_insecure_base_url)?;
+  secrets.load_admin_key(&base_url)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::bridge::mock::MockBridgeRunner;
+  use crate::secrets::mock::MemoryStore;
+  use tauri::async_runtime::block_on;
+
+  const BASE_URL: &str = "https://cp.example.com";
+
+  fn secrets() -> Secrets {
+    let secrets = Secrets::new(Box::new(MemoryStore::default()));
+    secrets.store_admin_key(BASE_URL, "old-key").unwrap();
+    secrets
+  }
+
+  fn rotate_with(mock: &MockBridgeRunner, secrets: &Secrets) -> Result<(), BridgeError> {
+    block_on(rotate(
+      &Bridge::new(mock.clone()),
+      secrets,
+      BASE_URL.to_string(),
+      "old-key".to_string(),
+    ))
+  }
+
+  #[test]
+  fn the_new_key_is_saved_once_it_works() {
+    let mock = MockBridgeRunner::default()
+      .respond("rotateAdminKey", Ok(json!({ "adminApiKey": "new-key" })))
+      .respond("verifyAdminKey", Ok(json!({ "ok": true })));
+    let secrets = secrets();
+
+    rotate_with(&mock, &secrets).unwrap();
+
+    assert_eq!(
+      secrets.load_admin_key(BASE_URL).unwrap().as_deref(),
+      Some("new-key")
+    );
+    assert_eq!(
+      mock.calls(),
+      [
+        (
+          "rotateAdminKey".to_string(),
+          json!({ "baseUrl": BASE_URL, "adminApiKey": "old-key" })
+        ),
+        (
+          "verifyAdminKey".to_string(),
+          json!({ "baseUrl": BASE_URL, "adminApiKey": "new-key" })
+        ),
+      ]
+    );
+  }
+
+  #[test]
+  fn a_key_that_fails_verification_is_never_saved() {
+    let mock = MockBridgeRunner::default()
+      .respond("rotateAdminKey", Ok(json!({ "adminApiKey": "new-key" })))
+      .respond(
+        "verifyAdminKey",
+        Err(BridgeError::BridgeReported {
+          code: None,
+          message: "Admin key check failed: HTTP 401".to_string(),
+        }),
+      );
+    let secrets = secrets();
+
+    let error = rotate_with(&mock, &secrets).unwrap_err();
+
+    assert_eq!(error.kind(), "adminKeyUnverified");
+    assert!(error.to_string().contains("HTTP 401"));
+    assert_eq!(
+      secrets.load_admin_key(BASE_URL).unwrap().as_deref(),
+      Some("old-key")
+    );
+  }
+
+  #[test]
+  fn a_failed_rotation_or_empty_key_is_not_verified() {
+    let secrets = secrets();
+    let refused = MockBridgeRunner::default().respond(
+      "rotateAdminKey",
+      Err(BridgeError::BridgeReported {
+        code: None,
+        message: "Admin key rotation failed: HTTP 403".to_string(),
+      }),
+    );
+    let empty =
+      MockBridgeRunner::default().respond("rotateAdminKey", Ok(json!({ "adminApiKey": " " })));
+
+    assert_eq!(
+      rotate_with(&refused, &secrets).unwrap_err().kind(),
+      "bridgeReported"
+    );
+    assert_eq!(
+      rotate_with(&empty, &secrets).unwrap_err().kind(),
+      "adminKeyUnverified"
+    );
+    assert_eq!(refused.calls().len(), 1);
+    assert_eq!(empty.calls().len(), 1);
+    assert_eq!(
+      secrets.load_admin_key(BASE_URL).unwrap().as_deref(),
+      Some("old-key")
+    );
+  }
+
+  #[test]
+  fn a_blank_current_key_never_reaches_the_bridge() {
+    let mock = MockBridgeRunner::default();
+
+    let result = block_on(rotate(
+      &Bridge::new(mock.clone()),
+      &secrets(),
+      BASE_URL.to_string(),
+      "  ".to_string(),
+    ));
+
+    assert_eq!(result, Err(BridgeError::MissingAdminKey));
+    assert!(mock.calls().is_empty());
+  }
+}
@@ -0,0 +1,262 @@
+use crate::bridge::Bridge;
+use crate::config::Config;
+use crate::error::BridgeError;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum AppPolicy {
+  /// The app's traffic always goes through the tunnel; with any such rule, only those apps do.
+  ForceVpn,
+  /// The app's traffic never goes through the tunnel.
+  Bypass,
+}
+
+/// Per-app routing, keyed by executable: one rule per path.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AppRule {
+  pub executable_path: String,
+  pub policy: AppPolicy,
+}
+
+/// The executable has to be there now: a typo would otherwise sit in the rules doing nothing.
+/// macOS `.app` bundles are directories, so those count as well.
+pub fn validate_path(path: &str) -> Result<(), BridgeError> {
+  if path.trim().is_empty() {
+    return Err(BridgeError::InvalidAppRule(
+      "executable path is empty".to_string(),
+    ));
+  }
+  let candidate = Path::new(path);
+  if !candidate.is_absolute() {
+    return Err(BridgeError::InvalidAppRule(format!(
+      "\"{path}\" is not an absolute path"
+    )));
+  }
+  let is_bundle = candidate.extension().is_some_and(|ext| ext == "app");
+  match std::fs::metadata(candidate) {
+    Ok(metadata) if metadata.is_file() || (metadata.is_dir() && is_bundle) => Ok(()),
+    Ok(_) => Err(BridgeError::InvalidAppRule(format!(
+      "\"{path}\" is not an executable"
+    ))),
+    Err(_) => Err(BridgeError::InvalidAppRule(format!(
+      "\"{path}\" does not exist"
+    ))),
+  }
+}
+
+/// Turns the bridge's `APP_RULES_UNSUPPORTED` into its own variant naming this platform.
+fn with_platform_code(error: BridgeError) -> BridgeError {
+  match error.code() {
+    Some("APP_RULES_UNSUPPORTED") => BridgeError::AppRulesUnsupported {
+      platform: std::env::consts::OS.to_string(),
+    },
+    _ => error,
+  }
+}
+
+/// Sends the whole rule set to the bridge, then saves it; `connect` re-sends the saved set.
+async fn apply(bridge: &Bridge, config: &Config, rules: Vec<AppRule>) -> Result<(), BridgeError> {
+  bridge
+    .call("appRules", json!({ "rules": rules }))
+    .await
+    .map_err(with_platform_code)?;
+  config.update(|settings| settings.app_rules = rules)?;
+  Ok(())
+}
+
+/// Adds `rule`, replacing the policy of an existing rule for the same executable.
+pub async fn set(bridge: &Bridge, config: &Config, rule: AppRule) -> Result<(), BridgeError> {
+  validate_path(&rule.executable_path)?;
+  let mut rules = config.get().app_rules;
+  match rules
+    .iter_mut()
+    .find(|existing| existing.executable_path == rule.executable_path)
+  {
+    Some(existing) => *existing = rule,
+    None => rules.push(rule),
+  }
+  apply(bridge, config, rules).await
+}
+
+/// No existence check here, so a rule for an uninstalled app can still be dropped.
+pub async fn remove(bridge: &Bridge, config: &Config, path: String) -> Result<(), BridgeError> {
+  let mut rules = config.get().app_rules;
+  let before = rules.len();
+  rules.retain(|rule| rule.executable_path != path);
+  if rules.len() == before {
+    return Err(BridgeError::UnknownAppRule { path });
+  }
+  apply(bridge, config, rules).await
+}
+
+#[allow(non_snake_case)]
+#[tauri::command]
+pub fn getAppRules(config: tauri::State<'_, Config>) -> Vec<AppRule> {
+  config.get().app_rules
+}
+
+#[allow(non_snake_case)]
+#[tauri::command]
+pub async fn setAppRule(
+  bridge: tauri::State<'_, Bridge>,
+  config: tauri::State<'_, Config>,
+  rule: AppRule,
+) -> Result<(), BridgeError> {
+  set(&bridge, &config, rule).await
+}
+
+#[allow(non_snake_case)]
+#[tauri::command]
+pub async fn removeAppRule(
+  bridge: tauri::State<'_, Bridge>,
+  config: tauri::State<'_, Config>,
+  path: String,
+) -> Result<(), BridgeError> {
+  remove(&bridge, &config, path).await
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::bridge::mock::MockBridgeRunner;
+  use serde_json::Value;
+  use tauri::async_runtime::block_on;
+
+  fn rule(path: &Path, policy: AppPolicy) -> AppRule {
+    AppRule {
+      executable_path: path.to_string_lossy().into_owned(),
+      policy,
+    }
+  }
+
+  #[test]
+  fn only_existing_executables_are_accepted() {
+    let dir = tempfile::tempdir().unwrap();
+    let firefox = dir.path().join("firefox");
+    std::fs::write(&firefox, "").unwrap();
+    let bundle = dir.path().join("Steam.app");
+    std::fs::create_dir(&bundle).unwrap();
+
+    assert_eq!(validate_path(&firefox.to_string_lossy()), Ok(()));
+    assert_eq!(validate_path(&bundle.to_string_lossy()), Ok(()));
+    for path in [
+      dir.path().join("missing"),
+      dir.path().to_path_buf(),
+      "firefox".into(),
+      " ".into(),
+    ] {
+      assert_eq!(
+        validate_path(&path.to_string_lossy()).unwrap_err().kind(),
+        "invalidAppRule",
+        "{}",
+        path.display()
+      );
+    }
+  }
+
+  #[test]
+  fn missing_executable_never_reaches_the_bridge() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = Config::load(dir.path().join("settings.json"));
+    let mock = MockBridgeRunner::default();
+    let bridge = Bridge::new(mock.clone());
+
+    let error = block_on(set(
+      &bridge,
+      &config,
+      rule(&dir.path().join("steam"), AppPolicy::Bypass),
+    ))
+    .unwrap_err();
+
+    assert_eq!(
+      error,
+      BridgeError::InvalidAppRule(format!(
+        "\"{}\" does not exist",
+        dir.path().join("steam").display()
+      ))
+    );
+    assert!(mock.calls().is_empty());
+    assert!(config.get().app_rules.is_empty());
+  }
+
+  #[test]
+  fn set_and_remove_round_trip_through_the_saved_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("settings.json");
+    let firefox = dir.path().join("firefox");
+    let steam = dir.path().join("steam");
+    std::fs::write(&firefox, "").unwrap();
+    std::fs::write(&steam, "").unwrap();
+    let mock = MockBridgeRunner::default().respond("appRules", Ok(Value::Null));
+    let bridge = Bridge::new(mock.clone());
+
+    block_on(set(
+      &bridge,
+      &Config::load(&path),
+      rule(&firefox, AppPolicy::Bypass),
+    ))
+    .unwrap();
+    block_on(set(
+      &bridge,
+      &Config::load(&path),
+      rule(&steam, AppPolicy::Bypass),
+    ))
+    .unwrap();
+    // Same executable: the policy changes, the rule isn't duplicated.
+    block_on(set(
+      &bridge,
+      &Config::load(&path),
+      rule(&firefox, AppPolicy::ForceVpn),
+    ))
+    .unwrap();
+    assert_eq!(
+      Config::load(&path).get().app_rules,
+      vec![
+        rule(&firefox, AppPolicy::ForceVpn),
+        rule(&steam, AppPolicy::Bypass)
+      ]
+    );
+
+    let steam_path = steam.to_string_lossy().into_owned();
+    block_on(remove(&bridge, &Config::load(&path), steam_path.clone())).unwrap();
+    assert_eq!(
+      Config::load(&path).get().app_rules,
+      vec![rule(&firefox, AppPolicy::ForceVpn)]
+    );
+    assert_eq!(
+      mock.calls().last(),
+      Some(&(
+        "appRules".to_string(),
+        json!({ "rules": [{ "executablePath": firefox, "policy": "forceVpn" }] })
+      ))
+    );
+    assert_eq!(
+      block_on(remove(&bridge, &Config::load(&path), steam_path.clone())),
+      Err(BridgeError::UnknownAppRule { path: steam_path })
+    );
+  }
+
+  #[test]
+  fn unsupported_platform_is_reported_as_such() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = Config::load(dir.path().join("settings.json"));
+    let firefox = dir.path().join("firefox");
+    std::fs::write(&firefox, "").unwrap();
+    let bridge = Bridge::new(MockBridgeRunner::default().respond(
+      "appRules",
+      Err(BridgeError::BridgeReported {
+        message: "Per-app routing is not available".to_string(),
+        code: Some("APP_RULES_UNSUPPORTED".to_string()),
+      }),
+    ));
+
+    let error = block_on(set(&bridge, &config, rule(&firefox, AppPolicy::Bypass))).unwrap_err();
+
+    assert_eq!(error.kind(), "appRulesUnsupported");
+    assert!(config.get().app_rules.is_empty());
+  }
+}
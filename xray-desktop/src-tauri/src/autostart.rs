@@ -0,0 +1,195 @@
+use crate::config::Config;
+use crate::error::BridgeError;
+use tauri_plugin_autostart::{AutoLaunchManager, MacosLauncher};
+
+/// OS launch-on-login registration: a Launch Agent plist on macOS, the `Run` registry key on
+/// Windows and an XDG autostart entry on Linux.
+pub trait LoginItem: Send + Sync {
+  fn enable(&self) -> Result<(), BridgeError>;
+  /// Must succeed when nothing is registered.
+  fn disable(&self) -> Result<(), BridgeError>;
+  fn is_enabled(&self) -> Result<bool, BridgeError>;
+}
+
+impl LoginItem for AutoLaunchManager {
+  fn enable(&self) -> Result<(), BridgeError> {
+    AutoLaunchManager::enable(self).map_err(autostart_error)
+  }
+
+  fn disable(&self) -> Result<(), BridgeError> {
+    AutoLaunchManager::disable(self).map_err(autostart_error)
+  }
+
+  fn is_enabled(&self) -> Result<bool, BridgeError> {
+    AutoLaunchManager::is_enabled(self).map_err(autostart_error)
+  }
+}
+
+fn autostart_error(error: tauri_plugin_autostart::Error) -> BridgeError {
+  BridgeError::AutostartFailed(error.to_string())
+}
+
+pub fn plugin() -> tauri::plugin::TauriPlugin<tauri::Wry> {
+  // A Launch Agent can be removed again without driving System Events over AppleScript.
+  tauri_plugin_autostart::init(MacosLauncher::LaunchAgent, None)
+}
+
+/// The OS registration is the source of truth, since users can remove login items behind the
+/// app's back. The saved preference answers when the OS can't be asked.
+pub fn get_autostart(item: &dyn LoginItem, config: &Config) -> bool {
+  item.is_enabled().unwrap_or_else(|_| config.get().autostart)
+}
+
+/// Registers or removes the login item, then saves the preference. A failed save rolls the
+/// registration back so the two never disagree.
+pub fn set_autostart(
+  item: &dyn LoginItem,
+  config: &Config,
+  enabled: bool,
+) -> Result<(), BridgeError> {
+  if enabled {
+    item.enable()?;
+  } else {
+    item.disable()?;
+  }
+
+  if let Err(error) = config.update(|settings| settings.autostart = enabled) {
+    let _ = if enabled {
+      item.disable()
+    } else {
+      item.enable()
+    };
+    return Err(error);
+  }
+  Ok(())
+}
+
+#[allow(non_snake_case)]
+#[tauri::command]
+pub fn getAutostart(
+  manager: tauri::State<'_, AutoLaunchManager>,
+  config: tauri::State<'_, Config>,
+) -> bool {
+  get_autostart(manager.inner(), &config)
+}
+
+#[allow(non_snake_case)]
+#[tauri::command]
+pub fn setAutostart(
+  manager: tauri::State<'_, AutoLaunchManager>,
+  config: tauri::State<'_, Config>,
+  enabled: bool,
+) -> Result<(), BridgeError> {
+  set_autostart(manager.inner(), &config, enabled)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::sync::Mutex;
+
+  #[derive(Default)]
+  struct FakeLoginItem {
+    registered: Mutex<bool>,
+    broken: bool,
+  }
+
+  impl LoginItem for FakeLoginItem {
+    fn enable(&self) -> Result<(), BridgeError> {
+      if self.broken {
+        return Err(BridgeError::AutostartFailed(
+          "permission denied".to_string(),
+        ));
+      }
+      *self.registered.lock().unwrap() = true;
+      Ok(())
+    }
+
+    fn disable(&self) -> Result<(), BridgeError> {
+      if self.broken {
+        return Err(BridgeError::AutostartFailed(
+          "permission denied".to_string(),
+        ));
+      }
+      *self.registered.lock().unwrap() = false;
+      Ok(())
+    }
+
+    fn is_enabled(&self) -> Result<bool, BridgeError> {
+      if self.broken {
+        return Err(BridgeError::AutostartFailed(
+          "permission denied".to_string(),
+        ));
+      }
+      Ok(*self.registered.lock().unwrap())
+    }
+  }
+
+  #[test]
+  fn toggle_is_saved_to_disk() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("settings.json");
+    let item = FakeLoginItem::default();
+
+    set_autostart(&item, &Config::load(&path), true).unwrap();
+    assert!(Config::load(&path).get().autostart);
+    assert!(*item.registered.lock().unwrap());
+
+    set_autostart(&item, &Config::load(&path), false).unwrap();
+    assert!(!Config::load(&path).get().autostart);
+    assert!(!*item.registered.lock().unwrap());
+  }
+
+  #[test]
+  fn disabling_without_a_registration_succeeds() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = Config::load(dir.path().join("settings.json"));
+
+    set_autostart(&FakeLoginItem::default(), &config, false).unwrap();
+
+    assert!(!config.get().autostart);
+  }
+
+  #[test]
+  fn failed_registration_is_not_saved() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = Config::load(dir.path().join("settings.json"));
+    let item = FakeLoginItem {
+      broken: true,
+      ..Default::default()
+    };
+
+    let result = set_autostart(&item, &config, true);
+
+    assert!(matches!(result, Err(BridgeError::AutostartFailed(_))));
+    assert!(!config.get().autostart);
+  }
+
+  #[test]
+  fn failed_save_rolls_back_the_registration() {
+    let dir = tempfile::tempdir().unwrap();
+    // The settings path is a directory, so the write fails.
+    let config = Config::load(dir.path());
+    let item = FakeLoginItem::default();
+
+    let result = set_autostart(&item, &config, true);
+
+    assert!(matches!(result, Err(BridgeError::SettingsIo(_))));
+    assert!(!*item.registered.lock().unwrap());
+  }
+
+  #[test]
+  fn saved_preference_answers_when_the_os_cannot() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = Config::load(dir.path().join("settings.json"));
+    config.update(|settings| settings.autostart = true).unwrap();
+
+    let broken = FakeLoginItem {
+      broken: true,
+      ..Default::default()
+    };
+    assert!(get_autostart(&broken, &config));
+    // A login item removed in the OS settings wins over the saved preference.
+    assert!(!get_autostart(&FakeLoginItem::default(), &config));
+  }
+}
@@ -0,0 +1,138 @@
+use crate::bridge::Bridge;
+use crate::config::Config;
+use crate::error::BridgeError;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+/// 10 Gbit/s; anything above is a typo, not a cap.
+pub const MAX_KBPS: u32 = 10_000_000;
+
+/// Throughput caps in kilobits per second; `None` leaves that direction unlimited.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct BandwidthLimit {
+  pub up_kbps: Option<u32>,
+  pub down_kbps: Option<u32>,
+}
+
+impl BandwidthLimit {
+  pub fn is_unlimited(&self) -> bool {
+    self.up_kbps.is_none() && self.down_kbps.is_none()
+  }
+}
+
+/// 0 would stall traffic rather than lift the cap; unlimited is `None`.
+pub fn validate(limit: BandwidthLimit) -> Result<BandwidthLimit, BridgeError> {
+  for (direction, value) in [("upload", limit.up_kbps), ("download", limit.down_kbps)] {
+    match value {
+      Some(0) => {
+        return Err(BridgeError::InvalidBandwidthLimit(format!(
+          "{direction} limit of 0 would block all traffic, use null for unlimited"
+        )))
+      }
+      Some(kbps) if kbps > MAX_KBPS => {
+        return Err(BridgeError::InvalidBandwidthLimit(format!(
+          "{direction} limit {kbps} kbps is above {MAX_KBPS}"
+        )))
+      }
+      _ => {}
+    }
+  }
+  Ok(limit)
+}
+
+/// Forwards the limit to the bridge and saves it; `connect` re-sends the saved one.
+pub async fn set(
+  bridge: &Bridge,
+  config: &Config,
+  limit: BandwidthLimit,
+) -> Result<(), BridgeError> {
+  let limit = validate(limit)?;
+  bridge.call("bandwidth", json!(limit)).await?;
+  config.update(|settings| settings.bandwidth_limit = limit)?;
+  Ok(())
+}
+
+#[allow(non_snake_case)]
+#[tauri::command]
+pub fn getBandwidthLimit(config: tauri::State<'_, Config>) -> BandwidthLimit {
+  config.get().bandwidth_limit
+}
+
+#[allow(non_snake_case)]
+#[tauri::command]
+pub async fn setBandwidthLimit(
+  bridge: tauri::State<'_, Bridge>,
+  config: tauri::State<'_, Config>,
+  limit: BandwidthLimit,
+) -> Result<(), BridgeError> {
+  set(&bridge, &config, limit).await
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::bridge::mock::MockBridgeRunner;
+  use serde_json::Value;
+  use tauri::async_runtime::block_on;
+
+  fn limit(up_kbps: Option<u32>, down_kbps: Option<u32>) -> BandwidthLimit {
+    BandwidthLimit { up_kbps, down_kbps }
+  }
+
+  #[test]
+  fn zero_is_rejected_and_null_is_unlimited() {
+    assert!(validate(limit(None, None)).unwrap().is_unlimited());
+    assert_eq!(validate(limit(Some(512), None)), Ok(limit(Some(512), None)));
+    assert_eq!(
+      validate(limit(Some(1), Some(MAX_KBPS))),
+      Ok(limit(Some(1), Some(MAX_KBPS)))
+    );
+    assert_eq!(
+      validate(limit(None, Some(0))).unwrap_err().kind(),
+      "invalidBandwidthLimit"
+    );
+    assert!(validate(limit(Some(0), Some(1000))).is_err());
+    assert!(validate(limit(Some(MAX_KBPS + 1), None)).is_err());
+  }
+
+  #[test]
+  fn forwards_and_saves_valid_limits_only() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("settings.json");
+    let mock = MockBridgeRunner::default().respond("bandwidth", Ok(Value::Null));
+    let bridge = Bridge::new(mock.clone());
+
+    block_on(set(
+      &bridge,
+      &Config::load(&path),
+      limit(Some(256), Some(2048)),
+    ))
+    .unwrap();
+    assert!(block_on(set(&bridge, &Config::load(&path), limit(Some(0), None))).is_err());
+
+    assert_eq!(
+      Config::load(&path).get().bandwidth_limit,
+      limit(Some(256), Some(2048))
+    );
+    assert_eq!(
+      mock.calls(),
+      [(
+        "bandwidth".to_string(),
+        json!({ "upKbps": 256, "downKbps": 2048 })
+      )]
+    );
+  }
+
+  #[test]
+  fn lifting_the_limit_is_saved_too() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = Config::load(dir.path().join("settings.json"));
+    let bridge = Bridge::new(MockBridgeRunner::default().respond("bandwidth", Ok(Value::Null)));
+
+    block_on(set(&bridge, &config, limit(Some(256), None))).unwrap();
+    block_on(set(&bridge, &config, BandwidthLimit::default())).unwrap();
+
+    assert!(config.get().bandwidth_limit.is_unlimited());
+  }
+}
@@ -0,0 +1,209 @@
+use crate::bridge::Bridge;
+use crate::config::Config;
+use crate::error::BridgeError;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tauri::Url;
+
+/// Canonical form of a control-plane URL as typed or pasted: `https://` is assumed when the
+/// scheme is missing and trailing slashes are dropped. Plain `http` only passes with
+/// `allow_insecure`.
+pub fn normalize_base_url(raw: &str, allow_insecure: bool) -> Result<String, BridgeError> {
+  let invalid = |reason: &str| BridgeError::InvalidBaseUrl(reason.to_string());
+  let raw = raw.trim();
+  if raw.is_empty() {
+    return Err(invalid("it is empty"));
+  }
+  let with_scheme = if raw.contains("://") {
+    raw.to_string()
+  } else {
+    format!("https://{raw}")
+  };
+  let url = Url::parse(&with_scheme).map_err(|error| invalid(&error.to_string()))?;
+
+  match url.scheme() {
+    "https" => {}
+    "http" if allow_insecure => {}
+    "http" => return Err(invalid("http is not allowed, use https")),
+    _ => return Err(invalid("it must use https")),
+  }
+  if url.host_str().is_none_or(str::is_empty) {
+    return Err(invalid("it has no host"));
+  }
+  Ok(url.as_str().trim_end_matches('/').to_string())
+}
+
+/// Whether a control server answers at all, before a token for it is imported.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ReachabilityResult {
+  pub reachable: bool,
+  pub rtt_ms: Option<u32>,
+  /// `None` for plain http and when no TLS handshake took place.
+  pub tls_valid: Option<bool>,
+  pub error: Option<String>,
+}
+
+/// Needs no token. The bridge gives up after 3 seconds, so this fails fast too.
+pub async fn test_base_url(
+  bridge: &Bridge,
+  raw: &str,
+  allow_insecure: bool,
+) -> Result<ReachabilityResult, BridgeError> {
+  let base_url = normalize_base_url(raw, allow_insecure)?;
+  let reply = bridge.call("ping", json!({ "baseUrl": base_url })).await?;
+  serde_json::from_value(reply).map_err(|error| BridgeError::InvalidJson(error.to_string()))
+}
+
+#[allow(non_snake_case)]
+#[tauri::command]
+pub async fn testBaseUrl(
+  bridge: tauri::State<'_, Bridge>,
+  config: tauri::State<'_, Config>,
+  baseUrl: String,
+) -> Result<ReachabilityResult, BridgeError> {
+  test_base_url(&bridge, &baseUrl, config.get().allow_insecure_base_url).await
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::bridge::mock::MockBridgeRunner;
+  use tauri::async_runtime::block_on;
+
+  fn reason(raw: &str, allow_insecure: bool) -> String {
+    match normalize_base_url(raw, allow_insecure) {
+      Err(BridgeError::InvalidBaseUrl(reason)) => reason,
+      other => panic!("expected InvalidBaseUrl, got {:?}", other),
+    }
+  }
+
+  #[test]
+  fn valid_urls_pass_unchanged() {
+    for url in [
+      "https://cp.example.com",
+      "https://cp.example.com:8443/api",
+      "https://10.0.0.1",
+    ] {
+      assert_eq!(normalize_base_url(url, false).unwrap(), url);
+    }
+  }
+
+  #[test]
+  fn scheme_less_input_gets_https() {
+    assert_eq!(
+      normalize_base_url("example.com", false).unwrap(),
+      "https://example.com"
+    );
+    assert_eq!(
+      normalize_base_url("  cp.example.com:8443/api ", false).unwrap(),
+      "https://cp.example.com:8443/api"
+    );
+  }
+
+  #[test]
+  fn trailing_slashes_are_stripped() {
+    assert_eq!(
+      normalize_base_url("https://cp.example.com/", false).unwrap(),
+      "https://cp.example.com"
+    );
+    assert_eq!(
+      normalize_base_url("https://cp.example.com/api//", false).unwrap(),
+      "https://cp.example.com/api"
+    );
+  }
+
+  #[test]
+  fn http_needs_allow_insecure() {
+    assert_eq!(
+      reason("http://10.0.0.1:8080", false),
+      "http is not allowed, use https"
+    );
+    assert_eq!(
+      normalize_base_url("http://10.0.0.1:8080/", true).unwrap(),
+      "http://10.0.0.1:8080"
+    );
+  }
+
+  #[test]
+  fn rejects_unparseable_and_foreign_urls() {
+    assert_eq!(reason("", false), "it is empty");
+    assert_eq!(reason("ftp://cp.example.com", true), "it must use https");
+    assert_eq!(reason("https://[::1", false), "invalid IPv6 address");
+    assert_eq!(reason("https://", false), "empty host");
+  }
+
+  #[test]
+  fn parses_reachable_and_unreachable_answers() {
+    let reachable: ReachabilityResult =
+      serde_json::from_value(json!({ "reachable": true, "rttMs": 42, "tlsValid": true })).unwrap();
+    let bad_cert: ReachabilityResult = serde_json::from_value(json!({
+      "reachable": true,
+      "rttMs": null,
+      "tlsValid": false,
+      "error": "certificate has expired"
+    }))
+    .unwrap();
+    let unreachable: ReachabilityResult =
+      serde_json::from_value(json!({ "reachable": false, "error": "No answer within 3000 ms" }))
+        .unwrap();
+
+    assert_eq!(
+      reachable,
+      ReachabilityResult {
+        reachable: true,
+        rtt_ms: Some(42),
+        tls_valid: Some(true),
+        error: None,
+      }
+    );
+    assert_eq!(bad_cert.tls_valid, Some(false));
+    assert_eq!(
+      unreachable,
+      ReachabilityResult {
+        reachable: false,
+        rtt_ms: None,
+        tls_valid: None,
+        error: Some("No answer within 3000 ms".to_string()),
+      }
+    );
+  }
+
+  #[test]
+  fn probes_the_normalized_url() {
+    let mock = MockBridgeRunner::default().respond(
+      "ping",
+      Ok(json!({ "reachable": true, "rttMs": 12, "tlsValid": true, "error": null })),
+    );
+
+    let result = block_on(test_base_url(
+      &Bridge::new(mock.clone()),
+      " cp.example.com/ ",
+      false,
+    ))
+    .unwrap();
+
+    assert!(result.reachable);
+    assert_eq!(
+      mock.calls(),
+      [(
+        "ping".to_string(),
+        json!({ "baseUrl": "https://cp.example.com" })
+      )]
+    );
+  }
+
+  #[test]
+  fn an_invalid_url_is_rejected_without_probing() {
+    let mock = MockBridgeRunner::default();
+
+    let result = block_on(test_base_url(
+      &Bridge::new(mock.clone()),
+      "http://cp.example.com",
+      false,
+    ));
+
+    assert!(matches!(result, Err(BridgeError::InvalidBaseUrl(_))));
+    assert!(mock.calls().is_empty());
+  }
+}
@@ -0,0 +1,50 @@
+use super::BridgeRunner;
+use crate::error::BridgeError;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+/// Answers each action with a scripted response and records every call. Cloning shares the
+/// script and the call log, so a test can keep a handle after moving one into a `Bridge`.
+#[derive(Clone, Default)]
+pub struct MockBridgeRunner {
+  responses: Arc<Mutex<HashMap<String, Result<Value, BridgeError>>>>,
+  calls: Arc<Mutex<Vec<(String, Value)>>>,
+}
+
+impl MockBridgeRunner {
+  pub fn respond(self, action: &str, response: Result<Value, BridgeError>) -> Self {
+    self
+      .responses
+      .lock()
+      .unwrap()
+      .insert(action.to_string(), response);
+    self
+  }
+
+  pub fn calls(&self) -> Vec<(String, Value)> {
+    self.calls.lock().unwrap().clone()
+  }
+}
+
+impl BridgeRunner for MockBridgeRunner {
+  fn run(&self, action: &str, payload: Value) -> Result<Value, BridgeError> {
+    self
+      .calls
+      .lock()
+      .unwrap()
+      .push((action.to_string(), payload));
+    self
+      .responses
+      .lock()
+      .unwrap()
+      .get(action)
+      .cloned()
+      .unwrap_or_else(|| {
+        Err(BridgeError::BridgeReported {
+          code: None,
+          message: format!("Unsupported bridge action: {}", action),
+        })
+      })
+  }
+}
@@ -0,0 +1,1781 @@
+#[cfg(test)]
+pub mod mock;
+mod node;
+mod process;
+
+use crate::error::BridgeError;
+use crate::fingerprint;
+use crate::readiness::Readiness;
+#[cfg(test)]
+pub use node::REQUIRED_NODE;
+pub use node::{resolve_node, ResolvedNode};
+use process::BridgeProcess;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, Weak};
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Manager};
+use tokio::sync::oneshot;
+
+const BRIDGE_RESOURCE: &str = "backend/desktop-bridge.cjs";
+/// Points the app at another bridge script for this run, ahead of `bridgeScriptPath`.
+pub const BRIDGE_SCRIPT_ENV: &str = "VPNATOR_BRIDGE_SCRIPT";
+
+pub fn resolve_bridge_script(resource_dir: Option<&Path>) -> PathBuf {
+  if let Some(bundled) = resource_dir.map(|dir| dir.join(BRIDGE_RESOURCE)) {
+    if bundled.is_file() {
+      return bundled;
+    }
+  }
+
+  // `tauri dev` runs without bundled resources, so use the script next to the sources.
+  PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+    .join("..")
+    .join(BRIDGE_RESOURCE)
+}
+
+/// The script to run instead of the bundled one, for working on a forked bridge:
+/// `VPNATOR_BRIDGE_SCRIPT`, then `bridgeScriptPath` from settings. One that names no readable
+/// file fails here, at launch, rather than on the first call.
+pub fn script_override(
+  from_env: Option<OsString>,
+  configured: Option<&Path>,
+) -> Result<Option<PathBuf>, BridgeError> {
+  let Some(path) = from_env
+    .filter(|value| !value.is_empty())
+    .map(PathBuf::from)
+    .or_else(|| {
+      configured
+        .filter(|path| !path.as_os_str().is_empty())
+        .map(Path::to_path_buf)
+    })
+  else {
+    return Ok(None);
+  };
+  let invalid = |reason: String| BridgeError::BridgeScriptInvalid {
+    path: path.display().to_string(),
+    reason,
+  };
+  let file = std::fs::File::open(&path).map_err(|error| invalid(error.to_string()))?;
+  if !file.metadata().is_ok_and(|metadata| metadata.is_file()) {
+    return Err(invalid("it is not a file".to_string()));
+  }
+  Ok(Some(path))
+}
+
+#[cfg(windows)]
+pub(crate) const CREATE_NO_WINDOW: u32 = 0x0800_0000;
+
+/// Overrides for the proxy and CA variables handed to node. Unset fields fall back to the value
+/// in our own environment.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ProxyEnv {
+  pub http_proxy: Option<String>,
+  pub https_proxy: Option<String>,
+  pub no_proxy: Option<String>,
+  pub node_extra_ca_certs: Option<String>,
+}
+
+impl ProxyEnv {
+  /// The only variables set explicitly on the bridge process, each with its override.
+  fn forwarded(&self) -> [(&'static str, Option<&String>); 7] {
+    [
+      ("HTTP_PROXY", self.http_proxy.as_ref()),
+      ("http_proxy", self.http_proxy.as_ref()),
+      ("HTTPS_PROXY", self.https_proxy.as_ref()),
+      ("https_proxy", self.https_proxy.as_ref()),
+      ("NO_PROXY", self.no_proxy.as_ref()),
+      ("no_proxy", self.no_proxy.as_ref()),
+      ("NODE_EXTRA_CA_CERTS", self.node_extra_ca_certs.as_ref()),
+    ]
+  }
+}
+
+/// Values for the forwarded variables: the override if set, else what `parent` has. Nothing
+/// outside `ProxyEnv::forwarded` is ever added.
+fn forwarded_env(
+  overrides: &ProxyEnv,
+  parent: impl Fn(&str) -> Option<OsString>,
+) -> Vec<(&'static str, OsString)> {
+  overrides
+    .forwarded()
+    .into_iter()
+    .filter_map(|(key, value)| Some((key, value.map(OsString::from).or_else(|| parent(key))?)))
+    .collect()
+}
+
+/// Extra environment and working directory for the bridge process, e.g. to point a debug build at
+/// a staging control server. One process serves every action, so they apply to all of them.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct BridgeOptions {
+  pub env: HashMap<String, String>,
+  /// Defaults to the folder holding `desktop-bridge.cjs`, so its relative requires resolve.
+  pub cwd: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone)]
+pub struct BridgeCommand {
+  program: String,
+  script: PathBuf,
+  /// What `node --version` reported while resolving `program`.
+  node_version: Option<String>,
+  /// `script` came from `script_override`, so it isn't expected to match this build.
+  overridden: bool,
+}
+
+impl BridgeCommand {
+  pub fn new(program: impl Into<String>, script: impl Into<PathBuf>) -> Self {
+    Self {
+      program: program.into(),
+      script: script.into(),
+      node_version: None,
+      overridden: false,
+    }
+  }
+
+  pub fn script(&self) -> &Path {
+    &self.script
+  }
+
+  pub fn is_overridden(&self) -> bool {
+    self.overridden
+  }
+
+  /// Runs `script` instead, if given. Logged at warn on every launch, since a forked bridge is
+  /// the first thing to rule out when reading a bug report's logs.
+  pub fn with_script_override(self, script: Option<PathBuf>) -> Self {
+    let Some(script) = script else {
+      return self;
+    };
+    tracing::warn!(
+      script = %script.display(),
+      bundled = %self.script.display(),
+      "BRIDGE SCRIPT OVERRIDE ACTIVE: running a script other than the bundled one"
+    );
+    Self {
+      script,
+      overridden: true,
+      ..self
+    }
+  }
+
+  /// Resolves node once; an unusable install is reported by every command instead.
+  pub fn for_app(
+    app: &AppHandle,
+    node_path: Option<&Path>,
+    script_path: Option<&Path>,
+  ) -> Result<Self, BridgeError> {
+    Self::resolve(
+      app.path().resource_dir().ok().as_deref(),
+      node_path,
+      script_path,
+    )
+  }
+
+  /// Like `for_app`, for callers without an app handle (the headless CLI). `script_path` is
+  /// `bridgeScriptPath`; see `script_override`.
+  pub fn resolve(
+    resource_dir: Option<&Path>,
+    node_path: Option<&Path>,
+    script_path: Option<&Path>,
+  ) -> Result<Self, BridgeError> {
+    let script = script_override(std::env::var_os(BRIDGE_SCRIPT_ENV), script_path)?;
+    let node = node::resolve_node(node_path)?;
+    let command = Self {
+      node_version: Some(node.version.to_string()),
+      ..Self::new(
+        node.path.to_string_lossy(),
+        resolve_bridge_script(resource_dir),
+      )
+    };
+    Ok(command.with_script_override(script))
+  }
+
+  fn to_command(&self, proxy: &ProxyEnv, options: &BridgeOptions) -> Command {
+    let mut command = Command::new(&self.program);
+    command.arg(&self.script);
+    let cwd = options.cwd.as_deref().or_else(|| {
+      self
+        .script
+        .parent()
+        .filter(|dir| !dir.as_os_str().is_empty())
+    });
+    if let Some(cwd) = cwd {
+      command.current_dir(cwd);
+    }
+    command.envs(&options.env);
+    // Set explicitly so a corporate proxy reaches node even from launchers that trim the
+    // environment (login items, desktop files).
+    command.envs(forwarded_env(proxy, |key| std::env::var_os(key)));
+
+    // node is a console program; without this flag every bridge call flashes a console window.
+    #[cfg(windows)]
+    {
+      use std::os::windows::process::CommandExt;
+      command.creation_flags(CREATE_NO_WINDOW);
+    }
+
+    command
+  }
+}
+
+/// Extra spawn attempts after a transient failure such as EAGAIN.
+pub const DEFAULT_SPAWN_RETRIES: u32 = 3;
+
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How often `start_reaper` sweeps for bridge processes that exited on their own.
+#[cfg(unix)]
+pub const REAP_INTERVAL: Duration = Duration::from_secs(60);
+
+pub fn timeout_for(action: &str) -> Duration {
+  match action {
+    "status" => Duration::from_secs(5),
+    // The bridge gives up on an endpoint after 3 seconds.
+    "ping" => Duration::from_secs(5),
+    // connect may download the xray binary and waits for the tunnel to come up.
+    "connect" => Duration::from_secs(90),
+    // Latency, then 20 seconds each way at most.
+    "speedTest" => Duration::from_secs(60),
+    // The bridge gives up on the front after 5 seconds.
+    "probeDisguise" => Duration::from_secs(8),
+    _ => DEFAULT_TIMEOUT,
+  }
+}
+
+/// Actions that change nothing. Their failures don't go to the error sink and their successes are
+/// logged at debug. `testDisguise` and `listDisguiseTemplates` only ask the control panel;
+/// `importSubscription` only downloads and `networkIdentity` and `vpnInventory` only ask the OS.
+/// `speedTest` only measures; `probeDisguise` only fetches a page from the server.
+fn is_read_only(action: &str) -> bool {
+  matches!(
+    action,
+    "status"
+      | "stats"
+      | "listServers"
+      | "ping"
+      | "testDisguise"
+      | "listDisguiseTemplates"
+      | "version"
+      | "dnsLeakTest"
+      | "publicIp"
+      | "latestRelease"
+      | "networkInterfaces"
+      | "networkIdentity"
+      | "vpnInventory"
+      | "importSubscription"
+      | "routes"
+      | "coreConfig"
+      | "speedTest"
+      | "capabilities"
+      | "probeDisguise"
+  )
+}
+
+/// Actions that skip the action queue: the read-only ones, so status polling keeps working while
+/// a `connect` is in flight and a `speedTest` doesn't hold up a `disconnect` for a minute, and
+/// `updateDisguise`, which changes the control panel rather than the agent, so batch updates can
+/// run side by side.
+fn is_unqueued(action: &str) -> bool {
+  is_read_only(action) || action == "updateDisguise"
+}
+
+/// Read-only actions the UI fires in bursts; identical calls already in flight share one answer
+/// instead of each waiting on the bridge, see `Bridge::coalesced`.
+fn is_coalesced(action: &str) -> bool {
+  matches!(action, "status" | "stats")
+}
+
+/// Callers waiting on a coalesced call, by action and payload.
+type Waiters = HashMap<(&'static str, String), Vec<oneshot::Sender<Result<Value, BridgeError>>>>;
+
+/// Receives every stderr line of the bridge process.
+pub type LogSink = Arc<dyn Fn(&str) + Send + Sync>;
+
+/// Receives the action and error of every failed state-changing call.
+pub type ErrorSink = Arc<dyn Fn(&'static str, &BridgeError) + Send + Sync>;
+
+/// Receives the action, duration and, on failure, `BridgeError::kind` of every call. It never
+/// sees payloads or results, which carry tokens and keys.
+pub type TimingSink = Arc<dyn Fn(&'static str, Duration, Option<&'static str>) + Send + Sync>;
+
+/// The certificate fingerprint pinned for a normalized base URL, if any.
+pub type PinSource = Arc<dyn Fn(&str) -> Option<String> + Send + Sync>;
+
+/// Receives the entries of a streamed list answer in order.
+pub type RecordSink = Box<dyn FnMut(Value) + Send>;
+
+/// Executes one bridge action. Implemented by the node process in production and by
+/// `mock::MockBridgeRunner` in tests.
+pub trait BridgeRunner: Send + Sync {
+  fn run(&self, action: &str, payload: Value) -> Result<Value, BridgeError>;
+
+  /// Hands a list answer to `on_record` entry by entry and returns how many there were; `null`
+  /// counts as an empty list. This default buffers the whole answer first; the node process
+  /// streams it.
+  fn run_streaming(
+    &self,
+    action: &str,
+    payload: Value,
+    mut on_record: RecordSink,
+  ) -> Result<usize, BridgeError> {
+    let records = match self.run(action, payload)? {
+      Value::Null => Vec::new(),
+      Value::Array(records) => records,
+      _ => {
+        return Err(BridgeError::InvalidJson(format!(
+          "{action} did not answer with a list"
+        )))
+      }
+    };
+    let count = records.len();
+    records.into_iter().for_each(&mut on_record);
+    Ok(count)
+  }
+
+  /// Aborts every running action, which then fails with `Cancelled`. Runners that can't
+  /// interrupt a call ignore it.
+  fn cancel(&self) {}
+}
+
+/// Owns the persistent `desktop-bridge.cjs` process. Cloning shares the same process.
+#[derive(Clone)]
+pub struct NodeBridgeRunner {
+  inner: Arc<NodeInner>,
+}
+
+struct NodeInner {
+  command: Result<BridgeCommand, BridgeError>,
+  process: Mutex<Option<Arc<BridgeProcess>>>,
+  /// Every process spawned so far that may not have been reaped yet: a replaced one can outlive
+  /// the slot while an in-flight request still holds it.
+  spawned: Mutex<Vec<Weak<BridgeProcess>>>,
+  next_id: AtomicU64,
+  log_sink: Mutex<Option<LogSink>>,
+  spawn_retries: AtomicU32,
+  proxy_env: Mutex<ProxyEnv>,
+  options: Mutex<BridgeOptions>,
+}
+
+impl NodeBridgeRunner {
+  pub fn new(command: BridgeCommand) -> Self {
+    Self::with_command(Ok(command))
+  }
+
+  /// A runner that cannot start (e.g. no usable node); every call returns `error`.
+  pub fn unavailable(error: BridgeError) -> Self {
+    Self::with_command(Err(error))
+  }
+
+  fn with_command(command: Result<BridgeCommand, BridgeError>) -> Self {
+    Self {
+      inner: Arc::new(NodeInner {
+        command,
+        process: Mutex::new(None),
+        spawned: Mutex::new(Vec::new()),
+        next_id: AtomicU64::new(1),
+        log_sink: Mutex::new(None),
+        spawn_retries: AtomicU32::new(DEFAULT_SPAWN_RETRIES),
+        proxy_env: Mutex::new(ProxyEnv::default()),
+        options: Mutex::new(BridgeOptions::default()),
+      }),
+    }
+  }
+
+  /// Routes stderr of bridge processes spawned from now on to `sink` instead of our own stderr.
+  pub fn set_log_sink(&self, sink: LogSink) {
+    *self.inner.log_sink.lock().unwrap() = Some(sink);
+  }
+
+  pub fn set_spawn_retries(&self, retries: u32) {
+    self.inner.spawn_retries.store(retries, Ordering::Relaxed);
+  }
+
+  /// Applies to bridge processes spawned from now on; `stop` makes the next call use it.
+  pub fn set_proxy_env(&self, proxy: ProxyEnv) {
+    *self.inner.proxy_env.lock().unwrap() = proxy;
+  }
+
+  /// Applies to bridge processes spawned from now on, like `set_proxy_env`.
+  pub fn set_options(&self, options: BridgeOptions) {
+    *self.inner.options.lock().unwrap() = options;
+  }
+
+  /// The resolved node version, `None` when node couldn't be resolved.
+  pub fn node_version(&self) -> Option<String> {
+    self.inner.command.as_ref().ok()?.node_version.clone()
+  }
+
+  #[cfg(test)]
+  pub fn start(&self) -> Result<(), BridgeError> {
+    self.process().map(|_| ())
+  }
+
+  /// Like `stop`, but requests still waiting on the process fail with `Cancelled`.
+  pub fn cancel(&self) {
+    if let Some(process) = self.inner.process.lock().unwrap().take() {
+      process.cancel();
+    }
+  }
+
+  /// Kills and reaps the bridge process, if any. The next call spawns a fresh one.
+  pub fn stop(&self) {
+    if let Some(process) = self.inner.process.lock().unwrap().take() {
+      process.kill();
+    }
+  }
+
+  /// Reaps every tracked process that has exited and returns the pids still running.
+  pub fn reap_exited(&self) -> Vec<u32> {
+    let mut spawned = self.inner.spawned.lock().unwrap();
+    spawned.retain(|process| {
+      process
+        .upgrade()
+        .is_some_and(|process| process.is_running())
+    });
+    spawned
+      .iter()
+      .filter_map(Weak::upgrade)
+      .map(|process| process.pid())
+      .collect()
+  }
+
+  /// Stops the current process and kills and reaps any straggler, for app exit.
+  pub fn kill_all(&self) {
+    self.stop();
+    for process in self.inner.spawned.lock().unwrap().drain(..) {
+      if let Some(process) = process.upgrade() {
+        process.kill();
+      }
+    }
+  }
+
+  /// Sweeps for exited processes every `interval` for the rest of the run.
+  #[cfg(unix)]
+  pub fn start_reaper(&self, interval: Duration) {
+    let runner = self.clone();
+    std::thread::spawn(move || loop {
+      std::thread::sleep(interval);
+      runner.reap_exited();
+    });
+  }
+
+  #[cfg(test)]
+  pub fn is_running(&self) -> bool {
+    self
+      .inner
+      .process
+      .lock()
+      .unwrap()
+      .as_ref()
+      .is_some_and(|process| process.is_running())
+  }
+
+  /// Returns the running bridge process, respawning it if it has exited since the last call.
+  fn process(&self) -> Result<Arc<BridgeProcess>, BridgeError> {
+    let mut slot = self.inner.process.lock().unwrap();
+    if let Some(process) = slot.as_ref() {
+      if process.is_running() {
+        return Ok(process.clone());
+      }
+    }
+
+    let command = self.inner.command.as_ref().map_err(Clone::clone)?;
+    let log_sink = self.inner.log_sink.lock().unwrap().clone();
+    let retries = self.inner.spawn_retries.load(Ordering::Relaxed);
+    let proxy = self.inner.proxy_env.lock().unwrap().clone();
+    let options = self.inner.options.lock().unwrap().clone();
+    let process = Arc::new(BridgeProcess::spawn(
+      command, &proxy, &options, log_sink, retries,
+    )?);
+    let mut spawned = self.inner.spawned.lock().unwrap();
+    spawned.retain(|process| process.strong_count() > 0);
+    spawned.push(Arc::downgrade(&process));
+    *slot = Some(process.clone());
+    Ok(process)
+  }
+
+  pub fn run_with_timeout(
+    &self,
+    action: &str,
+    payload: Value,
+    timeout: Duration,
+  ) -> Result<Value, BridgeError> {
+    let id = self.inner.next_id.fetch_add(1, Ordering::Relaxed);
+    self.process()?.request(id, action, &payload, timeout)
+  }
+}
+
+impl BridgeRunner for NodeBridgeRunner {
+  fn run(&self, action: &str, payload: Value) -> Result<Value, BridgeError> {
+    self.run_with_timeout(action, payload, timeout_for(action))
+  }
+
+  fn run_streaming(
+    &self,
+    action: &str,
+    payload: Value,
+    mut on_record: RecordSink,
+  ) -> Result<usize, BridgeError> {
+    let id = self.inner.next_id.fetch_add(1, Ordering::Relaxed);
+    let mut count = 0;
+    self
+      .process()?
+      .request_stream(id, action, &payload, timeout_for(action), &mut |record| {
+        count += 1;
+        on_record(record);
+      })?;
+    Ok(count)
+  }
+
+  fn cancel(&self) {
+    NodeBridgeRunner::cancel(self);
+  }
+}
+
+/// Keeps an action listed in `Bridge::running` for as long as its call is alive.
+struct Running {
+  actions: Arc<Mutex<Vec<&'static str>>>,
+  action: &'static str,
+}
+
+impl Running {
+  fn enter(actions: &Arc<Mutex<Vec<&'static str>>>, action: &'static str) -> Self {
+    actions.lock().unwrap().push(action);
+    Self {
+      actions: actions.clone(),
+      action,
+    }
+  }
+}
+
+impl Drop for Running {
+  fn drop(&mut self) {
+    let mut actions = self.actions.lock().unwrap();
+    if let Some(index) = actions.iter().position(|action| *action == self.action) {
+      actions.remove(index);
+    }
+  }
+}
+
+/// Managed handle the commands talk to. Cloning shares the runner and the action queue.
+#[derive(Clone)]
+pub struct Bridge {
+  runner: Arc<dyn BridgeRunner>,
+  /// Held for the whole of a state-changing action, so e.g. `disconnect` waits for `connect`.
+  actions: Arc<tokio::sync::Mutex<()>>,
+  /// Actions sent to the runner and not yet answered.
+  running: Arc<Mutex<Vec<&'static str>>>,
+  error_sink: Arc<Mutex<Option<ErrorSink>>>,
+  timing_sink: Arc<Mutex<Option<TimingSink>>>,
+  /// Until launch is through, `call` fails with `NotReady` instead of racing node's setup.
+  /// Bridges without one (the CLI, tests) are ready from the start.
+  readiness: Arc<Mutex<Option<Readiness>>>,
+  pin_source: Arc<Mutex<Option<PinSource>>>,
+  in_flight: Arc<Mutex<Waiters>>,
+}
+
+impl Bridge {
+  pub fn new(runner: impl BridgeRunner + 'static) -> Self {
+    Self {
+      runner: Arc::new(runner),
+      actions: Arc::new(tokio::sync::Mutex::new(())),
+      running: Arc::new(Mutex::new(Vec::new())),
+      error_sink: Arc::new(Mutex::new(None)),
+      timing_sink: Arc::new(Mutex::new(None)),
+      readiness: Arc::new(Mutex::new(None)),
+      pin_source: Arc::new(Mutex::new(None)),
+      in_flight: Arc::new(Mutex::new(HashMap::new())),
+    }
+  }
+
+  pub fn set_readiness(&self, readiness: Readiness) {
+    *self.readiness.lock().unwrap() = Some(readiness);
+  }
+
+  fn check_ready(&self) -> Result<(), BridgeError> {
+    match &*self.readiness.lock().unwrap() {
+      Some(readiness) => readiness.check(),
+      None => Ok(()),
+    }
+  }
+
+  pub fn set_pin_source(&self, source: PinSource) {
+    *self.pin_source.lock().unwrap() = Some(source);
+  }
+
+  /// Adds the fingerprint pinned for the payload's `baseUrl` as `pinnedFingerprint`, which the
+  /// bridge checks the live certificate against first. `fingerprint` goes out unpinned, so a
+  /// changed certificate can still be looked at. Returns the URL and pin for the error mapping.
+  fn pin(&self, action: &str, payload: &mut Value) -> Option<(String, String)> {
+    if action == "fingerprint" {
+      return None;
+    }
+    let source = self.pin_source.lock().unwrap().clone()?;
+    let base_url = payload.get("baseUrl")?.as_str()?.to_string();
+    let pinned = source(&base_url)?;
+    payload["pinnedFingerprint"] = json!(pinned);
+    Some((base_url, pinned))
+  }
+
+  /// Polled read-only actions and cancellations are not reported: neither is something the user
+  /// needs to find out about later.
+  pub fn set_error_sink(&self, sink: ErrorSink) {
+    *self.error_sink.lock().unwrap() = Some(sink);
+  }
+
+  pub fn set_timing_sink(&self, sink: TimingSink) {
+    *self.timing_sink.lock().unwrap() = Some(sink);
+  }
+
+  /// Whether a state-changing action is currently running.
+  pub fn is_busy(&self) -> bool {
+    self.actions.try_lock().is_err()
+  }
+
+  pub fn is_running(&self, action: &str) -> bool {
+    self.running.lock().unwrap().contains(&action)
+  }
+
+  /// Aborts whatever the runner is doing; see `BridgeRunner::cancel`.
+  pub fn cancel(&self) {
+    self.runner.cancel();
+  }
+
+  /// Runs the bridge on the blocking pool so a slow node process never stalls the IPC thread.
+  /// State-changing actions run one at a time, in the order they were issued.
+  pub async fn call(&self, action: &'static str, mut payload: Value) -> Result<Value, BridgeError> {
+    self.check_ready()?;
+    let pin = self.pin(action, &mut payload);
+    log_request(action, &payload);
+    let result = if is_coalesced(action) {
+      self.coalesced(action, payload).await
+    } else {
+      self
+        .dispatch(action, move |runner| runner.run(action, payload))
+        .await
+    };
+    result.map_err(|error| with_pin(error, pin))
+  }
+
+  /// Joins an identical call that is already in flight, or starts one for others to join. Not a
+  /// cache: a call made after the answer arrived goes to the bridge again. The call runs in a
+  /// task of its own, so the caller that started it giving up doesn't fail the rest.
+  async fn coalesced(&self, action: &'static str, payload: Value) -> Result<Value, BridgeError> {
+    let key = (action, payload.to_string());
+    let (sender, receiver) = oneshot::channel();
+    let leader = {
+      let mut in_flight = self.in_flight.lock().unwrap();
+      let waiters = in_flight.entry(key.clone()).or_default();
+      waiters.push(sender);
+      waiters.len() == 1
+    };
+    if leader {
+      let bridge = self.clone();
+      tauri::async_runtime::spawn(async move {
+        let result = bridge
+          .dispatch(action, move |runner| runner.run(action, payload))
+          .await;
+        let waiters = bridge.in_flight.lock().unwrap().remove(&key);
+        for waiter in waiters.unwrap_or_default() {
+          let _ = waiter.send(result.clone());
+        }
+      });
+    }
+    receiver.await.unwrap_or_else(|error| {
+      Err(BridgeError::SpawnFailed(format!(
+        "bridge task failed: {error}"
+      )))
+    })
+  }
+
+  /// Like `call` for an action answering with a list, whose entries go to `on_record` as they
+  /// arrive rather than in one value. Returns how many there were.
+  pub async fn call_streaming(
+    &self,
+    action: &'static str,
+    mut payload: Value,
+    on_record: impl FnMut(Value) + Send + 'static,
+  ) -> Result<usize, BridgeError> {
+    self.check_ready()?;
+    let pin = self.pin(action, &mut payload);
+    log_request(action, &payload);
+    self
+      .dispatch(action, move |runner| {
+        runner.run_streaming(action, payload, Box::new(on_record))
+      })
+      .await
+      .map_err(|error| with_pin(error, pin))
+  }
+
+  async fn dispatch<T: Send + 'static>(
+    &self,
+    action: &'static str,
+    work: impl FnOnce(&dyn BridgeRunner) -> Result<T, BridgeError> + Send + 'static,
+  ) -> Result<T, BridgeError> {
+    let _queued = if is_unqueued(action) {
+      None
+    } else {
+      Some(self.actions.lock().await)
+    };
+    let _running = Running::enter(&self.running, action);
+    let runner = self.runner.clone();
+    let started = Instant::now();
+    let result = tauri::async_runtime::spawn_blocking(move || work(runner.as_ref()))
+      .await
+      .map_err(|error| BridgeError::SpawnFailed(format!("bridge task failed: {}", error)))
+      .and_then(|result| result);
+    let elapsed = started.elapsed();
+    log_call(action, elapsed, &result);
+    let timing_sink = self.timing_sink.lock().unwrap().clone();
+    if let Some(sink) = timing_sink {
+      sink(
+        action,
+        elapsed,
+        result.as_ref().err().map(BridgeError::kind),
+      );
+    }
+    if let Err(error) = &result {
+      let sink = self.error_sink.lock().unwrap().clone();
+      if let Some(sink) = sink.filter(|_| !is_read_only(action) && *error != BridgeError::Cancelled)
+      {
+        sink(action, error);
+      }
+    }
+    result
+  }
+}
+
+fn with_pin(error: BridgeError, pin: Option<(String, String)>) -> BridgeError {
+  match pin {
+    Some((base_url, pinned)) => fingerprint::with_fingerprint_code(error, &base_url, &pinned),
+    None => error,
+  }
+}
+
+/// Runs `version` in the background at launch so node, the bridge script and the agent module are
+/// loaded before the user's first command. Best effort: `log_call` records a failure and the next
+/// command spawns a fresh bridge anyway. Skips the readiness gate, being one of the steps it
+/// waits for.
+pub fn prewarm(bridge: &Bridge, enabled: bool) -> Option<tauri::async_runtime::JoinHandle<()>> {
+  if !enabled {
+    return None;
+  }
+  let bridge = bridge.clone();
+  Some(tauri::async_runtime::spawn(async move {
+    let _ = bridge
+      .dispatch("version", |runner| runner.run("version", Value::Null))
+      .await;
+  }))
+}
+
+/// At debug, what a call was sent with: the payload's field names, never their values, which
+/// are tokens and keys as often as not.
+fn log_request(action: &str, payload: &Value) {
+  tracing::debug!(action, fields = %payload_fields(payload), "bridge call");
+}
+
+/// `baseUrl,token` for `{ "token": .., "baseUrl": .. }`; empty for anything but an object.
+pub fn payload_fields(payload: &Value) -> String {
+  let mut fields: Vec<&str> = payload
+    .as_object()
+    .map(|object| object.keys().map(String::as_str).collect())
+    .unwrap_or_default();
+  fields.sort_unstable();
+  fields.join(",")
+}
+
+/// Payloads and results carry tokens and keys, so only the action and outcome are logged.
+/// Polled read-only actions stay at debug so the file isn't one status line per second.
+fn log_call<T>(action: &str, elapsed: Duration, result: &Result<T, BridgeError>) {
+  let elapsed_ms = elapsed.as_millis() as u64;
+  match result {
+    Ok(_) if is_read_only(action) => tracing::debug!(action, elapsed_ms, "bridge call ok"),
+    Ok(_) => tracing::info!(action, elapsed_ms, "bridge call ok"),
+    Err(error) => tracing::warn!(
+      action,
+      elapsed_ms,
+      kind = error.kind(),
+      "bridge call failed: {error}"
+    ),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use mock::MockBridgeRunner;
+  use serde_json::json;
+  use std::ffi::OsStr;
+  use std::process::Stdio;
+
+  const ECHO_ID: &str = r#"id=$(printf '%s' "$line" | sed 's/.*"id":\([0-9]*\).*/\1/')"#;
+  const ECHO_ACTION: &str =
+    r#"action=$(printf '%s' "$line" | sed 's/.*"action":"\([^"]*\)".*/\1/')"#;
+
+  fn stub_bridge(dir: &tempfile::TempDir, body: &str) -> NodeBridgeRunner {
+    let script = dir.path().join("stub-bridge.sh");
+    std::fs::write(&script, body).unwrap();
+    NodeBridgeRunner::new(BridgeCommand::new("sh", &script))
+  }
+
+  fn parent(vars: &[(&str, &str)]) -> impl Fn(&str) -> Option<OsString> {
+    let vars: Vec<(String, String)> = vars
+      .iter()
+      .map(|(key, value)| (key.to_string(), value.to_string()))
+      .collect();
+    move |key| {
+      vars
+        .iter()
+        .find(|(name, _)| name == key)
+        .map(|(_, value)| OsString::from(value))
+    }
+  }
+
+  #[test]
+  fn forwards_only_whitelisted_proxy_variables() {
+    let env = forwarded_env(
+      &ProxyEnv::default(),
+      parent(&[
+        ("HTTPS_PROXY", "http://proxy.corp:3128"),
+        ("no_proxy", "localhost,.corp"),
+        ("NODE_EXTRA_CA_CERTS", "/etc/corp-ca.pem"),
+        ("AWS_SECRET_ACCESS_KEY", "secret"),
+      ]),
+    );
+
+    assert_eq!(
+      env,
+      [
+        ("HTTPS_PROXY", OsString::from("http://proxy.corp:3128")),
+        ("no_proxy", OsString::from("localhost,.corp")),
+        ("NODE_EXTRA_CA_CERTS", OsString::from("/etc/corp-ca.pem")),
+      ]
+    );
+  }
+
+  #[test]
+  fn profile_overrides_win_over_the_parent_environment() {
+    let proxy = ProxyEnv {
+      https_proxy: Some("http://home-proxy:8080".to_string()),
+      ..ProxyEnv::default()
+    };
+    let env = forwarded_env(
+      &proxy,
+      parent(&[
+        ("HTTPS_PROXY", "http://proxy.corp:3128"),
+        ("HTTP_PROXY", "http://proxy.corp:3128"),
+      ]),
+    );
+
+    assert_eq!(
+      env,
+      [
+        ("HTTP_PROXY", OsString::from("http://proxy.corp:3128")),
+        ("HTTPS_PROXY", OsString::from("http://home-proxy:8080")),
+        ("https_proxy", OsString::from("http://home-proxy:8080")),
+      ]
+    );
+  }
+
+  #[test]
+  fn bridge_command_carries_the_proxy_overrides() {
+    let proxy = ProxyEnv {
+      no_proxy: Some("cp.example.com".to_string()),
+      node_extra_ca_certs: Some("/opt/ca.pem".to_string()),
+      ..ProxyEnv::default()
+    };
+    let command =
+      BridgeCommand::new("node", "bridge.cjs").to_command(&proxy, &BridgeOptions::default());
+    let envs: Vec<(&OsStr, Option<&OsStr>)> = command.get_envs().collect();
+
+    for (key, value) in [
+      ("NO_PROXY", "cp.example.com"),
+      ("no_proxy", "cp.example.com"),
+      ("NODE_EXTRA_CA_CERTS", "/opt/ca.pem"),
+    ] {
+      assert!(
+        envs.contains(&(OsStr::new(key), Some(OsStr::new(value)))),
+        "{key} missing from {envs:?}"
+      );
+    }
+    assert!(envs.iter().all(|(key, _)| ProxyEnv::default()
+      .forwarded()
+      .iter()
+      .any(|(name, _)| OsStr::new(name) == *key)));
+  }
+
+  #[test]
+  fn bridge_command_carries_the_configured_env_and_cwd() {
+    let options = BridgeOptions {
+      env: HashMap::from([(
+        "XRAY_CP_URL".to_string(),
+        "https://staging.example.com".to_string(),
+      )]),
+      cwd: Some(PathBuf::from("/tmp/bridge-work")),
+    };
+    let command = BridgeCommand::new("node", "/opt/app/backend/bridge.cjs")
+      .to_command(&ProxyEnv::default(), &options);
+
+    assert!(command.get_envs().any(|entry| entry
+      == (
+        OsStr::new("XRAY_CP_URL"),
+        Some(OsStr::new("https://staging.example.com"))
+      )));
+    assert_eq!(
+      command.get_current_dir(),
+      Some(Path::new("/tmp/bridge-work"))
+    );
+  }
+
+  #[test]
+  fn bridge_runs_in_the_script_folder_by_default() {
+    let command = BridgeCommand::new("node", "/opt/app/backend/bridge.cjs")
+      .to_command(&ProxyEnv::default(), &BridgeOptions::default());
+
+    assert_eq!(
+      command.get_current_dir(),
+      Some(Path::new("/opt/app/backend"))
+    );
+  }
+
+  #[test]
+  fn resolves_bundled_bridge_script_from_resource_dir() {
+    let resource_dir = tempfile::tempdir().unwrap();
+    let bundled = resource_dir.path().join(BRIDGE_RESOURCE);
+    std::fs::create_dir_all(bundled.parent().unwrap()).unwrap();
+    std::fs::write(&bundled, "").unwrap();
+
+    let resolved = resolve_bridge_script(Some(resource_dir.path()));
+
+    assert_eq!(resolved, bundled);
+    assert!(resolved.exists());
+  }
+
+  #[test]
+  fn falls_back_to_dev_bridge_script_when_resource_is_missing() {
+    let resource_dir = tempfile::tempdir().unwrap();
+
+    let resolved = resolve_bridge_script(Some(resource_dir.path()));
+
+    assert!(resolved.ends_with(BRIDGE_RESOURCE));
+    assert!(resolved.exists());
+    assert_eq!(resolve_bridge_script(None), resolved);
+  }
+
+  #[test]
+  fn script_override_prefers_the_env_var_over_settings() {
+    let dir = tempfile::tempdir().unwrap();
+    let from_env = dir.path().join("env-bridge.cjs");
+    let configured = dir.path().join("configured-bridge.cjs");
+    std::fs::write(&from_env, "").unwrap();
+    std::fs::write(&configured, "").unwrap();
+
+    assert_eq!(
+      script_override(Some(from_env.clone().into()), Some(&configured)),
+      Ok(Some(from_env))
+    );
+    assert_eq!(
+      script_override(Some(OsString::new()), Some(&configured)),
+      Ok(Some(configured.clone()))
+    );
+    assert_eq!(script_override(None, None), Ok(None));
+    assert_eq!(script_override(None, Some(Path::new(""))), Ok(None));
+
+    let command = BridgeCommand::new("node", "bundled.cjs");
+    assert!(!command.is_overridden());
+    let command = command.with_script_override(Some(configured.clone()));
+    assert_eq!(command.script(), configured);
+    assert!(command.is_overridden());
+  }
+
+  #[test]
+  fn script_override_rejects_a_missing_file_or_a_directory() {
+    let dir = tempfile::tempdir().unwrap();
+    let missing = dir.path().join("missing.cjs");
+
+    let error = script_override(Some(missing.clone().into()), None).unwrap_err();
+    assert_eq!(error.kind(), "bridgeScriptInvalid");
+    assert!(error.to_string().contains(&missing.display().to_string()));
+    // A bad env value isn't papered over by a good setting.
+    let configured = dir.path().join("configured-bridge.cjs");
+    std::fs::write(&configured, "").unwrap();
+    assert!(script_override(Some(missing.into()), Some(&configured)).is_err());
+    assert!(matches!(
+      script_override(None, Some(dir.path())),
+      Err(BridgeError::BridgeScriptInvalid { reason, .. }) if reason == "it is not a file"
+    ));
+  }
+
+  #[cfg(unix)]
+  #[test]
+  fn replies_are_matched_to_request_ids() {
+    let dir = tempfile::tempdir().unwrap();
+    let bridge = stub_bridge(
+      &dir,
+      &format!(
+        "while read -r line; do\n{ECHO_ID}\nprintf '{{\"id\":%s,\"ok\":true,\"data\":%s}}\\n' \"$id\" \"$id\"\ndone\n"
+      ),
+    );
+
+    for expected in 1..=3 {
+      let data = bridge
+        .run_with_timeout("status", Value::Null, DEFAULT_TIMEOUT)
+        .unwrap();
+      assert_eq!(data, Value::from(expected));
+    }
+  }
+
+  #[cfg(unix)]
+  #[test]
+  fn streamed_records_arrive_in_order_ahead_of_the_reply() {
+    let dir = tempfile::tempdir().unwrap();
+    let bridge = stub_bridge(
+      &dir,
+      &format!(
+        r#"while read -r line; do
+{ECHO_ID}
+printf '{{"id":%s,"record":{{"id":"srv-1"}}}}\n' "$id"
+echo "xray: stray output"
+printf '{{"id":%s,"record":{{"id":"srv-2"}}}}\n' "$id"
+printf '{{"id":99,"record":{{"id":"someone else"}}}}\n'
+printf '{{"id":%s,"record":{{"id":"srv-3"}}}}\n' "$id"
+case "$line" in
+  *'"stream":true'*) printf '{{"id":%s,"ok":true,"data":null}}\n' "$id" ;;
+  *) printf '{{"id":%s,"ok":true,"data":"buffered"}}\n' "$id" ;;
+esac
+done
+"#
+      ),
+    );
+    let (sender, receiver) = std::sync::mpsc::channel();
+
+    let count = bridge
+      .run_streaming(
+        "listServers",
+        Value::Null,
+        Box::new(move |record| sender.send(record).unwrap()),
+      )
+      .unwrap();
+
+    assert_eq!(count, 3);
+    assert_eq!(
+      receiver.try_iter().collect::<Vec<_>>(),
+      [
+        json!({ "id": "srv-1" }),
+        json!({ "id": "srv-2" }),
+        json!({ "id": "srv-3" })
+      ]
+    );
+    // A plain request skips records and still gets its reply.
+    assert_eq!(bridge.run("listServers", Value::Null).unwrap(), "buffered");
+  }
+
+  #[test]
+  fn runners_without_streaming_hand_over_a_buffered_list() {
+    let runner = MockBridgeRunner::default()
+      .respond("listServers", Ok(json!([{ "id": "a" }, { "id": "b" }])))
+      .respond("status", Ok(json!({ "connected": true })));
+    let bridge = Bridge::new(runner);
+    let records = Arc::new(Mutex::new(Vec::new()));
+
+    let sink = records.clone();
+    let count = tauri::async_runtime::block_on(bridge.call_streaming(
+      "listServers",
+      Value::Null,
+      move |record| sink.lock().unwrap().push(record),
+    ))
+    .unwrap();
+
+    assert_eq!(count, 2);
+    assert_eq!(
+      *records.lock().unwrap(),
+      [json!({ "id": "a" }), json!({ "id": "b" })]
+    );
+    assert!(matches!(
+      tauri::async_runtime::block_on(bridge.call_streaming("status", Value::Null, |_| {})),
+      Err(BridgeError::InvalidJson(_))
+    ));
+  }
+
+  #[cfg(unix)]
+  #[test]
+  fn respawns_bridge_after_it_exits() {
+    let dir = tempfile::tempdir().unwrap();
+    let bridge = stub_bridge(
+      &dir,
+      &format!(
+        "read -r line\n{ECHO_ID}\nprintf '{{\"id\":%s,\"ok\":true,\"data\":%s}}\\n' \"$id\" \"$id\"\n"
+      ),
+    );
+
+    assert_eq!(
+      bridge
+        .run_with_timeout("status", Value::Null, DEFAULT_TIMEOUT)
+        .unwrap(),
+      Value::from(1)
+    );
+    // Give the one-shot stub a moment to exit so the next call observes a dead process.
+    std::thread::sleep(Duration::from_millis(200));
+    assert_eq!(
+      bridge
+        .run_with_timeout("status", Value::Null, DEFAULT_TIMEOUT)
+        .unwrap(),
+      Value::from(2)
+    );
+  }
+
+  #[cfg(unix)]
+  #[test]
+  fn surfaces_bridge_reported_errors() {
+    let dir = tempfile::tempdir().unwrap();
+    let bridge = stub_bridge(
+      &dir,
+      &format!(
+        "while read -r line; do\n{ECHO_ID}\nprintf '{{\"id\":%s,\"ok\":false,\"error\":\"No imported config found\"}}\\n' \"$id\"\ndone\n"
+      ),
+    );
+
+    let error = bridge
+      .run_with_timeout("connect", Value::Null, DEFAULT_TIMEOUT)
+      .unwrap_err();
+
+    assert_eq!(
+      error,
+      BridgeError::BridgeReported {
+        code: None,
+        message: "No imported config found".to_string()
+      }
+    );
+  }
+
+  #[cfg(unix)]
+  #[test]
+  fn maps_bridge_error_codes() {
+    let dir = tempfile::tempdir().unwrap();
+    let bridge = stub_bridge(
+      &dir,
+      &format!(
+        "while read -r line; do\n{ECHO_ID}\nprintf '{{\"id\":%s,\"ok\":false,\"code\":\"ELEVATION_REQUIRED\",\"error\":\"run with sudo\"}}\\n' \"$id\"\ndone\n"
+      ),
+    );
+
+    let error = bridge
+      .run_with_timeout("connect", Value::Null, DEFAULT_TIMEOUT)
+      .unwrap_err();
+
+    assert_eq!(
+      error,
+      BridgeError::BridgeReported {
+        code: Some("ELEVATION_REQUIRED".to_string()),
+        message: "run with sudo".to_string()
+      }
+    );
+  }
+
+  #[cfg(unix)]
+  #[test]
+  fn malformed_reply_is_invalid_json() {
+    let dir = tempfile::tempdir().unwrap();
+    let bridge = stub_bridge(
+      &dir,
+      &format!(
+        "while read -r line; do\n{ECHO_ID}\nprintf '{{\"id\":%s,\"ok\":\"yes\"}}\\n' \"$id\"\ndone\n"
+      ),
+    );
+
+    let error = bridge
+      .run_with_timeout("status", Value::Null, DEFAULT_TIMEOUT)
+      .unwrap_err();
+
+    assert!(matches!(error, BridgeError::InvalidJson(_)));
+  }
+
+  #[cfg(unix)]
+  #[test]
+  fn bridge_exiting_without_reply_is_empty_response() {
+    let dir = tempfile::tempdir().unwrap();
+    let bridge = stub_bridge(&dir, "read -r line\n");
+
+    let error = bridge
+      .run_with_timeout("status", Value::Null, DEFAULT_TIMEOUT)
+      .unwrap_err();
+
+    assert_eq!(error, BridgeError::EmptyResponse);
+  }
+
+  #[test]
+  fn prewarm_dispatches_version_once_when_enabled() {
+    let mock = MockBridgeRunner::default().respond("version", Ok(json!({ "bridge": "1.2.0" })));
+    let bridge = Bridge::new(mock.clone());
+
+    assert!(prewarm(&bridge, false).is_none());
+    tauri::async_runtime::block_on(prewarm(&bridge, true).unwrap()).unwrap();
+
+    assert_eq!(mock.calls(), [("version".to_string(), Value::Null)]);
+  }
+
+  #[test]
+  fn failed_prewarm_is_not_fatal() {
+    let bridge =
+      Bridge::new(MockBridgeRunner::default().respond("version", Err(BridgeError::EmptyResponse)));
+
+    assert!(tauri::async_runtime::block_on(prewarm(&bridge, true).unwrap()).is_ok());
+  }
+
+  #[test]
+  fn calls_before_setup_completes_are_not_ready() {
+    use crate::readiness::Prerequisite;
+
+    let mock = MockBridgeRunner::default()
+      .respond("version", Ok(json!({ "bridge": "1.2.0" })))
+      .respond("status", Ok(json!({ "state": "disconnected" })));
+    let bridge = Bridge::new(mock.clone());
+    let readiness = Readiness::default();
+    bridge.set_readiness(readiness.clone());
+
+    let early = tauri::async_runtime::block_on(bridge.call("status", Value::Null));
+    assert_eq!(early, Err(BridgeError::NotReady));
+    // Prewarm is itself a prerequisite, so the gate lets it through.
+    tauri::async_runtime::block_on(prewarm(&bridge, true).unwrap()).unwrap();
+    assert_eq!(mock.calls(), [("version".to_string(), Value::Null)]);
+
+    readiness.complete(Prerequisite::NodeResolved);
+    readiness.complete(Prerequisite::BridgeVerified);
+    readiness.complete(Prerequisite::Prewarmed);
+    assert!(tauri::async_runtime::block_on(bridge.call("status", Value::Null)).is_ok());
+  }
+
+  #[test]
+  fn error_sink_sees_failed_state_changing_calls_only() {
+    let bridge = Bridge::new(
+      MockBridgeRunner::default()
+        .respond("connect", Err(BridgeError::EmptyResponse))
+        .respond("disconnect", Err(BridgeError::Cancelled))
+        .respond("status", Err(BridgeError::EmptyResponse))
+        .respond("updateDisguise", Err(BridgeError::EmptyResponse))
+        .respond("pause", Ok(Value::Null)),
+    );
+    let seen = Arc::new(Mutex::new(Vec::new()));
+    let sink_seen = seen.clone();
+    bridge.set_error_sink(Arc::new(move |action, error| {
+      sink_seen.lock().unwrap().push((action, error.clone()));
+    }));
+
+    for action in ["connect", "disconnect", "status", "updateDisguise", "pause"] {
+      let _ = tauri::async_runtime::block_on(bridge.call(action, Value::Null));
+    }
+
+    assert_eq!(
+      *seen.lock().unwrap(),
+      [
+        ("connect", BridgeError::EmptyResponse),
+        ("updateDisguise", BridgeError::EmptyResponse)
+      ]
+    );
+  }
+
+  #[test]
+  fn timing_sink_sees_every_call_without_its_payload() {
+    let bridge = Bridge::new(
+      MockBridgeRunner::default()
+        .respond("status", Ok(json!({ "token": "abcDEF123_token_value" })))
+        .respond("connect", Err(BridgeError::EmptyResponse)),
+    );
+    let seen = Arc::new(Mutex::new(Vec::new()));
+    let sink_seen = seen.clone();
+    bridge.set_timing_sink(Arc::new(move |action, _elapsed, error| {
+      sink_seen.lock().unwrap().push((action, error));
+    }));
+
+    for action in ["status", "connect"] {
+      let _ = tauri::async_runtime::block_on(bridge.call(action, Value::Null));
+    }
+
+    assert_eq!(
+      *seen.lock().unwrap(),
+      [("status", None), ("connect", Some("emptyResponse"))]
+    );
+  }
+
+  #[cfg(unix)]
+  #[test]
+  fn bridge_exiting_non_zero_reports_a_crash() {
+    let dir = tempfile::tempdir().unwrap();
+    let bridge = stub_bridge(
+      &dir,
+      "read -r line
+echo 'loading addon' >&2
+echo 'Error: addon.node failed' >&2
+exit 3
+",
+    );
+
+    let error = bridge
+      .run_with_timeout("status", Value::Null, DEFAULT_TIMEOUT)
+      .unwrap_err();
+
+    assert_eq!(
+      error,
+      BridgeError::BridgeCrashed {
+        code: Some(3),
+        signal: None,
+        stderr: "loading addon\nError: addon.node failed".to_string(),
+      }
+    );
+    assert_eq!(error.kind(), "bridgeCrashed");
+  }
+
+  #[cfg(unix)]
+  #[test]
+  fn bridge_killed_by_a_signal_reports_it() {
+    let dir = tempfile::tempdir().unwrap();
+    let bridge = stub_bridge(
+      &dir,
+      "read -r line
+kill -SEGV $$
+",
+    );
+
+    let error = bridge
+      .run_with_timeout("status", Value::Null, DEFAULT_TIMEOUT)
+      .unwrap_err();
+
+    assert!(
+      matches!(
+        error,
+        BridgeError::BridgeCrashed {
+          code: None,
+          signal: Some(11),
+          ..
+        }
+      ),
+      "{error:?}"
+    );
+  }
+
+  #[test]
+  fn missing_node_is_node_not_found() {
+    let bridge = NodeBridgeRunner::new(BridgeCommand::new(
+      "definitely-not-a-node-binary",
+      "bridge.cjs",
+    ));
+
+    let error = bridge
+      .run_with_timeout("status", Value::Null, DEFAULT_TIMEOUT)
+      .unwrap_err();
+
+    assert!(matches!(error, BridgeError::NodeNotFound(_)));
+  }
+
+  #[test]
+  fn unavailable_bridge_reports_the_resolution_error() {
+    let too_old = BridgeError::NodeTooOld {
+      found: "18.19.0".to_string(),
+      required: "20.0.0".to_string(),
+    };
+    let bridge = NodeBridgeRunner::unavailable(too_old.clone());
+
+    assert_eq!(bridge.start(), Err(too_old.clone()));
+    assert_eq!(
+      bridge.run_with_timeout("status", Value::Null, DEFAULT_TIMEOUT),
+      Err(too_old)
+    );
+  }
+
+  #[cfg(unix)]
+  #[test]
+  fn unexecutable_program_is_spawn_failed() {
+    let dir = tempfile::tempdir().unwrap();
+    let program = dir.path().join("not-executable");
+    std::fs::write(&program, "").unwrap();
+    let bridge = NodeBridgeRunner::new(BridgeCommand::new(program.to_string_lossy(), "bridge.cjs"));
+
+    let error = bridge
+      .run_with_timeout("status", Value::Null, DEFAULT_TIMEOUT)
+      .unwrap_err();
+
+    assert!(matches!(error, BridgeError::SpawnFailed(_)));
+  }
+
+  #[cfg(unix)]
+  #[test]
+  fn concurrent_slow_calls_do_not_serialize() {
+    let dir = tempfile::tempdir().unwrap();
+    let bridge = stub_bridge(
+      &dir,
+      &format!(
+        "while read -r line; do\n{ECHO_ID}\n{ECHO_ACTION}\n(sleep 1; printf '{{\"id\":%s,\"ok\":true,\"data\":\"%s\"}}\\n' \"$id\" \"$action\") &\ndone\n"
+      ),
+    );
+    bridge.start().unwrap();
+    let bridge = Bridge::new(bridge);
+
+    let started = Instant::now();
+    let (first, second) = tauri::async_runtime::block_on(async {
+      let first = tauri::async_runtime::spawn({
+        let bridge = bridge.clone();
+        async move { bridge.call("status", Value::Null).await }
+      });
+      let second = tauri::async_runtime::spawn({
+        let bridge = bridge.clone();
+        async move { bridge.call("connect", Value::Null).await }
+      });
+      (first.await.unwrap(), second.await.unwrap())
+    });
+
+    assert_eq!(first.unwrap(), Value::from("status"));
+    assert_eq!(second.unwrap(), Value::from("connect"));
+    assert!(started.elapsed() < Duration::from_millis(1800));
+  }
+
+  #[cfg(unix)]
+  #[test]
+  fn overlapping_connect_and_disconnect_run_sequentially() {
+    let dir = tempfile::tempdir().unwrap();
+    let calls = dir.path().join("calls.log");
+    let bridge = stub_bridge(
+      &dir,
+      &format!(
+        "while read -r line; do\n{ECHO_ID}\n{ECHO_ACTION}\n(echo \"start $action\" >> '{log}'; sleep 0.5; echo \"end $action\" >> '{log}'; printf '{{\"id\":%s,\"ok\":true,\"data\":null}}\\n' \"$id\") &\ndone\n",
+        log = calls.display()
+      ),
+    );
+    bridge.start().unwrap();
+    let bridge = Bridge::new(bridge);
+
+    tauri::async_runtime::block_on(async {
+      let connect = tauri::async_runtime::spawn({
+        let bridge = bridge.clone();
+        async move { bridge.call("connect", Value::Null).await }
+      });
+      while !bridge.is_busy() {
+        tokio::time::sleep(Duration::from_millis(10)).await;
+      }
+      let disconnect = bridge.call("disconnect", Value::Null).await;
+      assert!(connect.await.unwrap().is_ok());
+      assert!(disconnect.is_ok());
+    });
+
+    assert!(!bridge.is_busy());
+    assert_eq!(
+      std::fs::read_to_string(&calls).unwrap(),
+      "start connect\nend connect\nstart disconnect\nend disconnect\n"
+    );
+  }
+
+  /// Answers every action after `delay` with its name and payload, logging each run.
+  #[derive(Clone, Default)]
+  struct SlowRunner {
+    delay: Duration,
+    runs: Arc<Mutex<Vec<&'static str>>>,
+  }
+
+  impl SlowRunner {
+    fn runs_of(&self, action: &str) -> usize {
+      self
+        .runs
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|run| **run == action)
+        .count()
+    }
+  }
+
+  impl BridgeRunner for SlowRunner {
+    fn run(&self, action: &str, payload: Value) -> Result<Value, BridgeError> {
+      let action = match action {
+        "status" => "status",
+        "stats" => "stats",
+        _ => "other",
+      };
+      self.runs.lock().unwrap().push(action);
+      std::thread::sleep(self.delay);
+      Ok(json!({ "action": action, "payload": payload }))
+    }
+  }
+
+  #[test]
+  fn concurrent_status_calls_share_one_bridge_call() {
+    let runner = SlowRunner {
+      delay: Duration::from_millis(300),
+      ..SlowRunner::default()
+    };
+    let bridge = Bridge::new(runner.clone());
+
+    let (statuses, set_mode) = tauri::async_runtime::block_on(async {
+      let statuses: Vec<_> = (0..10)
+        .map(|_| {
+          let bridge = bridge.clone();
+          tauri::async_runtime::spawn(async move { bridge.call("status", Value::Null).await })
+        })
+        .collect();
+      let set_mode = tauri::async_runtime::spawn({
+        let bridge = bridge.clone();
+        async move { bridge.call("setMode", json!({ "mode": "proxy" })).await }
+      });
+      let mut results = Vec::new();
+      for status in statuses {
+        results.push(status.await.unwrap());
+      }
+      (results, set_mode.await.unwrap())
+    });
+
+    assert_eq!(runner.runs_of("status"), 1);
+    assert_eq!(runner.runs_of("other"), 1);
+    for status in statuses {
+      assert_eq!(
+        status.unwrap(),
+        json!({ "action": "status", "payload": null })
+      );
+    }
+    assert_eq!(set_mode.unwrap()["payload"], json!({ "mode": "proxy" }));
+    assert!(bridge.in_flight.lock().unwrap().is_empty());
+  }
+
+  #[test]
+  fn only_identical_in_flight_reads_are_coalesced() {
+    let runner = SlowRunner {
+      delay: Duration::from_millis(100),
+      ..SlowRunner::default()
+    };
+    let bridge = Bridge::new(runner.clone());
+
+    tauri::async_runtime::block_on(async {
+      let first = tauri::async_runtime::spawn({
+        let bridge = bridge.clone();
+        async move { bridge.call("stats", json!({ "window": 1 })).await }
+      });
+      let second = bridge.call("stats", json!({ "window": 2 })).await.unwrap();
+      assert_eq!(second["payload"], json!({ "window": 2 }));
+      assert_eq!(
+        first.await.unwrap().unwrap()["payload"],
+        json!({ "window": 1 })
+      );
+
+      // Once answered, the next call goes to the bridge again.
+      bridge.call("status", Value::Null).await.unwrap();
+      bridge.call("status", Value::Null).await.unwrap();
+    });
+
+    assert_eq!(runner.runs_of("stats"), 2);
+    assert_eq!(runner.runs_of("status"), 2);
+  }
+
+  #[cfg(unix)]
+  #[test]
+  fn reply_followed_by_non_utf8_bytes_still_parses() {
+    let dir = tempfile::tempdir().unwrap();
+    let bridge = stub_bridge(
+      &dir,
+      &format!(
+        "while read -r line; do\n{ECHO_ID}\nprintf '\\377\\376 noise\\n'\nprintf '{{\"id\":%s,\"ok\":true,\"data\":\"fine\"}}\\377\\376\\200\\n' \"$id\"\ndone\n"
+      ),
+    );
+
+    for _ in 0..2 {
+      assert_eq!(
+        bridge
+          .run_with_timeout("status", Value::Null, DEFAULT_TIMEOUT)
+          .unwrap(),
+        Value::from("fine")
+      );
+    }
+  }
+
+  #[cfg(unix)]
+  #[test]
+  fn hung_bridge_is_killed_after_timeout() {
+    let dir = tempfile::tempdir().unwrap();
+    let pid_file = dir.path().join("bridge.pid");
+    let bridge = stub_bridge(
+      &dir,
+      &format!(
+        "echo $$ > '{}'\nwhile read -r line; do :; done\n",
+        pid_file.display()
+      ),
+    );
+
+    let started = Instant::now();
+    let result = bridge.run_with_timeout("connect", Value::Null, Duration::from_millis(300));
+
+    assert_eq!(
+      result.unwrap_err(),
+      BridgeError::Timeout(Duration::from_millis(300))
+    );
+    assert!(started.elapsed() < Duration::from_secs(5));
+    let pid = std::fs::read_to_string(&pid_file).unwrap();
+    let alive = Command::new("kill")
+      .args(["-0", pid.trim()])
+      .stderr(Stdio::null())
+      .status()
+      .unwrap();
+    assert!(!alive.success());
+  }
+
+  #[cfg(unix)]
+  #[test]
+  fn a_timeout_spares_the_requests_still_in_flight() {
+    let dir = tempfile::tempdir().unwrap();
+    // Never answers `ping`; answers everything else after a while, concurrently.
+    let bridge = stub_bridge(
+      &dir,
+      &format!(
+        "while read -r line; do\n{ECHO_ID}\n{ECHO_ACTION}\n[ \"$action\" = ping ] && continue\n(sleep 0.6; printf '{{\"id\":%s,\"ok\":true,\"data\":\"up\"}}\\n' \"$id\") &\ndone\n"
+      ),
+    );
+    bridge.start().unwrap();
+    let pids = bridge.reap_exited();
+
+    let connect = std::thread::spawn({
+      let bridge = bridge.clone();
+      move || bridge.run_with_timeout("connect", Value::Null, Duration::from_secs(5))
+    });
+    std::thread::sleep(Duration::from_millis(100));
+    let ping = bridge.run_with_timeout("ping", Value::Null, Duration::from_millis(200));
+
+    assert_eq!(
+      ping.unwrap_err(),
+      BridgeError::Timeout(Duration::from_millis(200))
+    );
+    assert_eq!(connect.join().unwrap().unwrap(), Value::from("up"));
+    assert_eq!(bridge.reap_exited(), pids);
+  }
+
+  /// `ps` state of `pid`, empty once it is gone for good; `Z` is a zombie.
+  #[cfg(unix)]
+  fn process_state(pid: u32) -> String {
+    let output = Command::new("ps")
+      .args(["-o", "stat=", "-p", &pid.to_string()])
+      .output()
+      .unwrap();
+    String::from_utf8_lossy(&output.stdout).trim().to_string()
+  }
+
+  #[cfg(unix)]
+  #[test]
+  fn timed_out_sleeping_child_leaves_no_zombie() {
+    let dir = tempfile::tempdir().unwrap();
+    let bridge = stub_bridge(&dir, "exec sleep 30\n");
+    bridge.start().unwrap();
+    let pids = bridge.reap_exited();
+    assert_eq!(pids.len(), 1);
+
+    let result = bridge.run_with_timeout("connect", Value::Null, Duration::from_millis(300));
+
+    assert_eq!(
+      result.unwrap_err(),
+      BridgeError::Timeout(Duration::from_millis(300))
+    );
+    assert_eq!(process_state(pids[0]), "");
+    assert!(bridge.reap_exited().is_empty());
+  }
+
+  #[cfg(unix)]
+  #[test]
+  fn sweep_reaps_a_bridge_that_exited_on_its_own() {
+    let dir = tempfile::tempdir().unwrap();
+    // Answers one request, then exits while nothing is waiting on it.
+    let bridge = stub_bridge(
+      &dir,
+      &format!("read -r line\n{ECHO_ID}\nprintf '{{\"id\":%s,\"ok\":true}}\\n' \"$id\"\nexit 0\n"),
+    );
+    bridge.start().unwrap();
+    let pid = bridge.reap_exited()[0];
+    bridge
+      .run_with_timeout("status", Value::Null, DEFAULT_TIMEOUT)
+      .unwrap();
+    std::thread::sleep(Duration::from_millis(200));
+    assert!(process_state(pid).starts_with('Z'));
+
+    assert!(bridge.reap_exited().is_empty());
+    assert_eq!(process_state(pid), "");
+  }
+
+  #[cfg(unix)]
+  #[test]
+  fn kill_all_reaps_every_tracked_process() {
+    let dir = tempfile::tempdir().unwrap();
+    let bridge = stub_bridge(&dir, "while read -r line; do :; done\n");
+    bridge.start().unwrap();
+    let pids = bridge.reap_exited();
+
+    bridge.kill_all();
+
+    assert_eq!(process_state(pids[0]), "");
+    assert!(bridge.reap_exited().is_empty());
+  }
+
+  #[test]
+  fn status_uses_a_shorter_timeout_than_connect() {
+    assert_eq!(timeout_for("status"), Duration::from_secs(5));
+    assert!(timeout_for("connect") > DEFAULT_TIMEOUT);
+    assert_eq!(timeout_for("setMode"), DEFAULT_TIMEOUT);
+  }
+
+  #[derive(Clone, Default)]
+  struct Captured(Arc<Mutex<Vec<u8>>>);
+
+  impl std::io::Write for Captured {
+    fn write(&mut self, bytes: &[u8]) -> std::io::Result<usize> {
+      self.0.lock().unwrap().extend_from_slice(bytes);
+      Ok(bytes.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+      Ok(())
+    }
+  }
+
+  #[test]
+  fn call_log_never_contains_the_payload() {
+    let captured = Captured::default();
+    let writer = captured.clone();
+    let subscriber = tracing_subscriber::fmt()
+      .with_writer(move || writer.clone())
+      .with_max_level(tracing::Level::TRACE)
+      .finish();
+    let bridge = Bridge::new(
+      mock::MockBridgeRunner::default()
+        .respond(
+          "importToken",
+          Ok(serde_json::json!({ "token": "secret_token_value" })),
+        )
+        .respond(
+          "connect",
+          Err(BridgeError::BridgeReported {
+            code: Some("STARTUP_FAILED".to_string()),
+            message: "Supervisor exited during startup".to_string(),
+          }),
+        ),
+    );
+
+    tracing::subscriber::with_default(subscriber, || {
+      let payload =
+        serde_json::json!({ "baseUrl": "https://cp.example", "token": "secret_token_value" });
+      tauri::async_runtime::block_on(async {
+        bridge.call("importToken", payload.clone()).await.unwrap();
+        bridge.call("connect", payload).await.unwrap_err();
+      });
+    });
+
+    let log = String::from_utf8(captured.0.lock().unwrap().clone()).unwrap();
+    let lines: Vec<&str> = log.lines().collect();
+    assert_eq!(lines.len(), 4, "{log}");
+    // What debug mode adds: field names, still no values.
+    assert!(lines[0].contains("DEBUG") && lines[0].contains("fields=baseUrl,token"));
+    assert!(lines[1].contains("INFO") && lines[1].contains("action=\"importToken\""));
+    assert!(lines[2].contains("DEBUG") && lines[2].contains("action=\"connect\""));
+    assert!(lines[3].contains("WARN") && lines[3].contains("kind=\"bridgeReported\""));
+    assert!(!log.contains("secret_token_value"), "{log}");
+    assert!(!log.contains("cp.example"), "{log}");
+  }
+}
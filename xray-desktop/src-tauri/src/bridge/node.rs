@@ -0,0 +1,277 @@
+use crate::error::BridgeError;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+/// Matches `engines.node` of `xray-client-agent`.
+pub const REQUIRED_NODE: NodeVersion = NodeVersion {
+  major: 20,
+  minor: 0,
+  patch: 0,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct NodeVersion {
+  pub major: u32,
+  pub minor: u32,
+  pub patch: u32,
+}
+
+impl NodeVersion {
+  /// Parses `node --version` output such as `v20.11.1` or `v22.0.0-nightly2024`.
+  pub fn parse(output: &str) -> Option<Self> {
+    let version = output.trim().strip_prefix('v').unwrap_or(output.trim());
+    let core = version.split(['-', '+']).next()?;
+    let mut parts = core.split('.').map(|part| part.parse::<u32>().ok());
+
+    Some(Self {
+      major: parts.next()??,
+      minor: parts.next().unwrap_or(Some(0))?,
+      patch: parts.next().unwrap_or(Some(0))?,
+    })
+  }
+}
+
+impl fmt::Display for NodeVersion {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+  }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResolvedNode {
+  pub path: PathBuf,
+  pub version: NodeVersion,
+}
+
+/// Finds a usable node: `NODE_BINARY`, then the path from settings, then well-known install
+/// locations, then PATH.
+pub fn resolve_node(configured: Option<&Path>) -> Result<ResolvedNode, BridgeError> {
+  let mut candidates = Vec::new();
+  candidates.extend(std::env::var_os("NODE_BINARY").map(PathBuf::from));
+  candidates.extend(configured.map(Path::to_path_buf));
+  candidates.extend(install_locations());
+  candidates.push(PathBuf::from(node_executable()));
+
+  resolve_from(candidates)
+}
+
+/// Returns the first candidate that is new enough. If only outdated installs were found the
+/// error names the newest of them.
+fn resolve_from(
+  candidates: impl IntoIterator<Item = PathBuf>,
+) -> Result<ResolvedNode, BridgeError> {
+  let mut newest_too_old: Option<NodeVersion> = None;
+
+  for path in candidates {
+    let Some(version) = probe(&path) else {
+      continue;
+    };
+    if version >= REQUIRED_NODE {
+      return Ok(ResolvedNode { path, version });
+    }
+    newest_too_old = newest_too_old.max(Some(version));
+  }
+
+  Err(match newest_too_old {
+    Some(found) => BridgeError::NodeTooOld {
+      found: found.to_string(),
+      required: REQUIRED_NODE.to_string(),
+    },
+    None => BridgeError::NodeNotFound(format!(
+      "install Node.js {} or newer, or set NODE_BINARY",
+      REQUIRED_NODE
+    )),
+  })
+}
+
+fn probe(path: &Path) -> Option<NodeVersion> {
+  let mut command = Command::new(path);
+  command
+    .arg("--version")
+    .stdin(Stdio::null())
+    .stderr(Stdio::null());
+
+  #[cfg(windows)]
+  {
+    use std::os::windows::process::CommandExt;
+    command.creation_flags(super::CREATE_NO_WINDOW);
+  }
+
+  let output = command.output().ok()?;
+  if !output.status.success() {
+    return None;
+  }
+  NodeVersion::parse(&String::from_utf8_lossy(&output.stdout))
+}
+
+fn node_executable() -> &'static str {
+  if cfg!(windows) {
+    "node.exe"
+  } else {
+    "node"
+  }
+}
+
+fn install_locations() -> Vec<PathBuf> {
+  let mut locations = Vec::new();
+
+  if cfg!(windows) {
+    for var in ["ProgramFiles", "ProgramFiles(x86)"] {
+      if let Some(dir) = std::env::var_os(var) {
+        locations.push(PathBuf::from(dir).join("nodejs").join(node_executable()));
+      }
+    }
+    // nvm-windows keeps the active version behind a symlink and the rest under %APPDATA%\nvm.
+    if let Some(dir) = std::env::var_os("NVM_SYMLINK") {
+      locations.push(PathBuf::from(dir).join(node_executable()));
+    }
+    if let Some(dir) = std::env::var_os("NVM_HOME") {
+      locations.extend(nvm_installs(Path::new(&dir), Path::new("")));
+    }
+  } else {
+    locations.push(PathBuf::from("/usr/local/bin/node"));
+    locations.push(PathBuf::from("/opt/homebrew/bin/node"));
+    let nvm_dir = std::env::var_os("NVM_DIR")
+      .map(PathBuf::from)
+      .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".nvm")));
+    if let Some(dir) = nvm_dir {
+      locations.extend(nvm_installs(
+        &dir.join("versions").join("node"),
+        Path::new("bin"),
+      ));
+    }
+  }
+
+  locations
+}
+
+/// Node binaries of every version installed under `root`, newest first.
+fn nvm_installs(root: &Path, bin: &Path) -> Vec<PathBuf> {
+  let Ok(entries) = std::fs::read_dir(root) else {
+    return Vec::new();
+  };
+
+  let mut installs: Vec<(NodeVersion, PathBuf)> = entries
+    .filter_map(Result::ok)
+    .filter_map(|entry| {
+      let version = NodeVersion::parse(&entry.file_name().to_string_lossy())?;
+      Some((version, entry.path().join(bin).join(node_executable())))
+    })
+    .collect();
+  installs.sort_by_key(|(version, _)| std::cmp::Reverse(*version));
+  installs.into_iter().map(|(_, path)| path).collect()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn version(major: u32, minor: u32, patch: u32) -> NodeVersion {
+    NodeVersion {
+      major,
+      minor,
+      patch,
+    }
+  }
+
+  #[test]
+  fn parses_node_version_output() {
+    assert_eq!(NodeVersion::parse("v20.11.1\n"), Some(version(20, 11, 1)));
+    assert_eq!(NodeVersion::parse("22.3.0"), Some(version(22, 3, 0)));
+    assert_eq!(
+      NodeVersion::parse("v23.0.0-nightly20240901"),
+      Some(version(23, 0, 0))
+    );
+    assert_eq!(NodeVersion::parse("v21"), Some(version(21, 0, 0)));
+  }
+
+  #[test]
+  fn rejects_garbage_version_output() {
+    assert_eq!(NodeVersion::parse(""), None);
+    assert_eq!(NodeVersion::parse("node: command not found"), None);
+    assert_eq!(NodeVersion::parse("v20.x.1"), None);
+  }
+
+  #[test]
+  fn compares_versions_numerically() {
+    assert!(version(20, 0, 0) >= REQUIRED_NODE);
+    assert!(version(19, 99, 99) < REQUIRED_NODE);
+    assert!(version(20, 10, 0) > version(20, 9, 5));
+  }
+
+  #[test]
+  fn lists_nvm_installs_newest_first() {
+    let dir = tempfile::tempdir().unwrap();
+    for name in ["v18.19.0", "v20.9.0", "v20.11.1", "not-a-version"] {
+      std::fs::create_dir(dir.path().join(name)).unwrap();
+    }
+
+    let installs = nvm_installs(dir.path(), Path::new("bin"));
+
+    let names: Vec<_> = installs
+      .iter()
+      .map(|path| path.strip_prefix(dir.path()).unwrap().to_path_buf())
+      .collect();
+    assert_eq!(
+      names,
+      ["v20.11.1", "v20.9.0", "v18.19.0"]
+        .iter()
+        .map(|name| Path::new(name).join("bin").join(node_executable()))
+        .collect::<Vec<_>>()
+    );
+  }
+
+  #[cfg(unix)]
+  fn fake_node(dir: &Path, name: &str, version: &str) -> PathBuf {
+    use std::os::unix::fs::PermissionsExt;
+
+    let path = dir.join(name);
+    std::fs::write(&path, format!("#!/bin/sh\necho {}\n", version)).unwrap();
+    std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+    path
+  }
+
+  #[cfg(unix)]
+  #[test]
+  fn skips_missing_and_outdated_candidates() {
+    let dir = tempfile::tempdir().unwrap();
+    let old = fake_node(dir.path(), "old-node", "v18.19.0");
+    let current = fake_node(dir.path(), "new-node", "v20.11.1");
+
+    let resolved = resolve_from([dir.path().join("missing"), old, current.clone()]).unwrap();
+
+    assert_eq!(
+      resolved,
+      ResolvedNode {
+        path: current,
+        version: version(20, 11, 1),
+      }
+    );
+  }
+
+  #[cfg(unix)]
+  #[test]
+  fn reports_outdated_node() {
+    let dir = tempfile::tempdir().unwrap();
+    let old = fake_node(dir.path(), "old-node", "v18.19.0");
+
+    assert_eq!(
+      resolve_from([old]),
+      Err(BridgeError::NodeTooOld {
+        found: "18.19.0".to_string(),
+        required: "20.0.0".to_string(),
+      })
+    );
+  }
+
+  #[test]
+  fn reports_missing_node() {
+    let dir = tempfile::tempdir().unwrap();
+
+    assert!(matches!(
+      resolve_from([dir.path().join("missing")]),
+      Err(BridgeError::NodeNotFound(_))
+    ));
+  }
+}
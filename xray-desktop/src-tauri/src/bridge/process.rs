@@ -0,0 +1,534 @@
+use super::{BridgeCommand, BridgeOptions, LogSink, ProxyEnv};
+use crate::error::BridgeError;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{HashMap, VecDeque};
+use std::io::{self, BufRead, BufReader, Write};
+use std::process::{Child, ChildStderr, ChildStdin, ChildStdout, ExitStatus, Stdio};
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+/// Stderr lines kept for `BridgeCrashed`.
+const STDERR_TAIL: usize = 20;
+/// How long a bridge whose stdout closed gets to finish exiting before its status is read.
+const EXIT_GRACE: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Serialize)]
+struct RpcRequest<'a> {
+  id: u64,
+  action: &'a str,
+  payload: &'a Value,
+  /// Asks for a list answer as one `{ id, record }` line per entry ahead of the reply.
+  #[serde(skip_serializing_if = "std::ops::Not::not")]
+  stream: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct RpcReply {
+  ok: bool,
+  data: Option<Value>,
+  error: Option<String>,
+  code: Option<String>,
+}
+
+impl RpcReply {
+  fn into_result(self) -> Result<Value, BridgeError> {
+    if self.ok {
+      return Ok(self.data.unwrap_or(Value::Null));
+    }
+
+    Err(BridgeError::BridgeReported {
+      code: self.code,
+      message: self
+        .error
+        .unwrap_or_else(|| "Unknown bridge error".to_string()),
+    })
+  }
+}
+
+/// What the dispatcher hands a waiting request: any number of records, then the reply.
+enum Frame {
+  Record(Value),
+  Reply(Result<RpcReply, BridgeError>),
+}
+
+type ReplySender = Sender<Frame>;
+
+/// Requests waiting for a reply, keyed by id. `None` once the bridge stdout has closed.
+type Pending = Arc<Mutex<Option<HashMap<u64, ReplySender>>>>;
+
+type StderrTail = Arc<Mutex<VecDeque<String>>>;
+
+/// What a bridge that went away without replying turns into: a clean exit is still an empty
+/// response, anything else a crash.
+fn exit_error(status: Option<ExitStatus>, stderr: String) -> BridgeError {
+  let Some(status) = status.filter(|status| !status.success()) else {
+    return BridgeError::EmptyResponse;
+  };
+  #[cfg(unix)]
+  let signal = std::os::unix::process::ExitStatusExt::signal(&status);
+  #[cfg(not(unix))]
+  let signal = None;
+  BridgeError::BridgeCrashed {
+    code: status.code(),
+    signal,
+    stderr,
+  }
+}
+
+/// Pause before spawn retry `attempt` (1-based): 50ms, 200ms, 800ms, then 800ms again.
+pub fn spawn_backoff(attempt: u32) -> Duration {
+  Duration::from_millis(50 * 4u64.pow(attempt.clamp(1, 3) - 1))
+}
+
+/// Resource exhaustion on a busy machine, which clears up on its own. A missing or unexecutable
+/// node never does, so those fail right away.
+fn is_transient(error: &io::Error) -> bool {
+  if matches!(
+    error.kind(),
+    io::ErrorKind::WouldBlock
+      | io::ErrorKind::Interrupted
+      | io::ErrorKind::ResourceBusy
+      | io::ErrorKind::OutOfMemory
+  ) {
+    return true;
+  }
+  // EMFILE / ENFILE (too many open files) have no `ErrorKind` of their own.
+  #[cfg(unix)]
+  if matches!(error.raw_os_error(), Some(23 | 24)) {
+    return true;
+  }
+  false
+}
+
+/// Calls `spawn` until it succeeds, fails for good, or `retries` extra attempts are used up.
+/// `on_retry` hears about each retry before its backoff sleep.
+pub fn retry_spawn<T>(
+  retries: u32,
+  mut spawn: impl FnMut() -> io::Result<T>,
+  mut on_retry: impl FnMut(u32, &io::Error, Duration),
+) -> io::Result<T> {
+  let mut attempt = 0;
+  loop {
+    match spawn() {
+      Err(error) if attempt < retries && is_transient(&error) => {
+        attempt += 1;
+        let delay = spawn_backoff(attempt);
+        on_retry(attempt, &error, delay);
+        thread::sleep(delay);
+      }
+      result => return result,
+    }
+  }
+}
+
+/// A long-lived `desktop-bridge.cjs --serve` child speaking line-delimited JSON-RPC.
+pub struct BridgeProcess {
+  pid: u32,
+  child: Mutex<Child>,
+  stdin: Mutex<ChildStdin>,
+  pending: Pending,
+  stderr_tail: StderrTail,
+  log_reader: JoinHandle<()>,
+}
+
+impl BridgeProcess {
+  pub fn spawn(
+    command: &BridgeCommand,
+    proxy: &ProxyEnv,
+    options: &BridgeOptions,
+    log_sink: Option<LogSink>,
+    retries: u32,
+  ) -> Result<Self, BridgeError> {
+    let spawn = || {
+      command
+        .to_command(proxy, options)
+        .arg("--serve")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+    };
+    let log_retry = |attempt: u32, error: &io::Error, delay: Duration| {
+      let line = format!(
+        "warn: bridge spawn failed ({error}), retry {attempt}/{retries} in {}ms",
+        delay.as_millis()
+      );
+      match &log_sink {
+        Some(sink) => sink(&line),
+        None => eprintln!("{}", line),
+      }
+    };
+    let mut child = retry_spawn(retries, spawn, log_retry).map_err(BridgeError::from_spawn)?;
+
+    let stdin = child.stdin.take().expect("stdin is piped");
+    let stdout = child.stdout.take().expect("stdout is piped");
+    let stderr = child.stderr.take().expect("stderr is piped");
+    let pending: Pending = Arc::new(Mutex::new(Some(HashMap::new())));
+    spawn_dispatcher(stdout, pending.clone());
+    let stderr_tail = StderrTail::default();
+    let log_reader = spawn_log_reader(stderr, log_sink, stderr_tail.clone());
+
+    Ok(Self {
+      pid: child.id(),
+      child: Mutex::new(child),
+      stdin: Mutex::new(stdin),
+      pending,
+      stderr_tail,
+      log_reader,
+    })
+  }
+
+  pub fn pid(&self) -> u32 {
+    self.pid
+  }
+
+  /// Also reaps the child once it has exited, so a bridge that died on its own is no zombie.
+  pub fn is_running(&self) -> bool {
+    matches!(self.child.lock().unwrap().try_wait(), Ok(None))
+  }
+
+  pub fn request(
+    &self,
+    id: u64,
+    action: &str,
+    payload: &Value,
+    timeout: Duration,
+  ) -> Result<Value, BridgeError> {
+    self.exchange(id, action, payload, timeout, None)
+  }
+
+  /// Like `request`, but a list answer arrives record by record and goes to `on_record` as each
+  /// line is read, so it is never held whole. `timeout` counts from the last line received.
+  pub fn request_stream(
+    &self,
+    id: u64,
+    action: &str,
+    payload: &Value,
+    timeout: Duration,
+    on_record: &mut dyn FnMut(Value),
+  ) -> Result<Value, BridgeError> {
+    self.exchange(id, action, payload, timeout, Some(on_record))
+  }
+
+  fn exchange(
+    &self,
+    id: u64,
+    action: &str,
+    payload: &Value,
+    timeout: Duration,
+    mut on_record: Option<&mut dyn FnMut(Value)>,
+  ) -> Result<Value, BridgeError> {
+    let line = serde_json::to_string(&RpcRequest {
+      id,
+      action,
+      payload,
+      stream: on_record.is_some(),
+    })
+    .map_err(|error| BridgeError::InvalidJson(error.to_string()))?;
+
+    let (sender, receiver) = mpsc::channel();
+    match self.pending.lock().unwrap().as_mut() {
+      Some(pending) => pending.insert(id, sender),
+      None => return Err(BridgeError::EmptyResponse),
+    };
+
+    let written = {
+      let mut stdin = self.stdin.lock().unwrap();
+      writeln!(stdin, "{}", line).and_then(|_| stdin.flush())
+    };
+    if written.is_err() {
+      // A closed stdin means the bridge is gone.
+      self.forget(id);
+      return Err(self.exit_error());
+    }
+
+    loop {
+      match receiver.recv_timeout(timeout) {
+        Ok(Frame::Record(record)) => {
+          if let Some(on_record) = on_record.as_mut() {
+            on_record(record);
+          }
+        }
+        Ok(Frame::Reply(reply)) => return reply?.into_result(),
+        Err(RecvTimeoutError::Timeout) => {
+          // Only this request gives up: the others may still be answered. A bridge left with
+          // nothing in flight is presumed hung, and the next call spawns a fresh one.
+          if self.forget(id) == 0 {
+            self.kill();
+          }
+          return Err(BridgeError::Timeout(timeout));
+        }
+        Err(RecvTimeoutError::Disconnected) => return Err(self.exit_error()),
+      }
+    }
+  }
+
+  /// Waits up to `EXIT_GRACE` for the exit status and the rest of stderr of a bridge whose
+  /// stdout closed.
+  fn exit_error(&self) -> BridgeError {
+    let deadline = Instant::now() + EXIT_GRACE;
+    let status = loop {
+      match self.child.lock().unwrap().try_wait() {
+        Ok(Some(status)) => break Some(status),
+        Ok(None) if Instant::now() < deadline => {}
+        _ => break None,
+      }
+      thread::sleep(Duration::from_millis(10));
+    };
+    // xray subprocesses can hold stderr open past the bridge's exit, so don't wait forever.
+    while !self.log_reader.is_finished() && Instant::now() < deadline {
+      thread::sleep(Duration::from_millis(10));
+    }
+    let stderr = Vec::from(self.stderr_tail.lock().unwrap().clone()).join("\n");
+    exit_error(status, stderr)
+  }
+
+  /// Fails every request still waiting with `Cancelled`, then kills the process.
+  pub fn cancel(&self) {
+    if let Some(pending) = self.pending.lock().unwrap().take() {
+      for sender in pending.into_values() {
+        let _ = sender.send(Frame::Reply(Err(BridgeError::Cancelled)));
+      }
+    }
+    self.kill();
+  }
+
+  pub fn kill(&self) {
+    let mut child = self.child.lock().unwrap();
+    let _ = child.kill();
+    let _ = child.wait();
+  }
+
+  /// Returns how many requests are still waiting.
+  fn forget(&self, id: u64) -> usize {
+    match self.pending.lock().unwrap().as_mut() {
+      Some(pending) => {
+        pending.remove(&id);
+        pending.len()
+      }
+      None => 0,
+    }
+  }
+}
+
+impl Drop for BridgeProcess {
+  fn drop(&mut self) {
+    self.kill();
+  }
+}
+
+fn spawn_dispatcher(stdout: ChildStdout, pending: Pending) {
+  thread::spawn(move || {
+    let mut reader = BufReader::new(stdout);
+    let mut buffer = Vec::new();
+    loop {
+      buffer.clear();
+      match reader.read_until(b'\n', &mut buffer) {
+        Ok(0) | Err(_) => break,
+        Ok(_) => {}
+      }
+      // xray subprocesses can leave binary noise on the shared stdout; it must not cost the
+      // reply sharing its line.
+      let line = String::from_utf8_lossy(&buffer);
+      // Anything without a request id (stray console output) is ignored.
+      let Some(value) =
+        first_json_object(&line).and_then(|json| serde_json::from_str::<Value>(json).ok())
+      else {
+        continue;
+      };
+      let Some(id) = value.get("id").and_then(Value::as_u64) else {
+        continue;
+      };
+
+      if let Some(record) = value.get("record") {
+        let sender = pending
+          .lock()
+          .unwrap()
+          .as_ref()
+          .and_then(|pending| pending.get(&id).cloned());
+        if let Some(sender) = sender {
+          let _ = sender.send(Frame::Record(record.clone()));
+        }
+        continue;
+      }
+
+      let sender = pending
+        .lock()
+        .unwrap()
+        .as_mut()
+        .and_then(|pending| pending.remove(&id));
+      if let Some(sender) = sender {
+        let reply = serde_json::from_value::<RpcReply>(value)
+          .map_err(|error| BridgeError::InvalidJson(error.to_string()));
+        let _ = sender.send(Frame::Reply(reply));
+      }
+    }
+
+    // Dropping the senders wakes every waiting request with a disconnect.
+    pending.lock().unwrap().take();
+  });
+}
+
+/// The first balanced `{ ... }` in `text`, skipping braces inside JSON strings.
+fn first_json_object(text: &str) -> Option<&str> {
+  let start = text.find('{')?;
+  let mut depth = 0usize;
+  let mut in_string = false;
+  let mut escaped = false;
+
+  for (offset, ch) in text[start..].char_indices() {
+    if in_string {
+      match ch {
+        _ if escaped => escaped = false,
+        '\\' => escaped = true,
+        '"' => in_string = false,
+        _ => {}
+      }
+      continue;
+    }
+
+    match ch {
+      '"' => in_string = true,
+      '{' => depth += 1,
+      '}' => {
+        depth -= 1;
+        if depth == 0 {
+          return Some(&text[start..start + offset + 1]);
+        }
+      }
+      _ => {}
+    }
+  }
+
+  None
+}
+
+fn spawn_log_reader(
+  stderr: ChildStderr,
+  log_sink: Option<LogSink>,
+  tail: StderrTail,
+) -> JoinHandle<()> {
+  thread::spawn(move || {
+    let mut reader = BufReader::new(stderr);
+    let mut buffer = Vec::new();
+    loop {
+      buffer.clear();
+      match reader.read_until(b'\n', &mut buffer) {
+        Ok(0) | Err(_) => break,
+        Ok(_) => {}
+      }
+      // xray subprocesses may print non-UTF-8 bytes; a log line is still useful without them.
+      let line = String::from_utf8_lossy(&buffer);
+      let line = line.trim_end();
+      {
+        let mut tail = tail.lock().unwrap();
+        if tail.len() == STDERR_TAIL {
+          tail.pop_front();
+        }
+        tail.push_back(line.to_string());
+      }
+      match &log_sink {
+        Some(sink) => sink(line),
+        None => eprintln!("{}", line),
+      }
+    }
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  /// Fails with `errors` in order, then hands out a value.
+  fn shim(errors: Vec<io::Error>) -> impl FnMut() -> io::Result<&'static str> {
+    let mut errors = errors.into_iter();
+    move || match errors.next() {
+      Some(error) => Err(error),
+      None => Ok("child"),
+    }
+  }
+
+  fn eagain() -> io::Error {
+    io::Error::from(io::ErrorKind::WouldBlock)
+  }
+
+  #[test]
+  fn transient_spawn_failures_are_retried_with_backoff() {
+    let mut retries = Vec::new();
+
+    let spawned = retry_spawn(3, shim(vec![eagain(), eagain()]), |attempt, _, delay| {
+      retries.push((attempt, delay))
+    });
+
+    assert_eq!(spawned.unwrap(), "child");
+    assert_eq!(
+      retries,
+      [
+        (1, Duration::from_millis(50)),
+        (2, Duration::from_millis(200))
+      ]
+    );
+  }
+
+  #[test]
+  fn permanent_spawn_failures_are_not_retried() {
+    let mut retried = 0;
+
+    let spawned = retry_spawn(
+      3,
+      shim(vec![io::Error::from(io::ErrorKind::NotFound)]),
+      |_, _, _| retried += 1,
+    );
+
+    assert_eq!(spawned.unwrap_err().kind(), io::ErrorKind::NotFound);
+    assert_eq!(retried, 0);
+  }
+
+  #[test]
+  fn gives_up_after_the_configured_retries() {
+    let mut retried = 0;
+
+    let spawned = retry_spawn(1, shim(vec![eagain(), eagain()]), |_, _, _| retried += 1);
+
+    assert_eq!(spawned.unwrap_err().kind(), io::ErrorKind::WouldBlock);
+    assert_eq!(retried, 1);
+  }
+
+  #[cfg(unix)]
+  #[test]
+  fn too_many_open_files_is_transient() {
+    assert!(is_transient(&io::Error::from_raw_os_error(24)));
+    assert!(!is_transient(&io::Error::from_raw_os_error(13)));
+    assert_eq!(spawn_backoff(3), Duration::from_millis(800));
+    assert_eq!(spawn_backoff(7), Duration::from_millis(800));
+  }
+
+  #[test]
+  fn extracts_the_first_json_object() {
+    assert_eq!(
+      first_json_object("{\"id\":1,\"ok\":true}\u{fffd}\u{fffd} trailing"),
+      Some("{\"id\":1,\"ok\":true}")
+    );
+    assert_eq!(
+      first_json_object("xray: {\"id\":2,\"data\":{\"a\":{}}} {\"id\":3}"),
+      Some("{\"id\":2,\"data\":{\"a\":{}}}")
+    );
+  }
+
+  #[test]
+  fn ignores_braces_inside_strings() {
+    assert_eq!(
+      first_json_object(r#"{"id":4,"error":"bad } brace \" {"}tail"#),
+      Some(r#"{"id":4,"error":"bad } brace \" {"}"#)
+    );
+  }
+
+  #[test]
+  fn incomplete_object_is_none() {
+    assert_eq!(first_json_object("{\"id\":5,"), None);
+    assert_eq!(first_json_object("no json here"), None);
+  }
+}
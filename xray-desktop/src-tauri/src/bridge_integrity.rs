@@ -0,0 +1,112 @@
+use crate::bridge::{self, BridgeCommand};
+use crate::error::BridgeError;
+use sha2::{Digest, Sha256};
+use std::path::Path;
+use tauri::{AppHandle, Manager};
+
+/// SHA-256 of `desktop-bridge.cjs` as it was when this binary was built.
+pub const EXPECTED_SHA256: &str = env!("BRIDGE_SHA256");
+
+pub fn sha256_hex(bytes: &[u8]) -> String {
+  Sha256::digest(bytes)
+    .iter()
+    .map(|byte| format!("{byte:02x}"))
+    .collect()
+}
+
+/// Fails with `BridgeTampered` unless `script` hashes to `expected`; an unreadable script counts
+/// as a mismatch too.
+pub fn verify(script: &Path, expected: &str) -> Result<(), BridgeError> {
+  let got = match std::fs::read(script) {
+    Ok(contents) => sha256_hex(&contents),
+    Err(error) => format!("unreadable ({error})"),
+  };
+  if got == expected {
+    Ok(())
+  } else {
+    Err(BridgeError::BridgeTampered {
+      expected: expected.to_string(),
+      got,
+    })
+  }
+}
+
+/// Run before the first spawn. With `enforce` off (`verifyBridge: false`, for working on the
+/// bridge) a mismatch is only logged; an overridden script isn't checked at all.
+pub fn check(command: BridgeCommand, enforce: bool) -> Result<BridgeCommand, BridgeError> {
+  if command.is_overridden() {
+    return Ok(command);
+  }
+  match verify(command.script(), EXPECTED_SHA256) {
+    Ok(()) => Ok(command),
+    Err(error) if !enforce => {
+      tracing::warn!(script = %command.script().display(), "running anyway: {error}");
+      Ok(command)
+    }
+    Err(error) => {
+      tracing::error!(script = %command.script().display(), "{error}");
+      Err(error)
+    }
+  }
+}
+
+/// Checks the script the app runs, whatever `verifyBridge` says.
+#[allow(non_snake_case)]
+#[tauri::command]
+pub fn verifyBridge(app: AppHandle) -> Result<(), BridgeError> {
+  let resource_dir = app.path().resource_dir().ok();
+  verify(
+    &bridge::resolve_bridge_script(resource_dir.as_deref()),
+    EXPECTED_SHA256,
+  )
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn hashes_as_sha256() {
+    assert_eq!(
+      sha256_hex(b"abc"),
+      "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+    );
+  }
+
+  #[test]
+  fn the_bundled_script_matches_this_build() {
+    assert_eq!(
+      verify(&bridge::resolve_bridge_script(None), EXPECTED_SHA256),
+      Ok(())
+    );
+  }
+
+  #[test]
+  fn a_changed_script_is_refused_unless_enforcement_is_off() {
+    let dir = tempfile::tempdir().unwrap();
+    let script = dir.path().join("desktop-bridge.cjs");
+    std::fs::write(&script, "console.log('pwned');\n").unwrap();
+    let got = sha256_hex(b"console.log('pwned');\n");
+
+    assert_eq!(verify(&script, &got), Ok(()));
+    assert_eq!(
+      verify(&script, EXPECTED_SHA256),
+      Err(BridgeError::BridgeTampered {
+        expected: EXPECTED_SHA256.to_string(),
+        got,
+      })
+    );
+    assert!(matches!(
+      check(BridgeCommand::new("node", &script), true),
+      Err(BridgeError::BridgeTampered { .. })
+    ));
+    assert!(check(BridgeCommand::new("node", &script), false).is_ok());
+    let overridden = BridgeCommand::new("node", "unused").with_script_override(Some(script));
+    assert!(check(overridden, true).is_ok());
+
+    assert!(matches!(
+      verify(&dir.path().join("missing.cjs"), EXPECTED_SHA256),
+      Err(BridgeError::BridgeTampered { got, .. }) if got.starts_with("unreadable")
+    ));
+  }
+}
@@ -0,0 +1,134 @@
+use serde::Serialize;
+use std::future::Future;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use tauri::async_runtime::JoinHandle;
+use tokio::sync::Semaphore;
+
+/// One cap shared by every fan-out over servers (`pingAll`, `batchUpdateDisguise`), so running
+/// several at once still leaves the bridge with at most `limit` calls of theirs.
+#[derive(Clone)]
+pub struct BridgePool {
+  permits: Arc<Semaphore>,
+  limit: usize,
+  in_flight: Arc<AtomicUsize>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PoolStatus {
+  pub limit: usize,
+  pub in_flight: usize,
+}
+
+/// `configured` from settings, else one per CPU.
+pub fn limit_for(configured: Option<usize>) -> usize {
+  configured
+    .unwrap_or_else(|| std::thread::available_parallelism().map_or(4, |cpus| cpus.get()))
+    .max(1)
+}
+
+impl Default for BridgePool {
+  fn default() -> Self {
+    Self::new(limit_for(None))
+  }
+}
+
+impl BridgePool {
+  pub fn new(limit: usize) -> Self {
+    let limit = limit.max(1);
+    Self {
+      permits: Arc::new(Semaphore::new(limit)),
+      limit,
+      in_flight: Arc::new(AtomicUsize::new(0)),
+    }
+  }
+
+  pub fn status(&self) -> PoolStatus {
+    PoolStatus {
+      limit: self.limit,
+      in_flight: self.in_flight.load(Ordering::SeqCst),
+    }
+  }
+
+  /// Runs `work` once a slot is free, on its own task.
+  pub fn spawn<T: Send + 'static>(
+    &self,
+    work: impl Future<Output = T> + Send + 'static,
+  ) -> JoinHandle<T> {
+    let pool = self.clone();
+    tauri::async_runtime::spawn(async move {
+      let _permit = pool.permits.acquire().await;
+      let _counted = InFlight::enter(&pool.in_flight);
+      work.await
+    })
+  }
+}
+
+/// Counts a task as in flight until it finishes or is aborted.
+struct InFlight<'a>(&'a AtomicUsize);
+
+impl<'a> InFlight<'a> {
+  fn enter(counter: &'a AtomicUsize) -> Self {
+    counter.fetch_add(1, Ordering::SeqCst);
+    Self(counter)
+  }
+}
+
+impl Drop for InFlight<'_> {
+  fn drop(&mut self) {
+    self.0.fetch_sub(1, Ordering::SeqCst);
+  }
+}
+
+#[allow(non_snake_case)]
+#[tauri::command]
+pub fn getBridgePoolStatus(pool: tauri::State<'_, BridgePool>) -> PoolStatus {
+  pool.status()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::time::Duration;
+  use tauri::async_runtime::block_on;
+
+  #[test]
+  fn caps_concurrency_when_many_tasks_arrive_at_once() {
+    let pool = BridgePool::new(3);
+    let max_seen = Arc::new(AtomicUsize::new(0));
+
+    let tasks: Vec<_> = (0..40)
+      .map(|_| {
+        let (pool_view, max_seen) = (pool.clone(), max_seen.clone());
+        pool.spawn(async move {
+          max_seen.fetch_max(pool_view.status().in_flight, Ordering::SeqCst);
+          tokio::time::sleep(Duration::from_millis(5)).await;
+        })
+      })
+      .collect();
+    block_on(async {
+      for task in tasks {
+        task.await.unwrap();
+      }
+    });
+
+    assert_eq!(max_seen.load(Ordering::SeqCst), 3);
+    assert_eq!(
+      pool.status(),
+      PoolStatus {
+        limit: 3,
+        in_flight: 0
+      }
+    );
+  }
+
+  #[test]
+  fn limit_defaults_to_the_cpu_count_and_is_never_zero() {
+    let cpus = std::thread::available_parallelism().unwrap().get();
+    assert_eq!(limit_for(None), cpus);
+    assert_eq!(limit_for(Some(6)), 6);
+    assert_eq!(limit_for(Some(0)), 1);
+    assert_eq!(BridgePool::new(0).status().limit, 1);
+  }
+}
@@ -0,0 +1,191 @@
+use crate::bridge::Bridge;
+use crate::error::BridgeError;
+use crate::hotkey::Hotkeys;
+use crate::tray::TRAY_ID;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tauri::{AppHandle, State};
+
+/// What the UI may offer. A feature the agent implements still needs the OS side, so the
+/// bridge-backed flags are both at once.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Capabilities {
+  pub kill_switch: bool,
+  pub split_tunnel: bool,
+  pub per_app: bool,
+  pub ipv6_control: bool,
+  pub mtu: bool,
+  pub dns: bool,
+  pub bandwidth_limit: bool,
+  pub speed_test: bool,
+  pub routes: bool,
+  pub core_config: bool,
+  pub tray: bool,
+  pub global_hotkey: bool,
+  pub autostart: bool,
+}
+
+/// The availability this build has on its own, before asking the bridge.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Platform {
+  pub kill_switch: bool,
+  pub split_tunnel: bool,
+  pub per_app: bool,
+  pub ipv6_control: bool,
+  pub mtu: bool,
+  pub autostart: bool,
+  /// Runtime facts: some Linux desktops have no tray, some builds no shortcut backend.
+  pub tray: bool,
+  pub global_hotkey: bool,
+}
+
+const DESKTOP: bool = cfg!(any(
+  target_os = "windows",
+  target_os = "macos",
+  target_os = "linux"
+));
+
+impl Platform {
+  pub fn current(tray: bool, global_hotkey: bool) -> Self {
+    Self {
+      kill_switch: DESKTOP,
+      split_tunnel: DESKTOP,
+      // A TUN client can't attribute flows to apps on macOS without a network extension.
+      per_app: cfg!(any(target_os = "windows", target_os = "linux")),
+      ipv6_control: DESKTOP,
+      mtu: DESKTOP,
+      autostart: DESKTOP,
+      tray,
+      global_hotkey,
+    }
+  }
+}
+
+/// The bridge's `capabilities` answer; a flag it doesn't send counts as unsupported.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct BridgeSupport {
+  pub kill_switch: bool,
+  pub split_tunnel: bool,
+  pub per_app: bool,
+  pub ipv6_control: bool,
+  pub mtu: bool,
+  pub dns: bool,
+  pub bandwidth_limit: bool,
+  pub speed_test: bool,
+  pub routes: bool,
+  pub core_config: bool,
+}
+
+pub fn merge(platform: Platform, bridge: BridgeSupport) -> Capabilities {
+  Capabilities {
+    kill_switch: platform.kill_switch && bridge.kill_switch,
+    split_tunnel: platform.split_tunnel && bridge.split_tunnel,
+    per_app: platform.per_app && bridge.per_app,
+    ipv6_control: platform.ipv6_control && bridge.ipv6_control,
+    mtu: platform.mtu && bridge.mtu,
+    dns: bridge.dns,
+    bandwidth_limit: bridge.bandwidth_limit,
+    speed_test: bridge.speed_test,
+    routes: bridge.routes,
+    core_config: bridge.core_config,
+    tray: platform.tray,
+    global_hotkey: platform.global_hotkey,
+    autostart: platform.autostart,
+  }
+}
+
+pub async fn probe(bridge: &Bridge) -> Result<BridgeSupport, BridgeError> {
+  let reply = bridge.call("capabilities", Value::Null).await?;
+  serde_json::from_value(reply).map_err(|error| BridgeError::InvalidJson(error.to_string()))
+}
+
+pub async fn get(bridge: &Bridge, platform: Platform) -> Result<Capabilities, BridgeError> {
+  Ok(merge(platform, probe(bridge).await?))
+}
+
+#[allow(non_snake_case)]
+#[tauri::command]
+pub async fn getCapabilities(
+  app: AppHandle,
+  bridge: State<'_, Bridge>,
+  hotkeys: State<'_, Hotkeys>,
+) -> Result<Capabilities, BridgeError> {
+  let platform = Platform::current(app.tray_by_id(TRAY_ID).is_some(), hotkeys.is_supported());
+  get(&bridge, platform).await
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::bridge::mock::MockBridgeRunner;
+  use serde_json::json;
+  use tauri::async_runtime::block_on;
+
+  fn everything() -> Platform {
+    Platform {
+      kill_switch: true,
+      split_tunnel: true,
+      per_app: true,
+      ipv6_control: true,
+      mtu: true,
+      autostart: true,
+      tray: true,
+      global_hotkey: true,
+    }
+  }
+
+  #[test]
+  fn bridge_backed_features_need_both_sides() {
+    let bridge = Bridge::new(MockBridgeRunner::default().respond(
+      "capabilities",
+      Ok(json!({ "killSwitch": true, "perApp": true, "mtu": false, "dns": true })),
+    ));
+    let platform = Platform {
+      per_app: false,
+      ..everything()
+    };
+
+    let capabilities = block_on(get(&bridge, platform)).unwrap();
+
+    // Built in and supported by the agent.
+    assert!(capabilities.kill_switch);
+    assert!(capabilities.dns);
+    // The agent has it, this OS doesn't.
+    assert!(!capabilities.per_app);
+    // This OS has it, the agent doesn't (or didn't say).
+    assert!(!capabilities.mtu);
+    assert!(!capabilities.split_tunnel);
+    // Nothing to ask the bridge about.
+    assert!(capabilities.tray);
+    assert!(capabilities.global_hotkey);
+  }
+
+  #[test]
+  fn app_side_features_ignore_the_bridge() {
+    let platform = Platform {
+      tray: false,
+      global_hotkey: false,
+      ..everything()
+    };
+
+    let capabilities = merge(platform, BridgeSupport::default());
+
+    assert!(!capabilities.tray);
+    assert!(!capabilities.global_hotkey);
+    assert!(capabilities.autostart);
+    assert!(!capabilities.kill_switch);
+  }
+
+  #[test]
+  fn malformed_probe_is_invalid_json() {
+    let bridge = Bridge::new(
+      MockBridgeRunner::default().respond("capabilities", Ok(json!({ "killSwitch": "yes" }))),
+    );
+
+    let error = block_on(probe(&bridge)).unwrap_err();
+
+    assert!(matches!(error, BridgeError::InvalidJson(_)));
+  }
+}
@@ -0,0 +1,193 @@
+// Headless mode: `xray-desktop --connect | --disconnect | --status | --mode <proxy|vpn>` runs one
+// bridge action, prints its JSON result and exits without opening a window.
+
+use crate::bridge::{Bridge, BridgeCommand, NodeBridgeRunner};
+use crate::bridge_integrity;
+use crate::commands;
+use crate::config::Config;
+use crate::error::BridgeError;
+use crate::mode::Mode;
+use crate::profiles;
+use crate::reconnect::Watchdog;
+use crate::secrets::Secrets;
+use serde_json::Value;
+use std::path::PathBuf;
+use tauri::async_runtime::block_on;
+
+pub const EXIT_OK: i32 = 0;
+/// The bridge action ran and failed.
+pub const EXIT_FAILED: i32 = 1;
+pub const EXIT_USAGE: i32 = 2;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum CliCommand {
+  Connect,
+  Disconnect,
+  Status,
+  SetMode(Mode),
+}
+
+impl CliCommand {
+  pub fn action(&self) -> &'static str {
+    match self {
+      Self::Connect => "connect",
+      Self::Disconnect => "disconnect",
+      Self::Status => "status",
+      Self::SetMode(_) => "setMode",
+    }
+  }
+}
+
+/// `Ok(None)` means "launch the GUI": no CLI flag was given, and anything else on the command
+/// line (a deep link, OS-added flags) is left for the app.
+pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Option<CliCommand>, String> {
+  let mut args = args.into_iter().skip(1);
+  let mut found = None;
+
+  while let Some(arg) = args.next() {
+    let command = match arg.as_str() {
+      "--connect" => CliCommand::Connect,
+      "--disconnect" => CliCommand::Disconnect,
+      "--status" => CliCommand::Status,
+      "--mode" => {
+        let value = args
+          .next()
+          .ok_or_else(|| "--mode needs a value: proxy or vpn".to_string())?;
+        CliCommand::SetMode(
+          value
+            .parse()
+            .map_err(|error: BridgeError| error.to_string())?,
+        )
+      }
+      _ => continue,
+    };
+    if found.is_some() {
+      return Err("only one of --connect, --disconnect, --status, --mode can be given".to_string());
+    }
+    found = Some(command);
+  }
+
+  Ok(found)
+}
+
+/// Runs `command` against a bridge of its own and returns the process exit code.
+pub fn run(command: CliCommand, context: &tauri::Context) -> i32 {
+  let config_dir = dirs::config_dir()
+    .unwrap_or_default()
+    .join(&context.config().identifier);
+  let config = Config::in_dir(&config_dir);
+  let resource_dir =
+    tauri::utils::platform::resource_dir(context.package_info(), &tauri::Env::default()).ok();
+  let node_path = config.get().node_path.map(PathBuf::from);
+
+  let script_path = config.get().bridge_script_path.map(PathBuf::from);
+  let runner = match BridgeCommand::resolve(
+    resource_dir.as_deref(),
+    node_path.as_deref(),
+    script_path.as_deref(),
+  )
+  .and_then(|command| bridge_integrity::check(command, config.get().verify_bridge))
+  {
+    Ok(bridge_command) => NodeBridgeRunner::new(bridge_command),
+    Err(error) => return report(&command, Err(error)),
+  };
+  runner.set_spawn_retries(config.get().bridge_spawn_retries);
+  runner.set_proxy_env(profiles::proxy_env(&config.get()));
+  runner.set_options(config.get().bridge_env);
+  let bridge = Bridge::new(runner.clone());
+  let result = block_on(execute(&bridge, &config, &command));
+  runner.stop();
+  report(&command, result)
+}
+
+async fn execute(
+  bridge: &Bridge,
+  config: &Config,
+  command: &CliCommand,
+) -> Result<Value, BridgeError> {
+  match command {
+    CliCommand::Connect => {
+      let watchdog = Watchdog::default();
+      commands::connect(bridge, &Secrets::keyring(), config, &watchdog, None).await
+    }
+    CliCommand::Disconnect => commands::disconnect(bridge, &Watchdog::default())
+      .await
+      .map(|()| Value::Null),
+    CliCommand::Status => commands::status(bridge)
+      .await
+      .map(|snapshot| serde_json::to_value(snapshot).unwrap_or_default()),
+    CliCommand::SetMode(mode) => commands::set_mode(bridge, config, mode.as_str()).await,
+  }
+}
+
+/// Prints the result (or the `{ kind, message }` error) as JSON on stdout. Failures are also
+/// summed up on stderr for people running it by hand.
+fn report(command: &CliCommand, result: Result<Value, BridgeError>) -> i32 {
+  match result {
+    Ok(value) => {
+      println!("{}", value);
+      EXIT_OK
+    }
+    Err(error) => {
+      eprintln!("xray-desktop: {} failed: {}", command.action(), error);
+      println!("{}", serde_json::to_value(&error).unwrap_or_default());
+      EXIT_FAILED
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn parsed(args: &[&str]) -> Result<Option<CliCommand>, String> {
+    parse(
+      std::iter::once("xray-desktop")
+        .chain(args.iter().copied())
+        .map(String::from),
+    )
+  }
+
+  #[test]
+  fn maps_flags_to_bridge_actions() {
+    let cases = [
+      (&["--connect"][..], "connect"),
+      (&["--disconnect"], "disconnect"),
+      (&["--status"], "status"),
+      (&["--mode", "vpn"], "setMode"),
+    ];
+
+    for (args, action) in cases {
+      assert_eq!(parsed(args).unwrap().unwrap().action(), action, "{args:?}");
+    }
+    assert_eq!(
+      parsed(&["--mode", "proxy"]),
+      Ok(Some(CliCommand::SetMode(Mode::Proxy)))
+    );
+  }
+
+  #[test]
+  fn no_flag_launches_the_gui() {
+    assert_eq!(parsed(&[]), Ok(None));
+    assert_eq!(
+      parsed(&["xraycp://import?baseUrl=https%3A%2F%2Fcp.example.com&token=abcDEF123_token_value"]),
+      Ok(None)
+    );
+    assert_eq!(parsed(&["--minimized"]), Ok(None));
+  }
+
+  #[test]
+  fn program_name_is_not_a_flag() {
+    assert_eq!(parse(["--status".to_string()]), Ok(None));
+  }
+
+  #[test]
+  fn rejects_bad_usage() {
+    assert!(parsed(&["--mode"]).is_err());
+    assert_eq!(
+      parsed(&["--mode", "prox"]),
+      Err("Invalid mode \"prox\", expected one of: proxy, vpn".to_string())
+    );
+    assert!(parsed(&["--connect", "--status"]).is_err());
+  }
+}
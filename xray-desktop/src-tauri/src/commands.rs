@@ -0,0 +1,1303 @@
+// Bodies of the bridge-facing IPC commands in `main.rs`, taking plain references so tests can
+// drive them with `MockBridgeRunner`.
+
+use crate::base_url::normalize_base_url;
+use crate::bridge::Bridge;
+use crate::config::{Config, Settings};
+use crate::error::BridgeError;
+use crate::external_import;
+use crate::favorites;
+use crate::mode::Mode;
+use crate::privileges;
+use crate::profiles;
+use crate::reconnect::Watchdog;
+use crate::secrets::{Secrets, StoredToken};
+use crate::status::{LinkState, StatusSnapshot};
+use crate::transport::Transport;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tauri::{AppHandle, Emitter, Manager};
+
+pub const AUTH_REQUIRED_EVENT: &str = "vpn://auth-required";
+
+/// Payload of `vpn://auth-required`: what the re-import prompt can be prefilled with.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AuthRequired {
+  /// `authExpired` or `authInvalid`.
+  pub kind: &'static str,
+  pub base_url: Option<String>,
+  /// Set when the rejected token was a profile's.
+  pub profile_id: Option<String>,
+}
+
+/// The `vpn://auth-required` payload for a failed bridge call, if only a new token fixes it.
+pub fn auth_required(error: &BridgeError, settings: &Settings) -> Option<AuthRequired> {
+  let error = error.clone().with_auth_code();
+  if !error.needs_reimport() {
+    return None;
+  }
+  let profile = profiles::active(settings);
+  Some(AuthRequired {
+    kind: error.kind(),
+    base_url: profile
+      .map(|profile| profile.base_url.clone())
+      .or_else(|| settings.default_base_url.clone()),
+    profile_id: profile.map(|profile| profile.id.clone()),
+  })
+}
+
+/// Called for every failed bridge call, so the tray, the watchdog and scheduled connects prompt
+/// for a re-import the same way the window does.
+pub fn announce_auth_required(app: &AppHandle, error: &BridgeError) {
+  let Some(config) = app.try_state::<Config>() else {
+    return;
+  };
+  if let Some(payload) = auth_required(error, &config.get()) {
+    let _ = app.emit(AUTH_REQUIRED_EVENT, payload);
+  }
+}
+
+pub async fn import_token(
+  bridge: &Bridge,
+  secrets: &Secrets,
+  config: &Config,
+  base_url: String,
+  token: String,
+) -> Result<(), BridgeError> {
+  let base_url = normalize_base_url(&base_url, config.get().allow_insecure_base_url)?;
+  bridge
+    .call(
+      "importToken",
+      json!({ "baseUrl": base_url, "token": token }),
+    )
+    .await?;
+  let _ = config.update(|settings| settings.default_base_url = Some(base_url.clone()));
+  // The agent already holds the imported config, so a missing keyring only costs re-import.
+  let _ = secrets.store_token(&StoredToken {
+    base_url,
+    token,
+    expires_at: None,
+  });
+  Ok(())
+}
+
+/// One-shot target for `connectWith`; `remember` saves the given fields as the new defaults.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ConnectOptions {
+  pub base_url: Option<String>,
+  pub server_id: Option<String>,
+  pub mode: Option<Mode>,
+  pub remember: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct ConnectTarget {
+  base_url: Option<String>,
+  server_id: Option<String>,
+  mode: Option<Mode>,
+}
+
+/// Each field left out of `options` falls back to the stored state and then to the config
+/// default. `stored_base_url` is the one saved with the token (or the active profile's), which
+/// beats `default_base_url`.
+fn resolve(
+  options: &ConnectOptions,
+  settings: &Settings,
+  stored_base_url: Option<String>,
+) -> Result<ConnectTarget, BridgeError> {
+  let base_url = match &options.base_url {
+    Some(url) => Some(normalize_base_url(url, settings.allow_insecure_base_url)?),
+    None => stored_base_url.or_else(|| settings.default_base_url.clone()),
+  };
+  Ok(ConnectTarget {
+    base_url,
+    server_id: options
+      .server_id
+      .clone()
+      .or_else(|| settings.selected_server_id.clone()),
+    mode: options.mode.or(settings.last_mode),
+  })
+}
+
+/// Connects to `server_id`, or to the server picked with `selectServer` when none is given, and
+/// moves it to the front of the recent servers once the tunnel is up.
+/// Credentials come from the active profile, or the `storeToken` token without one.
+pub async fn connect(
+  bridge: &Bridge,
+  secrets: &Secrets,
+  config: &Config,
+  watchdog: &Watchdog,
+  server_id: Option<String>,
+) -> Result<Value, BridgeError> {
+  let options = ConnectOptions {
+    server_id,
+    ..ConnectOptions::default()
+  };
+  connect_with(bridge, secrets, config, watchdog, options).await
+}
+
+pub async fn connect_with(
+  bridge: &Bridge,
+  secrets: &Secrets,
+  config: &Config,
+  watchdog: &Watchdog,
+  options: ConnectOptions,
+) -> Result<Value, BridgeError> {
+  let settings = config.get();
+  let mut payload = match profiles::active(&settings) {
+    Some(profile) => {
+      let mut payload = secrets.connect_payload_at(&profile.token_ref);
+      if payload.is_object() {
+        payload["baseUrl"] = json!(profile.base_url);
+      }
+      payload
+    }
+    None => secrets.connect_payload(),
+  };
+  // Before touching the watchdog, so a bad URL or a forgotten import leaves the current session
+  // alone.
+  let stored_base_url = payload["baseUrl"].as_str().map(str::to_string);
+  let target = resolve(&options, &settings, stored_base_url)?;
+  privileges::require_for(target.mode)?;
+  let server_id = target.server_id;
+  let outbound = match &server_id {
+    Some(server_id) if external_import::is_imported(server_id) => {
+      let outbound = secrets.load_outbound(server_id)?;
+      Some(outbound.ok_or_else(|| BridgeError::UnknownServer {
+        id: server_id.clone(),
+      })?)
+    }
+    _ => None,
+  };
+  watchdog.user_connected();
+  if let Some(base_url) = &target.base_url {
+    payload["baseUrl"] = json!(base_url);
+  }
+  if let Some(mode) = target.mode {
+    payload["mode"] = json!(mode);
+  }
+  match (outbound, &server_id) {
+    (Some(outbound), _) => payload["outbound"] = outbound,
+    (None, Some(server_id)) => payload["serverId"] = json!(server_id),
+    (None, None) => {}
+  }
+  if settings.kill_switch {
+    // The bridge arms it once the tunnel is up; the watchdog's reconnects re-arm it the same way.
+    payload["killSwitch"] = json!(true);
+  }
+  if !settings.split_tunnel.is_empty() {
+    payload["splitTunnel"] = json!(settings.split_tunnel);
+  }
+  if !settings.app_rules.is_empty() {
+    payload["appRules"] = json!(settings.app_rules);
+  }
+  if !settings.dns_servers.is_empty() {
+    payload["dns"] = json!(settings.dns_servers);
+  }
+  if !settings.ipv6_enabled {
+    payload["ipv6"] = json!(false);
+  }
+  if let Some(mtu) = settings.mtu {
+    payload["mtu"] = json!(mtu);
+  }
+  if settings.transport != Transport::Auto {
+    payload["transport"] = json!(settings.transport);
+  }
+  if !settings.bandwidth_limit.is_unlimited() {
+    payload["bandwidth"] = json!(settings.bandwidth_limit);
+  }
+  let result = bridge
+    .call("connect", payload)
+    .await
+    .map_err(BridgeError::with_auth_code);
+  if let Err(error) = &result {
+    if error.needs_reimport() {
+      // The agent would only reject it again. Settings, profiles included, stay as they are.
+      let _ = secrets.clear_token_at(profiles::token_account(&settings));
+    }
+  }
+  let result = result?;
+  if let Some(server_id) = server_id {
+    // The tunnel is up by now; a settings file that won't save shouldn't report it as failed.
+    if let Err(error) = favorites::record_recent(config, &server_id) {
+      tracing::warn!("couldn't record {server_id} as recent: {error}");
+    }
+  }
+  if options.remember {
+    config.update(|settings| {
+      if options.base_url.is_some() {
+        settings.default_base_url = target.base_url;
+      }
+      if options.server_id.is_some() {
+        settings.selected_server_id = options.server_id;
+      }
+      if options.mode.is_some() {
+        settings.last_mode = options.mode;
+      }
+    })?;
+  }
+  Ok(result)
+}
+
+pub async fn set_mode(bridge: &Bridge, config: &Config, mode: &str) -> Result<Value, BridgeError> {
+  // Reject typos here instead of surfacing an opaque bridge error.
+  let mode: Mode = mode.parse()?;
+  let result = bridge.call("setMode", json!({ "mode": mode })).await?;
+  config.update(|settings| settings.last_mode = Some(mode))?;
+  Ok(result)
+}
+
+/// A clean disconnect also lifts the kill switch block; the setting itself stays on.
+pub async fn disconnect(bridge: &Bridge, watchdog: &Watchdog) -> Result<(), BridgeError> {
+  // Before the call, so the watcher already sees the drop as requested.
+  watchdog.user_disconnected();
+  bridge.call("disconnect", Value::Null).await.map(|_| ())
+}
+
+/// Aborts a `connect` still in flight, which fails with `Cancelled`. Returns whether there was
+/// one. The bridge is killed mid-handshake, so a fresh one is asked to `disconnect` whatever the
+/// agent had set up.
+pub async fn cancel_connect(bridge: &Bridge, watchdog: &Watchdog) -> Result<bool, BridgeError> {
+  if !bridge.is_running("connect") {
+    return Ok(false);
+  }
+  watchdog.user_disconnected();
+  bridge.cancel();
+  bridge.call("disconnect", Value::Null).await?;
+  Ok(true)
+}
+
+/// Serializes `toggle_connection` and `reconnect_now` end to end: the bridge queue alone would
+/// let two quick toggles both read "disconnected" and connect twice.
+#[derive(Default)]
+pub struct ToggleLock(tokio::sync::Mutex<()>);
+
+impl ToggleLock {
+  /// For other multi-step operations that must not interleave with a toggle.
+  pub async fn hold(&self) -> tokio::sync::MutexGuard<'_, ()> {
+    self.0.lock().await
+  }
+}
+
+/// A paused tunnel is still up, so toggling it disconnects.
+pub fn toggle_disconnects(snapshot: &StatusSnapshot) -> bool {
+  snapshot.state() != LinkState::Disconnected
+}
+
+/// Disconnects a live or paused tunnel, connects otherwise, and returns the state afterwards.
+pub async fn toggle_connection(
+  bridge: &Bridge,
+  secrets: &Secrets,
+  config: &Config,
+  watchdog: &Watchdog,
+  lock: &ToggleLock,
+) -> Result<StatusSnapshot, BridgeError> {
+  let _toggling = lock.0.lock().await;
+  if toggle_disconnects(&status(bridge).await?) {
+    disconnect(bridge, watchdog).await?;
+  } else {
+    connect(bridge, secrets, config, watchdog, None).await?;
+  }
+  status(bridge).await
+}
+
+/// `{ ok, error }` of one `reconnect_now` step.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StepOutcome {
+  pub ok: bool,
+  pub error: Option<BridgeError>,
+}
+
+impl<T> From<Result<T, BridgeError>> for StepOutcome {
+  fn from(result: Result<T, BridgeError>) -> Self {
+    Self {
+      ok: result.is_ok(),
+      error: result.err(),
+    }
+  }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReconnectReport {
+  pub disconnect: StepOutcome,
+  pub connect: StepOutcome,
+}
+
+/// Disconnects and connects again under `lock`, so no toggle runs in between. The connect is
+/// tried even when the disconnect failed, since a half torn-down tunnel is what this is for;
+/// `before_connect` runs between the two.
+pub async fn reconnect_now(
+  bridge: &Bridge,
+  secrets: &Secrets,
+  config: &Config,
+  watchdog: &Watchdog,
+  lock: &ToggleLock,
+  before_connect: impl FnOnce(),
+) -> ReconnectReport {
+  let _reconnecting = lock.0.lock().await;
+  let disconnected = disconnect(bridge, watchdog).await;
+  before_connect();
+  let connected = connect(bridge, secrets, config, watchdog, None).await;
+  ReconnectReport {
+    disconnect: disconnected.into(),
+    connect: connected.into(),
+  }
+}
+
+/// Keeps the session but stops routing traffic through it, so `resume` needs no new handshake.
+pub async fn pause(bridge: &Bridge) -> Result<(), BridgeError> {
+  bridge.call("pause", Value::Null).await.map(|_| ())
+}
+
+pub async fn resume(bridge: &Bridge) -> Result<(), BridgeError> {
+  bridge.call("resume", Value::Null).await.map(|_| ())
+}
+
+/// `base_url` must already be normalized; the commands run it through `normalize_base_url`.
+pub async fn update_disguise(
+  bridge: &Bridge,
+  base_url: String,
+  server_id: String,
+  admin_api_key: String,
+  disguise: Value,
+) -> Result<Value, BridgeError> {
+  bridge
+    .call(
+      "updateDisguise",
+      json!({
+        "baseUrl": base_url,
+        "serverId": server_id,
+        "adminApiKey": admin_api_key,
+        "disguise": disguise
+      }),
+    )
+    .await
+}
+
+pub async fn status(bridge: &Bridge) -> Result<StatusSnapshot, BridgeError> {
+  StatusSnapshot::from_bridge(bridge.call("status", Value::Null).await?)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::bridge::mock::MockBridgeRunner;
+  use crate::secrets::mock::MemoryStore;
+  use crate::split_tunnel::{SplitKind, SplitRule};
+  use crate::status::LinkState;
+  use std::sync::Arc;
+  use tauri::async_runtime::block_on;
+
+  fn secrets() -> Secrets {
+    Secrets::new(Box::new(MemoryStore::default()))
+  }
+
+  fn config(dir: &tempfile::TempDir) -> Config {
+    Config::load(dir.path().join("settings.json"))
+  }
+
+  #[test]
+  fn connect_sends_the_stored_token() {
+    let mock = MockBridgeRunner::default().respond("connect", Ok(json!({ "connected": true })));
+    let bridge = Bridge::new(mock.clone());
+    let secrets = secrets();
+    secrets
+      .store_token(&StoredToken {
+        base_url: "https://cp.example.com".to_string(),
+        token: "abcDEF123_token_value".to_string(),
+        expires_at: None,
+      })
+      .unwrap();
+
+    let dir = tempfile::tempdir().unwrap();
+    let status = block_on(connect(
+      &bridge,
+      &secrets,
+      &config(&dir),
+      &Watchdog::default(),
+      None,
+    ))
+    .unwrap();
+
+    assert_eq!(status, json!({ "connected": true }));
+    assert_eq!(
+      mock.calls(),
+      [(
+        "connect".to_string(),
+        json!({ "baseUrl": "https://cp.example.com", "token": "abcDEF123_token_value" })
+      )]
+    );
+  }
+
+  #[test]
+  fn connect_surfaces_bridge_errors() {
+    let failure = BridgeError::BridgeReported {
+      code: Some("STARTUP_FAILED".to_string()),
+      message: "STARTUP_FAILED: Supervisor exited during startup".to_string(),
+    };
+    let bridge = Bridge::new(MockBridgeRunner::default().respond("connect", Err(failure.clone())));
+
+    let dir = tempfile::tempdir().unwrap();
+
+    assert_eq!(
+      block_on(connect(
+        &bridge,
+        &secrets(),
+        &config(&dir),
+        &Watchdog::default(),
+        None
+      )),
+      Err(failure)
+    );
+  }
+
+  #[test]
+  fn connect_uses_the_selected_server_unless_one_is_given() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = config(&dir);
+    config
+      .update(|settings| settings.selected_server_id = Some("srv-1".to_string()))
+      .unwrap();
+    let mock = MockBridgeRunner::default().respond("connect", Ok(json!({ "connected": true })));
+    let bridge = Bridge::new(mock.clone());
+    let watchdog = Watchdog::default();
+
+    block_on(connect(&bridge, &secrets(), &config, &watchdog, None)).unwrap();
+    block_on(connect(
+      &bridge,
+      &secrets(),
+      &config,
+      &watchdog,
+      Some("srv-2".to_string()),
+    ))
+    .unwrap();
+
+    assert_eq!(
+      mock.calls(),
+      [
+        ("connect".to_string(), json!({ "serverId": "srv-1" })),
+        ("connect".to_string(), json!({ "serverId": "srv-2" })),
+      ]
+    );
+  }
+
+  #[test]
+  fn explicit_options_beat_stored_state_which_beats_config_defaults() {
+    let mut settings = Settings {
+      default_base_url: Some("https://default.example.com".to_string()),
+      ..Settings::default()
+    };
+    let none = ConnectOptions::default();
+
+    // Only the config default.
+    assert_eq!(
+      resolve(&none, &settings, None).unwrap(),
+      ConnectTarget {
+        base_url: Some("https://default.example.com".to_string()),
+        server_id: None,
+        mode: None,
+      }
+    );
+
+    // Stored state over the default.
+    settings.selected_server_id = Some("srv-1".to_string());
+    settings.last_mode = Some(Mode::Proxy);
+    assert_eq!(
+      resolve(
+        &none,
+        &settings,
+        Some("https://token.example.com".to_string())
+      )
+      .unwrap(),
+      ConnectTarget {
+        base_url: Some("https://token.example.com".to_string()),
+        server_id: Some("srv-1".to_string()),
+        mode: Some(Mode::Proxy),
+      }
+    );
+
+    // Explicit over both.
+    let explicit = ConnectOptions {
+      base_url: Some("cp.example.com/".to_string()),
+      server_id: Some("srv-2".to_string()),
+      mode: Some(Mode::Vpn),
+      remember: false,
+    };
+    assert_eq!(
+      resolve(
+        &explicit,
+        &settings,
+        Some("https://token.example.com".to_string())
+      )
+      .unwrap(),
+      ConnectTarget {
+        base_url: Some("https://cp.example.com".to_string()),
+        server_id: Some("srv-2".to_string()),
+        mode: Some(Mode::Vpn),
+      }
+    );
+  }
+
+  #[test]
+  fn connect_with_rejects_a_bad_base_url_before_calling_the_bridge() {
+    let dir = tempfile::tempdir().unwrap();
+    let mock = MockBridgeRunner::default();
+    let options = ConnectOptions {
+      base_url: Some("http://cp.example.com".to_string()),
+      ..ConnectOptions::default()
+    };
+
+    let result = block_on(connect_with(
+      &Bridge::new(mock.clone()),
+      &secrets(),
+      &config(&dir),
+      &Watchdog::default(),
+      options,
+    ));
+
+    assert!(matches!(result, Err(BridgeError::InvalidBaseUrl(_))));
+    assert!(mock.calls().is_empty());
+  }
+
+  #[test]
+  fn connect_with_only_saves_the_options_when_asked_to() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = config(&dir);
+    config
+      .update(|settings| settings.selected_server_id = Some("srv-1".to_string()))
+      .unwrap();
+    let mock = MockBridgeRunner::default().respond("connect", Ok(json!({ "connected": true })));
+    let bridge = Bridge::new(mock.clone());
+    let watchdog = Watchdog::default();
+    let options = ConnectOptions {
+      server_id: Some("srv-2".to_string()),
+      mode: Some(Mode::Proxy),
+      ..ConnectOptions::default()
+    };
+
+    block_on(connect_with(
+      &bridge,
+      &secrets(),
+      &config,
+      &watchdog,
+      options.clone(),
+    ))
+    .unwrap();
+    assert_eq!(config.get().selected_server_id.as_deref(), Some("srv-1"));
+    assert_eq!(config.get().last_mode, None);
+
+    let remembered = ConnectOptions {
+      remember: true,
+      ..options
+    };
+    block_on(connect_with(
+      &bridge,
+      &secrets(),
+      &config,
+      &watchdog,
+      remembered,
+    ))
+    .unwrap();
+    assert_eq!(config.get().selected_server_id.as_deref(), Some("srv-2"));
+    assert_eq!(config.get().last_mode, Some(Mode::Proxy));
+    assert_eq!(
+      mock.calls()[0],
+      (
+        "connect".to_string(),
+        json!({ "serverId": "srv-2", "mode": "proxy" })
+      )
+    );
+  }
+
+  #[test]
+  fn imported_servers_connect_through_their_stored_outbound() {
+    let dir = tempfile::tempdir().unwrap();
+    let secrets = secrets();
+    let outbound = json!({ "protocol": "trojan", "settings": { "servers": [] } });
+    secrets
+      .store_outbound("ext-0123456789ab", &outbound)
+      .unwrap();
+    let mock = MockBridgeRunner::default().respond("connect", Ok(json!({ "connected": true })));
+    let connect = |server_id: &str| {
+      block_on(connect_with(
+        &Bridge::new(mock.clone()),
+        &secrets,
+        &config(&dir),
+        &Watchdog::default(),
+        ConnectOptions {
+          server_id: Some(server_id.to_string()),
+          ..ConnectOptions::default()
+        },
+      ))
+    };
+
+    connect("ext-0123456789ab").unwrap();
+    let forgotten = connect("ext-ffffffffffff").unwrap_err();
+
+    assert_eq!(
+      mock.calls(),
+      [("connect".to_string(), json!({ "outbound": outbound }))]
+    );
+    assert_eq!(
+      forgotten,
+      BridgeError::UnknownServer {
+        id: "ext-ffffffffffff".to_string()
+      }
+    );
+  }
+
+  #[test]
+  fn a_rejected_token_is_a_typed_error_and_only_the_token_is_cleared() {
+    for (code, expected) in [
+      ("AUTH_EXPIRED", BridgeError::AuthExpired),
+      ("AUTH_INVALID", BridgeError::AuthInvalid),
+    ] {
+      let dir = tempfile::tempdir().unwrap();
+      let config = config(&dir);
+      config
+        .update(|settings| {
+          settings.selected_server_id = Some("srv-1".to_string());
+          settings.default_base_url = Some("https://cp.example.com".to_string());
+        })
+        .unwrap();
+      let secrets = secrets();
+      secrets
+        .store_token(&StoredToken {
+          base_url: "https://cp.example.com".to_string(),
+          token: "abcDEF123_token_value".to_string(),
+          expires_at: None,
+        })
+        .unwrap();
+      let bridge = Bridge::new(MockBridgeRunner::default().respond(
+        "connect",
+        Err(BridgeError::BridgeReported {
+          code: Some(code.to_string()),
+          message: format!("{code}: Connect rejected"),
+        }),
+      ));
+      let before = config.get();
+
+      let result = block_on(connect(
+        &bridge,
+        &secrets,
+        &config,
+        &Watchdog::default(),
+        None,
+      ));
+
+      assert_eq!(result, Err(expected));
+      assert_eq!(secrets.load_token().unwrap(), None);
+      assert_eq!(config.get(), before);
+    }
+  }
+
+  #[test]
+  fn only_auth_failures_ask_for_a_new_token() {
+    let expired = BridgeError::BridgeReported {
+      code: Some("AUTH_EXPIRED".to_string()),
+      message: "AUTH_EXPIRED: Connect rejected".to_string(),
+    };
+    let settings = Settings {
+      default_base_url: Some("https://cp.example.com".to_string()),
+      ..Settings::default()
+    };
+
+    assert_eq!(
+      auth_required(&expired, &settings),
+      Some(AuthRequired {
+        kind: "authExpired",
+        base_url: Some("https://cp.example.com".to_string()),
+        profile_id: None,
+      })
+    );
+    assert_eq!(
+      auth_required(&BridgeError::AuthInvalid, &settings).map(|payload| payload.kind),
+      Some("authInvalid")
+    );
+    assert_eq!(auth_required(&BridgeError::EmptyResponse, &settings), None);
+  }
+
+  #[test]
+  fn connect_uses_the_active_profile_credentials() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = config(&dir);
+    let secrets = secrets();
+    secrets
+      .store_token(&StoredToken {
+        base_url: "https://cp.example.com".to_string(),
+        token: "default_token_value".to_string(),
+        expires_at: None,
+      })
+      .unwrap();
+    let profile = profiles::create(
+      &secrets,
+      &config,
+      "Work".to_string(),
+      "https://cp.work.example".to_string(),
+      "work_token_value".to_string(),
+    )
+    .unwrap();
+    let mock = MockBridgeRunner::default().respond("connect", Ok(json!({ "connected": true })));
+    let bridge = Bridge::new(mock.clone());
+    let watchdog = Watchdog::default();
+
+    block_on(connect(&bridge, &secrets, &config, &watchdog, None)).unwrap();
+    profiles::delete(&secrets, &config, &profile.id).unwrap();
+    block_on(connect(&bridge, &secrets, &config, &watchdog, None)).unwrap();
+
+    assert_eq!(
+      mock.calls(),
+      [
+        (
+          "connect".to_string(),
+          json!({ "baseUrl": "https://cp.work.example", "token": "work_token_value" })
+        ),
+        (
+          "connect".to_string(),
+          json!({ "baseUrl": "https://cp.example.com", "token": "default_token_value" })
+        ),
+      ]
+    );
+  }
+
+  #[test]
+  fn connect_re_sends_saved_split_tunnel_rules() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = config(&dir);
+    config
+      .update(|settings| {
+        settings.split_tunnel = vec![SplitRule {
+          id: "lan".to_string(),
+          kind: SplitKind::Cidr,
+          value: "192.168.0.0/16".to_string(),
+        }]
+      })
+      .unwrap();
+    let mock = MockBridgeRunner::default().respond("connect", Ok(json!({ "connected": true })));
+    let bridge = Bridge::new(mock.clone());
+
+    block_on(connect(
+      &bridge,
+      &secrets(),
+      &config,
+      &Watchdog::default(),
+      None,
+    ))
+    .unwrap();
+
+    assert_eq!(
+      mock.calls(),
+      [(
+        "connect".to_string(),
+        json!({ "splitTunnel": [{ "id": "lan", "kind": "cidr", "value": "192.168.0.0/16" }] })
+      )]
+    );
+  }
+
+  #[test]
+  fn connect_re_sends_saved_app_rules() {
+    use crate::app_rules::{AppPolicy, AppRule};
+
+    let dir = tempfile::tempdir().unwrap();
+    let config = config(&dir);
+    config
+      .update(|settings| {
+        settings.app_rules = vec![AppRule {
+          executable_path: "/usr/bin/firefox".to_string(),
+          policy: AppPolicy::ForceVpn,
+        }]
+      })
+      .unwrap();
+    let mock = MockBridgeRunner::default().respond("connect", Ok(json!({ "connected": true })));
+    let bridge = Bridge::new(mock.clone());
+
+    block_on(connect(
+      &bridge,
+      &secrets(),
+      &config,
+      &Watchdog::default(),
+      None,
+    ))
+    .unwrap();
+
+    assert_eq!(
+      mock.calls(),
+      [(
+        "connect".to_string(),
+        json!({ "appRules": [{ "executablePath": "/usr/bin/firefox", "policy": "forceVpn" }] })
+      )]
+    );
+  }
+
+  #[test]
+  fn connect_sends_a_pinned_transport_only() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = config(&dir);
+    let mock = MockBridgeRunner::default().respond("connect", Ok(json!({ "connected": true })));
+    let bridge = Bridge::new(mock.clone());
+    let connect_now = || {
+      block_on(connect(
+        &bridge,
+        &secrets(),
+        &config,
+        &Watchdog::default(),
+        None,
+      ))
+      .unwrap()
+    };
+
+    connect_now();
+    config
+      .update(|settings| settings.transport = Transport::VlessGrpc)
+      .unwrap();
+    connect_now();
+
+    let calls = mock.calls();
+    assert_eq!(calls[0].1.get("transport"), None);
+    assert_eq!(calls[1].1["transport"], json!("vless+grpc"));
+  }
+
+  #[test]
+  fn connect_asks_the_bridge_to_arm_the_kill_switch_when_enabled() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = config(&dir);
+    config
+      .update(|settings| settings.kill_switch = true)
+      .unwrap();
+    let mock = MockBridgeRunner::default().respond("connect", Ok(json!({ "connected": true })));
+    let bridge = Bridge::new(mock.clone());
+
+    block_on(connect(
+      &bridge,
+      &secrets(),
+      &config,
+      &Watchdog::default(),
+      None,
+    ))
+    .unwrap();
+
+    assert_eq!(
+      mock.calls(),
+      [("connect".to_string(), json!({ "killSwitch": true }))]
+    );
+  }
+
+  #[test]
+  fn connect_re_applies_disabled_ipv6_the_mtu_and_the_bandwidth_limit() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = config(&dir);
+    config
+      .update(|settings| {
+        settings.ipv6_enabled = false;
+        settings.mtu = Some(1400);
+        settings.bandwidth_limit.down_kbps = Some(4096);
+      })
+      .unwrap();
+    let mock = MockBridgeRunner::default().respond("connect", Ok(json!({ "connected": true })));
+
+    block_on(connect(
+      &Bridge::new(mock.clone()),
+      &secrets(),
+      &config,
+      &Watchdog::default(),
+      None,
+    ))
+    .unwrap();
+
+    assert_eq!(
+      mock.calls(),
+      [(
+        "connect".to_string(),
+        json!({
+          "ipv6": false,
+          "mtu": 1400,
+          "bandwidth": { "upKbps": null, "downKbps": 4096 }
+        })
+      )]
+    );
+  }
+
+  #[test]
+  fn disconnect_stands_the_watchdog_down_until_connect() {
+    let bridge = Bridge::new(
+      MockBridgeRunner::default()
+        .respond("disconnect", Ok(Value::Null))
+        .respond("connect", Ok(json!({ "connected": true }))),
+    );
+    let watchdog = Watchdog::default();
+
+    block_on(disconnect(&bridge, &watchdog)).unwrap();
+    assert!(!watchdog.should_reconnect(LinkState::Connected, LinkState::Disconnected, true));
+
+    let dir = tempfile::tempdir().unwrap();
+    block_on(connect(&bridge, &secrets(), &config(&dir), &watchdog, None)).unwrap();
+    assert!(watchdog.should_reconnect(LinkState::Connected, LinkState::Disconnected, true));
+  }
+
+  #[test]
+  fn set_mode_forwards_and_persists_a_valid_mode() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = Config::load(dir.path().join("settings.json"));
+    let mock = MockBridgeRunner::default().respond("setMode", Ok(json!({ "mode": "vpn" })));
+    let bridge = Bridge::new(mock.clone());
+
+    block_on(set_mode(&bridge, &config, "vpn")).unwrap();
+
+    assert_eq!(
+      mock.calls(),
+      [("setMode".to_string(), json!({ "mode": "vpn" }))]
+    );
+    assert_eq!(config.get().last_mode, Some(Mode::Vpn));
+  }
+
+  #[test]
+  fn set_mode_rejects_unknown_modes_without_calling_the_bridge() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = Config::load(dir.path().join("settings.json"));
+    let mock = MockBridgeRunner::default();
+    let bridge = Bridge::new(mock.clone());
+
+    let error = block_on(set_mode(&bridge, &config, "prox")).unwrap_err();
+
+    assert!(matches!(error, BridgeError::InvalidMode { .. }));
+    assert!(mock.calls().is_empty());
+    assert_eq!(config.get().last_mode, None);
+  }
+
+  #[test]
+  fn update_disguise_builds_the_bridge_payload() {
+    let mock = MockBridgeRunner::default().respond("updateDisguise", Ok(json!({ "jobId": "j-1" })));
+    let bridge = Bridge::new(mock.clone());
+    let disguise = json!({ "serverName": "vk.com", "dest": "vk.com:443", "fingerprint": "chrome" });
+
+    let result = block_on(update_disguise(
+      &bridge,
+      "https://cp.example.com".to_string(),
+      "srv-1".to_string(),
+      "admin-key".to_string(),
+      disguise.clone(),
+    ))
+    .unwrap();
+
+    assert_eq!(result, json!({ "jobId": "j-1" }));
+    assert_eq!(
+      mock.calls(),
+      [(
+        "updateDisguise".to_string(),
+        json!({
+          "baseUrl": "https://cp.example.com",
+          "serverId": "srv-1",
+          "adminApiKey": "admin-key",
+          "disguise": disguise
+        })
+      )]
+    );
+  }
+
+  #[test]
+  fn status_parses_the_bridge_payload() {
+    let payload = json!({ "connected": false, "mode": "proxy", "serverId": null });
+    let bridge = Bridge::new(MockBridgeRunner::default().respond("status", Ok(payload)));
+
+    assert_eq!(
+      block_on(status(&bridge)).unwrap(),
+      StatusSnapshot {
+        mode: Some(Mode::Proxy),
+        ..StatusSnapshot::default()
+      }
+    );
+  }
+
+  #[test]
+  fn status_rejects_a_payload_of_the_wrong_shape() {
+    let bridge = Bridge::new(MockBridgeRunner::default().respond("status", Ok(json!("up"))));
+
+    assert_eq!(block_on(status(&bridge)).unwrap_err().kind(), "invalidJson");
+  }
+
+  #[test]
+  fn import_token_remembers_base_url_and_token() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = Config::load(dir.path().join("settings.json"));
+    let secrets = secrets();
+    let bridge = Bridge::new(MockBridgeRunner::default().respond("importToken", Ok(Value::Null)));
+
+    block_on(import_token(
+      &bridge,
+      &secrets,
+      &config,
+      "https://cp.example.com".to_string(),
+      "abcDEF123_token_value".to_string(),
+    ))
+    .unwrap();
+
+    assert_eq!(
+      config.get().default_base_url.as_deref(),
+      Some("https://cp.example.com")
+    );
+    assert_eq!(
+      secrets.load_token().unwrap().map(|stored| stored.token),
+      Some("abcDEF123_token_value".to_string())
+    );
+  }
+
+  #[test]
+  fn import_token_rejects_http_before_calling_the_bridge() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = Config::load(dir.path().join("settings.json"));
+    let mock = MockBridgeRunner::default();
+
+    let error = block_on(import_token(
+      &Bridge::new(mock.clone()),
+      &secrets(),
+      &config,
+      "http://cp.example.com/".to_string(),
+      "abcDEF123_token_value".to_string(),
+    ))
+    .unwrap_err();
+
+    assert_eq!(error.kind(), "invalidBaseUrl");
+    assert!(mock.calls().is_empty());
+    assert_eq!(config.get().default_base_url, None);
+  }
+
+  #[test]
+  fn cancel_without_a_connect_in_flight_does_nothing() {
+    let mock = MockBridgeRunner::default();
+
+    assert_eq!(
+      block_on(cancel_connect(
+        &Bridge::new(mock.clone()),
+        &Watchdog::default()
+      )),
+      Ok(false)
+    );
+    assert!(mock.calls().is_empty());
+  }
+
+  #[test]
+  fn toggle_disconnects_a_live_or_paused_tunnel_and_connects_otherwise() {
+    let snapshot = |connected, paused| StatusSnapshot {
+      connected,
+      paused,
+      ..StatusSnapshot::default()
+    };
+
+    assert!(toggle_disconnects(&snapshot(true, false)));
+    assert!(toggle_disconnects(&snapshot(true, true)));
+    assert!(toggle_disconnects(&snapshot(false, true)));
+    assert!(!toggle_disconnects(&snapshot(false, false)));
+  }
+
+  /// Answers `status` with whatever the last `connect` / `disconnect` left behind.
+  #[derive(Clone, Default)]
+  struct FakeAgent {
+    connected: Arc<std::sync::atomic::AtomicBool>,
+    calls: Arc<std::sync::Mutex<Vec<&'static str>>>,
+  }
+
+  impl crate::bridge::BridgeRunner for FakeAgent {
+    fn run(&self, action: &str, _payload: Value) -> Result<Value, BridgeError> {
+      use std::sync::atomic::Ordering;
+      let action = match action {
+        "status" => "status",
+        "connect" => {
+          // Long enough for a racing toggle to read the old state if nothing held it back.
+          std::thread::sleep(std::time::Duration::from_millis(50));
+          self.connected.store(true, Ordering::SeqCst);
+          "connect"
+        }
+        "disconnect" => {
+          self.connected.store(false, Ordering::SeqCst);
+          "disconnect"
+        }
+        other => panic!("unexpected action {other}"),
+      };
+      self.calls.lock().unwrap().push(action);
+      Ok(json!({ "connected": self.connected.load(Ordering::SeqCst) }))
+    }
+  }
+
+  #[test]
+  fn a_double_toggle_connects_once_then_disconnects() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = Arc::new(config(&dir));
+    let agent = FakeAgent::default();
+    let bridge = Bridge::new(agent.clone());
+    let secrets = Arc::new(secrets());
+    let watchdog = Arc::new(Watchdog::default());
+    let lock = Arc::new(ToggleLock::default());
+
+    let toggles: Vec<_> = (0..2)
+      .map(|_| {
+        let (bridge, secrets, config) = (bridge.clone(), secrets.clone(), config.clone());
+        let (watchdog, lock) = (watchdog.clone(), lock.clone());
+        tauri::async_runtime::spawn(async move {
+          toggle_connection(&bridge, &secrets, &config, &watchdog, &lock).await
+        })
+      })
+      .collect();
+    let states: Vec<LinkState> = toggles
+      .into_iter()
+      .map(|toggle| block_on(toggle).unwrap().unwrap().state())
+      .collect();
+
+    assert_eq!(
+      *agent.calls.lock().unwrap(),
+      [
+        "status",
+        "connect",
+        "status",
+        "status",
+        "disconnect",
+        "status"
+      ]
+    );
+    assert!(states.contains(&LinkState::Connected));
+    assert!(states.contains(&LinkState::Disconnected));
+  }
+
+  #[test]
+  fn reconnect_now_connects_even_when_the_disconnect_fails() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = config(&dir);
+    let mock = MockBridgeRunner::default()
+      .respond("disconnect", Err(BridgeError::NotConnected))
+      .respond("connect", Ok(json!({})));
+    let bridge = Bridge::new(mock.clone());
+    let announced = std::cell::Cell::new(false);
+
+    let report = block_on(reconnect_now(
+      &bridge,
+      &secrets(),
+      &config,
+      &Watchdog::default(),
+      &ToggleLock::default(),
+      || {
+        assert_eq!(mock.calls().len(), 1, "announced after the disconnect");
+        announced.set(true);
+      },
+    ));
+
+    assert!(announced.get());
+    assert_eq!(
+      report,
+      ReconnectReport {
+        disconnect: StepOutcome {
+          ok: false,
+          error: Some(BridgeError::NotConnected),
+        },
+        connect: StepOutcome {
+          ok: true,
+          error: None,
+        },
+      }
+    );
+    let actions: Vec<String> = mock.calls().into_iter().map(|(action, _)| action).collect();
+    assert_eq!(actions, ["disconnect", "connect"]);
+  }
+
+  #[test]
+  fn nothing_runs_between_the_two_reconnect_steps() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = Arc::new(config(&dir));
+    let agent = FakeAgent::default();
+    let bridge = Bridge::new(agent.clone());
+    let secrets = Arc::new(secrets());
+    let watchdog = Arc::new(Watchdog::default());
+    let lock = Arc::new(ToggleLock::default());
+
+    let reconnect = {
+      let (bridge, secrets, config) = (bridge.clone(), secrets.clone(), config.clone());
+      let (watchdog, lock) = (watchdog.clone(), lock.clone());
+      tauri::async_runtime::spawn(async move {
+        reconnect_now(&bridge, &secrets, &config, &watchdog, &lock, || {}).await
+      })
+    };
+    // Lets `reconnect_now` take the lock first.
+    std::thread::sleep(std::time::Duration::from_millis(20));
+    let toggle = {
+      let (bridge, secrets, config) = (bridge.clone(), secrets.clone(), config.clone());
+      tauri::async_runtime::spawn(async move {
+        toggle_connection(&bridge, &secrets, &config, &watchdog, &lock).await
+      })
+    };
+    let report = block_on(reconnect).unwrap();
+    let after_toggle = block_on(toggle).unwrap().unwrap();
+
+    assert!(report.disconnect.ok && report.connect.ok);
+    // The toggle waited for the reconnect, so it saw a live tunnel and took it down.
+    assert_eq!(
+      *agent.calls.lock().unwrap(),
+      ["disconnect", "connect", "status", "disconnect", "status"]
+    );
+    assert_eq!(after_toggle.state(), LinkState::Disconnected);
+  }
+
+  #[cfg(unix)]
+  #[test]
+  fn cancel_kills_a_hanging_connect_and_disconnects() {
+    use crate::bridge::{BridgeCommand, NodeBridgeRunner};
+
+    let dir = tempfile::tempdir().unwrap();
+    let (pids, actions) = (dir.path().join("pids"), dir.path().join("actions"));
+    let script = dir.path().join("stub-bridge.sh");
+    std::fs::write(
+      &script,
+      format!(
+        r#"echo $$ >> '{pids}'
+while read -r line; do
+id=$(printf '%s' "$line" | sed 's/.*"id":\([0-9]*\).*/\1/')
+action=$(printf '%s' "$line" | sed 's/.*"action":"\([A-Za-z]*\)".*/\1/')
+echo "$action" >> '{actions}'
+[ "$action" = connect ] && sleep 30
+printf '{{"id":%s,"ok":true,"data":null}}\n' "$id"
+done
+"#,
+        pids = pids.display(),
+        actions = actions.display()
+      ),
+    )
+    .unwrap();
+    let bridge = Bridge::new(NodeBridgeRunner::new(BridgeCommand::new("sh", &script)));
+    let watchdog = Watchdog::default();
+    let logged = |path: &std::path::Path| -> Vec<String> {
+      std::fs::read_to_string(path)
+        .unwrap_or_default()
+        .lines()
+        .map(str::to_string)
+        .collect()
+    };
+
+    let connect_result = block_on(async {
+      let connecting = {
+        let bridge = bridge.clone();
+        tauri::async_runtime::spawn(async move { bridge.call("connect", Value::Null).await })
+      };
+      while logged(&actions).is_empty() {
+        tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+      }
+
+      assert_eq!(cancel_connect(&bridge, &watchdog).await, Ok(true));
+      connecting.await.unwrap()
+    });
+
+    assert_eq!(connect_result, Err(BridgeError::Cancelled));
+    assert_eq!(logged(&actions), ["connect", "disconnect"]);
+    let pids = logged(&pids);
+    assert_eq!(pids.len(), 2, "a fresh bridge handled the disconnect");
+    let first_alive = std::process::Command::new("kill")
+      .args(["-0", &pids[0]])
+      .stderr(std::process::Stdio::null())
+      .status()
+      .unwrap()
+      .success();
+    assert!(!first_alive, "the hanging bridge was killed");
+    assert!(!bridge.is_running("connect"));
+    assert!(!watchdog.should_reconnect(LinkState::Connected, LinkState::Disconnected, true));
+  }
+}
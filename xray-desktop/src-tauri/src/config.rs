@@ -0,0 +1,790 @@
+use crate::app_rules::{self, AppRule};
+use crate::bandwidth::{self, BandwidthLimit};
+use crate::base_url::normalize_base_url;
+use crate::bridge::{BridgeOptions, DEFAULT_SPAWN_RETRIES};
+use crate::dns;
+use crate::error::BridgeError;
+use crate::event_batch::BatchOptions;
+use crate::hotkey::{self, Hotkeys, DEFAULT_HOTKEY};
+use crate::i18n;
+use crate::mode::Mode;
+use crate::mtu;
+use crate::persist;
+use crate::profiles::Profile;
+use crate::schedules::{CronSpec, ScheduleRule};
+use crate::servers::{ServerCatalog, ServerInfo};
+use crate::split_tunnel::{self, SplitRule};
+use crate::status::{self, StatusWatcher};
+use crate::token_refresh::DEFAULT_REFRESH_WINDOW_SECS;
+use crate::transport::{self, Transport};
+use crate::trusted_networks;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+use tauri::{AppHandle, Manager};
+
+const SETTINGS_FILE: &str = "settings.json";
+/// Bumped whenever the on-disk format changes in a way `migrate` has to handle. Files written
+/// before versioning have no `schemaVersion` and count as 1.
+pub const SCHEMA_VERSION: u64 = 2;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct Settings {
+  /// Format of the file; always written as `SCHEMA_VERSION`.
+  pub schema_version: u64,
+  pub default_base_url: Option<String>,
+  pub last_mode: Option<Mode>,
+  /// Connect once the bridge is up, including launches at login.
+  pub auto_connect: bool,
+  /// Reconnect with backoff after drops the user didn't ask for.
+  pub auto_reconnect: bool,
+  /// Server picked with `selectServer`; `connect` uses it when no server is passed.
+  pub selected_server_id: Option<String>,
+  /// Launch on login. Mirrors the OS registration made by `setAutostart`.
+  pub autostart: bool,
+  /// Explicit node binary, tried right after `NODE_BINARY`.
+  pub node_path: Option<String>,
+  /// Bridge script run instead of the bundled one; `VPNATOR_BRIDGE_SCRIPT` beats it. For
+  /// debugging a forked bridge, so integrity checks are skipped for it.
+  pub bridge_script_path: Option<String>,
+  /// Verbose logging from the agent's xray core, the bridge and the app; see `debug_mode`.
+  pub debug_mode: bool,
+  /// Extra attempts when spawning the bridge fails for lack of resources.
+  pub bridge_spawn_retries: u32,
+  /// Native notifications on connect, disconnect and connection loss.
+  pub notifications_enabled: bool,
+  /// Block traffic outside the tunnel while connected and after unexpected drops.
+  pub kill_switch: bool,
+  /// Apps and ranges that bypass the tunnel, re-sent to the bridge on every `connect`.
+  pub split_tunnel: Vec<SplitRule>,
+  /// Resolvers forced through the tunnel; empty keeps the system ones.
+  pub dns_servers: Vec<String>,
+  /// Saved accounts; their tokens are in the keyring.
+  pub profiles: Vec<Profile>,
+  /// Profile `connect` uses; `None` means the token from `storeToken`.
+  pub active_profile_id: Option<String>,
+  /// Refresh the token in the background once it expires within this many seconds; 0 never does.
+  pub token_refresh_window_secs: u64,
+  /// Fan-out calls (`pingAll`, `batchUpdateDisguise`) in flight at once; `None` is one per CPU.
+  pub bridge_pool_size: Option<usize>,
+  /// Accept plain `http` control-plane URLs, e.g. a test server on the LAN.
+  pub allow_insecure_base_url: bool,
+  /// Closing the window hides it to the tray instead of quitting.
+  pub close_to_tray: bool,
+  /// Route IPv6 through the tunnel; off blocks it so it can't leak. Re-sent on every `connect`.
+  pub ipv6_enabled: bool,
+  /// MTU of the TUN interface, re-sent on every `connect`; `None` keeps the agent's default.
+  pub mtu: Option<u16>,
+  /// `checkForUpdates` may look up the latest release; off never contacts the release feed.
+  pub check_updates: bool,
+  /// Extra environment and working directory for the bridge process, read at startup.
+  pub bridge_env: BridgeOptions,
+  /// Serve status and stats on a loopback port for status bars, read at startup.
+  pub local_api: bool,
+  /// Quitting with a tunnel up asks whether to disconnect first.
+  pub confirm_quit: bool,
+  /// Connect and disconnect rules evaluated against the local clock.
+  pub schedules: Vec<ScheduleRule>,
+  /// Server ids starred in the picker.
+  pub favorite_servers: Vec<String>,
+  /// Servers a `connect` succeeded to, most recent first, capped at `favorites::RECENT_LIMIT`.
+  pub recent_servers: Vec<String>,
+  /// Start the bridge in the background at launch; off defers it to the first command.
+  pub prewarm_bridge: bool,
+  /// Throughput caps, re-sent on every `connect`; unlimited by default.
+  pub bandwidth_limit: BandwidthLimit,
+  /// Tint the tray icon red while disconnected after a failed action.
+  pub tray_error_indicator: bool,
+  /// System-wide shortcut for `toggleConnection`; `None` turns it off.
+  pub hotkey: Option<String>,
+  /// Set by `completeOnboarding` or a deep-link import; hides the first-run setup flow.
+  pub onboarding_complete: bool,
+  /// SSIDs and gateway MACs on which `autoConnect` stays off.
+  pub trusted_networks: Vec<String>,
+  /// Record bridge call timings for `getTelemetry`; they never leave the machine.
+  pub telemetry_local: bool,
+  /// Fixed status poll interval; `None` polls fast around transitions and slowly otherwise.
+  pub status_poll_ms: Option<u64>,
+  /// Refuse a `desktop-bridge.cjs` that differs from the one built in; off while working on it.
+  pub verify_bridge: bool,
+  /// Executables forced through or kept out of the tunnel, re-sent on every `connect`.
+  pub app_rules: Vec<AppRule>,
+  /// Transport forced on every `connect`; `auto` leaves it to the server's config.
+  pub transport: Transport,
+  /// SHA-256 certificate fingerprints by normalized base URL, sent with every bridge call for
+  /// that URL; the bridge refuses to talk to a server presenting another certificate.
+  pub pinned_fingerprints: BTreeMap<String, String>,
+  /// Cadence and size of the `vpn://stats` / `vpn://log` batches; read at launch.
+  pub event_batching: BatchOptions,
+  /// Language of notifications, tray tooltips and menu labels; unknown tags read as English.
+  pub locale: String,
+  /// Servers from `importExternalConfig`; their outbounds, credentials included, are in the
+  /// keyring.
+  pub imported_servers: Vec<ServerInfo>,
+}
+
+impl Default for Settings {
+  fn default() -> Self {
+    Self {
+      schema_version: SCHEMA_VERSION,
+      default_base_url: None,
+      last_mode: None,
+      auto_connect: false,
+      auto_reconnect: false,
+      selected_server_id: None,
+      autostart: false,
+      node_path: None,
+      bridge_script_path: None,
+      debug_mode: false,
+      bridge_spawn_retries: DEFAULT_SPAWN_RETRIES,
+      notifications_enabled: true,
+      kill_switch: false,
+      split_tunnel: Vec::new(),
+      dns_servers: Vec::new(),
+      profiles: Vec::new(),
+      active_profile_id: None,
+      token_refresh_window_secs: DEFAULT_REFRESH_WINDOW_SECS,
+      bridge_pool_size: None,
+      allow_insecure_base_url: false,
+      close_to_tray: false,
+      ipv6_enabled: true,
+      mtu: None,
+      check_updates: true,
+      bridge_env: BridgeOptions::default(),
+      local_api: false,
+      confirm_quit: true,
+      schedules: Vec::new(),
+      favorite_servers: Vec::new(),
+      recent_servers: Vec::new(),
+      prewarm_bridge: true,
+      bandwidth_limit: BandwidthLimit::default(),
+      tray_error_indicator: true,
+      hotkey: Some(DEFAULT_HOTKEY.to_string()),
+      onboarding_complete: false,
+      trusted_networks: Vec::new(),
+      telemetry_local: false,
+      status_poll_ms: None,
+      verify_bridge: true,
+      app_rules: Vec::new(),
+      transport: Transport::Auto,
+      pinned_fingerprints: BTreeMap::new(),
+      event_batching: BatchOptions::default(),
+      locale: i18n::DEFAULT_LOCALE.to_string(),
+      imported_servers: Vec::new(),
+    }
+  }
+}
+
+/// Upgrades a parsed settings file one version at a time to `SCHEMA_VERSION`. Fields a step
+/// doesn't touch pass through as they are.
+pub fn migrate(mut raw: Value) -> Result<Value, BridgeError> {
+  let mut version = raw
+    .get("schemaVersion")
+    .and_then(Value::as_u64)
+    .unwrap_or(1);
+  if version > SCHEMA_VERSION {
+    return Err(BridgeError::SettingsTooNew {
+      found: version,
+      supported: SCHEMA_VERSION,
+    });
+  }
+  while version < SCHEMA_VERSION {
+    match version {
+      // 1 → 2 only introduced the version field itself.
+      1 => {}
+      _ => unreachable!("no migration from schema {version}"),
+    }
+    version += 1;
+    raw["schemaVersion"] = json!(version);
+  }
+  Ok(raw)
+}
+
+/// Migrates a parsed file and reads it as `Settings`.
+fn read(raw: Value) -> Result<Settings, BridgeError> {
+  serde_json::from_value(migrate(raw)?)
+    .map_err(|error| BridgeError::SettingsInvalid(error.to_string()))
+}
+
+/// User preferences persisted as `settings.json` in the app config dir.
+pub struct Config {
+  path: PathBuf,
+  settings: Mutex<Settings>,
+  /// Set when the file is from a newer build or isn't settings at all; saving would throw its
+  /// fields away, so `update` fails with this instead.
+  unwritable: Mutex<Option<BridgeError>>,
+}
+
+impl Config {
+  /// A corrupt file is recovered from its backup; with neither, the defaults are used and
+  /// written on the next save. An older file is migrated and written back right away. A file
+  /// that parses but doesn't read as settings is left alone: the backup is used if it reads,
+  /// otherwise the defaults, and saving is refused until the file is fixed or deleted.
+  pub fn load(path: impl Into<PathBuf>) -> Self {
+    let path = path.into();
+    let raw: Option<Value> = persist::load(&path, |raw| serde_json::from_str(raw).ok());
+    let current = raw
+      .as_ref()
+      .is_some_and(|raw| raw.get("schemaVersion") == Some(&json!(SCHEMA_VERSION)));
+    let (settings, unwritable) = match raw.map(read) {
+      Some(Ok(settings)) => {
+        if !current {
+          if let Err(error) = save(&path, &settings) {
+            tracing::warn!(path = %path.display(), "could not write migrated settings: {error}");
+          }
+        }
+        (settings, None)
+      }
+      Some(Err(error @ BridgeError::SettingsInvalid(_))) => {
+        tracing::error!(path = %path.display(), "{error}");
+        match read_backup(&path) {
+          Some(settings) => {
+            tracing::warn!(path = %path.display(), "using the backup copy");
+            (settings, None)
+          }
+          None => (Settings::default(), Some(error)),
+        }
+      }
+      Some(Err(error)) => {
+        tracing::error!(path = %path.display(), "{error}");
+        (Settings::default(), Some(error))
+      }
+      None => (Settings::default(), None),
+    };
+
+    Self {
+      path,
+      settings: Mutex::new(settings),
+      unwritable: Mutex::new(unwritable),
+    }
+  }
+
+  pub fn for_app(app: &AppHandle) -> tauri::Result<Self> {
+    Ok(Self::in_dir(&app.path().app_config_dir()?))
+  }
+
+  pub fn in_dir(config_dir: &Path) -> Self {
+    Self::load(config_dir.join(SETTINGS_FILE))
+  }
+
+  pub fn get(&self) -> Settings {
+    self.settings.lock().unwrap().clone()
+  }
+
+  /// Applies `change` and writes the result to disk. The in-memory copy is only updated once
+  /// the write succeeded.
+  pub fn update(&self, change: impl FnOnce(&mut Settings)) -> Result<Settings, BridgeError> {
+    if let Some(error) = self.unwritable.lock().unwrap().clone() {
+      return Err(error);
+    }
+    let mut settings = self.settings.lock().unwrap();
+    let mut next = settings.clone();
+    change(&mut next);
+    next.schema_version = SCHEMA_VERSION;
+    save(&self.path, &next)?;
+    *settings = next.clone();
+    Ok(next)
+  }
+
+  /// Deletes the file and goes back to defaults, which are written on the next `update`.
+  /// Returns whether there was a file.
+  pub fn reset(&self) -> Result<bool, BridgeError> {
+    let mut settings = self.settings.lock().unwrap();
+    let removed =
+      persist::remove(&self.path).map_err(|error| BridgeError::SettingsIo(error.to_string()))?;
+    *settings = Settings::default();
+    *self.unwritable.lock().unwrap() = None;
+    Ok(removed)
+  }
+}
+
+fn read_backup(path: &Path) -> Option<Settings> {
+  let raw = std::fs::read_to_string(persist::backup_path(path)).ok()?;
+  read(serde_json::from_str(&raw).ok()?).ok()
+}
+
+fn save(path: &Path, settings: &Settings) -> Result<(), BridgeError> {
+  let io = |error: std::io::Error| BridgeError::SettingsIo(error.to_string());
+  let raw = serde_json::to_string_pretty(settings)
+    .map_err(|error| BridgeError::SettingsIo(error.to_string()))?;
+  persist::atomic_write(path, raw).map_err(io)
+}
+
+#[allow(non_snake_case)]
+#[tauri::command]
+pub fn getSettings(config: tauri::State<'_, Config>) -> Settings {
+  config.get()
+}
+
+/// `updateSettings` can't touch these: they decide what code the bridge runs and whether it's
+/// checked, so only the user editing the file may change them. `bridgeEnv` is one of them, as
+/// `NODE_OPTIONS` alone can load any script into node.
+fn refuse_protected(current: &Settings, next: &Settings) -> Result<(), BridgeError> {
+  let changed = [
+    ("verifyBridge", current.verify_bridge != next.verify_bridge),
+    (
+      "bridgeScriptPath",
+      current.bridge_script_path != next.bridge_script_path,
+    ),
+    ("nodePath", current.node_path != next.node_path),
+    ("bridgeEnv", current.bridge_env != next.bridge_env),
+  ];
+  match changed.iter().find(|(_, changed)| *changed) {
+    Some((field, _)) => Err(BridgeError::ProtectedSetting(field.to_string())),
+    None => Ok(()),
+  }
+}
+
+/// Puts back the fields `refuse_protected` guards, for imports that take settings from a file
+/// someone else may have written.
+pub fn keep_protected(current: &Settings, next: &mut Settings) {
+  next.verify_bridge = current.verify_bridge;
+  next.bridge_script_path = current.bridge_script_path.clone();
+  next.node_path = current.node_path.clone();
+  next.bridge_env = current.bridge_env.clone();
+}
+
+/// Runs every field `next` changes through the check its own setter makes, normalized the way
+/// that setter saves it. Unchanged fields pass as they are, so an app rule for an uninstalled
+/// app doesn't block unrelated changes.
+pub fn validate_update(
+  current: &Settings,
+  mut next: Settings,
+  catalog: &ServerCatalog,
+) -> Result<Settings, BridgeError> {
+  refuse_protected(current, &next)?;
+  if next.default_base_url != current.default_base_url {
+    next.default_base_url = next
+      .default_base_url
+      .as_deref()
+      .map(|url| normalize_base_url(url, next.allow_insecure_base_url))
+      .transpose()?;
+  }
+  if next.mtu != current.mtu {
+    next.mtu = next.mtu.map(mtu::validate).transpose()?;
+  }
+  if next.dns_servers != current.dns_servers && !next.dns_servers.is_empty() {
+    next.dns_servers = dns::validate(&next.dns_servers)?;
+  }
+  if next.hotkey != current.hotkey {
+    next.hotkey = next.hotkey.as_deref().map(hotkey::normalize).transpose()?;
+  }
+  if next.transport != current.transport {
+    transport::check_offered(next.transport, next.selected_server_id.as_deref(), catalog)?;
+  }
+  if next.status_poll_ms != current.status_poll_ms {
+    next.status_poll_ms = next
+      .status_poll_ms
+      .map(status::validate_poll_ms)
+      .transpose()?;
+  }
+  if next.bandwidth_limit != current.bandwidth_limit {
+    next.bandwidth_limit = bandwidth::validate(next.bandwidth_limit)?;
+  }
+  for rule in &next.split_tunnel {
+    if !current.split_tunnel.contains(rule) {
+      split_tunnel::validate(rule)?;
+    }
+  }
+  for rule in &next.schedules {
+    if !current.schedules.contains(rule) {
+      if rule.id.trim().is_empty() {
+        return Err(BridgeError::InvalidSchedule("id is empty".to_string()));
+      }
+      CronSpec::parse(&rule.cron_like)?;
+    }
+  }
+  for rule in &next.app_rules {
+    if !current.app_rules.contains(rule) {
+      app_rules::validate_path(&rule.executable_path)?;
+    }
+  }
+  if next.trusted_networks != current.trusted_networks {
+    next.trusted_networks = next
+      .trusted_networks
+      .iter()
+      .map(|network| trusted_networks::normalize(network))
+      .collect::<Result<_, _>>()?;
+  }
+  if next.locale != current.locale {
+    next.locale = i18n::normalize_locale(&next.locale)?;
+  }
+  Ok(next)
+}
+
+/// Saves a whole settings object from the UI. A changed hotkey is registered and a changed poll
+/// interval takes effect right away, as with their own setters.
+#[allow(non_snake_case)]
+#[tauri::command]
+pub fn updateSettings(
+  config: tauri::State<'_, Config>,
+  catalog: tauri::State<'_, ServerCatalog>,
+  hotkeys: tauri::State<'_, Hotkeys>,
+  watcher: tauri::State<'_, StatusWatcher>,
+  settings: Settings,
+) -> Result<Settings, BridgeError> {
+  let current = config.get();
+  let next = validate_update(&current, settings, &catalog)?;
+  if next.hotkey != current.hotkey {
+    hotkeys.set(&config, next.hotkey.as_deref())?;
+  }
+  let saved = config.update(|settings| *settings = next)?;
+  if saved.status_poll_ms != current.status_poll_ms {
+    watcher.set_interval(saved.status_poll_ms.map(Duration::from_millis));
+  }
+  Ok(saved)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::fs;
+
+  #[test]
+  fn missing_file_loads_defaults() {
+    let dir = tempfile::tempdir().unwrap();
+
+    let config = Config::load(dir.path().join(SETTINGS_FILE));
+
+    assert_eq!(config.get(), Settings::default());
+  }
+
+  #[test]
+  fn settings_round_trip_through_disk() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("nested").join(SETTINGS_FILE);
+
+    Config::load(&path)
+      .update(|settings| {
+        settings.default_base_url = Some("https://cp.example.com".to_string());
+        settings.last_mode = Some(Mode::Vpn);
+        settings.auto_connect = true;
+      })
+      .unwrap();
+
+    assert_eq!(
+      Config::load(&path).get(),
+      Settings {
+        schema_version: SCHEMA_VERSION,
+        default_base_url: Some("https://cp.example.com".to_string()),
+        last_mode: Some(Mode::Vpn),
+        auto_connect: true,
+        auto_reconnect: false,
+        selected_server_id: None,
+        autostart: false,
+        node_path: None,
+        bridge_script_path: None,
+        debug_mode: false,
+        bridge_spawn_retries: DEFAULT_SPAWN_RETRIES,
+        notifications_enabled: true,
+        kill_switch: false,
+        split_tunnel: Vec::new(),
+        dns_servers: Vec::new(),
+        profiles: Vec::new(),
+        active_profile_id: None,
+        token_refresh_window_secs: DEFAULT_REFRESH_WINDOW_SECS,
+        bridge_pool_size: None,
+        allow_insecure_base_url: false,
+        close_to_tray: false,
+        ipv6_enabled: true,
+        mtu: None,
+        check_updates: true,
+        bridge_env: BridgeOptions::default(),
+        local_api: false,
+        confirm_quit: true,
+        schedules: Vec::new(),
+        favorite_servers: Vec::new(),
+        recent_servers: Vec::new(),
+        prewarm_bridge: true,
+        bandwidth_limit: BandwidthLimit::default(),
+        tray_error_indicator: true,
+        hotkey: Some(DEFAULT_HOTKEY.to_string()),
+        onboarding_complete: false,
+        trusted_networks: Vec::new(),
+        telemetry_local: false,
+        status_poll_ms: None,
+        verify_bridge: true,
+        app_rules: Vec::new(),
+        transport: Transport::Auto,
+        pinned_fingerprints: BTreeMap::new(),
+        event_batching: BatchOptions::default(),
+        locale: "en".to_string(),
+        imported_servers: Vec::new(),
+      }
+    );
+  }
+
+  #[test]
+  fn unversioned_files_migrate_to_the_current_schema_keeping_every_field() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join(SETTINGS_FILE);
+    fs::write(
+      &path,
+      r#"{ "autoConnect": true, "mtu": 1400, "dnsServers": ["9.9.9.9"], "favoriteServers": ["srv-1"] }"#,
+    )
+    .unwrap();
+
+    let settings = Config::load(&path).get();
+
+    assert_eq!(settings.schema_version, SCHEMA_VERSION);
+    assert!(settings.auto_connect);
+    assert_eq!(settings.mtu, Some(1400));
+    assert_eq!(settings.dns_servers, ["9.9.9.9"]);
+    assert_eq!(settings.favorite_servers, ["srv-1"]);
+    let written: Value = serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+    assert_eq!(written["schemaVersion"], json!(SCHEMA_VERSION));
+    assert_eq!(written["mtu"], json!(1400));
+  }
+
+  #[test]
+  fn migrate_passes_unknown_fields_through() {
+    let migrated = migrate(json!({ "autoConnect": true, "futureField": [1, 2] })).unwrap();
+
+    assert_eq!(
+      migrated,
+      json!({ "autoConnect": true, "futureField": [1, 2], "schemaVersion": SCHEMA_VERSION })
+    );
+  }
+
+  #[test]
+  fn newer_files_are_rejected_and_left_untouched() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join(SETTINGS_FILE);
+    let newer = r#"{ "schemaVersion": 99, "autoConnect": true }"#;
+    fs::write(&path, newer).unwrap();
+
+    let config = Config::load(&path);
+
+    assert_eq!(
+      config.update(|settings| settings.auto_connect = false),
+      Err(BridgeError::SettingsTooNew {
+        found: 99,
+        supported: SCHEMA_VERSION
+      })
+    );
+    assert_eq!(fs::read_to_string(&path).unwrap(), newer);
+  }
+
+  #[test]
+  fn files_that_are_not_settings_are_never_overwritten() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join(SETTINGS_FILE);
+    let unreadable = r#"{ "autoConnect": true, "mtu": "large" }"#;
+    fs::write(&path, unreadable).unwrap();
+
+    let config = Config::load(&path);
+
+    assert_eq!(config.get(), Settings::default());
+    assert_eq!(
+      config
+        .update(|settings| settings.auto_connect = false)
+        .unwrap_err()
+        .kind(),
+      "settingsInvalid"
+    );
+    assert_eq!(fs::read_to_string(&path).unwrap(), unreadable);
+  }
+
+  #[test]
+  fn files_that_are_not_settings_fall_back_to_the_backup() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join(SETTINGS_FILE);
+    let config = Config::load(&path);
+    config
+      .update(|settings| settings.auto_connect = true)
+      .unwrap();
+    config.update(|settings| settings.mtu = Some(1400)).unwrap();
+    let unreadable = r#"{ "schemaVersion": 2, "mtu": "large" }"#;
+    fs::write(&path, unreadable).unwrap();
+
+    let recovered = Config::load(&path);
+
+    assert!(recovered.get().auto_connect);
+    assert_eq!(fs::read_to_string(&path).unwrap(), unreadable);
+  }
+
+  #[test]
+  fn update_refuses_protected_fields() {
+    let current = Settings::default();
+    let catalog = ServerCatalog::default();
+
+    for next in [
+      Settings {
+        verify_bridge: false,
+        ..Settings::default()
+      },
+      Settings {
+        bridge_script_path: Some("/tmp/desktop-bridge.cjs".to_string()),
+        ..Settings::default()
+      },
+      Settings {
+        bridge_env: BridgeOptions {
+          env: [(
+            "NODE_OPTIONS".to_string(),
+            "--require /tmp/x.js".to_string(),
+          )]
+          .into(),
+          cwd: None,
+        },
+        ..Settings::default()
+      },
+    ] {
+      assert_eq!(
+        validate_update(&current, next, &catalog)
+          .unwrap_err()
+          .kind(),
+        "protectedSetting"
+      );
+    }
+  }
+
+  #[test]
+  fn update_validates_and_normalizes_changed_fields() {
+    let current = Settings::default();
+    let catalog = ServerCatalog::default();
+
+    let next = validate_update(
+      &current,
+      Settings {
+        hotkey: Some("ctrl+alt+v".to_string()),
+        locale: "RU_ru".to_string(),
+        mtu: Some(1400),
+        ..Settings::default()
+      },
+      &catalog,
+    )
+    .unwrap();
+    assert_eq!(next.hotkey.as_deref(), Some("Ctrl+Alt+V"));
+    assert_eq!(next.locale, "ru-ru");
+
+    for bad in [
+      Settings {
+        mtu: Some(70),
+        ..Settings::default()
+      },
+      Settings {
+        dns_servers: vec!["dns.example.com".to_string()],
+        ..Settings::default()
+      },
+      Settings {
+        status_poll_ms: Some(1),
+        ..Settings::default()
+      },
+      Settings {
+        schedules: vec![ScheduleRule {
+          id: "morning".to_string(),
+          cron_like: "61 9 * * *".to_string(),
+          action: crate::schedules::ScheduleAction::Connect,
+        }],
+        ..Settings::default()
+      },
+    ] {
+      assert!(validate_update(&current, bad, &catalog).is_err());
+    }
+  }
+
+  #[test]
+  fn update_leaves_unchanged_fields_unchecked() {
+    let current = Settings {
+      app_rules: vec![AppRule {
+        executable_path: "/opt/uninstalled/app".to_string(),
+        policy: app_rules::AppPolicy::Bypass,
+      }],
+      ..Settings::default()
+    };
+    let next = Settings {
+      auto_connect: true,
+      ..current.clone()
+    };
+
+    assert!(
+      validate_update(&current, next, &ServerCatalog::default())
+        .unwrap()
+        .auto_connect
+    );
+  }
+
+  #[test]
+  fn a_crash_mid_write_leaves_the_previous_file_intact() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join(SETTINGS_FILE);
+    let config = Config::load(&path);
+    config
+      .update(|settings| settings.auto_connect = true)
+      .unwrap();
+
+    // What a crash after writing half the temp file and before the rename leaves behind.
+    fs::write(
+      persist::temp_path(&path),
+      r#"{ "schemaVersion": 2, "autoCon"#,
+    )
+    .unwrap();
+
+    assert!(Config::load(&path).get().auto_connect);
+    config.update(|settings| settings.mtu = Some(1400)).unwrap();
+    let reloaded = Config::load(&path).get();
+    assert!(reloaded.auto_connect);
+    assert_eq!(reloaded.mtu, Some(1400));
+    assert!(!persist::temp_path(&path).exists());
+  }
+
+  #[test]
+  fn corrupt_file_is_recovered_from_the_backup() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join(SETTINGS_FILE);
+    let config = Config::load(&path);
+    config
+      .update(|settings| settings.auto_connect = true)
+      .unwrap();
+    config.update(|settings| settings.mtu = Some(1400)).unwrap();
+
+    fs::write(&path, r#"{ "schemaVersion": 2, "autoCon"#).unwrap();
+
+    let recovered = Config::load(&path).get();
+    assert!(recovered.auto_connect);
+    assert_eq!(recovered.mtu, None);
+  }
+
+  #[test]
+  fn notifications_default_to_on_for_older_files() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join(SETTINGS_FILE);
+    fs::write(&path, r#"{ "autoConnect": true }"#).unwrap();
+
+    let settings = Config::load(&path).get();
+
+    assert!(settings.auto_connect);
+    assert!(settings.notifications_enabled);
+  }
+
+  #[test]
+  fn corrupt_file_falls_back_to_defaults_and_is_overwritten() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join(SETTINGS_FILE);
+    fs::write(&path, "{ not json").unwrap();
+
+    let config = Config::load(&path);
+    assert_eq!(config.get(), Settings::default());
+
+    config
+      .update(|settings| settings.last_mode = Some(Mode::Proxy))
+      .unwrap();
+    assert_eq!(Config::load(&path).get().last_mode, Some(Mode::Proxy));
+  }
+
+  #[test]
+  fn failed_save_keeps_previous_settings() {
+    let dir = tempfile::tempdir().unwrap();
+    // The settings path is a directory, so the write fails.
+    let config = Config::load(dir.path());
+
+    let result = config.update(|settings| settings.auto_connect = true);
+
+    assert!(matches!(result, Err(BridgeError::SettingsIo(_))));
+    assert_eq!(config.get(), Settings::default());
+  }
+}
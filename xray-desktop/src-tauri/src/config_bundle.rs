@@ -0,0 +1,670 @@
+use crate::config::{self, Config, Settings};
+use crate::error::BridgeError;
+use crate::persist;
+use crate::secrets::{Secrets, StoredToken, TOKEN_ACCOUNT};
+use crate::servers::ServerCatalog;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+
+/// Bumped when the bundle layout changes; `settings` carries its own `schemaVersion`.
+pub const BUNDLE_VERSION: u64 = 1;
+
+/// Keyring contents carried in a bundle exported with secrets.
+#[derive(Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct BundleSecrets {
+  /// By keyring account: `default` and each profile's `token_ref`.
+  pub tokens: BTreeMap<String, StoredToken>,
+  /// By control panel base URL.
+  pub admin_keys: BTreeMap<String, String>,
+  /// xray outbounds of imported servers, by server id.
+  pub outbounds: BTreeMap<String, Value>,
+}
+
+/// Everything needed to set the app up on another machine: `settings.json` (profiles,
+/// favorites, schedules and preferences) and, optionally, the keyring entries that go with it.
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConfigBundle {
+  pub bundle_version: u64,
+  /// As in `settings.json`, migrated on import like the file itself is.
+  pub settings: Value,
+  /// `None` when exported without secrets; profiles then need their token entered again.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub secrets: Option<BundleSecrets>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ImportMode {
+  /// Adds the bundle's profiles, schedules, imported servers, favorites and pins next to the
+  /// existing ones; the other settings and any secret already in the keyring stay as they are.
+  #[default]
+  Merge,
+  /// Takes the bundle's settings as they are. Tokens of profiles and outbounds of imported
+  /// servers it doesn't have are deleted.
+  Replace,
+}
+
+/// What `importConfigBundle` changed. Counts only, never values.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportSummary {
+  pub mode: ImportMode,
+  /// Profiles that weren't saved on this machine before.
+  pub profiles_added: usize,
+  /// Keyring entries written from the bundle.
+  pub secrets_restored: usize,
+}
+
+fn invalid(error: impl ToString) -> BridgeError {
+  BridgeError::BundleInvalid(error.to_string())
+}
+
+/// Control panels whose admin key belongs in the bundle.
+fn base_urls(settings: &Settings) -> BTreeSet<String> {
+  let mut base_urls: BTreeSet<String> = settings.default_base_url.iter().cloned().collect();
+  base_urls.extend(
+    settings
+      .profiles
+      .iter()
+      .map(|profile| profile.base_url.clone()),
+  );
+  base_urls
+}
+
+/// Keyring accounts a bundle may write a token to; anything else in it is ignored.
+fn token_accounts(settings: &Settings) -> BTreeSet<String> {
+  let mut accounts = BTreeSet::from([TOKEN_ACCOUNT.to_string()]);
+  accounts.extend(
+    settings
+      .profiles
+      .iter()
+      .map(|profile| profile.token_ref.clone()),
+  );
+  accounts
+}
+
+fn collect_secrets(settings: &Settings, secrets: &Secrets) -> Result<BundleSecrets, BridgeError> {
+  let mut bundled = BundleSecrets::default();
+  for account in token_accounts(settings) {
+    if let Some(stored) = secrets.load_token_at(&account)? {
+      bundled.tokens.insert(account, stored);
+    }
+  }
+  for base_url in base_urls(settings) {
+    if let Some(key) = secrets.load_admin_key(&base_url)? {
+      bundled.admin_keys.insert(base_url, key);
+    }
+  }
+  for server in &settings.imported_servers {
+    if let Some(outbound) = secrets.load_outbound(&server.id)? {
+      bundled.outbounds.insert(server.id.clone(), outbound);
+    }
+  }
+  Ok(bundled)
+}
+
+pub fn build(
+  config: &Config,
+  secrets: &Secrets,
+  include_secrets: bool,
+) -> Result<ConfigBundle, BridgeError> {
+  let settings = config.get();
+  Ok(ConfigBundle {
+    bundle_version: BUNDLE_VERSION,
+    settings: serde_json::to_value(&settings).map_err(invalid)?,
+    secrets: include_secrets
+      .then(|| collect_secrets(&settings, secrets))
+      .transpose()?,
+  })
+}
+
+/// Writes the bundle to `dest`, readable by the owner only since it may hold tokens and keys in
+/// plain text. Without `include_secrets` the keyring isn't read at all.
+pub fn export(
+  config: &Config,
+  secrets: &Secrets,
+  dest: &Path,
+  include_secrets: bool,
+) -> Result<PathBuf, BridgeError> {
+  let bundle = build(config, secrets, include_secrets)?;
+  let raw = serde_json::to_string_pretty(&bundle).map_err(invalid)?;
+  persist::atomic_write_private(dest, raw)
+    .map_err(|error| BridgeError::BundleIo(format!("{}: {error}", dest.display())))?;
+  tracing::info!(path = %dest.display(), include_secrets, "config bundle exported");
+  Ok(dest.to_path_buf())
+}
+
+/// The bundle's settings, checked against what this build can read.
+fn parse(bundle: &ConfigBundle) -> Result<Settings, BridgeError> {
+  if bundle.bundle_version > BUNDLE_VERSION {
+    return Err(BridgeError::BundleTooNew {
+      found: bundle.bundle_version,
+      supported: BUNDLE_VERSION,
+    });
+  }
+  let migrated = config::migrate(bundle.settings.clone())?;
+  serde_json::from_value(migrated).map_err(invalid)
+}
+
+fn merge(current: &mut Settings, incoming: Settings) {
+  for profile in incoming.profiles {
+    if !current.profiles.iter().any(|known| known.id == profile.id) {
+      current.profiles.push(profile);
+    }
+  }
+  for rule in incoming.schedules {
+    if !current.schedules.iter().any(|known| known.id == rule.id) {
+      current.schedules.push(rule);
+    }
+  }
+  for server in incoming.imported_servers {
+    if !current
+      .imported_servers
+      .iter()
+      .any(|known| known.id == server.id)
+    {
+      current.imported_servers.push(server);
+    }
+  }
+  for server_id in incoming.favorite_servers {
+    if !current.favorite_servers.contains(&server_id) {
+      current.favorite_servers.push(server_id);
+    }
+  }
+  for (base_url, fingerprint) in incoming.pinned_fingerprints {
+    current
+      .pinned_fingerprints
+      .entry(base_url)
+      .or_insert(fingerprint);
+  }
+  current.default_base_url = current
+    .default_base_url
+    .take()
+    .or(incoming.default_base_url);
+  current.active_profile_id = current
+    .active_profile_id
+    .take()
+    .or(incoming.active_profile_id);
+}
+
+/// Writes the bundle's keyring entries for `settings`. Merging never overwrites an entry that
+/// is already there.
+fn restore_secrets(
+  secrets: &Secrets,
+  settings: &Settings,
+  bundled: BundleSecrets,
+  mode: ImportMode,
+) -> Result<usize, BridgeError> {
+  let accounts = token_accounts(settings);
+  let base_urls = base_urls(settings);
+  let mut restored = 0;
+  for (account, stored) in bundled.tokens {
+    if !accounts.contains(&account)
+      || (mode == ImportMode::Merge && secrets.load_token_at(&account)?.is_some())
+    {
+      continue;
+    }
+    secrets.store_token_at(&account, &stored)?;
+    restored += 1;
+  }
+  for (base_url, key) in bundled.admin_keys {
+    if !base_urls.contains(&base_url)
+      || (mode == ImportMode::Merge && secrets.load_admin_key(&base_url)?.is_some())
+    {
+      continue;
+    }
+    secrets.store_admin_key(&base_url, &key)?;
+    restored += 1;
+  }
+  for (server_id, outbound) in bundled.outbounds {
+    if !settings
+      .imported_servers
+      .iter()
+      .any(|server| server.id == server_id)
+      || (mode == ImportMode::Merge && secrets.load_outbound(&server_id)?.is_some())
+    {
+      continue;
+    }
+    secrets.store_outbound(&server_id, &outbound)?;
+    restored += 1;
+  }
+  Ok(restored)
+}
+
+/// Applies `bundle`. Nothing is written unless the whole bundle is readable by this build and
+/// passes the checks `updateSettings` makes. The bridge's script, node, environment and integrity
+/// check always stay as they are here: a bundle is a file from elsewhere.
+pub fn apply(
+  config: &Config,
+  secrets: &Secrets,
+  catalog: &ServerCatalog,
+  bundle: ConfigBundle,
+  mode: ImportMode,
+) -> Result<ImportSummary, BridgeError> {
+  let incoming = parse(&bundle)?;
+  let before = config.get();
+  let mut next = before.clone();
+  match mode {
+    ImportMode::Merge => merge(&mut next, incoming),
+    ImportMode::Replace => next = incoming,
+  }
+  config::keep_protected(&before, &mut next);
+  let next = config::validate_update(&before, next, catalog)?;
+  let saved = config.update(|settings| *settings = next)?;
+  let profiles_added = saved
+    .profiles
+    .iter()
+    .filter(|profile| !before.profiles.iter().any(|known| known.id == profile.id))
+    .count();
+  if mode == ImportMode::Replace {
+    for dropped in before
+      .profiles
+      .iter()
+      .filter(|profile| !saved.profiles.iter().any(|kept| kept.id == profile.id))
+    {
+      let _ = secrets.clear_token_at(&dropped.token_ref);
+    }
+    for dropped in before.imported_servers.iter().filter(|server| {
+      !saved
+        .imported_servers
+        .iter()
+        .any(|kept| kept.id == server.id)
+    }) {
+      let _ = secrets.clear_outbound(&dropped.id);
+    }
+  }
+  let secrets_restored = match bundle.secrets {
+    Some(bundled) => restore_secrets(secrets, &saved, bundled, mode)?,
+    None => 0,
+  };
+  tracing::info!(
+    ?mode,
+    profiles_added,
+    secrets_restored,
+    "config bundle imported"
+  );
+  Ok(ImportSummary {
+    mode,
+    profiles_added,
+    secrets_restored,
+  })
+}
+
+pub fn import(
+  config: &Config,
+  secrets: &Secrets,
+  catalog: &ServerCatalog,
+  src: &Path,
+  mode: ImportMode,
+) -> Result<ImportSummary, BridgeError> {
+  let raw = std::fs::read_to_string(src)
+    .map_err(|error| BridgeError::BundleIo(format!("{}: {error}", src.display())))?;
+  let bundle: ConfigBundle = serde_json::from_str(&raw).map_err(invalid)?;
+  apply(config, secrets, catalog, bundle, mode)
+}
+
+#[allow(non_snake_case)]
+#[tauri::command]
+pub fn exportConfigBundle(
+  config: tauri::State<'_, Config>,
+  secrets: tauri::State<'_, Secrets>,
+  destPath: String,
+  includeSecrets: bool,
+) -> Result<String, BridgeError> {
+  let path = export(&config, &secrets, Path::new(&destPath), includeSecrets)?;
+  Ok(path.display().to_string())
+}
+
+#[allow(non_snake_case)]
+#[tauri::command]
+pub fn importConfigBundle(
+  config: tauri::State<'_, Config>,
+  secrets: tauri::State<'_, Secrets>,
+  catalog: tauri::State<'_, ServerCatalog>,
+  srcPath: String,
+  mode: Option<ImportMode>,
+) -> Result<ImportSummary, BridgeError> {
+  let summary = import(
+    &config,
+    &secrets,
+    &catalog,
+    Path::new(&srcPath),
+    mode.unwrap_or_default(),
+  )?;
+  catalog.set_imported(config.get().imported_servers);
+  Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::config::SCHEMA_VERSION;
+  use crate::profiles;
+  use crate::secrets::mock::MemoryStore;
+  use serde_json::json;
+
+  const TOKEN: &str = "abcDEF123_token_value";
+  const BASE_URL: &str = "https://cp.example.com";
+
+  fn fresh(dir: &tempfile::TempDir, name: &str) -> (Config, Secrets) {
+    (
+      Config::load(dir.path().join(name).join("settings.json")),
+      Secrets::new(Box::new(MemoryStore::default())),
+    )
+  }
+
+  /// A machine with one profile, a favorite and an admin key.
+  fn old_machine(dir: &tempfile::TempDir) -> (Config, Secrets, profiles::Profile) {
+    let (config, secrets) = fresh(dir, "old");
+    let profile = profiles::create(
+      &secrets,
+      &config,
+      "Work".to_string(),
+      BASE_URL.to_string(),
+      TOKEN.to_string(),
+    )
+    .unwrap();
+    config
+      .update(|settings| {
+        settings.favorite_servers = vec!["de-1".to_string()];
+        settings.kill_switch = true;
+      })
+      .unwrap();
+    secrets.store_admin_key(BASE_URL, "admin-key").unwrap();
+    (config, secrets, profile)
+  }
+
+  #[test]
+  fn round_trips_with_secrets() {
+    let dir = tempfile::tempdir().unwrap();
+    let (old, old_secrets, profile) = old_machine(&dir);
+    let path = dir.path().join("bundle.json");
+    export(&old, &old_secrets, &path, true).unwrap();
+
+    let (new, new_secrets) = fresh(&dir, "new");
+    let summary = import(
+      &new,
+      &new_secrets,
+      &ServerCatalog::default(),
+      &path,
+      ImportMode::Replace,
+    )
+    .unwrap();
+
+    assert_eq!(
+      summary,
+      ImportSummary {
+        mode: ImportMode::Replace,
+        profiles_added: 1,
+        secrets_restored: 2,
+      }
+    );
+    assert_eq!(new.get(), old.get());
+    let restored = new_secrets.load_token_at(&profile.token_ref).unwrap();
+    assert_eq!(restored.map(|stored| stored.token), Some(TOKEN.to_string()));
+    assert_eq!(
+      new_secrets.load_admin_key(BASE_URL).unwrap().as_deref(),
+      Some("admin-key")
+    );
+  }
+
+  #[test]
+  fn without_secrets_the_bundle_holds_no_token() {
+    let dir = tempfile::tempdir().unwrap();
+    let (old, old_secrets, profile) = old_machine(&dir);
+    let path = dir.path().join("bundle.json");
+    export(&old, &old_secrets, &path, false).unwrap();
+
+    let raw = std::fs::read_to_string(&path).unwrap();
+    assert!(!raw.contains(TOKEN) && !raw.contains("admin-key"));
+    assert!(!raw.contains("\"secrets\""));
+
+    let (new, new_secrets) = fresh(&dir, "new");
+    let summary = import(
+      &new,
+      &new_secrets,
+      &ServerCatalog::default(),
+      &path,
+      ImportMode::Merge,
+    )
+    .unwrap();
+    assert_eq!(summary.secrets_restored, 0);
+    assert_eq!(new.get().profiles[0], profile);
+    assert_eq!(new_secrets.load_token_at(&profile.token_ref).unwrap(), None);
+  }
+
+  #[test]
+  fn merge_keeps_local_state_and_replace_drops_it() {
+    let dir = tempfile::tempdir().unwrap();
+    let (old, old_secrets, _) = old_machine(&dir);
+    let bundle = || build(&old, &old_secrets, true).unwrap();
+
+    let (new, new_secrets) = fresh(&dir, "new");
+    // Profile ids are millisecond stamps; one created in the same millisecond on the other
+    // machine would count as the same profile.
+    std::thread::sleep(std::time::Duration::from_millis(2));
+    let local = profiles::create(
+      &new_secrets,
+      &new,
+      "Home".to_string(),
+      "https://home.example.com".to_string(),
+      "home-token".to_string(),
+    )
+    .unwrap();
+    new
+      .update(|settings| settings.favorite_servers = vec!["nl-2".to_string()])
+      .unwrap();
+
+    apply(
+      &new,
+      &new_secrets,
+      &ServerCatalog::default(),
+      bundle(),
+      ImportMode::Merge,
+    )
+    .unwrap();
+    let merged = new.get();
+    assert_eq!(merged.profiles.len(), 2);
+    assert_eq!(merged.favorite_servers, ["nl-2", "de-1"]);
+    assert_eq!(merged.active_profile_id, Some(local.id.clone()));
+    assert!(!merged.kill_switch);
+
+    let summary = apply(
+      &new,
+      &new_secrets,
+      &ServerCatalog::default(),
+      bundle(),
+      ImportMode::Replace,
+    )
+    .unwrap();
+    assert_eq!(summary.profiles_added, 0);
+    assert_eq!(new.get(), old.get());
+    assert_eq!(new_secrets.load_token_at(&local.token_ref).unwrap(), None);
+  }
+
+  #[test]
+  fn secrets_for_accounts_the_settings_do_not_name_are_ignored() {
+    let dir = tempfile::tempdir().unwrap();
+    let (old, old_secrets, _) = old_machine(&dir);
+    let mut bundle = build(&old, &old_secrets, true).unwrap();
+    let stray = bundle
+      .secrets
+      .as_ref()
+      .unwrap()
+      .tokens
+      .values()
+      .next()
+      .cloned();
+    let bundled = bundle.secrets.as_mut().unwrap();
+    bundled.tokens.insert("admin:x".to_string(), stray.unwrap());
+    bundled
+      .admin_keys
+      .insert("https://unknown.example.com".to_string(), "k".to_string());
+
+    let (new, new_secrets) = fresh(&dir, "new");
+    let summary = apply(
+      &new,
+      &new_secrets,
+      &ServerCatalog::default(),
+      bundle,
+      ImportMode::Merge,
+    )
+    .unwrap();
+
+    assert_eq!(summary.secrets_restored, 2);
+    assert_eq!(new_secrets.load_token_at("admin:x").unwrap(), None);
+  }
+
+  #[test]
+  fn imported_servers_travel_with_their_outbounds() {
+    let dir = tempfile::tempdir().unwrap();
+    let (old, old_secrets) = fresh(&dir, "old");
+    let server = crate::servers::ServerInfo {
+      id: "ext-0123456789ab".to_string(),
+      name: "Amsterdam".to_string(),
+      country: None,
+      endpoint: Some("nl.example.com:443".to_string()),
+      group: Some(crate::external_import::IMPORTED_GROUP.to_string()),
+      transports: Vec::new(),
+    };
+    let outbound = json!({ "protocol": "trojan", "settings": { "servers": [] } });
+    old_secrets.store_outbound(&server.id, &outbound).unwrap();
+    old
+      .update(|settings| settings.imported_servers = vec![server.clone()])
+      .unwrap();
+    let bundle = || build(&old, &old_secrets, true).unwrap();
+
+    for mode in [ImportMode::Merge, ImportMode::Replace] {
+      let (new, new_secrets) = fresh(&dir, &format!("{mode:?}"));
+      let summary = apply(
+        &new,
+        &new_secrets,
+        &ServerCatalog::default(),
+        bundle(),
+        mode,
+      )
+      .unwrap();
+
+      assert_eq!(summary.secrets_restored, 1);
+      assert_eq!(new.get().imported_servers, std::slice::from_ref(&server));
+      assert_eq!(
+        new_secrets.load_outbound(&server.id).unwrap(),
+        Some(outbound.clone())
+      );
+    }
+  }
+
+  #[test]
+  fn bundles_cannot_change_what_the_bridge_runs() {
+    let dir = tempfile::tempdir().unwrap();
+    let (config, secrets) = fresh(&dir, "new");
+    let path = dir.path().join("bundle.json");
+    std::fs::write(
+      &path,
+      json!({
+        "bundleVersion": BUNDLE_VERSION,
+        "settings": {
+          "autoConnect": true,
+          "bridgeScriptPath": "/tmp/evil.cjs",
+          "nodePath": "/tmp/evil",
+          "verifyBridge": false,
+          "bridgeEnv": { "env": { "NODE_OPTIONS": "--require /tmp/evil.js" } },
+        },
+      })
+      .to_string(),
+    )
+    .unwrap();
+
+    for mode in [ImportMode::Replace, ImportMode::Merge] {
+      import(&config, &secrets, &ServerCatalog::default(), &path, mode).unwrap();
+      let settings = config.get();
+      assert_eq!(settings.bridge_script_path, None);
+      assert_eq!(settings.node_path, None);
+      assert!(settings.verify_bridge);
+      assert!(settings.bridge_env.env.is_empty());
+    }
+    assert!(config.get().auto_connect, "the rest of a replace is taken");
+  }
+
+  #[cfg(unix)]
+  #[test]
+  fn bundles_are_written_owner_only() {
+    use std::os::unix::fs::PermissionsExt;
+    let dir = tempfile::tempdir().unwrap();
+    let (old, old_secrets, _) = old_machine(&dir);
+    let path = dir.path().join("bundle.json");
+
+    export(&old, &old_secrets, &path, true).unwrap();
+
+    let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+    assert_eq!(mode & 0o777, 0o600);
+  }
+
+  #[test]
+  fn newer_bundles_are_refused_before_anything_is_written() {
+    let dir = tempfile::tempdir().unwrap();
+    let (config, secrets) = fresh(&dir, "new");
+    let path = dir.path().join("bundle.json");
+
+    std::fs::write(
+      &path,
+      json!({ "bundleVersion": BUNDLE_VERSION + 1, "settings": {} }).to_string(),
+    )
+    .unwrap();
+    assert_eq!(
+      import(
+        &config,
+        &secrets,
+        &ServerCatalog::default(),
+        &path,
+        ImportMode::Replace
+      )
+      .unwrap_err(),
+      BridgeError::BundleTooNew {
+        found: BUNDLE_VERSION + 1,
+        supported: BUNDLE_VERSION,
+      }
+    );
+
+    std::fs::write(
+      &path,
+      json!({
+        "bundleVersion": BUNDLE_VERSION,
+        "settings": { "schemaVersion": SCHEMA_VERSION + 1 },
+      })
+      .to_string(),
+    )
+    .unwrap();
+    assert_eq!(
+      import(
+        &config,
+        &secrets,
+        &ServerCatalog::default(),
+        &path,
+        ImportMode::Replace
+      )
+      .unwrap_err()
+      .kind(),
+      "settingsTooNew"
+    );
+    assert!(!dir.path().join("new").join("settings.json").exists());
+
+    std::fs::write(&path, "not json").unwrap();
+    assert_eq!(
+      import(
+        &config,
+        &secrets,
+        &ServerCatalog::default(),
+        &path,
+        ImportMode::Merge
+      )
+      .unwrap_err()
+      .kind(),
+      "bundleInvalid"
+    );
+  }
+}
@@ -0,0 +1,283 @@
+use crate::bridge::Bridge;
+use crate::error::BridgeError;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tauri::{AppHandle, Emitter};
+
+pub const CONFLICT_DETECTED_EVENT: &str = "vpn://conflict-detected";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+  /// Usually harmless, like the `utun` devices macOS keeps for iCloud and Handoff.
+  Low,
+  /// Might take routes, or might be our own tunnel: a generic TUN device, a mesh VPN.
+  Medium,
+  /// Another VPN client is running or has its tunnel up; `connect` is likely to half-work.
+  High,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Conflict {
+  /// The interface or process, as the OS names it.
+  pub name: String,
+  pub detail: String,
+  pub severity: Severity,
+}
+
+/// The bridge `vpnInventory` answer.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct Inventory {
+  /// Every interface name, TUN and TAP devices included.
+  pub interfaces: Vec<String>,
+  /// Executable names of running processes, with or without a path.
+  pub processes: Vec<String>,
+}
+
+/// Process names of VPN clients, lower-case and without `.exe`.
+const KNOWN_PROCESSES: &[(&str, &str, Severity)] = &[
+  ("openvpn", "OpenVPN", Severity::High),
+  ("openvpn-gui", "OpenVPN", Severity::High),
+  ("openvpnserv", "OpenVPN", Severity::High),
+  ("wireguard", "WireGuard", Severity::High),
+  ("wireguard-go", "WireGuard", Severity::High),
+  ("openconnect", "OpenConnect", Severity::High),
+  ("vpnc", "vpnc", Severity::High),
+  ("vpnagent", "Cisco AnyConnect", Severity::High),
+  ("vpnagentd", "Cisco AnyConnect", Severity::High),
+  ("pangps", "GlobalProtect", Severity::High),
+  ("pangpa", "GlobalProtect", Severity::High),
+  ("fortitray", "FortiClient", Severity::High),
+  ("forticlient", "FortiClient", Severity::High),
+  ("nordvpnd", "NordVPN", Severity::High),
+  ("nordvpn", "NordVPN", Severity::High),
+  ("expressvpnd", "ExpressVPN", Severity::High),
+  ("expressvpn", "ExpressVPN", Severity::High),
+  ("mullvad-daemon", "Mullvad", Severity::High),
+  ("protonvpn", "Proton VPN", Severity::High),
+  ("tailscaled", "Tailscale", Severity::Medium),
+  ("zerotier-one", "ZeroTier", Severity::Medium),
+  ("sing-box", "sing-box", Severity::Medium),
+  ("clash", "Clash", Severity::Medium),
+];
+
+/// `openvpn` for `/usr/sbin/openvpn`, `C:\...\OpenVPN.exe` or `OpenVPN.EXE`.
+fn process_key(raw: &str) -> String {
+  let name = raw.trim().rsplit(['/', '\\']).next().unwrap_or_default();
+  let name = name.to_ascii_lowercase();
+  name.strip_suffix(".exe").unwrap_or(&name).to_string()
+}
+
+/// `prefix` followed by digits only, like `wg0` or `utun12`.
+fn numbered(name: &str, prefix: &str) -> bool {
+  name
+    .strip_prefix(prefix)
+    .is_some_and(|rest| !rest.is_empty() && rest.chars().all(|c| c.is_ascii_digit()))
+}
+
+fn classify_interface(name: &str) -> Option<(&'static str, Severity)> {
+  let lower = name.to_ascii_lowercase();
+  // Windows reports adapters by their friendly name.
+  if lower.contains("wireguard") || numbered(&lower, "wg") {
+    return Some(("WireGuard tunnel", Severity::High));
+  }
+  if lower.contains("tap-windows") || lower.contains("openvpn") || numbered(&lower, "tap") {
+    return Some(("TAP adapter, usually OpenVPN", Severity::High));
+  }
+  if numbered(&lower, "ppp") {
+    return Some(("PPP link (PPTP, L2TP or a modem)", Severity::Medium));
+  }
+  if lower.starts_with("tailscale") || numbered(&lower, "zt") {
+    return Some(("mesh VPN interface", Severity::Medium));
+  }
+  if numbered(&lower, "utun") {
+    return Some(("macOS TUN device", Severity::Low));
+  }
+  if numbered(&lower, "tun") {
+    return Some(("TUN device", Severity::Medium));
+  }
+  None
+}
+
+/// Everything in `inventory` that may fight over the routing table, most severe first. A
+/// client seen by several processes is listed once.
+pub fn classify(inventory: &Inventory) -> Vec<Conflict> {
+  let mut conflicts: Vec<Conflict> = inventory
+    .interfaces
+    .iter()
+    .filter_map(|name| {
+      classify_interface(name).map(|(detail, severity)| Conflict {
+        name: name.clone(),
+        detail: detail.to_string(),
+        severity,
+      })
+    })
+    .collect();
+  for raw in &inventory.processes {
+    let key = process_key(raw);
+    let Some((_, client, severity)) = KNOWN_PROCESSES.iter().find(|(name, ..)| *name == key) else {
+      continue;
+    };
+    let detail = format!("{client} is running");
+    if conflicts.iter().any(|conflict| conflict.detail == detail) {
+      continue;
+    }
+    conflicts.push(Conflict {
+      name: key,
+      detail,
+      severity: *severity,
+    });
+  }
+  conflicts.sort_by_key(|conflict| std::cmp::Reverse(conflict.severity));
+  conflicts
+}
+
+pub async fn detect(bridge: &Bridge) -> Result<Vec<Conflict>, BridgeError> {
+  let value = bridge.call("vpnInventory", Value::Null).await?;
+  let inventory: Inventory =
+    serde_json::from_value(value).map_err(|error| BridgeError::InvalidJson(error.to_string()))?;
+  Ok(classify(&inventory))
+}
+
+/// Run before a user-initiated `connect`. High-severity conflicts are announced with
+/// `vpn://conflict-detected` but don't stop the connect; a failed detection is only logged.
+pub async fn warn_before_connect(app: &AppHandle, bridge: &Bridge) {
+  let conflicts = match detect(bridge).await {
+    Ok(conflicts) => conflicts,
+    Err(error) => {
+      tracing::debug!("conflict detection failed: {error}");
+      return;
+    }
+  };
+  let high: Vec<Conflict> = conflicts
+    .into_iter()
+    .filter(|conflict| conflict.severity == Severity::High)
+    .collect();
+  if !high.is_empty() {
+    tracing::warn!(conflicts = ?high, "another VPN client may hold the routes");
+    let _ = app.emit(CONFLICT_DETECTED_EVENT, &high);
+  }
+}
+
+#[allow(non_snake_case)]
+#[tauri::command]
+pub async fn detectConflicts(
+  bridge: tauri::State<'_, Bridge>,
+) -> Result<Vec<Conflict>, BridgeError> {
+  detect(&bridge).await
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::bridge::mock::MockBridgeRunner;
+  use serde_json::json;
+  use tauri::async_runtime::block_on;
+
+  fn inventory(interfaces: &[&str], processes: &[&str]) -> Inventory {
+    Inventory {
+      interfaces: interfaces.iter().map(|name| name.to_string()).collect(),
+      processes: processes.iter().map(|name| name.to_string()).collect(),
+    }
+  }
+
+  fn severities(conflicts: &[Conflict]) -> Vec<(&str, Severity)> {
+    conflicts
+      .iter()
+      .map(|conflict| (conflict.name.as_str(), conflict.severity))
+      .collect()
+  }
+
+  #[test]
+  fn a_clean_machine_has_no_conflicts() {
+    let clean = inventory(
+      &["lo", "eth0", "wlan0", "en0", "Wi-Fi", "docker0", "tunnel"],
+      &["systemd", "/usr/bin/bash", "chrome.exe", "xray"],
+    );
+
+    assert_eq!(classify(&clean), []);
+  }
+
+  #[test]
+  fn classifies_interfaces_per_platform() {
+    let conflicts = classify(&inventory(
+      &[
+        "utun3",
+        "tun0",
+        "wg0",
+        "OpenVPN TAP-Windows6",
+        "WireGuard Tunnel",
+        "tailscale0",
+        "ppp0",
+      ],
+      &[],
+    ));
+
+    assert_eq!(
+      severities(&conflicts),
+      [
+        ("wg0", Severity::High),
+        ("OpenVPN TAP-Windows6", Severity::High),
+        ("WireGuard Tunnel", Severity::High),
+        ("tun0", Severity::Medium),
+        ("tailscale0", Severity::Medium),
+        ("ppp0", Severity::Medium),
+        ("utun3", Severity::Low),
+      ]
+    );
+  }
+
+  #[test]
+  fn recognizes_clients_by_process_name_once_each() {
+    let conflicts = classify(&inventory(
+      &[],
+      &[
+        "/usr/sbin/openvpn",
+        "C:\\Program Files\\OpenVPN\\bin\\OpenVPN.EXE",
+        "tailscaled",
+        "vpnagentd",
+        "vpnagent.exe",
+        "openvpnish",
+      ],
+    ));
+
+    assert_eq!(
+      conflicts,
+      [
+        Conflict {
+          name: "openvpn".to_string(),
+          detail: "OpenVPN is running".to_string(),
+          severity: Severity::High,
+        },
+        Conflict {
+          name: "vpnagentd".to_string(),
+          detail: "Cisco AnyConnect is running".to_string(),
+          severity: Severity::High,
+        },
+        Conflict {
+          name: "tailscaled".to_string(),
+          detail: "Tailscale is running".to_string(),
+          severity: Severity::Medium,
+        },
+      ]
+    );
+  }
+
+  #[test]
+  fn detect_asks_the_bridge_for_its_inventory() {
+    let mock = MockBridgeRunner::default().respond(
+      "vpnInventory",
+      Ok(json!({ "interfaces": ["eth0", "wg0"], "processes": ["wireguard.exe"] })),
+    );
+
+    let conflicts = block_on(detect(&Bridge::new(mock.clone()))).unwrap();
+
+    assert_eq!(
+      severities(&conflicts),
+      [("wg0", Severity::High), ("wireguard", Severity::High)]
+    );
+    assert_eq!(mock.calls(), [("vpnInventory".to_string(), Value::Null)]);
+  }
+}
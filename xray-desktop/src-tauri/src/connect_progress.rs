@@ -0,0 +1,141 @@
+use crate::error::BridgeError;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+pub const CONNECT_PROGRESS_EVENT: &str = "vpn://connect-progress";
+/// Marks a stderr line of the bridge as a progress report rather than a log line.
+const PROGRESS_PREFIX: &str = "@progress ";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConnectPhase {
+  ResolvingServer,
+  SpawningXray,
+  ConfiguringTun,
+  Handshaking,
+  Connected,
+  Failed,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ConnectProgress {
+  pub phase: ConnectPhase,
+  #[serde(default)]
+  pub detail: Option<String>,
+}
+
+impl ConnectProgress {
+  /// `@progress {"phase":"handshaking","detail":"..."}` from the bridge. The final phases come
+  /// from the connect result instead, so the bridge can't report them.
+  pub fn parse(line: &str) -> Option<Self> {
+    let progress: Self = serde_json::from_str(line.strip_prefix(PROGRESS_PREFIX)?).ok()?;
+    (!matches!(
+      progress.phase,
+      ConnectPhase::Connected | ConnectPhase::Failed
+    ))
+    .then_some(progress)
+  }
+
+  /// The last event of a connect attempt.
+  pub fn finished(result: &Result<Value, BridgeError>) -> Self {
+    match result {
+      Ok(_) => Self {
+        phase: ConnectPhase::Connected,
+        detail: None,
+      },
+      Err(error) => Self {
+        phase: ConnectPhase::Failed,
+        detail: Some(error.to_string()),
+      },
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::bridge::{BridgeCommand, NodeBridgeRunner, DEFAULT_TIMEOUT};
+  use std::sync::{Arc, Mutex};
+
+  fn progress(phase: ConnectPhase, detail: Option<&str>) -> ConnectProgress {
+    ConnectProgress {
+      phase,
+      detail: detail.map(str::to_string),
+    }
+  }
+
+  #[test]
+  fn parses_only_intermediate_progress_lines() {
+    assert_eq!(
+      ConnectProgress::parse(r#"@progress {"phase":"handshaking","detail":"vk.com:443"}"#),
+      Some(progress(ConnectPhase::Handshaking, Some("vk.com:443")))
+    );
+    assert_eq!(
+      ConnectProgress::parse(r#"@progress {"phase":"spawning_xray"}"#),
+      Some(progress(ConnectPhase::SpawningXray, None))
+    );
+    for line in [
+      r#"@progress {"phase":"connected"}"#,
+      r#"@progress {"phase":"warp_drive"}"#,
+      r#"{"phase":"handshaking"}"#,
+      "@progress not json",
+    ] {
+      assert_eq!(ConnectProgress::parse(line), None, "{line}");
+    }
+  }
+
+  #[cfg(unix)]
+  #[test]
+  fn scripted_connect_reports_phases_in_order() {
+    let dir = tempfile::tempdir().unwrap();
+    let script = dir.path().join("stub-bridge.sh");
+    std::fs::write(
+      &script,
+      r#"while read -r line; do
+id=$(printf '%s' "$line" | sed 's/.*"id":\([0-9]*\).*/\1/')
+echo '@progress {"phase":"resolving_server","detail":"srv-1"}' >&2
+echo "starting xray" >&2
+echo '@progress {"phase":"spawning_xray"}' >&2
+echo '@progress {"phase":"configuring_tun"}' >&2
+echo '@progress {"phase":"handshaking","detail":"vk.com:443"}' >&2
+case "$line" in
+  *'"action":"connect"'*) printf '{"id":%s,"ok":true,"data":{"connected":true}}\n' "$id" ;;
+  *) printf '{"id":%s,"ok":false,"error":"handshake timed out"}\n' "$id" ;;
+esac
+done
+"#,
+    )
+    .unwrap();
+    let bridge = NodeBridgeRunner::new(BridgeCommand::new("sh", &script));
+    let (sender, receiver) = std::sync::mpsc::channel();
+    let sender = Mutex::new(sender);
+    bridge.set_log_sink(Arc::new(move |line| {
+      if let Some(progress) = ConnectProgress::parse(line) {
+        let _ = sender.lock().unwrap().send(progress);
+      }
+    }));
+
+    let attempt = |action| {
+      let result = bridge.run_with_timeout(action, Value::Null, DEFAULT_TIMEOUT);
+      let mut events: Vec<ConnectProgress> = (0..4)
+        .map(|_| receiver.recv_timeout(DEFAULT_TIMEOUT).unwrap())
+        .collect();
+      events.push(ConnectProgress::finished(&result));
+      events
+    };
+    let intermediate = vec![
+      progress(ConnectPhase::ResolvingServer, Some("srv-1")),
+      progress(ConnectPhase::SpawningXray, None),
+      progress(ConnectPhase::ConfiguringTun, None),
+      progress(ConnectPhase::Handshaking, Some("vk.com:443")),
+    ];
+
+    let mut expected = intermediate.clone();
+    expected.push(progress(ConnectPhase::Connected, None));
+    assert_eq!(attempt("connect"), expected);
+
+    let mut expected = intermediate;
+    expected.push(progress(ConnectPhase::Failed, Some("handshake timed out")));
+    assert_eq!(attempt("connectFailing"), expected);
+  }
+}
@@ -0,0 +1,203 @@
+use crate::bridge::Bridge;
+use crate::commands;
+use crate::error::BridgeError;
+use crate::status::LinkState;
+use serde_json::{json, Value};
+use tauri::{AppHandle, Emitter};
+
+/// Asks the window to offer a reconnect; payload `{ reason }`.
+pub const RECONNECT_REQUIRED_EVENT: &str = "vpn://reconnect-required";
+
+/// Xray refuses to start without these; each must be an array.
+const REQUIRED_LISTS: [&str; 2] = ["inbounds", "outbounds"];
+/// Optional sections that must be objects when present.
+const OBJECT_SECTIONS: [&str; 5] = ["log", "dns", "routing", "policy", "api"];
+
+/// Every problem with `config` rather than just the first, so the editor can show them together.
+/// Only the shape xray needs to start is checked; the agent validates the rest.
+pub fn validate(config: &Value) -> Result<(), BridgeError> {
+  let Some(root) = config.as_object() else {
+    return Err(BridgeError::InvalidCoreConfig {
+      errors: vec!["the config must be a JSON object".to_string()],
+    });
+  };
+
+  let mut errors = Vec::new();
+  for key in REQUIRED_LISTS {
+    match root.get(key) {
+      None => errors.push(format!("missing required key \"{key}\"")),
+      Some(Value::Array(entries)) => {
+        for (index, entry) in entries.iter().enumerate() {
+          if !entry.get("protocol").is_some_and(Value::is_string) {
+            errors.push(format!("{key}[{index}] has no \"protocol\""));
+          }
+        }
+      }
+      Some(_) => errors.push(format!("\"{key}\" must be an array")),
+    }
+  }
+  if root
+    .get("outbounds")
+    .and_then(Value::as_array)
+    .is_some_and(Vec::is_empty)
+  {
+    errors.push("\"outbounds\" needs at least one entry".to_string());
+  }
+  for key in OBJECT_SECTIONS {
+    if root.get(key).is_some_and(|section| !section.is_object()) {
+      errors.push(format!("\"{key}\" must be an object"));
+    }
+  }
+
+  if errors.is_empty() {
+    Ok(())
+  } else {
+    Err(BridgeError::InvalidCoreConfig { errors })
+  }
+}
+
+pub async fn get(bridge: &Bridge) -> Result<Value, BridgeError> {
+  bridge.call("coreConfig", Value::Null).await
+}
+
+/// Validates and hands `config` to the agent. Returns whether the tunnel is up, in which case
+/// the new config only takes effect after a reconnect.
+pub async fn set(bridge: &Bridge, config: Value) -> Result<bool, BridgeError> {
+  validate(&config)?;
+  bridge
+    .call("setCoreConfig", json!({ "config": config }))
+    .await?;
+  Ok(commands::status(bridge).await?.state() != LinkState::Disconnected)
+}
+
+#[allow(non_snake_case)]
+#[tauri::command]
+pub async fn getCoreConfig(bridge: tauri::State<'_, Bridge>) -> Result<Value, BridgeError> {
+  get(&bridge).await
+}
+
+/// Returns whether a reconnect is needed; `vpn://reconnect-required` is emitted as well, so the
+/// prompt appears even when the editor isn't the one waiting on the answer.
+#[allow(non_snake_case)]
+#[tauri::command]
+pub async fn setCoreConfig(
+  app: AppHandle,
+  bridge: tauri::State<'_, Bridge>,
+  config: Value,
+) -> Result<bool, BridgeError> {
+  let reconnect_required = set(&bridge, config).await?;
+  if reconnect_required {
+    let _ = app.emit(RECONNECT_REQUIRED_EVENT, json!({ "reason": "coreConfig" }));
+  }
+  Ok(reconnect_required)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::bridge::mock::MockBridgeRunner;
+  use tauri::async_runtime::block_on;
+
+  fn minimal() -> Value {
+    json!({
+      "log": { "loglevel": "warning" },
+      "inbounds": [{ "tag": "socks", "port": 10808, "protocol": "socks" }],
+      "outbounds": [
+        { "tag": "proxy", "protocol": "vless", "settings": {} },
+        { "tag": "direct", "protocol": "freedom" }
+      ],
+      "routing": { "rules": [] }
+    })
+  }
+
+  fn errors(config: Value) -> Vec<String> {
+    match validate(&config) {
+      Err(BridgeError::InvalidCoreConfig { errors }) => errors,
+      other => panic!("{config} gave {other:?}"),
+    }
+  }
+
+  #[test]
+  fn accepts_a_valid_config() {
+    assert_eq!(validate(&minimal()), Ok(()));
+    assert_eq!(
+      validate(&json!({ "inbounds": [], "outbounds": [{ "protocol": "freedom" }] })),
+      Ok(())
+    );
+  }
+
+  #[test]
+  fn lists_every_missing_or_misshapen_key() {
+    assert_eq!(
+      errors(json!({ "log": {} })),
+      [
+        "missing required key \"inbounds\"",
+        "missing required key \"outbounds\""
+      ]
+    );
+    assert_eq!(
+      errors(json!({
+        "inbounds": {},
+        "outbounds": [],
+        "routing": [],
+      })),
+      [
+        "\"inbounds\" must be an array",
+        "\"outbounds\" needs at least one entry",
+        "\"routing\" must be an object"
+      ]
+    );
+    assert_eq!(
+      errors(json!({
+        "inbounds": [{ "port": 1080 }],
+        "outbounds": [{ "protocol": "vless" }, { "protocol": 7 }],
+      })),
+      [
+        "inbounds[0] has no \"protocol\"",
+        "outbounds[1] has no \"protocol\""
+      ]
+    );
+    assert_eq!(errors(json!([])), ["the config must be a JSON object"]);
+  }
+
+  #[test]
+  fn errors_reach_the_window_as_a_list() {
+    let error = validate(&json!({ "outbounds": [{ "protocol": "freedom" }] })).unwrap_err();
+    assert_eq!(
+      serde_json::to_value(&error).unwrap(),
+      json!({
+        "kind": "invalidCoreConfig",
+        "message": "Invalid core config: missing required key \"inbounds\"",
+        "errors": ["missing required key \"inbounds\""],
+      })
+    );
+  }
+
+  #[test]
+  fn an_invalid_config_never_reaches_the_bridge() {
+    let runner = MockBridgeRunner::default();
+    let bridge = Bridge::new(runner.clone());
+
+    assert!(block_on(set(&bridge, json!({}))).is_err());
+    assert!(runner.calls().is_empty());
+  }
+
+  #[test]
+  fn applying_while_connected_asks_for_a_reconnect() {
+    let connected = |connected: bool| {
+      let runner = MockBridgeRunner::default()
+        .respond("setCoreConfig", Ok(Value::Null))
+        .respond("status", Ok(json!({ "connected": connected })));
+      let bridge = Bridge::new(runner.clone());
+      let reconnect = block_on(set(&bridge, minimal())).unwrap();
+      assert_eq!(
+        runner.calls()[0],
+        ("setCoreConfig".to_string(), json!({ "config": minimal() }))
+      );
+      reconnect
+    };
+
+    assert!(connected(true));
+    assert!(!connected(false));
+  }
+}
@@ -0,0 +1,137 @@
+use crate::bridge::{Bridge, BridgeOptions, NodeBridgeRunner};
+use crate::config::Config;
+use crate::error::BridgeError;
+use crate::logs::{self, AppLog};
+use serde_json::json;
+use tauri::{AppHandle, Manager};
+use tracing_subscriber::filter::LevelFilter;
+
+/// Read by the bridge at spawn, so one respawned after a crash comes back as verbose.
+pub const LOG_LEVEL_ENV: &str = "VPNATOR_LOG_LEVEL";
+
+/// xray's `loglevel`. Off is `warning`, the agent's own default.
+pub fn xray_log_level(enabled: bool) -> &'static str {
+  if enabled {
+    "debug"
+  } else {
+    "warning"
+  }
+}
+
+/// What our own log records. Debug builds are at debug already, so only release builds change.
+pub fn tracing_level(enabled: bool) -> LevelFilter {
+  if enabled {
+    LevelFilter::DEBUG
+  } else {
+    logs::default_level()
+  }
+}
+
+/// `bridgeEnv` with the log level the bridge should start at. Without debug mode the bridge is
+/// left at its default, or at whatever `bridgeEnv` sets.
+pub fn bridge_options(mut options: BridgeOptions, enabled: bool) -> BridgeOptions {
+  if enabled {
+    options.env.insert(
+      LOG_LEVEL_ENV.to_string(),
+      xray_log_level(enabled).to_string(),
+    );
+  }
+  options
+}
+
+/// Raises our own log level at launch when debug mode was left on.
+pub fn restore(app: &AppHandle, config: &Config) {
+  if let Some(log) = app.try_state::<AppLog>() {
+    log.set_level(tracing_level(config.get().debug_mode));
+  }
+}
+
+/// Tells the running bridge first, so nothing is saved if it can't take the level. Even at
+/// debug, bridge calls are logged by action and payload field names only.
+pub async fn set(bridge: &Bridge, config: &Config, enabled: bool) -> Result<bool, BridgeError> {
+  bridge
+    .call("setLogLevel", json!({ "level": xray_log_level(enabled) }))
+    .await?;
+  config.update(|settings| settings.debug_mode = enabled)?;
+  tracing::info!(enabled, "debug mode changed");
+  Ok(enabled)
+}
+
+#[allow(non_snake_case)]
+#[tauri::command]
+pub fn getDebugMode(config: tauri::State<'_, Config>) -> bool {
+  config.get().debug_mode
+}
+
+#[allow(non_snake_case)]
+#[tauri::command]
+pub async fn setDebugMode(
+  app: AppHandle,
+  bridge: tauri::State<'_, Bridge>,
+  node: tauri::State<'_, NodeBridgeRunner>,
+  config: tauri::State<'_, Config>,
+  enabled: bool,
+) -> Result<bool, BridgeError> {
+  set(&bridge, &config, enabled).await?;
+  node.set_options(bridge_options(config.get().bridge_env, enabled));
+  if let Some(log) = app.try_state::<AppLog>() {
+    log.set_level(tracing_level(enabled));
+  }
+  Ok(enabled)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::bridge::mock::MockBridgeRunner;
+  use tauri::async_runtime::block_on;
+
+  #[test]
+  fn maps_the_mode_to_levels() {
+    assert_eq!(xray_log_level(true), "debug");
+    assert_eq!(xray_log_level(false), "warning");
+    assert_eq!(tracing_level(true), LevelFilter::DEBUG);
+    assert_eq!(tracing_level(false), logs::default_level());
+    assert!(tracing_level(false) <= LevelFilter::DEBUG);
+
+    let on = bridge_options(BridgeOptions::default(), true);
+    assert_eq!(on.env.get(LOG_LEVEL_ENV).map(String::as_str), Some("debug"));
+    assert!(bridge_options(BridgeOptions::default(), false)
+      .env
+      .is_empty());
+  }
+
+  #[test]
+  fn forwards_the_level_and_saves_the_mode() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = Config::load(dir.path().join("settings.json"));
+    let mock = MockBridgeRunner::default().respond("setLogLevel", Ok(json!({ "level": "debug" })));
+    let bridge = Bridge::new(mock.clone());
+
+    assert!(block_on(set(&bridge, &config, true)).unwrap());
+    assert!(
+      Config::load(dir.path().join("settings.json"))
+        .get()
+        .debug_mode
+    );
+    assert!(!block_on(set(&bridge, &config, false)).unwrap());
+
+    assert_eq!(
+      mock.calls(),
+      [
+        ("setLogLevel".to_string(), json!({ "level": "debug" })),
+        ("setLogLevel".to_string(), json!({ "level": "warning" })),
+      ]
+    );
+  }
+
+  #[test]
+  fn a_bridge_that_refuses_the_level_saves_nothing() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = Config::load(dir.path().join("settings.json"));
+    let bridge = Bridge::new(MockBridgeRunner::default());
+
+    assert!(block_on(set(&bridge, &config, true)).is_err());
+    assert!(!config.get().debug_mode);
+  }
+}
@@ -0,0 +1,259 @@
+use crate::bridge::Bridge;
+use crate::commands;
+use crate::config::Config;
+use crate::error::BridgeError;
+use crate::onboarding;
+use crate::secrets::Secrets;
+use serde::Serialize;
+use tauri::{App, AppHandle, Emitter, Manager, Url};
+use tauri_plugin_deep_link::DeepLinkExt;
+
+/// Must match `plugins.deep-link.desktop.schemes` in `tauri.conf.json`.
+pub const SCHEME: &str = "xraycp";
+pub const TOKEN_IMPORTED_EVENT: &str = "vpn://token-imported";
+pub const DEEP_LINK_ERROR_EVENT: &str = "vpn://deep-link-error";
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct TokenImported {
+  base_url: String,
+}
+
+/// Parsed `xraycp://import?baseUrl=<urlencoded>&token=<urlencoded>` link.
+///
+/// `baseUrl` is the control-plane URL (http or https; `import_token` decides whether http is
+/// allowed) and `token` the share token returned by `POST /users/:id/share`. The action may also
+/// arrive as a path (`xraycp:///import?...`), which is how some browsers hand the link over.
+#[derive(Clone, PartialEq)]
+pub struct ImportLink {
+  pub base_url: String,
+  pub token: String,
+}
+
+impl std::fmt::Debug for ImportLink {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.debug_struct("ImportLink")
+      .field("base_url", &self.base_url)
+      .field("token", &"<redacted>")
+      .finish()
+  }
+}
+
+pub fn parse_import_link(raw: &str) -> Result<ImportLink, BridgeError> {
+  let invalid = |reason: &str| BridgeError::InvalidDeepLink(reason.to_string());
+  let url = Url::parse(raw).map_err(|_| invalid("malformed deep link URL"))?;
+
+  if url.scheme() != SCHEME {
+    return Err(invalid("unsupported deep link scheme"));
+  }
+
+  let action = match url.host_str() {
+    Some(host) if !host.is_empty() => host.to_string(),
+    _ => url.path().trim_start_matches('/').to_string(),
+  };
+  if !action.eq_ignore_ascii_case("import") {
+    return Err(invalid("unsupported deep link action"));
+  }
+
+  let param = |name: &str| {
+    url
+      .query_pairs()
+      .find(|(key, _)| key == name)
+      .map(|(_, value)| value.trim().to_string())
+      .filter(|value| !value.is_empty())
+  };
+  let base_url = param("baseUrl").ok_or_else(|| invalid("missing baseUrl parameter"))?;
+  let token = param("token").ok_or_else(|| invalid("missing token parameter"))?;
+
+  match Url::parse(&base_url) {
+    Ok(parsed) if matches!(parsed.scheme(), "http" | "https") => {}
+    Ok(_) => return Err(invalid("baseUrl must use http or https")),
+    Err(_) => return Err(invalid("invalid baseUrl parameter")),
+  }
+
+  let well_formed = token.len() >= 16
+    && token
+      .chars()
+      .all(|ch| ch.is_ascii_alphanumeric() || ch == '_' || ch == '-');
+  if !well_formed {
+    return Err(invalid("invalid token format"));
+  }
+
+  Ok(ImportLink { base_url, token })
+}
+
+/// Makes the OS route `xraycp://` links to this app.
+///
+/// Linux and Windows register the scheme at runtime. On macOS the scheme is declared in the
+/// bundle's `Info.plist` (generated from the deep-link plugin config) and Launch Services routes
+/// links to the running instance itself; links arrive through the plugin's `on_open_url`.
+pub fn register(app: &App) -> Result<(), tauri_plugin_deep_link::Error> {
+  #[cfg(any(target_os = "linux", target_os = "windows"))]
+  app.deep_link().register_all()?;
+
+  let handle = app.handle().clone();
+  app.deep_link().on_open_url(move |event| {
+    let urls: Vec<String> = event.urls().into_iter().map(String::from).collect();
+    handle_urls(&handle, urls);
+  });
+
+  // The link the app was launched with (if any) is not replayed through `on_open_url`.
+  if let Ok(Some(urls)) = app.deep_link().get_current() {
+    handle_urls(app.handle(), urls.into_iter().map(String::from).collect());
+  }
+
+  Ok(())
+}
+
+/// The first valid import link among `urls`, or the reason the first one was rejected.
+pub fn pick_link(urls: &[String]) -> Result<ImportLink, BridgeError> {
+  let mut first_error = None;
+  for url in urls {
+    match parse_import_link(url) {
+      Ok(link) => return Ok(link),
+      Err(error) => {
+        first_error.get_or_insert(error);
+      }
+    }
+  }
+  Err(first_error.unwrap_or_else(|| BridgeError::InvalidDeepLink("no deep link URL".to_string())))
+}
+
+/// Imports the first valid link among `urls` through the regular `importToken` flow, then emits
+/// `vpn://token-imported`. A successful import also completes onboarding. Rejected links and
+/// failed imports emit `vpn://deep-link-error`.
+pub fn handle_urls(app: &AppHandle, urls: Vec<String>) {
+  if urls.is_empty() {
+    return;
+  }
+  let link = match pick_link(&urls) {
+    Ok(link) => link,
+    Err(error) => {
+      let _ = app.emit(DEEP_LINK_ERROR_EVENT, error);
+      return;
+    }
+  };
+
+  let app = app.clone();
+  tauri::async_runtime::spawn(async move {
+    let (Some(bridge), Some(secrets), Some(config)) = (
+      app.try_state::<Bridge>(),
+      app.try_state::<Secrets>(),
+      app.try_state::<Config>(),
+    ) else {
+      return;
+    };
+    let base_url = link.base_url.clone();
+    match commands::import_token(&bridge, &secrets, &config, link.base_url, link.token).await {
+      Ok(()) => {
+        if let Err(error) = onboarding::complete(&config) {
+          tracing::warn!("failed to mark onboarding complete: {error}");
+        }
+        let _ = app.emit(TOKEN_IMPORTED_EVENT, TokenImported { base_url });
+      }
+      Err(error) => {
+        let _ = app.emit(DEEP_LINK_ERROR_EVENT, error);
+      }
+    }
+  });
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn reason(raw: &str) -> String {
+    match parse_import_link(raw) {
+      Err(BridgeError::InvalidDeepLink(reason)) => reason,
+      other => panic!("expected an invalid link, got {:?}", other),
+    }
+  }
+
+  #[test]
+  fn parses_documented_import_link() {
+    assert_eq!(
+      parse_import_link(
+        "xraycp://import?baseUrl=https%3A%2F%2Fcp.example.com&token=abcDEF123_token_value"
+      )
+      .unwrap(),
+      ImportLink {
+        base_url: "https://cp.example.com".to_string(),
+        token: "abcDEF123_token_value".to_string(),
+      }
+    );
+  }
+
+  #[test]
+  fn accepts_action_as_path() {
+    let link = parse_import_link(
+      "xraycp:///import?baseUrl=http://10.0.0.1:8080&token=abcDEF123_token_value",
+    )
+    .unwrap();
+
+    assert_eq!(link.base_url, "http://10.0.0.1:8080");
+  }
+
+  #[test]
+  fn rejects_malformed_links() {
+    assert_eq!(
+      reason("https://import?baseUrl=https://cp.example.com&token=abcDEF123_token_value"),
+      "unsupported deep link scheme"
+    );
+    assert_eq!(
+      reason("xraycp://export?baseUrl=https://cp.example.com&token=abcDEF123_token_value"),
+      "unsupported deep link action"
+    );
+    assert_eq!(
+      reason("xraycp://import?baseUrl=ftp://cp.example.com&token=abcDEF123_token_value"),
+      "baseUrl must use http or https"
+    );
+    assert_eq!(
+      reason("xraycp://import?baseUrl=https://cp.example.com&token=short"),
+      "invalid token format"
+    );
+    assert_eq!(reason("not a url"), "malformed deep link URL");
+  }
+
+  #[test]
+  fn rejects_missing_params() {
+    assert_eq!(
+      reason("xraycp://import?token=abcDEF123_token_value"),
+      "missing baseUrl parameter"
+    );
+    assert_eq!(
+      reason("xraycp://import?baseUrl=https://cp.example.com&token="),
+      "missing token parameter"
+    );
+    assert_eq!(reason("xraycp://import"), "missing baseUrl parameter");
+  }
+
+  #[test]
+  fn decodes_url_encoded_token() {
+    let link = parse_import_link(
+      "xraycp://import?baseUrl=https%3A%2F%2Fcp.example.com%2Fapi&token=abc%2DDEF%5F123%5Ftoken%5Fvalue",
+    )
+    .unwrap();
+
+    assert_eq!(link.base_url, "https://cp.example.com/api");
+    assert_eq!(link.token, "abc-DEF_123_token_value");
+  }
+
+  #[test]
+  fn picks_the_first_valid_link() {
+    let urls = vec![
+      "https://example.com".to_string(),
+      "xraycp://import?baseUrl=https://cp.example.com&token=abcDEF123_token_value".to_string(),
+    ];
+
+    assert_eq!(
+      pick_link(&urls).unwrap().token,
+      "abcDEF123_token_value".to_string()
+    );
+    assert_eq!(
+      pick_link(&urls[..1]),
+      Err(BridgeError::InvalidDeepLink(
+        "unsupported deep link scheme".to_string()
+      ))
+    );
+  }
+}
@@ -0,0 +1,274 @@
+use crate::bridge::Bridge;
+use crate::commands;
+use crate::error::BridgeError;
+use crate::event_batch;
+use crate::reconnect::Watchdog;
+use crate::status;
+use serde::Serialize;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tauri::async_runtime::JoinHandle;
+use tauri::{AppHandle, Emitter, Manager};
+
+pub const TIMER_WARNING_EVENT: &str = "vpn://timer-warning";
+/// How long before the disconnect `vpn://timer-warning` goes out, for the UI to offer more time.
+const WARNING_LEAD: Duration = Duration::from_secs(60);
+pub const MAX_MINUTES: u32 = 24 * 60;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TimerStatus {
+  pub remaining_secs: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimerEvent {
+  /// `WARNING_LEAD` is left, or all of it for a timer shorter than that.
+  Warning(TimerStatus),
+  Fired,
+}
+
+/// Receives the armed timer's events, from its own task.
+pub type TimerSink = Arc<dyn Fn(TimerEvent) + Send + Sync>;
+
+struct Armed {
+  deadline: Instant,
+  task: JoinHandle<()>,
+}
+
+/// The one-shot auto-disconnect, if armed. In memory only: a restart forgets it.
+#[derive(Default)]
+pub struct DisconnectTimer {
+  armed: Mutex<Option<Armed>>,
+}
+
+impl DisconnectTimer {
+  /// Starts counting `duration` down, replacing whatever timer was armed, with the warning
+  /// `lead` before the end.
+  pub fn arm(&self, duration: Duration, lead: Duration, sink: TimerSink) -> TimerStatus {
+    let deadline = Instant::now() + duration;
+    let task = tauri::async_runtime::spawn(async move {
+      let warn_after = duration.saturating_sub(lead);
+      tokio::time::sleep(warn_after).await;
+      sink(TimerEvent::Warning(TimerStatus {
+        remaining_secs: (duration - warn_after).as_secs(),
+      }));
+      tokio::time::sleep_until(deadline.into()).await;
+      sink(TimerEvent::Fired);
+    });
+    if let Some(previous) = self.armed.lock().unwrap().replace(Armed { deadline, task }) {
+      previous.task.abort();
+    }
+    TimerStatus {
+      remaining_secs: duration.as_secs(),
+    }
+  }
+
+  /// Returns whether a timer was pending.
+  pub fn cancel(&self) -> bool {
+    let Some(armed) = self.armed.lock().unwrap().take() else {
+      return false;
+    };
+    armed.task.abort();
+    armed.deadline > Instant::now()
+  }
+
+  /// `None` when nothing is armed or the timer already fired.
+  pub fn status(&self) -> Option<TimerStatus> {
+    let armed = self.armed.lock().unwrap();
+    let remaining = armed
+      .as_ref()?
+      .deadline
+      .checked_duration_since(Instant::now())?;
+    Some(TimerStatus {
+      remaining_secs: remaining.as_secs_f64().ceil() as u64,
+    })
+  }
+}
+
+pub fn duration_for(minutes: u32) -> Result<Duration, BridgeError> {
+  if !(1..=MAX_MINUTES).contains(&minutes) {
+    return Err(BridgeError::InvalidTimer(format!(
+      "{minutes} minutes is out of range, expected 1 to {MAX_MINUTES}"
+    )));
+  }
+  Ok(Duration::from_secs(u64::from(minutes) * 60))
+}
+
+/// Emits the warning, then disconnects like the Disconnect button would.
+fn app_sink(app: &AppHandle) -> TimerSink {
+  let app = app.clone();
+  Arc::new(move |event| match event {
+    TimerEvent::Warning(status) => {
+      let _ = app.emit(TIMER_WARNING_EVENT, status);
+    }
+    TimerEvent::Fired => {
+      let app = app.clone();
+      tauri::async_runtime::spawn(async move {
+        let (Some(bridge), Some(watchdog)) =
+          (app.try_state::<Bridge>(), app.try_state::<Watchdog>())
+        else {
+          return;
+        };
+        tracing::info!("disconnect timer fired");
+        status::expect_change(&app);
+        if let Err(error) = commands::disconnect(&bridge, &watchdog).await {
+          tracing::warn!(kind = error.kind(), "timed disconnect failed: {error}");
+        }
+        event_batch::flush(&app);
+      });
+    }
+  })
+}
+
+/// Called once the tunnel is down, however that happened, so a timer from this session doesn't
+/// cut the next one short.
+pub fn cancel(app: &AppHandle) {
+  if let Some(timer) = app.try_state::<DisconnectTimer>() {
+    timer.cancel();
+  }
+}
+
+/// Arming again replaces the running timer, which is how the UI extends it.
+#[allow(non_snake_case)]
+#[tauri::command]
+pub fn setDisconnectTimer(
+  app: AppHandle,
+  timer: tauri::State<'_, DisconnectTimer>,
+  minutes: u32,
+) -> Result<TimerStatus, BridgeError> {
+  let duration = duration_for(minutes)?;
+  Ok(timer.arm(duration, WARNING_LEAD, app_sink(&app)))
+}
+
+#[allow(non_snake_case)]
+#[tauri::command]
+pub fn cancelDisconnectTimer(timer: tauri::State<'_, DisconnectTimer>) -> bool {
+  timer.cancel()
+}
+
+#[allow(non_snake_case)]
+#[tauri::command]
+pub fn getDisconnectTimer(timer: tauri::State<'_, DisconnectTimer>) -> Option<TimerStatus> {
+  timer.status()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  type Events = Arc<Mutex<Vec<(TimerEvent, Instant)>>>;
+
+  fn recording() -> (TimerSink, Events) {
+    let events: Events = Arc::default();
+    let sink = events.clone();
+    (
+      Arc::new(move |event| sink.lock().unwrap().push((event, Instant::now()))),
+      events,
+    )
+  }
+
+  fn kinds(events: &Events) -> Vec<TimerEvent> {
+    events
+      .lock()
+      .unwrap()
+      .iter()
+      .map(|(event, _)| *event)
+      .collect()
+  }
+
+  fn sleep(ms: u64) {
+    std::thread::sleep(Duration::from_millis(ms));
+  }
+
+  #[test]
+  fn warns_a_lead_before_firing_once() {
+    let timer = DisconnectTimer::default();
+    let (sink, events) = recording();
+    let armed_at = Instant::now();
+
+    timer.arm(Duration::from_millis(300), Duration::from_millis(100), sink);
+    assert!(timer.status().is_some());
+    sleep(450);
+
+    let events = events.lock().unwrap();
+    assert_eq!(
+      events.iter().map(|(event, _)| *event).collect::<Vec<_>>(),
+      [
+        TimerEvent::Warning(TimerStatus { remaining_secs: 0 }),
+        TimerEvent::Fired
+      ]
+    );
+    let warned = events[0].1 - armed_at;
+    let fired = events[1].1 - armed_at;
+    assert!(warned >= Duration::from_millis(200));
+    assert!(fired >= Duration::from_millis(300));
+    assert!(fired - warned >= Duration::from_millis(90));
+    assert_eq!(timer.status(), None);
+  }
+
+  #[test]
+  fn cancel_stops_both_events() {
+    let timer = DisconnectTimer::default();
+    let (sink, events) = recording();
+
+    timer.arm(Duration::from_millis(200), Duration::from_millis(100), sink);
+    assert!(timer.cancel());
+    assert!(!timer.cancel());
+    sleep(300);
+
+    assert_eq!(kinds(&events), []);
+    assert_eq!(timer.status(), None);
+  }
+
+  #[test]
+  fn arming_again_replaces_the_timer() {
+    let timer = DisconnectTimer::default();
+    let (first, first_events) = recording();
+    let (second, second_events) = recording();
+
+    timer.arm(Duration::from_millis(150), Duration::from_millis(50), first);
+    timer.arm(
+      Duration::from_millis(400),
+      Duration::from_millis(50),
+      second,
+    );
+    sleep(250);
+
+    assert_eq!(kinds(&first_events), []);
+    assert_eq!(kinds(&second_events), []);
+    sleep(250);
+    assert_eq!(kinds(&first_events), []);
+    assert_eq!(
+      kinds(&second_events),
+      [
+        TimerEvent::Warning(TimerStatus { remaining_secs: 0 }),
+        TimerEvent::Fired
+      ]
+    );
+  }
+
+  #[test]
+  fn a_timer_shorter_than_the_lead_warns_right_away() {
+    let timer = DisconnectTimer::default();
+    let (sink, events) = recording();
+
+    let armed = timer.arm(Duration::from_secs(30), WARNING_LEAD, sink);
+    sleep(50);
+
+    assert_eq!(armed.remaining_secs, 30);
+    assert_eq!(
+      kinds(&events),
+      [TimerEvent::Warning(TimerStatus { remaining_secs: 30 })]
+    );
+    assert_eq!(timer.status(), Some(TimerStatus { remaining_secs: 30 }));
+    timer.cancel();
+  }
+
+  #[test]
+  fn minutes_are_range_checked() {
+    assert_eq!(duration_for(30).unwrap(), Duration::from_secs(1800));
+    assert_eq!(duration_for(0).unwrap_err().kind(), "invalidTimer");
+    assert!(duration_for(MAX_MINUTES + 1).is_err());
+  }
+}
@@ -0,0 +1,681 @@
+use crate::base_url::normalize_base_url;
+use crate::bridge::Bridge;
+use crate::bridge_pool::BridgePool;
+use crate::commands;
+use crate::config::Config;
+use crate::error::BridgeError;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DisguiseUpdate {
+  pub server_id: String,
+  pub disguise: Value,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BatchResult {
+  pub server_id: String,
+  pub ok: bool,
+  /// `{ kind, message }` like any command failure; `null` on success.
+  pub error: Option<BridgeError>,
+}
+
+/// What the panel would serve with a disguise, rendered without saving it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DisguisePreview {
+  #[serde(default)]
+  pub headers: BTreeMap<String, String>,
+  #[serde(default)]
+  pub sample_response: String,
+  pub valid: bool,
+  #[serde(default)]
+  pub warnings: Vec<String>,
+}
+
+/// One top-level disguise key that differs; `null` stands for a key that is absent.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FieldChange {
+  pub field: String,
+  #[serde(default)]
+  pub from: Value,
+  #[serde(default)]
+  pub to: Value,
+}
+
+/// What `updateDisguise` would change, worked out by the bridge from the server's current
+/// disguise without saving anything.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DisguiseDiff {
+  #[serde(default)]
+  pub changed_fields: Vec<FieldChange>,
+  pub current: Value,
+  pub proposed: Value,
+}
+
+/// The panel's answer when applied, the diff on a dry run.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(untagged)]
+pub enum UpdateOutcome {
+  Applied(Value),
+  DryRun(DisguiseDiff),
+}
+
+/// How the UI should edit a template parameter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum FieldType {
+  String,
+  Number,
+  Boolean,
+  /// One of `TemplateField::options`.
+  Select,
+  /// `host:port`, like `dest`.
+  HostPort,
+  /// A type this build doesn't know yet; edit it as text.
+  #[serde(other)]
+  Other,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TemplateField {
+  /// Disguise key the value is written to, e.g. `serverName`.
+  pub key: String,
+  #[serde(default)]
+  pub label: Option<String>,
+  #[serde(rename = "type")]
+  pub field_type: FieldType,
+  #[serde(default)]
+  pub required: bool,
+  #[serde(default)]
+  pub default: Option<Value>,
+  #[serde(default)]
+  pub options: Vec<String>,
+}
+
+/// A disguise preset offered by the panel, with the parameters an admin may change.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DisguiseTemplate {
+  pub id: String,
+  pub name: String,
+  #[serde(default)]
+  pub description: Option<String>,
+  #[serde(default)]
+  pub fields: Vec<TemplateField>,
+}
+
+/// Catches disguises the panel would reject anyway: `serverName` must be a non-empty string and
+/// `dest` a `host:port`.
+pub fn validate(disguise: &Value) -> Result<(), BridgeError> {
+  let invalid = |reason: &str| BridgeError::InvalidDisguise(reason.to_string());
+  let fields = disguise
+    .as_object()
+    .ok_or_else(|| invalid("disguise must be an object"))?;
+  let text = |key: &str| {
+    fields
+      .get(key)
+      .and_then(Value::as_str)
+      .map(str::trim)
+      .filter(|value| !value.is_empty())
+  };
+
+  text("serverName").ok_or_else(|| invalid("serverName is required"))?;
+  let dest = text("dest").ok_or_else(|| invalid("dest is required"))?;
+  let port = dest.rsplit_once(':').and_then(|(host, port)| {
+    let port = port.parse::<u16>().ok()?;
+    (!host.is_empty() && port != 0).then_some(port)
+  });
+  if port.is_none() {
+    return Err(invalid("dest must be host:port"));
+  }
+  if fields
+    .get("fingerprint")
+    .is_some_and(|value| !value.is_string())
+  {
+    return Err(invalid("fingerprint must be a string"));
+  }
+  Ok(())
+}
+
+/// Validates `disguise` first on both paths. A dry run asks the bridge for the diff instead of
+/// pushing the change.
+pub async fn update(
+  bridge: &Bridge,
+  base_url: String,
+  server_id: String,
+  admin_api_key: String,
+  disguise: Value,
+  dry_run: bool,
+) -> Result<UpdateOutcome, BridgeError> {
+  validate(&disguise)?;
+  if !dry_run {
+    return commands::update_disguise(bridge, base_url, server_id, admin_api_key, disguise)
+      .await
+      .map(UpdateOutcome::Applied);
+  }
+  let value = bridge
+    .call(
+      "updateDisguise",
+      json!({
+        "baseUrl": base_url,
+        "serverId": server_id,
+        "adminApiKey": admin_api_key,
+        "disguise": disguise,
+        "dryRun": true
+      }),
+    )
+    .await?;
+  serde_json::from_value(value)
+    .map(UpdateOutcome::DryRun)
+    .map_err(|error| BridgeError::InvalidJson(error.to_string()))
+}
+
+pub async fn test(
+  bridge: &Bridge,
+  base_url: String,
+  server_id: String,
+  admin_api_key: String,
+  disguise: Value,
+) -> Result<DisguisePreview, BridgeError> {
+  validate(&disguise)?;
+  let value = bridge
+    .call(
+      "testDisguise",
+      json!({
+        "baseUrl": base_url,
+        "serverId": server_id,
+        "adminApiKey": admin_api_key,
+        "disguise": disguise
+      }),
+    )
+    .await?;
+  serde_json::from_value(value).map_err(|error| BridgeError::InvalidJson(error.to_string()))
+}
+
+pub async fn list_templates(
+  bridge: &Bridge,
+  base_url: String,
+  admin_api_key: String,
+) -> Result<Vec<DisguiseTemplate>, BridgeError> {
+  if admin_api_key.trim().is_empty() {
+    return Err(BridgeError::MissingAdminKey);
+  }
+  let value = bridge
+    .call(
+      "listDisguiseTemplates",
+      json!({ "baseUrl": base_url, "adminApiKey": admin_api_key }),
+    )
+    .await?;
+  if value.is_null() {
+    return Ok(Vec::new());
+  }
+  serde_json::from_value(value).map_err(|error| BridgeError::InvalidJson(error.to_string()))
+}
+
+/// Runs `updateDisguise` for every entry through `pool`. A failure only marks its own server;
+/// results come back in the order of `updates`.
+pub async fn batch_update(
+  bridge: &Bridge,
+  pool: &BridgePool,
+  base_url: String,
+  admin_api_key: String,
+  updates: Vec<DisguiseUpdate>,
+) -> Vec<BatchResult> {
+  let tasks: Vec<_> = updates
+    .into_iter()
+    .map(|update| {
+      let bridge = bridge.clone();
+      let base_url = base_url.clone();
+      let admin_api_key = admin_api_key.clone();
+      let server_id = update.server_id.clone();
+      let task = pool.spawn(async move {
+        commands::update_disguise(
+          &bridge,
+          base_url,
+          update.server_id,
+          admin_api_key,
+          update.disguise,
+        )
+        .await
+      });
+      (server_id, task)
+    })
+    .collect();
+
+  let mut results = Vec::with_capacity(tasks.len());
+  for (server_id, task) in tasks {
+    let outcome = task
+      .await
+      .unwrap_or_else(|error| Err(BridgeError::SpawnFailed(error.to_string())));
+    results.push(BatchResult {
+      server_id,
+      ok: outcome.is_ok(),
+      error: outcome.err(),
+    });
+  }
+  results
+}
+
+#[allow(non_snake_case)]
+#[tauri::command]
+pub async fn batchUpdateDisguise(
+  bridge: tauri::State<'_, Bridge>,
+  pool: tauri::State<'_, BridgePool>,
+  config: tauri::State<'_, Config>,
+  baseUrl: String,
+  adminApiKey: String,
+  updates: Vec<DisguiseUpdate>,
+) -> Result<Vec<BatchResult>, BridgeError> {
+  let base_url = normalize_base_url(&baseUrl, config.get().allow_insecure_base_url)?;
+  Ok(batch_update(&bridge, &pool, base_url, adminApiKey, updates).await)
+}
+
+#[allow(non_snake_case)]
+#[tauri::command]
+pub async fn testDisguise(
+  bridge: tauri::State<'_, Bridge>,
+  config: tauri::State<'_, Config>,
+  baseUrl: String,
+  serverId: String,
+  adminApiKey: String,
+  disguise: Value,
+) -> Result<DisguisePreview, BridgeError> {
+  let base_url = normalize_base_url(&baseUrl, config.get().allow_insecure_base_url)?;
+  test(&bridge, base_url, serverId, adminApiKey, disguise).await
+}
+
+#[allow(non_snake_case)]
+#[tauri::command]
+pub async fn listDisguiseTemplates(
+  bridge: tauri::State<'_, Bridge>,
+  config: tauri::State<'_, Config>,
+  baseUrl: String,
+  adminApiKey: String,
+) -> Result<Vec<DisguiseTemplate>, BridgeError> {
+  let base_url = normalize_base_url(&baseUrl, config.get().allow_insecure_base_url)?;
+  list_templates(&bridge, base_url, adminApiKey).await
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::bridge::mock::MockBridgeRunner;
+  use crate::bridge::BridgeRunner;
+  use std::sync::atomic::{AtomicUsize, Ordering};
+  use std::sync::Arc;
+  use std::time::Duration;
+
+  /// Fails the servers in `failing` as an HTTP 502 from the panel, tracking overlapping calls.
+  #[derive(Clone, Default)]
+  struct FakePanel {
+    failing: Vec<&'static str>,
+    in_flight: Arc<AtomicUsize>,
+    max_in_flight: Arc<AtomicUsize>,
+  }
+
+  impl BridgeRunner for FakePanel {
+    fn run(&self, _action: &str, payload: Value) -> Result<Value, BridgeError> {
+      let now = self.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+      self.max_in_flight.fetch_max(now, Ordering::SeqCst);
+      std::thread::sleep(Duration::from_millis(20));
+      self.in_flight.fetch_sub(1, Ordering::SeqCst);
+
+      let server_id = payload["serverId"].as_str().unwrap_or_default();
+      if self.failing.contains(&server_id) {
+        return Err(BridgeError::BridgeReported {
+          code: None,
+          message: "Disguise update failed: HTTP 502".to_string(),
+        });
+      }
+      Ok(json!({ "serverId": server_id }))
+    }
+  }
+
+  fn updates(ids: &[&str]) -> Vec<DisguiseUpdate> {
+    ids
+      .iter()
+      .map(|id| DisguiseUpdate {
+        server_id: id.to_string(),
+        disguise: json!({ "serverName": "vk.com", "dest": "vk.com:443" }),
+      })
+      .collect()
+  }
+
+  fn run(panel: FakePanel, ids: &[&str]) -> Vec<BatchResult> {
+    tauri::async_runtime::block_on(batch_update(
+      &Bridge::new(panel),
+      &BridgePool::new(4),
+      "https://cp.example.com".to_string(),
+      "admin-key".to_string(),
+      updates(ids),
+    ))
+  }
+
+  #[test]
+  fn partial_failures_are_reported_per_server() {
+    let panel = FakePanel {
+      failing: vec!["srv-2", "srv-4"],
+      ..Default::default()
+    };
+
+    let results = run(panel, &["srv-1", "srv-2", "srv-3", "srv-4"]);
+
+    let outcome: Vec<(&str, bool)> = results
+      .iter()
+      .map(|result| (result.server_id.as_str(), result.ok))
+      .collect();
+    assert_eq!(
+      outcome,
+      [
+        ("srv-1", true),
+        ("srv-2", false),
+        ("srv-3", true),
+        ("srv-4", false)
+      ]
+    );
+    assert_eq!(
+      serde_json::to_value(&results[1]).unwrap(),
+      json!({
+        "serverId": "srv-2",
+        "ok": false,
+        "error": { "kind": "bridgeReported", "message": "Disguise update failed: HTTP 502" }
+      })
+    );
+    assert_eq!(results[0].error, None);
+  }
+
+  #[test]
+  fn runs_a_bounded_number_of_updates_at_once() {
+    let panel = FakePanel::default();
+    let ids: Vec<String> = (0..12).map(|n| format!("srv-{n}")).collect();
+    let ids: Vec<&str> = ids.iter().map(String::as_str).collect();
+
+    let results = run(panel.clone(), &ids);
+
+    assert!(results.iter().all(|result| result.ok));
+    let max = panel.max_in_flight.load(Ordering::SeqCst);
+    assert!(max <= 4, "{max} updates overlapped");
+    assert!(max > 1, "updates ran one by one");
+  }
+
+  fn reason(disguise: Value) -> String {
+    match validate(&disguise) {
+      Err(BridgeError::InvalidDisguise(reason)) => reason,
+      other => panic!("expected an invalid disguise, got {other:?}"),
+    }
+  }
+
+  #[test]
+  fn accepts_the_disguise_the_frontend_sends() {
+    let disguise = json!({ "serverName": "vk.com", "dest": "vk.com:443", "fingerprint": "chrome" });
+
+    assert_eq!(validate(&disguise), Ok(()));
+    assert_eq!(
+      validate(&json!({ "serverName": "vk.com", "dest": "[2001:db8::1]:8443" })),
+      Ok(())
+    );
+  }
+
+  #[test]
+  fn rejects_obviously_broken_disguises() {
+    assert_eq!(reason(json!("vk.com")), "disguise must be an object");
+    assert_eq!(
+      reason(json!({ "dest": "vk.com:443" })),
+      "serverName is required"
+    );
+    assert_eq!(
+      reason(json!({ "serverName": " ", "dest": "vk.com:443" })),
+      "serverName is required"
+    );
+    assert_eq!(
+      reason(json!({ "serverName": "vk.com" })),
+      "dest is required"
+    );
+    for dest in ["vk.com", "vk.com:https", ":443", "vk.com:0", "vk.com:70000"] {
+      assert_eq!(
+        reason(json!({ "serverName": "vk.com", "dest": dest })),
+        "dest must be host:port",
+        "{dest}"
+      );
+    }
+    assert_eq!(
+      reason(json!({ "serverName": "vk.com", "dest": "vk.com:443", "fingerprint": 1 })),
+      "fingerprint must be a string"
+    );
+  }
+
+  #[test]
+  fn invalid_disguise_never_reaches_the_bridge() {
+    let mock = MockBridgeRunner::default();
+
+    let result = tauri::async_runtime::block_on(test(
+      &Bridge::new(mock.clone()),
+      "https://cp.example.com".to_string(),
+      "srv-1".to_string(),
+      "admin-key".to_string(),
+      json!({ "serverName": "vk.com" }),
+    ));
+
+    assert_eq!(result.unwrap_err().kind(), "invalidDisguise");
+    assert!(mock.calls().is_empty());
+  }
+
+  #[test]
+  fn deserializes_a_preview() {
+    let mock = MockBridgeRunner::default().respond(
+      "testDisguise",
+      Ok(json!({
+        "headers": { "server": "nginx", "content-type": "text/html" },
+        "sampleResponse": "<!doctype html><title>VK</title>",
+        "valid": true,
+        "warnings": ["dest answers with HTTP/1.1 only"],
+        "renderedAt": "2026-10-14T10:00:00Z"
+      })),
+    );
+
+    let preview = tauri::async_runtime::block_on(test(
+      &Bridge::new(mock),
+      "https://cp.example.com".to_string(),
+      "srv-1".to_string(),
+      "admin-key".to_string(),
+      json!({ "serverName": "vk.com", "dest": "vk.com:443" }),
+    ))
+    .unwrap();
+
+    assert_eq!(
+      preview,
+      DisguisePreview {
+        headers: BTreeMap::from([
+          ("content-type".to_string(), "text/html".to_string()),
+          ("server".to_string(), "nginx".to_string()),
+        ]),
+        sample_response: "<!doctype html><title>VK</title>".to_string(),
+        valid: true,
+        warnings: vec!["dest answers with HTTP/1.1 only".to_string()],
+      }
+    );
+    assert_eq!(
+      serde_json::from_value::<DisguisePreview>(json!({ "valid": false })).unwrap(),
+      DisguisePreview {
+        headers: BTreeMap::new(),
+        sample_response: String::new(),
+        valid: false,
+        warnings: Vec::new(),
+      }
+    );
+  }
+
+  fn update_with(mock: &MockBridgeRunner, dry_run: bool) -> Result<UpdateOutcome, BridgeError> {
+    tauri::async_runtime::block_on(update(
+      &Bridge::new(mock.clone()),
+      "https://cp.example.com".to_string(),
+      "srv-1".to_string(),
+      "admin-key".to_string(),
+      json!({ "serverName": "vk.com", "dest": "vk.com:443" }),
+      dry_run,
+    ))
+  }
+
+  #[test]
+  fn a_dry_run_returns_the_diff() {
+    let mock = MockBridgeRunner::default().respond(
+      "updateDisguise",
+      Ok(json!({
+        "changedFields": [
+          { "field": "dest", "from": "ya.ru:443", "to": "vk.com:443" },
+          { "field": "fingerprint", "from": "chrome" },
+          { "field": "serverName", "from": "ya.ru", "to": "vk.com" }
+        ],
+        "current": { "serverName": "ya.ru", "dest": "ya.ru:443", "fingerprint": "chrome" },
+        "proposed": { "serverName": "vk.com", "dest": "vk.com:443" }
+      })),
+    );
+
+    let UpdateOutcome::DryRun(diff) = update_with(&mock, true).unwrap() else {
+      panic!("expected a diff");
+    };
+
+    assert_eq!(
+      diff.changed_fields[1],
+      FieldChange {
+        field: "fingerprint".to_string(),
+        from: json!("chrome"),
+        to: Value::Null,
+      }
+    );
+    assert_eq!(diff.changed_fields.len(), 3);
+    assert_eq!(diff.proposed["serverName"], "vk.com");
+    assert_eq!(mock.calls()[0].1["dryRun"], true);
+    assert!(serde_json::from_value::<DisguiseDiff>(json!({ "changedFields": [] })).is_err());
+  }
+
+  #[test]
+  fn applying_forwards_the_update_unchanged() {
+    let mock = MockBridgeRunner::default().respond("updateDisguise", Ok(json!({ "jobId": "j-1" })));
+
+    let outcome = update_with(&mock, false).unwrap();
+
+    assert_eq!(outcome, UpdateOutcome::Applied(json!({ "jobId": "j-1" })));
+    assert_eq!(
+      serde_json::to_value(&outcome).unwrap(),
+      json!({ "jobId": "j-1" })
+    );
+    assert_eq!(
+      mock.calls(),
+      [(
+        "updateDisguise".to_string(),
+        json!({
+          "baseUrl": "https://cp.example.com",
+          "serverId": "srv-1",
+          "adminApiKey": "admin-key",
+          "disguise": { "serverName": "vk.com", "dest": "vk.com:443" }
+        })
+      )]
+    );
+  }
+
+  #[test]
+  fn both_paths_validate_first() {
+    let mock = MockBridgeRunner::default();
+    for dry_run in [false, true] {
+      let result = tauri::async_runtime::block_on(update(
+        &Bridge::new(mock.clone()),
+        "https://cp.example.com".to_string(),
+        "srv-1".to_string(),
+        "admin-key".to_string(),
+        json!({ "serverName": "vk.com", "dest": "vk.com" }),
+        dry_run,
+      ));
+      assert_eq!(result.unwrap_err().kind(), "invalidDisguise");
+    }
+    assert!(mock.calls().is_empty());
+  }
+
+  #[test]
+  fn parses_the_ipc_entries() {
+    let parsed: Vec<DisguiseUpdate> = serde_json::from_value(json!([
+      { "serverId": "srv-1", "disguise": { "serverName": "vk.com" } }
+    ]))
+    .unwrap();
+
+    assert_eq!(parsed[0].server_id, "srv-1");
+    assert_eq!(parsed[0].disguise, json!({ "serverName": "vk.com" }));
+  }
+
+  #[test]
+  fn lists_typed_templates() {
+    let mock = MockBridgeRunner::default().respond(
+      "listDisguiseTemplates",
+      Ok(json!([
+        {
+          "id": "reality-vk",
+          "name": "VK (Reality)",
+          "description": "Looks like vk.com over TLS 1.3",
+          "fields": [
+            { "key": "serverName", "label": "SNI", "type": "string", "required": true, "default": "vk.com" },
+            { "key": "dest", "type": "hostPort", "required": true },
+            { "key": "fingerprint", "type": "select", "options": ["chrome", "firefox"] },
+            { "key": "spiderX", "type": "regex" }
+          ]
+        },
+        { "id": "plain", "name": "Plain" }
+      ])),
+    );
+
+    let templates = tauri::async_runtime::block_on(list_templates(
+      &Bridge::new(mock.clone()),
+      "https://cp.example.com".to_string(),
+      "admin-key".to_string(),
+    ))
+    .unwrap();
+
+    assert_eq!(templates.len(), 2);
+    let fields = &templates[0].fields;
+    assert_eq!(
+      fields[0],
+      TemplateField {
+        key: "serverName".to_string(),
+        label: Some("SNI".to_string()),
+        field_type: FieldType::String,
+        required: true,
+        default: Some(json!("vk.com")),
+        options: Vec::new(),
+      }
+    );
+    assert_eq!(fields[1].field_type, FieldType::HostPort);
+    assert_eq!(fields[2].options, ["chrome", "firefox"]);
+    assert_eq!(fields[3].field_type, FieldType::Other);
+    assert_eq!(templates[1].description, None);
+    assert!(templates[1].fields.is_empty());
+    assert_eq!(
+      mock.calls(),
+      [(
+        "listDisguiseTemplates".to_string(),
+        json!({ "baseUrl": "https://cp.example.com", "adminApiKey": "admin-key" })
+      )]
+    );
+  }
+
+  #[test]
+  fn template_list_needs_an_admin_key() {
+    let mock = MockBridgeRunner::default();
+
+    let error = tauri::async_runtime::block_on(list_templates(
+      &Bridge::new(mock.clone()),
+      "https://cp.example.com".to_string(),
+      "  ".to_string(),
+    ))
+    .unwrap_err();
+
+    assert_eq!(error, BridgeError::MissingAdminKey);
+    assert!(mock.calls().is_empty());
+  }
+}
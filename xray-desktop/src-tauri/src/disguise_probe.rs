@@ -0,0 +1,260 @@
+use crate::base_url::normalize_base_url;
+use crate::bridge::Bridge;
+use crate::config::Config;
+use crate::error::BridgeError;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+/// How much of the page `DisguiseProbe` carries; the bridge reads a few KiB at most.
+const PREVIEW_CHARS: usize = 512;
+
+/// What the server's front served to an ordinary HTTPS request, as a censor's scanner would see
+/// it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DisguiseProbe {
+  pub status_code: u16,
+  pub content_type: Option<String>,
+  pub body_preview: String,
+  pub looks_like_cover: bool,
+}
+
+/// The bridge's `probeDisguise` answer.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProbeReply {
+  pub status_code: u16,
+  #[serde(default)]
+  pub content_type: Option<String>,
+  #[serde(default)]
+  pub location: Option<String>,
+  #[serde(default)]
+  pub body: String,
+  /// The disguise's `serverName`, when the imported config has one.
+  #[serde(default)]
+  pub cover_host: Option<String>,
+}
+
+/// Pages a bare web server, reverse proxy or xray itself answers with. A cover site never does.
+const GIVEAWAYS: [&str; 5] = [
+  "400 bad request",
+  "plain http request was sent to https port",
+  "welcome to nginx",
+  "<h1>it works!</h1>",
+  "default web site page",
+];
+
+/// The label people recognise a site by: `microsoft` for `www.microsoft.com`.
+fn site_label(host: &str) -> Option<&str> {
+  let host = host.split(':').next()?.trim_end_matches('.');
+  let mut labels = host.rsplit('.');
+  labels.next()?;
+  labels.next().filter(|label| !label.is_empty())
+}
+
+/// A cover answers like a real site: a 2xx page or 3xx redirect, HTML, none of the default
+/// pages, and, when the cover's name is known, something mentioning it.
+pub fn looks_like_cover(reply: &ProbeReply) -> bool {
+  if !(200..400).contains(&reply.status_code) {
+    return false;
+  }
+  let body = reply.body.to_lowercase();
+  if GIVEAWAYS.iter().any(|giveaway| body.contains(giveaway)) {
+    return false;
+  }
+  let location = reply.location.as_deref().unwrap_or_default().to_lowercase();
+  let redirect = (300..400).contains(&reply.status_code) && !location.is_empty();
+  let html = reply
+    .content_type
+    .as_deref()
+    .is_some_and(|content_type| content_type.to_lowercase().starts_with("text/html"))
+    && (body.contains("<html") || body.contains("<!doctype html"));
+  if !redirect && !html {
+    return false;
+  }
+  match reply.cover_host.as_deref().and_then(site_label) {
+    Some(label) => {
+      let label = label.to_lowercase();
+      body.contains(&label) || location.contains(&label)
+    }
+    None => true,
+  }
+}
+
+pub fn from_reply(reply: ProbeReply) -> DisguiseProbe {
+  DisguiseProbe {
+    status_code: reply.status_code,
+    looks_like_cover: looks_like_cover(&reply),
+    body_preview: reply.body.chars().take(PREVIEW_CHARS).collect(),
+    content_type: reply.content_type,
+  }
+}
+
+pub async fn probe(
+  bridge: &Bridge,
+  base_url: String,
+  server_id: String,
+) -> Result<DisguiseProbe, BridgeError> {
+  let value = bridge
+    .call(
+      "probeDisguise",
+      json!({ "baseUrl": base_url, "serverId": server_id }),
+    )
+    .await?;
+  serde_json::from_value(value)
+    .map(from_reply)
+    .map_err(|error| BridgeError::InvalidJson(error.to_string()))
+}
+
+#[allow(non_snake_case)]
+#[tauri::command]
+pub async fn probeDisguise(
+  bridge: tauri::State<'_, Bridge>,
+  config: tauri::State<'_, Config>,
+  baseUrl: String,
+  serverId: String,
+) -> Result<DisguiseProbe, BridgeError> {
+  let base_url = normalize_base_url(&baseUrl, config.get().allow_insecure_base_url)?;
+  probe(&bridge, base_url, serverId).await
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::bridge::mock::MockBridgeRunner;
+  use serde_json::Value;
+  use tauri::async_runtime::block_on;
+
+  fn reply(raw: Value) -> ProbeReply {
+    serde_json::from_value(raw).unwrap()
+  }
+
+  #[test]
+  fn cover_page_from_the_bridge_is_recognised() {
+    let bridge = Bridge::new(MockBridgeRunner::default().respond(
+      "probeDisguise",
+      Ok(json!({
+        "statusCode": 200,
+        "contentType": "text/html; charset=utf-8",
+        "location": null,
+        "body": format!(
+          "<!DOCTYPE html><html><head><title>Microsoft – AI, Cloud</title></head>{}",
+          "x".repeat(2000)
+        ),
+        "coverHost": "www.microsoft.com"
+      })),
+    ));
+
+    let probe = block_on(probe(
+      &bridge,
+      "https://panel.example".to_string(),
+      "srv-1".to_string(),
+    ))
+    .unwrap();
+
+    assert_eq!(probe.status_code, 200);
+    assert_eq!(
+      probe.content_type.as_deref(),
+      Some("text/html; charset=utf-8")
+    );
+    assert!(probe.looks_like_cover);
+    assert_eq!(probe.body_preview.chars().count(), PREVIEW_CHARS);
+  }
+
+  #[test]
+  fn minimal_reply_deserializes() {
+    let parsed = reply(json!({ "statusCode": 502 }));
+
+    assert_eq!(parsed.body, "");
+    assert_eq!(parsed.cover_host, None);
+    assert!(!looks_like_cover(&parsed));
+  }
+
+  #[test]
+  fn malformed_reply_is_invalid_json() {
+    let bridge = Bridge::new(
+      MockBridgeRunner::default().respond("probeDisguise", Ok(json!({ "statusCode": "200" }))),
+    );
+
+    let error = block_on(probe(
+      &bridge,
+      "https://panel.example".to_string(),
+      "srv-1".to_string(),
+    ))
+    .unwrap_err();
+
+    assert!(matches!(error, BridgeError::InvalidJson(_)));
+  }
+
+  #[test]
+  fn redirect_to_the_cover_counts() {
+    assert!(looks_like_cover(&reply(json!({
+      "statusCode": 301,
+      "location": "https://www.apple.com/",
+      "coverHost": "www.apple.com"
+    }))));
+    assert!(!looks_like_cover(&reply(json!({
+      "statusCode": 302,
+      "location": "https://login.example.net/",
+      "coverHost": "www.apple.com"
+    }))));
+  }
+
+  #[test]
+  fn default_pages_and_errors_give_the_server_away() {
+    for (status, body) in [
+      (
+        200,
+        "<html><head><title>Welcome to nginx!</title></head></html>",
+      ),
+      (
+        400,
+        "<html><title>400 Bad Request</title>The plain HTTP request was sent to HTTPS port</html>",
+      ),
+      (200, "<html><body><h1>It works!</h1></body></html>"),
+      (404, "<html><body>microsoft</body></html>"),
+    ] {
+      let probe = reply(json!({
+        "statusCode": status,
+        "contentType": "text/html",
+        "body": body,
+        "coverHost": "www.microsoft.com"
+      }));
+      assert!(!looks_like_cover(&probe), "{body}");
+    }
+  }
+
+  #[test]
+  fn page_must_be_html_and_about_the_cover() {
+    let html = |body: &str, content_type: &str| {
+      reply(json!({
+        "statusCode": 200,
+        "contentType": content_type,
+        "body": body,
+        "coverHost": "www.microsoft.com"
+      }))
+    };
+
+    assert!(!looks_like_cover(&html(
+      "<html><title>Shop</title></html>",
+      "text/html"
+    )));
+    assert!(!looks_like_cover(&html(
+      r#"{"microsoft": true}"#,
+      "application/json"
+    )));
+    // Without a known cover name any ordinary page passes.
+    let unnamed = ProbeReply {
+      cover_host: None,
+      ..html("<html><title>Shop</title></html>", "text/html")
+    };
+    assert!(looks_like_cover(&unnamed));
+  }
+
+  #[test]
+  fn site_label_is_the_registrable_name() {
+    assert_eq!(site_label("www.microsoft.com"), Some("microsoft"));
+    assert_eq!(site_label("apple.com:443"), Some("apple"));
+    assert_eq!(site_label("localhost"), None);
+  }
+}
@@ -0,0 +1,364 @@
+use crate::base_url::normalize_base_url;
+use crate::bridge::Bridge;
+use crate::commands;
+use crate::config::Config;
+use crate::disguise;
+use crate::error::BridgeError;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::sync::Mutex;
+use std::time::Duration;
+use tauri::async_runtime::JoinHandle;
+use tauri::{AppHandle, Manager};
+
+/// Shortest interval the scheduler accepts; the panel rewrites its config on every update.
+pub const MIN_INTERVAL_SECS: u64 = 60;
+
+/// Disguises to cycle one server through. Held in memory only, since it carries the admin key.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RotationPlan {
+  pub base_url: String,
+  pub server_id: String,
+  pub admin_api_key: String,
+  pub templates: Vec<Value>,
+  /// `None` rotates only on `rotateDisguise`.
+  #[serde(default)]
+  pub interval_secs: Option<u64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RotationStatus {
+  pub server_id: String,
+  pub templates: usize,
+  /// Template the panel serves now; `None` until the first rotation succeeds.
+  pub active_index: Option<usize>,
+  pub interval_secs: Option<u64>,
+  /// Why the last rotation failed; cleared by the next success.
+  pub last_error: Option<BridgeError>,
+}
+
+struct Rotation {
+  plan: RotationPlan,
+  active_index: Option<usize>,
+  last_error: Option<BridgeError>,
+}
+
+impl Rotation {
+  /// The template after the active one, wrapping around; the first before any rotation.
+  fn next_index(&self) -> usize {
+    self
+      .active_index
+      .map_or(0, |index| (index + 1) % self.plan.templates.len())
+  }
+
+  fn status(&self) -> RotationStatus {
+    RotationStatus {
+      server_id: self.plan.server_id.clone(),
+      templates: self.plan.templates.len(),
+      active_index: self.active_index,
+      interval_secs: self.plan.interval_secs,
+      last_error: self.last_error.clone(),
+    }
+  }
+}
+
+/// The configured rotation and its timer, if any.
+#[derive(Default)]
+pub struct DisguiseRotation {
+  rotation: Mutex<Option<Rotation>>,
+  task: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl DisguiseRotation {
+  /// Replaces the plan, starting over from the first template. The timer is the caller's.
+  pub fn configure(&self, mut plan: RotationPlan, allow_insecure: bool) -> Result<(), BridgeError> {
+    let invalid = |reason: &str| BridgeError::InvalidDisguise(reason.to_string());
+    plan.base_url = normalize_base_url(&plan.base_url, allow_insecure)?;
+    if plan.templates.is_empty() {
+      return Err(invalid("rotation needs at least one template"));
+    }
+    if plan
+      .interval_secs
+      .is_some_and(|interval| interval < MIN_INTERVAL_SECS)
+    {
+      return Err(invalid(&format!(
+        "rotation interval must be at least {MIN_INTERVAL_SECS} seconds"
+      )));
+    }
+    plan.templates.iter().try_for_each(disguise::validate)?;
+    *self.rotation.lock().unwrap() = Some(Rotation {
+      plan,
+      active_index: None,
+      last_error: None,
+    });
+    Ok(())
+  }
+
+  pub fn clear(&self) {
+    self.rotation.lock().unwrap().take();
+    self.stop_timer();
+  }
+
+  pub fn status(&self) -> Option<RotationStatus> {
+    self.rotation.lock().unwrap().as_ref().map(Rotation::status)
+  }
+
+  fn stop_timer(&self) {
+    if let Some(task) = self.task.lock().unwrap().take() {
+      task.abort();
+    }
+  }
+
+  /// Restarts the timer for the current plan's interval, or stops it when there is none.
+  pub fn restart_timer(&self, app: &AppHandle) {
+    self.stop_timer();
+    let interval = self
+      .rotation
+      .lock()
+      .unwrap()
+      .as_ref()
+      .and_then(|rotation| rotation.plan.interval_secs);
+    if let Some(interval) = interval {
+      let app = app.clone();
+      *self.task.lock().unwrap() = Some(tauri::async_runtime::spawn(rotation_loop(
+        app,
+        Duration::from_secs(interval),
+      )));
+    }
+  }
+}
+
+/// Applies the next template. The cursor only moves on success, so a failed rotation retries the
+/// same template on the next tick.
+pub async fn rotate(
+  bridge: &Bridge,
+  state: &DisguiseRotation,
+) -> Result<RotationStatus, BridgeError> {
+  let (plan, index) = {
+    let rotation = state.rotation.lock().unwrap();
+    let rotation = rotation.as_ref().ok_or(BridgeError::NoDisguiseRotation)?;
+    (rotation.plan.clone(), rotation.next_index())
+  };
+  let outcome = commands::update_disguise(
+    bridge,
+    plan.base_url.clone(),
+    plan.server_id.clone(),
+    plan.admin_api_key.clone(),
+    plan.templates[index].clone(),
+  )
+  .await;
+
+  let mut rotation = state.rotation.lock().unwrap();
+  // Reconfigured or cleared while the update was in flight: the result belongs to an old plan.
+  let rotation = match rotation.as_mut() {
+    Some(rotation) if rotation.plan == plan => rotation,
+    _ => return outcome.and(Err(BridgeError::Cancelled)),
+  };
+  match outcome {
+    Ok(_) => {
+      rotation.active_index = Some(index);
+      rotation.last_error = None;
+      Ok(rotation.status())
+    }
+    Err(error) => {
+      rotation.last_error = Some(error.clone());
+      Err(error)
+    }
+  }
+}
+
+async fn rotation_loop(app: AppHandle, interval: Duration) {
+  loop {
+    tokio::time::sleep(interval).await;
+    let (Some(bridge), Some(state)) = (
+      app.try_state::<Bridge>(),
+      app.try_state::<DisguiseRotation>(),
+    ) else {
+      continue;
+    };
+    if let Err(error) = rotate(&bridge, &state).await {
+      tracing::warn!(
+        kind = error.kind(),
+        "disguise rotation failed, retrying next tick: {error}"
+      );
+    }
+  }
+}
+
+/// Sets up rotation for one server, or stops it with `null`. With `intervalSecs` a timer rotates
+/// on its own; either way `rotateDisguise` rotates right away.
+#[allow(non_snake_case)]
+#[tauri::command]
+pub fn setDisguiseRotation(
+  app: AppHandle,
+  state: tauri::State<'_, DisguiseRotation>,
+  config: tauri::State<'_, Config>,
+  plan: Option<RotationPlan>,
+) -> Result<(), BridgeError> {
+  match plan {
+    Some(plan) => {
+      state.configure(plan, config.get().allow_insecure_base_url)?;
+      state.restart_timer(&app);
+    }
+    None => state.clear(),
+  }
+  Ok(())
+}
+
+#[allow(non_snake_case)]
+#[tauri::command]
+pub async fn rotateDisguise(
+  bridge: tauri::State<'_, Bridge>,
+  state: tauri::State<'_, DisguiseRotation>,
+) -> Result<RotationStatus, BridgeError> {
+  rotate(&bridge, &state).await
+}
+
+#[allow(non_snake_case)]
+#[tauri::command]
+pub fn getDisguiseRotation(state: tauri::State<'_, DisguiseRotation>) -> Option<RotationStatus> {
+  state.status()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::bridge::BridgeRunner;
+  use serde_json::json;
+  use std::sync::atomic::{AtomicUsize, Ordering};
+  use std::sync::Arc;
+  use tauri::async_runtime::block_on;
+
+  /// Fails the first `failures` updates with an HTTP 502, records every disguise sent.
+  #[derive(Clone, Default)]
+  struct FlakyPanel {
+    failures: Arc<AtomicUsize>,
+    sent: Arc<Mutex<Vec<Value>>>,
+  }
+
+  impl BridgeRunner for FlakyPanel {
+    fn run(&self, _action: &str, payload: Value) -> Result<Value, BridgeError> {
+      self.sent.lock().unwrap().push(payload["disguise"].clone());
+      let failing = self
+        .failures
+        .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |left| {
+          left.checked_sub(1)
+        })
+        .is_ok();
+      if failing {
+        return Err(BridgeError::BridgeReported {
+          code: None,
+          message: "Disguise update failed: HTTP 502".to_string(),
+        });
+      }
+      Ok(Value::Null)
+    }
+  }
+
+  fn template(name: &str) -> Value {
+    json!({ "serverName": name, "dest": format!("{name}:443") })
+  }
+
+  fn plan(names: &[&str]) -> RotationPlan {
+    RotationPlan {
+      base_url: "https://cp.example.com".to_string(),
+      server_id: "srv-1".to_string(),
+      admin_api_key: "admin-key".to_string(),
+      templates: names.iter().map(|name| template(name)).collect(),
+      interval_secs: Some(3600),
+    }
+  }
+
+  fn configured(names: &[&str]) -> DisguiseRotation {
+    let state = DisguiseRotation::default();
+    state.configure(plan(names), false).unwrap();
+    state
+  }
+
+  #[test]
+  fn cursor_advances_and_wraps_around() {
+    let panel = FlakyPanel::default();
+    let bridge = Bridge::new(panel.clone());
+    let state = configured(&["vk.com", "ya.ru", "mail.ru"]);
+    assert_eq!(state.status().unwrap().active_index, None);
+
+    let active: Vec<Option<usize>> = (0..4)
+      .map(|_| block_on(rotate(&bridge, &state)).unwrap().active_index)
+      .collect();
+
+    assert_eq!(active, [Some(0), Some(1), Some(2), Some(0)]);
+    assert_eq!(
+      *panel.sent.lock().unwrap(),
+      [
+        template("vk.com"),
+        template("ya.ru"),
+        template("mail.ru"),
+        template("vk.com")
+      ]
+    );
+  }
+
+  #[test]
+  fn a_failed_rotation_retries_the_same_template() {
+    let panel = FlakyPanel::default();
+    panel.failures.store(2, Ordering::SeqCst);
+    let bridge = Bridge::new(panel.clone());
+    let state = configured(&["vk.com", "ya.ru"]);
+
+    assert!(block_on(rotate(&bridge, &state)).is_err());
+    assert!(block_on(rotate(&bridge, &state)).is_err());
+    let failed = state.status().unwrap();
+    assert_eq!(failed.active_index, None);
+    assert_eq!(failed.last_error.unwrap().kind(), "bridgeReported");
+
+    let recovered = block_on(rotate(&bridge, &state)).unwrap();
+    assert_eq!(recovered.active_index, Some(0));
+    assert_eq!(recovered.last_error, None);
+    assert_eq!(
+      *panel.sent.lock().unwrap(),
+      [template("vk.com"), template("vk.com"), template("vk.com")]
+    );
+  }
+
+  #[test]
+  fn rejects_plans_that_cannot_rotate() {
+    let state = DisguiseRotation::default();
+    let kind = |plan: RotationPlan| state.configure(plan, false).unwrap_err().kind();
+
+    assert_eq!(kind(plan(&[])), "invalidDisguise");
+    assert_eq!(
+      kind(RotationPlan {
+        interval_secs: Some(5),
+        ..plan(&["vk.com"])
+      }),
+      "invalidDisguise"
+    );
+    assert_eq!(
+      kind(RotationPlan {
+        templates: vec![template("vk.com"), json!({ "serverName": "ya.ru" })],
+        ..plan(&[])
+      }),
+      "invalidDisguise"
+    );
+    assert_eq!(
+      kind(RotationPlan {
+        base_url: "http://cp.example.com".to_string(),
+        ..plan(&["vk.com"])
+      }),
+      "invalidBaseUrl"
+    );
+    assert_eq!(state.status(), None);
+  }
+
+  #[test]
+  fn rotating_without_a_plan_is_an_error() {
+    let bridge = Bridge::new(FlakyPanel::default());
+
+    assert_eq!(
+      block_on(rotate(&bridge, &DisguiseRotation::default())),
+      Err(BridgeError::NoDisguiseRotation)
+    );
+  }
+}
@@ -0,0 +1,212 @@
+use crate::bridge::Bridge;
+use crate::commands;
+use crate::config::Config;
+use crate::error::BridgeError;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::net::IpAddr;
+
+/// Which resolvers answered the agent's probe lookups. `leaking` means at least one of them
+/// was reached outside the tunnel.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct DnsLeakResult {
+  pub leaking: bool,
+  pub resolver_ips: Vec<String>,
+  pub through_tunnel: bool,
+}
+
+/// Parses every entry as an IP address and returns them in canonical form. Hostnames are
+/// rejected: resolving them would itself need DNS.
+pub fn validate(servers: &[String]) -> Result<Vec<String>, BridgeError> {
+  if servers.is_empty() {
+    return Err(BridgeError::InvalidDns(
+      "at least one server is required".to_string(),
+    ));
+  }
+  servers
+    .iter()
+    .map(|server| {
+      server
+        .parse::<IpAddr>()
+        .map(|address| address.to_string())
+        .map_err(|_| BridgeError::InvalidDns(format!("\"{server}\" is not an IP address")))
+    })
+    .collect()
+}
+
+/// Forwards the servers to the bridge and saves them; `connect` re-sends the saved list.
+pub async fn set(
+  bridge: &Bridge,
+  config: &Config,
+  servers: Vec<String>,
+) -> Result<(), BridgeError> {
+  let servers = validate(&servers)?;
+  bridge.call("dns", json!({ "servers": servers })).await?;
+  config.update(|settings| settings.dns_servers = servers)?;
+  Ok(())
+}
+
+/// Only meaningful through a live tunnel, so it refuses to run while disconnected.
+pub async fn leak_test(bridge: &Bridge) -> Result<DnsLeakResult, BridgeError> {
+  if !commands::status(bridge).await?.connected {
+    return Err(BridgeError::NotConnected);
+  }
+  serde_json::from_value(bridge.call("dnsLeakTest", Value::Null).await?)
+    .map_err(|error| BridgeError::InvalidJson(error.to_string()))
+}
+
+/// Empty when the system resolvers are used.
+#[allow(non_snake_case)]
+#[tauri::command]
+pub fn getDnsConfig(config: tauri::State<'_, Config>) -> Vec<String> {
+  config.get().dns_servers
+}
+
+#[allow(non_snake_case)]
+#[tauri::command]
+pub async fn setDnsConfig(
+  bridge: tauri::State<'_, Bridge>,
+  config: tauri::State<'_, Config>,
+  servers: Vec<String>,
+) -> Result<(), BridgeError> {
+  set(&bridge, &config, servers).await
+}
+
+#[allow(non_snake_case)]
+#[tauri::command]
+pub async fn testDnsLeak(bridge: tauri::State<'_, Bridge>) -> Result<DnsLeakResult, BridgeError> {
+  leak_test(&bridge).await
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::bridge::mock::MockBridgeRunner;
+  use tauri::async_runtime::block_on;
+
+  fn strings(values: &[&str]) -> Vec<String> {
+    values.iter().map(|value| value.to_string()).collect()
+  }
+
+  #[test]
+  fn accepts_ipv4_and_ipv6_addresses() {
+    assert_eq!(
+      validate(&strings(&[
+        "1.1.1.1",
+        "10.0.0.53",
+        "2606:4700:4700:0:0:0:0:1111",
+        "::1"
+      ])),
+      Ok(strings(&[
+        "1.1.1.1",
+        "10.0.0.53",
+        "2606:4700:4700::1111",
+        "::1"
+      ]))
+    );
+  }
+
+  #[test]
+  fn rejects_hostnames_and_garbage() {
+    for server in [
+      "dns.google",
+      "1.1.1",
+      "1.1.1.1:53",
+      "10.0.0.0/8",
+      " 8.8.8.8",
+      "",
+    ] {
+      assert_eq!(
+        validate(&strings(&["1.1.1.1", server])),
+        Err(BridgeError::InvalidDns(format!(
+          "\"{server}\" is not an IP address"
+        ))),
+        "{server:?}"
+      );
+    }
+  }
+
+  #[test]
+  fn rejects_an_empty_list() {
+    assert_eq!(validate(&[]).unwrap_err().kind(), "invalidDns");
+  }
+
+  #[test]
+  fn forwards_and_saves_valid_servers_only() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("settings.json");
+    let mock = MockBridgeRunner::default().respond("dns", Ok(Value::Null));
+    let bridge = Bridge::new(mock.clone());
+
+    block_on(set(&bridge, &Config::load(&path), strings(&["9.9.9.9"]))).unwrap();
+    assert!(block_on(set(
+      &bridge,
+      &Config::load(&path),
+      strings(&["dns.quad9.net"])
+    ))
+    .is_err());
+
+    assert_eq!(Config::load(&path).get().dns_servers, strings(&["9.9.9.9"]));
+    assert_eq!(
+      mock.calls(),
+      [("dns".to_string(), json!({ "servers": ["9.9.9.9"] }))]
+    );
+  }
+
+  fn connected_bridge(result: Value) -> (MockBridgeRunner, Bridge) {
+    let mock = MockBridgeRunner::default()
+      .respond("status", Ok(json!({ "connected": true })))
+      .respond("dnsLeakTest", Ok(result));
+    (mock.clone(), Bridge::new(mock))
+  }
+
+  #[test]
+  fn reports_a_leaking_resolver() {
+    let (_, bridge) = connected_bridge(json!({
+      "leaking": true,
+      "resolverIps": ["10.0.0.1", "192.0.2.53"],
+      "throughTunnel": false
+    }));
+
+    assert_eq!(
+      block_on(leak_test(&bridge)),
+      Ok(DnsLeakResult {
+        leaking: true,
+        resolver_ips: strings(&["10.0.0.1", "192.0.2.53"]),
+        through_tunnel: false,
+      })
+    );
+  }
+
+  #[test]
+  fn reports_a_clean_result() {
+    let (_, bridge) = connected_bridge(json!({
+      "leaking": false,
+      "resolverIps": ["1.1.1.1"],
+      "throughTunnel": true
+    }));
+
+    assert_eq!(
+      block_on(leak_test(&bridge)),
+      Ok(DnsLeakResult {
+        leaking: false,
+        resolver_ips: strings(&["1.1.1.1"]),
+        through_tunnel: true,
+      })
+    );
+  }
+
+  #[test]
+  fn leak_test_refuses_to_run_while_disconnected() {
+    let mock = MockBridgeRunner::default().respond("status", Ok(json!({ "connected": false })));
+    let bridge = Bridge::new(mock.clone());
+
+    assert_eq!(block_on(leak_test(&bridge)), Err(BridgeError::NotConnected));
+    assert_eq!(
+      mock.calls(),
+      [("status".to_string(), Value::Null)],
+      "the probe never runs"
+    );
+  }
+}
@@ -0,0 +1,397 @@
+use serde::{Serialize, Serializer};
+use std::time::Duration;
+
+/// Every command failure. Serializes to a stable `{ kind, message, code? }` object so the
+/// frontend can branch on `kind` instead of parsing message text.
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+pub enum BridgeError {
+  #[error("Node.js was not found: {0}")]
+  NodeNotFound(String),
+  #[error("Node.js {found} is too old, {required} or newer is required")]
+  NodeTooOld { found: String, required: String },
+  #[error("Failed to run bridge: {0}")]
+  SpawnFailed(String),
+  #[error("Bridge timed out after {} seconds", .0.as_secs_f64())]
+  Timeout(Duration),
+  #[error("Invalid bridge JSON: {0}")]
+  InvalidJson(String),
+  #[error("{message}")]
+  BridgeReported {
+    code: Option<String>,
+    message: String,
+  },
+  #[error("Bridge exited without a response")]
+  EmptyResponse,
+  #[error("{}", crash_message(*.code, *.signal, .stderr))]
+  BridgeCrashed {
+    code: Option<i32>,
+    signal: Option<i32>,
+    /// The last lines the bridge wrote to stderr.
+    stderr: String,
+  },
+  #[error("Secret storage is unavailable: {0}")]
+  SecretStoreUnavailable(String),
+  #[error("Failed to save settings: {0}")]
+  SettingsIo(String),
+  #[error("Invalid mode \"{got}\", expected one of: {}", .allowed.join(", "))]
+  InvalidMode {
+    got: String,
+    allowed: &'static [&'static str],
+  },
+  #[error("Invalid deep link: {0}")]
+  InvalidDeepLink(String),
+  #[error("Failed to update launch on login: {0}")]
+  AutostartFailed(String),
+  #[error("Unknown server \"{id}\"")]
+  UnknownServer { id: String },
+  #[error("No server is reachable")]
+  NoReachableServer,
+  #[error("Invalid split tunnel rule: {0}")]
+  InvalidSplitRule(String),
+  #[error("Unknown split tunnel rule \"{id}\"")]
+  UnknownSplitRule { id: String },
+  #[error("Invalid DNS configuration: {0}")]
+  InvalidDns(String),
+  #[error("Unknown profile \"{id}\"")]
+  UnknownProfile { id: String },
+  #[error("Could not read image: {0}")]
+  InvalidImage(String),
+  #[error("No QR code found in the image")]
+  QrNotFound,
+  #[error("Invalid disguise: {0}")]
+  InvalidDisguise(String),
+  #[error("Not connected")]
+  NotConnected,
+  #[error("Failed to access connection history: {0}")]
+  HistoryIo(String),
+  #[error("The token has expired, import it again")]
+  AuthExpired,
+  #[error("The token was rejected, import it again")]
+  AuthInvalid,
+  #[error("Cancelled")]
+  Cancelled,
+  #[error("Invalid base URL: {0}")]
+  InvalidBaseUrl(String),
+  #[error("MTU {0} is out of range, expected 576 to 9000")]
+  InvalidMtu(u16),
+  #[error("Invalid schedule: {0}")]
+  InvalidSchedule(String),
+  #[error("Unknown schedule \"{id}\"")]
+  UnknownSchedule { id: String },
+  #[error("Failed to write diagnostics: {0}")]
+  DiagnosticsIo(String),
+  #[error("settings.json is from a newer version (schema {found}, this build reads up to {supported}), update the app")]
+  SettingsTooNew { found: u64, supported: u64 },
+  #[error("settings.json could not be read as settings ({0}); fix or delete it")]
+  SettingsInvalid(String),
+  #[error("{0} can't be changed from the app; edit settings.json while it isn't running")]
+  ProtectedSetting(String),
+  #[error("No disguise rotation is configured")]
+  NoDisguiseRotation,
+  #[error("An admin API key is required")]
+  MissingAdminKey,
+  #[error("Invalid bandwidth limit: {0}")]
+  InvalidBandwidthLimit(String),
+  #[error("Failed to open folder: {0}")]
+  OpenFailed(String),
+  #[error("Failed to save the last error: {0}")]
+  LastErrorIo(String),
+  #[error("Invalid hotkey: {0}")]
+  InvalidHotkey(String),
+  #[error("Could not register {hotkey}: {reason}")]
+  HotkeyUnavailable { hotkey: String, reason: String },
+  #[error("Invalid trusted network: {0}")]
+  InvalidTrustedNetwork(String),
+  #[error("The new admin API key did not work, the old one was kept: {0}")]
+  AdminKeyUnverified(String),
+  #[error("Failed to save telemetry: {0}")]
+  TelemetryIo(String),
+  #[error("Invalid subscription URL: {0}")]
+  InvalidSubscriptionUrl(String),
+  #[error("Status poll interval {0} ms is out of range, expected 250 to 60000")]
+  InvalidPollInterval(u64),
+  #[error("The tunnel is still up, so nothing was reset")]
+  StillConnected,
+  #[error("Invalid core config: {}", .errors.join("; "))]
+  InvalidCoreConfig { errors: Vec<String> },
+  #[error("The bridge script does not match this build (expected SHA-256 {expected}, got {got})")]
+  BridgeTampered { expected: String, got: String },
+  #[error("The app is still starting, try again in a moment")]
+  NotReady,
+  #[error("Invalid app rule: {0}")]
+  InvalidAppRule(String),
+  #[error("No app rule for \"{path}\"")]
+  UnknownAppRule { path: String },
+  #[error("Per-app routing is not available on {platform}")]
+  AppRulesUnsupported { platform: String },
+  #[error("Invalid transport \"{got}\", expected one of: {}", .allowed.join(", "))]
+  InvalidTransport {
+    got: String,
+    allowed: &'static [&'static str],
+  },
+  #[error("Server \"{server_id}\" does not offer {transport}")]
+  UnsupportedTransport {
+    transport: String,
+    server_id: String,
+  },
+  #[error("Invalid certificate fingerprint: {0}")]
+  InvalidFingerprint(String),
+  #[error("The certificate of {base_url} does not match the pinned fingerprint {pinned}")]
+  FingerprintMismatch { base_url: String, pinned: String },
+  #[error("Invalid locale \"{0}\"")]
+  InvalidLocale(String),
+  #[error("Failed to export the QR code: {0}")]
+  QrExport(String),
+  #[error("Bridge script override {path} can't be used: {reason}")]
+  BridgeScriptInvalid { path: String, reason: String },
+  #[error("Failed to read or write the config bundle: {0}")]
+  BundleIo(String),
+  #[error("Invalid config bundle: {0}")]
+  BundleInvalid(String),
+  #[error("The config bundle is from a newer version (bundle {found}, this build reads up to {supported}), update the app")]
+  BundleTooNew { found: u64, supported: u64 },
+  #[error("Invalid disconnect timer: {0}")]
+  InvalidTimer(String),
+  #[error("Not enough privileges for vpn mode: {0}")]
+  InsufficientPrivileges(String),
+  #[error("Failed to read the external config: {0}")]
+  ExternalConfigIo(String),
+  #[error("Invalid external config: {0}")]
+  ExternalConfigInvalid(String),
+}
+
+impl BridgeError {
+  pub fn kind(&self) -> &'static str {
+    match self {
+      Self::NodeNotFound(_) => "nodeNotFound",
+      Self::NodeTooOld { .. } => "nodeTooOld",
+      Self::SpawnFailed(_) => "spawnFailed",
+      Self::Timeout(_) => "timeout",
+      Self::InvalidJson(_) => "invalidJson",
+      Self::BridgeReported { .. } => "bridgeReported",
+      Self::EmptyResponse => "emptyResponse",
+      Self::BridgeCrashed { .. } => "bridgeCrashed",
+      Self::SecretStoreUnavailable(_) => "secretStoreUnavailable",
+      Self::SettingsIo(_) => "settingsIo",
+      Self::InvalidMode { .. } => "invalidMode",
+      Self::InvalidDeepLink(_) => "invalidDeepLink",
+      Self::AutostartFailed(_) => "autostartFailed",
+      Self::UnknownServer { .. } => "unknownServer",
+      Self::NoReachableServer => "noReachableServer",
+      Self::InvalidSplitRule(_) => "invalidSplitRule",
+      Self::UnknownSplitRule { .. } => "unknownSplitRule",
+      Self::InvalidDns(_) => "invalidDns",
+      Self::UnknownProfile { .. } => "unknownProfile",
+      Self::InvalidImage(_) => "invalidImage",
+      Self::QrNotFound => "qrNotFound",
+      Self::InvalidDisguise(_) => "invalidDisguise",
+      Self::NotConnected => "notConnected",
+      Self::HistoryIo(_) => "historyIo",
+      Self::AuthExpired => "authExpired",
+      Self::AuthInvalid => "authInvalid",
+      Self::Cancelled => "cancelled",
+      Self::InvalidBaseUrl(_) => "invalidBaseUrl",
+      Self::InvalidMtu(_) => "invalidMtu",
+      Self::InvalidSchedule(_) => "invalidSchedule",
+      Self::UnknownSchedule { .. } => "unknownSchedule",
+      Self::DiagnosticsIo(_) => "diagnosticsIo",
+      Self::SettingsTooNew { .. } => "settingsTooNew",
+      Self::SettingsInvalid(_) => "settingsInvalid",
+      Self::ProtectedSetting(_) => "protectedSetting",
+      Self::NoDisguiseRotation => "noDisguiseRotation",
+      Self::MissingAdminKey => "missingAdminKey",
+      Self::InvalidBandwidthLimit(_) => "invalidBandwidthLimit",
+      Self::OpenFailed(_) => "openFailed",
+      Self::LastErrorIo(_) => "lastErrorIo",
+      Self::InvalidHotkey(_) => "invalidHotkey",
+      Self::HotkeyUnavailable { .. } => "hotkeyUnavailable",
+      Self::InvalidTrustedNetwork(_) => "invalidTrustedNetwork",
+      Self::AdminKeyUnverified(_) => "adminKeyUnverified",
+      Self::TelemetryIo(_) => "telemetryIo",
+      Self::InvalidSubscriptionUrl(_) => "invalidSubscriptionUrl",
+      Self::InvalidPollInterval(_) => "invalidPollInterval",
+      Self::StillConnected => "stillConnected",
+      Self::InvalidCoreConfig { .. } => "invalidCoreConfig",
+      Self::BridgeTampered { .. } => "bridgeTampered",
+      Self::NotReady => "notReady",
+      Self::InvalidAppRule(_) => "invalidAppRule",
+      Self::UnknownAppRule { .. } => "unknownAppRule",
+      Self::AppRulesUnsupported { .. } => "appRulesUnsupported",
+      Self::InvalidTransport { .. } => "invalidTransport",
+      Self::UnsupportedTransport { .. } => "unsupportedTransport",
+      Self::InvalidFingerprint(_) => "invalidFingerprint",
+      Self::FingerprintMismatch { .. } => "fingerprintMismatch",
+      Self::InvalidLocale(_) => "invalidLocale",
+      Self::QrExport(_) => "qrExport",
+      Self::BridgeScriptInvalid { .. } => "bridgeScriptInvalid",
+      Self::BundleIo(_) => "bundleIo",
+      Self::BundleInvalid(_) => "bundleInvalid",
+      Self::BundleTooNew { .. } => "bundleTooNew",
+      Self::InvalidTimer(_) => "invalidTimer",
+      Self::InsufficientPrivileges(_) => "insufficientPrivileges",
+      Self::ExternalConfigIo(_) => "externalConfigIo",
+      Self::ExternalConfigInvalid(_) => "externalConfigInvalid",
+    }
+  }
+
+  pub fn code(&self) -> Option<&str> {
+    match self {
+      Self::BridgeReported { code, .. } => code.as_deref(),
+      _ => None,
+    }
+  }
+
+  /// Turns the bridge's `AUTH_EXPIRED` / `AUTH_INVALID` codes into their own variants.
+  pub fn with_auth_code(self) -> Self {
+    match &self {
+      Self::BridgeReported {
+        code: Some(code), ..
+      } => match code.as_str() {
+        "AUTH_EXPIRED" => Self::AuthExpired,
+        "AUTH_INVALID" => Self::AuthInvalid,
+        _ => self,
+      },
+      _ => self,
+    }
+  }
+
+  /// Only importing a new token fixes these.
+  pub fn needs_reimport(&self) -> bool {
+    matches!(self, Self::AuthExpired | Self::AuthInvalid)
+  }
+
+  pub fn from_spawn(error: std::io::Error) -> Self {
+    match error.kind() {
+      std::io::ErrorKind::NotFound => Self::NodeNotFound(error.to_string()),
+      _ => Self::SpawnFailed(error.to_string()),
+    }
+  }
+}
+
+fn crash_message(code: Option<i32>, signal: Option<i32>, stderr: &str) -> String {
+  let status = match (code, signal) {
+    (Some(code), _) => format!("exit code {code}"),
+    (None, Some(signal)) => format!("signal {signal}"),
+    (None, None) => "an unknown status".to_string(),
+  };
+  match stderr.lines().rev().find(|line| !line.trim().is_empty()) {
+    Some(last) => format!("Bridge crashed with {status}: {}", last.trim()),
+    None => format!("Bridge crashed with {status}"),
+  }
+}
+
+#[derive(Serialize)]
+struct SerializedError<'a> {
+  kind: &'static str,
+  message: String,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  code: Option<&'a str>,
+  /// Every problem `InvalidCoreConfig` found, for the editor to list.
+  #[serde(skip_serializing_if = "Option::is_none")]
+  errors: Option<&'a [String]>,
+}
+
+impl Serialize for BridgeError {
+  fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+    SerializedError {
+      kind: self.kind(),
+      message: self.to_string(),
+      code: self.code(),
+      errors: match self {
+        Self::InvalidCoreConfig { errors } => Some(errors),
+        _ => None,
+      },
+    }
+    .serialize(serializer)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use serde_json::json;
+
+  #[test]
+  fn serializes_kind_and_message() {
+    let error = BridgeError::Timeout(Duration::from_secs(5));
+
+    assert_eq!(
+      serde_json::to_value(&error).unwrap(),
+      json!({ "kind": "timeout", "message": "Bridge timed out after 5 seconds" })
+    );
+  }
+
+  #[test]
+  fn serializes_bridge_reported_code() {
+    let error = BridgeError::BridgeReported {
+      code: Some("STARTUP_FAILED".to_string()),
+      message: "STARTUP_FAILED: Supervisor exited during startup".to_string(),
+    };
+
+    assert_eq!(
+      serde_json::to_value(&error).unwrap(),
+      json!({
+        "kind": "bridgeReported",
+        "message": "STARTUP_FAILED: Supervisor exited during startup",
+        "code": "STARTUP_FAILED"
+      })
+    );
+  }
+
+  #[test]
+  fn crash_message_names_the_status_and_the_last_stderr_line() {
+    let crashed = |code, signal, stderr: &str| {
+      BridgeError::BridgeCrashed {
+        code,
+        signal,
+        stderr: stderr.to_string(),
+      }
+      .to_string()
+    };
+
+    assert_eq!(
+      crashed(Some(1), None, "starting\nError: addon failed\n\n"),
+      "Bridge crashed with exit code 1: Error: addon failed"
+    );
+    assert_eq!(crashed(None, Some(11), ""), "Bridge crashed with signal 11");
+  }
+
+  #[test]
+  fn maps_bridge_auth_codes_to_their_own_errors() {
+    let reported = |code: &str| BridgeError::BridgeReported {
+      code: Some(code.to_string()),
+      message: format!("{code}: Connect rejected"),
+    };
+
+    assert_eq!(
+      reported("AUTH_EXPIRED").with_auth_code(),
+      BridgeError::AuthExpired
+    );
+    assert_eq!(
+      reported("AUTH_INVALID").with_auth_code(),
+      BridgeError::AuthInvalid
+    );
+    assert_eq!(
+      reported("STARTUP_FAILED").with_auth_code(),
+      reported("STARTUP_FAILED")
+    );
+    assert_eq!(
+      BridgeError::EmptyResponse.with_auth_code(),
+      BridgeError::EmptyResponse
+    );
+    assert!(BridgeError::AuthInvalid.needs_reimport());
+    assert!(!reported("AUTH_INVALID").needs_reimport());
+  }
+
+  #[test]
+  fn missing_program_maps_to_node_not_found() {
+    let error = std::process::Command::new("definitely-not-a-node-binary")
+      .spawn()
+      .unwrap_err();
+
+    assert!(matches!(
+      BridgeError::from_spawn(error),
+      BridgeError::NodeNotFound(_)
+    ));
+  }
+}
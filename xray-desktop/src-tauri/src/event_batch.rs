@@ -0,0 +1,238 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tauri::async_runtime::JoinHandle;
+use tauri::{AppHandle, Emitter, Manager};
+
+const DEFAULT_FLUSH_MS: u64 = 100;
+const DEFAULT_MAX_BATCH: usize = 200;
+
+/// How `vpn://stats` and `vpn://log` are batched up; saved as `eventBatching`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct BatchOptions {
+  /// How often pending items go out, clamped to 10..=2000 ms.
+  pub flush_ms: u64,
+  /// A batch this big goes out right away instead of waiting for the next flush.
+  pub max_batch: usize,
+}
+
+impl Default for BatchOptions {
+  fn default() -> Self {
+    Self {
+      flush_ms: DEFAULT_FLUSH_MS,
+      max_batch: DEFAULT_MAX_BATCH,
+    }
+  }
+}
+
+impl BatchOptions {
+  fn clamped(self) -> Self {
+    Self {
+      flush_ms: self.flush_ms.clamp(10, 2000),
+      max_batch: self.max_batch.clamp(1, 10_000),
+    }
+  }
+}
+
+/// Receives one event name and every item batched up for it, oldest first.
+pub type BatchSink = Arc<dyn Fn(&'static str, Vec<Value>) + Send + Sync>;
+
+/// Collects high-frequency events and emits each as an array on a fixed cadence, so a chatty
+/// bridge costs one IPC message per flush rather than one per line. Cloning shares the queue.
+#[derive(Clone)]
+pub struct EventBatcher {
+  pending: Arc<Mutex<BTreeMap<&'static str, Vec<Value>>>>,
+  options: BatchOptions,
+  sink: BatchSink,
+  task: Arc<Mutex<Option<JoinHandle<()>>>>,
+}
+
+impl EventBatcher {
+  pub fn new(options: BatchOptions, sink: BatchSink) -> Self {
+    Self {
+      pending: Arc::new(Mutex::new(BTreeMap::new())),
+      options: options.clamped(),
+      sink,
+      task: Arc::new(Mutex::new(None)),
+    }
+  }
+
+  /// Emits through `app`, flushing every `flush_ms` until `stop`.
+  pub fn for_app(app: &AppHandle, options: BatchOptions) -> Self {
+    let handle = app.clone();
+    let batcher = Self::new(
+      options,
+      Arc::new(move |event, items| {
+        let _ = handle.emit(event, items);
+      }),
+    );
+    batcher.start();
+    batcher
+  }
+
+  pub fn push(&self, event: &'static str, item: impl Serialize) {
+    let Ok(item) = serde_json::to_value(item) else {
+      return;
+    };
+    let full = {
+      let mut pending = self.pending.lock().unwrap();
+      let items = pending.entry(event).or_default();
+      items.push(item);
+      (items.len() >= self.options.max_batch).then(|| std::mem::take(items))
+    };
+    if let Some(items) = full {
+      (self.sink)(event, items);
+    }
+  }
+
+  /// Emits whatever is pending now. Returns how many batches went out.
+  pub fn flush(&self) -> usize {
+    let pending = std::mem::take(&mut *self.pending.lock().unwrap());
+    let mut flushed = 0;
+    for (event, items) in pending.into_iter().filter(|(_, items)| !items.is_empty()) {
+      (self.sink)(event, items);
+      flushed += 1;
+    }
+    flushed
+  }
+
+  fn start(&self) {
+    let batcher = self.clone();
+    let interval = Duration::from_millis(self.options.flush_ms);
+    *self.task.lock().unwrap() = Some(tauri::async_runtime::spawn(async move {
+      loop {
+        tokio::time::sleep(interval).await;
+        batcher.flush();
+      }
+    }));
+  }
+
+  /// Stops the cadence after a last flush, so nothing pending is lost.
+  pub fn stop(&self) {
+    if let Some(task) = self.task.lock().unwrap().take() {
+      task.abort();
+    }
+    self.flush();
+  }
+}
+
+/// Queues `item` for `event`, or emits it as a batch of one before the batcher is managed.
+pub fn emit(app: &AppHandle, event: &'static str, item: impl Serialize) {
+  match app.try_state::<EventBatcher>() {
+    Some(batcher) => batcher.push(event, item),
+    None => {
+      let _ = app.emit(event, &[item]);
+    }
+  }
+}
+
+/// Sends what is pending right away; called once the tunnel is down, so the last lines of a
+/// session arrive with it.
+pub fn flush(app: &AppHandle) {
+  if let Some(batcher) = app.try_state::<EventBatcher>() {
+    batcher.flush();
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use serde_json::json;
+
+  type Emitted = Arc<Mutex<Vec<(&'static str, Vec<Value>)>>>;
+
+  fn recording(options: BatchOptions) -> (EventBatcher, Emitted) {
+    let emitted: Emitted = Arc::default();
+    let sink = emitted.clone();
+    let batcher = EventBatcher::new(
+      options,
+      Arc::new(move |event, items| sink.lock().unwrap().push((event, items))),
+    );
+    (batcher, emitted)
+  }
+
+  fn sizes(emitted: &Emitted) -> Vec<(&'static str, usize)> {
+    let emitted = emitted.lock().unwrap();
+    emitted
+      .iter()
+      .map(|(event, items)| (*event, items.len()))
+      .collect()
+  }
+
+  #[test]
+  fn rapid_items_go_out_in_full_batches_then_the_rest() {
+    let (batcher, emitted) = recording(BatchOptions {
+      flush_ms: 100,
+      max_batch: 100,
+    });
+
+    for n in 0..250 {
+      batcher.push("vpn://log", json!({ "level": "info", "message": n }));
+    }
+    assert_eq!(sizes(&emitted), [("vpn://log", 100), ("vpn://log", 100)]);
+
+    assert_eq!(batcher.flush(), 1);
+    assert_eq!(
+      sizes(&emitted),
+      [("vpn://log", 100), ("vpn://log", 100), ("vpn://log", 50)]
+    );
+    let emitted = emitted.lock().unwrap();
+    let order: Vec<Value> = emitted
+      .iter()
+      .flat_map(|(_, items)| items)
+      .map(|item| item["message"].clone())
+      .collect();
+    assert_eq!(order, (0..250).map(|n| json!(n)).collect::<Vec<_>>());
+  }
+
+  #[test]
+  fn each_event_gets_its_own_batch() {
+    let (batcher, emitted) = recording(BatchOptions::default());
+
+    for n in 0..3 {
+      batcher.push("vpn://stats", json!({ "uplinkBytes": n }));
+      batcher.push("vpn://log", json!({ "level": "info", "message": "line" }));
+    }
+    assert!(emitted.lock().unwrap().is_empty());
+
+    assert_eq!(batcher.flush(), 2);
+    assert_eq!(sizes(&emitted), [("vpn://log", 3), ("vpn://stats", 3)]);
+    // Nothing pending, nothing emitted.
+    assert_eq!(batcher.flush(), 0);
+    assert_eq!(sizes(&emitted).len(), 2);
+  }
+
+  #[test]
+  fn stop_flushes_the_partial_batch() {
+    let (batcher, emitted) = recording(BatchOptions::default());
+    batcher.push(
+      "vpn://log",
+      json!({ "level": "warn", "message": "tunnel down" }),
+    );
+
+    batcher.stop();
+
+    assert_eq!(sizes(&emitted), [("vpn://log", 1)]);
+  }
+
+  #[test]
+  fn options_are_clamped() {
+    let (batcher, emitted) = recording(BatchOptions {
+      flush_ms: 0,
+      max_batch: 0,
+    });
+    assert_eq!(
+      batcher.options,
+      BatchOptions {
+        flush_ms: 10,
+        max_batch: 1,
+      }
+    );
+
+    batcher.push("vpn://stats", json!({}));
+    assert_eq!(sizes(&emitted), [("vpn://stats", 1)]);
+  }
+}
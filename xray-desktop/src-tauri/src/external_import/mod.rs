@@ -0,0 +1,348 @@
+mod parse;
+
+use crate::bridge_integrity::sha256_hex;
+use crate::config::Config;
+use crate::error::BridgeError;
+use crate::secrets::Secrets;
+use crate::servers::{ServerCatalog, ServerInfo};
+use parse::ParsedServer;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// Group imported servers are listed under, apart from the provider's regions.
+pub const IMPORTED_GROUP: &str = "Imported";
+const IMPORTED_ID_PREFIX: &str = "ext-";
+
+/// Imported servers connect through their stored outbound instead of the agent's config.
+pub fn is_imported(server_id: &str) -> bool {
+  server_id.starts_with(IMPORTED_ID_PREFIX)
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ExternalFormat {
+  /// Sniffed from the contents.
+  #[default]
+  Auto,
+  /// A v2rayN subscription: `vmess://`, `vless://`, `trojan://` and `ss://` links, one per line,
+  /// as is or in base64.
+  #[serde(rename = "v2rayN")]
+  V2rayN,
+  /// An xray or v2ray client config, or an array of them as JSON subscriptions serve.
+  XrayJson,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EntryError {
+  /// Where in the file: `line 3`, `outbounds[1]`.
+  pub entry: String,
+  pub reason: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportSummary {
+  pub format: ExternalFormat,
+  pub added: usize,
+  /// Already imported before; their outbounds are refreshed.
+  pub updated: usize,
+  /// Outbounds that aren't servers, like `freedom`.
+  pub skipped: usize,
+  pub errors: Vec<EntryError>,
+}
+
+/// The same server imported twice, from any file, gets the same id.
+fn server_id(server: &ParsedServer) -> String {
+  let digest = sha256_hex(server.outbound["settings"].to_string().as_bytes());
+  format!("{IMPORTED_ID_PREFIX}{}", &digest[..12])
+}
+
+fn server_info(id: String, server: &ParsedServer) -> ServerInfo {
+  ServerInfo {
+    id,
+    name: server.name.clone(),
+    country: None,
+    endpoint: Some(server.endpoint.clone()),
+    group: Some(IMPORTED_GROUP.to_string()),
+    transports: server.transports.clone(),
+  }
+}
+
+/// Parses the file and saves every server it could: the outbound in the keyring, the rest in
+/// `importedServers`. An entry that doesn't parse is reported and skipped; only a file that is
+/// unreadable or holds no servers at all fails the import.
+pub fn import(
+  secrets: &Secrets,
+  config: &Config,
+  path: &Path,
+  format: ExternalFormat,
+) -> Result<ImportSummary, BridgeError> {
+  let text = std::fs::read_to_string(path)
+    .map_err(|error| BridgeError::ExternalConfigIo(error.to_string()))?;
+  let parsed = parse::parse(&text, format)?;
+  let known = config.get().imported_servers;
+  let mut summary = ImportSummary {
+    format: parsed.format,
+    added: 0,
+    updated: 0,
+    skipped: parsed.skipped,
+    errors: Vec::new(),
+  };
+  let mut imported: Vec<ServerInfo> = Vec::new();
+  for entry in parsed.entries {
+    let stored = entry.result.and_then(|server| {
+      let id = server_id(&server);
+      secrets
+        .store_outbound(&id, &server.outbound)
+        .map(|()| server_info(id, &server))
+        .map_err(|error| error.to_string())
+    });
+    match stored {
+      // A file listing a server twice imports it once.
+      Ok(server) if imported.iter().any(|seen| seen.id == server.id) => {}
+      Ok(server) => imported.push(server),
+      Err(reason) => summary.errors.push(EntryError {
+        entry: entry.label,
+        reason,
+      }),
+    }
+  }
+
+  let saved = config.update(|settings| {
+    for server in &imported {
+      match settings
+        .imported_servers
+        .iter_mut()
+        .find(|known| known.id == server.id)
+      {
+        Some(known) => *known = server.clone(),
+        None => settings.imported_servers.push(server.clone()),
+      }
+    }
+  });
+  if let Err(error) = saved {
+    // Don't leave outbounds behind that no setting points at.
+    for server in &imported {
+      if !known.iter().any(|known| known.id == server.id) {
+        let _ = secrets.clear_outbound(&server.id);
+      }
+    }
+    return Err(error);
+  }
+  for server in &imported {
+    if known.iter().any(|known| known.id == server.id) {
+      summary.updated += 1;
+    } else {
+      summary.added += 1;
+    }
+  }
+  tracing::info!(
+    format = ?summary.format,
+    added = summary.added,
+    updated = summary.updated,
+    failed = summary.errors.len(),
+    "external config imported"
+  );
+  Ok(summary)
+}
+
+/// Forgets an imported server and deletes its outbound. A selection pointing at it is cleared,
+/// so `connect` doesn't keep reaching for it.
+pub fn remove(secrets: &Secrets, config: &Config, server_id: &str) -> Result<(), BridgeError> {
+  if !config
+    .get()
+    .imported_servers
+    .iter()
+    .any(|server| server.id == server_id)
+  {
+    return Err(BridgeError::UnknownServer {
+      id: server_id.to_string(),
+    });
+  }
+  config.update(|settings| {
+    settings
+      .imported_servers
+      .retain(|server| server.id != server_id);
+    if settings.selected_server_id.as_deref() == Some(server_id) {
+      settings.selected_server_id = None;
+    }
+  })?;
+  secrets.clear_outbound(server_id)
+}
+
+#[allow(non_snake_case)]
+#[tauri::command]
+pub fn importExternalConfig(
+  secrets: tauri::State<'_, Secrets>,
+  config: tauri::State<'_, Config>,
+  catalog: tauri::State<'_, ServerCatalog>,
+  path: String,
+  format: ExternalFormat,
+) -> Result<ImportSummary, BridgeError> {
+  let summary = import(&secrets, &config, Path::new(&path), format)?;
+  catalog.set_imported(config.get().imported_servers);
+  Ok(summary)
+}
+
+#[allow(non_snake_case)]
+#[tauri::command]
+pub fn removeImportedServer(
+  secrets: tauri::State<'_, Secrets>,
+  config: tauri::State<'_, Config>,
+  catalog: tauri::State<'_, ServerCatalog>,
+  serverId: String,
+) -> Result<(), BridgeError> {
+  let result = remove(&secrets, &config, &serverId);
+  catalog.set_imported(config.get().imported_servers);
+  result
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::secrets::mock::MemoryStore;
+  use crate::transport::Transport;
+
+  /// A v2rayN export: base64 of one link per line, one of them broken.
+  const SUBSCRIPTION: &str = "vless://0f3c2b1e-8d4a-4f6b-9c2d-1a2b3c4d5e6f@nl.example.com:443?type=tcp&security=reality&sni=www.microsoft.com&fp=chrome&pbk=KEY&sid=ab12#Amsterdam
+trojan://hunter2@tr.example.com:443#Trojan
+vless://@broken.example.com:443#Broken
+";
+
+  fn setup(dir: &tempfile::TempDir) -> (Secrets, Config) {
+    (
+      Secrets::new(Box::new(MemoryStore::default())),
+      Config::load(dir.path().join("settings.json")),
+    )
+  }
+
+  fn write(dir: &tempfile::TempDir, contents: &str) -> std::path::PathBuf {
+    let path = dir.path().join("export.txt");
+    std::fs::write(&path, contents).unwrap();
+    path
+  }
+
+  #[test]
+  fn imports_a_subscription_and_reports_bad_entries() {
+    let dir = tempfile::tempdir().unwrap();
+    let (secrets, config) = setup(&dir);
+
+    let summary = import(
+      &secrets,
+      &config,
+      &write(&dir, SUBSCRIPTION),
+      ExternalFormat::Auto,
+    )
+    .unwrap();
+
+    assert_eq!(
+      summary,
+      ImportSummary {
+        format: ExternalFormat::V2rayN,
+        added: 2,
+        updated: 0,
+        skipped: 0,
+        errors: vec![EntryError {
+          entry: "line 3".to_string(),
+          reason: "missing user id".to_string(),
+        }],
+      }
+    );
+    let servers = config.get().imported_servers;
+    assert_eq!(
+      servers
+        .iter()
+        .map(|server| (server.name.as_str(), server.endpoint.as_deref()))
+        .collect::<Vec<_>>(),
+      [
+        ("Amsterdam", Some("nl.example.com:443")),
+        ("Trojan", Some("tr.example.com:443")),
+      ]
+    );
+    assert_eq!(servers[0].transports, [Transport::Reality]);
+    assert_eq!(servers[0].group.as_deref(), Some(IMPORTED_GROUP));
+    assert!(servers.iter().all(|server| is_imported(&server.id)));
+
+    // Credentials are in the keyring only.
+    let raw = std::fs::read_to_string(dir.path().join("settings.json")).unwrap();
+    assert!(!raw.contains("hunter2") && !raw.contains("0f3c2b1e"));
+    let outbound = secrets.load_outbound(&servers[1].id).unwrap().unwrap();
+    assert_eq!(outbound["settings"]["servers"][0]["password"], "hunter2");
+  }
+
+  #[test]
+  fn importing_again_updates_instead_of_duplicating() {
+    let dir = tempfile::tempdir().unwrap();
+    let (secrets, config) = setup(&dir);
+    let path = write(&dir, SUBSCRIPTION);
+
+    import(&secrets, &config, &path, ExternalFormat::V2rayN).unwrap();
+    let again = import(&secrets, &config, &path, ExternalFormat::V2rayN).unwrap();
+
+    assert_eq!((again.added, again.updated), (0, 2));
+    assert_eq!(config.get().imported_servers.len(), 2);
+  }
+
+  #[test]
+  fn removing_forgets_the_server_and_its_outbound() {
+    let dir = tempfile::tempdir().unwrap();
+    let (secrets, config) = setup(&dir);
+    import(
+      &secrets,
+      &config,
+      &write(&dir, SUBSCRIPTION),
+      ExternalFormat::V2rayN,
+    )
+    .unwrap();
+    let id = config.get().imported_servers[0].id.clone();
+    config
+      .update(|settings| settings.selected_server_id = Some(id.clone()))
+      .unwrap();
+
+    remove(&secrets, &config, &id).unwrap();
+
+    let settings = config.get();
+    assert_eq!(settings.imported_servers.len(), 1);
+    assert_eq!(settings.selected_server_id, None);
+    assert_eq!(secrets.load_outbound(&id).unwrap(), None);
+    assert_eq!(
+      remove(&secrets, &config, &id).unwrap_err().kind(),
+      "unknownServer"
+    );
+  }
+
+  #[test]
+  fn unreadable_or_empty_files_import_nothing() {
+    let dir = tempfile::tempdir().unwrap();
+    let (secrets, config) = setup(&dir);
+
+    let missing = import(
+      &secrets,
+      &config,
+      &dir.path().join("missing.json"),
+      ExternalFormat::Auto,
+    );
+    assert_eq!(missing.unwrap_err().kind(), "externalConfigIo");
+    let garbage = import(
+      &secrets,
+      &config,
+      &write(&dir, "definitely not a config"),
+      ExternalFormat::Auto,
+    );
+    assert_eq!(garbage.unwrap_err().kind(), "externalConfigInvalid");
+    assert!(config.get().imported_servers.is_empty());
+  }
+
+  #[test]
+  fn formats_use_their_ui_names() {
+    assert_eq!(
+      serde_json::from_str::<ExternalFormat>("\"v2rayN\"").unwrap(),
+      ExternalFormat::V2rayN
+    );
+    assert_eq!(
+      serde_json::to_value(ExternalFormat::XrayJson).unwrap(),
+      "xrayJson"
+    );
+  }
+}
@@ -0,0 +1,768 @@
+use super::ExternalFormat;
+use crate::error::BridgeError;
+use crate::transport::Transport;
+use serde_json::{json, Map, Value};
+use tauri::Url;
+
+/// A proxy outbound found in the file, rebuilt as an xray outbound whatever it was written as.
+#[derive(Clone, PartialEq)]
+pub struct ParsedServer {
+  pub name: String,
+  pub protocol: &'static str,
+  /// `host:port`, with an IPv6 host in brackets.
+  pub endpoint: String,
+  pub transports: Vec<Transport>,
+  /// Holds the UUID or password, so it only ever goes to the keyring.
+  pub outbound: Value,
+}
+
+// Hand-written so a stray `{:?}` can never print the credentials.
+impl std::fmt::Debug for ParsedServer {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.debug_struct("ParsedServer")
+      .field("name", &self.name)
+      .field("protocol", &self.protocol)
+      .field("endpoint", &self.endpoint)
+      .field("outbound", &"<redacted>")
+      .finish()
+  }
+}
+
+/// One server of the file, by where it was (`line 3`, `outbounds[1]`). The error never quotes
+/// the entry, which may hold a password.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Entry {
+  pub label: String,
+  pub result: Result<ParsedServer, String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Parsed {
+  /// `format` as given, or what `Auto` took the file for.
+  pub format: ExternalFormat,
+  pub entries: Vec<Entry>,
+  /// Outbounds that aren't servers, like `freedom` and `blackhole`.
+  pub skipped: usize,
+}
+
+/// Outbound protocols xray routes with, not to.
+const NOT_SERVERS: &[&str] = &["freedom", "blackhole", "dns", "loopback"];
+
+fn invalid(reason: impl Into<String>) -> BridgeError {
+  BridgeError::ExternalConfigInvalid(reason.into())
+}
+
+/// Standard or URL-safe alphabet, padding optional and whitespace ignored, as subscriptions
+/// come in every variant of it.
+pub fn decode_base64(text: &str) -> Option<Vec<u8>> {
+  let mut decoded = Vec::with_capacity(text.len() * 3 / 4);
+  let (mut bits, mut count) = (0u32, 0u32);
+  for byte in text.bytes() {
+    let value = match byte {
+      b'A'..=b'Z' => byte - b'A',
+      b'a'..=b'z' => byte - b'a' + 26,
+      b'0'..=b'9' => byte - b'0' + 52,
+      b'+' | b'-' => 62,
+      b'/' | b'_' => 63,
+      b'=' => break,
+      byte if byte.is_ascii_whitespace() => continue,
+      _ => return None,
+    };
+    bits = (bits << 6) | u32::from(value);
+    count += 6;
+    if count >= 8 {
+      count -= 8;
+      decoded.push((bits >> count) as u8);
+      bits &= (1 << count) - 1;
+    }
+  }
+  Some(decoded)
+}
+
+fn decode_base64_text(text: &str) -> Option<String> {
+  String::from_utf8(decode_base64(text)?).ok()
+}
+
+fn percent_decode(text: &str) -> String {
+  let bytes = text.as_bytes();
+  let mut decoded = Vec::with_capacity(bytes.len());
+  let mut at = 0;
+  while at < bytes.len() {
+    let escaped = (bytes[at] == b'%')
+      .then(|| bytes.get(at + 1..at + 3))
+      .flatten()
+      .and_then(|hex| std::str::from_utf8(hex).ok())
+      .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+    match escaped {
+      Some(byte) => {
+        decoded.push(byte);
+        at += 3;
+      }
+      None => {
+        decoded.push(bytes[at]);
+        at += 1;
+      }
+    }
+  }
+  String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// `Auto` takes JSON for an xray config and anything else for a v2rayN subscription, plain or
+/// base64.
+pub fn sniff(text: &str) -> Result<ExternalFormat, BridgeError> {
+  let text = text.trim();
+  if text.starts_with('{') || text.starts_with('[') {
+    return Ok(ExternalFormat::XrayJson);
+  }
+  let has_links = |text: &str| text.lines().any(|line| line.contains("://"));
+  if has_links(text) || decode_base64_text(text).is_some_and(|decoded| has_links(&decoded)) {
+    return Ok(ExternalFormat::V2rayN);
+  }
+  Err(invalid("not an xray JSON config or a v2rayN subscription"))
+}
+
+pub fn parse(text: &str, format: ExternalFormat) -> Result<Parsed, BridgeError> {
+  let text = text.trim_start_matches('\u{feff}');
+  let format = match format {
+    ExternalFormat::Auto => sniff(text)?,
+    format => format,
+  };
+  let (entries, skipped) = match format {
+    ExternalFormat::XrayJson => parse_xray_json(text)?,
+    ExternalFormat::V2rayN | ExternalFormat::Auto => (parse_subscription(text)?, 0),
+  };
+  if entries.is_empty() {
+    return Err(invalid("no servers in the file"));
+  }
+  Ok(Parsed {
+    format,
+    entries,
+    skipped,
+  })
+}
+
+fn parse_subscription(text: &str) -> Result<Vec<Entry>, BridgeError> {
+  let decoded;
+  let text = if text.contains("://") {
+    text
+  } else {
+    decoded = decode_base64_text(text)
+      .ok_or_else(|| invalid("subscription is neither links nor base64"))?;
+    &decoded
+  };
+  Ok(
+    text
+      .lines()
+      .enumerate()
+      .map(|(index, line)| (index + 1, line.trim()))
+      .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+      .map(|(number, line)| Entry {
+        label: format!("line {number}"),
+        result: parse_link(line),
+      })
+      .collect(),
+  )
+}
+
+/// `vmess://`, `vless://`, `trojan://` or `ss://`, as v2rayN shares them.
+pub fn parse_link(link: &str) -> Result<ParsedServer, String> {
+  let (scheme, rest) = link
+    .split_once("://")
+    .ok_or_else(|| "not a share link".to_string())?;
+  match scheme.to_ascii_lowercase().as_str() {
+    "vmess" => parse_vmess(rest),
+    "vless" => parse_url_link(link, "vless"),
+    "trojan" => parse_url_link(link, "trojan"),
+    "ss" => parse_shadowsocks(rest),
+    other => Err(format!("unsupported link scheme {other:?}")),
+  }
+}
+
+fn endpoint(address: &str, port: u16) -> String {
+  if address.contains(':') && !address.starts_with('[') {
+    format!("[{address}]:{port}")
+  } else {
+    format!("{address}:{port}")
+  }
+}
+
+fn port_of(value: Option<&Value>) -> Result<u16, String> {
+  let port = match value {
+    Some(Value::Number(number)) => number.as_u64().and_then(|port| u16::try_from(port).ok()),
+    Some(Value::String(text)) => text.trim().parse().ok(),
+    _ => None,
+  };
+  port
+    .filter(|port| *port != 0)
+    .ok_or_else(|| "missing or invalid port".to_string())
+}
+
+fn required<'a>(value: Option<&'a str>, what: &str) -> Result<&'a str, String> {
+  value
+    .map(str::trim)
+    .filter(|value| !value.is_empty())
+    .ok_or_else(|| format!("missing {what}"))
+}
+
+/// Transport options as share links carry them, named as in v2rayN's query strings.
+#[derive(Debug, Default)]
+struct Stream<'a> {
+  network: Option<&'a str>,
+  security: Option<&'a str>,
+  sni: Option<&'a str>,
+  host: Option<&'a str>,
+  path: Option<&'a str>,
+  service_name: Option<&'a str>,
+  fingerprint: Option<&'a str>,
+  public_key: Option<&'a str>,
+  short_id: Option<&'a str>,
+}
+
+fn insert(map: &mut Map<String, Value>, key: &str, value: Option<&str>) {
+  if let Some(value) = value.map(str::trim).filter(|value| !value.is_empty()) {
+    map.insert(key.to_string(), json!(value));
+  }
+}
+
+fn stream_settings(stream: &Stream<'_>) -> Result<Value, String> {
+  let network = stream
+    .network
+    .filter(|network| !network.is_empty())
+    .unwrap_or("tcp");
+  let security = stream
+    .security
+    .filter(|security| !security.is_empty())
+    .unwrap_or("none");
+  let mut settings = Map::new();
+  settings.insert("network".to_string(), json!(network));
+  settings.insert("security".to_string(), json!(security));
+  match network {
+    "ws" | "httpupgrade" | "xhttp" | "splithttp" => {
+      let mut transport = Map::new();
+      insert(&mut transport, "path", stream.path);
+      insert(&mut transport, "host", stream.host);
+      let key = match network {
+        "ws" => "wsSettings",
+        "httpupgrade" => "httpupgradeSettings",
+        _ => "xhttpSettings",
+      };
+      settings.insert(key.to_string(), Value::Object(transport));
+    }
+    "grpc" => {
+      let mut transport = Map::new();
+      insert(
+        &mut transport,
+        "serviceName",
+        stream.service_name.or(stream.path),
+      );
+      settings.insert("grpcSettings".to_string(), Value::Object(transport));
+    }
+    "tcp" | "raw" | "kcp" | "quic" | "h2" | "http" => {}
+    other => return Err(format!("unsupported network {other:?}")),
+  }
+  match security {
+    "tls" => {
+      let mut tls = Map::new();
+      insert(&mut tls, "serverName", stream.sni.or(stream.host));
+      insert(&mut tls, "fingerprint", stream.fingerprint);
+      settings.insert("tlsSettings".to_string(), Value::Object(tls));
+    }
+    "reality" => {
+      let mut reality = Map::new();
+      insert(&mut reality, "serverName", stream.sni);
+      insert(&mut reality, "fingerprint", stream.fingerprint);
+      insert(
+        &mut reality,
+        "publicKey",
+        Some(required(stream.public_key, "reality public key (pbk)")?),
+      );
+      insert(&mut reality, "shortId", stream.short_id);
+      settings.insert("realitySettings".to_string(), Value::Object(reality));
+    }
+    "none" => {}
+    other => return Err(format!("unsupported security {other:?}")),
+  }
+  Ok(Value::Object(settings))
+}
+
+/// What `setTransport` could pin on this server.
+fn transports(protocol: &str, stream: &Value) -> Vec<Transport> {
+  if protocol != "vless" {
+    return Vec::new();
+  }
+  let transport = match (stream["security"].as_str(), stream["network"].as_str()) {
+    (Some("reality"), _) => Transport::Reality,
+    (_, Some("ws")) => Transport::VlessWs,
+    (_, Some("grpc")) => Transport::VlessGrpc,
+    _ => return Vec::new(),
+  };
+  vec![transport]
+}
+
+fn server(
+  name: Option<&str>,
+  protocol: &'static str,
+  address: &str,
+  port: u16,
+  outbound: Value,
+) -> ParsedServer {
+  let endpoint = endpoint(address, port);
+  ParsedServer {
+    name: name
+      .map(str::trim)
+      .filter(|name| !name.is_empty())
+      .unwrap_or(endpoint.as_str())
+      .to_string(),
+    protocol,
+    transports: transports(protocol, &outbound["streamSettings"]),
+    endpoint,
+    outbound,
+  }
+}
+
+/// `vmess://` + base64 of v2rayN's JSON (`add`, `port`, `id`, `net`, `tls`, ...).
+fn parse_vmess(rest: &str) -> Result<ParsedServer, String> {
+  let json = decode_base64_text(rest).ok_or_else(|| "vmess link is not base64".to_string())?;
+  let link: Value =
+    serde_json::from_str(&json).map_err(|_| "vmess link is not v2rayN JSON".to_string())?;
+  let text = |key: &str| link[key].as_str();
+  let address = required(text("add"), "address")?;
+  let port = port_of(link.get("port"))?;
+  let id = required(text("id"), "user id")?;
+  let alter_id = port_of(link.get("aid")).unwrap_or(0);
+  let stream = stream_settings(&Stream {
+    network: text("net"),
+    security: text("tls"),
+    sni: text("sni"),
+    host: text("host"),
+    path: text("path"),
+    service_name: text("path"),
+    fingerprint: text("fp"),
+    ..Stream::default()
+  })?;
+  let outbound = json!({
+    "protocol": "vmess",
+    "settings": { "vnext": [{
+      "address": address,
+      "port": port,
+      "users": [{ "id": id, "alterId": alter_id, "security": text("scy").unwrap_or("auto") }],
+    }] },
+    "streamSettings": stream,
+  });
+  Ok(server(text("ps"), "vmess", address, port, outbound))
+}
+
+/// `vless://<uuid>@host:port?type=ws&security=tls&...#name`, and `trojan://` with the
+/// password in place of the UUID.
+fn parse_url_link(link: &str, protocol: &'static str) -> Result<ParsedServer, String> {
+  let url = Url::parse(link).map_err(|_| format!("malformed {protocol} link"))?;
+  let secret = percent_decode(url.username());
+  let secret = required(
+    Some(secret.as_str()),
+    if protocol == "vless" {
+      "user id"
+    } else {
+      "password"
+    },
+  )?;
+  let address = required(url.host_str(), "address")?;
+  let address = address.trim_start_matches('[').trim_end_matches(']');
+  let port = url
+    .port()
+    .ok_or_else(|| "missing or invalid port".to_string())?;
+  let query: Vec<(String, String)> = url.query_pairs().into_owned().collect();
+  let param = |key: &str| {
+    query
+      .iter()
+      .find(|(name, _)| name == key)
+      .map(|(_, value)| value.as_str())
+  };
+  let default_security = if protocol == "trojan" { "tls" } else { "none" };
+  let stream = stream_settings(&Stream {
+    network: param("type"),
+    security: Some(param("security").unwrap_or(default_security)),
+    sni: param("sni").or(param("peer")),
+    host: param("host"),
+    path: param("path"),
+    service_name: param("serviceName"),
+    fingerprint: param("fp"),
+    public_key: param("pbk"),
+    short_id: param("sid"),
+  })?;
+  let settings = if protocol == "vless" {
+    let mut user = Map::new();
+    user.insert("id".to_string(), json!(secret));
+    user.insert(
+      "encryption".to_string(),
+      json!(param("encryption").unwrap_or("none")),
+    );
+    insert(&mut user, "flow", param("flow"));
+    json!({ "vnext": [{ "address": address, "port": port, "users": [user] }] })
+  } else {
+    json!({ "servers": [{ "address": address, "port": port, "password": secret }] })
+  };
+  let outbound = json!({
+    "protocol": protocol,
+    "settings": settings,
+    "streamSettings": stream,
+  });
+  let name = url.fragment().map(percent_decode);
+  Ok(server(name.as_deref(), protocol, address, port, outbound))
+}
+
+/// SIP002 `ss://<base64 method:password>@host:port#name`, or the older form with everything
+/// before `#` in base64.
+fn parse_shadowsocks(rest: &str) -> Result<ParsedServer, String> {
+  let (body, name) = match rest.split_once('#') {
+    Some((body, name)) => (body, Some(percent_decode(name))),
+    None => (rest, None),
+  };
+  let body = body.split(['?', '/']).next().unwrap_or_default();
+  let (user_info, host_port) = match body.rsplit_once('@') {
+    Some((user_info, host_port)) => {
+      let user_info = percent_decode(user_info);
+      let user_info = decode_base64_text(&user_info)
+        .filter(|decoded| decoded.contains(':'))
+        .unwrap_or(user_info);
+      (user_info, host_port.to_string())
+    }
+    None => {
+      let decoded =
+        decode_base64_text(body).ok_or_else(|| "shadowsocks link is not base64".to_string())?;
+      let (user_info, host_port) = decoded
+        .rsplit_once('@')
+        .ok_or_else(|| "missing address".to_string())?;
+      (user_info.to_string(), host_port.to_string())
+    }
+  };
+  let (method, password) = user_info
+    .split_once(':')
+    .ok_or_else(|| "missing method or password".to_string())?;
+  let method = required(Some(method), "method")?;
+  let password = required(Some(password), "password")?;
+  let (address, port) = host_port
+    .rsplit_once(':')
+    .ok_or_else(|| "missing or invalid port".to_string())?;
+  let address = required(
+    Some(address.trim_start_matches('[').trim_end_matches(']')),
+    "address",
+  )?;
+  let port = port_of(Some(&json!(port)))?;
+  let outbound = json!({
+    "protocol": "shadowsocks",
+    "settings": { "servers": [{
+      "address": address,
+      "port": port,
+      "method": method,
+      "password": password,
+    }] },
+  });
+  Ok(server(
+    name.as_deref(),
+    "shadowsocks",
+    address,
+    port,
+    outbound,
+  ))
+}
+
+/// An xray config (`{ "outbounds": [...] }`), or the JSON subscription form: an array of them,
+/// each named by its `remarks`.
+fn parse_xray_json(text: &str) -> Result<(Vec<Entry>, usize), BridgeError> {
+  let value: Value = serde_json::from_str(text).map_err(|error| invalid(error.to_string()))?;
+  let configs: Vec<(String, &Value)> = match &value {
+    Value::Array(configs) => configs
+      .iter()
+      .enumerate()
+      .map(|(index, config)| (format!("[{index}]."), config))
+      .collect(),
+    config => vec![(String::new(), config)],
+  };
+  let mut entries = Vec::new();
+  let mut skipped = 0;
+  for (prefix, config) in configs {
+    let Some(outbounds) = config["outbounds"].as_array() else {
+      entries.push(Entry {
+        label: format!("{prefix}outbounds"),
+        result: Err("missing outbounds array".to_string()),
+      });
+      continue;
+    };
+    for (index, outbound) in outbounds.iter().enumerate() {
+      let protocol = outbound["protocol"].as_str().unwrap_or_default();
+      if NOT_SERVERS.contains(&protocol) {
+        skipped += 1;
+        continue;
+      }
+      entries.push(Entry {
+        label: format!("{prefix}outbounds[{index}]"),
+        result: parse_outbound(outbound, config["remarks"].as_str()),
+      });
+    }
+  }
+  Ok((entries, skipped))
+}
+
+/// A single-config subscription tags its server `proxy`, which names nothing; `remarks` does.
+fn parse_outbound(outbound: &Value, remarks: Option<&str>) -> Result<ParsedServer, String> {
+  let protocol = match outbound["protocol"].as_str() {
+    Some("vless") => "vless",
+    Some("vmess") => "vmess",
+    Some("trojan") => "trojan",
+    Some("shadowsocks") => "shadowsocks",
+    Some(other) => return Err(format!("unsupported protocol {other:?}")),
+    None => return Err("missing protocol".to_string()),
+  };
+  let settings = &outbound["settings"];
+  let target = match protocol {
+    "vless" | "vmess" => &settings["vnext"][0],
+    _ => &settings["servers"][0],
+  };
+  let address = required(target["address"].as_str(), "address")?;
+  let port = port_of(target.get("port"))?;
+  match protocol {
+    "vless" | "vmess" => {
+      required(target["users"][0]["id"].as_str(), "user id")?;
+    }
+    "shadowsocks" => {
+      required(target["method"].as_str(), "method")?;
+      required(target["password"].as_str(), "password")?;
+    }
+    _ => {
+      required(target["password"].as_str(), "password")?;
+    }
+  }
+  let tag = outbound["tag"].as_str().filter(|tag| *tag != "proxy");
+  Ok(server(
+    tag.or(remarks),
+    protocol,
+    address,
+    port,
+    outbound.clone(),
+  ))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn base64(text: &str) -> String {
+    const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut encoded = String::new();
+    for chunk in text.as_bytes().chunks(3) {
+      let bits = chunk.iter().enumerate().fold(0u32, |bits, (at, byte)| {
+        bits | u32::from(*byte) << (16 - 8 * at)
+      });
+      for at in 0..=chunk.len() {
+        encoded.push(ALPHABET[(bits >> (18 - 6 * at) & 63) as usize] as char);
+      }
+    }
+    encoded
+  }
+
+  #[test]
+  fn decodes_every_base64_variant() {
+    assert_eq!(
+      decode_base64_text("aGVsbG8gd29ybGQ=").unwrap(),
+      "hello world"
+    );
+    assert_eq!(
+      decode_base64_text("aGVsbG8gd29ybGQ").unwrap(),
+      "hello world"
+    );
+    assert_eq!(
+      decode_base64_text("aGVs\nbG8g\r\nd29y bGQ=").unwrap(),
+      "hello world"
+    );
+    assert_eq!(decode_base64("-_8"), decode_base64("+/8"));
+    assert_eq!(base64("hello world"), "aGVsbG8gd29ybGQ");
+    assert!(decode_base64("not base64!").is_none());
+  }
+
+  #[test]
+  fn parses_vless_reality_and_ws_links() {
+    let reality = parse_link(
+      "vless://0f3c2b1e-8d4a-4f6b-9c2d-1a2b3c4d5e6f@nl.example.com:443?type=tcp&security=reality\
+       &sni=www.microsoft.com&fp=chrome&pbk=KEY&sid=ab12&flow=xtls-rprx-vision#Amsterdam%20%F0%9F%87%B3%F0%9F%87%B1",
+    )
+    .unwrap();
+    assert_eq!(reality.name, "Amsterdam 🇳🇱");
+    assert_eq!(reality.endpoint, "nl.example.com:443");
+    assert_eq!(reality.transports, [Transport::Reality]);
+    assert_eq!(
+      reality.outbound["settings"]["vnext"][0]["users"][0],
+      json!({ "id": "0f3c2b1e-8d4a-4f6b-9c2d-1a2b3c4d5e6f", "encryption": "none", "flow": "xtls-rprx-vision" })
+    );
+    assert_eq!(
+      reality.outbound["streamSettings"]["realitySettings"],
+      json!({ "serverName": "www.microsoft.com", "fingerprint": "chrome", "publicKey": "KEY", "shortId": "ab12" })
+    );
+
+    let ws =
+      parse_link("vless://id@[2001:db8::1]:8443?type=ws&security=tls&path=%2Fws&host=cdn.example")
+        .unwrap();
+    assert_eq!(ws.name, "[2001:db8::1]:8443");
+    assert_eq!(ws.transports, [Transport::VlessWs]);
+    assert_eq!(
+      ws.outbound["streamSettings"],
+      json!({
+        "network": "ws",
+        "security": "tls",
+        "wsSettings": { "path": "/ws", "host": "cdn.example" },
+        "tlsSettings": { "serverName": "cdn.example" },
+      })
+    );
+  }
+
+  #[test]
+  fn parses_vmess_trojan_and_shadowsocks_links() {
+    let vmess = format!(
+      "vmess://{}",
+      base64(
+        r#"{"v":"2","ps":"Frankfurt","add":"de.example.com","port":"443","id":"uuid-1","aid":"0","net":"grpc","path":"svc","tls":"tls"}"#
+      )
+    );
+    let vmess = parse_link(&vmess).unwrap();
+    assert_eq!(
+      (vmess.name.as_str(), vmess.protocol),
+      ("Frankfurt", "vmess")
+    );
+    assert_eq!(
+      vmess.outbound["streamSettings"]["grpcSettings"],
+      json!({ "serviceName": "svc" })
+    );
+    assert_eq!(vmess.transports, []);
+
+    let trojan =
+      parse_link("trojan://p%40ss@tr.example.com:443?sni=tr.example.com#Trojan").unwrap();
+    assert_eq!(
+      trojan.outbound["settings"]["servers"][0]["password"],
+      "p@ss"
+    );
+    assert_eq!(trojan.outbound["streamSettings"]["security"], "tls");
+
+    let sip002 = format!(
+      "ss://{}@ss.example.com:8388#SS",
+      base64("aes-256-gcm:secret")
+    );
+    let legacy = format!(
+      "ss://{}#SS",
+      base64("aes-256-gcm:secret@ss.example.com:8388")
+    );
+    for link in [sip002, legacy] {
+      let ss = parse_link(&link).unwrap();
+      assert_eq!(ss.endpoint, "ss.example.com:8388");
+      assert_eq!(
+        ss.outbound["settings"]["servers"][0],
+        json!({ "address": "ss.example.com", "port": 8388, "method": "aes-256-gcm", "password": "secret" })
+      );
+    }
+  }
+
+  #[test]
+  fn malformed_links_say_why_without_quoting_them() {
+    for (link, reason) in [
+      ("vless://@host:443", "missing user id"),
+      ("vless://secret-id@host", "missing or invalid port"),
+      (
+        "vless://secret-id@host:443?security=reality",
+        "missing reality public key (pbk)",
+      ),
+      (
+        "vless://secret-id@host:443?type=carrier-pigeon",
+        "unsupported network \"carrier-pigeon\"",
+      ),
+      ("vmess://@@@", "vmess link is not base64"),
+      ("ss://bm90LWFuLXNzLWxpbms", "missing address"),
+      (
+        "hysteria2://secret-id@host:443",
+        "unsupported link scheme \"hysteria2\"",
+      ),
+    ] {
+      let error = parse_link(link).unwrap_err();
+      assert_eq!(error, reason, "{link}");
+      assert!(!error.contains("secret-id"));
+    }
+  }
+
+  #[test]
+  fn sniffs_the_format() {
+    let links = "vless://id@host:443\ntrojan://pw@host:443";
+    assert_eq!(
+      sniff(" {\"outbounds\": []}").unwrap(),
+      ExternalFormat::XrayJson
+    );
+    assert_eq!(sniff("[{}]").unwrap(), ExternalFormat::XrayJson);
+    assert_eq!(sniff(links).unwrap(), ExternalFormat::V2rayN);
+    assert_eq!(sniff(&base64(links)).unwrap(), ExternalFormat::V2rayN);
+    assert_eq!(sniff("hello").unwrap_err().kind(), "externalConfigInvalid");
+  }
+
+  #[test]
+  fn xray_json_skips_direct_outbounds_and_keeps_going_past_bad_ones() {
+    let config = json!([
+      {
+        "remarks": "Tokyo",
+        "outbounds": [
+          { "tag": "proxy", "protocol": "vless", "settings": { "vnext": [{ "address": "jp.example.com", "port": 443, "users": [{ "id": "uuid" }] }] } },
+          { "tag": "direct", "protocol": "freedom" },
+          { "tag": "block", "protocol": "blackhole" },
+        ],
+      },
+      {
+        "outbounds": [
+          { "tag": "ss-out", "protocol": "shadowsocks", "settings": { "servers": [{ "address": "ss.example.com", "port": "8388", "password": "pw" }] } },
+          { "protocol": "wireguard" },
+        ],
+      },
+      { "log": {} },
+    ]);
+
+    let parsed = parse(&config.to_string(), ExternalFormat::Auto).unwrap();
+
+    assert_eq!(parsed.format, ExternalFormat::XrayJson);
+    assert_eq!(parsed.skipped, 2);
+    let results: Vec<(&str, Result<&str, &str>)> = parsed
+      .entries
+      .iter()
+      .map(|entry| {
+        (
+          entry.label.as_str(),
+          entry
+            .result
+            .as_ref()
+            .map(|server| server.name.as_str())
+            .map_err(String::as_str),
+        )
+      })
+      .collect();
+    assert_eq!(
+      results,
+      [
+        ("[0].outbounds[0]", Ok("Tokyo")),
+        ("[1].outbounds[0]", Err("missing method")),
+        (
+          "[1].outbounds[1]",
+          Err("unsupported protocol \"wireguard\"")
+        ),
+        ("[2].outbounds", Err("missing outbounds array")),
+      ]
+    );
+  }
+
+  #[test]
+  fn whole_file_failures_are_errors() {
+    assert_eq!(
+      parse("{ not json", ExternalFormat::XrayJson)
+        .unwrap_err()
+        .kind(),
+      "externalConfigInvalid"
+    );
+    assert!(parse("# only a comment", ExternalFormat::V2rayN).is_err());
+    assert!(parse(
+      r#"{"outbounds":[{"protocol":"freedom"}]}"#,
+      ExternalFormat::Auto
+    )
+    .is_err());
+  }
+}
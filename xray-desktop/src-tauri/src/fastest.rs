@@ -0,0 +1,261 @@
+use crate::bridge::Bridge;
+use crate::bridge_pool::BridgePool;
+use crate::commands;
+use crate::config::Config;
+use crate::error::BridgeError;
+use crate::latency::{self, LatencyCache, LatencyResult};
+use crate::reconnect::Watchdog;
+use crate::secrets::Secrets;
+use crate::servers::{self, ServerCatalog};
+use serde::Serialize;
+use serde_json::Value;
+use tauri::{AppHandle, Emitter, Manager};
+
+pub const CONNECT_FASTEST_EVENT: &str = "vpn://connect-fastest";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "stage", rename_all = "camelCase")]
+pub enum Progress {
+  Measuring,
+  #[serde(rename_all = "camelCase")]
+  Selecting {
+    server_id: String,
+    rtt_ms: u32,
+  },
+  #[serde(rename_all = "camelCase")]
+  Connecting {
+    server_id: String,
+  },
+}
+
+/// The reachable server with the lowest round trip.
+pub fn pick_fastest(results: &[LatencyResult]) -> Option<(&str, u32)> {
+  results
+    .iter()
+    .filter(|result| result.reachable)
+    .filter_map(|result| Some((result.server_id.as_str(), result.rtt_ms?)))
+    .min_by_key(|(_, rtt_ms)| *rtt_ms)
+}
+
+/// Everything `connect_fastest` touches, borrowed from managed state.
+pub struct Context<'a> {
+  pub bridge: &'a Bridge,
+  pub secrets: &'a Secrets,
+  pub config: &'a Config,
+  pub watchdog: &'a Watchdog,
+  pub catalog: &'a ServerCatalog,
+  pub pool: &'a BridgePool,
+  pub cache: &'a LatencyCache,
+}
+
+/// Measures (or reuses recent measurements), selects the fastest reachable server and connects
+/// to it, reporting each stage through `progress`.
+pub async fn connect_fastest(
+  context: Context<'_>,
+  progress: impl Fn(Progress),
+) -> Result<Value, BridgeError> {
+  progress(Progress::Measuring);
+  let results =
+    latency::measure(context.bridge, context.catalog, context.pool, context.cache).await?;
+  let (server_id, rtt_ms) = pick_fastest(&results).ok_or(BridgeError::NoReachableServer)?;
+  let server_id = server_id.to_string();
+
+  progress(Progress::Selecting {
+    server_id: server_id.clone(),
+    rtt_ms,
+  });
+  servers::select(
+    context.bridge,
+    context.catalog,
+    context.config,
+    server_id.clone(),
+  )
+  .await?;
+
+  progress(Progress::Connecting {
+    server_id: server_id.clone(),
+  });
+  commands::connect(
+    context.bridge,
+    context.secrets,
+    context.config,
+    context.watchdog,
+    Some(server_id),
+  )
+  .await
+}
+
+#[allow(non_snake_case)]
+#[tauri::command]
+pub async fn connectFastest(
+  app: AppHandle,
+  bridge: tauri::State<'_, Bridge>,
+  secrets: tauri::State<'_, Secrets>,
+  config: tauri::State<'_, Config>,
+  watchdog: tauri::State<'_, Watchdog>,
+  catalog: tauri::State<'_, ServerCatalog>,
+  cache: tauri::State<'_, LatencyCache>,
+) -> Result<Value, BridgeError> {
+  let pool = app.state::<BridgePool>();
+  let context = Context {
+    bridge: &bridge,
+    secrets: &secrets,
+    config: &config,
+    watchdog: &watchdog,
+    catalog: &catalog,
+    pool: &pool,
+    cache: &cache,
+  };
+  connect_fastest(context, |stage| {
+    let _ = app.emit(CONNECT_FASTEST_EVENT, stage);
+  })
+  .await
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::bridge::mock::MockBridgeRunner;
+  use crate::secrets::mock::MemoryStore;
+  use serde_json::json;
+  use std::sync::Mutex;
+  use std::time::Instant;
+  use tauri::async_runtime::block_on;
+
+  fn result(server_id: &str, rtt_ms: Option<u32>) -> LatencyResult {
+    LatencyResult {
+      server_id: server_id.to_string(),
+      rtt_ms,
+      reachable: rtt_ms.is_some(),
+    }
+  }
+
+  fn listing() -> Value {
+    json!([
+      { "id": "ams", "name": "Amsterdam" },
+      { "id": "fra", "name": "Frankfurt" },
+      { "id": "hel", "name": "Helsinki" },
+      { "id": "waw", "name": "Warsaw" }
+    ])
+  }
+
+  struct Run {
+    outcome: Result<Value, BridgeError>,
+    stages: Vec<Progress>,
+    /// Bridge calls after the initial `listServers`.
+    calls: Vec<(String, Value)>,
+  }
+
+  /// Runs `connect_fastest` against cached `measurements`.
+  fn run(measurements: Vec<LatencyResult>) -> Run {
+    let dir = tempfile::tempdir().unwrap();
+    let mock = MockBridgeRunner::default()
+      .respond("listServers", Ok(listing()))
+      .respond("selectServer", Ok(Value::Null))
+      .respond("connect", Ok(json!({ "connected": true })));
+    let bridge = Bridge::new(mock.clone());
+    let catalog = ServerCatalog::default();
+    block_on(servers::list(&bridge, &catalog)).unwrap();
+    let cache = LatencyCache::default();
+    cache.store(measurements, Instant::now());
+    let stages = Mutex::new(Vec::new());
+
+    let outcome = block_on(connect_fastest(
+      Context {
+        bridge: &bridge,
+        secrets: &Secrets::new(Box::new(MemoryStore::default())),
+        config: &Config::load(dir.path().join("settings.json")),
+        watchdog: &Watchdog::default(),
+        catalog: &catalog,
+        pool: &BridgePool::default(),
+        cache: &cache,
+      },
+      |stage| stages.lock().unwrap().push(stage),
+    ));
+
+    Run {
+      outcome,
+      stages: stages.into_inner().unwrap(),
+      calls: mock.calls().into_iter().skip(1).collect(),
+    }
+  }
+
+  #[test]
+  fn picks_the_lowest_reachable_rtt() {
+    let results = vec![
+      result("ams", Some(48)),
+      result("fra", None),
+      result("hel", Some(31)),
+      result("waw", Some(95)),
+    ];
+
+    assert_eq!(pick_fastest(&results), Some(("hel", 31)));
+    assert_eq!(pick_fastest(&[result("fra", None)]), None);
+    assert_eq!(pick_fastest(&[]), None);
+  }
+
+  #[test]
+  fn selects_and_connects_to_the_fastest_server() {
+    let Run {
+      outcome,
+      stages,
+      calls,
+    } = run(vec![
+      result("fra", None),
+      result("waw", Some(95)),
+      result("ams", Some(48)),
+    ]);
+
+    assert_eq!(outcome, Ok(json!({ "connected": true })));
+    assert_eq!(
+      stages,
+      vec![
+        Progress::Measuring,
+        Progress::Selecting {
+          server_id: "ams".to_string(),
+          rtt_ms: 48
+        },
+        Progress::Connecting {
+          server_id: "ams".to_string()
+        },
+      ]
+    );
+    assert_eq!(
+      calls,
+      [
+        ("selectServer".to_string(), json!({ "serverId": "ams" })),
+        ("connect".to_string(), json!({ "serverId": "ams" })),
+      ],
+      "fresh measurements are reused instead of pinging again"
+    );
+  }
+
+  #[test]
+  fn refuses_to_connect_when_nothing_is_reachable() {
+    let Run {
+      outcome,
+      stages,
+      calls,
+    } = run(vec![result("ams", None), result("fra", None)]);
+
+    assert_eq!(outcome, Err(BridgeError::NoReachableServer));
+    assert_eq!(stages, vec![Progress::Measuring]);
+    assert!(calls.is_empty());
+  }
+
+  #[test]
+  fn progress_serializes_with_a_stage_tag() {
+    assert_eq!(
+      serde_json::to_value(Progress::Selecting {
+        server_id: "ams".to_string(),
+        rtt_ms: 48
+      })
+      .unwrap(),
+      json!({ "stage": "selecting", "serverId": "ams", "rttMs": 48 })
+    );
+    assert_eq!(
+      serde_json::to_value(Progress::Measuring).unwrap(),
+      json!({ "stage": "measuring" })
+    );
+  }
+}
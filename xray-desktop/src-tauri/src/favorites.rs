@@ -0,0 +1,122 @@
+use crate::config::Config;
+use crate::error::BridgeError;
+
+/// How many recently connected servers are remembered.
+pub const RECENT_LIMIT: usize = 10;
+
+/// Adds or removes `server_id` from the favorites. Returns whether it is a favorite now.
+pub fn toggle(config: &Config, server_id: String) -> Result<bool, BridgeError> {
+  let mut favorite = false;
+  config.update(|settings| {
+    let before = settings.favorite_servers.len();
+    settings.favorite_servers.retain(|id| *id != server_id);
+    if settings.favorite_servers.len() == before {
+      settings.favorite_servers.push(server_id);
+      favorite = true;
+    }
+  })?;
+  Ok(favorite)
+}
+
+/// Moves `server_id` to the front of the recent list, dropping the oldest past `RECENT_LIMIT`.
+pub fn record_recent(config: &Config, server_id: &str) -> Result<(), BridgeError> {
+  config.update(|settings| {
+    settings.recent_servers.retain(|id| id != server_id);
+    settings.recent_servers.insert(0, server_id.to_string());
+    settings.recent_servers.truncate(RECENT_LIMIT);
+  })?;
+  Ok(())
+}
+
+#[allow(non_snake_case)]
+#[tauri::command]
+pub fn listFavorites(config: tauri::State<'_, Config>) -> Vec<String> {
+  config.get().favorite_servers
+}
+
+#[allow(non_snake_case)]
+#[tauri::command]
+pub fn toggleFavorite(
+  config: tauri::State<'_, Config>,
+  serverId: String,
+) -> Result<bool, BridgeError> {
+  toggle(&config, serverId)
+}
+
+/// Most recent first.
+#[allow(non_snake_case)]
+#[tauri::command]
+pub fn listRecentServers(config: tauri::State<'_, Config>) -> Vec<String> {
+  config.get().recent_servers
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::bridge::mock::MockBridgeRunner;
+  use crate::bridge::Bridge;
+  use crate::commands;
+  use crate::reconnect::Watchdog;
+  use crate::secrets::mock::MemoryStore;
+  use crate::secrets::Secrets;
+  use serde_json::json;
+  use tauri::async_runtime::block_on;
+
+  fn config(dir: &tempfile::TempDir) -> Config {
+    Config::load(dir.path().join("settings.json"))
+  }
+
+  #[test]
+  fn toggling_twice_removes_the_favorite() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = config(&dir);
+
+    assert!(toggle(&config, "srv-1".to_string()).unwrap());
+    assert!(toggle(&config, "srv-2".to_string()).unwrap());
+    assert!(!toggle(&config, "srv-1".to_string()).unwrap());
+
+    assert_eq!(config.get().favorite_servers, ["srv-2"]);
+  }
+
+  #[test]
+  fn successful_connects_update_the_recent_list() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = config(&dir);
+    let secrets = Secrets::new(Box::new(MemoryStore::default()));
+    let watchdog = Watchdog::default();
+    let connected = Bridge::new(MockBridgeRunner::default().respond("connect", Ok(json!({}))));
+    let failing =
+      Bridge::new(MockBridgeRunner::default().respond("connect", Err(BridgeError::NotConnected)));
+    let connect = |bridge: &Bridge, server_id: &str| {
+      block_on(commands::connect(
+        bridge,
+        &secrets,
+        &config,
+        &watchdog,
+        Some(server_id.to_string()),
+      ))
+    };
+
+    connect(&connected, "srv-1").unwrap();
+    connect(&connected, "srv-2").unwrap();
+    connect(&connected, "srv-1").unwrap();
+    connect(&failing, "srv-3").unwrap_err();
+
+    assert_eq!(config.get().recent_servers, ["srv-1", "srv-2"]);
+  }
+
+  #[test]
+  fn recent_list_is_capped() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = config(&dir);
+
+    for n in 0..15 {
+      record_recent(&config, &format!("srv-{n}")).unwrap();
+    }
+
+    let recent = config.get().recent_servers;
+    assert_eq!(recent.len(), RECENT_LIMIT);
+    assert_eq!(recent.first().unwrap(), "srv-14");
+    assert_eq!(recent.last().unwrap(), "srv-5");
+  }
+}
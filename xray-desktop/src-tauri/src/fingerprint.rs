@@ -0,0 +1,226 @@
+use crate::base_url::normalize_base_url;
+use crate::bridge::Bridge;
+use crate::config::Config;
+use crate::error::BridgeError;
+use serde::Deserialize;
+use serde_json::json;
+
+#[derive(Deserialize)]
+struct Reply {
+  fingerprint: String,
+}
+
+/// Canonical form of a SHA-256 fingerprint, `AB:CD:...` as browsers and `openssl` print it.
+/// Accepts any case, with or without colons or spaces.
+pub fn normalize_fingerprint(raw: &str) -> Result<String, BridgeError> {
+  let hex: String = raw
+    .chars()
+    .filter(|c| !matches!(c, ':' | ' ' | '-'))
+    .collect::<String>()
+    .to_uppercase();
+  if hex.len() != 64 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+    return Err(BridgeError::InvalidFingerprint(format!(
+      "expected 32 hex bytes, got \"{}\"",
+      raw.trim()
+    )));
+  }
+  let bytes: Vec<&str> = (0..64).step_by(2).map(|at| &hex[at..at + 2]).collect();
+  Ok(bytes.join(":"))
+}
+
+/// The pin `Bridge::call` sends along with a payload for `base_url`, if there is one.
+pub fn pinned(config: &Config, base_url: &str) -> Option<String> {
+  config.get().pinned_fingerprints.get(base_url).cloned()
+}
+
+/// Turns the bridge's `FINGERPRINT_MISMATCH` into `FingerprintMismatch`.
+pub fn with_fingerprint_code(error: BridgeError, base_url: &str, pinned: &str) -> BridgeError {
+  match error.code() {
+    Some("FINGERPRINT_MISMATCH") => BridgeError::FingerprintMismatch {
+      base_url: base_url.to_string(),
+      pinned: pinned.to_string(),
+    },
+    _ => error,
+  }
+}
+
+/// What the server presents right now, pinned or not, so a changed certificate can be looked
+/// at before pinning it instead.
+pub async fn fetch(bridge: &Bridge, base_url: &str) -> Result<String, BridgeError> {
+  let reply = bridge
+    .call("fingerprint", json!({ "baseUrl": base_url }))
+    .await?;
+  let reply: Reply =
+    serde_json::from_value(reply).map_err(|error| BridgeError::InvalidJson(error.to_string()))?;
+  normalize_fingerprint(&reply.fingerprint)
+}
+
+/// `None` un-pins. Returns whether anything changed.
+pub fn pin(
+  config: &Config,
+  base_url: &str,
+  fingerprint: Option<&str>,
+) -> Result<bool, BridgeError> {
+  let fingerprint = fingerprint.map(normalize_fingerprint).transpose()?;
+  let mut changed = false;
+  config.update(|settings| {
+    let pins = &mut settings.pinned_fingerprints;
+    changed = match fingerprint {
+      Some(fingerprint) => {
+        pins.insert(base_url.to_string(), fingerprint.clone()) != Some(fingerprint)
+      }
+      None => pins.remove(base_url).is_some(),
+    };
+  })?;
+  Ok(changed)
+}
+
+#[allow(non_snake_case)]
+#[tauri::command]
+pub async fn getServerFingerprint(
+  bridge: tauri::State<'_, Bridge>,
+  config: tauri::State<'_, Config>,
+  baseUrl: String,
+) -> Result<String, BridgeError> {
+  let base_url = normalize_base_url(&baseUrl, config.get().allow_insecure_base_url)?;
+  fetch(&bridge, &base_url).await
+}
+
+#[allow(non_snake_case)]
+#[tauri::command]
+pub fn pinServerFingerprint(
+  config: tauri::State<'_, Config>,
+  baseUrl: String,
+  fingerprint: Option<String>,
+) -> Result<bool, BridgeError> {
+  let base_url = normalize_base_url(&baseUrl, config.get().allow_insecure_base_url)?;
+  pin(&config, &base_url, fingerprint.as_deref())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::bridge::mock::MockBridgeRunner;
+  use serde_json::Value;
+  use std::sync::Arc;
+  use tauri::async_runtime::block_on;
+
+  const BASE_URL: &str = "https://cp.example.com";
+  const LIVE: &str = "3A:1F:00:9B:C4:E2:77:58:0D:AA:61:2C:9E:F0:13:B8:\
+     45:6D:E7:02:8C:39:F1:5A:B6:0E:D4:72:1B:C8:93:6F";
+
+  fn pinned_bridge(mock: &MockBridgeRunner, config: &Arc<Config>) -> Bridge {
+    let bridge = Bridge::new(mock.clone());
+    let config = config.clone();
+    bridge.set_pin_source(Arc::new(move |base_url| pinned(&config, base_url)));
+    bridge
+  }
+
+  #[test]
+  fn normalizes_any_spelling() {
+    let bare = LIVE.replace(':', "").to_lowercase();
+
+    assert_eq!(normalize_fingerprint(&bare).unwrap(), LIVE);
+    assert_eq!(normalize_fingerprint(&format!(" {LIVE} ")).unwrap(), LIVE);
+    assert_eq!(
+      normalize_fingerprint("3A:1F").unwrap_err().kind(),
+      "invalidFingerprint"
+    );
+    assert!(normalize_fingerprint(&"zz".repeat(32)).is_err());
+  }
+
+  #[test]
+  fn fetches_the_live_fingerprint_without_checking_the_pin() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = Arc::new(Config::load(dir.path().join("settings.json")));
+    pin(&config, BASE_URL, Some(&"00".repeat(32))).unwrap();
+    let mock = MockBridgeRunner::default().respond(
+      "fingerprint",
+      Ok(json!({ "fingerprint": LIVE.to_lowercase() })),
+    );
+
+    let live = block_on(fetch(&pinned_bridge(&mock, &config), BASE_URL)).unwrap();
+
+    assert_eq!(live, LIVE);
+    assert_eq!(
+      mock.calls(),
+      [("fingerprint".to_string(), json!({ "baseUrl": BASE_URL }))]
+    );
+  }
+
+  #[test]
+  fn pinned_fingerprint_goes_with_every_call_for_that_url() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = Arc::new(Config::load(dir.path().join("settings.json")));
+    assert!(pin(&config, BASE_URL, Some(LIVE)).unwrap());
+    assert!(!pin(&config, BASE_URL, Some(LIVE)).unwrap());
+    assert_eq!(
+      Config::load(dir.path().join("settings.json"))
+        .get()
+        .pinned_fingerprints
+        .get(BASE_URL)
+        .map(String::as_str),
+      Some(LIVE)
+    );
+    let mock = MockBridgeRunner::default()
+      .respond("verifyAdminKey", Ok(json!({ "ok": true })))
+      .respond("ping", Ok(Value::Null));
+    let bridge = pinned_bridge(&mock, &config);
+
+    block_on(bridge.call(
+      "verifyAdminKey",
+      json!({ "baseUrl": BASE_URL, "adminApiKey": "key" }),
+    ))
+    .unwrap();
+    block_on(bridge.call("ping", json!({ "baseUrl": "https://other.example.com" }))).unwrap();
+
+    assert_eq!(
+      mock.calls(),
+      [
+        (
+          "verifyAdminKey".to_string(),
+          json!({ "baseUrl": BASE_URL, "adminApiKey": "key", "pinnedFingerprint": LIVE })
+        ),
+        (
+          "ping".to_string(),
+          json!({ "baseUrl": "https://other.example.com" })
+        ),
+      ]
+    );
+  }
+
+  #[test]
+  fn mismatch_is_its_own_error_until_unpinned() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = Arc::new(Config::load(dir.path().join("settings.json")));
+    pin(&config, BASE_URL, Some(LIVE)).unwrap();
+    let mock = MockBridgeRunner::default().respond(
+      "verifyAdminKey",
+      Err(BridgeError::BridgeReported {
+        code: Some("FINGERPRINT_MISMATCH".to_string()),
+        message: "The certificate does not match".to_string(),
+      }),
+    );
+    let bridge = pinned_bridge(&mock, &config);
+    let verify = || {
+      block_on(bridge.call(
+        "verifyAdminKey",
+        json!({ "baseUrl": BASE_URL, "adminApiKey": "key" }),
+      ))
+    };
+
+    assert_eq!(
+      verify().unwrap_err(),
+      BridgeError::FingerprintMismatch {
+        base_url: BASE_URL.to_string(),
+        pinned: LIVE.to_string(),
+      }
+    );
+
+    assert!(pin(&config, BASE_URL, None).unwrap());
+    assert!(!pin(&config, BASE_URL, None).unwrap());
+    // Without a pin the bridge checks nothing, so its code is passed through as is.
+    assert_eq!(verify().unwrap_err().kind(), "bridgeReported");
+    assert_eq!(mock.calls()[1].1.get("pinnedFingerprint"), None);
+  }
+}
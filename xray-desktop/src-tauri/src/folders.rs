@@ -0,0 +1,182 @@
+use crate::error::BridgeError;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use tauri::{Manager, Runtime};
+
+/// The directory lookups `Folder` needs, so tests can stand in for an app.
+pub trait AppDirs {
+  fn app_config_dir(&self) -> tauri::Result<PathBuf>;
+  fn app_log_dir(&self) -> tauri::Result<PathBuf>;
+  fn app_data_dir(&self) -> tauri::Result<PathBuf>;
+}
+
+impl<R: Runtime> AppDirs for tauri::path::PathResolver<R> {
+  fn app_config_dir(&self) -> tauri::Result<PathBuf> {
+    tauri::path::PathResolver::app_config_dir(self)
+  }
+
+  fn app_log_dir(&self) -> tauri::Result<PathBuf> {
+    tauri::path::PathResolver::app_log_dir(self)
+  }
+
+  fn app_data_dir(&self) -> tauri::Result<PathBuf> {
+    tauri::path::PathResolver::app_data_dir(self)
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Folder {
+  /// `settings.json` and the window state.
+  Config,
+  /// The rolling app log.
+  Log,
+  /// Connection history.
+  Data,
+}
+
+impl Folder {
+  pub fn path(self, dirs: &impl AppDirs) -> Result<PathBuf, BridgeError> {
+    match self {
+      Self::Config => dirs.app_config_dir(),
+      Self::Log => dirs.app_log_dir(),
+      Self::Data => dirs.app_data_dir(),
+    }
+    .map_err(|error| BridgeError::OpenFailed(error.to_string()))
+  }
+}
+
+/// Creates the folder if nothing has been written there yet, then hands it to `launch`.
+/// Returns the path so the UI can show it when no file manager comes up.
+pub fn open(
+  folder: Folder,
+  dirs: &impl AppDirs,
+  launch: impl FnOnce(&Path) -> io::Result<()>,
+) -> Result<PathBuf, BridgeError> {
+  let path = folder.path(dirs)?;
+  let failed = |error: io::Error| BridgeError::OpenFailed(format!("{}: {error}", path.display()));
+  std::fs::create_dir_all(&path).map_err(failed)?;
+  launch(&path).map_err(failed)?;
+  Ok(path)
+}
+
+fn file_manager() -> &'static str {
+  if cfg!(target_os = "macos") {
+    "open"
+  } else if cfg!(windows) {
+    "explorer"
+  } else {
+    "xdg-open"
+  }
+}
+
+/// Fails on a headless Linux box, where `xdg-open` is missing or finds nothing to hand off to.
+fn open_in_file_manager(path: &Path) -> io::Result<()> {
+  let program = file_manager();
+  let status = Command::new(program).arg(path).status()?;
+  // explorer exits with 1 even when the window opened.
+  if status.success() || cfg!(windows) {
+    Ok(())
+  } else {
+    Err(io::Error::other(format!("{program} exited with {status}")))
+  }
+}
+
+fn open_for_app<R: Runtime>(
+  app: &tauri::AppHandle<R>,
+  folder: Folder,
+) -> Result<PathBuf, BridgeError> {
+  open(folder, app.path(), open_in_file_manager)
+}
+
+#[allow(non_snake_case)]
+#[tauri::command]
+pub fn openConfigDir(app: tauri::AppHandle) -> Result<PathBuf, BridgeError> {
+  open_for_app(&app, Folder::Config)
+}
+
+#[allow(non_snake_case)]
+#[tauri::command]
+pub fn openLogDir(app: tauri::AppHandle) -> Result<PathBuf, BridgeError> {
+  open_for_app(&app, Folder::Log)
+}
+
+#[allow(non_snake_case)]
+#[tauri::command]
+pub fn openDataDir(app: tauri::AppHandle) -> Result<PathBuf, BridgeError> {
+  open_for_app(&app, Folder::Data)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  struct FakeDirs(PathBuf);
+
+  impl AppDirs for FakeDirs {
+    fn app_config_dir(&self) -> tauri::Result<PathBuf> {
+      Ok(self.0.join("config"))
+    }
+
+    fn app_log_dir(&self) -> tauri::Result<PathBuf> {
+      Ok(self.0.join("logs"))
+    }
+
+    fn app_data_dir(&self) -> tauri::Result<PathBuf> {
+      Ok(self.0.join("data"))
+    }
+  }
+
+  #[test]
+  fn each_folder_uses_its_tauri_dir() {
+    let dirs = FakeDirs(PathBuf::from("/app"));
+
+    assert_eq!(
+      Folder::Config.path(&dirs).unwrap(),
+      dirs.app_config_dir().unwrap()
+    );
+    assert_eq!(
+      Folder::Log.path(&dirs).unwrap(),
+      dirs.app_log_dir().unwrap()
+    );
+    assert_eq!(
+      Folder::Data.path(&dirs).unwrap(),
+      dirs.app_data_dir().unwrap()
+    );
+  }
+
+  #[test]
+  fn a_missing_folder_is_created_before_it_is_opened() {
+    let root = tempfile::tempdir().unwrap();
+    let dirs = FakeDirs(root.path().join("nested"));
+    let mut opened = None;
+
+    let path = open(Folder::Log, &dirs, |path| {
+      assert!(path.is_dir());
+      opened = Some(path.to_path_buf());
+      Ok(())
+    })
+    .unwrap();
+
+    assert_eq!(path, root.path().join("nested").join("logs"));
+    assert_eq!(opened, Some(path));
+  }
+
+  #[test]
+  fn a_file_manager_failure_is_an_open_failed_error() {
+    let root = tempfile::tempdir().unwrap();
+    let dirs = FakeDirs(root.path().to_path_buf());
+
+    let error = open(Folder::Data, &dirs, |_| {
+      Err(io::Error::new(
+        io::ErrorKind::NotFound,
+        "xdg-open not found",
+      ))
+    })
+    .unwrap_err();
+
+    assert_eq!(error.kind(), "openFailed");
+    assert!(error.to_string().contains("xdg-open not found"));
+    assert!(root.path().join("data").is_dir());
+  }
+}
@@ -0,0 +1,275 @@
+use crate::error::BridgeError;
+use crate::persist;
+use crate::status::{LinkState, StatusSnapshot};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tauri::{AppHandle, Manager};
+
+const HISTORY_FILE: &str = "connection-history.jsonl";
+/// Past this the oldest half of the file is dropped.
+const MAX_BYTES: u64 = 256 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum SessionEnd {
+  Disconnected,
+  ConnectionLost,
+}
+
+/// One finished session: when it started (unix seconds), where to, how it ended and how long it
+/// lasted.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConnectionEvent {
+  pub timestamp: u64,
+  pub server_id: Option<String>,
+  pub action: SessionEnd,
+  pub duration_secs: u64,
+}
+
+/// Turns the watcher's snapshots into finished sessions. A paused session is still running.
+#[derive(Debug, Default)]
+pub struct SessionRecorder {
+  started: Option<(SystemTime, Option<String>)>,
+}
+
+impl SessionRecorder {
+  pub fn observe(&mut self, snapshot: &StatusSnapshot, now: SystemTime) -> Option<ConnectionEvent> {
+    let up = snapshot.state() != LinkState::Disconnected;
+    match (&self.started, up) {
+      (None, true) => {
+        // A session found already running started `uptime_secs` ago.
+        let uptime = Duration::from_secs(snapshot.uptime_secs.unwrap_or(0));
+        let since = now.checked_sub(uptime).unwrap_or(now);
+        self.started = Some((since, snapshot.server_id.clone()));
+        None
+      }
+      (Some(_), false) => {
+        let (since, server_id) = self.started.take()?;
+        Some(ConnectionEvent {
+          timestamp: since
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs(),
+          server_id,
+          action: if snapshot.last_error.is_some() {
+            SessionEnd::ConnectionLost
+          } else {
+            SessionEnd::Disconnected
+          },
+          duration_secs: now.duration_since(since).unwrap_or_default().as_secs(),
+        })
+      }
+      _ => None,
+    }
+  }
+}
+
+/// Append-only JSONL log of finished sessions in the app data dir.
+pub struct History {
+  path: PathBuf,
+  max_bytes: u64,
+  file: Mutex<()>,
+}
+
+fn io_error(error: impl std::fmt::Display) -> BridgeError {
+  BridgeError::HistoryIo(error.to_string())
+}
+
+impl History {
+  pub fn for_app(app: &AppHandle) -> tauri::Result<Self> {
+    Ok(Self::in_dir(&app.path().app_data_dir()?))
+  }
+
+  pub fn in_dir(data_dir: &Path) -> Self {
+    Self::with_max_bytes(data_dir.join(HISTORY_FILE), MAX_BYTES)
+  }
+
+  pub fn with_max_bytes(path: PathBuf, max_bytes: u64) -> Self {
+    Self {
+      path,
+      max_bytes,
+      file: Mutex::new(()),
+    }
+  }
+
+  pub fn append(&self, event: &ConnectionEvent) -> Result<(), BridgeError> {
+    let line = serde_json::to_string(event).map_err(io_error)?;
+    let _file = self.file.lock().unwrap();
+    if let Some(dir) = self.path.parent() {
+      fs::create_dir_all(dir).map_err(io_error)?;
+    }
+    let mut file = OpenOptions::new()
+      .create(true)
+      .append(true)
+      .open(&self.path)
+      .map_err(io_error)?;
+    writeln!(file, "{line}").map_err(io_error)?;
+
+    if file.metadata().map_err(io_error)?.len() > self.max_bytes {
+      self.trim()?;
+    }
+    Ok(())
+  }
+
+  /// Keeps the newest lines that fit in half the limit, so trimming doesn't run on every append.
+  fn trim(&self) -> Result<(), BridgeError> {
+    let raw = fs::read_to_string(&self.path).map_err(io_error)?;
+    let mut kept = Vec::new();
+    let mut size = 0;
+    for line in raw.lines().rev() {
+      size += line.len() as u64 + 1;
+      if size > self.max_bytes / 2 {
+        break;
+      }
+      kept.push(line);
+    }
+    kept.reverse();
+
+    let trimmed: String = kept.iter().map(|line| format!("{line}\n")).collect();
+    persist::atomic_write(&self.path, trimmed).map_err(io_error)
+  }
+
+  /// Deletes the file; returns whether there was one.
+  pub fn clear(&self) -> Result<bool, BridgeError> {
+    let _file = self.file.lock().unwrap();
+    persist::remove(&self.path).map_err(io_error)
+  }
+
+  /// The newest `limit` events, newest first. Lines that don't parse are skipped.
+  pub fn recent(&self, limit: usize) -> Result<Vec<ConnectionEvent>, BridgeError> {
+    let _file = self.file.lock().unwrap();
+    let raw = match fs::read_to_string(&self.path) {
+      Ok(raw) => raw,
+      Err(error) if error.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+      Err(error) => return Err(io_error(error)),
+    };
+    Ok(
+      raw
+        .lines()
+        .rev()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .take(limit)
+        .collect(),
+    )
+  }
+}
+
+#[allow(non_snake_case)]
+#[tauri::command]
+pub fn getConnectionHistory(
+  history: tauri::State<'_, History>,
+  limit: Option<usize>,
+) -> Result<Vec<ConnectionEvent>, BridgeError> {
+  history.recent(limit.unwrap_or(50))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use serde_json::{json, Value};
+
+  fn event(timestamp: u64) -> ConnectionEvent {
+    ConnectionEvent {
+      timestamp,
+      server_id: Some("srv-1".to_string()),
+      action: SessionEnd::Disconnected,
+      duration_secs: 60,
+    }
+  }
+
+  fn snapshot(value: Value) -> StatusSnapshot {
+    serde_json::from_value(value).unwrap()
+  }
+
+  #[test]
+  fn append_and_read_round_trip() {
+    let dir = tempfile::tempdir().unwrap();
+    let history = History::in_dir(&dir.path().join("data"));
+    assert_eq!(history.recent(10).unwrap(), vec![]);
+
+    for timestamp in 1..=3 {
+      history.append(&event(timestamp)).unwrap();
+    }
+    std::fs::OpenOptions::new()
+      .append(true)
+      .open(dir.path().join("data").join(HISTORY_FILE))
+      .unwrap()
+      .write_all(b"not json\n")
+      .unwrap();
+
+    assert_eq!(
+      History::in_dir(&dir.path().join("data")).recent(2).unwrap(),
+      vec![event(3), event(2)]
+    );
+  }
+
+  #[test]
+  fn trims_the_oldest_events_past_the_limit() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join(HISTORY_FILE);
+    let line_len = serde_json::to_string(&event(1000)).unwrap().len() as u64 + 1;
+    let history = History::with_max_bytes(path.clone(), line_len * 10);
+
+    for timestamp in 1000..1011 {
+      history.append(&event(timestamp)).unwrap();
+    }
+
+    let kept = history.recent(100).unwrap();
+    assert_eq!(kept.len(), 5);
+    assert_eq!(kept.first(), Some(&event(1010)));
+    assert_eq!(kept.last(), Some(&event(1006)));
+    assert!(std::fs::metadata(&path).unwrap().len() <= line_len * 10);
+  }
+
+  #[test]
+  fn records_one_event_per_session() {
+    let start = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+    let at = |secs| start + Duration::from_secs(secs);
+    let mut recorder = SessionRecorder::default();
+
+    let script = [
+      (0, json!({ "connected": false })),
+      (10, json!({ "connected": true, "serverId": "srv-1" })),
+      (
+        20,
+        json!({ "connected": true, "paused": true, "serverId": "srv-1" }),
+      ),
+      (70, json!({ "connected": false })),
+      (
+        80,
+        json!({ "connected": true, "serverId": "srv-2", "uptimeSecs": 5 }),
+      ),
+      (
+        100,
+        json!({ "connected": false, "lastError": "xray exited" }),
+      ),
+    ];
+    let events: Vec<ConnectionEvent> = script
+      .into_iter()
+      .filter_map(|(secs, value)| recorder.observe(&snapshot(value), at(secs)))
+      .collect();
+
+    assert_eq!(
+      events,
+      vec![
+        ConnectionEvent {
+          timestamp: 1_700_000_010,
+          server_id: Some("srv-1".to_string()),
+          action: SessionEnd::Disconnected,
+          duration_secs: 60,
+        },
+        ConnectionEvent {
+          timestamp: 1_700_000_075,
+          server_id: Some("srv-2".to_string()),
+          action: SessionEnd::ConnectionLost,
+          duration_secs: 25,
+        },
+      ]
+    );
+  }
+}
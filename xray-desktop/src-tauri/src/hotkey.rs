@@ -0,0 +1,320 @@
+use crate::config::Config;
+use crate::error::BridgeError;
+use crate::tray;
+use tauri::AppHandle;
+use tauri_plugin_global_shortcut::{GlobalShortcutExt, ShortcutState};
+
+pub const DEFAULT_HOTKEY: &str = "Ctrl+Alt+V";
+
+const MODIFIERS: [(&str, &[&str]); 4] = [
+  (
+    "Ctrl",
+    &["ctrl", "control", "cmdorctrl", "commandorcontrol"],
+  ),
+  ("Alt", &["alt", "option"]),
+  ("Shift", &["shift"]),
+  ("Super", &["super", "cmd", "command", "meta", "win"]),
+];
+
+const NAMED_KEYS: [&str; 15] = [
+  "Space",
+  "Tab",
+  "Enter",
+  "Escape",
+  "Backspace",
+  "Delete",
+  "Insert",
+  "Home",
+  "End",
+  "PageUp",
+  "PageDown",
+  "Up",
+  "Down",
+  "Left",
+  "Right",
+];
+
+fn key(raw: &str) -> Option<String> {
+  let mut chars = raw.chars();
+  if let (Some(c), None) = (chars.next(), chars.next()) {
+    return c
+      .is_ascii_alphanumeric()
+      .then(|| c.to_ascii_uppercase().to_string());
+  }
+  if let Some(n) = raw
+    .strip_prefix(['F', 'f'])
+    .and_then(|n| n.parse::<u8>().ok())
+  {
+    return (1..=24).contains(&n).then(|| format!("F{n}"));
+  }
+  NAMED_KEYS
+    .iter()
+    .find(|named| named.eq_ignore_ascii_case(raw))
+    .map(|named| named.to_string())
+}
+
+/// Canonical `Ctrl+Alt+Shift+Super+Key` form of `raw`. At least one modifier is required: a bare
+/// key would be swallowed from every app's typing.
+pub fn normalize(raw: &str) -> Result<String, BridgeError> {
+  let invalid = |reason: String| BridgeError::InvalidHotkey(reason);
+  let mut held = [false; MODIFIERS.len()];
+  let mut pressed = None;
+  for part in raw.split('+').map(str::trim) {
+    if part.is_empty() {
+      return Err(invalid(format!("\"{raw}\" has an empty key")));
+    }
+    let lower = part.to_ascii_lowercase();
+    if let Some(index) = MODIFIERS
+      .iter()
+      .position(|(_, aliases)| aliases.contains(&lower.as_str()))
+    {
+      if std::mem::replace(&mut held[index], true) {
+        return Err(invalid(format!("{} is given twice", MODIFIERS[index].0)));
+      }
+    } else if pressed.is_some() {
+      return Err(invalid(format!("\"{raw}\" has more than one key")));
+    } else {
+      pressed = Some(key(part).ok_or_else(|| invalid(format!("unknown key \"{part}\"")))?);
+    }
+  }
+  let pressed = pressed.ok_or_else(|| invalid(format!("\"{raw}\" has no key")))?;
+  if !held.contains(&true) {
+    return Err(invalid(format!("{pressed} needs at least one modifier")));
+  }
+  let mut parts: Vec<String> = MODIFIERS
+    .iter()
+    .zip(held)
+    .filter(|(_, held)| *held)
+    .map(|((name, _), _)| name.to_string())
+    .collect();
+  parts.push(pressed);
+  Ok(parts.join("+"))
+}
+
+/// The OS side of a system-wide shortcut; a press runs `toggle_connection`.
+pub trait ShortcutRegistrar: Send + Sync {
+  /// Fails with `HotkeyUnavailable`, e.g. when another app already holds `accelerator`.
+  fn register(&self, accelerator: &str) -> Result<(), BridgeError>;
+  fn unregister(&self, accelerator: &str);
+  /// False when this build can't register system-wide shortcuts at all.
+  fn is_supported(&self) -> bool {
+    true
+  }
+}
+
+/// Registers through `tauri-plugin-global-shortcut`; presses reach `plugin`'s handler. The
+/// `normalize` form is one the plugin parses as is.
+pub struct GlobalShortcuts(pub AppHandle);
+
+impl ShortcutRegistrar for GlobalShortcuts {
+  fn register(&self, accelerator: &str) -> Result<(), BridgeError> {
+    self
+      .0
+      .global_shortcut()
+      .register(accelerator)
+      .map_err(|error| BridgeError::HotkeyUnavailable {
+        hotkey: accelerator.to_string(),
+        reason: error.to_string(),
+      })
+  }
+
+  fn unregister(&self, accelerator: &str) {
+    if let Err(error) = self.0.global_shortcut().unregister(accelerator) {
+      tracing::debug!(hotkey = accelerator, "hotkey not unregistered: {error}");
+    }
+  }
+}
+
+/// Only our own binding is ever registered, so any press toggles the connection.
+pub fn plugin() -> tauri::plugin::TauriPlugin<tauri::Wry> {
+  tauri_plugin_global_shortcut::Builder::new()
+    .with_handler(|app, _shortcut, event| {
+      if event.state == ShortcutState::Pressed {
+        tray::toggle_in_background(app);
+      }
+    })
+    .build()
+}
+
+pub struct Hotkeys {
+  registrar: Box<dyn ShortcutRegistrar>,
+}
+
+impl Hotkeys {
+  pub fn new(registrar: Box<dyn ShortcutRegistrar>) -> Self {
+    Self { registrar }
+  }
+
+  pub fn is_supported(&self) -> bool {
+    self.registrar.is_supported()
+  }
+
+  /// Registers the saved binding at launch. A failure is only logged; the binding stays saved
+  /// for the next launch.
+  pub fn register_saved(&self, config: &Config) {
+    if let Some(hotkey) = config.get().hotkey {
+      if let Err(error) = self.registrar.register(&hotkey) {
+        tracing::warn!(
+          hotkey,
+          kind = error.kind(),
+          "hotkey not registered: {error}"
+        );
+      }
+    }
+  }
+
+  /// Switches to `hotkey` (`None` turns the shortcut off) and saves it. The new binding is
+  /// registered before the old one is let go, so a taken or invalid one changes nothing.
+  pub fn set(&self, config: &Config, hotkey: Option<&str>) -> Result<Option<String>, BridgeError> {
+    let hotkey = hotkey.map(normalize).transpose()?;
+    let current = config.get().hotkey;
+    if hotkey == current {
+      return Ok(hotkey);
+    }
+    if let Some(hotkey) = &hotkey {
+      self.registrar.register(hotkey)?;
+    }
+    if let Err(error) = config.update(|settings| settings.hotkey = hotkey.clone()) {
+      if let Some(hotkey) = &hotkey {
+        self.registrar.unregister(hotkey);
+      }
+      return Err(error);
+    }
+    if let Some(current) = &current {
+      self.registrar.unregister(current);
+    }
+    Ok(hotkey)
+  }
+}
+
+#[allow(non_snake_case)]
+#[tauri::command]
+pub fn getHotkey(config: tauri::State<'_, Config>) -> Option<String> {
+  config.get().hotkey
+}
+
+#[allow(non_snake_case)]
+#[tauri::command]
+pub fn setHotkey(
+  hotkeys: tauri::State<'_, Hotkeys>,
+  config: tauri::State<'_, Config>,
+  hotkey: Option<String>,
+) -> Result<Option<String>, BridgeError> {
+  hotkeys.set(&config, hotkey.as_deref())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::sync::{Arc, Mutex};
+
+  #[derive(Clone, Default)]
+  struct FakeRegistrar {
+    calls: Arc<Mutex<Vec<String>>>,
+    taken: Vec<&'static str>,
+  }
+
+  impl ShortcutRegistrar for FakeRegistrar {
+    fn register(&self, accelerator: &str) -> Result<(), BridgeError> {
+      if self.taken.contains(&accelerator) {
+        return Err(BridgeError::HotkeyUnavailable {
+          hotkey: accelerator.to_string(),
+          reason: "it is used by another app".to_string(),
+        });
+      }
+      self.calls.lock().unwrap().push(format!("+{accelerator}"));
+      Ok(())
+    }
+
+    fn unregister(&self, accelerator: &str) {
+      self.calls.lock().unwrap().push(format!("-{accelerator}"));
+    }
+  }
+
+  fn calls(registrar: &FakeRegistrar) -> Vec<String> {
+    registrar.calls.lock().unwrap().clone()
+  }
+
+  #[test]
+  fn normalizes_aliases_case_and_order() {
+    assert_eq!(normalize("ctrl+alt+v").unwrap(), "Ctrl+Alt+V");
+    assert_eq!(
+      normalize(" Shift + Control + f12 ").unwrap(),
+      "Ctrl+Shift+F12"
+    );
+    assert_eq!(normalize("Cmd+Option+space").unwrap(), "Alt+Super+Space");
+    assert_eq!(normalize("CmdOrCtrl+1").unwrap(), "Ctrl+1");
+  }
+
+  #[test]
+  fn rejects_bare_keys_unknown_keys_and_chords() {
+    let reason = |raw: &str| match normalize(raw) {
+      Err(BridgeError::InvalidHotkey(reason)) => reason,
+      other => panic!("{raw} gave {other:?}"),
+    };
+
+    assert_eq!(reason("V"), "V needs at least one modifier");
+    assert_eq!(reason("Ctrl+Alt"), "\"Ctrl+Alt\" has no key");
+    assert_eq!(reason("Ctrl+V+B"), "\"Ctrl+V+B\" has more than one key");
+    assert_eq!(reason("Ctrl+F25"), "unknown key \"F25\"");
+    assert_eq!(reason("Ctrl+Control+V"), "Ctrl is given twice");
+    assert_eq!(reason("Ctrl++V"), "\"Ctrl++V\" has an empty key");
+  }
+
+  #[test]
+  fn a_new_binding_is_registered_before_the_old_one_is_dropped() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = Config::load(dir.path().join("settings.json"));
+    let registrar = FakeRegistrar::default();
+    let hotkeys = Hotkeys::new(Box::new(registrar.clone()));
+    hotkeys.register_saved(&config);
+
+    assert_eq!(
+      hotkeys
+        .set(&config, Some("shift+ctrl+k"))
+        .unwrap()
+        .as_deref(),
+      Some("Ctrl+Shift+K")
+    );
+    // Same binding, nothing to do.
+    hotkeys.set(&config, Some("Ctrl+Shift+K")).unwrap();
+    hotkeys.set(&config, None).unwrap();
+
+    assert_eq!(
+      calls(&registrar),
+      [
+        "+Ctrl+Alt+V",
+        "+Ctrl+Shift+K",
+        "-Ctrl+Alt+V",
+        "-Ctrl+Shift+K"
+      ]
+    );
+    assert_eq!(config.get().hotkey, None);
+  }
+
+  #[test]
+  fn a_taken_or_invalid_binding_leaves_the_old_one_in_place() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = Config::load(dir.path().join("settings.json"));
+    let registrar = FakeRegistrar {
+      taken: vec!["Ctrl+Alt+T"],
+      ..FakeRegistrar::default()
+    };
+    let hotkeys = Hotkeys::new(Box::new(registrar.clone()));
+
+    assert_eq!(
+      hotkeys.set(&config, Some("Ctrl+Alt+T")),
+      Err(BridgeError::HotkeyUnavailable {
+        hotkey: "Ctrl+Alt+T".to_string(),
+        reason: "it is used by another app".to_string(),
+      })
+    );
+    assert!(matches!(
+      hotkeys.set(&config, Some("T")),
+      Err(BridgeError::InvalidHotkey(_))
+    ));
+
+    assert!(calls(&registrar).is_empty());
+    assert_eq!(config.get().hotkey.as_deref(), Some(DEFAULT_HOTKEY));
+  }
+}
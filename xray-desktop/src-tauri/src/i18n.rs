@@ -0,0 +1,192 @@
+use crate::config::Config;
+use crate::error::BridgeError;
+use tauri::{AppHandle, Manager};
+
+pub const DEFAULT_LOCALE: &str = "en";
+
+type Table = &'static [(&'static str, &'static str)];
+
+/// Every key the backend uses. Other tables may leave keys out; `text` falls back to these.
+const EN: Table = &[
+  ("notice.connected.title", "Connected"),
+  ("notice.connected.body", "The tunnel is up."),
+  ("notice.disconnected.title", "Disconnected"),
+  ("notice.disconnected.body", "The tunnel was stopped."),
+  ("notice.connectionLost.title", "Connection lost"),
+  (
+    "notice.connectionLost.body",
+    "The tunnel went down unexpectedly.",
+  ),
+  ("notice.paused.title", "Paused"),
+  (
+    "notice.paused.body",
+    "Traffic is not going through the tunnel.",
+  ),
+  ("notice.resumed.title", "Resumed"),
+  (
+    "notice.resumed.body",
+    "Traffic goes through the tunnel again.",
+  ),
+  ("tray.connected", "xray-desktop: Connected"),
+  ("tray.paused", "xray-desktop: Paused"),
+  ("tray.disconnected", "xray-desktop: Disconnected"),
+  ("tray.error", "xray-desktop: Error"),
+  ("menu.connect", "Connect"),
+  ("menu.disconnect", "Disconnect"),
+  ("menu.show", "Show Window"),
+  ("menu.quit", "Quit"),
+];
+
+const RU: Table = &[
+  ("notice.connected.title", "Подключено"),
+  ("notice.connected.body", "Туннель поднят."),
+  ("notice.disconnected.title", "Отключено"),
+  ("notice.disconnected.body", "Туннель остановлен."),
+  ("notice.connectionLost.title", "Соединение потеряно"),
+  ("notice.connectionLost.body", "Туннель неожиданно упал."),
+  ("notice.paused.title", "Пауза"),
+  ("notice.paused.body", "Трафик не идёт через туннель."),
+  ("notice.resumed.title", "Возобновлено"),
+  ("notice.resumed.body", "Трафик снова идёт через туннель."),
+  ("tray.connected", "xray-desktop: подключено"),
+  ("tray.paused", "xray-desktop: пауза"),
+  ("tray.disconnected", "xray-desktop: отключено"),
+  ("tray.error", "xray-desktop: ошибка"),
+  ("menu.connect", "Подключить"),
+  ("menu.disconnect", "Отключить"),
+  ("menu.show", "Показать окно"),
+  ("menu.quit", "Выход"),
+];
+
+/// The bundled table for `locale` (`ru`, `ru-RU`, `ru_RU`), by its language subtag.
+fn table_for(locale: &str) -> Option<Table> {
+  let language = locale.split(['-', '_']).next()?.to_ascii_lowercase();
+  match language.as_str() {
+    "en" => Some(EN),
+    "ru" => Some(RU),
+    _ => None,
+  }
+}
+
+/// The first table with `key`; the key itself when none has it, which is a bug but still
+/// readable.
+fn lookup(tables: &[Table], key: &'static str) -> &'static str {
+  tables
+    .iter()
+    .find_map(|table| table.iter().find(|(k, _)| *k == key))
+    .map_or(key, |(_, text)| text)
+}
+
+/// `key` in `locale`, or in English when the locale, or just this key, isn't translated.
+pub fn text(locale: &str, key: &'static str) -> &'static str {
+  match table_for(locale) {
+    Some(table) => lookup(&[table, EN], key),
+    None => lookup(&[EN], key),
+  }
+}
+
+/// `text` in the saved locale.
+pub fn text_for(app: &AppHandle, key: &'static str) -> &'static str {
+  match app.try_state::<Config>() {
+    Some(config) => text(&config.get().locale, key),
+    None => text(DEFAULT_LOCALE, key),
+  }
+}
+
+/// Canonical form of a BCP 47 style tag: `ru-RU`, `pt_br` and ` EN ` become `ru-ru`, `pt-br`
+/// and `en`. A tag without a bundled table is fine; it reads as English.
+pub fn normalize_locale(raw: &str) -> Result<String, BridgeError> {
+  let tag = raw.trim().replace('_', "-").to_ascii_lowercase();
+  let mut subtags = tag.split('-');
+  let language_ok = subtags.next().is_some_and(|language| {
+    (2..=3).contains(&language.len()) && language.chars().all(|c| c.is_ascii_alphabetic())
+  });
+  let rest_ok = subtags.all(|subtag| {
+    (1..=8).contains(&subtag.len()) && subtag.chars().all(|c| c.is_ascii_alphanumeric())
+  });
+  if language_ok && rest_ok {
+    Ok(tag)
+  } else {
+    Err(BridgeError::InvalidLocale(raw.trim().to_string()))
+  }
+}
+
+#[allow(non_snake_case)]
+#[tauri::command]
+pub fn getLocale(config: tauri::State<'_, Config>) -> String {
+  config.get().locale
+}
+
+/// Notifications and the tray tooltip use it from the next one on; tray menu labels are set at
+/// launch.
+#[allow(non_snake_case)]
+#[tauri::command]
+pub fn setLocale(config: tauri::State<'_, Config>, locale: String) -> Result<String, BridgeError> {
+  let locale = normalize_locale(&locale)?;
+  config.update(|settings| settings.locale = locale.clone())?;
+  Ok(locale)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn looks_keys_up_by_language() {
+    assert_eq!(text("en", "tray.paused"), "xray-desktop: Paused");
+    assert_eq!(text("ru", "notice.connected.title"), "Подключено");
+    assert_eq!(text("ru-RU", "menu.quit"), "Выход");
+    assert_eq!(text("RU_ru", "menu.quit"), "Выход");
+  }
+
+  #[test]
+  fn missing_keys_fall_back_to_english() {
+    let partial: Table = &[("tray.paused", "xray-desktop: pausa")];
+
+    assert_eq!(lookup(&[partial, EN], "tray.paused"), "xray-desktop: pausa");
+    assert_eq!(
+      lookup(&[partial, EN], "tray.connected"),
+      "xray-desktop: Connected"
+    );
+    assert_eq!(text("ru", "no.such.key"), "no.such.key");
+  }
+
+  #[test]
+  fn unknown_locale_reads_as_english() {
+    assert_eq!(text("de-DE", "menu.connect"), "Connect");
+    assert_eq!(text("", "menu.connect"), "Connect");
+    assert_eq!(normalize_locale("de_DE").unwrap(), "de-de");
+  }
+
+  #[test]
+  fn bundled_tables_cover_every_english_key() {
+    for (key, _) in EN {
+      assert!(RU.iter().any(|(k, _)| k == key), "ru lacks {key}");
+    }
+  }
+
+  #[test]
+  fn rejects_malformed_tags() {
+    for raw in ["", "e", "english", "en--us", "ru/RU"] {
+      assert_eq!(
+        normalize_locale(raw).unwrap_err().kind(),
+        "invalidLocale",
+        "{raw}"
+      );
+    }
+  }
+
+  #[test]
+  fn set_locale_is_saved() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = Config::load(dir.path().join("settings.json"));
+    assert_eq!(config.get().locale, DEFAULT_LOCALE);
+
+    config
+      .update(|settings| settings.locale = normalize_locale("ru_RU").unwrap())
+      .unwrap();
+
+    let locale = Config::load(dir.path().join("settings.json")).get().locale;
+    assert_eq!(text(&locale, "tray.error"), "xray-desktop: ошибка");
+  }
+}
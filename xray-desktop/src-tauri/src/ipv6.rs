@@ -0,0 +1,126 @@
+use crate::bridge::Bridge;
+use crate::config::Config;
+use crate::error::BridgeError;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tauri::{AppHandle, Emitter};
+
+pub const IPV6_WARNING_EVENT: &str = "vpn://ipv6-warning";
+
+/// The bridge `ipv6` answer. `fully_blocked` only matters when disabling: `false` means some
+/// IPv6 traffic may still bypass the tunnel.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+struct Ipv6Reply {
+  fully_blocked: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Ipv6Warning {
+  pub message: String,
+}
+
+/// Forwards the toggle and saves it once the bridge accepted it; `connect` re-applies it.
+/// Returns a warning when IPv6 was disabled but couldn't be blocked completely.
+pub async fn set(
+  bridge: &Bridge,
+  config: &Config,
+  enabled: bool,
+) -> Result<Option<Ipv6Warning>, BridgeError> {
+  let reply = bridge.call("ipv6", json!({ "enabled": enabled })).await?;
+  config.update(|settings| settings.ipv6_enabled = enabled)?;
+  // Older agents answer `null`; without a confirmation the block is assumed to have held.
+  let reply = serde_json::from_value::<Option<Ipv6Reply>>(reply)
+    .map_err(|error| BridgeError::InvalidJson(error.to_string()))?;
+  Ok(warning(enabled, reply))
+}
+
+fn warning(enabled: bool, reply: Option<Ipv6Reply>) -> Option<Ipv6Warning> {
+  let reply = reply?;
+  (!enabled && !reply.fully_blocked).then(|| Ipv6Warning {
+    message: "IPv6 could not be fully blocked; some traffic may bypass the tunnel".to_string(),
+  })
+}
+
+#[allow(non_snake_case)]
+#[tauri::command]
+pub fn getIpv6Enabled(config: tauri::State<'_, Config>) -> bool {
+  config.get().ipv6_enabled
+}
+
+#[allow(non_snake_case)]
+#[tauri::command]
+pub async fn setIpv6Enabled(
+  app: AppHandle,
+  bridge: tauri::State<'_, Bridge>,
+  config: tauri::State<'_, Config>,
+  enabled: bool,
+) -> Result<(), BridgeError> {
+  if let Some(warning) = set(&bridge, &config, enabled).await? {
+    let _ = app.emit(IPV6_WARNING_EVENT, warning);
+  }
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::bridge::mock::MockBridgeRunner;
+  use serde_json::Value;
+  use tauri::async_runtime::block_on;
+
+  #[test]
+  fn toggle_is_forwarded_and_saved() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("settings.json");
+    let mock = MockBridgeRunner::default().respond("ipv6", Ok(json!({ "fullyBlocked": true })));
+    let bridge = Bridge::new(mock.clone());
+    assert!(Config::load(&path).get().ipv6_enabled);
+
+    assert_eq!(
+      block_on(set(&bridge, &Config::load(&path), false)),
+      Ok(None)
+    );
+    assert!(!Config::load(&path).get().ipv6_enabled);
+
+    block_on(set(&bridge, &Config::load(&path), true)).unwrap();
+    assert!(Config::load(&path).get().ipv6_enabled);
+
+    assert_eq!(
+      mock.calls(),
+      [
+        ("ipv6".to_string(), json!({ "enabled": false })),
+        ("ipv6".to_string(), json!({ "enabled": true })),
+      ]
+    );
+  }
+
+  #[test]
+  fn rejected_toggle_is_not_saved() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = Config::load(dir.path().join("settings.json"));
+    let unsupported = BridgeError::BridgeReported {
+      code: Some("IPV6_UNSUPPORTED".to_string()),
+      message: "IPv6 control is not supported by this xray-client-agent version".to_string(),
+    };
+    let bridge = Bridge::new(MockBridgeRunner::default().respond("ipv6", Err(unsupported.clone())));
+
+    assert_eq!(block_on(set(&bridge, &config, false)), Err(unsupported));
+    assert!(config.get().ipv6_enabled);
+  }
+
+  #[test]
+  fn warns_only_when_disabling_did_not_fully_block() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = Config::load(dir.path().join("settings.json"));
+    let leaky = Bridge::new(
+      MockBridgeRunner::default().respond("ipv6", Ok(json!({ "fullyBlocked": false }))),
+    );
+    let silent = Bridge::new(MockBridgeRunner::default().respond("ipv6", Ok(Value::Null)));
+
+    assert!(block_on(set(&leaky, &config, false)).unwrap().is_some());
+    assert_eq!(block_on(set(&leaky, &config, true)), Ok(None));
+    assert_eq!(block_on(set(&silent, &config, false)), Ok(None));
+  }
+}
@@ -0,0 +1,76 @@
+use crate::bridge::Bridge;
+use crate::config::Config;
+use crate::error::BridgeError;
+use serde_json::json;
+
+/// Forwards the toggle to the bridge and saves it once the bridge accepted it. The bridge only
+/// blocks traffic while a tunnel is up (or has dropped); `connect` arms it and a clean
+/// `disconnect` lifts the block.
+pub async fn set(bridge: &Bridge, config: &Config, enabled: bool) -> Result<(), BridgeError> {
+  bridge
+    .call("killSwitch", json!({ "enabled": enabled }))
+    .await?;
+  config.update(|settings| settings.kill_switch = enabled)?;
+  Ok(())
+}
+
+#[allow(non_snake_case)]
+#[tauri::command]
+pub fn getKillSwitch(config: tauri::State<'_, Config>) -> bool {
+  config.get().kill_switch
+}
+
+#[allow(non_snake_case)]
+#[tauri::command]
+pub async fn setKillSwitch(
+  bridge: tauri::State<'_, Bridge>,
+  config: tauri::State<'_, Config>,
+  enabled: bool,
+) -> Result<(), BridgeError> {
+  set(&bridge, &config, enabled).await
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::bridge::mock::MockBridgeRunner;
+  use serde_json::Value;
+  use tauri::async_runtime::block_on;
+
+  #[test]
+  fn toggle_is_forwarded_and_saved() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("settings.json");
+    let mock = MockBridgeRunner::default().respond("killSwitch", Ok(Value::Null));
+    let bridge = Bridge::new(mock.clone());
+
+    block_on(set(&bridge, &Config::load(&path), true)).unwrap();
+    assert!(Config::load(&path).get().kill_switch);
+
+    block_on(set(&bridge, &Config::load(&path), false)).unwrap();
+    assert!(!Config::load(&path).get().kill_switch);
+
+    assert_eq!(
+      mock.calls(),
+      [
+        ("killSwitch".to_string(), json!({ "enabled": true })),
+        ("killSwitch".to_string(), json!({ "enabled": false })),
+      ]
+    );
+  }
+
+  #[test]
+  fn rejected_toggle_is_not_saved() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = Config::load(dir.path().join("settings.json"));
+    let unsupported = BridgeError::BridgeReported {
+      code: Some("KILL_SWITCH_UNSUPPORTED".to_string()),
+      message: "Kill switch is not supported by this xray-client-agent version".to_string(),
+    };
+    let bridge =
+      Bridge::new(MockBridgeRunner::default().respond("killSwitch", Err(unsupported.clone())));
+
+    assert_eq!(block_on(set(&bridge, &config, true)), Err(unsupported));
+    assert!(!config.get().kill_switch);
+  }
+}
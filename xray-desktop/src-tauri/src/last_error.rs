@@ -0,0 +1,235 @@
+use crate::config::{Config, Settings};
+use crate::diagnostics;
+use crate::error::BridgeError;
+use crate::persist;
+use crate::secrets::Secrets;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tauri::{AppHandle, Emitter, Manager};
+
+const LAST_ERROR_FILE: &str = "last-error.json";
+/// Carries the new `LastError`, or `null` once it is cleared.
+pub const LAST_ERROR_EVENT: &str = "vpn://last-error";
+
+/// The most recent failed bridge action, kept so an error hit while the window was hidden (or
+/// before the app crashed) can still be read later.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LastError {
+  pub action: String,
+  /// `BridgeError::kind`.
+  pub kind: String,
+  /// With every known secret scrubbed.
+  pub message: String,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub code: Option<String>,
+  /// Unix seconds.
+  pub timestamp: u64,
+}
+
+impl LastError {
+  pub fn new(action: &str, error: &BridgeError, secrets: &[String], now: SystemTime) -> Self {
+    let code = match error {
+      BridgeError::BridgeReported { code, .. } => code.clone(),
+      _ => None,
+    };
+    Self {
+      action: action.to_string(),
+      kind: error.kind().to_string(),
+      message: diagnostics::scrub(&error.to_string(), secrets),
+      code,
+      timestamp: now.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs(),
+    }
+  }
+}
+
+/// Strings that must never reach the file: stored tokens, `bridgeEnv` values and proxy URLs,
+/// which may embed credentials.
+pub fn known_secrets(secrets: &Secrets, settings: &Settings) -> Vec<String> {
+  let mut known = diagnostics::stored_tokens(secrets, settings);
+  known.extend(settings.bridge_env.env.values().cloned());
+  known.extend(
+    settings
+      .profiles
+      .iter()
+      .flat_map(|profile| [&profile.proxy.http_proxy, &profile.proxy.https_proxy])
+      .flatten()
+      .cloned(),
+  );
+  known
+}
+
+/// `last-error.json` in the app data dir, mirrored in memory.
+pub struct LastErrorStore {
+  path: PathBuf,
+  current: Mutex<Option<LastError>>,
+}
+
+fn io_error(error: impl std::fmt::Display) -> BridgeError {
+  BridgeError::LastErrorIo(error.to_string())
+}
+
+impl LastErrorStore {
+  pub fn for_app(app: &AppHandle) -> tauri::Result<Self> {
+    Ok(Self::in_dir(&app.path().app_data_dir()?))
+  }
+
+  /// A missing or unreadable file just means there is no error to show.
+  pub fn in_dir(data_dir: &Path) -> Self {
+    let path = data_dir.join(LAST_ERROR_FILE);
+    let current = persist::load(&path, |raw| serde_json::from_str(raw).ok());
+    Self {
+      path,
+      current: Mutex::new(current),
+    }
+  }
+
+  pub fn get(&self) -> Option<LastError> {
+    self.current.lock().unwrap().clone()
+  }
+
+  pub fn record(&self, error: LastError) -> Result<(), BridgeError> {
+    let mut current = self.current.lock().unwrap();
+    let json = serde_json::to_string_pretty(&error).map_err(io_error)?;
+    persist::atomic_write(&self.path, json).map_err(io_error)?;
+    *current = Some(error);
+    Ok(())
+  }
+
+  /// Returns whether there was a file to delete.
+  pub fn clear(&self) -> Result<bool, BridgeError> {
+    let mut current = self.current.lock().unwrap();
+    let removed = persist::remove(&self.path).map_err(io_error)?;
+    *current = None;
+    Ok(removed)
+  }
+}
+
+/// Saves a failure `Bridge::set_error_sink` reported and announces it as `vpn://last-error`,
+/// which the tray listens to.
+pub fn record(app: &AppHandle, action: &str, error: &BridgeError) {
+  let (Some(store), Some(secrets), Some(config)) = (
+    app.try_state::<LastErrorStore>(),
+    app.try_state::<Secrets>(),
+    app.try_state::<Config>(),
+  ) else {
+    return;
+  };
+  let secrets = known_secrets(&secrets, &config.get());
+  let last = LastError::new(action, error, &secrets, SystemTime::now());
+  if let Err(error) = store.record(last.clone()) {
+    tracing::warn!("failed to save the last error: {error}");
+  }
+  let _ = app.emit(LAST_ERROR_EVENT, Some(last));
+}
+
+#[allow(non_snake_case)]
+#[tauri::command]
+pub fn getLastError(store: tauri::State<'_, LastErrorStore>) -> Option<LastError> {
+  store.get()
+}
+
+#[allow(non_snake_case)]
+#[tauri::command]
+pub fn clearLastError(
+  app: AppHandle,
+  store: tauri::State<'_, LastErrorStore>,
+) -> Result<(), BridgeError> {
+  store.clear()?;
+  let _ = app.emit(LAST_ERROR_EVENT, None::<LastError>);
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::secrets::mock::MemoryStore;
+  use crate::secrets::StoredToken;
+  use std::fs;
+  use std::time::Duration;
+
+  fn at(secs: u64) -> SystemTime {
+    UNIX_EPOCH + Duration::from_secs(secs)
+  }
+
+  #[test]
+  fn persists_reads_back_and_clears() {
+    let dir = tempfile::tempdir().unwrap();
+    let store = LastErrorStore::in_dir(dir.path());
+    assert_eq!(store.get(), None);
+
+    let error = BridgeError::BridgeReported {
+      code: Some("STARTUP_FAILED".to_string()),
+      message: "STARTUP_FAILED: Supervisor exited during startup".to_string(),
+    };
+    let last = LastError::new("connect", &error, &[], at(1_700_000_000));
+    store.record(last.clone()).unwrap();
+
+    assert_eq!(
+      LastErrorStore::in_dir(dir.path()).get(),
+      Some(LastError {
+        action: "connect".to_string(),
+        kind: "bridgeReported".to_string(),
+        message: "STARTUP_FAILED: Supervisor exited during startup".to_string(),
+        code: Some("STARTUP_FAILED".to_string()),
+        timestamp: 1_700_000_000,
+      })
+    );
+
+    store.clear().unwrap();
+    assert_eq!(store.get(), None);
+    assert_eq!(LastErrorStore::in_dir(dir.path()).get(), None);
+    assert!(!dir.path().join(LAST_ERROR_FILE).exists());
+    // Clearing twice is fine.
+    store.clear().unwrap();
+  }
+
+  #[test]
+  fn a_corrupt_file_reads_as_no_error() {
+    let dir = tempfile::tempdir().unwrap();
+    fs::write(dir.path().join(LAST_ERROR_FILE), "{ not json").unwrap();
+
+    assert_eq!(LastErrorStore::in_dir(dir.path()).get(), None);
+  }
+
+  #[test]
+  fn secrets_never_reach_the_stored_error() {
+    let dir = tempfile::tempdir().unwrap();
+    let secrets = Secrets::new(Box::new(MemoryStore::default()));
+    secrets
+      .store_token(&StoredToken {
+        base_url: "https://cp.example.com".to_string(),
+        token: "abcDEF123_token_value".to_string(),
+        expires_at: None,
+      })
+      .unwrap();
+    let mut settings = Settings::default();
+    settings
+      .bridge_env
+      .env
+      .insert("XRAY_API_KEY".to_string(), "sk-live-42".to_string());
+    let error = BridgeError::BridgeReported {
+      code: None,
+      message: "import of abcDEF123_token_value failed, key sk-live-42 rejected".to_string(),
+    };
+
+    let store = LastErrorStore::in_dir(dir.path());
+    let last = LastError::new(
+      "importToken",
+      &error,
+      &known_secrets(&secrets, &settings),
+      at(0),
+    );
+    store.record(last).unwrap();
+
+    let on_disk = fs::read_to_string(dir.path().join(LAST_ERROR_FILE)).unwrap();
+    assert!(!on_disk.contains("abcDEF123_token_value"));
+    assert!(!on_disk.contains("sk-live-42"));
+    assert_eq!(
+      store.get().unwrap().message,
+      "import of <redacted> failed, key <redacted> rejected"
+    );
+  }
+}
@@ -0,0 +1,307 @@
+use crate::bridge::Bridge;
+use crate::bridge_pool::BridgePool;
+use crate::error::BridgeError;
+use crate::external_import;
+use crate::servers::{self, ServerCatalog};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// How long a `pingAll` result is reused by `connectFastest`.
+const CACHE_TTL: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LatencyResult {
+  pub server_id: String,
+  pub rtt_ms: Option<u32>,
+  pub reachable: bool,
+}
+
+impl LatencyResult {
+  fn unreachable(server_id: String) -> Self {
+    Self {
+      server_id,
+      rtt_ms: None,
+      reachable: false,
+    }
+  }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BridgePing {
+  #[serde(default)]
+  rtt_ms: Option<u32>,
+}
+
+/// Measures the round trip to one server's endpoint. A timeout is an answer (unreachable), not
+/// an error.
+pub async fn ping(bridge: &Bridge, server_id: String) -> Result<LatencyResult, BridgeError> {
+  let value = match bridge.call("ping", json!({ "serverId": server_id })).await {
+    Ok(value) => value,
+    Err(BridgeError::Timeout(_)) => return Ok(LatencyResult::unreachable(server_id)),
+    Err(error) => return Err(error),
+  };
+  let ping: BridgePing =
+    serde_json::from_value(value).map_err(|error| BridgeError::InvalidJson(error.to_string()))?;
+
+  Ok(LatencyResult {
+    server_id,
+    reachable: ping.rtt_ms.is_some(),
+    rtt_ms: ping.rtt_ms,
+  })
+}
+
+/// Pings every listed server through `pool`. A server that fails to answer is reported
+/// unreachable instead of failing the batch. Fastest first, unreachable last. Imported servers
+/// aren't pinged: the agent can only reach its own.
+pub async fn ping_all(
+  bridge: &Bridge,
+  catalog: &ServerCatalog,
+  pool: &BridgePool,
+) -> Result<Vec<LatencyResult>, BridgeError> {
+  let listed = servers::list(bridge, catalog).await?;
+
+  let tasks: Vec<_> = listed
+    .into_iter()
+    .filter(|server| !external_import::is_imported(&server.id))
+    .map(|server| {
+      let bridge = bridge.clone();
+      pool.spawn(async move {
+        ping(&bridge, server.id.clone())
+          .await
+          .unwrap_or_else(|_| LatencyResult::unreachable(server.id))
+      })
+    })
+    .collect();
+
+  let mut results = Vec::with_capacity(tasks.len());
+  for task in tasks {
+    if let Ok(result) = task.await {
+      results.push(result);
+    }
+  }
+  results.sort_by_key(|result| result.rtt_ms.unwrap_or(u32::MAX));
+  Ok(results)
+}
+
+/// The last `pingAll` result, so repeated `connectFastest` clicks don't re-ping everything.
+#[derive(Default)]
+pub struct LatencyCache {
+  last: Mutex<Option<(Instant, Vec<LatencyResult>)>>,
+}
+
+impl LatencyCache {
+  pub fn store(&self, results: Vec<LatencyResult>, now: Instant) {
+    *self.last.lock().unwrap() = Some((now, results));
+  }
+
+  pub fn clear(&self) {
+    self.last.lock().unwrap().take();
+  }
+
+  pub fn fresh(&self, now: Instant) -> Option<Vec<LatencyResult>> {
+    match &*self.last.lock().unwrap() {
+      Some((measured, results)) if now.duration_since(*measured) < CACHE_TTL => {
+        Some(results.clone())
+      }
+      _ => None,
+    }
+  }
+}
+
+/// Cached results when still fresh, otherwise a new `ping_all`.
+pub async fn measure(
+  bridge: &Bridge,
+  catalog: &ServerCatalog,
+  pool: &BridgePool,
+  cache: &LatencyCache,
+) -> Result<Vec<LatencyResult>, BridgeError> {
+  if let Some(results) = cache.fresh(Instant::now()) {
+    return Ok(results);
+  }
+  let results = ping_all(bridge, catalog, pool).await?;
+  cache.store(results.clone(), Instant::now());
+  Ok(results)
+}
+
+#[allow(non_snake_case)]
+#[tauri::command]
+pub async fn pingServer(
+  bridge: tauri::State<'_, Bridge>,
+  serverId: String,
+) -> Result<LatencyResult, BridgeError> {
+  ping(&bridge, serverId).await
+}
+
+#[allow(non_snake_case)]
+#[tauri::command]
+pub async fn pingAll(
+  bridge: tauri::State<'_, Bridge>,
+  catalog: tauri::State<'_, ServerCatalog>,
+  pool: tauri::State<'_, BridgePool>,
+  cache: tauri::State<'_, LatencyCache>,
+) -> Result<Vec<LatencyResult>, BridgeError> {
+  let results = ping_all(&bridge, &catalog, &pool).await?;
+  cache.store(results.clone(), Instant::now());
+  Ok(results)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::bridge::BridgeRunner;
+  use serde_json::Value;
+  use std::collections::HashMap;
+  use std::sync::atomic::{AtomicUsize, Ordering};
+  use std::sync::Arc;
+  use tauri::async_runtime::block_on;
+
+  /// Answers `listServers` with `servers` and each `ping` after a short delay, tracking how
+  /// many pings overlap. Clones share the counters.
+  #[derive(Clone, Default)]
+  struct FakePinger {
+    servers: Vec<String>,
+    answers: HashMap<String, Result<Value, BridgeError>>,
+    in_flight: Arc<AtomicUsize>,
+    max_in_flight: Arc<AtomicUsize>,
+  }
+
+  impl BridgeRunner for FakePinger {
+    fn run(&self, action: &str, payload: Value) -> Result<Value, BridgeError> {
+      if action == "listServers" {
+        let listed: Vec<Value> = self
+          .servers
+          .iter()
+          .map(|id| json!({ "id": id, "name": id }))
+          .collect();
+        return Ok(Value::Array(listed));
+      }
+
+      let now = self.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+      self.max_in_flight.fetch_max(now, Ordering::SeqCst);
+      std::thread::sleep(Duration::from_millis(20));
+      self.in_flight.fetch_sub(1, Ordering::SeqCst);
+
+      let id = payload["serverId"].as_str().unwrap_or_default();
+      self
+        .answers
+        .get(id)
+        .cloned()
+        .unwrap_or(Ok(json!({ "rttMs": 50 })))
+    }
+  }
+
+  fn result(server_id: &str, rtt_ms: Option<u32>) -> LatencyResult {
+    LatencyResult {
+      server_id: server_id.to_string(),
+      rtt_ms,
+      reachable: rtt_ms.is_some(),
+    }
+  }
+
+  #[test]
+  fn ping_all_runs_at_most_the_pool_limit_at_once() {
+    let fake = FakePinger {
+      servers: (0..24).map(|n| format!("srv-{n:02}")).collect(),
+      ..Default::default()
+    };
+    let bridge = Bridge::new(fake.clone());
+
+    let results = block_on(ping_all(
+      &bridge,
+      &ServerCatalog::default(),
+      &BridgePool::new(8),
+    ))
+    .unwrap();
+
+    assert_eq!(results.len(), 24);
+    let max = fake.max_in_flight.load(Ordering::SeqCst);
+    assert!(max <= 8, "{max} pings overlapped");
+    assert!(max > 1, "pings ran one by one");
+  }
+
+  #[test]
+  fn timeouts_and_failures_mark_servers_unreachable() {
+    let answers = HashMap::from([
+      (
+        "slow".to_string(),
+        Err(BridgeError::Timeout(Duration::from_secs(5))),
+      ),
+      (
+        "gone".to_string(),
+        Err(BridgeError::BridgeReported {
+          code: None,
+          message: "Server gone is not imported".to_string(),
+        }),
+      ),
+      ("near".to_string(), Ok(json!({ "rttMs": 12 }))),
+      ("far".to_string(), Ok(json!({ "rttMs": 180 }))),
+      ("dark".to_string(), Ok(json!({ "rttMs": null }))),
+    ]);
+    let fake = FakePinger {
+      servers: vec!["far", "slow", "near", "gone", "dark"]
+        .into_iter()
+        .map(String::from)
+        .collect(),
+      answers,
+      ..Default::default()
+    };
+    let bridge = Bridge::new(fake);
+
+    let results = block_on(ping_all(
+      &bridge,
+      &ServerCatalog::default(),
+      &BridgePool::default(),
+    ))
+    .unwrap();
+
+    assert_eq!(
+      &results[..2],
+      &[result("near", Some(12)), result("far", Some(180))]
+    );
+    let mut unreachable: Vec<&str> = results[2..]
+      .iter()
+      .inspect(|result| assert!(!result.reachable))
+      .map(|result| result.server_id.as_str())
+      .collect();
+    unreachable.sort_unstable();
+    assert_eq!(unreachable, vec!["dark", "gone", "slow"]);
+  }
+
+  #[test]
+  fn single_ping_treats_a_timeout_as_unreachable() {
+    let fake = FakePinger {
+      answers: HashMap::from([(
+        "slow".to_string(),
+        Err(BridgeError::Timeout(Duration::from_secs(5))),
+      )]),
+      ..Default::default()
+    };
+    let bridge = Bridge::new(fake);
+
+    assert_eq!(
+      block_on(ping(&bridge, "slow".to_string())).unwrap(),
+      result("slow", None)
+    );
+    assert_eq!(
+      block_on(ping(&bridge, "fast".to_string())).unwrap(),
+      result("fast", Some(50))
+    );
+  }
+
+  #[test]
+  fn cached_results_expire_after_the_ttl() {
+    let cache = LatencyCache::default();
+    let measured = Instant::now();
+    cache.store(vec![result("near", Some(12))], measured);
+
+    assert_eq!(
+      cache.fresh(measured + Duration::from_secs(5)),
+      Some(vec![result("near", Some(12))])
+    );
+    assert_eq!(cache.fresh(measured + CACHE_TTL), None);
+  }
+}
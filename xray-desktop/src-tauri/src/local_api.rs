@@ -0,0 +1,293 @@
+use crate::bridge::Bridge;
+use crate::commands;
+use crate::config::Config;
+use crate::error::BridgeError;
+use crate::persist;
+use crate::stats;
+use serde::Serialize;
+use serde_json::Value;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{Ipv4Addr, TcpListener, TcpStream};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+use tauri::async_runtime::block_on;
+use tauri::{AppHandle, Manager};
+
+const READ_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// `LocalApiInfo` as JSON in the app config dir, rewritten at every start.
+const INFO_FILE: &str = "local-api.json";
+
+/// Where status bars (polybar, Rainmeter) find the API. `token` goes in
+/// `Authorization: Bearer <token>`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LocalApiInfo {
+  pub port: u16,
+  pub token: String,
+}
+
+/// Answers a request path with JSON; `None` is a 404.
+type Routes = dyn Fn(&str) -> Option<Result<Value, BridgeError>> + Send + Sync;
+
+/// `GET /status` and `GET /stats`, straight from the bridge.
+fn bridge_routes(bridge: Bridge) -> Arc<Routes> {
+  Arc::new(move |path| {
+    let reply = match path {
+      "/status" => block_on(commands::status(&bridge)).map(serde_json::to_value),
+      "/stats" => block_on(stats::fetch(&bridge)).map(serde_json::to_value),
+      _ => return None,
+    };
+    Some(reply.map(|value| value.unwrap_or(Value::Null)))
+  })
+}
+
+fn new_token() -> io::Result<String> {
+  let mut bytes = [0u8; 32];
+  getrandom::getrandom(&mut bytes).map_err(io::Error::other)?;
+  Ok(bytes.iter().map(|byte| format!("{byte:02x}")).collect())
+}
+
+/// Serves `routes` on `listener` until the app exits, each connection on its own thread so a
+/// client that stops sending can't hold up the others.
+fn serve(listener: TcpListener, token: String, routes: Arc<Routes>) {
+  let token: Arc<str> = token.into();
+  thread::spawn(move || {
+    for stream in listener.incoming().flatten() {
+      // Bound to 127.0.0.1, so this only guards against a misconfigured listener.
+      if !stream.peer_addr().is_ok_and(|peer| peer.ip().is_loopback()) {
+        continue;
+      }
+      let (token, routes) = (token.clone(), routes.clone());
+      thread::spawn(move || {
+        let _ = handle(stream, &token, &*routes);
+      });
+    }
+  });
+}
+
+fn handle(mut stream: TcpStream, token: &str, routes: &Routes) -> io::Result<()> {
+  stream.set_read_timeout(Some(READ_TIMEOUT))?;
+  let mut reader = BufReader::new(stream.try_clone()?);
+  let mut request_line = String::new();
+  reader.read_line(&mut request_line)?;
+  let mut authorized = false;
+  loop {
+    let mut header = String::new();
+    if reader.read_line(&mut header)? == 0 || header.trim().is_empty() {
+      break;
+    }
+    if let Some((name, value)) = header.split_once(':') {
+      authorized |= name.trim().eq_ignore_ascii_case("authorization")
+        && value.trim().strip_prefix("Bearer ") == Some(token);
+    }
+  }
+
+  let mut parts = request_line.split_whitespace();
+  let (method, path) = (parts.next().unwrap_or(""), parts.next().unwrap_or(""));
+  let (status, body) = if !authorized {
+    ("401 Unauthorized", error_body("unauthorized"))
+  } else if method != "GET" {
+    ("405 Method Not Allowed", error_body("method not allowed"))
+  } else {
+    match routes(path) {
+      Some(Ok(value)) => ("200 OK", value.to_string()),
+      Some(Err(error)) => (
+        "502 Bad Gateway",
+        serde_json::to_string(&error).unwrap_or_default(),
+      ),
+      None => ("404 Not Found", error_body("not found")),
+    }
+  };
+  write!(
+    stream,
+    "HTTP/1.1 {status}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+    body.len()
+  )?;
+  stream.flush()
+}
+
+fn error_body(message: &str) -> String {
+  serde_json::json!({ "message": message }).to_string()
+}
+
+/// Binds an ephemeral loopback port and starts serving with a fresh token.
+fn start_with(routes: Arc<Routes>) -> io::Result<LocalApiInfo> {
+  let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0))?;
+  let info = LocalApiInfo {
+    port: listener.local_addr()?.port(),
+    token: new_token()?,
+  };
+  serve(listener, info.token.clone(), routes);
+  Ok(info)
+}
+
+/// Writes `info` to `local-api.json` in `dir`, owner-only since it holds the token, or removes a
+/// stale one when the API isn't running.
+fn publish(dir: &Path, info: Option<&LocalApiInfo>) -> io::Result<()> {
+  let path = dir.join(INFO_FILE);
+  match info {
+    Some(info) => persist::atomic_write_private(&path, serde_json::to_vec_pretty(info)?),
+    None => match std::fs::remove_file(&path) {
+      Err(error) if error.kind() != io::ErrorKind::NotFound => Err(error),
+      _ => Ok(()),
+    },
+  }
+}
+
+/// The running API, if `localApi` was on at startup.
+#[derive(Default)]
+pub struct LocalApi {
+  info: Mutex<Option<LocalApiInfo>>,
+}
+
+impl LocalApi {
+  pub fn start(&self, app: &AppHandle) {
+    if app.state::<Config>().get().local_api {
+      let bridge = app.state::<Bridge>().inner().clone();
+      match start_with(bridge_routes(bridge)) {
+        Ok(info) => *self.info.lock().unwrap() = Some(info),
+        Err(error) => tracing::warn!("local API failed to start: {error}"),
+      }
+    }
+    let published = app
+      .path()
+      .app_config_dir()
+      .map_err(io::Error::other)
+      .and_then(|dir| publish(&dir, self.info.lock().unwrap().as_ref()));
+    if let Err(error) = published {
+      tracing::warn!("couldn't write {INFO_FILE}: {error}");
+    }
+  }
+}
+
+/// `None` unless `localApi` was on at startup.
+#[allow(non_snake_case)]
+#[tauri::command]
+pub fn getLocalApiInfo(api: tauri::State<'_, LocalApi>) -> Option<LocalApiInfo> {
+  api.info.lock().unwrap().clone()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::bridge::mock::MockBridgeRunner;
+  use serde_json::json;
+  use std::io::Read;
+
+  fn api() -> LocalApiInfo {
+    let bridge = Bridge::new(
+      MockBridgeRunner::default()
+        .respond(
+          "status",
+          Ok(json!({ "connected": true, "serverId": "srv-1" })),
+        )
+        .respond(
+          "stats",
+          Ok(json!({ "uplinkBytes": 10, "downlinkBytes": 20, "uptimeSecs": 3 })),
+        ),
+    );
+    start_with(bridge_routes(bridge)).unwrap()
+  }
+
+  /// Status code and body of a raw request.
+  fn get(info: &LocalApiInfo, path: &str, token: Option<&str>) -> (u16, Value) {
+    let mut stream = TcpStream::connect((Ipv4Addr::LOCALHOST, info.port)).unwrap();
+    let auth = token.map_or(String::new(), |token| {
+      format!("Authorization: Bearer {token}\r\n")
+    });
+    write!(
+      stream,
+      "GET {path} HTTP/1.1\r\nHost: localhost\r\n{auth}\r\n"
+    )
+    .unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    let (head, body) = response.split_once("\r\n\r\n").unwrap();
+    let status = head.split_whitespace().nth(1).unwrap().parse().unwrap();
+    (status, serde_json::from_str(body).unwrap())
+  }
+
+  #[test]
+  fn serves_status_and_stats_with_the_token() {
+    let info = api();
+
+    let (status, body) = get(&info, "/status", Some(&info.token));
+    assert_eq!(status, 200);
+    assert_eq!(body["connected"], json!(true));
+    assert_eq!(body["serverId"], json!("srv-1"));
+
+    let (status, body) = get(&info, "/stats", Some(&info.token));
+    assert_eq!(status, 200);
+    assert_eq!(
+      body,
+      json!({
+        "uplinkBytes": 10,
+        "downlinkBytes": 20,
+        "uptimeSecs": 3,
+        "rttMs": null,
+        "lossPct": null
+      })
+    );
+  }
+
+  #[test]
+  fn rejects_a_missing_or_wrong_token() {
+    let info = api();
+
+    assert_eq!(get(&info, "/status", None).0, 401);
+    assert_eq!(get(&info, "/stats", Some("not-the-token")).0, 401);
+  }
+
+  #[test]
+  fn unknown_paths_are_not_found() {
+    let info = api();
+
+    assert_eq!(get(&info, "/connect", Some(&info.token)).0, 404);
+  }
+
+  #[test]
+  fn a_stalled_client_does_not_hold_up_the_next_one() {
+    let info = api();
+    let mut stalled = TcpStream::connect((Ipv4Addr::LOCALHOST, info.port)).unwrap();
+    write!(stalled, "GET /status HTTP/1.1\r\n").unwrap();
+
+    let started = std::time::Instant::now();
+    assert_eq!(get(&info, "/status", Some(&info.token)).0, 200);
+    assert!(started.elapsed() < READ_TIMEOUT);
+  }
+
+  #[test]
+  fn the_info_file_is_owner_only_and_removed_when_off() {
+    let dir = tempfile::tempdir().unwrap();
+    let info = LocalApiInfo {
+      port: 4321,
+      token: "t".repeat(64),
+    };
+    let path = dir.path().join(INFO_FILE);
+
+    publish(dir.path(), Some(&info)).unwrap();
+    let saved: Value = serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+    assert_eq!(saved, json!({ "port": 4321, "token": info.token }));
+    #[cfg(unix)]
+    {
+      use std::os::unix::fs::PermissionsExt;
+      let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+      assert_eq!(mode & 0o777, 0o600);
+    }
+
+    publish(dir.path(), None).unwrap();
+    assert!(!path.exists());
+    publish(dir.path(), None).unwrap();
+  }
+
+  #[test]
+  fn tokens_are_random_and_long() {
+    let (first, second) = (new_token().unwrap(), new_token().unwrap());
+
+    assert_eq!(first.len(), 64);
+    assert_ne!(first, second);
+  }
+}
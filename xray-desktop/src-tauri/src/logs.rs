@@ -0,0 +1,276 @@
+use crate::bridge::{LogSink, NodeBridgeRunner};
+use crate::connect_progress::{ConnectProgress, CONNECT_PROGRESS_EVENT};
+use crate::event_batch;
+use crate::speed_test::{SpeedTestProgress, SPEED_TEST_PROGRESS_EVENT};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tauri::{AppHandle, Emitter, Manager};
+use tracing_appender::non_blocking::WorkerGuard;
+use tracing_subscriber::filter::LevelFilter;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{reload, Registry};
+
+pub const LOG_EVENT: &str = "vpn://log";
+const CAPACITY: usize = 500;
+const LOG_FILE: &str = "vpnator.log";
+
+/// The app's own log, `vpnator.log.YYYY-MM-DD` in the log dir. Keeps the writer thread alive,
+/// so it lives in managed state for the whole run.
+pub struct AppLog {
+  dir: PathBuf,
+  level: reload::Handle<LevelFilter, Registry>,
+  _guard: WorkerGuard,
+}
+
+/// What the file logs at outside debug mode: info, debug in debug builds.
+pub fn default_level() -> LevelFilter {
+  if cfg!(debug_assertions) {
+    LevelFilter::DEBUG
+  } else {
+    LevelFilter::INFO
+  }
+}
+
+impl AppLog {
+  /// Installs the global subscriber: the file at `default_level`, plus the console in debug
+  /// builds. `None` if a subscriber is already set.
+  pub fn init(dir: &Path) -> Option<Self> {
+    let (writer, guard) =
+      tracing_appender::non_blocking(tracing_appender::rolling::daily(dir, LOG_FILE));
+    let (level, handle) = reload::Layer::new(default_level());
+    let file = tracing_subscriber::fmt::layer()
+      .with_writer(writer)
+      .with_ansi(false);
+    let console = cfg!(debug_assertions).then(tracing_subscriber::fmt::layer);
+
+    tracing_subscriber::registry()
+      .with(level)
+      .with(file)
+      .with(console)
+      .try_init()
+      .ok()?;
+    Some(Self {
+      dir: dir.to_path_buf(),
+      level: handle,
+      _guard: guard,
+    })
+  }
+
+  /// Changes what is logged from the next event on, without a restart.
+  pub fn set_level(&self, level: LevelFilter) {
+    if let Err(error) = self.level.reload(level) {
+      tracing::warn!("could not change the log level: {error}");
+    }
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogLevel {
+  Error,
+  Warn,
+  Info,
+}
+
+impl LogLevel {
+  /// Guesses the level from markers like `ERROR:`, `[Warning]` or `warn:` near the start of the
+  /// line. Anything unrecognised is `info`.
+  pub fn guess(line: &str) -> Self {
+    let head: String = line
+      .trim_start()
+      .chars()
+      .take(48)
+      .collect::<String>()
+      .to_lowercase();
+    let marked = |name: &str| head.starts_with(name) || head.contains(&format!("[{}", name));
+
+    if marked("error") || marked("fatal") {
+      Self::Error
+    } else if marked("warn") {
+      Self::Warn
+    } else {
+      Self::Info
+    }
+  }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LogLine {
+  pub level: LogLevel,
+  pub message: String,
+}
+
+impl LogLine {
+  pub fn parse(line: &str) -> Self {
+    Self {
+      level: LogLevel::guess(line),
+      message: line.to_string(),
+    }
+  }
+}
+
+/// The most recent bridge log lines, oldest first.
+#[derive(Debug)]
+pub struct LogBuffer {
+  lines: Mutex<VecDeque<LogLine>>,
+  capacity: usize,
+}
+
+impl Default for LogBuffer {
+  fn default() -> Self {
+    Self::with_capacity(CAPACITY)
+  }
+}
+
+impl LogBuffer {
+  pub fn with_capacity(capacity: usize) -> Self {
+    Self {
+      lines: Mutex::new(VecDeque::with_capacity(capacity)),
+      capacity,
+    }
+  }
+
+  pub fn push(&self, line: LogLine) {
+    let mut lines = self.lines.lock().unwrap();
+    if lines.len() == self.capacity {
+      lines.pop_front();
+    }
+    lines.push_back(line);
+  }
+
+  pub fn recent(&self) -> Vec<LogLine> {
+    self.lines.lock().unwrap().iter().cloned().collect()
+  }
+}
+
+/// Buffers every bridge stderr line and re-emits it, batched, as `vpn://log`. Connect and speed
+/// test progress reports go out as `vpn://connect-progress` and `vpn://speedtest-progress`
+/// instead, one event each.
+pub fn attach(app: &AppHandle, bridge: &NodeBridgeRunner) {
+  let app = app.clone();
+  let sink: LogSink = Arc::new(move |raw| {
+    if raw.is_empty() {
+      return;
+    }
+    if let Some(progress) = ConnectProgress::parse(raw) {
+      let _ = app.emit(CONNECT_PROGRESS_EVENT, progress);
+      return;
+    }
+    if let Some(progress) = SpeedTestProgress::parse(raw) {
+      let _ = app.emit(SPEED_TEST_PROGRESS_EVENT, progress);
+      return;
+    }
+    let line = LogLine::parse(raw);
+    if let Some(buffer) = app.try_state::<LogBuffer>() {
+      buffer.push(line.clone());
+    }
+    event_batch::emit(&app, LOG_EVENT, line);
+  });
+  bridge.set_log_sink(sink);
+}
+
+/// The folder holding `vpnator.log.*`, `None` if file logging couldn't be set up.
+#[allow(non_snake_case)]
+#[tauri::command]
+pub fn getLogPath(app: AppHandle) -> Option<String> {
+  let log = app.try_state::<AppLog>()?;
+  Some(log.dir.to_string_lossy().into_owned())
+}
+
+#[allow(non_snake_case)]
+#[tauri::command]
+pub fn getRecentLogs(buffer: tauri::State<'_, LogBuffer>) -> Vec<LogLine> {
+  buffer.recent()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn guesses_level_from_prefix() {
+    assert_eq!(LogLevel::guess("Error: spawn xray ENOENT"), LogLevel::Error);
+    assert_eq!(
+      LogLevel::guess("2024/05/01 10:00:00 [Warning] core: deprecated"),
+      LogLevel::Warn
+    );
+    assert_eq!(
+      LogLevel::guess("WARN proxy settings unchanged"),
+      LogLevel::Warn
+    );
+    assert_eq!(
+      LogLevel::guess("2024/05/01 10:00:00 [Info] started"),
+      LogLevel::Info
+    );
+    assert_eq!(LogLevel::guess("connected without errors"), LogLevel::Info);
+  }
+
+  #[test]
+  fn buffer_keeps_only_the_latest_lines() {
+    let buffer = LogBuffer::with_capacity(3);
+    for n in 1..=5 {
+      buffer.push(LogLine::parse(&format!("line {}", n)));
+    }
+
+    let messages: Vec<_> = buffer
+      .recent()
+      .into_iter()
+      .map(|line| line.message)
+      .collect();
+
+    assert_eq!(messages, ["line 3", "line 4", "line 5"]);
+  }
+
+  #[cfg(unix)]
+  #[test]
+  fn scripted_stderr_lines_arrive_in_order() {
+    use crate::bridge::{BridgeCommand, DEFAULT_TIMEOUT};
+    use serde_json::Value;
+
+    let dir = tempfile::tempdir().unwrap();
+    let script = dir.path().join("stub-bridge.sh");
+    std::fs::write(
+      &script,
+      r#"read -r line
+id=$(printf '%s' "$line" | sed 's/.*"id":\([0-9]*\).*/\1/')
+echo "starting xray" >&2
+echo "[Warning] slow handshake" >&2
+echo "Error: tunnel failed" >&2
+printf '{"id":%s,"ok":true,"data":null}\n' "$id"
+"#,
+    )
+    .unwrap();
+
+    let bridge = NodeBridgeRunner::new(BridgeCommand::new("sh", &script));
+    let (sender, receiver) = std::sync::mpsc::channel();
+    let sender = Mutex::new(sender);
+    bridge.set_log_sink(Arc::new(move |line| {
+      let _ = sender.lock().unwrap().send(LogLine::parse(line));
+    }));
+
+    bridge
+      .run_with_timeout("connect", Value::Null, DEFAULT_TIMEOUT)
+      .unwrap();
+    let received: Vec<_> = (0..3)
+      .map(|_| receiver.recv_timeout(DEFAULT_TIMEOUT).unwrap())
+      .collect();
+
+    assert_eq!(
+      received,
+      vec![
+        LogLine::parse("starting xray"),
+        LogLine {
+          level: LogLevel::Warn,
+          message: "[Warning] slow handshake".to_string(),
+        },
+        LogLine {
+          level: LogLevel::Error,
+          message: "Error: tunnel failed".to_string(),
+        },
+      ]
+    );
+  }
+}
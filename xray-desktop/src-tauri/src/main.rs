@@ -1,130 +1,585 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
-use serde::Deserialize;
+mod admin_key;
+mod app_rules;
+mod autostart;
+mod bandwidth;
+mod base_url;
+mod bridge;
+mod bridge_integrity;
+mod bridge_pool;
+mod capabilities;
+mod cli;
+mod commands;
+mod config;
+mod config_bundle;
+mod conflicts;
+mod connect_progress;
+mod core_config;
+mod debug_mode;
+mod deep_link;
+mod diagnostics;
+mod disconnect_timer;
+mod disguise;
+mod disguise_probe;
+mod disguise_rotation;
+mod dns;
+mod error;
+mod event_batch;
+mod external_import;
+mod fastest;
+mod favorites;
+mod fingerprint;
+mod folders;
+mod history;
+mod hotkey;
+mod i18n;
+mod ipv6;
+mod kill_switch;
+mod last_error;
+mod latency;
+mod local_api;
+mod logs;
+mod mode;
+mod mtu;
+mod network;
+mod notifications;
+mod onboarding;
+mod persist;
+mod privileges;
+mod profiles;
+mod public_ip;
+mod qr_export;
+mod qr_import;
+mod quality;
+mod readiness;
+mod reconnect;
+mod reset;
+mod routes;
+mod schedules;
+mod secrets;
+mod selftest;
+mod servers;
+mod shutdown;
+mod single_instance;
+mod speed_test;
+mod split_tunnel;
+mod stats;
+mod status;
+mod subscription;
+mod telemetry;
+mod token_refresh;
+mod transport;
+mod tray;
+mod trusted_networks;
+mod updates;
+mod versions;
+mod window_state;
+
+use bridge::{Bridge, BridgeCommand, NodeBridgeRunner};
+use config::Config;
+use error::BridgeError;
+use reconnect::Watchdog;
+use secrets::Secrets;
 use serde_json::{json, Value};
+use shutdown::{CloseAction, Shutdown};
+use status::{StatusCache, StatusSnapshot, StatusWatcher};
 use std::path::PathBuf;
-use std::process::Command;
-#[cfg(any(target_os = "linux", target_os = "windows"))]
-use tauri_plugin_deep_link::DeepLinkExt;
-
-#[derive(Debug, Deserialize)]
-struct BridgeResponse {
-  ok: bool,
-  data: Option<Value>,
-  error: Option<String>,
-}
+use std::sync::Arc;
+use tauri::{AppHandle, Emitter, Manager, RunEvent, State, WindowEvent};
+use window_state::WindowState;
 
-fn bridge_script_path() -> PathBuf {
-  PathBuf::from(env!("CARGO_MANIFEST_DIR"))
-    .join("..")
-    .join("backend")
-    .join("desktop-bridge.cjs")
+#[allow(non_snake_case)]
+#[tauri::command]
+async fn importToken(
+  bridge: State<'_, Bridge>,
+  secrets: State<'_, Secrets>,
+  config: State<'_, Config>,
+  baseUrl: String,
+  token: String,
+) -> Result<(), BridgeError> {
+  commands::import_token(&bridge, &secrets, &config, baseUrl, token).await
 }
 
-fn node_binary() -> String {
-  std::env::var("NODE_BINARY").unwrap_or_else(|_| "node".to_string())
+#[tauri::command]
+#[allow(non_snake_case)]
+async fn connect(
+  app: AppHandle,
+  bridge: State<'_, Bridge>,
+  secrets: State<'_, Secrets>,
+  config: State<'_, Config>,
+  watchdog: State<'_, Watchdog>,
+  serverId: Option<String>,
+) -> Result<Value, BridgeError> {
+  status::expect_change(&app);
+  conflicts::warn_before_connect(&app, &bridge).await;
+  let result = commands::connect(&bridge, &secrets, &config, &watchdog, serverId).await;
+  let _ = app.emit(
+    connect_progress::CONNECT_PROGRESS_EVENT,
+    connect_progress::ConnectProgress::finished(&result),
+  );
+  result
 }
 
-fn run_bridge(action: &str, payload: Value) -> Result<Value, String> {
-  let payload_json = serde_json::to_string(&payload).map_err(|error| error.to_string())?;
-  let output = Command::new(node_binary())
-    .arg(bridge_script_path())
-    .arg(action)
-    .arg(payload_json)
-    .output()
-    .map_err(|error| format!("Failed to run bridge: {}", error))?;
-
-  let stdout = String::from_utf8(output.stdout).map_err(|error| error.to_string())?;
-  let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
-  let stdout_trimmed = stdout.trim();
-
-  if stdout_trimmed.is_empty() {
-    if stderr.is_empty() {
-      return Err("Bridge returned empty response".to_string());
-    }
-
-    return Err(stderr);
-  }
+#[tauri::command]
+#[allow(non_snake_case)]
+async fn connectWith(
+  app: AppHandle,
+  bridge: State<'_, Bridge>,
+  secrets: State<'_, Secrets>,
+  config: State<'_, Config>,
+  watchdog: State<'_, Watchdog>,
+  options: commands::ConnectOptions,
+) -> Result<Value, BridgeError> {
+  status::expect_change(&app);
+  conflicts::warn_before_connect(&app, &bridge).await;
+  let result = commands::connect_with(&bridge, &secrets, &config, &watchdog, options).await;
+  let _ = app.emit(
+    connect_progress::CONNECT_PROGRESS_EVENT,
+    connect_progress::ConnectProgress::finished(&result),
+  );
+  result
+}
 
-  let response: BridgeResponse =
-    serde_json::from_str(stdout_trimmed).map_err(|error| format!("Invalid bridge JSON: {}", error))?;
+#[tauri::command]
+#[allow(non_snake_case)]
+async fn setMode(
+  bridge: State<'_, Bridge>,
+  config: State<'_, Config>,
+  mode: String,
+) -> Result<Value, BridgeError> {
+  commands::set_mode(&bridge, &config, &mode).await
+}
 
-  if response.ok {
-    return Ok(response.data.unwrap_or(Value::Null));
-  }
+#[tauri::command]
+async fn disconnect(
+  app: AppHandle,
+  bridge: State<'_, Bridge>,
+  watchdog: State<'_, Watchdog>,
+) -> Result<(), BridgeError> {
+  status::expect_change(&app);
+  let result = commands::disconnect(&bridge, &watchdog).await;
+  event_batch::flush(&app);
+  result
+}
 
-  let message = response
-    .error
-    .or_else(|| if stderr.is_empty() { None } else { Some(stderr) })
-    .unwrap_or_else(|| "Unknown bridge error".to_string());
-  Err(message)
+#[allow(non_snake_case)]
+#[tauri::command]
+async fn toggleConnection(
+  app: AppHandle,
+  bridge: State<'_, Bridge>,
+  secrets: State<'_, Secrets>,
+  config: State<'_, Config>,
+  watchdog: State<'_, Watchdog>,
+  lock: State<'_, commands::ToggleLock>,
+) -> Result<StatusSnapshot, BridgeError> {
+  status::expect_change(&app);
+  commands::toggle_connection(&bridge, &secrets, &config, &watchdog, &lock).await
 }
 
 #[allow(non_snake_case)]
 #[tauri::command]
-fn importToken(baseUrl: String, token: String) -> Result<(), String> {
-  run_bridge("importToken", json!({ "baseUrl": baseUrl, "token": token })).map(|_| ())
+async fn reconnectNow(
+  app: AppHandle,
+  bridge: State<'_, Bridge>,
+  secrets: State<'_, Secrets>,
+  config: State<'_, Config>,
+  watchdog: State<'_, Watchdog>,
+  lock: State<'_, commands::ToggleLock>,
+) -> Result<commands::ReconnectReport, BridgeError> {
+  status::expect_change(&app);
+  let announce = || reconnect::announce_reconnect_now(&app);
+  Ok(commands::reconnect_now(&bridge, &secrets, &config, &watchdog, &lock, announce).await)
 }
 
+#[allow(non_snake_case)]
 #[tauri::command]
-fn connect() -> Result<Value, String> {
-  run_bridge("connect", Value::Null)
+async fn cancelConnect(
+  bridge: State<'_, Bridge>,
+  watchdog: State<'_, Watchdog>,
+) -> Result<bool, BridgeError> {
+  commands::cancel_connect(&bridge, &watchdog).await
 }
 
 #[tauri::command]
-#[allow(non_snake_case)]
-fn setMode(mode: String) -> Result<Value, String> {
-  run_bridge("setMode", json!({ "mode": mode }))
+async fn pause(bridge: State<'_, Bridge>) -> Result<(), BridgeError> {
+  commands::pause(&bridge).await
 }
 
 #[tauri::command]
-fn disconnect() -> Result<(), String> {
-  run_bridge("disconnect", Value::Null).map(|_| ())
+async fn resume(bridge: State<'_, Bridge>) -> Result<(), BridgeError> {
+  commands::resume(&bridge).await
 }
 
 #[allow(non_snake_case)]
 #[tauri::command]
-fn updateDisguise(
+async fn updateDisguise(
+  bridge: State<'_, Bridge>,
+  config: State<'_, Config>,
   baseUrl: String,
   serverId: String,
   adminApiKey: String,
   disguise: Value,
-) -> Result<Value, String> {
-  run_bridge(
-    "updateDisguise",
-    json!({
-      "baseUrl": baseUrl,
-      "serverId": serverId,
-      "adminApiKey": adminApiKey,
-      "disguise": disguise
-    }),
-  )
+  dryRun: Option<bool>,
+) -> Result<disguise::UpdateOutcome, BridgeError> {
+  let base_url = base_url::normalize_base_url(&baseUrl, config.get().allow_insecure_base_url)?;
+  let dry_run = dryRun.unwrap_or(false);
+  disguise::update(&bridge, base_url, serverId, adminApiKey, disguise, dry_run).await
+}
+
+#[allow(non_snake_case)]
+#[tauri::command]
+async fn status(
+  bridge: State<'_, Bridge>,
+  cache: State<'_, StatusCache>,
+  forceRefresh: Option<bool>,
+) -> Result<StatusSnapshot, BridgeError> {
+  cache.get(&bridge, forceRefresh.unwrap_or(false)).await
 }
 
+#[allow(non_snake_case)]
 #[tauri::command]
-fn status() -> Result<Value, String> {
-  run_bridge("status", Value::Null)
+fn isBusy(bridge: State<'_, Bridge>) -> bool {
+  bridge.is_busy()
+}
+
+/// Re-applies the last mode and, if enabled and not on a trusted network, connects without
+/// waiting for the UI.
+fn restore_session(app: AppHandle) {
+  tauri::async_runtime::spawn(async move {
+    let bridge = app.state::<Bridge>().inner().clone();
+    let settings = app.state::<Config>().get();
+    if app
+      .state::<readiness::Readiness>()
+      .wait(readiness::STARTUP_TIMEOUT)
+      .await
+      .is_err()
+    {
+      tracing::warn!("not restoring the session: still not ready");
+      return;
+    }
+    if let Some(mode) = settings.last_mode {
+      let _ = bridge.call("setMode", json!({ "mode": mode })).await;
+    }
+    if trusted_networks::auto_connect_allowed(&bridge, &settings).await {
+      let _ = commands::connect(
+        &bridge,
+        &app.state::<Secrets>(),
+        &app.state::<Config>(),
+        &app.state::<Watchdog>(),
+        None,
+      )
+      .await;
+    }
+  });
 }
 
 fn main() {
+  let context = tauri::generate_context!();
+  let args = std::env::args_os().map(|arg| arg.to_string_lossy().into_owned());
+  match cli::parse(args) {
+    Ok(Some(command)) => std::process::exit(cli::run(command, &context)),
+    Ok(None) => {}
+    Err(usage) => {
+      eprintln!("{}", usage);
+      std::process::exit(cli::EXIT_USAGE);
+    }
+  }
+
   tauri::Builder::default()
-    .setup(|_app| {
-      #[cfg(any(target_os = "linux", target_os = "windows"))]
+    // Must be the first plugin so a second launch exits before it spawns its own bridge.
+    .plugin(single_instance::init())
+    .setup(|app| {
+      if let Some(log) = app
+        .path()
+        .app_log_dir()
+        .ok()
+        .and_then(|dir| logs::AppLog::init(&dir))
       {
-        _app.deep_link().register_all()?;
+        app.manage(log);
+      }
+      tracing::info!(version = env!("CARGO_PKG_VERSION"), "starting");
+      let config = Config::for_app(app.handle())?;
+      debug_mode::restore(app.handle(), &config);
+      let readiness = readiness::Readiness::default();
+      app.manage(readiness.clone());
+      let node_path = config.get().node_path.map(PathBuf::from);
+      let script_path = config.get().bridge_script_path.map(PathBuf::from);
+      let node =
+        match BridgeCommand::for_app(app.handle(), node_path.as_deref(), script_path.as_deref())
+          .and_then(|command| bridge_integrity::check(command, config.get().verify_bridge))
+        {
+          Ok(command) => NodeBridgeRunner::new(command),
+          Err(error) => NodeBridgeRunner::unavailable(error),
+        };
+      // Settled either way: a missing node or a tampered script is reported by the runner.
+      readiness::complete(app.handle(), readiness::Prerequisite::NodeResolved);
+      readiness::complete(app.handle(), readiness::Prerequisite::BridgeVerified);
+      node.set_spawn_retries(config.get().bridge_spawn_retries);
+      node.set_proxy_env(profiles::proxy_env(&config.get()));
+      node.set_options(debug_mode::bridge_options(
+        config.get().bridge_env,
+        config.get().debug_mode,
+      ));
+      app.manage(logs::LogBuffer::default());
+      app.manage(event_batch::EventBatcher::for_app(
+        app.handle(),
+        config.get().event_batching,
+      ));
+      logs::attach(app.handle(), &node);
+      #[cfg(unix)]
+      node.start_reaper(bridge::REAP_INTERVAL);
+      let bridge = Bridge::new(node.clone());
+      let handle = app.handle().clone();
+      bridge.set_error_sink(Arc::new(move |action, error| {
+        last_error::record(&handle, action, error);
+        commands::announce_auth_required(&handle, error);
+      }));
+      let handle = app.handle().clone();
+      bridge.set_timing_sink(Arc::new(move |action, elapsed, error| {
+        telemetry::observe(&handle, action, elapsed, error);
+      }));
+      let handle = app.handle().clone();
+      bridge.set_pin_source(Arc::new(move |base_url| {
+        let config = handle.try_state::<Config>()?;
+        fingerprint::pinned(&config, base_url)
+      }));
+      bridge.set_readiness(readiness);
+      // So the first command doesn't pay node's startup cost.
+      let prewarm = bridge::prewarm(&bridge, config.get().prewarm_bridge);
+      let handle = app.handle().clone();
+      tauri::async_runtime::spawn(async move {
+        if let Some(prewarm) = prewarm {
+          let _ = prewarm.await;
+        }
+        readiness::complete(&handle, readiness::Prerequisite::Prewarmed);
+      });
+      app.manage(bridge);
+      app.manage(node);
+      app.manage(bridge_pool::BridgePool::new(bridge_pool::limit_for(
+        config.get().bridge_pool_size,
+      )));
+      let hotkeys = hotkey::Hotkeys::new(Box::new(hotkey::GlobalShortcuts(app.handle().clone())));
+      hotkeys.register_saved(&config);
+      app.manage(hotkeys);
+      let catalog = servers::ServerCatalog::default();
+      catalog.set_imported(config.get().imported_servers);
+      app.manage(catalog);
+      app.manage(config);
+      app.manage(Shutdown::default());
+      app.manage(Secrets::keyring());
+      app.manage(Watchdog::default());
+      app.manage(commands::ToggleLock::default());
+      app.manage(latency::LatencyCache::default());
+      app.manage(StatusCache::default());
+      app.manage(public_ip::PublicIpCache::default());
+      app.manage(disguise_rotation::DisguiseRotation::default());
+      app.manage(history::History::for_app(app.handle())?);
+      app.manage(last_error::LastErrorStore::for_app(app.handle())?);
+      app.manage(telemetry::Telemetry::for_app(app.handle())?);
+      onboarding::announce(app.handle());
+      deep_link::register(app)?;
+      restore_session(app.handle().clone());
+
+      let watcher = StatusWatcher::default();
+      watcher.start(app.handle().clone());
+      app.manage(watcher);
+      app.manage(stats::StatsEmitter::default());
+      app.manage(quality::ConnectionQuality::default());
+      app.manage(disconnect_timer::DisconnectTimer::default());
+      let local_api = local_api::LocalApi::default();
+      local_api.start(app.handle());
+      app.manage(local_api);
+      let refresher = token_refresh::TokenRefresher::default();
+      refresher.start(app.handle().clone());
+      app.manage(refresher);
+      let scheduler = schedules::Scheduler::default();
+      scheduler.start(app.handle().clone());
+      app.manage(scheduler);
+      let network_watcher = network::NetworkWatcher::default();
+      network_watcher.start(app.handle().clone());
+      app.manage(network_watcher);
+
+      let window_state = WindowState::for_app(app.handle())?;
+      if let Some(window) = app.get_webview_window("main") {
+        window_state.restore(&window);
       }
+      app.manage(window_state);
+
+      // Some Linux desktops have no tray; the window remains the only UI there.
+      let _ = tray::init(app);
 
       Ok(())
     })
+    .on_window_event(|window, event| match event {
+      WindowEvent::Moved(_) | WindowEvent::Resized(_) => {
+        if let Some(window_state) = window.try_state::<WindowState>() {
+          window_state.record(window);
+        }
+      }
+      WindowEvent::CloseRequested { api, .. } => {
+        if let Some(window_state) = window.try_state::<WindowState>() {
+          window_state.flush();
+        }
+        api.prevent_close();
+        let app = window.app_handle();
+        let close_to_tray = app
+          .try_state::<Config>()
+          .is_some_and(|config| config.get().close_to_tray);
+        match shutdown::close_action(close_to_tray, app.tray_by_id(tray::TRAY_ID).is_some()) {
+          // The tunnel and the tray stay up; "Show Window" brings it back.
+          CloseAction::Hide => {
+            let _ = window.hide();
+          }
+          // Keep the window alive until the tunnel is down (or the user chose to keep it), then
+          // exit.
+          CloseAction::Exit => shutdown::request_quit(app),
+        }
+      }
+      _ => {}
+    })
     .plugin(tauri_plugin_deep_link::init())
+    .plugin(autostart::plugin())
+    .plugin(hotkey::plugin())
+    .plugin(tauri_plugin_notification::init())
     .invoke_handler(tauri::generate_handler![
       importToken,
       connect,
+      connectWith,
       setMode,
       updateDisguise,
       disconnect,
-      status
+      toggleConnection,
+      reconnectNow,
+      hotkey::getHotkey,
+      hotkey::setHotkey,
+      onboarding::completeOnboarding,
+      cancelConnect,
+      pause,
+      resume,
+      status,
+      isBusy,
+      autostart::getAutostart,
+      autostart::setAutostart,
+      status::startStatusWatcher,
+      status::stopStatusWatcher,
+      status::setStatusPollInterval,
+      secrets::storeToken,
+      secrets::loadToken,
+      secrets::clearToken,
+      config::getSettings,
+      config::updateSettings,
+      config_bundle::exportConfigBundle,
+      config_bundle::importConfigBundle,
+      external_import::importExternalConfig,
+      external_import::removeImportedServer,
+      conflicts::detectConflicts,
+      logs::getRecentLogs,
+      diagnostics::exportDiagnostics,
+      logs::getLogPath,
+      debug_mode::getDebugMode,
+      debug_mode::setDebugMode,
+      folders::openConfigDir,
+      folders::openLogDir,
+      folders::openDataDir,
+      history::getConnectionHistory,
+      last_error::getLastError,
+      last_error::clearLastError,
+      bridge_pool::getBridgePoolStatus,
+      token_refresh::refreshToken,
+      stats::getStats,
+      stats::startStatsEmitter,
+      stats::stopStatsEmitter,
+      local_api::getLocalApiInfo,
+      servers::listServers,
+      servers::filterServers,
+      servers::listServerGroups,
+      servers::streamServers,
+      telemetry::getTelemetry,
+      subscription::importSubscription,
+      routes::getRoutes,
+      reset::resetAllData,
+      core_config::getCoreConfig,
+      core_config::setCoreConfig,
+      speed_test::speedTest,
+      speed_test::cancelSpeedTest,
+      bridge_integrity::verifyBridge,
+      readiness::waitUntilReady,
+      admin_key::rotateAdminKey,
+      admin_key::storeAdminKey,
+      admin_key::loadAdminKey,
+      trusted_networks::listTrustedNetworks,
+      trusted_networks::addTrustedNetwork,
+      trusted_networks::removeTrustedNetwork,
+      trusted_networks::addCurrentNetworkAsTrusted,
+      servers::selectServer,
+      latency::pingServer,
+      latency::pingAll,
+      fastest::connectFastest,
+      kill_switch::getKillSwitch,
+      kill_switch::setKillSwitch,
+      split_tunnel::getSplitTunnel,
+      split_tunnel::addSplitTunnelRule,
+      split_tunnel::removeSplitTunnelRule,
+      app_rules::getAppRules,
+      app_rules::setAppRule,
+      app_rules::removeAppRule,
+      capabilities::getCapabilities,
+      disguise_probe::probeDisguise,
+      schedules::listSchedules,
+      schedules::addSchedule,
+      schedules::removeSchedule,
+      favorites::listFavorites,
+      favorites::toggleFavorite,
+      favorites::listRecentServers,
+      dns::getDnsConfig,
+      dns::setDnsConfig,
+      dns::testDnsLeak,
+      ipv6::getIpv6Enabled,
+      ipv6::setIpv6Enabled,
+      mtu::getMtu,
+      mtu::setMtu,
+      i18n::getLocale,
+      i18n::setLocale,
+      fingerprint::getServerFingerprint,
+      fingerprint::pinServerFingerprint,
+      transport::getTransport,
+      transport::setTransport,
+      bandwidth::getBandwidthLimit,
+      bandwidth::setBandwidthLimit,
+      public_ip::getPublicIp,
+      profiles::listProfiles,
+      profiles::createProfile,
+      profiles::deleteProfile,
+      profiles::setProfileProxy,
+      profiles::switchProfile,
+      qr_import::importTokenFromImage,
+      qr_export::exportProfileQr,
+      quality::getConnectionQuality,
+      privileges::checkPrivileges,
+      disconnect_timer::setDisconnectTimer,
+      disconnect_timer::cancelDisconnectTimer,
+      disconnect_timer::getDisconnectTimer,
+      disguise::batchUpdateDisguise,
+      disguise::testDisguise,
+      disguise::listDisguiseTemplates,
+      disguise_rotation::setDisguiseRotation,
+      disguise_rotation::rotateDisguise,
+      disguise_rotation::getDisguiseRotation,
+      base_url::testBaseUrl,
+      versions::getVersions,
+      selftest::runSelfTest,
+      shutdown::confirmQuit,
+      updates::checkForUpdates
     ])
-    .run(tauri::generate_context!())
-    .expect("error while running tauri application");
+    .build(context)
+    .expect("error while building tauri application")
+    .run(|app, event| {
+      if let RunEvent::Exit = event {
+        if let Some(window_state) = app.try_state::<WindowState>() {
+          window_state.flush();
+        }
+        shutdown::graceful(app);
+      }
+    });
 }
@@ -0,0 +1,83 @@
+use crate::error::BridgeError;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+/// Tunnel modes supported by `xray-client-agent`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Mode {
+  /// System proxy only.
+  Proxy,
+  /// TUN device routing all traffic.
+  Vpn,
+}
+
+impl Mode {
+  pub const ALLOWED: &'static [&'static str] = &["proxy", "vpn"];
+
+  pub fn as_str(self) -> &'static str {
+    match self {
+      Self::Proxy => "proxy",
+      Self::Vpn => "vpn",
+    }
+  }
+}
+
+impl fmt::Display for Mode {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str(self.as_str())
+  }
+}
+
+impl FromStr for Mode {
+  type Err = BridgeError;
+
+  fn from_str(value: &str) -> Result<Self, Self::Err> {
+    match value {
+      "proxy" => Ok(Self::Proxy),
+      "vpn" => Ok(Self::Vpn),
+      _ => Err(BridgeError::InvalidMode {
+        got: value.to_string(),
+        allowed: Self::ALLOWED,
+      }),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use serde_json::json;
+
+  #[test]
+  fn parses_every_supported_mode() {
+    assert_eq!("proxy".parse::<Mode>().unwrap(), Mode::Proxy);
+    assert_eq!("vpn".parse::<Mode>().unwrap(), Mode::Vpn);
+  }
+
+  #[test]
+  fn allowed_list_matches_the_variants() {
+    for name in Mode::ALLOWED {
+      assert_eq!(name.parse::<Mode>().unwrap().as_str(), *name);
+    }
+  }
+
+  #[test]
+  fn rejects_unknown_mode_with_allowed_values() {
+    let error = "prox".parse::<Mode>().unwrap_err();
+
+    assert_eq!(
+      serde_json::to_value(&error).unwrap(),
+      json!({
+        "kind": "invalidMode",
+        "message": "Invalid mode \"prox\", expected one of: proxy, vpn"
+      })
+    );
+  }
+
+  #[test]
+  fn serializes_as_the_bridge_name() {
+    assert_eq!(serde_json::to_value(Mode::Vpn).unwrap(), json!("vpn"));
+  }
+}
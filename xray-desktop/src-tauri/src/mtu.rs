@@ -0,0 +1,76 @@
+use crate::bridge::Bridge;
+use crate::config::Config;
+use crate::error::BridgeError;
+use serde_json::json;
+use std::ops::RangeInclusive;
+
+/// 576 is the smallest MTU IPv4 guarantees; 9000 covers jumbo frames.
+pub const MTU_RANGE: RangeInclusive<u16> = 576..=9000;
+
+pub fn validate(value: u16) -> Result<u16, BridgeError> {
+  if MTU_RANGE.contains(&value) {
+    Ok(value)
+  } else {
+    Err(BridgeError::InvalidMtu(value))
+  }
+}
+
+/// Forwards the MTU to the bridge and saves it; `connect` re-sends the saved value.
+pub async fn set(bridge: &Bridge, config: &Config, value: u16) -> Result<(), BridgeError> {
+  let value = validate(value)?;
+  bridge.call("mtu", json!({ "value": value })).await?;
+  config.update(|settings| settings.mtu = Some(value))?;
+  Ok(())
+}
+
+/// `None` while the TUN interface uses the agent's default.
+#[allow(non_snake_case)]
+#[tauri::command]
+pub fn getMtu(config: tauri::State<'_, Config>) -> Option<u16> {
+  config.get().mtu
+}
+
+#[allow(non_snake_case)]
+#[tauri::command]
+pub async fn setMtu(
+  bridge: tauri::State<'_, Bridge>,
+  config: tauri::State<'_, Config>,
+  value: u16,
+) -> Result<(), BridgeError> {
+  set(&bridge, &config, value).await
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::bridge::mock::MockBridgeRunner;
+  use serde_json::Value;
+  use tauri::async_runtime::block_on;
+
+  #[test]
+  fn accepts_the_bounds_and_rejects_values_outside() {
+    assert_eq!(validate(576), Ok(576));
+    assert_eq!(validate(1420), Ok(1420));
+    assert_eq!(validate(9000), Ok(9000));
+    assert_eq!(validate(575), Err(BridgeError::InvalidMtu(575)));
+    assert_eq!(validate(9001), Err(BridgeError::InvalidMtu(9001)));
+    assert_eq!(validate(0).unwrap_err().kind(), "invalidMtu");
+  }
+
+  #[test]
+  fn forwards_and_saves_valid_values_only() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("settings.json");
+    let mock = MockBridgeRunner::default().respond("mtu", Ok(Value::Null));
+    let bridge = Bridge::new(mock.clone());
+
+    block_on(set(&bridge, &Config::load(&path), 1400)).unwrap();
+    assert!(block_on(set(&bridge, &Config::load(&path), 100)).is_err());
+
+    assert_eq!(Config::load(&path).get().mtu, Some(1400));
+    assert_eq!(
+      mock.calls(),
+      [("mtu".to_string(), json!({ "value": 1400 }))]
+    );
+  }
+}
@@ -0,0 +1,191 @@
+use crate::bridge::Bridge;
+use crate::commands;
+use crate::config::Config;
+use crate::reconnect::Watchdog;
+use crate::secrets::Secrets;
+use crate::status::LinkState;
+use crate::trusted_networks;
+use serde::{Deserialize, Serialize};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tauri::async_runtime::JoinHandle;
+use tauri::{AppHandle, Emitter, Manager};
+
+pub const NETWORK_CHANGED_EVENT: &str = "vpn://network-changed";
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+/// Interfaces must stay unchanged this long before a change is acted on, so a burst (waking from
+/// sleep, a Wi-Fi roam) reconnects once.
+pub const SETTLE: Duration = Duration::from_secs(5);
+
+/// One address on a physical interface, as reported by the bridge `networkInterfaces` action.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InterfaceAddress {
+  pub name: String,
+  pub address: String,
+}
+
+/// Collapses a storm of changes into one, reported after `SETTLE` without further changes.
+#[derive(Debug, Default)]
+pub struct Debouncer {
+  last_change: Option<Instant>,
+}
+
+impl Debouncer {
+  pub fn changed(&mut self, now: Instant) {
+    self.last_change = Some(now);
+  }
+
+  /// `true` once per burst, when it has settled.
+  pub fn settled(&mut self, now: Instant) -> bool {
+    match self.last_change {
+      Some(at) if now.saturating_duration_since(at) >= SETTLE => {
+        self.last_change = None;
+        true
+      }
+      _ => false,
+    }
+  }
+}
+
+/// A paused tunnel is left alone, like the watchdog does.
+pub fn should_reconnect(state: LinkState, auto_reconnect: bool) -> bool {
+  auto_reconnect && state == LinkState::Connected
+}
+
+pub async fn interfaces(bridge: &Bridge) -> Option<Vec<InterfaceAddress>> {
+  let value = bridge
+    .call("networkInterfaces", serde_json::Value::Null)
+    .await
+    .ok()?;
+  let mut addresses: Vec<InterfaceAddress> = serde_json::from_value(value).ok()?;
+  addresses.sort();
+  Some(addresses)
+}
+
+/// Polls the machine's interfaces and re-establishes the tunnel after they change, instead of
+/// waiting for the status poll to notice the dead connection.
+#[derive(Default)]
+pub struct NetworkWatcher {
+  task: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl NetworkWatcher {
+  pub fn start(&self, app: AppHandle) {
+    let mut task = self.task.lock().unwrap();
+    if task.is_none() {
+      *task = Some(tauri::async_runtime::spawn(watch_loop(app)));
+    }
+  }
+}
+
+async fn watch_loop(app: AppHandle) {
+  let mut known: Option<Vec<InterfaceAddress>> = None;
+  let mut debouncer = Debouncer::default();
+  loop {
+    tokio::time::sleep(POLL_INTERVAL).await;
+    let Some(bridge) = app.try_state::<Bridge>() else {
+      continue;
+    };
+    // A failed read isn't a change; the next poll tries again.
+    if let Some(current) = interfaces(&bridge).await {
+      if known.as_ref().is_some_and(|known| *known != current) {
+        debouncer.changed(Instant::now());
+      }
+      known = Some(current);
+    }
+    if !debouncer.settled(Instant::now()) {
+      continue;
+    }
+
+    let _ = app.emit(NETWORK_CHANGED_EVENT, known.clone().unwrap_or_default());
+    let (Some(secrets), Some(config), Some(watchdog)) = (
+      app.try_state::<Secrets>(),
+      app.try_state::<Config>(),
+      app.try_state::<Watchdog>(),
+    ) else {
+      continue;
+    };
+    let state = commands::status(&bridge)
+      .await
+      .map_or(LinkState::Disconnected, |snapshot| snapshot.state());
+    if state == LinkState::Disconnected {
+      // Joining an untrusted network brings the tunnel up like a launch with `autoConnect` does.
+      if trusted_networks::auto_connect_allowed(&bridge, &config.get()).await {
+        tracing::info!("joined an untrusted network, connecting");
+        if let Err(error) = commands::connect(&bridge, &secrets, &config, &watchdog, None).await {
+          tracing::warn!(
+            kind = error.kind(),
+            "auto-connect after a network change failed: {error}"
+          );
+        }
+      }
+      continue;
+    }
+    if !should_reconnect(state, config.get().auto_reconnect) {
+      continue;
+    }
+    tracing::info!("network changed, reconnecting");
+    // Disconnecting first also stands the watchdog down, so it doesn't retry alongside.
+    let _ = commands::disconnect(&bridge, &watchdog).await;
+    if let Err(error) = commands::connect(&bridge, &secrets, &config, &watchdog, None).await {
+      tracing::warn!(
+        kind = error.kind(),
+        "reconnect after a network change failed: {error}"
+      );
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn a_storm_of_changes_settles_once() {
+    let start = Instant::now();
+    let mut debouncer = Debouncer::default();
+    let mut fired = Vec::new();
+
+    // A change every second for 10s, as when waking from sleep, then quiet; polled every 1s.
+    for second in 0..30 {
+      let now = start + Duration::from_secs(second);
+      if second < 10 {
+        debouncer.changed(now);
+      }
+      if debouncer.settled(now) {
+        fired.push(second);
+      }
+    }
+
+    assert_eq!(fired, [9 + SETTLE.as_secs()]);
+  }
+
+  #[test]
+  fn separate_changes_each_settle() {
+    let start = Instant::now();
+    let mut debouncer = Debouncer::default();
+
+    debouncer.changed(start);
+    assert!(!debouncer.settled(start + SETTLE / 2));
+    assert!(debouncer.settled(start + SETTLE));
+    assert!(!debouncer.settled(start + SETTLE * 2));
+
+    let later = start + SETTLE * 3;
+    debouncer.changed(later);
+    assert!(debouncer.settled(later + SETTLE));
+  }
+
+  #[test]
+  fn nothing_settles_without_a_change() {
+    assert!(!Debouncer::default().settled(Instant::now() + SETTLE * 10));
+  }
+
+  #[test]
+  fn reconnects_only_a_live_tunnel_with_auto_reconnect() {
+    assert!(should_reconnect(LinkState::Connected, true));
+    assert!(!should_reconnect(LinkState::Connected, false));
+    assert!(!should_reconnect(LinkState::Paused, true));
+    assert!(!should_reconnect(LinkState::Disconnected, true));
+  }
+}
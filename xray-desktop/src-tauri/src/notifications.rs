@@ -0,0 +1,211 @@
+use crate::config::Config;
+use crate::i18n;
+use crate::status::{LinkState, StatusSnapshot};
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Manager};
+use tauri_plugin_notification::NotificationExt;
+
+/// How long a new connection state must hold before it is announced. A drop that recovers
+/// within this window produces no notification at all.
+const SETTLE: Duration = Duration::from_secs(4);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Notice {
+  Connected,
+  Disconnected,
+  ConnectionLost,
+  Paused,
+  Resumed,
+}
+
+impl Notice {
+  /// `i18n` keys of the title and the body.
+  fn keys(self) -> (&'static str, &'static str) {
+    match self {
+      Self::Connected => ("notice.connected.title", "notice.connected.body"),
+      Self::Disconnected => ("notice.disconnected.title", "notice.disconnected.body"),
+      Self::ConnectionLost => ("notice.connectionLost.title", "notice.connectionLost.body"),
+      Self::Paused => ("notice.paused.title", "notice.paused.body"),
+      Self::Resumed => ("notice.resumed.title", "notice.resumed.body"),
+    }
+  }
+}
+
+/// Turns the snapshots seen by the status watcher into notices, announcing a connection state
+/// only once it has held for `SETTLE`.
+#[derive(Debug, Default)]
+pub struct Notifier {
+  /// Last connection state the user was told about (or found at startup).
+  announced: Option<LinkState>,
+  pending: Option<Pending>,
+}
+
+#[derive(Debug)]
+struct Pending {
+  state: LinkState,
+  notice: Notice,
+  since: Instant,
+}
+
+impl Notifier {
+  pub fn observe(&mut self, snapshot: &StatusSnapshot, now: Instant) -> Option<Notice> {
+    let Some(announced) = self.announced else {
+      // The state found at startup is not a transition.
+      self.announced = Some(snapshot.state());
+      return None;
+    };
+
+    let state = snapshot.state();
+    let observed = self
+      .pending
+      .as_ref()
+      .map_or(announced, |pending| pending.state);
+    if state != observed {
+      self.pending = (state != announced).then(|| Pending {
+        state,
+        notice: notice_for(snapshot, announced),
+        since: now,
+      });
+    }
+
+    let settled = self
+      .pending
+      .as_ref()
+      .is_some_and(|pending| now.duration_since(pending.since) >= SETTLE);
+    if !settled {
+      return None;
+    }
+    let pending = self.pending.take()?;
+    self.announced = Some(pending.state);
+    Some(pending.notice)
+  }
+}
+
+fn notice_for(snapshot: &StatusSnapshot, announced: LinkState) -> Notice {
+  match snapshot.state() {
+    LinkState::Paused => Notice::Paused,
+    LinkState::Connected if announced == LinkState::Paused => Notice::Resumed,
+    LinkState::Connected => Notice::Connected,
+    LinkState::Disconnected if snapshot.last_error.is_some() => Notice::ConnectionLost,
+    LinkState::Disconnected => Notice::Disconnected,
+  }
+}
+
+/// Posts `notice` in the saved locale unless the user silenced notifications.
+pub fn post(app: &AppHandle, notice: Notice) {
+  let Some(settings) = app.try_state::<Config>().map(|config| config.get()) else {
+    return;
+  };
+  if !settings.notifications_enabled {
+    return;
+  }
+
+  let (title, body) = notice.keys();
+  let _ = app
+    .notification()
+    .builder()
+    .title(i18n::text(&settings.locale, title))
+    .body(i18n::text(&settings.locale, body))
+    .show();
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use serde_json::{json, Value};
+
+  fn snapshot(value: Value) -> StatusSnapshot {
+    serde_json::from_value(value).unwrap()
+  }
+
+  /// Feeds `(seconds, snapshot)` pairs and collects what would be posted.
+  fn notices(script: Vec<(u64, Value)>) -> Vec<Notice> {
+    let start = Instant::now();
+    let mut notifier = Notifier::default();
+
+    script
+      .into_iter()
+      .filter_map(|(secs, value)| {
+        notifier.observe(&snapshot(value), start + Duration::from_secs(secs))
+      })
+      .collect()
+  }
+
+  #[test]
+  fn announces_settled_transitions() {
+    let script = vec![
+      (0, json!({ "connected": false })),
+      (2, json!({ "connected": true })),
+      (4, json!({ "connected": true })),
+      (6, json!({ "connected": true })),
+      (8, json!({ "connected": false })),
+      (10, json!({ "connected": false })),
+      (12, json!({ "connected": false })),
+    ];
+
+    assert_eq!(
+      notices(script),
+      vec![Notice::Connected, Notice::Disconnected]
+    );
+  }
+
+  #[test]
+  fn startup_state_is_not_announced() {
+    let script = vec![
+      (0, json!({ "connected": true })),
+      (2, json!({ "connected": true })),
+      (10, json!({ "connected": true })),
+    ];
+
+    assert_eq!(notices(script), vec![]);
+  }
+
+  #[test]
+  fn brief_reconnect_is_not_announced() {
+    let script = vec![
+      (0, json!({ "connected": true })),
+      (2, json!({ "connected": false, "lastError": "xray exited" })),
+      (4, json!({ "connected": true })),
+      (6, json!({ "connected": false, "lastError": "xray exited" })),
+      (8, json!({ "connected": true })),
+      (20, json!({ "connected": true })),
+    ];
+
+    assert_eq!(notices(script), vec![]);
+  }
+
+  #[test]
+  fn lasting_drop_with_an_error_is_connection_lost() {
+    let script = vec![
+      (0, json!({ "connected": true })),
+      (2, json!({ "connected": false, "lastError": "xray exited" })),
+      (4, json!({ "connected": true })),
+      (6, json!({ "connected": false, "lastError": "xray exited" })),
+      (8, json!({ "connected": false, "lastError": "xray exited" })),
+      (
+        10,
+        json!({ "connected": false, "lastError": "xray exited" }),
+      ),
+      (12, json!({ "connected": true })),
+      (16, json!({ "connected": true })),
+    ];
+
+    assert_eq!(
+      notices(script),
+      vec![Notice::ConnectionLost, Notice::Connected]
+    );
+  }
+
+  #[test]
+  fn pause_and_resume_are_announced_as_such() {
+    let script = vec![
+      (0, json!({ "connected": true })),
+      (2, json!({ "connected": true, "paused": true })),
+      (8, json!({ "connected": true, "paused": true })),
+      (10, json!({ "connected": true })),
+      (16, json!({ "connected": true })),
+    ];
+
+    assert_eq!(notices(script), vec![Notice::Paused, Notice::Resumed]);
+  }
+}
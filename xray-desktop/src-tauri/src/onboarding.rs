@@ -0,0 +1,84 @@
+use crate::config::{Config, Settings};
+use crate::error::BridgeError;
+use crate::profiles;
+use crate::secrets::Secrets;
+use tauri::{AppHandle, Emitter, Manager};
+
+/// Emitted once at launch while setup hasn't been finished and there is nothing to connect with.
+pub const NEEDS_ONBOARDING_EVENT: &str = "vpn://needs-onboarding";
+
+/// A user who imported a token before the flag existed already has one, so they aren't sent
+/// back through setup. An unreadable keychain counts as no token.
+pub fn needs_onboarding(settings: &Settings, secrets: &Secrets) -> bool {
+  !settings.onboarding_complete
+    && !matches!(
+      secrets.load_token_at(profiles::token_account(settings)),
+      Ok(Some(_))
+    )
+}
+
+pub fn complete(config: &Config) -> Result<(), BridgeError> {
+  if !config.get().onboarding_complete {
+    config.update(|settings| settings.onboarding_complete = true)?;
+  }
+  Ok(())
+}
+
+/// Called from `setup` once `Config` and `Secrets` are managed.
+pub fn announce(app: &AppHandle) {
+  let (Some(config), Some(secrets)) = (app.try_state::<Config>(), app.try_state::<Secrets>())
+  else {
+    return;
+  };
+  if needs_onboarding(&config.get(), &secrets) {
+    let _ = app.emit(NEEDS_ONBOARDING_EVENT, ());
+  }
+}
+
+#[allow(non_snake_case)]
+#[tauri::command]
+pub fn completeOnboarding(config: tauri::State<'_, Config>) -> Result<(), BridgeError> {
+  complete(&config)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::secrets::mock::MemoryStore;
+  use crate::secrets::StoredToken;
+
+  fn token() -> StoredToken {
+    StoredToken {
+      base_url: "https://cp.example.com".to_string(),
+      token: "abcDEF123_token_value".to_string(),
+      expires_at: None,
+    }
+  }
+
+  #[test]
+  fn a_fresh_install_needs_onboarding_until_a_token_or_the_flag_is_set() {
+    let secrets = Secrets::new(Box::new(MemoryStore::default()));
+    let mut settings = Settings::default();
+    assert!(needs_onboarding(&settings, &secrets));
+
+    settings.onboarding_complete = true;
+    assert!(!needs_onboarding(&settings, &secrets));
+
+    settings.onboarding_complete = false;
+    secrets.store_token(&token()).unwrap();
+    assert!(!needs_onboarding(&settings, &secrets));
+  }
+
+  #[test]
+  fn completing_is_saved_and_idempotent() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("settings.json");
+    let config = Config::load(&path);
+    assert!(!config.get().onboarding_complete);
+
+    complete(&config).unwrap();
+    complete(&config).unwrap();
+
+    assert!(Config::load(&path).get().onboarding_complete);
+  }
+}
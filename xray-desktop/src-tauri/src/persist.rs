@@ -0,0 +1,169 @@
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+fn sibling(path: &Path, suffix: &str) -> PathBuf {
+  let mut name = path.file_name().unwrap_or_default().to_os_string();
+  name.push(suffix);
+  path.with_file_name(name)
+}
+
+pub fn temp_path(path: &Path) -> PathBuf {
+  sibling(path, ".tmp")
+}
+
+/// The previous contents of `path`, kept by `atomic_write`.
+pub fn backup_path(path: &Path) -> PathBuf {
+  sibling(path, ".bak")
+}
+
+/// Writes a synced sibling temp file and renames it over `path`, so a crash or power loss
+/// mid-write leaves the old file rather than a truncated one. The old file is copied to `.bak`
+/// first, for `load` to fall back on if `path` turns out corrupt anyway.
+pub fn atomic_write(path: &Path, bytes: impl AsRef<[u8]>) -> io::Result<()> {
+  write_replacing(path, bytes.as_ref(), false)
+}
+
+/// `atomic_write` for files holding secrets: readable by the owner only on unix (0600), and
+/// with no `.bak` left behind holding the previous ones.
+pub fn atomic_write_private(path: &Path, bytes: impl AsRef<[u8]>) -> io::Result<()> {
+  write_replacing(path, bytes.as_ref(), true)
+}
+
+fn write_replacing(path: &Path, bytes: &[u8], private: bool) -> io::Result<()> {
+  if let Some(dir) = path.parent() {
+    fs::create_dir_all(dir)?;
+  }
+  let temp = temp_path(path);
+  let mut options = fs::OpenOptions::new();
+  options.write(true).truncate(true).create(true);
+  #[cfg(unix)]
+  if private {
+    use std::os::unix::fs::OpenOptionsExt;
+    // The mode only applies to a file being created, so don't reuse a stale one.
+    let _ = fs::remove_file(&temp);
+    options.mode(0o600);
+  }
+  let mut file = options.open(&temp)?;
+  file.write_all(bytes)?;
+  file.sync_all()?;
+  drop(file);
+  if path.is_file() && !private {
+    fs::copy(path, backup_path(path))?;
+  }
+  fs::rename(&temp, path)?;
+  // Makes the rename itself durable; Windows can't open directories this way.
+  #[cfg(unix)]
+  if let Some(dir) = path.parent() {
+    let _ = File::open(dir).and_then(|dir| dir.sync_all());
+  }
+  Ok(())
+}
+
+/// Parses `path`, or its backup when `path` is missing, unreadable or rejected by `parse`.
+pub fn load<T>(path: &Path, parse: impl Fn(&str) -> Option<T>) -> Option<T> {
+  if let Some(parsed) = fs::read_to_string(path).ok().and_then(|raw| parse(&raw)) {
+    return Some(parsed);
+  }
+  let recovered = fs::read_to_string(backup_path(path))
+    .ok()
+    .and_then(|raw| parse(&raw))?;
+  tracing::warn!(path = %path.display(), "recovered from the backup copy");
+  Some(recovered)
+}
+
+/// Deletes `path` and its backup, so nothing comes back on the next `load`. Returns whether
+/// `path` existed.
+pub fn remove(path: &Path) -> io::Result<bool> {
+  let removed = match fs::remove_file(path) {
+    Ok(()) => true,
+    Err(error) if error.kind() == io::ErrorKind::NotFound => false,
+    Err(error) => return Err(error),
+  };
+  match fs::remove_file(backup_path(path)) {
+    Err(error) if error.kind() != io::ErrorKind::NotFound => Err(error),
+    _ => Ok(removed),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn parse_json(raw: &str) -> Option<serde_json::Value> {
+    serde_json::from_str(raw).ok()
+  }
+
+  #[test]
+  fn interrupted_write_leaves_the_previous_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("state.json");
+    atomic_write(&path, r#"{ "v": 1 }"#).unwrap();
+
+    // A crash after half the temp file was written, before the rename.
+    fs::write(temp_path(&path), r#"{ "v": "#).unwrap();
+
+    assert_eq!(load(&path, parse_json), Some(serde_json::json!({ "v": 1 })));
+    atomic_write(&path, r#"{ "v": 2 }"#).unwrap();
+    assert_eq!(load(&path, parse_json), Some(serde_json::json!({ "v": 2 })));
+    assert!(!temp_path(&path).exists());
+  }
+
+  #[test]
+  fn corrupt_file_is_recovered_from_the_backup() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("state.json");
+    atomic_write(&path, r#"{ "v": 1 }"#).unwrap();
+    atomic_write(&path, r#"{ "v": 2 }"#).unwrap();
+    assert_eq!(
+      fs::read_to_string(backup_path(&path)).unwrap(),
+      r#"{ "v": 1 }"#
+    );
+
+    // Truncated behind our back, e.g. by a disk that lied about the sync.
+    fs::write(&path, r#"{ "v"#).unwrap();
+    assert_eq!(load(&path, parse_json), Some(serde_json::json!({ "v": 1 })));
+
+    fs::remove_file(&path).unwrap();
+    assert_eq!(load(&path, parse_json), Some(serde_json::json!({ "v": 1 })));
+  }
+
+  #[test]
+  fn nothing_to_load_without_either_copy() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("state.json");
+    fs::write(&path, "{ not json").unwrap();
+
+    assert_eq!(load(&path, parse_json), None);
+  }
+
+  #[cfg(unix)]
+  #[test]
+  fn private_writes_are_owner_only_without_a_backup() {
+    use std::os::unix::fs::PermissionsExt;
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("secrets.json");
+    atomic_write_private(&path, "1").unwrap();
+    atomic_write_private(&path, "2").unwrap();
+
+    assert_eq!(fs::read_to_string(&path).unwrap(), "2");
+    assert_eq!(
+      fs::metadata(&path).unwrap().permissions().mode() & 0o777,
+      0o600
+    );
+    assert!(!backup_path(&path).exists());
+  }
+
+  #[test]
+  fn remove_takes_the_backup_too() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("state.json");
+    atomic_write(&path, "1").unwrap();
+    atomic_write(&path, "2").unwrap();
+
+    assert!(remove(&path).unwrap());
+    assert_eq!(load(&path, parse_json), None);
+    assert!(!backup_path(&path).exists());
+    assert!(!remove(&path).unwrap());
+  }
+}
@@ -0,0 +1,306 @@
+use crate::error::BridgeError;
+use crate::mode::Mode;
+use serde::Serialize;
+
+/// From `linux/capability.h`.
+const CAP_NET_ADMIN: u32 = 12;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PrivilegeStatus {
+  /// Root, or an elevated administrator token on Windows.
+  pub elevated: bool,
+  /// Whether `vpn` mode can bring its TUN device up. Also true when the check itself failed:
+  /// then the agent gets to try, and `detail` says the check was inconclusive.
+  pub can_create_tun: bool,
+  pub detail: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Platform {
+  Linux,
+  MacOs,
+  Windows,
+  Other,
+}
+
+impl Platform {
+  pub fn current() -> Self {
+    if cfg!(target_os = "linux") {
+      Self::Linux
+    } else if cfg!(target_os = "macos") {
+      Self::MacOs
+    } else if cfg!(windows) {
+      Self::Windows
+    } else {
+      Self::Other
+    }
+  }
+}
+
+/// What the platform checks found. `None` where a check doesn't apply or couldn't run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Probe {
+  pub platform: Platform,
+  /// Effective uid 0.
+  pub root: Option<bool>,
+  /// `CAP_NET_ADMIN` in this process's effective set, Linux only. The bridge is a separate
+  /// `execve` and doesn't inherit it unless it's ambient, so it proves nothing on its own.
+  pub net_admin: Option<bool>,
+  /// The process token is elevated, Windows only.
+  pub admin_token: Option<bool>,
+}
+
+impl Probe {
+  fn new(platform: Platform) -> Self {
+    Self {
+      platform,
+      root: None,
+      net_admin: None,
+      admin_token: None,
+    }
+  }
+}
+
+/// `(root, CAP_NET_ADMIN)` from `/proc/self/status`, by the effective uid and `CapEff`.
+pub fn parse_proc_status(status: &str) -> (Option<bool>, Option<bool>) {
+  let field = |name: &str| {
+    status
+      .lines()
+      .find_map(|line| line.strip_prefix(name)?.strip_prefix(':'))
+      .map(str::trim)
+  };
+  let root = field("Uid")
+    .and_then(|ids| ids.split_whitespace().nth(1))
+    .and_then(|euid| euid.parse::<u32>().ok())
+    .map(|euid| euid == 0);
+  let net_admin = field("CapEff")
+    .and_then(|caps| u64::from_str_radix(caps, 16).ok())
+    .map(|caps| caps & (1 << CAP_NET_ADMIN) != 0);
+  (root, net_admin)
+}
+
+fn succeeds(program: &str, args: &[&str]) -> Option<bool> {
+  let mut command = std::process::Command::new(program);
+  command
+    .args(args)
+    .stdin(std::process::Stdio::null())
+    .stdout(std::process::Stdio::null())
+    .stderr(std::process::Stdio::null());
+  #[cfg(windows)]
+  {
+    use std::os::windows::process::CommandExt;
+    command.creation_flags(crate::bridge::CREATE_NO_WINDOW);
+  }
+  command.status().ok().map(|status| status.success())
+}
+
+fn effective_uid() -> Option<u32> {
+  let output = std::process::Command::new("id").arg("-u").output().ok()?;
+  String::from_utf8_lossy(&output.stdout).trim().parse().ok()
+}
+
+/// Runs this platform's checks.
+pub fn probe() -> Probe {
+  let mut probe = Probe::new(Platform::current());
+  match probe.platform {
+    Platform::Linux => {
+      if let Ok(status) = std::fs::read_to_string("/proc/self/status") {
+        (probe.root, probe.net_admin) = parse_proc_status(&status);
+      }
+    }
+    // `net session` only succeeds for an elevated administrator.
+    Platform::Windows => probe.admin_token = succeeds("net", &["session"]),
+    Platform::MacOs | Platform::Other => probe.root = effective_uid().map(|uid| uid == 0),
+  }
+  probe
+}
+
+fn inconclusive(what: &str) -> PrivilegeStatus {
+  PrivilegeStatus {
+    elevated: false,
+    can_create_tun: true,
+    detail: format!("Couldn't check {what}; vpn mode will be attempted anyway"),
+  }
+}
+
+pub fn decide(probe: &Probe) -> PrivilegeStatus {
+  let status = |elevated, can_create_tun, detail: &str| PrivilegeStatus {
+    elevated,
+    can_create_tun,
+    detail: detail.to_string(),
+  };
+  match probe.platform {
+    Platform::Windows => match probe.admin_token {
+      Some(true) => status(true, true, "Running as administrator"),
+      Some(false) => status(
+        false,
+        false,
+        "Creating the TUN adapter needs administrator rights: restart the app with \"Run as \
+         administrator\", or use proxy mode",
+      ),
+      None => inconclusive("for administrator rights"),
+    },
+    Platform::Linux => match (probe.root, probe.net_admin) {
+      (Some(true), _) => status(true, true, "Running as root"),
+      (_, Some(true)) => inconclusive("whether the bridge keeps the app's CAP_NET_ADMIN"),
+      (Some(false), Some(false)) => status(
+        false,
+        false,
+        "Creating a TUN device needs root: start the app with sudo, or use proxy mode",
+      ),
+      _ => inconclusive("for root"),
+    },
+    Platform::MacOs | Platform::Other => match probe.root {
+      Some(true) => status(true, true, "Running as root"),
+      Some(false) => status(
+        false,
+        false,
+        "Creating a utun device needs root: start the app with sudo, or use proxy mode",
+      ),
+      None => inconclusive("for root"),
+    },
+  }
+}
+
+pub fn current() -> PrivilegeStatus {
+  decide(&probe())
+}
+
+/// Only `vpn` mode needs the TUN device. An unset mode is left to the agent, which keeps
+/// whatever mode it was in.
+pub fn ensure(mode: Option<Mode>, status: &PrivilegeStatus) -> Result<(), BridgeError> {
+  if mode == Some(Mode::Vpn) && !status.can_create_tun {
+    return Err(BridgeError::InsufficientPrivileges(status.detail.clone()));
+  }
+  Ok(())
+}
+
+/// Checked by `commands::connect_with`, so no path into `connect` (the tray, the CLI, schedules,
+/// a restored session) lets missing rights surface as whatever the agent makes of a TUN device it
+/// couldn't open.
+pub fn require_for(mode: Option<Mode>) -> Result<(), BridgeError> {
+  if mode != Some(Mode::Vpn) {
+    return Ok(());
+  }
+  let result = ensure(mode, &current());
+  if let Err(error) = &result {
+    tracing::warn!("connect refused: {error}");
+  }
+  result
+}
+
+#[allow(non_snake_case)]
+#[tauri::command]
+pub fn checkPrivileges() -> PrivilegeStatus {
+  current()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn probe(platform: Platform) -> Probe {
+    Probe::new(platform)
+  }
+
+  #[test]
+  fn reads_root_and_net_admin_from_proc_status() {
+    let user = "Name:\txray-desktop\nUid:\t1000\t1000\t1000\t1000\nCapEff:\t0000000000000000\n";
+    let capable = "Uid:\t1000\t1000\t1000\t1000\nCapEff:\t0000000000001000\n";
+    let root = "Uid:\t1000\t0\t0\t0\nCapEff:\t000001ffffffffff\n";
+
+    assert_eq!(parse_proc_status(user), (Some(false), Some(false)));
+    assert_eq!(parse_proc_status(capable), (Some(false), Some(true)));
+    assert_eq!(parse_proc_status(root), (Some(true), Some(true)));
+    assert_eq!(parse_proc_status("Name:\tx\n"), (None, None));
+  }
+
+  #[test]
+  fn linux_needs_root_or_net_admin() {
+    let decide_linux = |root, net_admin| {
+      let status = decide(&Probe {
+        root,
+        net_admin,
+        ..probe(Platform::Linux)
+      });
+      (status.elevated, status.can_create_tun)
+    };
+
+    assert_eq!(decide_linux(Some(true), Some(false)), (true, true));
+    assert_eq!(decide_linux(Some(false), Some(true)), (false, true));
+    assert_eq!(decide_linux(Some(false), Some(false)), (false, false));
+    assert_eq!(decide_linux(None, None), (false, true));
+  }
+
+  #[test]
+  fn linux_capability_alone_is_inconclusive() {
+    let capable = decide(&Probe {
+      root: Some(false),
+      net_admin: Some(true),
+      ..probe(Platform::Linux)
+    });
+    let denied = decide(&Probe {
+      root: Some(false),
+      net_admin: Some(false),
+      ..probe(Platform::Linux)
+    });
+
+    assert!(capable.can_create_tun);
+    assert!(capable.detail.contains("attempted anyway"));
+    assert!(!denied.detail.contains("setcap"));
+  }
+
+  #[test]
+  fn windows_needs_an_elevated_token() {
+    let elevated = decide(&Probe {
+      admin_token: Some(true),
+      ..probe(Platform::Windows)
+    });
+    let limited = decide(&Probe {
+      admin_token: Some(false),
+      // A uid means nothing on Windows.
+      root: Some(true),
+      ..probe(Platform::Windows)
+    });
+
+    assert!(elevated.elevated && elevated.can_create_tun);
+    assert!(!limited.elevated && !limited.can_create_tun);
+    assert!(limited.detail.contains("Run as administrator"));
+  }
+
+  #[test]
+  fn macos_needs_root() {
+    let user = decide(&Probe {
+      root: Some(false),
+      ..probe(Platform::MacOs)
+    });
+
+    assert!(!user.can_create_tun);
+    assert!(user.detail.contains("sudo"));
+    assert!(
+      decide(&Probe {
+        root: Some(true),
+        ..probe(Platform::MacOs)
+      })
+      .can_create_tun
+    );
+    assert!(decide(&probe(Platform::MacOs)).can_create_tun);
+  }
+
+  #[test]
+  fn only_vpn_mode_is_refused() {
+    let denied = decide(&Probe {
+      root: Some(false),
+      net_admin: Some(false),
+      ..probe(Platform::Linux)
+    });
+
+    let error = ensure(Some(Mode::Vpn), &denied).unwrap_err();
+    assert_eq!(error.kind(), "insufficientPrivileges");
+    assert!(error.to_string().contains("needs root"));
+    assert!(ensure(Some(Mode::Proxy), &denied).is_ok());
+    assert!(ensure(None, &denied).is_ok());
+    assert!(ensure(Some(Mode::Vpn), &inconclusive("for root")).is_ok());
+  }
+}
@@ -0,0 +1,460 @@
+use crate::base_url::normalize_base_url;
+use crate::bridge::{Bridge, NodeBridgeRunner, ProxyEnv};
+use crate::commands;
+use crate::config::{Config, Settings};
+use crate::error::BridgeError;
+use crate::reconnect::Watchdog;
+use crate::secrets::{Secrets, StoredToken, TOKEN_ACCOUNT};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// A saved account. The token lives in the keyring under `token_ref`, never in `settings.json`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Profile {
+  pub id: String,
+  pub name: String,
+  pub base_url: String,
+  pub token_ref: String,
+  /// Per-account proxy overrides for the bridge process; see `ProxyEnv`.
+  #[serde(default)]
+  pub proxy: ProxyEnv,
+}
+
+/// The profile `connect` uses, if one is active and still saved.
+pub fn active(settings: &Settings) -> Option<&Profile> {
+  let id = settings.active_profile_id.as_deref()?;
+  settings.profiles.iter().find(|profile| profile.id == id)
+}
+
+/// Keyring account holding the token `connect` sends.
+pub fn token_account(settings: &Settings) -> &str {
+  active(settings).map_or(TOKEN_ACCOUNT, |profile| &profile.token_ref)
+}
+
+/// Proxy overrides of the active profile, empty when no profile is active.
+pub fn proxy_env(settings: &Settings) -> ProxyEnv {
+  active(settings)
+    .map(|profile| profile.proxy.clone())
+    .unwrap_or_default()
+}
+
+fn new_id(existing: &[Profile]) -> String {
+  let mut stamp = SystemTime::now()
+    .duration_since(UNIX_EPOCH)
+    .map(|elapsed| elapsed.as_millis())
+    .unwrap_or_default();
+  // Two profiles created within the same millisecond still get distinct ids.
+  while existing
+    .iter()
+    .any(|profile| profile.id == format!("{stamp:x}"))
+  {
+    stamp += 1;
+  }
+  format!("{stamp:x}")
+}
+
+/// Saves the token in the keyring and the profile in settings. The first profile becomes active.
+pub fn create(
+  secrets: &Secrets,
+  config: &Config,
+  name: String,
+  base_url: String,
+  token: String,
+) -> Result<Profile, BridgeError> {
+  let base_url = normalize_base_url(&base_url, config.get().allow_insecure_base_url)?;
+  let id = new_id(&config.get().profiles);
+  let profile = Profile {
+    token_ref: format!("profile:{id}"),
+    id,
+    name,
+    base_url: base_url.clone(),
+    proxy: ProxyEnv::default(),
+  };
+  let stored = StoredToken {
+    base_url,
+    token,
+    expires_at: None,
+  };
+  secrets.store_token_at(&profile.token_ref, &stored)?;
+
+  let saved = config.update(|settings| {
+    if settings.active_profile_id.is_none() {
+      settings.active_profile_id = Some(profile.id.clone());
+    }
+    settings.profiles.push(profile.clone());
+  });
+  if let Err(error) = saved {
+    // Don't leave a token behind that no profile points at.
+    let _ = secrets.clear_token_at(&profile.token_ref);
+    return Err(error);
+  }
+  Ok(profile)
+}
+
+/// Removes the profile and its keyring entry. Deleting the active profile leaves none active,
+/// so `connect` falls back to the token from `storeToken`.
+pub fn delete(secrets: &Secrets, config: &Config, id: &str) -> Result<(), BridgeError> {
+  let Some(profile) = config
+    .get()
+    .profiles
+    .into_iter()
+    .find(|profile| profile.id == id)
+  else {
+    return Err(BridgeError::UnknownProfile { id: id.to_string() });
+  };
+
+  config.update(|settings| {
+    settings.profiles.retain(|profile| profile.id != id);
+    if settings.active_profile_id.as_deref() == Some(id) {
+      settings.active_profile_id = None;
+    }
+  })?;
+  let _ = secrets.clear_token_at(&profile.token_ref);
+  Ok(())
+}
+
+/// Replaces the profile's proxy overrides. They reach the bridge the next time it is spawned.
+pub fn set_proxy(config: &Config, id: &str, proxy: ProxyEnv) -> Result<Profile, BridgeError> {
+  let mut updated = None;
+  config.update(|settings| {
+    if let Some(profile) = settings
+      .profiles
+      .iter_mut()
+      .find(|profile| profile.id == id)
+    {
+      profile.proxy = proxy;
+      updated = Some(profile.clone());
+    }
+  })?;
+  updated.ok_or_else(|| BridgeError::UnknownProfile { id: id.to_string() })
+}
+
+/// Makes `id` the active profile. A live tunnel belongs to the old account, so it is torn down
+/// first; `use_proxy` then gets the new profile's proxy overrides, and the agent its config,
+/// before anything is saved.
+pub async fn switch(
+  bridge: &Bridge,
+  secrets: &Secrets,
+  config: &Config,
+  watchdog: &Watchdog,
+  id: String,
+  use_proxy: impl FnOnce(&ProxyEnv),
+) -> Result<(), BridgeError> {
+  let Some(profile) = config
+    .get()
+    .profiles
+    .into_iter()
+    .find(|profile| profile.id == id)
+  else {
+    return Err(BridgeError::UnknownProfile { id });
+  };
+
+  if commands::status(bridge).await?.connected {
+    commands::disconnect(bridge, watchdog).await?;
+  }
+  use_proxy(&profile.proxy);
+  if let Some(stored) = secrets.load_token_at(&profile.token_ref)? {
+    bridge
+      .call(
+        "importToken",
+        json!({ "baseUrl": profile.base_url, "token": stored.token }),
+      )
+      .await?;
+  }
+  config.update(|settings| settings.active_profile_id = Some(id))?;
+  Ok(())
+}
+
+#[allow(non_snake_case)]
+#[tauri::command]
+pub fn listProfiles(config: tauri::State<'_, Config>) -> Vec<Profile> {
+  config.get().profiles
+}
+
+#[allow(non_snake_case)]
+#[tauri::command]
+pub fn createProfile(
+  secrets: tauri::State<'_, Secrets>,
+  config: tauri::State<'_, Config>,
+  name: String,
+  baseUrl: String,
+  token: String,
+) -> Result<Profile, BridgeError> {
+  create(&secrets, &config, name, baseUrl, token)
+}
+
+#[allow(non_snake_case)]
+#[tauri::command]
+pub fn deleteProfile(
+  secrets: tauri::State<'_, Secrets>,
+  config: tauri::State<'_, Config>,
+  id: String,
+) -> Result<(), BridgeError> {
+  delete(&secrets, &config, &id)
+}
+
+#[allow(non_snake_case)]
+#[tauri::command]
+pub fn setProfileProxy(
+  config: tauri::State<'_, Config>,
+  id: String,
+  proxy: ProxyEnv,
+) -> Result<Profile, BridgeError> {
+  set_proxy(&config, &id, proxy)
+}
+
+#[allow(non_snake_case)]
+#[tauri::command]
+pub async fn switchProfile(
+  bridge: tauri::State<'_, Bridge>,
+  node: tauri::State<'_, NodeBridgeRunner>,
+  secrets: tauri::State<'_, Secrets>,
+  config: tauri::State<'_, Config>,
+  watchdog: tauri::State<'_, Watchdog>,
+  id: String,
+) -> Result<(), BridgeError> {
+  let node = node.inner().clone();
+  switch(&bridge, &secrets, &config, &watchdog, id, |proxy| {
+    // The tunnel is down at this point, so restarting node to pick up the proxy is safe.
+    node.set_proxy_env(proxy.clone());
+    node.stop();
+  })
+  .await
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::bridge::mock::MockBridgeRunner;
+  use crate::secrets::mock::MemoryStore;
+  use crate::status::LinkState;
+  use serde_json::Value;
+  use tauri::async_runtime::block_on;
+
+  fn secrets() -> Secrets {
+    Secrets::new(Box::new(MemoryStore::default()))
+  }
+
+  fn create_two(secrets: &Secrets, config: &Config) -> (Profile, Profile) {
+    let work = create(
+      secrets,
+      config,
+      "Work".to_string(),
+      "https://cp.work.example".to_string(),
+      "work_token_value".to_string(),
+    )
+    .unwrap();
+    let home = create(
+      secrets,
+      config,
+      "Home".to_string(),
+      "https://cp.home.example".to_string(),
+      "home_token_value".to_string(),
+    )
+    .unwrap();
+    (work, home)
+  }
+
+  #[test]
+  fn create_and_delete_round_trip() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("settings.json");
+    let secrets = secrets();
+
+    let (work, home) = create_two(&secrets, &Config::load(&path));
+
+    assert_ne!(work.id, home.id);
+    let settings = Config::load(&path).get();
+    assert_eq!(settings.profiles, vec![work.clone(), home.clone()]);
+    assert_eq!(
+      active(&settings),
+      Some(&work),
+      "the first profile becomes active"
+    );
+    assert_eq!(
+      secrets
+        .load_token_at(&home.token_ref)
+        .unwrap()
+        .map(|stored| stored.token),
+      Some("home_token_value".to_string())
+    );
+    assert!(!std::fs::read_to_string(&path)
+      .unwrap()
+      .contains("home_token_value"));
+
+    delete(&secrets, &Config::load(&path), &work.id).unwrap();
+
+    let settings = Config::load(&path).get();
+    assert_eq!(settings.profiles, vec![home]);
+    assert_eq!(settings.active_profile_id, None);
+    assert_eq!(secrets.load_token_at(&work.token_ref).unwrap(), None);
+    assert_eq!(
+      delete(&secrets, &Config::load(&path), &work.id),
+      Err(BridgeError::UnknownProfile { id: work.id })
+    );
+  }
+
+  #[test]
+  fn switching_while_connected_disconnects_before_importing() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = Config::load(dir.path().join("settings.json"));
+    let secrets = secrets();
+    let (_, home) = create_two(&secrets, &config);
+    let mock = MockBridgeRunner::default()
+      .respond("status", Ok(json!({ "connected": true })))
+      .respond("disconnect", Ok(Value::Null))
+      .respond("importToken", Ok(Value::Null));
+    let bridge = Bridge::new(mock.clone());
+    let watchdog = Watchdog::default();
+
+    block_on(switch(
+      &bridge,
+      &secrets,
+      &config,
+      &watchdog,
+      home.id.clone(),
+      |_| {},
+    ))
+    .unwrap();
+
+    assert_eq!(
+      mock.calls(),
+      [
+        ("status".to_string(), Value::Null),
+        ("disconnect".to_string(), Value::Null),
+        (
+          "importToken".to_string(),
+          json!({ "baseUrl": "https://cp.home.example", "token": "home_token_value" })
+        ),
+      ]
+    );
+    assert_eq!(active(&config.get()), Some(&home));
+    assert!(
+      !watchdog.should_reconnect(LinkState::Connected, LinkState::Disconnected, true),
+      "the switch's disconnect is not a drop"
+    );
+  }
+
+  #[test]
+  fn switching_applies_the_new_proxy_once_the_tunnel_is_down() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = Config::load(dir.path().join("settings.json"));
+    let secrets = secrets();
+    let (_, home) = create_two(&secrets, &config);
+    let proxy = ProxyEnv {
+      https_proxy: Some("http://home-proxy:8080".to_string()),
+      ..ProxyEnv::default()
+    };
+    assert_eq!(
+      set_proxy(&config, &home.id, proxy.clone()).unwrap().proxy,
+      proxy
+    );
+    let mock = MockBridgeRunner::default()
+      .respond("status", Ok(json!({ "connected": true })))
+      .respond("disconnect", Ok(Value::Null))
+      .respond("importToken", Ok(Value::Null));
+    let bridge = Bridge::new(mock.clone());
+    let mut applied = None;
+
+    block_on(switch(
+      &bridge,
+      &secrets,
+      &config,
+      &Watchdog::default(),
+      home.id.clone(),
+      |proxy| applied = Some((proxy.clone(), mock.calls().len())),
+    ))
+    .unwrap();
+
+    assert_eq!(
+      applied,
+      Some((proxy.clone(), 2)),
+      "after status and disconnect, before importToken"
+    );
+    assert_eq!(proxy_env(&config.get()), proxy);
+    assert_eq!(
+      set_proxy(&config, "gone", ProxyEnv::default()),
+      Err(BridgeError::UnknownProfile {
+        id: "gone".to_string()
+      })
+    );
+  }
+
+  #[test]
+  fn switching_while_disconnected_skips_the_disconnect() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = Config::load(dir.path().join("settings.json"));
+    let secrets = secrets();
+    let (_, home) = create_two(&secrets, &config);
+    let mock = MockBridgeRunner::default()
+      .respond("status", Ok(json!({ "connected": false })))
+      .respond("importToken", Ok(Value::Null));
+    let bridge = Bridge::new(mock.clone());
+
+    block_on(switch(
+      &bridge,
+      &secrets,
+      &config,
+      &Watchdog::default(),
+      home.id.clone(),
+      |_| {},
+    ))
+    .unwrap();
+
+    let actions: Vec<String> = mock.calls().into_iter().map(|(action, _)| action).collect();
+    assert_eq!(actions, ["status", "importToken"]);
+    assert_eq!(active(&config.get()), Some(&home));
+  }
+
+  #[test]
+  fn failed_disconnect_keeps_the_old_profile_active() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = Config::load(dir.path().join("settings.json"));
+    let secrets = secrets();
+    let (work, home) = create_two(&secrets, &config);
+    let failure = BridgeError::Timeout(std::time::Duration::from_secs(30));
+    let bridge = Bridge::new(
+      MockBridgeRunner::default()
+        .respond("status", Ok(json!({ "connected": true })))
+        .respond("disconnect", Err(failure.clone())),
+    );
+
+    assert_eq!(
+      block_on(switch(
+        &bridge,
+        &secrets,
+        &config,
+        &Watchdog::default(),
+        home.id,
+        |_| panic!("the old tunnel is still up"),
+      )),
+      Err(failure)
+    );
+    assert_eq!(active(&config.get()), Some(&work));
+  }
+
+  #[test]
+  fn switching_to_an_unknown_profile_touches_nothing() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = Config::load(dir.path().join("settings.json"));
+    let mock = MockBridgeRunner::default();
+    let bridge = Bridge::new(mock.clone());
+
+    assert_eq!(
+      block_on(switch(
+        &bridge,
+        &secrets(),
+        &config,
+        &Watchdog::default(),
+        "gone".to_string(),
+        |_| {},
+      )),
+      Err(BridgeError::UnknownProfile {
+        id: "gone".to_string()
+      })
+    );
+    assert!(mock.calls().is_empty());
+  }
+}
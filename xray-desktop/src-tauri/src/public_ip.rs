@@ -0,0 +1,201 @@
+use crate::bridge::Bridge;
+use crate::commands;
+use crate::error::BridgeError;
+use crate::status::{LinkState, StatusSnapshot};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// How long a lookup is reused while the tunnel stays in the same state.
+const CACHE_TTL: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PublicIpResult {
+  pub ip: String,
+  pub country: Option<String>,
+  pub through_tunnel: bool,
+}
+
+/// The bridge `publicIp` answer: what an external service sees us as.
+#[derive(Debug, Clone, Deserialize)]
+struct Lookup {
+  ip: String,
+  #[serde(default)]
+  country: Option<String>,
+}
+
+/// The address counts as the tunnel's while connected (not paused) and it matches the exit IP the
+/// agent reports, if it reports one.
+fn shape(lookup: Lookup, status: &StatusSnapshot) -> PublicIpResult {
+  let through_tunnel = status.state() == LinkState::Connected
+    && status
+      .public_ip
+      .as_deref()
+      .is_none_or(|tunnel_ip| tunnel_ip == lookup.ip);
+  PublicIpResult {
+    ip: lookup.ip,
+    country: lookup.country,
+    through_tunnel,
+  }
+}
+
+/// The last answer with the tunnel state and server it was looked up under; a change of either
+/// makes it stale.
+#[derive(Default)]
+pub struct PublicIpCache {
+  last: Mutex<Option<Cached>>,
+}
+
+struct Cached {
+  looked_up: Instant,
+  state: LinkState,
+  server_id: Option<String>,
+  result: PublicIpResult,
+}
+
+impl PublicIpCache {
+  pub fn clear(&self) {
+    self.last.lock().unwrap().take();
+  }
+
+  fn fresh(&self, status: &StatusSnapshot, now: Instant) -> Option<PublicIpResult> {
+    match &*self.last.lock().unwrap() {
+      Some(cached)
+        if now.duration_since(cached.looked_up) < CACHE_TTL
+          && cached.state == status.state()
+          && cached.server_id == status.server_id =>
+      {
+        Some(cached.result.clone())
+      }
+      _ => None,
+    }
+  }
+
+  fn store(&self, status: &StatusSnapshot, result: PublicIpResult, now: Instant) {
+    *self.last.lock().unwrap() = Some(Cached {
+      looked_up: now,
+      state: status.state(),
+      server_id: status.server_id.clone(),
+      result,
+    });
+  }
+}
+
+/// Works either way: while disconnected it reports the real address with `through_tunnel: false`.
+pub async fn lookup(bridge: &Bridge, cache: &PublicIpCache) -> Result<PublicIpResult, BridgeError> {
+  let status = commands::status(bridge).await?;
+  if let Some(result) = cache.fresh(&status, Instant::now()) {
+    return Ok(result);
+  }
+  let lookup: Lookup = serde_json::from_value(bridge.call("publicIp", Value::Null).await?)
+    .map_err(|error| BridgeError::InvalidJson(error.to_string()))?;
+  let result = shape(lookup, &status);
+  cache.store(&status, result.clone(), Instant::now());
+  Ok(result)
+}
+
+#[allow(non_snake_case)]
+#[tauri::command]
+pub async fn getPublicIp(
+  bridge: tauri::State<'_, Bridge>,
+  cache: tauri::State<'_, PublicIpCache>,
+) -> Result<PublicIpResult, BridgeError> {
+  lookup(&bridge, &cache).await
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::bridge::mock::MockBridgeRunner;
+  use serde_json::json;
+  use tauri::async_runtime::block_on;
+
+  fn bridge(status: Value) -> (MockBridgeRunner, Bridge) {
+    let mock = MockBridgeRunner::default()
+      .respond("status", Ok(status))
+      .respond(
+        "publicIp",
+        Ok(json!({ "ip": "203.0.113.7", "country": "NL" })),
+      );
+    (mock.clone(), Bridge::new(mock))
+  }
+
+  fn lookups(mock: &MockBridgeRunner) -> usize {
+    mock
+      .calls()
+      .iter()
+      .filter(|(action, _)| action == "publicIp")
+      .count()
+  }
+
+  #[test]
+  fn connected_lookup_matching_the_exit_ip_is_through_the_tunnel() {
+    let (_, bridge) = bridge(json!({ "connected": true, "publicIp": "203.0.113.7" }));
+
+    assert_eq!(
+      block_on(lookup(&bridge, &PublicIpCache::default())).unwrap(),
+      PublicIpResult {
+        ip: "203.0.113.7".to_string(),
+        country: Some("NL".to_string()),
+        through_tunnel: true,
+      }
+    );
+  }
+
+  #[test]
+  fn disconnected_lookup_still_returns_the_real_ip() {
+    let (_, bridge) = bridge(json!({ "connected": false }));
+
+    let result = block_on(lookup(&bridge, &PublicIpCache::default())).unwrap();
+
+    assert_eq!(result.ip, "203.0.113.7");
+    assert!(!result.through_tunnel);
+  }
+
+  #[test]
+  fn an_ip_other_than_the_exit_ip_or_a_paused_tunnel_is_not_through_it() {
+    let lookup = || Lookup {
+      ip: "198.51.100.1".to_string(),
+      country: None,
+    };
+    let connected = StatusSnapshot {
+      connected: true,
+      public_ip: Some("203.0.113.7".to_string()),
+      ..StatusSnapshot::default()
+    };
+    let paused = StatusSnapshot {
+      connected: true,
+      paused: true,
+      ..StatusSnapshot::default()
+    };
+
+    assert!(!shape(lookup(), &connected).through_tunnel);
+    assert!(!shape(lookup(), &paused).through_tunnel);
+  }
+
+  #[test]
+  fn repeated_checks_in_the_same_state_reuse_the_lookup() {
+    let (mock, bridge) = bridge(json!({ "connected": true }));
+    let cache = PublicIpCache::default();
+
+    block_on(lookup(&bridge, &cache)).unwrap();
+    block_on(lookup(&bridge, &cache)).unwrap();
+
+    assert_eq!(lookups(&mock), 1);
+  }
+
+  #[test]
+  fn a_state_change_bypasses_the_cache() {
+    let cache = PublicIpCache::default();
+    let (_, bridge_up) = bridge(json!({ "connected": true }));
+    block_on(lookup(&bridge_up, &cache)).unwrap();
+
+    let (mock, bridge_down) = bridge(json!({ "connected": false }));
+    let result = block_on(lookup(&bridge_down, &cache)).unwrap();
+
+    assert_eq!(lookups(&mock), 1);
+    assert!(!result.through_tunnel);
+  }
+}
@@ -0,0 +1,183 @@
+use crate::config::Config;
+use crate::deep_link::SCHEME;
+use crate::error::BridgeError;
+use crate::secrets::Secrets;
+use image::{ImageFormat, Luma};
+use qrcode::QrCode;
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+use tauri::Url;
+
+/// Big enough for a phone camera across a desk; the modules scale up to it.
+const MIN_SIZE: u32 = 512;
+
+/// The `xraycp://import?...` link `deep_link::parse_import_link` reads back.
+pub fn import_link(base_url: &str, token: &str) -> String {
+  let mut link = Url::parse(&format!("{SCHEME}://import")).expect("static import link");
+  link
+    .query_pairs_mut()
+    .append_pair("baseUrl", base_url)
+    .append_pair("token", token);
+  link.into()
+}
+
+/// A black on white PNG of `payload`, with the standard quiet zone.
+pub fn encode_png(payload: &str) -> Result<Vec<u8>, BridgeError> {
+  let code =
+    QrCode::new(payload.as_bytes()).map_err(|error| BridgeError::QrExport(error.to_string()))?;
+  let image = code
+    .render::<Luma<u8>>()
+    .min_dimensions(MIN_SIZE, MIN_SIZE)
+    .build();
+  let mut png = Cursor::new(Vec::new());
+  image
+    .write_to(&mut png, ImageFormat::Png)
+    .map_err(|error| BridgeError::QrExport(error.to_string()))?;
+  Ok(png.into_inner())
+}
+
+/// Writes the profile's import link as a QR code to `dest`. With `redact_token` only the base
+/// URL is encoded, for a device that signs in again itself; the keyring isn't read at all.
+pub fn export(
+  config: &Config,
+  secrets: &Secrets,
+  profile_id: &str,
+  dest: &Path,
+  redact_token: bool,
+) -> Result<PathBuf, BridgeError> {
+  let settings = config.get();
+  let profile = settings
+    .profiles
+    .iter()
+    .find(|profile| profile.id == profile_id)
+    .ok_or_else(|| BridgeError::UnknownProfile {
+      id: profile_id.to_string(),
+    })?;
+  let payload = if redact_token {
+    profile.base_url.clone()
+  } else {
+    let stored = secrets.load_token_at(&profile.token_ref)?.ok_or_else(|| {
+      BridgeError::QrExport(format!("no token is saved for profile \"{profile_id}\""))
+    })?;
+    import_link(&profile.base_url, &stored.token)
+  };
+  let png = encode_png(&payload)?;
+  std::fs::write(dest, png)
+    .map_err(|error| BridgeError::QrExport(format!("{}: {error}", dest.display())))?;
+  // The payload is the token in all but name, so only the profile and the path are logged.
+  tracing::info!(profile = profile_id, path = %dest.display(), redact_token, "profile QR exported");
+  Ok(dest.to_path_buf())
+}
+
+#[allow(non_snake_case)]
+#[tauri::command]
+pub fn exportProfileQr(
+  config: tauri::State<'_, Config>,
+  secrets: tauri::State<'_, Secrets>,
+  profileId: String,
+  destPath: String,
+  redactToken: Option<bool>,
+) -> Result<String, BridgeError> {
+  let path = export(
+    &config,
+    &secrets,
+    &profileId,
+    Path::new(&destPath),
+    redactToken.unwrap_or(false),
+  )?;
+  Ok(path.display().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::deep_link::{parse_import_link, ImportLink};
+  use crate::profiles;
+  use crate::qr_import;
+  use crate::secrets::mock::MemoryStore;
+
+  const TOKEN: &str = "abcDEF123_token_value";
+
+  fn with_profile(dir: &tempfile::TempDir) -> (Config, Secrets, String) {
+    let config = Config::load(dir.path().join("settings.json"));
+    let secrets = Secrets::new(Box::new(MemoryStore::default()));
+    let profile = profiles::create(
+      &secrets,
+      &config,
+      "Work".to_string(),
+      "https://cp.example.com/api".to_string(),
+      TOKEN.to_string(),
+    )
+    .unwrap();
+    (config, secrets, profile.id)
+  }
+
+  #[test]
+  fn exported_png_decodes_to_the_import_link() {
+    let dir = tempfile::tempdir().unwrap();
+    let (config, secrets, id) = with_profile(&dir);
+    let dest = dir.path().join("work.png");
+
+    let written = export(&config, &secrets, &id, &dest, false).unwrap();
+
+    assert_eq!(written, dest);
+    let link = qr_import::link_from_image(&dest).unwrap();
+    assert_eq!(
+      link,
+      ImportLink {
+        base_url: "https://cp.example.com/api".to_string(),
+        token: TOKEN.to_string(),
+      }
+    );
+  }
+
+  #[test]
+  fn redacted_export_holds_only_the_base_url() {
+    let dir = tempfile::tempdir().unwrap();
+    let (config, secrets, id) = with_profile(&dir);
+    let dest = dir.path().join("reauth.png");
+
+    export(&config, &secrets, &id, &dest, true).unwrap();
+
+    let payloads = qr_import::decode(&std::fs::read(&dest).unwrap()).unwrap();
+    assert_eq!(payloads, ["https://cp.example.com/api"]);
+  }
+
+  #[test]
+  fn import_link_round_trips_through_the_deep_link_parser() {
+    let link = import_link("https://cp.example.com/a b", "abc-DEF_123_token");
+
+    assert_eq!(
+      parse_import_link(&link).unwrap(),
+      ImportLink {
+        base_url: "https://cp.example.com/a b".to_string(),
+        token: "abc-DEF_123_token".to_string(),
+      }
+    );
+  }
+
+  #[test]
+  fn unknown_profile_or_missing_token_writes_nothing() {
+    let dir = tempfile::tempdir().unwrap();
+    let (config, secrets, id) = with_profile(&dir);
+    let dest = dir.path().join("out.png");
+
+    assert_eq!(
+      export(&config, &secrets, "nope", &dest, false).unwrap_err(),
+      BridgeError::UnknownProfile {
+        id: "nope".to_string()
+      }
+    );
+    let token_ref = config.get().profiles[0].token_ref.clone();
+    secrets.clear_token_at(&token_ref).unwrap();
+    assert_eq!(
+      export(&config, &secrets, &id, &dest, false)
+        .unwrap_err()
+        .kind(),
+      "qrExport"
+    );
+    assert!(!dest.exists());
+    // A redacted code needs no token.
+    export(&config, &secrets, &id, &dest, true).unwrap();
+  }
+}
@@ -0,0 +1,107 @@
+use crate::bridge::Bridge;
+use crate::commands;
+use crate::config::Config;
+use crate::deep_link::{self, ImportLink};
+use crate::error::BridgeError;
+use crate::secrets::Secrets;
+use std::path::Path;
+
+/// Every QR code payload found in the image (PNG or JPEG), in detection order.
+pub fn decode(bytes: &[u8]) -> Result<Vec<String>, BridgeError> {
+  let image = image::load_from_memory(bytes)
+    .map_err(|error| BridgeError::InvalidImage(error.to_string()))?
+    .to_luma8();
+  let mut prepared = rqrr::PreparedImage::prepare_from_greyscale(
+    image.width() as usize,
+    image.height() as usize,
+    |x, y| image.get_pixel(x as u32, y as u32).0[0],
+  );
+
+  let payloads: Vec<String> = prepared
+    .detect_grids()
+    .into_iter()
+    .filter_map(|grid| grid.decode().ok())
+    .map(|(_, content)| content)
+    .collect();
+  if payloads.is_empty() {
+    return Err(BridgeError::QrNotFound);
+  }
+  Ok(payloads)
+}
+
+/// The import link encoded in the image at `path`, checked like a clicked `xraycp://` link.
+pub fn link_from_image(path: &Path) -> Result<ImportLink, BridgeError> {
+  let bytes = std::fs::read(path)
+    .map_err(|error| BridgeError::InvalidImage(format!("{}: {error}", path.display())))?;
+  deep_link::pick_link(&decode(&bytes)?)
+}
+
+#[allow(non_snake_case)]
+#[tauri::command]
+pub async fn importTokenFromImage(
+  bridge: tauri::State<'_, Bridge>,
+  secrets: tauri::State<'_, Secrets>,
+  config: tauri::State<'_, Config>,
+  path: String,
+) -> Result<(), BridgeError> {
+  let link = link_from_image(Path::new(&path))?;
+  commands::import_token(&bridge, &secrets, &config, link.base_url, link.token).await
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::path::PathBuf;
+
+  fn fixture(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+      .join("fixtures")
+      .join(name)
+  }
+
+  #[test]
+  fn reads_the_import_link_from_a_qr_code() {
+    assert_eq!(
+      link_from_image(&fixture("qr-import-link.png")).unwrap(),
+      ImportLink {
+        base_url: "https://cp.example.com".to_string(),
+        token: "abcDEF123_token_value".to_string(),
+      }
+    );
+  }
+
+  #[test]
+  fn qr_code_with_another_payload_is_not_an_import_link() {
+    let bytes = std::fs::read(fixture("qr-other-link.png")).unwrap();
+    assert_eq!(
+      decode(&bytes).unwrap(),
+      ["https://example.com/not-an-import-link"]
+    );
+
+    assert_eq!(
+      link_from_image(&fixture("qr-other-link.png")),
+      Err(BridgeError::InvalidDeepLink(
+        "unsupported deep link scheme".to_string()
+      ))
+    );
+  }
+
+  #[test]
+  fn image_without_a_qr_code_is_reported() {
+    let mut png = std::io::Cursor::new(Vec::new());
+    image::GrayImage::from_pixel(64, 64, image::Luma([255]))
+      .write_to(&mut png, image::ImageFormat::Png)
+      .unwrap();
+
+    assert_eq!(decode(png.get_ref()), Err(BridgeError::QrNotFound));
+  }
+
+  #[test]
+  fn unreadable_files_are_invalid_images() {
+    assert_eq!(decode(b"not an image").unwrap_err().kind(), "invalidImage");
+    assert_eq!(
+      link_from_image(&fixture("missing.png")).unwrap_err().kind(),
+      "invalidImage"
+    );
+  }
+}
@@ -0,0 +1,264 @@
+use crate::stats::TrafficStats;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use tauri::{AppHandle, Emitter, Manager};
+
+pub const QUALITY_CHANGED_EVENT: &str = "vpn://quality-changed";
+/// Stats samples averaged per verdict; at the emitter's default of one a second, ten seconds.
+const WINDOW: usize = 10;
+
+/// A band per level, entered at the tighter bound and only left past the looser one, so a link
+/// hovering at a threshold keeps its verdict.
+struct Band {
+  enter_rtt_ms: f64,
+  enter_loss_pct: f64,
+  leave_rtt_ms: f64,
+  leave_loss_pct: f64,
+}
+
+/// Good needs both metrics under the bound, and leaves once either is over the looser one.
+const GOOD: Band = Band {
+  enter_rtt_ms: 150.0,
+  enter_loss_pct: 1.0,
+  leave_rtt_ms: 200.0,
+  leave_loss_pct: 2.0,
+};
+
+/// Poor takes either metric over the bound, and leaves once both are under the looser one.
+const POOR: Band = Band {
+  enter_rtt_ms: 400.0,
+  enter_loss_pct: 5.0,
+  leave_rtt_ms: 300.0,
+  leave_loss_pct: 3.0,
+};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Quality {
+  Good,
+  Fair,
+  Poor,
+  /// Not connected, or the agent reports neither RTT nor loss.
+  #[default]
+  Unknown,
+}
+
+/// Means over the window; a metric the agent never reported counts as fine.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Metrics {
+  rtt_ms: f64,
+  loss_pct: f64,
+}
+
+fn mean(values: impl Iterator<Item = f64>) -> Option<f64> {
+  let (sum, count) = values.fold((0.0, 0), |(sum, count), value| (sum + value, count + 1));
+  (count > 0).then(|| sum / count as f64)
+}
+
+/// What the window says given the verdict so far.
+fn classify(current: Quality, metrics: Metrics) -> Quality {
+  let Metrics { rtt_ms, loss_pct } = metrics;
+  let enters_poor = rtt_ms >= POOR.enter_rtt_ms || loss_pct >= POOR.enter_loss_pct;
+  let enters_good = rtt_ms <= GOOD.enter_rtt_ms && loss_pct <= GOOD.enter_loss_pct;
+  let leaves_good = rtt_ms > GOOD.leave_rtt_ms || loss_pct > GOOD.leave_loss_pct;
+  let leaves_poor = rtt_ms < POOR.leave_rtt_ms && loss_pct < POOR.leave_loss_pct;
+  match current {
+    Quality::Good if !leaves_good => Quality::Good,
+    Quality::Poor if !leaves_poor => Quality::Poor,
+    _ if enters_poor => Quality::Poor,
+    _ if enters_good => Quality::Good,
+    _ => Quality::Fair,
+  }
+}
+
+/// Rolling window over the stats stream and the verdict it last gave.
+#[derive(Debug, Default)]
+pub struct QualityMonitor {
+  window: VecDeque<(Option<f64>, Option<f64>)>,
+  quality: Quality,
+}
+
+impl QualityMonitor {
+  pub fn quality(&self) -> Quality {
+    self.quality
+  }
+
+  /// Returns the new quality when this sample changed it. Samples without RTT or loss (a
+  /// disconnected tunnel, an agent that doesn't measure) are skipped.
+  pub fn observe(&mut self, stats: &TrafficStats) -> Option<Quality> {
+    let rtt = stats.rtt_ms.map(f64::from);
+    let loss = stats.loss_pct.filter(|loss| loss.is_finite());
+    if rtt.is_none() && loss.is_none() {
+      return None;
+    }
+    if self.window.len() == WINDOW {
+      self.window.pop_front();
+    }
+    self.window.push_back((rtt, loss));
+    let metrics = Metrics {
+      rtt_ms: mean(self.window.iter().filter_map(|(rtt, _)| *rtt)).unwrap_or(0.0),
+      loss_pct: mean(self.window.iter().filter_map(|(_, loss)| *loss)).unwrap_or(0.0),
+    };
+    let next = classify(self.quality, metrics);
+    (next != self.quality).then(|| {
+      self.quality = next;
+      next
+    })
+  }
+
+  /// Back to `Unknown` with an empty window. Returns whether that changed the quality.
+  pub fn reset(&mut self) -> bool {
+    self.window.clear();
+    std::mem::take(&mut self.quality) != Quality::Unknown
+  }
+}
+
+/// Managed form of `QualityMonitor`, fed by every stats sample.
+#[derive(Default)]
+pub struct ConnectionQuality(Mutex<QualityMonitor>);
+
+/// Feeds `stats` to the managed monitor and announces a transition.
+pub fn observe(app: &AppHandle, stats: &TrafficStats) {
+  let Some(state) = app.try_state::<ConnectionQuality>() else {
+    return;
+  };
+  let changed = state.0.lock().unwrap().observe(stats);
+  if let Some(quality) = changed {
+    let _ = app.emit(QUALITY_CHANGED_EVENT, quality);
+  }
+}
+
+/// Called once the tunnel is down, so the next session doesn't start from the last verdict.
+pub fn reset(app: &AppHandle) {
+  let Some(state) = app.try_state::<ConnectionQuality>() else {
+    return;
+  };
+  if state.0.lock().unwrap().reset() {
+    let _ = app.emit(QUALITY_CHANGED_EVENT, Quality::Unknown);
+  }
+}
+
+#[allow(non_snake_case)]
+#[tauri::command]
+pub fn getConnectionQuality(state: tauri::State<'_, ConnectionQuality>) -> Quality {
+  state.0.lock().unwrap().quality()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn sample(rtt_ms: Option<u32>, loss_pct: Option<f64>) -> TrafficStats {
+    TrafficStats {
+      rtt_ms,
+      loss_pct,
+      ..TrafficStats::default()
+    }
+  }
+
+  /// Feeds the samples in order and collects every transition.
+  fn transitions(monitor: &mut QualityMonitor, samples: &[TrafficStats]) -> Vec<Quality> {
+    samples
+      .iter()
+      .filter_map(|stats| monitor.observe(stats))
+      .collect()
+  }
+
+  #[test]
+  fn classifies_steady_windows() {
+    for (rtt, loss, expected) in [
+      (40, 0.0, Quality::Good),
+      (180, 0.0, Quality::Fair),
+      (90, 2.5, Quality::Fair),
+      (450, 0.0, Quality::Poor),
+      (60, 8.0, Quality::Poor),
+    ] {
+      let mut monitor = QualityMonitor::default();
+      transitions(&mut monitor, &vec![sample(Some(rtt), Some(loss)); WINDOW]);
+      assert_eq!(monitor.quality(), expected, "{rtt} ms, {loss}%");
+    }
+  }
+
+  #[test]
+  fn hovering_at_a_threshold_does_not_flap() {
+    let mut monitor = QualityMonitor::default();
+    transitions(&mut monitor, &vec![sample(Some(100), None); WINDOW]);
+    assert_eq!(monitor.quality(), Quality::Good);
+
+    // Alternating just over and under 150 ms stays inside Good's band.
+    let hovering: Vec<_> = (0..30)
+      .map(|n| sample(Some(if n % 2 == 0 { 170 } else { 140 }), None))
+      .collect();
+    assert_eq!(transitions(&mut monitor, &hovering), []);
+    assert_eq!(monitor.quality(), Quality::Good);
+
+    // Past the looser bound it drops, and coming back to 170 ms isn't Good again.
+    assert_eq!(
+      transitions(&mut monitor, &vec![sample(Some(260), None); WINDOW]),
+      [Quality::Fair]
+    );
+    assert_eq!(
+      transitions(&mut monitor, &vec![sample(Some(170), None); WINDOW]),
+      []
+    );
+    assert_eq!(monitor.quality(), Quality::Fair);
+  }
+
+  #[test]
+  fn poor_needs_both_metrics_back_under_the_looser_bound() {
+    let mut monitor = QualityMonitor::default();
+    assert_eq!(
+      transitions(&mut monitor, &vec![sample(Some(500), Some(0.0)); WINDOW]),
+      [Quality::Poor]
+    );
+
+    // 350 ms is under Poor's entry bound but not past its exit.
+    assert_eq!(
+      transitions(&mut monitor, &vec![sample(Some(350), Some(0.0)); WINDOW]),
+      []
+    );
+    assert_eq!(
+      transitions(&mut monitor, &vec![sample(Some(120), Some(0.2)); WINDOW]),
+      [Quality::Fair, Quality::Good]
+    );
+  }
+
+  #[test]
+  fn one_spike_is_averaged_out() {
+    let mut monitor = QualityMonitor::default();
+    transitions(&mut monitor, &vec![sample(Some(60), Some(0.0)); WINDOW]);
+
+    assert_eq!(
+      transitions(&mut monitor, &[sample(Some(900), Some(0.0))]),
+      []
+    );
+    assert_eq!(monitor.quality(), Quality::Good);
+  }
+
+  #[test]
+  fn unmeasured_samples_are_skipped_and_reset_forgets() {
+    let mut monitor = QualityMonitor::default();
+    assert_eq!(transitions(&mut monitor, &[TrafficStats::default(); 3]), []);
+    assert_eq!(monitor.quality(), Quality::Unknown);
+
+    assert_eq!(
+      transitions(&mut monitor, &[sample(None, Some(9.0))]),
+      [Quality::Poor]
+    );
+    assert!(monitor.reset());
+    assert!(!monitor.reset());
+    assert_eq!(
+      transitions(&mut monitor, &[sample(Some(50), None)]),
+      [Quality::Good]
+    );
+  }
+
+  #[test]
+  fn serializes_lowercase() {
+    assert_eq!(
+      serde_json::to_value(Quality::Fair).unwrap(),
+      serde_json::json!("fair")
+    );
+  }
+}
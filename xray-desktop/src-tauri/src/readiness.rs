@@ -0,0 +1,138 @@
+use crate::error::BridgeError;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager, State};
+use tokio::sync::watch;
+
+pub const READY_EVENT: &str = "vpn://ready";
+/// How long the session restore waits for launch; prewarm is bounded by the `version` timeout.
+pub const STARTUP_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// What launch has to get through before the bridge takes commands. A step that failed still
+/// counts as done: the runner then reports that failure itself, which says more than `NotReady`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Prerequisite {
+  NodeResolved,
+  BridgeVerified,
+  Prewarmed,
+}
+
+const ALL: [Prerequisite; 3] = [
+  Prerequisite::NodeResolved,
+  Prerequisite::BridgeVerified,
+  Prerequisite::Prewarmed,
+];
+
+#[derive(Clone)]
+pub struct Readiness {
+  done: Arc<Mutex<Vec<Prerequisite>>>,
+  ready: Arc<watch::Sender<bool>>,
+}
+
+impl Default for Readiness {
+  fn default() -> Self {
+    Self {
+      done: Arc::new(Mutex::new(Vec::new())),
+      ready: Arc::new(watch::channel(false).0),
+    }
+  }
+}
+
+impl Readiness {
+  pub fn is_ready(&self) -> bool {
+    *self.ready.borrow()
+  }
+
+  /// Marks `step` done. True only for the call that completed the last one.
+  pub fn complete(&self, step: Prerequisite) -> bool {
+    let mut done = self.done.lock().unwrap();
+    if done.contains(&step) {
+      return false;
+    }
+    done.push(step);
+    let ready = ALL.iter().all(|step| done.contains(step));
+    if ready {
+      self.ready.send_replace(true);
+    }
+    ready
+  }
+
+  pub fn check(&self) -> Result<(), BridgeError> {
+    if self.is_ready() {
+      Ok(())
+    } else {
+      Err(BridgeError::NotReady)
+    }
+  }
+
+  pub async fn wait(&self, timeout: Duration) -> Result<(), BridgeError> {
+    let mut ready = self.ready.subscribe();
+    let waited = tokio::time::timeout(timeout, ready.wait_for(|ready| *ready))
+      .await
+      .is_ok_and(|result| result.is_ok());
+    if waited {
+      Ok(())
+    } else {
+      Err(BridgeError::NotReady)
+    }
+  }
+}
+
+/// `complete` for the app's readiness, announcing `vpn://ready` once everything is done.
+pub fn complete(app: &AppHandle, step: Prerequisite) {
+  let Some(readiness) = app.try_state::<Readiness>() else {
+    return;
+  };
+  if readiness.complete(step) {
+    tracing::info!("ready");
+    let _ = app.emit(READY_EVENT, ());
+  }
+}
+
+#[allow(non_snake_case)]
+#[tauri::command]
+pub async fn waitUntilReady(
+  readiness: State<'_, Readiness>,
+  timeoutMs: u64,
+) -> Result<(), BridgeError> {
+  readiness.wait(Duration::from_millis(timeoutMs)).await
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use tauri::async_runtime::block_on;
+
+  #[test]
+  fn ready_only_once_every_prerequisite_is_done() {
+    let readiness = Readiness::default();
+    assert_eq!(readiness.check(), Err(BridgeError::NotReady));
+    assert!(!readiness.complete(Prerequisite::NodeResolved));
+    assert!(!readiness.complete(Prerequisite::NodeResolved));
+    assert!(!readiness.complete(Prerequisite::Prewarmed));
+    assert!(!readiness.is_ready());
+    assert!(readiness.complete(Prerequisite::BridgeVerified));
+    assert!(readiness.is_ready());
+    assert_eq!(readiness.check(), Ok(()));
+    // Already ready: nothing left to announce.
+    assert!(!readiness.complete(Prerequisite::Prewarmed));
+  }
+
+  #[test]
+  fn wait_resolves_when_ready_and_times_out_otherwise() {
+    let readiness = Readiness::default();
+    assert_eq!(
+      block_on(readiness.wait(Duration::from_millis(20))),
+      Err(BridgeError::NotReady)
+    );
+
+    let waiting = readiness.clone();
+    let waiter =
+      tauri::async_runtime::spawn(async move { waiting.wait(Duration::from_secs(5)).await });
+    for step in ALL {
+      readiness.complete(step);
+    }
+    assert_eq!(block_on(waiter).unwrap(), Ok(()));
+    assert_eq!(block_on(readiness.wait(Duration::ZERO)), Ok(()));
+  }
+}
@@ -0,0 +1,175 @@
+use crate::bridge::Bridge;
+use crate::commands;
+use crate::config::Config;
+use crate::secrets::Secrets;
+use crate::status::LinkState;
+use serde::Serialize;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tauri::async_runtime::JoinHandle;
+use tauri::{AppHandle, Emitter, Manager};
+
+pub const RECONNECTING_EVENT: &str = "vpn://reconnecting";
+pub const MAX_ATTEMPTS: u32 = 10;
+const FIRST_DELAY: Duration = Duration::from_secs(1);
+const MAX_DELAY: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Reconnecting {
+  attempt: u32,
+  max_attempts: u32,
+  delay_ms: u64,
+}
+
+/// Wait before the given (1-based) attempt: 1s, 2s, 4s, ... capped at a minute.
+pub fn backoff(attempt: u32) -> Duration {
+  let doublings = attempt.saturating_sub(1).min(31);
+  FIRST_DELAY.saturating_mul(1 << doublings).min(MAX_DELAY)
+}
+
+/// Brings the tunnel back after drops the user didn't ask for (`autoReconnect` in settings).
+#[derive(Default)]
+pub struct Watchdog {
+  user_disconnected: AtomicBool,
+  last_user_action: Mutex<Option<Instant>>,
+  task: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl Watchdog {
+  pub fn user_connected(&self) {
+    self.user_disconnected.store(false, Ordering::SeqCst);
+    *self.last_user_action.lock().unwrap() = Some(Instant::now());
+  }
+
+  /// Stands the watchdog down until the next user connect, cancelling any retries in flight.
+  pub fn user_disconnected(&self) {
+    self.user_disconnected.store(true, Ordering::SeqCst);
+    *self.last_user_action.lock().unwrap() = Some(Instant::now());
+    if let Some(task) = self.task.lock().unwrap().take() {
+      task.abort();
+    }
+  }
+
+  /// When `user_connected` or `user_disconnected` last ran, so schedules can tell manual changes.
+  pub fn last_user_action(&self) -> Option<Instant> {
+    *self.last_user_action.lock().unwrap()
+  }
+
+  /// Only a drop from a live tunnel counts; a paused session is still the user's choice.
+  pub fn should_reconnect(&self, previous: LinkState, current: LinkState, enabled: bool) -> bool {
+    enabled
+      && previous == LinkState::Connected
+      && current == LinkState::Disconnected
+      && !self.user_disconnected.load(Ordering::SeqCst)
+  }
+
+  /// Called by the status watcher on every connection state change.
+  pub fn on_transition(&self, app: &AppHandle, previous: LinkState, current: LinkState) {
+    let enabled = app
+      .try_state::<Config>()
+      .is_some_and(|config| config.get().auto_reconnect);
+    if !self.should_reconnect(previous, current, enabled) {
+      return;
+    }
+
+    let mut task = self.task.lock().unwrap();
+    if task
+      .as_ref()
+      .is_some_and(|task| !task.inner().is_finished())
+    {
+      return;
+    }
+    *task = Some(tauri::async_runtime::spawn(retry(app.clone())));
+  }
+}
+
+/// `reconnectNow` is a single attempt, made right away.
+pub fn announce_reconnect_now(app: &AppHandle) {
+  let _ = app.emit(
+    RECONNECTING_EVENT,
+    Reconnecting {
+      attempt: 1,
+      max_attempts: 1,
+      delay_ms: 0,
+    },
+  );
+}
+
+async fn retry(app: AppHandle) {
+  for attempt in 1..=MAX_ATTEMPTS {
+    let delay = backoff(attempt);
+    let _ = app.emit(
+      RECONNECTING_EVENT,
+      Reconnecting {
+        attempt,
+        max_attempts: MAX_ATTEMPTS,
+        delay_ms: delay.as_millis() as u64,
+      },
+    );
+    tokio::time::sleep(delay).await;
+
+    let (Some(bridge), Some(secrets), Some(config), Some(watchdog)) = (
+      app.try_state::<Bridge>(),
+      app.try_state::<Secrets>(),
+      app.try_state::<Config>(),
+      app.try_state::<Watchdog>(),
+    ) else {
+      return;
+    };
+    if commands::connect(&bridge, &secrets, &config, &watchdog, None)
+      .await
+      .is_ok()
+    {
+      return;
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn backoff_doubles_up_to_a_minute() {
+    let schedule: Vec<u64> = (1..=MAX_ATTEMPTS)
+      .map(|attempt| backoff(attempt).as_secs())
+      .collect();
+
+    assert_eq!(schedule, vec![1, 2, 4, 8, 16, 32, 60, 60, 60, 60]);
+    assert_eq!(backoff(u32::MAX), MAX_DELAY);
+  }
+
+  use LinkState::{Connected, Disconnected, Paused};
+
+  #[test]
+  fn reconnects_only_after_an_unrequested_drop() {
+    let watchdog = Watchdog::default();
+
+    assert!(watchdog.should_reconnect(Connected, Disconnected, true));
+    assert!(!watchdog.should_reconnect(Connected, Disconnected, false));
+    assert!(!watchdog.should_reconnect(Disconnected, Disconnected, true));
+    assert!(!watchdog.should_reconnect(Disconnected, Connected, true));
+  }
+
+  #[test]
+  fn ignores_the_paused_state() {
+    let watchdog = Watchdog::default();
+
+    assert!(!watchdog.should_reconnect(Connected, Paused, true));
+    assert!(!watchdog.should_reconnect(Paused, Disconnected, true));
+    assert!(!watchdog.should_reconnect(Paused, Connected, true));
+  }
+
+  #[test]
+  fn user_disconnect_suppresses_until_next_connect() {
+    let watchdog = Watchdog::default();
+
+    watchdog.user_disconnected();
+    assert!(!watchdog.should_reconnect(Connected, Disconnected, true));
+
+    watchdog.user_connected();
+    assert!(watchdog.should_reconnect(Connected, Disconnected, true));
+  }
+}
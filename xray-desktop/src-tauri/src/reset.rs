@@ -0,0 +1,425 @@
+use crate::bridge::Bridge;
+use crate::commands::{self, ToggleLock};
+use crate::config::Config;
+use crate::disguise_rotation::DisguiseRotation;
+use crate::error::BridgeError;
+use crate::history::History;
+use crate::last_error::LastErrorStore;
+use crate::latency::LatencyCache;
+use crate::onboarding;
+use crate::public_ip::PublicIpCache;
+use crate::reconnect::Watchdog;
+use crate::secrets::{self, Secrets, TOKEN_ACCOUNT};
+use crate::servers::ServerCatalog;
+use crate::status::LinkState;
+use crate::telemetry::Telemetry;
+use crate::window_state::WindowState;
+use serde::Serialize;
+use std::collections::BTreeSet;
+use tauri::{AppHandle, Manager};
+
+/// What `resetAllData` removed. Names only: keyring accounts and kinds of file, never values.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResetSummary {
+  /// `false` when the call wasn't confirmed and nothing was touched.
+  pub reset: bool,
+  /// The tunnel was up and got disconnected first.
+  pub disconnected: bool,
+  /// Keyring accounts that held a token or admin key.
+  pub secrets: Vec<String>,
+  /// Of `settings`, `history`, `lastError`, `telemetry` and `windowState`.
+  pub files: Vec<String>,
+}
+
+/// Everything the reset wipes that tests can stand in for.
+pub struct LocalData<'a> {
+  pub config: &'a Config,
+  pub secrets: &'a Secrets,
+  pub history: &'a History,
+  pub last_error: &'a LastErrorStore,
+  pub telemetry: &'a Telemetry,
+}
+
+/// Every keyring account the app may have written: the default token, each profile's token, an
+/// admin key per control panel it knows of and each imported server's outbound.
+fn accounts(data: &LocalData<'_>) -> BTreeSet<String> {
+  let settings = data.config.get();
+  let mut tokens = vec![TOKEN_ACCOUNT.to_string()];
+  tokens.extend(
+    settings
+      .profiles
+      .iter()
+      .map(|profile| profile.token_ref.clone()),
+  );
+
+  let mut base_urls: BTreeSet<String> = settings.default_base_url.into_iter().collect();
+  base_urls.extend(
+    settings
+      .profiles
+      .iter()
+      .map(|profile| profile.base_url.clone()),
+  );
+  base_urls.extend(
+    tokens
+      .iter()
+      .filter_map(|account| data.secrets.load_token_at(account).ok().flatten())
+      .map(|stored| stored.base_url),
+  );
+
+  let mut accounts: BTreeSet<String> = tokens.into_iter().collect();
+  accounts.extend(
+    base_urls
+      .iter()
+      .map(|base_url| secrets::admin_account(base_url)),
+  );
+  accounts.extend(
+    settings
+      .imported_servers
+      .iter()
+      .map(|server| secrets::outbound_account(&server.id)),
+  );
+  accounts
+}
+
+/// Takes the tunnel down, then deletes every secret and file in `data`. Nothing is deleted
+/// unless the tunnel is confirmed down: wiping the token under a live tunnel would leave one
+/// the app can no longer reconnect or account for.
+pub async fn reset_all(
+  bridge: &Bridge,
+  watchdog: &Watchdog,
+  lock: &ToggleLock,
+  data: LocalData<'_>,
+  confirm: bool,
+) -> Result<ResetSummary, BridgeError> {
+  if !confirm {
+    return Ok(ResetSummary::default());
+  }
+  let _toggling = lock.hold().await;
+  commands::cancel_connect(bridge, watchdog).await?;
+  let disconnected = commands::status(bridge).await?.state() != LinkState::Disconnected;
+  if disconnected {
+    commands::disconnect(bridge, watchdog).await?;
+    if commands::status(bridge).await?.state() != LinkState::Disconnected {
+      return Err(BridgeError::StillConnected);
+    }
+  }
+
+  let mut summary = ResetSummary {
+    reset: true,
+    disconnected,
+    ..ResetSummary::default()
+  };
+  for account in accounts(&data) {
+    if data.secrets.remove(&account)? {
+      summary.secrets.push(account);
+    }
+  }
+  for (file, removed) in [
+    ("settings", data.config.reset()?),
+    ("history", data.history.clear()?),
+    ("lastError", data.last_error.clear()?),
+    ("telemetry", data.telemetry.clear()?),
+  ] {
+    if removed {
+      summary.files.push(file.to_string());
+    }
+  }
+  Ok(summary)
+}
+
+/// `reset_all` plus the state only the running app holds: caches, the rotation timer and the
+/// saved window geometry. The first-run flow is offered again afterwards.
+#[allow(non_snake_case)]
+#[tauri::command]
+pub async fn resetAllData(
+  app: AppHandle,
+  bridge: tauri::State<'_, Bridge>,
+  watchdog: tauri::State<'_, Watchdog>,
+  lock: tauri::State<'_, ToggleLock>,
+  confirm: bool,
+) -> Result<ResetSummary, BridgeError> {
+  let data = LocalData {
+    config: &app.state::<Config>(),
+    secrets: &app.state::<Secrets>(),
+    history: &app.state::<History>(),
+    last_error: &app.state::<LastErrorStore>(),
+    telemetry: &app.state::<Telemetry>(),
+  };
+  let mut summary = reset_all(&bridge, &watchdog, &lock, data, confirm).await?;
+  if !summary.reset {
+    return Ok(summary);
+  }
+
+  if app
+    .try_state::<WindowState>()
+    .is_some_and(|window_state| window_state.forget())
+  {
+    summary.files.push("windowState".to_string());
+  }
+  app.state::<ServerCatalog>().replace(Vec::new());
+  app.state::<LatencyCache>().clear();
+  app.state::<PublicIpCache>().clear();
+  app.state::<DisguiseRotation>().clear();
+  tracing::info!(
+    secrets = summary.secrets.len(),
+    files = ?summary.files,
+    "all local data reset"
+  );
+  onboarding::announce(&app);
+  Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::bridge::mock::MockBridgeRunner;
+  use crate::bridge::BridgeRunner;
+  use crate::history::{ConnectionEvent, SessionEnd};
+  use crate::last_error::LastError;
+  use crate::profiles;
+  use crate::secrets::mock::MemoryStore;
+  use crate::secrets::StoredToken;
+  use serde_json::{json, Value};
+  use std::sync::atomic::{AtomicBool, Ordering};
+  use std::sync::{Arc, Mutex};
+  use std::time::{Duration, SystemTime};
+  use tauri::async_runtime::block_on;
+
+  /// What `Fixture::populated` leaves on disk, the settings backup from its second save included.
+  const STORED_FILES: [&str; 5] = [
+    "connection-history.jsonl",
+    "last-error.json",
+    "settings.json",
+    "settings.json.bak",
+    "telemetry.json",
+  ];
+
+  struct Fixture {
+    dir: tempfile::TempDir,
+    config: Config,
+    secrets: Secrets,
+    history: History,
+    last_error: LastErrorStore,
+    telemetry: Telemetry,
+  }
+
+  impl Fixture {
+    /// One of everything the app stores: a default token, a profile token, admin keys, settings
+    /// and each data file.
+    fn populated() -> Self {
+      let dir = tempfile::tempdir().unwrap();
+      let fixture = Self {
+        config: Config::in_dir(&dir.path().join("config")),
+        secrets: Secrets::new(Box::new(MemoryStore::default())),
+        history: History::in_dir(&dir.path().join("data")),
+        last_error: LastErrorStore::in_dir(&dir.path().join("data")),
+        telemetry: Telemetry::in_dir(&dir.path().join("data")),
+        dir,
+      };
+      let stored = |base_url: &str, token: &str| StoredToken {
+        base_url: base_url.to_string(),
+        token: token.to_string(),
+        expires_at: None,
+      };
+      fixture
+        .secrets
+        .store_token(&stored("https://panel.example", "tok-default"))
+        .unwrap();
+      profiles::create(
+        &fixture.secrets,
+        &fixture.config,
+        "Work".to_string(),
+        "https://work.example".to_string(),
+        "tok-work".to_string(),
+      )
+      .unwrap();
+      fixture
+        .secrets
+        .store_admin_key("https://panel.example", "admin-1")
+        .unwrap();
+      fixture
+        .secrets
+        .store_admin_key("https://work.example", "admin-2")
+        .unwrap();
+      fixture
+        .config
+        .update(|settings| {
+          settings.auto_connect = true;
+          settings.telemetry_local = true;
+        })
+        .unwrap();
+      fixture
+        .history
+        .append(&ConnectionEvent {
+          timestamp: 1_700_000_000,
+          server_id: Some("srv-1".to_string()),
+          action: SessionEnd::Disconnected,
+          duration_secs: 60,
+        })
+        .unwrap();
+      fixture
+        .last_error
+        .record(LastError {
+          action: "connect".to_string(),
+          kind: "timeout".to_string(),
+          message: "timed out".to_string(),
+          code: None,
+          timestamp: 1_700_000_000,
+        })
+        .unwrap();
+      fixture.telemetry.record(
+        "status",
+        Duration::from_millis(20),
+        false,
+        SystemTime::now(),
+      );
+      fixture.telemetry.flush().unwrap();
+      fixture
+    }
+
+    fn data(&self) -> LocalData<'_> {
+      LocalData {
+        config: &self.config,
+        secrets: &self.secrets,
+        history: &self.history,
+        last_error: &self.last_error,
+        telemetry: &self.telemetry,
+      }
+    }
+
+    fn files(&self) -> Vec<String> {
+      let mut files: Vec<String> = ["config", "data"]
+        .iter()
+        .flat_map(|dir| std::fs::read_dir(self.dir.path().join(dir)).unwrap())
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+        .collect();
+      files.sort();
+      files
+    }
+  }
+
+  /// Connected until asked to `disconnect`.
+  #[derive(Clone, Default)]
+  struct Tunnel {
+    actions: Arc<Mutex<Vec<String>>>,
+    down: Arc<AtomicBool>,
+  }
+
+  impl BridgeRunner for Tunnel {
+    fn run(&self, action: &str, _payload: Value) -> Result<Value, BridgeError> {
+      self.actions.lock().unwrap().push(action.to_string());
+      if action == "disconnect" {
+        self.down.store(true, Ordering::SeqCst);
+      }
+      Ok(json!({ "connected": !self.down.load(Ordering::SeqCst) }))
+    }
+  }
+
+  #[test]
+  fn removes_every_kind_of_stored_data() {
+    let fixture = Fixture::populated();
+    let profile = fixture.config.get().profiles[0].clone();
+    assert_eq!(fixture.files(), STORED_FILES);
+
+    let tunnel = Tunnel::default();
+    let bridge = Bridge::new(tunnel.clone());
+    let summary = block_on(reset_all(
+      &bridge,
+      &Watchdog::default(),
+      &ToggleLock::default(),
+      fixture.data(),
+      true,
+    ))
+    .unwrap();
+
+    let mut secrets = vec![
+      TOKEN_ACCOUNT.to_string(),
+      profile.token_ref.clone(),
+      secrets::admin_account("https://panel.example"),
+      secrets::admin_account("https://work.example"),
+    ];
+    secrets.sort();
+    assert_eq!(
+      summary,
+      ResetSummary {
+        reset: true,
+        disconnected: true,
+        secrets,
+        files: ["settings", "history", "lastError", "telemetry"]
+          .map(String::from)
+          .to_vec(),
+      }
+    );
+    assert_eq!(
+      *tunnel.actions.lock().unwrap(),
+      ["status", "disconnect", "status"]
+    );
+    assert!(fixture.files().is_empty());
+    assert_eq!(fixture.secrets.load_token().unwrap(), None);
+    assert_eq!(
+      fixture.secrets.load_token_at(&profile.token_ref).unwrap(),
+      None
+    );
+    assert_eq!(
+      fixture
+        .secrets
+        .load_admin_key("https://work.example")
+        .unwrap(),
+      None
+    );
+    assert_eq!(fixture.config.get(), crate::config::Settings::default());
+    assert_eq!(fixture.history.recent(10).unwrap(), []);
+    assert_eq!(fixture.last_error.get(), None);
+    assert_eq!(fixture.telemetry.report(true).actions, []);
+  }
+
+  #[test]
+  fn unconfirmed_is_a_no_op() {
+    let fixture = Fixture::populated();
+    let runner = MockBridgeRunner::default();
+    let bridge = Bridge::new(runner.clone());
+
+    let summary = block_on(reset_all(
+      &bridge,
+      &Watchdog::default(),
+      &ToggleLock::default(),
+      fixture.data(),
+      false,
+    ))
+    .unwrap();
+
+    assert_eq!(summary, ResetSummary::default());
+    assert!(runner.calls().is_empty());
+    assert_eq!(fixture.files(), STORED_FILES);
+    assert!(fixture.secrets.load_token().unwrap().is_some());
+    assert!(fixture.config.get().auto_connect);
+  }
+
+  #[test]
+  fn a_tunnel_that_stays_up_keeps_everything() {
+    let fixture = Fixture::populated();
+    let runner = MockBridgeRunner::default()
+      .respond("status", Ok(json!({ "connected": true })))
+      .respond("disconnect", Ok(json!(null)));
+    let bridge = Bridge::new(runner.clone());
+
+    let result = block_on(reset_all(
+      &bridge,
+      &Watchdog::default(),
+      &ToggleLock::default(),
+      fixture.data(),
+      true,
+    ));
+
+    assert_eq!(result, Err(BridgeError::StillConnected));
+    let actions: Vec<String> = runner
+      .calls()
+      .into_iter()
+      .map(|(action, _)| action)
+      .collect();
+    assert_eq!(actions, ["status", "disconnect", "status"]);
+    assert_eq!(fixture.files(), STORED_FILES);
+    assert!(fixture.secrets.load_token().unwrap().is_some());
+  }
+}
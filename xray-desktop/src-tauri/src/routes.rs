@@ -0,0 +1,170 @@
+use crate::bridge::Bridge;
+use crate::error::BridgeError;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::net::IpAddr;
+
+/// One route the tunnel installed.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RouteEntry {
+  /// Canonical CIDR: host bits cleared, `default` spelled `0.0.0.0/0` or `::/0`.
+  pub destination: String,
+  /// Next hop; empty for an on-link route.
+  pub via: String,
+  pub interface: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RawRoute {
+  destination: String,
+  #[serde(default)]
+  via: Option<String>,
+  #[serde(default)]
+  interface: Option<String>,
+}
+
+fn masked(address: IpAddr, prefix: u8) -> IpAddr {
+  match address {
+    IpAddr::V4(v4) => {
+      let bits = u32::from(v4) & u32::MAX.checked_shl(32 - u32::from(prefix)).unwrap_or(0);
+      IpAddr::V4(bits.into())
+    }
+    IpAddr::V6(v6) => {
+      let bits = u128::from(v6) & u128::MAX.checked_shl(128 - u32::from(prefix)).unwrap_or(0);
+      IpAddr::V6(bits.into())
+    }
+  }
+}
+
+/// `address/prefix`, a bare address (a host route) or `default`, whose family follows `via`.
+pub fn normalize_destination(raw: &str, via: &str) -> Option<String> {
+  let raw = raw.trim();
+  if raw.eq_ignore_ascii_case("default") {
+    let v6 = via.parse::<IpAddr>().is_ok_and(|via| via.is_ipv6());
+    return Some(if v6 { "::/0" } else { "0.0.0.0/0" }.to_string());
+  }
+  let (address, prefix) = match raw.split_once('/') {
+    Some((address, prefix)) => (address, Some(prefix)),
+    None => (raw, None),
+  };
+  let address: IpAddr = address.parse().ok()?;
+  let max_prefix = if address.is_ipv4() { 32 } else { 128 };
+  let prefix = match prefix {
+    Some(prefix) if !prefix.is_empty() && prefix.bytes().all(|byte| byte.is_ascii_digit()) => {
+      prefix
+        .parse::<u8>()
+        .ok()
+        .filter(|prefix| *prefix <= max_prefix)?
+    }
+    Some(_) => return None,
+    None => max_prefix,
+  };
+  Some(format!("{}/{prefix}", masked(address, prefix)))
+}
+
+/// Parses the bridge `routes` answer. `null` (no tunnel) is an empty table, and an entry that
+/// isn't a route is skipped so one odd line from the OS can't hide the rest.
+pub fn from_bridge(value: Value) -> Result<Vec<RouteEntry>, BridgeError> {
+  let entries: Vec<Value> = match value {
+    Value::Null => return Ok(Vec::new()),
+    value => {
+      serde_json::from_value(value).map_err(|error| BridgeError::InvalidJson(error.to_string()))?
+    }
+  };
+  Ok(
+    entries
+      .into_iter()
+      .filter_map(|entry| {
+        let raw: RawRoute = serde_json::from_value(entry).ok()?;
+        let via = raw.via.unwrap_or_default().trim().to_string();
+        let Some(destination) = normalize_destination(&raw.destination, &via) else {
+          tracing::debug!(destination = raw.destination, "skipping unparsable route");
+          return None;
+        };
+        Some(RouteEntry {
+          destination,
+          via,
+          interface: raw.interface.unwrap_or_default().trim().to_string(),
+        })
+      })
+      .collect(),
+  )
+}
+
+pub async fn routes(bridge: &Bridge) -> Result<Vec<RouteEntry>, BridgeError> {
+  from_bridge(bridge.call("routes", Value::Null).await?)
+}
+
+#[allow(non_snake_case)]
+#[tauri::command]
+pub async fn getRoutes(bridge: tauri::State<'_, Bridge>) -> Result<Vec<RouteEntry>, BridgeError> {
+  routes(&bridge).await
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::bridge::mock::MockBridgeRunner;
+  use serde_json::json;
+  use tauri::async_runtime::block_on;
+
+  fn route(destination: &str, via: &str, interface: &str) -> RouteEntry {
+    RouteEntry {
+      destination: destination.to_string(),
+      via: via.to_string(),
+      interface: interface.to_string(),
+    }
+  }
+
+  #[test]
+  fn deserializes_and_normalizes_a_route_table() {
+    let bridge = Bridge::new(MockBridgeRunner::default().respond(
+      "routes",
+      Ok(json!([
+        { "destination": "default", "via": "10.8.0.1", "interface": "utun4" },
+        { "destination": "default", "via": "fe80::1", "interface": "utun4" },
+        { "destination": "10.8.0.7/24", "via": "", "interface": "utun4" },
+        { "destination": "203.0.113.9", "via": "192.168.1.1", "interface": "en0" },
+        { "destination": "2001:db8::1/32", "interface": "utun4", "metric": 5 }
+      ])),
+    ));
+
+    assert_eq!(
+      block_on(routes(&bridge)).unwrap(),
+      [
+        route("0.0.0.0/0", "10.8.0.1", "utun4"),
+        route("::/0", "fe80::1", "utun4"),
+        route("10.8.0.0/24", "", "utun4"),
+        route("203.0.113.9/32", "192.168.1.1", "en0"),
+        route("2001:db8::/32", "", "utun4"),
+      ]
+    );
+  }
+
+  #[test]
+  fn malformed_entries_are_skipped() {
+    let parsed = from_bridge(json!([
+      "default via 10.8.0.1",
+      { "via": "10.8.0.1" },
+      { "destination": "10.0.0.0/33", "interface": "utun4" },
+      { "destination": "10.0.0.0/+8", "interface": "utun4" },
+      { "destination": "link#14", "interface": "en0" },
+      { "destination": 42 },
+      { "destination": " 0.0.0.0/1 ", "via": " 10.8.0.1 ", "interface": " utun4 " }
+    ]))
+    .unwrap();
+
+    assert_eq!(parsed, [route("0.0.0.0/1", "10.8.0.1", "utun4")]);
+  }
+
+  #[test]
+  fn no_tunnel_is_an_empty_table() {
+    assert_eq!(from_bridge(Value::Null).unwrap(), []);
+    assert!(matches!(
+      from_bridge(json!({ "routes": [] })),
+      Err(BridgeError::InvalidJson(_))
+    ));
+  }
+}
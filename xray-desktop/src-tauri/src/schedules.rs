@@ -0,0 +1,482 @@
+use crate::bridge::Bridge;
+use crate::commands;
+use crate::config::Config;
+use crate::error::BridgeError;
+use crate::reconnect::Watchdog;
+use crate::secrets::Secrets;
+use crate::status::LinkState;
+use chrono::{Datelike, Duration as WallDuration, Local, NaiveDateTime, Timelike};
+use serde::{Deserialize, Serialize};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tauri::async_runtime::JoinHandle;
+use tauri::{AppHandle, Manager};
+
+const CHECK_INTERVAL: Duration = Duration::from_secs(60);
+/// Wall-clock minutes skipped by a DST jump (or a late tick) still fire, up to this far back. A
+/// longer gap, like waking from sleep, only evaluates the current minute.
+const MAX_CATCH_UP: WallDuration = WallDuration::hours(2);
+/// A rule doesn't undo what the user did by hand this recently.
+const MANUAL_GRACE: Duration = Duration::from_secs(30 * 60);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ScheduleAction {
+  Connect,
+  Disconnect,
+}
+
+/// `cron_like` is the usual five cron fields, `minute hour day-of-month month day-of-week`, in
+/// local time: `0 9 * * 1-5` is 9:00 on weekdays.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScheduleRule {
+  pub id: String,
+  pub cron_like: String,
+  pub action: ScheduleAction,
+}
+
+/// A parsed `cron_like`; bit `n` of each field is set when value `n` matches.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CronSpec {
+  minutes: u64,
+  hours: u32,
+  days: u32,
+  months: u16,
+  weekdays: u8,
+  /// Cron's own quirk: with both day fields restricted, either one matching is enough.
+  days_restricted: bool,
+  weekdays_restricted: bool,
+}
+
+/// One field: `*`, `5`, `1-5`, `*/15`, `0-30/10` or a comma list of those.
+fn parse_field(field: &str, min: u32, max: u32) -> Option<u64> {
+  let mut bits = 0u64;
+  for part in field.split(',') {
+    let (range, step) = match part.split_once('/') {
+      Some((range, step)) => (range, step.parse::<u32>().ok().filter(|step| *step > 0)?),
+      None => (part, 1),
+    };
+    let (start, end) = if range == "*" {
+      (min, max)
+    } else if let Some((start, end)) = range.split_once('-') {
+      (start.parse().ok()?, end.parse().ok()?)
+    } else {
+      let value = range.parse().ok()?;
+      (value, value)
+    };
+    if start < min || end > max || start > end {
+      return None;
+    }
+    for value in (start..=end).step_by(step as usize) {
+      bits |= 1 << value;
+    }
+  }
+  Some(bits)
+}
+
+impl CronSpec {
+  pub fn parse(cron_like: &str) -> Result<Self, BridgeError> {
+    let invalid = |reason: &str| BridgeError::InvalidSchedule(format!("\"{cron_like}\": {reason}"));
+    let fields: Vec<&str> = cron_like.split_whitespace().collect();
+    let [minute, hour, day, month, weekday] = fields[..] else {
+      return Err(invalid("expected 5 fields"));
+    };
+    let field = |value: &str, name: &str, min: u32, max: u32| {
+      parse_field(value, min, max).ok_or_else(|| invalid(&format!("bad {name} field")))
+    };
+    let weekdays = field(weekday, "day-of-week", 0, 7)?;
+    Ok(Self {
+      minutes: field(minute, "minute", 0, 59)?,
+      hours: field(hour, "hour", 0, 23)? as u32,
+      days: field(day, "day-of-month", 1, 31)? as u32,
+      months: field(month, "month", 1, 12)? as u16,
+      // 7 is another spelling of Sunday.
+      weekdays: ((weekdays | (weekdays >> 7)) & 0x7f) as u8,
+      days_restricted: day != "*",
+      weekdays_restricted: weekday != "*",
+    })
+  }
+
+  pub fn matches(&self, time: NaiveDateTime) -> bool {
+    let bit = |bits: u64, value: u32| bits & (1 << value) != 0;
+    let day = bit(u64::from(self.days), time.day());
+    let weekday = bit(
+      u64::from(self.weekdays),
+      time.weekday().num_days_from_sunday(),
+    );
+    let day_matches = match (self.days_restricted, self.weekdays_restricted) {
+      (true, true) => day || weekday,
+      _ => day && weekday,
+    };
+    bit(self.minutes, time.minute())
+      && bit(u64::from(self.hours), time.hour())
+      && bit(u64::from(self.months), time.month())
+      && day_matches
+  }
+}
+
+fn start_of_minute(time: NaiveDateTime) -> NaiveDateTime {
+  time
+    .with_second(0)
+    .and_then(|time| time.with_nanosecond(0))
+    .unwrap_or(time)
+}
+
+/// Rules to fire for the wall-clock minutes after `last` up to and including `now`, ordered by
+/// the latest minute each one matched, rules matching the same minute in list order. Every minute
+/// is covered once, so a tick twice within a minute doesn't fire twice, a minute skipped by the
+/// spring-forward jump still fires, and the hour repeated by a fall-back (local time going
+/// backwards) fires nothing again as long as `last` never moves back; see `take_due`.
+pub fn due(rules: &[ScheduleRule], last: NaiveDateTime, now: NaiveDateTime) -> Vec<ScheduleRule> {
+  let (last, now) = (start_of_minute(last), start_of_minute(now));
+  if now <= last {
+    return Vec::new();
+  }
+  let first = if now - last > MAX_CATCH_UP {
+    now
+  } else {
+    last + WallDuration::minutes(1)
+  };
+  let mut matched: Vec<(NaiveDateTime, &ScheduleRule)> = rules
+    .iter()
+    .filter_map(|rule| {
+      let spec = CronSpec::parse(&rule.cron_like).ok()?;
+      let latest = std::iter::successors(Some(first), |minute| {
+        Some(*minute + WallDuration::minutes(1))
+      })
+      .take_while(|minute| *minute <= now)
+      .filter(|minute| spec.matches(*minute))
+      .last()?;
+      Some((latest, rule))
+    })
+    .collect();
+  // Stable, so a tie keeps list order.
+  matched.sort_by_key(|(minute, _)| *minute);
+  matched.into_iter().map(|(_, rule)| rule.clone()).collect()
+}
+
+/// The rule to act on for the minutes since `last`, the one that matched latest, and `last`
+/// moved up to `now`. Never back: after a fall-back, the repeated hour stays covered.
+pub fn take_due(
+  rules: &[ScheduleRule],
+  last: &mut NaiveDateTime,
+  now: NaiveDateTime,
+) -> Option<ScheduleRule> {
+  let fired = due(rules, *last, now).pop();
+  *last = (*last).max(now);
+  fired
+}
+
+/// Whether a rule may act: not when the user toggled the tunnel by hand since the scheduler's own
+/// last action, less than `MANUAL_GRACE` ago.
+pub fn may_fire(
+  last_user_action: Option<Instant>,
+  own_action: Option<Instant>,
+  now: Instant,
+) -> bool {
+  match last_user_action {
+    Some(user) if own_action.is_none_or(|own| user > own) => {
+      now.saturating_duration_since(user) >= MANUAL_GRACE
+    }
+    _ => true,
+  }
+}
+
+/// Adds `rule`, replacing an existing rule with the same id.
+pub fn add(config: &Config, rule: ScheduleRule) -> Result<(), BridgeError> {
+  if rule.id.trim().is_empty() {
+    return Err(BridgeError::InvalidSchedule("id is empty".to_string()));
+  }
+  CronSpec::parse(&rule.cron_like)?;
+  config.update(|settings| {
+    match settings
+      .schedules
+      .iter_mut()
+      .find(|existing| existing.id == rule.id)
+    {
+      Some(existing) => *existing = rule,
+      None => settings.schedules.push(rule),
+    }
+  })?;
+  Ok(())
+}
+
+pub fn remove(config: &Config, id: String) -> Result<(), BridgeError> {
+  if !config.get().schedules.iter().any(|rule| rule.id == id) {
+    return Err(BridgeError::UnknownSchedule { id });
+  }
+  config.update(|settings| settings.schedules.retain(|rule| rule.id != id))?;
+  Ok(())
+}
+
+/// Background task evaluating the schedule once a minute.
+#[derive(Default)]
+pub struct Scheduler {
+  task: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl Scheduler {
+  pub fn start(&self, app: AppHandle) {
+    let mut task = self.task.lock().unwrap();
+    if task.is_none() {
+      *task = Some(tauri::async_runtime::spawn(schedule_loop(app)));
+    }
+  }
+}
+
+async fn schedule_loop(app: AppHandle) {
+  let mut last = Local::now().naive_local();
+  let mut own_action = None;
+  loop {
+    tokio::time::sleep(CHECK_INTERVAL).await;
+    let now = Local::now().naive_local();
+    let (Some(bridge), Some(secrets), Some(config), Some(watchdog)) = (
+      app.try_state::<Bridge>(),
+      app.try_state::<Secrets>(),
+      app.try_state::<Config>(),
+      app.try_state::<Watchdog>(),
+    ) else {
+      continue;
+    };
+    let Some(rule) = take_due(&config.get().schedules, &mut last, now) else {
+      continue;
+    };
+    if !may_fire(watchdog.last_user_action(), own_action, Instant::now()) {
+      tracing::info!(rule = %rule.id, "schedule skipped after a manual change");
+      continue;
+    }
+    let state = commands::status(&bridge)
+      .await
+      .map_or(LinkState::Disconnected, |snapshot| snapshot.state());
+    let result = match rule.action {
+      ScheduleAction::Connect if state == LinkState::Disconnected => {
+        commands::connect(&bridge, &secrets, &config, &watchdog, None)
+          .await
+          .map(|_| ())
+      }
+      ScheduleAction::Disconnect if state != LinkState::Disconnected => {
+        commands::disconnect(&bridge, &watchdog).await
+      }
+      _ => continue,
+    };
+    own_action = Some(Instant::now());
+    if let Err(error) = result {
+      tracing::warn!(rule = %rule.id, kind = error.kind(), "scheduled action failed: {error}");
+    }
+  }
+}
+
+#[allow(non_snake_case)]
+#[tauri::command]
+pub fn listSchedules(config: tauri::State<'_, Config>) -> Vec<ScheduleRule> {
+  config.get().schedules
+}
+
+#[allow(non_snake_case)]
+#[tauri::command]
+pub fn addSchedule(
+  config: tauri::State<'_, Config>,
+  rule: ScheduleRule,
+) -> Result<(), BridgeError> {
+  add(&config, rule)
+}
+
+#[allow(non_snake_case)]
+#[tauri::command]
+pub fn removeSchedule(config: tauri::State<'_, Config>, id: String) -> Result<(), BridgeError> {
+  remove(&config, id)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use chrono::NaiveDate;
+
+  fn at(date: (i32, u32, u32), hour: u32, minute: u32) -> NaiveDateTime {
+    NaiveDate::from_ymd_opt(date.0, date.1, date.2)
+      .unwrap()
+      .and_hms_opt(hour, minute, 0)
+      .unwrap()
+  }
+
+  fn rule(id: &str, cron_like: &str, action: ScheduleAction) -> ScheduleRule {
+    ScheduleRule {
+      id: id.to_string(),
+      cron_like: cron_like.to_string(),
+      action,
+    }
+  }
+
+  fn fired(rules: &[ScheduleRule], last: NaiveDateTime, now: NaiveDateTime) -> Vec<String> {
+    due(rules, last, now)
+      .into_iter()
+      .map(|rule| rule.id)
+      .collect()
+  }
+
+  // 2024-03-11 is a Monday.
+  const MONDAY: (i32, u32, u32) = (2024, 3, 11);
+  const SATURDAY: (i32, u32, u32) = (2024, 3, 16);
+
+  #[test]
+  fn weekday_morning_rule_matches_only_on_weekdays() {
+    let spec = CronSpec::parse("0 9 * * 1-5").unwrap();
+
+    assert!(spec.matches(at(MONDAY, 9, 0)));
+    assert!(!spec.matches(at(MONDAY, 9, 1)));
+    assert!(!spec.matches(at(MONDAY, 10, 0)));
+    assert!(!spec.matches(at(SATURDAY, 9, 0)));
+  }
+
+  #[test]
+  fn supports_lists_steps_and_sunday_as_seven() {
+    let every_quarter = CronSpec::parse("*/15 * * * *").unwrap();
+    assert!(every_quarter.matches(at(MONDAY, 3, 45)));
+    assert!(!every_quarter.matches(at(MONDAY, 3, 50)));
+
+    let sunday = CronSpec::parse("30 18 * * 7").unwrap();
+    assert!(sunday.matches(at((2024, 3, 17), 18, 30)));
+    assert_eq!(sunday, CronSpec::parse("30 18 * * 0").unwrap());
+
+    let either_day = CronSpec::parse("0 12 1 * 6").unwrap();
+    assert!(either_day.matches(at(SATURDAY, 12, 0)));
+    assert!(either_day.matches(at((2024, 4, 1), 12, 0)));
+    assert!(!either_day.matches(at(MONDAY, 12, 0)));
+  }
+
+  #[test]
+  fn rejects_malformed_rules() {
+    for cron_like in [
+      "",
+      "0 9 * *",
+      "60 9 * * *",
+      "0 24 * * *",
+      "0 9 0 * *",
+      "*/0 * * * *",
+      "5-1 * * * *",
+      "a * * * *",
+    ] {
+      assert_eq!(
+        CronSpec::parse(cron_like).unwrap_err().kind(),
+        "invalidSchedule",
+        "{cron_like:?}"
+      );
+    }
+  }
+
+  #[test]
+  fn fires_once_per_matching_minute() {
+    let rules = [rule("work", "0 9 * * 1-5", ScheduleAction::Connect)];
+
+    assert_eq!(fired(&rules, at(MONDAY, 8, 59), at(MONDAY, 9, 0)), ["work"]);
+    // A second tick within the same minute.
+    let late_tick = at(MONDAY, 9, 0) + WallDuration::seconds(40);
+    assert!(fired(&rules, at(MONDAY, 9, 0), late_tick).is_empty());
+    assert!(fired(&rules, late_tick, at(MONDAY, 9, 1)).is_empty());
+  }
+
+  #[test]
+  fn a_minute_skipped_by_spring_forward_still_fires_once() {
+    // 2024-03-31 in Europe: clocks jump from 02:00 straight to 03:00, so 02:30 never happens.
+    let rules = [rule("night", "30 2 * * *", ScheduleAction::Disconnect)];
+    let before = at((2024, 3, 31), 1, 59);
+    let after = at((2024, 3, 31), 3, 0);
+
+    assert_eq!(fired(&rules, before, after), ["night"]);
+    assert!(fired(&rules, after, at((2024, 3, 31), 3, 1)).is_empty());
+  }
+
+  #[test]
+  fn the_hour_repeated_by_fall_back_does_not_fire_again() {
+    // 2024-10-27 in Europe: 02:59 is followed by 02:00 again.
+    let rules = [rule("night", "30 2 * * *", ScheduleAction::Disconnect)];
+
+    assert_eq!(
+      fired(&rules, at((2024, 10, 27), 2, 29), at((2024, 10, 27), 2, 30)),
+      ["night"]
+    );
+    assert!(fired(&rules, at((2024, 10, 27), 2, 59), at((2024, 10, 27), 2, 0)).is_empty());
+
+    // Ticking on through the repeated hour, as the loop does.
+    let mut last = at((2024, 10, 27), 2, 59);
+    for minute in 0..=59 {
+      assert_eq!(
+        take_due(&rules, &mut last, at((2024, 10, 27), 2, minute)),
+        None
+      );
+    }
+    assert_eq!(last, at((2024, 10, 27), 2, 59));
+    assert_eq!(take_due(&rules, &mut last, at((2024, 10, 27), 3, 0)), None);
+  }
+
+  #[test]
+  fn the_rule_that_matched_latest_wins_a_catch_up() {
+    let rules = [
+      rule("late", "5 9 * * *", ScheduleAction::Disconnect),
+      rule("early", "0 9 * * *", ScheduleAction::Connect),
+    ];
+    let mut last = at(MONDAY, 8, 59);
+
+    assert_eq!(fired(&rules, last, at(MONDAY, 9, 6)), ["early", "late"]);
+    assert_eq!(
+      take_due(&rules, &mut last, at(MONDAY, 9, 6)).map(|rule| rule.id),
+      Some("late".to_string())
+    );
+  }
+
+  #[test]
+  fn waking_from_a_long_sleep_does_not_replay_missed_rules() {
+    let rules = [
+      rule("morning", "0 9 * * *", ScheduleAction::Connect),
+      rule("noon", "0 12 * * *", ScheduleAction::Disconnect),
+    ];
+
+    assert_eq!(fired(&rules, at(MONDAY, 7, 0), at(MONDAY, 12, 0)), ["noon"]);
+  }
+
+  #[test]
+  fn holds_off_after_a_manual_change() {
+    let now = Instant::now() + MANUAL_GRACE * 2;
+    let recently = now - Duration::from_secs(60);
+    let long_ago = now - MANUAL_GRACE - Duration::from_secs(1);
+
+    assert!(may_fire(None, None, now));
+    assert!(!may_fire(Some(recently), None, now));
+    assert!(may_fire(Some(long_ago), None, now));
+    // The scheduler's own connect also stamps the watchdog; that is not a manual change.
+    assert!(may_fire(Some(recently), Some(recently), now));
+    assert!(!may_fire(Some(recently), Some(long_ago), now));
+  }
+
+  #[test]
+  fn rules_are_validated_and_saved() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = Config::load(dir.path().join("settings.json"));
+
+    add(
+      &config,
+      rule("work", "0 9 * * 1-5", ScheduleAction::Connect),
+    )
+    .unwrap();
+    add(
+      &config,
+      rule("work", "0 8 * * 1-5", ScheduleAction::Connect),
+    )
+    .unwrap();
+    assert!(add(&config, rule("bad", "every day", ScheduleAction::Connect)).is_err());
+
+    assert_eq!(
+      config.get().schedules,
+      [rule("work", "0 8 * * 1-5", ScheduleAction::Connect)]
+    );
+    assert_eq!(
+      remove(&config, "missing".to_string()),
+      Err(BridgeError::UnknownSchedule {
+        id: "missing".to_string()
+      })
+    );
+    remove(&config, "work".to_string()).unwrap();
+    assert!(config.get().schedules.is_empty());
+  }
+}
@@ -0,0 +1,318 @@
+use crate::base_url::normalize_base_url;
+use crate::config::Config;
+use crate::error::BridgeError;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::fmt;
+
+pub const SERVICE: &str = "com.vpnator.token";
+/// Keyring account of the token saved by `storeToken`, used when no profile is active.
+pub const TOKEN_ACCOUNT: &str = "default";
+
+/// Key/value secret storage. Production uses the OS keyring; tests use an in-memory map.
+pub trait SecretStore: Send + Sync {
+  fn get(&self, key: &str) -> Result<Option<String>, BridgeError>;
+  fn set(&self, key: &str, value: &str) -> Result<(), BridgeError>;
+  fn delete(&self, key: &str) -> Result<(), BridgeError>;
+}
+
+pub struct KeyringStore {
+  service: String,
+}
+
+impl KeyringStore {
+  pub fn new(service: impl Into<String>) -> Self {
+    Self {
+      service: service.into(),
+    }
+  }
+
+  fn entry(&self, key: &str) -> Result<keyring::Entry, BridgeError> {
+    keyring::Entry::new(&self.service, key).map_err(keyring_error)
+  }
+}
+
+impl SecretStore for KeyringStore {
+  fn get(&self, key: &str) -> Result<Option<String>, BridgeError> {
+    match self.entry(key)?.get_password() {
+      Ok(value) => Ok(Some(value)),
+      Err(keyring::Error::NoEntry) => Ok(None),
+      Err(error) => Err(keyring_error(error)),
+    }
+  }
+
+  fn set(&self, key: &str, value: &str) -> Result<(), BridgeError> {
+    self.entry(key)?.set_password(value).map_err(keyring_error)
+  }
+
+  fn delete(&self, key: &str) -> Result<(), BridgeError> {
+    match self.entry(key)?.delete_credential() {
+      Ok(()) | Err(keyring::Error::NoEntry) => Ok(()),
+      Err(error) => Err(keyring_error(error)),
+    }
+  }
+}
+
+fn keyring_error(error: keyring::Error) -> BridgeError {
+  BridgeError::SecretStoreUnavailable(error.to_string())
+}
+
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StoredToken {
+  pub base_url: String,
+  pub token: String,
+  /// Unix seconds, when the control server said so on the last `refreshToken`.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub expires_at: Option<u64>,
+}
+
+// Hand-written so a stray `{:?}` can never print the token.
+impl fmt::Debug for StoredToken {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_struct("StoredToken")
+      .field("base_url", &self.base_url)
+      .field("token", &"<redacted>")
+      .field("expires_at", &self.expires_at)
+      .finish()
+  }
+}
+
+/// Managed state owning the secret store.
+pub struct Secrets {
+  store: Box<dyn SecretStore>,
+}
+
+impl Secrets {
+  pub fn new(store: Box<dyn SecretStore>) -> Self {
+    Self { store }
+  }
+
+  pub fn keyring() -> Self {
+    Self::new(Box::new(KeyringStore::new(SERVICE)))
+  }
+
+  pub fn store_token(&self, token: &StoredToken) -> Result<(), BridgeError> {
+    self.store_token_at(TOKEN_ACCOUNT, token)
+  }
+
+  pub fn load_token(&self) -> Result<Option<StoredToken>, BridgeError> {
+    self.load_token_at(TOKEN_ACCOUNT)
+  }
+
+  pub fn clear_token(&self) -> Result<(), BridgeError> {
+    self.clear_token_at(TOKEN_ACCOUNT)
+  }
+
+  /// Like `store_token`, under a keyring account of its own (a profile's `token_ref`).
+  pub fn store_token_at(&self, account: &str, token: &StoredToken) -> Result<(), BridgeError> {
+    let encoded =
+      serde_json::to_string(token).map_err(|error| BridgeError::InvalidJson(error.to_string()))?;
+    self.store.set(account, &encoded)
+  }
+
+  pub fn load_token_at(&self, account: &str) -> Result<Option<StoredToken>, BridgeError> {
+    let Some(encoded) = self.store.get(account)? else {
+      return Ok(None);
+    };
+
+    serde_json::from_str(&encoded)
+      .map(Some)
+      .map_err(|error| BridgeError::InvalidJson(error.to_string()))
+  }
+
+  pub fn clear_token_at(&self, account: &str) -> Result<(), BridgeError> {
+    self.store.delete(account)
+  }
+
+  /// The control plane's admin API key, one keyring account per `base_url`.
+  pub fn store_admin_key(&self, base_url: &str, key: &str) -> Result<(), BridgeError> {
+    self.store.set(&admin_account(base_url), key)
+  }
+
+  pub fn load_admin_key(&self, base_url: &str) -> Result<Option<String>, BridgeError> {
+    self.store.get(&admin_account(base_url))
+  }
+
+  /// An imported server's xray outbound, which holds its UUID or password.
+  pub fn store_outbound(&self, server_id: &str, outbound: &Value) -> Result<(), BridgeError> {
+    self
+      .store
+      .set(&outbound_account(server_id), &outbound.to_string())
+  }
+
+  pub fn load_outbound(&self, server_id: &str) -> Result<Option<Value>, BridgeError> {
+    let Some(encoded) = self.store.get(&outbound_account(server_id))? else {
+      return Ok(None);
+    };
+    serde_json::from_str(&encoded)
+      .map(Some)
+      .map_err(|error| BridgeError::InvalidJson(error.to_string()))
+  }
+
+  pub fn clear_outbound(&self, server_id: &str) -> Result<(), BridgeError> {
+    self.store.delete(&outbound_account(server_id))
+  }
+
+  /// Deletes whatever is under `account` and returns whether there was anything.
+  pub fn remove(&self, account: &str) -> Result<bool, BridgeError> {
+    if self.store.get(account)?.is_none() {
+      return Ok(false);
+    }
+    self.store.delete(account)?;
+    Ok(true)
+  }
+
+  /// Payload for the bridge `connect` action. Without a usable keyring the bridge falls back
+  /// to the config it already imported.
+  pub fn connect_payload(&self) -> Value {
+    self.connect_payload_at(TOKEN_ACCOUNT)
+  }
+
+  pub fn connect_payload_at(&self, account: &str) -> Value {
+    match self.load_token_at(account) {
+      Ok(Some(stored)) => json!({ "baseUrl": stored.base_url, "token": stored.token }),
+      _ => Value::Null,
+    }
+  }
+}
+
+pub fn admin_account(base_url: &str) -> String {
+  format!("admin:{base_url}")
+}
+
+pub fn outbound_account(server_id: &str) -> String {
+  format!("imported:{server_id}")
+}
+
+#[allow(non_snake_case)]
+#[tauri::command]
+pub fn storeToken(
+  secrets: tauri::State<'_, Secrets>,
+  config: tauri::State<'_, Config>,
+  baseUrl: String,
+  token: String,
+) -> Result<(), BridgeError> {
+  secrets.store_token(&StoredToken {
+    base_url: normalize_base_url(&baseUrl, config.get().allow_insecure_base_url)?,
+    token,
+    expires_at: None,
+  })
+}
+
+#[allow(non_snake_case)]
+#[tauri::command]
+pub fn loadToken(secrets: tauri::State<'_, Secrets>) -> Result<Option<StoredToken>, BridgeError> {
+  secrets.load_token()
+}
+
+#[allow(non_snake_case)]
+#[tauri::command]
+pub fn clearToken(secrets: tauri::State<'_, Secrets>) -> Result<(), BridgeError> {
+  secrets.clear_token()
+}
+
+#[cfg(test)]
+pub mod mock {
+  use super::*;
+  use std::collections::HashMap;
+  use std::sync::Mutex;
+
+  #[derive(Default)]
+  pub struct MemoryStore {
+    entries: Mutex<HashMap<String, String>>,
+  }
+
+  impl SecretStore for MemoryStore {
+    fn get(&self, key: &str) -> Result<Option<String>, BridgeError> {
+      Ok(self.entries.lock().unwrap().get(key).cloned())
+    }
+
+    fn set(&self, key: &str, value: &str) -> Result<(), BridgeError> {
+      self
+        .entries
+        .lock()
+        .unwrap()
+        .insert(key.to_string(), value.to_string());
+      Ok(())
+    }
+
+    fn delete(&self, key: &str) -> Result<(), BridgeError> {
+      self.entries.lock().unwrap().remove(key);
+      Ok(())
+    }
+  }
+
+  /// Behaves like a headless Linux session without a secret service.
+  pub struct UnavailableStore;
+
+  impl SecretStore for UnavailableStore {
+    fn get(&self, _key: &str) -> Result<Option<String>, BridgeError> {
+      Err(BridgeError::SecretStoreUnavailable(
+        "no secret service".to_string(),
+      ))
+    }
+
+    fn set(&self, _key: &str, _value: &str) -> Result<(), BridgeError> {
+      self.get("").map(|_| ())
+    }
+
+    fn delete(&self, _key: &str) -> Result<(), BridgeError> {
+      self.get("").map(|_| ())
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::mock::{MemoryStore, UnavailableStore};
+  use super::*;
+
+  fn stored() -> StoredToken {
+    StoredToken {
+      base_url: "https://cp.example.com".to_string(),
+      token: "abcDEF123_token_value".to_string(),
+      expires_at: None,
+    }
+  }
+
+  #[test]
+  fn token_round_trips_through_the_store() {
+    let secrets = Secrets::new(Box::new(MemoryStore::default()));
+
+    assert_eq!(secrets.load_token().unwrap(), None);
+    secrets.store_token(&stored()).unwrap();
+    assert_eq!(secrets.load_token().unwrap(), Some(stored()));
+    secrets.clear_token().unwrap();
+    assert_eq!(secrets.load_token().unwrap(), None);
+  }
+
+  #[test]
+  fn connect_payload_carries_stored_credentials() {
+    let secrets = Secrets::new(Box::new(MemoryStore::default()));
+    secrets.store_token(&stored()).unwrap();
+
+    assert_eq!(
+      secrets.connect_payload(),
+      json!({ "baseUrl": "https://cp.example.com", "token": "abcDEF123_token_value" })
+    );
+  }
+
+  #[test]
+  fn unavailable_keyring_is_a_typed_error_and_connect_still_works() {
+    let secrets = Secrets::new(Box::new(UnavailableStore));
+
+    assert!(matches!(
+      secrets.store_token(&stored()),
+      Err(BridgeError::SecretStoreUnavailable(_))
+    ));
+    assert_eq!(secrets.connect_payload(), Value::Null);
+  }
+
+  #[test]
+  fn debug_output_redacts_the_token() {
+    let printed = format!("{:?}", stored());
+
+    assert!(!printed.contains("abcDEF123_token_value"));
+  }
+}
@@ -0,0 +1,258 @@
+use crate::bridge::{self, Bridge, ResolvedNode};
+use crate::config::Config;
+use crate::error::BridgeError;
+use serde::Serialize;
+use serde_json::Value;
+use std::ffi::{OsStr, OsString};
+use std::path::{Path, PathBuf};
+use tauri::Manager;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SelfTestResult {
+  pub name: &'static str,
+  pub passed: bool,
+  pub detail: String,
+}
+
+impl SelfTestResult {
+  fn new(name: &'static str, outcome: Result<String, String>) -> Self {
+    let passed = outcome.is_ok();
+    Self {
+      name,
+      passed,
+      detail: outcome.unwrap_or_else(|error| error),
+    }
+  }
+}
+
+/// What the checks look at, gathered up front so tests can hand in a broken one.
+pub struct Toolchain {
+  pub node: Result<ResolvedNode, BridgeError>,
+  pub script: PathBuf,
+  /// `PATH`, searched for xray when the bridge can't tell where it is.
+  pub path: Option<OsString>,
+}
+
+impl Toolchain {
+  /// Resolves node afresh, so a fix made since launch shows up without a restart.
+  pub fn detect(resource_dir: Option<&Path>, node_path: Option<&Path>) -> Self {
+    Self {
+      node: bridge::resolve_node(node_path),
+      script: bridge::resolve_bridge_script(resource_dir),
+      path: std::env::var_os("PATH"),
+    }
+  }
+}
+
+/// Runs every check in order; none is skipped because an earlier one failed, so the list
+/// shows everything that is wrong at once.
+pub async fn run(toolchain: &Toolchain, bridge: &Bridge) -> Vec<SelfTestResult> {
+  vec![
+    SelfTestResult::new("node", check_node(&toolchain.node)),
+    SelfTestResult::new("bridgeScript", check_script(&toolchain.script)),
+    SelfTestResult::new("bridgePing", check_ping(bridge).await),
+    SelfTestResult::new("xray", check_xray(bridge, toolchain.path.as_deref()).await),
+  ]
+}
+
+fn check_node(node: &Result<ResolvedNode, BridgeError>) -> Result<String, String> {
+  match node {
+    Ok(node) => Ok(format!(
+      "Node.js {} at {}",
+      node.version,
+      node.path.display()
+    )),
+    Err(error) => Err(error.to_string()),
+  }
+}
+
+fn check_script(script: &Path) -> Result<String, String> {
+  if !script.is_file() {
+    return Err(format!("{} does not exist", script.display()));
+  }
+  std::fs::File::open(script)
+    .map(|_| script.display().to_string())
+    .map_err(|error| format!("{} is not readable: {error}", script.display()))
+}
+
+async fn check_ping(bridge: &Bridge) -> Result<String, String> {
+  match bridge.call("ping", Value::Null).await {
+    Ok(reply) if reply["pong"] == true => Ok("The bridge answered".to_string()),
+    Ok(reply) => Err(format!("Unexpected reply: {reply}")),
+    Err(error) => Err(error.to_string()),
+  }
+}
+
+async fn check_xray(bridge: &Bridge, path: Option<&OsStr>) -> Result<String, String> {
+  let reported = bridge.call("version", Value::Null).await.ok();
+  if let Some(version) = reported.as_ref().and_then(|reply| reply["xray"].as_str()) {
+    return Ok(format!("xray {version}"));
+  }
+  match find_on_path(xray_executable(), path) {
+    Some(found) => Ok(found.display().to_string()),
+    None => Err("xray was not found; the agent reports no version and it is not on PATH".into()),
+  }
+}
+
+fn xray_executable() -> &'static str {
+  if cfg!(windows) {
+    "xray.exe"
+  } else {
+    "xray"
+  }
+}
+
+fn find_on_path(name: &str, path: Option<&OsStr>) -> Option<PathBuf> {
+  std::env::split_paths(path?)
+    .map(|dir| dir.join(name))
+    .find(|candidate| candidate.is_file())
+}
+
+#[allow(non_snake_case)]
+#[tauri::command]
+pub async fn runSelfTest(
+  app: tauri::AppHandle,
+  bridge: tauri::State<'_, Bridge>,
+  config: tauri::State<'_, Config>,
+) -> Result<Vec<SelfTestResult>, BridgeError> {
+  let resource_dir = app.path().resource_dir().ok();
+  let node_path = config.get().node_path.map(PathBuf::from);
+  // Probing node spawns processes; keep that off the async workers.
+  let toolchain = tauri::async_runtime::spawn_blocking(move || {
+    Toolchain::detect(resource_dir.as_deref(), node_path.as_deref())
+  })
+  .await
+  .map_err(|error| BridgeError::SpawnFailed(error.to_string()))?;
+  Ok(run(&toolchain, &bridge).await)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::bridge::mock::MockBridgeRunner;
+  use serde_json::json;
+  use tauri::async_runtime::block_on;
+
+  fn healthy(dir: &tempfile::TempDir) -> Toolchain {
+    let script = dir.path().join("desktop-bridge.cjs");
+    std::fs::write(&script, "").unwrap();
+    Toolchain {
+      node: Ok(ResolvedNode {
+        path: PathBuf::from("/usr/bin/node"),
+        version: bridge::REQUIRED_NODE,
+      }),
+      script,
+      path: None,
+    }
+  }
+
+  fn working_bridge() -> MockBridgeRunner {
+    MockBridgeRunner::default()
+      .respond("ping", Ok(json!({ "pong": true })))
+      .respond(
+        "version",
+        Ok(json!({ "bridge": "0.4.2", "xray": "1.8.24" })),
+      )
+  }
+
+  fn passed(results: &[SelfTestResult]) -> Vec<(&'static str, bool)> {
+    results
+      .iter()
+      .map(|result| (result.name, result.passed))
+      .collect()
+  }
+
+  #[test]
+  fn a_healthy_toolchain_passes_every_check_in_order() {
+    let dir = tempfile::tempdir().unwrap();
+
+    let results = block_on(run(&healthy(&dir), &Bridge::new(working_bridge())));
+
+    assert_eq!(
+      passed(&results),
+      [
+        ("node", true),
+        ("bridgeScript", true),
+        ("bridgePing", true),
+        ("xray", true)
+      ]
+    );
+    assert_eq!(results[3].detail, "xray 1.8.24");
+  }
+
+  #[test]
+  fn a_missing_node_fails_alone_and_the_rest_still_run() {
+    let dir = tempfile::tempdir().unwrap();
+    let toolchain = Toolchain {
+      node: Err(BridgeError::NodeTooOld {
+        found: "18.19.0".to_string(),
+        required: "20.0.0".to_string(),
+      }),
+      ..healthy(&dir)
+    };
+    let mock = working_bridge();
+
+    let results = block_on(run(&toolchain, &Bridge::new(mock.clone())));
+
+    assert_eq!(
+      passed(&results),
+      [
+        ("node", false),
+        ("bridgeScript", true),
+        ("bridgePing", true),
+        ("xray", true)
+      ]
+    );
+    assert!(results[0].detail.contains("18.19.0"));
+    assert_eq!(mock.calls().len(), 2);
+  }
+
+  #[test]
+  fn a_missing_script_and_a_dead_bridge_are_reported_separately() {
+    let dir = tempfile::tempdir().unwrap();
+    let toolchain = Toolchain {
+      script: dir.path().join("gone.cjs"),
+      ..healthy(&dir)
+    };
+    let bridge = Bridge::new(
+      MockBridgeRunner::default()
+        .respond("ping", Err(BridgeError::EmptyResponse))
+        .respond("version", Err(BridgeError::EmptyResponse)),
+    );
+
+    let results = block_on(run(&toolchain, &bridge));
+
+    assert_eq!(
+      passed(&results),
+      [
+        ("node", true),
+        ("bridgeScript", false),
+        ("bridgePing", false),
+        ("xray", false)
+      ]
+    );
+    assert!(results[1].detail.contains("gone.cjs"));
+    assert_eq!(results[2].detail, BridgeError::EmptyResponse.to_string());
+  }
+
+  #[test]
+  fn xray_on_path_passes_when_the_agent_reports_no_version() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(dir.path().join(xray_executable()), "").unwrap();
+    let toolchain = Toolchain {
+      path: Some(dir.path().as_os_str().to_owned()),
+      ..healthy(&dir)
+    };
+    let bridge = Bridge::new(
+      MockBridgeRunner::default()
+        .respond("ping", Ok(json!({ "pong": true })))
+        .respond("version", Ok(json!({ "bridge": "0.4.2", "xray": null }))),
+    );
+
+    let results = block_on(run(&toolchain, &bridge));
+
+    assert!(results[3].passed);
+    assert!(results[3].detail.ends_with(xray_executable()));
+  }
+}
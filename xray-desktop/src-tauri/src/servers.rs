@@ -0,0 +1,658 @@
+use crate::bridge::Bridge;
+use crate::config::Config;
+use crate::error::BridgeError;
+use crate::external_import;
+use crate::latency::{LatencyCache, LatencyResult};
+use crate::transport::{known_transports, Transport};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use tauri::{AppHandle, Emitter};
+
+/// One server of a `streamServers` listing (payload: `ServerInfo`).
+pub const SERVER_LISTED_EVENT: &str = "vpn://server-listed";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ServerInfo {
+  pub id: String,
+  pub name: String,
+  #[serde(default)]
+  pub country: Option<String>,
+  /// `host:port` the tunnel connects to.
+  #[serde(default)]
+  pub endpoint: Option<String>,
+  /// Provider region, e.g. `Europe`.
+  #[serde(default)]
+  pub group: Option<String>,
+  /// What `setTransport` may pin for this server; empty when the bridge doesn't say.
+  #[serde(default, deserialize_with = "known_transports")]
+  pub transports: Vec<Transport>,
+}
+
+/// Bucket for servers the provider didn't put in a region.
+pub const OTHER_GROUP: &str = "Other";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ServerGroup {
+  pub name: String,
+  pub server_ids: Vec<String>,
+}
+
+/// Groups sorted by name with `Other` last; servers keep their list order inside a group. A
+/// blank group counts as none.
+pub fn groups(servers: &[ServerInfo]) -> Vec<ServerGroup> {
+  let mut groups: Vec<ServerGroup> = Vec::new();
+  for server in servers {
+    let name = server
+      .group
+      .as_deref()
+      .map(str::trim)
+      .filter(|name| !name.is_empty())
+      .unwrap_or(OTHER_GROUP);
+    match groups.iter_mut().find(|group| group.name == name) {
+      Some(group) => group.server_ids.push(server.id.clone()),
+      None => groups.push(ServerGroup {
+        name: name.to_string(),
+        server_ids: vec![server.id.clone()],
+      }),
+    }
+  }
+  groups.sort_by(|a, b| (a.name == OTHER_GROUP, &a.name).cmp(&(b.name == OTHER_GROUP, &b.name)));
+  groups
+}
+
+/// Parses the bridge `listServers` answer, sorted by name. `null` (nothing imported yet) reads
+/// as an empty list.
+pub fn from_bridge(value: Value) -> Result<Vec<ServerInfo>, BridgeError> {
+  if value.is_null() {
+    return Ok(Vec::new());
+  }
+  let mut servers: Vec<ServerInfo> =
+    serde_json::from_value(value).map_err(|error| BridgeError::InvalidJson(error.to_string()))?;
+  // Stable, so servers sharing a name keep the bridge's order.
+  servers.sort_by(|a, b| a.name.cmp(&b.name));
+  Ok(servers)
+}
+
+pub async fn fetch(bridge: &Bridge) -> Result<Vec<ServerInfo>, BridgeError> {
+  from_bridge(bridge.call("listServers", Value::Null).await?)
+}
+
+/// The last `listServers` result followed by the imported servers; `selectServer` only accepts
+/// ids from it.
+#[derive(Default)]
+pub struct ServerCatalog {
+  servers: Mutex<Vec<ServerInfo>>,
+  imported: Mutex<Vec<ServerInfo>>,
+}
+
+impl ServerCatalog {
+  /// Replaces the provider's servers; the imported ones stay.
+  pub fn replace(&self, servers: Vec<ServerInfo>) {
+    *self.servers.lock().unwrap() = servers;
+  }
+
+  /// Set from `importedServers` at launch and after every import.
+  pub fn set_imported(&self, servers: Vec<ServerInfo>) {
+    *self.imported.lock().unwrap() = servers;
+  }
+
+  pub fn imported(&self) -> Vec<ServerInfo> {
+    self.imported.lock().unwrap().clone()
+  }
+
+  pub fn contains(&self, id: &str) -> bool {
+    self.servers().iter().any(|server| server.id == id)
+  }
+
+  pub fn servers(&self) -> Vec<ServerInfo> {
+    let mut servers = self.servers.lock().unwrap().clone();
+    servers.extend(self.imported());
+    servers
+  }
+}
+
+/// Every given criterion must match; an empty query returns the whole list.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ServerQuery {
+  /// Case-insensitive substring of the name or endpoint.
+  #[serde(default)]
+  pub text: Option<String>,
+  /// Country code, compared case-insensitively.
+  #[serde(default)]
+  pub country: Option<String>,
+  /// Servers without a measured round trip don't pass this.
+  #[serde(default)]
+  pub max_rtt_ms: Option<u32>,
+}
+
+/// Filters `servers` by `query`. With latency data, measured servers come first, fastest first;
+/// the rest keep their name order.
+pub fn filter(
+  servers: Vec<ServerInfo>,
+  latencies: &[LatencyResult],
+  query: &ServerQuery,
+) -> Vec<ServerInfo> {
+  let text = query
+    .text
+    .as_deref()
+    .map(str::trim)
+    .filter(|text| !text.is_empty())
+    .map(str::to_lowercase);
+  let rtt = |server: &ServerInfo| {
+    latencies
+      .iter()
+      .find(|result| result.server_id == server.id)
+      .and_then(|result| result.rtt_ms)
+  };
+
+  let mut matching: Vec<ServerInfo> = servers
+    .into_iter()
+    .filter(|server| {
+      text.as_ref().is_none_or(|text| {
+        server.name.to_lowercase().contains(text)
+          || server
+            .endpoint
+            .as_ref()
+            .is_some_and(|endpoint| endpoint.to_lowercase().contains(text))
+      })
+    })
+    .filter(|server| {
+      query.country.as_ref().is_none_or(|country| {
+        server
+          .country
+          .as_ref()
+          .is_some_and(|own| own.eq_ignore_ascii_case(country.trim()))
+      })
+    })
+    .filter(|server| {
+      query
+        .max_rtt_ms
+        .is_none_or(|max| rtt(server).is_some_and(|rtt| rtt <= max))
+    })
+    .collect();
+  // Stable, so ties and unmeasured servers stay sorted by name.
+  matching.sort_by_key(|server| rtt(server).unwrap_or(u32::MAX));
+  matching
+}
+
+pub async fn list(
+  bridge: &Bridge,
+  catalog: &ServerCatalog,
+) -> Result<Vec<ServerInfo>, BridgeError> {
+  catalog.replace(fetch(bridge).await?);
+  Ok(catalog.servers())
+}
+
+/// `list` for deployments too big for one answer: each server goes to `on_server` as the bridge
+/// reads it, in the bridge's order, and the catalog is replaced once all have arrived. An entry
+/// that isn't a server is skipped rather than failing the rest. Imported servers are at hand
+/// and go out first. Returns how many were listed.
+pub async fn stream(
+  bridge: &Bridge,
+  catalog: &ServerCatalog,
+  mut on_server: impl FnMut(&ServerInfo) + Send + 'static,
+) -> Result<usize, BridgeError> {
+  let imported = catalog.imported();
+  imported.iter().for_each(&mut on_server);
+  let listed = Arc::new(Mutex::new(Vec::new()));
+  let sink = listed.clone();
+  bridge
+    .call_streaming(
+      "listServers",
+      Value::Null,
+      move |record| match serde_json::from_value::<ServerInfo>(record) {
+        Ok(server) => {
+          on_server(&server);
+          sink.lock().unwrap().push(server);
+        }
+        Err(error) => tracing::debug!("skipping unparsable server: {error}"),
+      },
+    )
+    .await?;
+  let mut servers = std::mem::take(&mut *listed.lock().unwrap());
+  servers.sort_by(|a, b| a.name.cmp(&b.name));
+  let count = servers.len() + imported.len();
+  catalog.replace(servers);
+  Ok(count)
+}
+
+pub async fn select(
+  bridge: &Bridge,
+  catalog: &ServerCatalog,
+  config: &Config,
+  server_id: String,
+) -> Result<(), BridgeError> {
+  if !catalog.contains(&server_id) {
+    return Err(BridgeError::UnknownServer { id: server_id });
+  }
+  // The agent has never heard of an imported server; `connect` hands it the outbound.
+  if !external_import::is_imported(&server_id) {
+    bridge
+      .call("selectServer", json!({ "serverId": server_id }))
+      .await?;
+  }
+  config.update(|settings| settings.selected_server_id = Some(server_id))?;
+  Ok(())
+}
+
+#[allow(non_snake_case)]
+#[tauri::command]
+pub async fn listServers(
+  bridge: tauri::State<'_, Bridge>,
+  catalog: tauri::State<'_, ServerCatalog>,
+) -> Result<Vec<ServerInfo>, BridgeError> {
+  list(&bridge, &catalog).await
+}
+
+#[allow(non_snake_case)]
+#[tauri::command]
+pub async fn streamServers(
+  app: AppHandle,
+  bridge: tauri::State<'_, Bridge>,
+  catalog: tauri::State<'_, ServerCatalog>,
+) -> Result<usize, BridgeError> {
+  stream(&bridge, &catalog, move |server| {
+    let _ = app.emit(SERVER_LISTED_EVENT, server);
+  })
+  .await
+}
+
+#[allow(non_snake_case)]
+#[tauri::command]
+pub async fn selectServer(
+  bridge: tauri::State<'_, Bridge>,
+  catalog: tauri::State<'_, ServerCatalog>,
+  config: tauri::State<'_, Config>,
+  serverId: String,
+) -> Result<(), BridgeError> {
+  select(&bridge, &catalog, &config, serverId).await
+}
+
+/// Regions of the last `listServers` result; empty before the first list.
+#[allow(non_snake_case)]
+#[tauri::command]
+pub fn listServerGroups(catalog: tauri::State<'_, ServerCatalog>) -> Vec<ServerGroup> {
+  groups(&catalog.servers())
+}
+
+/// Filters the last `listServers` result, using `pingAll` latencies while they are fresh.
+#[allow(non_snake_case)]
+#[tauri::command]
+pub fn filterServers(
+  catalog: tauri::State<'_, ServerCatalog>,
+  cache: tauri::State<'_, LatencyCache>,
+  query: ServerQuery,
+) -> Vec<ServerInfo> {
+  let latencies = cache.fresh(Instant::now()).unwrap_or_default();
+  filter(catalog.servers(), &latencies, &query)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::bridge::mock::MockBridgeRunner;
+  use tauri::async_runtime::block_on;
+
+  #[test]
+  fn streamed_servers_arrive_in_bridge_order_and_fill_the_catalog() {
+    let bridge = Bridge::new(MockBridgeRunner::default().respond(
+      "listServers",
+      Ok(json!([
+        { "id": "srv-2", "name": "Frankfurt" },
+        { "id": "broken" },
+        { "id": "srv-1", "name": "Amsterdam", "group": "Europe" }
+      ])),
+    ));
+    let catalog = ServerCatalog::default();
+    let seen = Arc::new(Mutex::new(Vec::new()));
+
+    let sink = seen.clone();
+    let count = block_on(stream(&bridge, &catalog, move |server| {
+      sink.lock().unwrap().push(server.id.clone())
+    }))
+    .unwrap();
+
+    assert_eq!(count, 2);
+    assert_eq!(*seen.lock().unwrap(), ["srv-2", "srv-1"]);
+    let names: Vec<String> = catalog
+      .servers()
+      .into_iter()
+      .map(|server| server.name)
+      .collect();
+    assert_eq!(names, ["Amsterdam", "Frankfurt"]);
+  }
+
+  fn listed(mock: MockBridgeRunner) -> (Bridge, ServerCatalog) {
+    let bridge = Bridge::new(mock.respond(
+      "listServers",
+      Ok(json!([{ "id": "srv-1", "name": "Amsterdam" }, { "id": "srv-2", "name": "Frankfurt" }])),
+    ));
+    let catalog = ServerCatalog::default();
+    block_on(list(&bridge, &catalog)).unwrap();
+    (bridge, catalog)
+  }
+
+  #[test]
+  fn deserializes_and_sorts_by_name() {
+    let servers = from_bridge(json!([
+      { "id": "srv-3", "name": "Helsinki", "country": "FI", "endpoint": "hel.example.com:443" },
+      { "id": "srv-1", "name": "Amsterdam", "country": "NL", "endpoint": "ams.example.com:443" },
+      { "id": "srv-2", "name": "Frankfurt" }
+    ]))
+    .unwrap();
+
+    assert_eq!(
+      servers,
+      vec![
+        ServerInfo {
+          id: "srv-1".to_string(),
+          name: "Amsterdam".to_string(),
+          country: Some("NL".to_string()),
+          endpoint: Some("ams.example.com:443".to_string()),
+          group: None,
+          transports: Vec::new(),
+        },
+        ServerInfo {
+          id: "srv-2".to_string(),
+          name: "Frankfurt".to_string(),
+          country: None,
+          endpoint: None,
+          group: None,
+          transports: Vec::new(),
+        },
+        ServerInfo {
+          id: "srv-3".to_string(),
+          name: "Helsinki".to_string(),
+          country: Some("FI".to_string()),
+          endpoint: Some("hel.example.com:443".to_string()),
+          group: None,
+          transports: Vec::new(),
+        },
+      ]
+    );
+  }
+
+  #[test]
+  fn equal_names_keep_bridge_order() {
+    let servers = from_bridge(json!([
+      { "id": "b", "name": "Edge" },
+      { "id": "a", "name": "Edge" },
+      { "id": "c", "name": "Core" }
+    ]))
+    .unwrap();
+
+    let ids: Vec<&str> = servers.iter().map(|server| server.id.as_str()).collect();
+    assert_eq!(ids, vec!["c", "b", "a"]);
+  }
+
+  #[test]
+  fn null_is_an_empty_list() {
+    assert_eq!(from_bridge(Value::Null).unwrap(), vec![]);
+  }
+
+  #[test]
+  fn rejects_entries_without_an_id() {
+    assert!(matches!(
+      from_bridge(json!([{ "name": "Amsterdam" }])),
+      Err(BridgeError::InvalidJson(_))
+    ));
+  }
+
+  fn catalog() -> Vec<ServerInfo> {
+    from_bridge(json!([
+      { "id": "ams", "name": "Amsterdam", "country": "NL", "endpoint": "ams.example.com:443" },
+      { "id": "fra", "name": "Frankfurt", "country": "DE", "endpoint": "fra.example.com:443" },
+      { "id": "ber", "name": "Berlin", "country": "DE", "endpoint": "edge-1.example.net:443" },
+      { "id": "hel", "name": "Helsinki", "country": "FI" }
+    ]))
+    .unwrap()
+  }
+
+  fn rtt(server_id: &str, rtt_ms: Option<u32>) -> LatencyResult {
+    LatencyResult {
+      server_id: server_id.to_string(),
+      rtt_ms,
+      reachable: rtt_ms.is_some(),
+    }
+  }
+
+  fn ids(servers: &[ServerInfo]) -> Vec<&str> {
+    servers.iter().map(|server| server.id.as_str()).collect()
+  }
+
+  fn group_ids(groups: &[ServerGroup]) -> Vec<(&str, Vec<&str>)> {
+    groups
+      .iter()
+      .map(|group| {
+        let ids = group.server_ids.iter().map(String::as_str).collect();
+        (group.name.as_str(), ids)
+      })
+      .collect()
+  }
+
+  #[test]
+  fn groups_are_sorted_with_ungrouped_servers_last() {
+    let servers = from_bridge(json!([
+      { "id": "tok", "name": "Tokyo", "group": "Asia" },
+      { "id": "ams", "name": "Amsterdam", "group": "Europe" },
+      { "id": "mia", "name": "Miami" },
+      { "id": "fra", "name": "Frankfurt", "group": "Europe" },
+      { "id": "lab", "name": "Lab", "group": "  " },
+      { "id": "sgp", "name": "Singapore", "group": " Asia " }
+    ]))
+    .unwrap();
+
+    assert_eq!(
+      group_ids(&groups(&servers)),
+      [
+        ("Asia", vec!["sgp", "tok"]),
+        ("Europe", vec!["ams", "fra"]),
+        ("Other", vec!["lab", "mia"]),
+      ]
+    );
+  }
+
+  #[test]
+  fn a_provider_group_named_other_shares_the_bucket() {
+    let servers = from_bridge(json!([
+      { "id": "a", "name": "A", "group": "Other" },
+      { "id": "b", "name": "B" },
+      { "id": "c", "name": "C", "group": "Zone" }
+    ]))
+    .unwrap();
+
+    assert_eq!(
+      group_ids(&groups(&servers)),
+      [("Zone", vec!["c"]), ("Other", vec!["a", "b"])]
+    );
+    assert!(groups(&[]).is_empty());
+  }
+
+  #[test]
+  fn filter_without_latency_keeps_name_order() {
+    let query = ServerQuery {
+      country: Some("de".to_string()),
+      ..ServerQuery::default()
+    };
+
+    assert_eq!(ids(&filter(catalog(), &[], &query)), ["ber", "fra"]);
+    assert_eq!(
+      ids(&filter(catalog(), &[], &ServerQuery::default())).len(),
+      4
+    );
+  }
+
+  #[test]
+  fn text_matches_name_or_endpoint_case_insensitively() {
+    let query = |text: &str| ServerQuery {
+      text: Some(text.to_string()),
+      ..ServerQuery::default()
+    };
+
+    assert_eq!(ids(&filter(catalog(), &[], &query("FRANK"))), ["fra"]);
+    assert_eq!(ids(&filter(catalog(), &[], &query("Edge-1"))), ["ber"]);
+    assert_eq!(ids(&filter(catalog(), &[], &query("  "))).len(), 4);
+  }
+
+  #[test]
+  fn combined_filters_sort_by_latency() {
+    let latencies = [
+      rtt("fra", Some(40)),
+      rtt("ber", Some(25)),
+      rtt("ams", Some(90)),
+      rtt("hel", None),
+    ];
+    let query = ServerQuery {
+      text: Some("example".to_string()),
+      country: None,
+      max_rtt_ms: Some(50),
+    };
+
+    assert_eq!(ids(&filter(catalog(), &latencies, &query)), ["ber", "fra"]);
+    assert_eq!(
+      ids(&filter(catalog(), &latencies, &ServerQuery::default())),
+      ["ber", "fra", "ams", "hel"]
+    );
+  }
+
+  #[test]
+  fn filters_can_match_nothing() {
+    let unmeasured = ServerQuery {
+      max_rtt_ms: Some(100),
+      ..ServerQuery::default()
+    };
+    let nowhere = ServerQuery {
+      text: Some("amsterdam".to_string()),
+      country: Some("DE".to_string()),
+      max_rtt_ms: None,
+    };
+
+    assert!(filter(catalog(), &[], &unmeasured).is_empty());
+    assert!(filter(catalog(), &[], &nowhere).is_empty());
+    assert!(filter(Vec::new(), &[], &ServerQuery::default()).is_empty());
+  }
+
+  #[test]
+  fn select_forwards_and_persists_a_listed_server() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("settings.json");
+    let mock = MockBridgeRunner::default().respond("selectServer", Ok(Value::Null));
+    let (bridge, catalog) = listed(mock.clone());
+
+    block_on(select(
+      &bridge,
+      &catalog,
+      &Config::load(&path),
+      "srv-2".to_string(),
+    ))
+    .unwrap();
+
+    assert_eq!(
+      mock.calls().last().unwrap(),
+      &("selectServer".to_string(), json!({ "serverId": "srv-2" }))
+    );
+    assert_eq!(
+      Config::load(&path).get().selected_server_id.as_deref(),
+      Some("srv-2")
+    );
+  }
+
+  #[test]
+  fn select_rejects_unlisted_servers() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = Config::load(dir.path().join("settings.json"));
+    let mock = MockBridgeRunner::default();
+    let (bridge, catalog) = listed(mock.clone());
+
+    let error = block_on(select(&bridge, &catalog, &config, "srv-9".to_string())).unwrap_err();
+
+    assert_eq!(
+      error,
+      BridgeError::UnknownServer {
+        id: "srv-9".to_string()
+      }
+    );
+    assert_eq!(mock.calls().len(), 1, "only listServers reached the bridge");
+    assert_eq!(config.get().selected_server_id, None);
+  }
+
+  fn imported(id: &str, name: &str) -> ServerInfo {
+    ServerInfo {
+      id: id.to_string(),
+      name: name.to_string(),
+      country: None,
+      endpoint: Some("203.0.113.5:443".to_string()),
+      group: Some(external_import::IMPORTED_GROUP.to_string()),
+      transports: Vec::new(),
+    }
+  }
+
+  #[test]
+  fn imported_servers_follow_the_providers_and_survive_a_relist() {
+    let bridge = Bridge::new(MockBridgeRunner::default().respond(
+      "listServers",
+      Ok(json!([{ "id": "srv-1", "name": "Frankfurt" }])),
+    ));
+    let catalog = ServerCatalog::default();
+    catalog.set_imported(vec![imported("ext-0123456789ab", "Amsterdam")]);
+
+    let servers = block_on(list(&bridge, &catalog)).unwrap();
+    block_on(list(&bridge, &catalog)).unwrap();
+
+    assert_eq!(ids(&servers), ["srv-1", "ext-0123456789ab"]);
+    assert_eq!(ids(&catalog.servers()), ["srv-1", "ext-0123456789ab"]);
+    let streamed = Arc::new(Mutex::new(Vec::new()));
+    let sink = streamed.clone();
+    let count = block_on(stream(&bridge, &catalog, move |server| {
+      sink.lock().unwrap().push(server.id.clone())
+    }))
+    .unwrap();
+    assert_eq!(count, 2);
+    assert_eq!(*streamed.lock().unwrap(), ["ext-0123456789ab", "srv-1"]);
+  }
+
+  #[test]
+  fn selecting_an_imported_server_only_saves_it() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = Config::load(dir.path().join("settings.json"));
+    let mock = MockBridgeRunner::default();
+    let (bridge, catalog) = listed(mock.clone());
+    catalog.set_imported(vec![imported("ext-0123456789ab", "Amsterdam")]);
+
+    block_on(select(
+      &bridge,
+      &catalog,
+      &config,
+      "ext-0123456789ab".to_string(),
+    ))
+    .unwrap();
+
+    assert_eq!(mock.calls().len(), 1, "only listServers reached the bridge");
+    assert_eq!(
+      config.get().selected_server_id.as_deref(),
+      Some("ext-0123456789ab")
+    );
+  }
+
+  #[test]
+  fn nothing_is_selectable_before_the_first_list() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = Config::load(dir.path().join("settings.json"));
+    let bridge = Bridge::new(MockBridgeRunner::default());
+
+    assert!(matches!(
+      block_on(select(
+        &bridge,
+        &ServerCatalog::default(),
+        &config,
+        "srv-1".to_string()
+      )),
+      Err(BridgeError::UnknownServer { .. })
+    ));
+  }
+}
@@ -0,0 +1,190 @@
+use crate::bridge::{Bridge, NodeBridgeRunner};
+use crate::commands;
+use crate::config::Config;
+use crate::event_batch::EventBatcher;
+use crate::reconnect::Watchdog;
+use crate::status::LinkState;
+use crate::tray;
+use serde_json::Value;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager};
+
+pub const CONFIRM_QUIT_EVENT: &str = "vpn://confirm-quit";
+const DISCONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Tears the tunnel down exactly once, however many exit paths fire (window close, tray quit,
+/// OS shutdown).
+#[derive(Default)]
+pub struct Shutdown {
+  done: AtomicBool,
+}
+
+impl Shutdown {
+  /// Returns `false` if shutdown already ran.
+  pub fn run(&self, bridge: &NodeBridgeRunner) -> bool {
+    if self.done.swap(true, Ordering::SeqCst) {
+      return false;
+    }
+
+    // Best effort: a failed disconnect must not keep the app from exiting.
+    let _ = bridge.run_with_timeout("disconnect", Value::Null, DISCONNECT_TIMEOUT);
+    bridge.kill_all();
+    true
+  }
+
+  /// Marks shutdown as done without touching the tunnel, for quitting with it left up.
+  pub fn skip(&self) {
+    self.done.store(true, Ordering::SeqCst);
+  }
+}
+
+/// Blocks until the tunnel is down and the bridge process has been reaped.
+pub fn graceful(app: &AppHandle) {
+  if let Some(watchdog) = app.try_state::<Watchdog>() {
+    watchdog.user_disconnected();
+  }
+  if let (Some(shutdown), Some(bridge)) = (
+    app.try_state::<Shutdown>(),
+    app.try_state::<NodeBridgeRunner>(),
+  ) {
+    shutdown.run(&bridge);
+  }
+  if let Some(batcher) = app.try_state::<EventBatcher>() {
+    batcher.stop();
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CloseAction {
+  Hide,
+  Exit,
+}
+
+/// What closing the main window does. `close_to_tray` only hides it, and only when there is a
+/// tray to bring it back from; the tray's Quit still exits.
+pub fn close_action(close_to_tray: bool, has_tray: bool) -> CloseAction {
+  if close_to_tray && has_tray {
+    CloseAction::Hide
+  } else {
+    CloseAction::Exit
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QuitAction {
+  Exit,
+  /// Ask the frontend, which answers with `confirmQuit`.
+  Confirm,
+}
+
+/// A paused tunnel still counts: quitting would drop the session.
+pub fn quit_action(state: LinkState, confirm_quit: bool) -> QuitAction {
+  if confirm_quit && state != LinkState::Disconnected {
+    QuitAction::Confirm
+  } else {
+    QuitAction::Exit
+  }
+}
+
+/// The tray's Quit and closing the window: exits, or with a tunnel up and `confirmQuit` on, shows
+/// the window and emits `vpn://confirm-quit` instead.
+pub fn request_quit(app: &AppHandle) {
+  let app = app.clone();
+  tauri::async_runtime::spawn(async move {
+    let confirm_quit = app
+      .try_state::<Config>()
+      .is_some_and(|config| config.get().confirm_quit);
+    // A status that can't be read doesn't hold up quitting.
+    let state = match app.try_state::<Bridge>() {
+      Some(bridge) if confirm_quit => commands::status(&bridge)
+        .await
+        .map_or(LinkState::Disconnected, |snapshot| snapshot.state()),
+      _ => LinkState::Disconnected,
+    };
+    match quit_action(state, confirm_quit) {
+      QuitAction::Confirm => {
+        tray::show_main_window(&app);
+        let _ = app.emit(CONFIRM_QUIT_EVENT, ());
+      }
+      QuitAction::Exit => quit(&app),
+    }
+  });
+}
+
+/// The answer to `vpn://confirm-quit`. Without `disconnect` the app exits and leaves the tunnel
+/// up.
+#[allow(non_snake_case)]
+#[tauri::command]
+pub fn confirmQuit(app: AppHandle, disconnect: bool) {
+  if disconnect {
+    quit(&app);
+    return;
+  }
+  tracing::warn!("quitting with the tunnel left up");
+  if let Some(shutdown) = app.try_state::<Shutdown>() {
+    shutdown.skip();
+  }
+  app.exit(0);
+}
+
+/// Disconnects off the event loop, then exits the app.
+pub fn quit(app: &AppHandle) {
+  let app = app.clone();
+  tauri::async_runtime::spawn_blocking(move || {
+    graceful(&app);
+    app.exit(0);
+  });
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn close_hides_only_when_asked_and_a_tray_exists() {
+    assert_eq!(close_action(true, true), CloseAction::Hide);
+    assert_eq!(close_action(true, false), CloseAction::Exit);
+    assert_eq!(close_action(false, true), CloseAction::Exit);
+    assert_eq!(close_action(false, false), CloseAction::Exit);
+  }
+
+  #[test]
+  fn quit_asks_first_only_with_a_tunnel_up_and_the_guard_on() {
+    assert_eq!(quit_action(LinkState::Connected, true), QuitAction::Confirm);
+    assert_eq!(quit_action(LinkState::Paused, true), QuitAction::Confirm);
+    assert_eq!(quit_action(LinkState::Disconnected, true), QuitAction::Exit);
+    assert_eq!(quit_action(LinkState::Connected, false), QuitAction::Exit);
+  }
+
+  #[cfg(unix)]
+  #[test]
+  fn disconnects_exactly_once_across_exit_paths() {
+    let dir = tempfile::tempdir().unwrap();
+    let script = dir.path().join("stub-bridge.sh");
+    let calls = dir.path().join("calls.log");
+    std::fs::write(
+      &script,
+      format!(
+        r#"while read -r line; do
+id=$(printf '%s' "$line" | sed 's/.*"id":\([0-9]*\).*/\1/')
+action=$(printf '%s' "$line" | sed 's/.*"action":"\([^"]*\)".*/\1/')
+echo "$action" >> '{}'
+printf '{{"id":%s,"ok":true,"data":null}}\n' "$id"
+done
+"#,
+        calls.display()
+      ),
+    )
+    .unwrap();
+    let bridge = NodeBridgeRunner::new(crate::bridge::BridgeCommand::new("sh", &script));
+    let shutdown = Shutdown::default();
+
+    // Window close followed by the app exit hook, without ever connecting.
+    assert!(shutdown.run(&bridge));
+    assert!(!shutdown.run(&bridge));
+
+    assert_eq!(std::fs::read_to_string(&calls).unwrap(), "disconnect\n");
+    assert!(!bridge.is_running());
+  }
+}
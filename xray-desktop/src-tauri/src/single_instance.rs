@@ -0,0 +1,66 @@
+use crate::{deep_link, tray};
+use tauri::plugin::TauriPlugin;
+use tauri::{AppHandle, Wry};
+
+/// Keeps one app (and one bridge) per user session. A second launch hands its argv to the
+/// running instance and exits.
+pub fn init() -> TauriPlugin<Wry> {
+  tauri_plugin_single_instance::init(|app, argv, _cwd| on_second_instance(app, argv))
+}
+
+fn on_second_instance(app: &AppHandle, argv: Vec<String>) {
+  tray::show_main_window(app);
+  route_links(argv, |urls| deep_link::handle_urls(app, urls));
+}
+
+/// Passes the deep-link URLs among a forwarded argv to `import`. On Linux and Windows a clicked
+/// link arrives as `argv[1]`; the executable path and other flags are ignored.
+fn route_links(argv: Vec<String>, import: impl FnOnce(Vec<String>)) {
+  let prefix = format!("{}:", deep_link::SCHEME);
+  let urls: Vec<String> = argv
+    .into_iter()
+    .skip(1)
+    .filter(|arg| arg.to_ascii_lowercase().starts_with(&prefix))
+    .collect();
+
+  if !urls.is_empty() {
+    import(urls);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn routed(argv: &[&str]) -> Option<Vec<String>> {
+    let mut routed = None;
+    route_links(argv.iter().map(|arg| arg.to_string()).collect(), |urls| {
+      routed = Some(urls)
+    });
+    routed
+  }
+
+  #[test]
+  fn forwarded_deep_link_is_routed_to_import() {
+    let link = "xraycp://import?baseUrl=https%3A%2F%2Fcp.example.com&token=abcDEF123_token_value";
+
+    assert_eq!(
+      routed(&["/usr/bin/xray-desktop", link]),
+      Some(vec![link.to_string()])
+    );
+  }
+
+  #[test]
+  fn plain_relaunch_routes_nothing() {
+    assert_eq!(
+      routed(&["C:\\Program Files\\xray-desktop\\xray-desktop.exe"]),
+      None
+    );
+    assert_eq!(routed(&["/usr/bin/xray-desktop", "--minimized"]), None);
+  }
+
+  #[test]
+  fn executable_path_is_never_treated_as_a_link() {
+    assert_eq!(routed(&["xraycp://import"]), None);
+  }
+}
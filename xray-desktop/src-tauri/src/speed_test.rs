@@ -0,0 +1,266 @@
+use crate::bridge::Bridge;
+use crate::commands;
+use crate::error::BridgeError;
+use crate::status::LinkState;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tauri::{AppHandle, Emitter};
+
+pub const SPEED_TEST_PROGRESS_EVENT: &str = "vpn://speedtest-progress";
+/// Marks a stderr line of the bridge as a speed test report rather than a log line.
+const PROGRESS_PREFIX: &str = "@speedtest ";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SpeedTestPhase {
+  Latency,
+  Download,
+  Upload,
+  Finished,
+  Failed,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SpeedTestProgress {
+  pub phase: SpeedTestPhase,
+  /// How far into the current phase, 0 to 100.
+  #[serde(default)]
+  pub percent: Option<u8>,
+  /// Throughput measured so far in this phase.
+  #[serde(default)]
+  pub mbps: Option<f64>,
+  #[serde(default)]
+  pub detail: Option<String>,
+}
+
+impl SpeedTestProgress {
+  /// `@speedtest {"phase":"download","percent":40,"mbps":83.1}` from the bridge. As with connect
+  /// progress, the final phases come from the result instead.
+  pub fn parse(line: &str) -> Option<Self> {
+    let progress: Self = serde_json::from_str(line.strip_prefix(PROGRESS_PREFIX)?).ok()?;
+    (!matches!(
+      progress.phase,
+      SpeedTestPhase::Finished | SpeedTestPhase::Failed
+    ))
+    .then_some(progress)
+  }
+
+  /// The last event of a run.
+  pub fn finished(result: &Result<SpeedTestResult, BridgeError>) -> Self {
+    match result {
+      Ok(_) => Self {
+        phase: SpeedTestPhase::Finished,
+        percent: Some(100),
+        mbps: None,
+        detail: None,
+      },
+      Err(error) => Self {
+        phase: SpeedTestPhase::Failed,
+        percent: None,
+        mbps: None,
+        detail: Some(error.to_string()),
+      },
+    }
+  }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SpeedTestResult {
+  pub down_mbps: f64,
+  pub up_mbps: f64,
+  pub latency_ms: u32,
+  pub server_id: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Measured {
+  down_mbps: f64,
+  up_mbps: f64,
+  latency_ms: u32,
+}
+
+/// Measures through the live tunnel; a paused one carries no traffic, so it is refused too.
+/// `cancel` stops a run midway.
+pub async fn run(bridge: &Bridge) -> Result<SpeedTestResult, BridgeError> {
+  let status = commands::status(bridge).await?;
+  if status.state() != LinkState::Connected {
+    return Err(BridgeError::NotConnected);
+  }
+  let measured: Measured = serde_json::from_value(bridge.call("speedTest", Value::Null).await?)
+    .map_err(|error| BridgeError::InvalidJson(error.to_string()))?;
+  Ok(SpeedTestResult {
+    down_mbps: measured.down_mbps,
+    up_mbps: measured.up_mbps,
+    latency_ms: measured.latency_ms,
+    server_id: status.server_id.unwrap_or_default(),
+  })
+}
+
+/// Aborts a running speed test, which fails with `Cancelled`; the tunnel stays up. Returns
+/// whether there was one.
+pub fn cancel(bridge: &Bridge) -> bool {
+  if !bridge.is_running("speedTest") {
+    return false;
+  }
+  bridge.cancel();
+  true
+}
+
+#[allow(non_snake_case)]
+#[tauri::command]
+pub async fn speedTest(
+  app: AppHandle,
+  bridge: tauri::State<'_, Bridge>,
+) -> Result<SpeedTestResult, BridgeError> {
+  let result = run(&bridge).await;
+  let _ = app.emit(
+    SPEED_TEST_PROGRESS_EVENT,
+    SpeedTestProgress::finished(&result),
+  );
+  result
+}
+
+#[allow(non_snake_case)]
+#[tauri::command]
+pub fn cancelSpeedTest(bridge: tauri::State<'_, Bridge>) -> bool {
+  cancel(&bridge)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::bridge::mock::MockBridgeRunner;
+  use crate::bridge::{BridgeCommand, NodeBridgeRunner};
+  use serde_json::json;
+  use std::sync::{Arc, Mutex};
+  use std::time::Duration;
+  use tauri::async_runtime::block_on;
+
+  fn progress(phase: SpeedTestPhase, percent: Option<u8>, mbps: Option<f64>) -> SpeedTestProgress {
+    SpeedTestProgress {
+      phase,
+      percent,
+      mbps,
+      detail: None,
+    }
+  }
+
+  #[test]
+  fn parses_only_intermediate_progress_lines() {
+    assert_eq!(
+      SpeedTestProgress::parse(r#"@speedtest {"phase":"download","percent":40,"mbps":83.5}"#),
+      Some(progress(SpeedTestPhase::Download, Some(40), Some(83.5)))
+    );
+    for line in [
+      r#"@speedtest {"phase":"finished"}"#,
+      r#"@speedtest {"phase":"warp"}"#,
+      r#"@progress {"phase":"download"}"#,
+      "@speedtest not json",
+    ] {
+      assert_eq!(SpeedTestProgress::parse(line), None, "{line}");
+    }
+  }
+
+  #[test]
+  fn refuses_to_run_without_a_live_tunnel() {
+    for status in [
+      json!({ "connected": false }),
+      json!({ "connected": true, "paused": true }),
+    ] {
+      let runner = MockBridgeRunner::default().respond("status", Ok(status));
+      let bridge = Bridge::new(runner.clone());
+
+      assert_eq!(block_on(run(&bridge)), Err(BridgeError::NotConnected));
+      assert_eq!(runner.calls().len(), 1, "only status was asked");
+    }
+  }
+
+  #[test]
+  fn reports_the_server_measured_through() {
+    let bridge = Bridge::new(
+      MockBridgeRunner::default()
+        .respond(
+          "status",
+          Ok(json!({ "connected": true, "serverId": "srv-2" })),
+        )
+        .respond(
+          "speedTest",
+          Ok(json!({ "downMbps": 94.2, "upMbps": 41.0, "latencyMs": 38 })),
+        ),
+    );
+
+    assert_eq!(
+      block_on(run(&bridge)).unwrap(),
+      SpeedTestResult {
+        down_mbps: 94.2,
+        up_mbps: 41.0,
+        latency_ms: 38,
+        server_id: "srv-2".to_string(),
+      }
+    );
+  }
+
+  #[cfg(unix)]
+  #[test]
+  fn scripted_run_reports_phases_in_order() {
+    let dir = tempfile::tempdir().unwrap();
+    let script = dir.path().join("stub-bridge.sh");
+    std::fs::write(
+      &script,
+      r#"while read -r line; do
+id=$(printf '%s' "$line" | sed 's/.*"id":\([0-9]*\).*/\1/')
+case "$line" in
+  *'"action":"status"'*)
+    printf '{"id":%s,"ok":true,"data":{"connected":true,"serverId":"srv-1"}}\n' "$id" ;;
+  *)
+    echo '@speedtest {"phase":"latency"}' >&2
+    echo "warming up" >&2
+    echo '@speedtest {"phase":"download","percent":50,"mbps":80}' >&2
+    echo '@speedtest {"phase":"download","percent":100,"mbps":90}' >&2
+    echo '@speedtest {"phase":"upload","percent":100,"mbps":40}' >&2
+    printf '{"id":%s,"ok":true,"data":{"downMbps":90,"upMbps":40,"latencyMs":31}}\n' "$id" ;;
+esac
+done
+"#,
+    )
+    .unwrap();
+    let runner = NodeBridgeRunner::new(BridgeCommand::new("sh", &script));
+    let (sender, receiver) = std::sync::mpsc::channel();
+    let sender = Mutex::new(sender);
+    runner.set_log_sink(Arc::new(move |line| {
+      if let Some(progress) = SpeedTestProgress::parse(line) {
+        let _ = sender.lock().unwrap().send(progress);
+      }
+    }));
+    let bridge = Bridge::new(runner);
+
+    let result = block_on(run(&bridge));
+    let mut events: Vec<SpeedTestProgress> = (0..4)
+      .map(|_| receiver.recv_timeout(Duration::from_secs(5)).unwrap())
+      .collect();
+    events.push(SpeedTestProgress::finished(&result));
+
+    assert_eq!(
+      events,
+      [
+        progress(SpeedTestPhase::Latency, None, None),
+        progress(SpeedTestPhase::Download, Some(50), Some(80.0)),
+        progress(SpeedTestPhase::Download, Some(100), Some(90.0)),
+        progress(SpeedTestPhase::Upload, Some(100), Some(40.0)),
+        progress(SpeedTestPhase::Finished, Some(100), None),
+      ]
+    );
+    assert_eq!(result.unwrap().server_id, "srv-1");
+    assert_eq!(
+      SpeedTestProgress::finished(&Err(BridgeError::Cancelled)),
+      SpeedTestProgress {
+        phase: SpeedTestPhase::Failed,
+        percent: None,
+        mbps: None,
+        detail: Some(BridgeError::Cancelled.to_string()),
+      }
+    );
+  }
+}
@@ -0,0 +1,244 @@
+use crate::bridge::Bridge;
+use crate::config::Config;
+use crate::error::BridgeError;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::net::IpAddr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SplitKind {
+  /// Application path or bundle id whose traffic skips the tunnel.
+  App,
+  /// Destination range that skips the tunnel, e.g. `192.168.0.0/16`.
+  Cidr,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SplitRule {
+  pub id: String,
+  pub kind: SplitKind,
+  pub value: String,
+}
+
+/// Accepts `address/prefix` with an IPv4 or IPv6 address and a prefix that fits it.
+pub fn validate_cidr(value: &str) -> Result<(), BridgeError> {
+  let invalid = || BridgeError::InvalidSplitRule(format!("\"{value}\" is not a CIDR range"));
+  let (address, prefix) = value.split_once('/').ok_or_else(invalid)?;
+  let address: IpAddr = address.parse().map_err(|_| invalid())?;
+  let max_prefix = if address.is_ipv4() { 32 } else { 128 };
+  // `u8` parsing alone would let "+8" through.
+  if prefix.is_empty() || !prefix.bytes().all(|byte| byte.is_ascii_digit()) {
+    return Err(invalid());
+  }
+  match prefix.parse::<u8>() {
+    Ok(prefix) if prefix <= max_prefix => Ok(()),
+    _ => Err(invalid()),
+  }
+}
+
+pub fn validate(rule: &SplitRule) -> Result<(), BridgeError> {
+  if rule.id.trim().is_empty() {
+    return Err(BridgeError::InvalidSplitRule("id is empty".to_string()));
+  }
+  match rule.kind {
+    SplitKind::Cidr => validate_cidr(&rule.value),
+    SplitKind::App if rule.value.trim().is_empty() => Err(BridgeError::InvalidSplitRule(
+      "app path is empty".to_string(),
+    )),
+    SplitKind::App => Ok(()),
+  }
+}
+
+/// Sends the whole rule set to the bridge, then saves it. `connect` re-sends the saved set so it
+/// survives agent restarts.
+async fn apply(bridge: &Bridge, config: &Config, rules: Vec<SplitRule>) -> Result<(), BridgeError> {
+  bridge
+    .call("setSplitTunnel", json!({ "rules": rules }))
+    .await?;
+  config.update(|settings| settings.split_tunnel = rules)?;
+  Ok(())
+}
+
+/// Adds `rule`, replacing an existing rule with the same id.
+pub async fn add(bridge: &Bridge, config: &Config, rule: SplitRule) -> Result<(), BridgeError> {
+  validate(&rule)?;
+  let mut rules = config.get().split_tunnel;
+  match rules.iter_mut().find(|existing| existing.id == rule.id) {
+    Some(existing) => *existing = rule,
+    None => rules.push(rule),
+  }
+  apply(bridge, config, rules).await
+}
+
+pub async fn remove(bridge: &Bridge, config: &Config, id: String) -> Result<(), BridgeError> {
+  let mut rules = config.get().split_tunnel;
+  let before = rules.len();
+  rules.retain(|rule| rule.id != id);
+  if rules.len() == before {
+    return Err(BridgeError::UnknownSplitRule { id });
+  }
+  apply(bridge, config, rules).await
+}
+
+#[allow(non_snake_case)]
+#[tauri::command]
+pub fn getSplitTunnel(config: tauri::State<'_, Config>) -> Vec<SplitRule> {
+  config.get().split_tunnel
+}
+
+#[allow(non_snake_case)]
+#[tauri::command]
+pub async fn addSplitTunnelRule(
+  bridge: tauri::State<'_, Bridge>,
+  config: tauri::State<'_, Config>,
+  rule: SplitRule,
+) -> Result<(), BridgeError> {
+  add(&bridge, &config, rule).await
+}
+
+#[allow(non_snake_case)]
+#[tauri::command]
+pub async fn removeSplitTunnelRule(
+  bridge: tauri::State<'_, Bridge>,
+  config: tauri::State<'_, Config>,
+  id: String,
+) -> Result<(), BridgeError> {
+  remove(&bridge, &config, id).await
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::bridge::mock::MockBridgeRunner;
+  use serde_json::Value;
+  use tauri::async_runtime::block_on;
+
+  fn rule(id: &str, kind: SplitKind, value: &str) -> SplitRule {
+    SplitRule {
+      id: id.to_string(),
+      kind,
+      value: value.to_string(),
+    }
+  }
+
+  #[test]
+  fn accepts_ipv4_and_ipv6_ranges() {
+    for value in [
+      "10.0.0.0/8",
+      "192.168.1.17/32",
+      "0.0.0.0/0",
+      "fd00::/8",
+      "::1/128",
+    ] {
+      assert_eq!(validate_cidr(value), Ok(()), "{value}");
+    }
+  }
+
+  #[test]
+  fn rejects_malformed_ranges() {
+    for value in [
+      "10.0.0.0",
+      "10.0.0.0/33",
+      "fd00::/129",
+      "10.0.0/8",
+      "256.0.0.0/8",
+      "10.0.0.0/",
+      "10.0.0.0/+8",
+      "example.com/24",
+      " 10.0.0.0/8",
+    ] {
+      assert_eq!(
+        validate_cidr(value),
+        Err(BridgeError::InvalidSplitRule(format!(
+          "\"{value}\" is not a CIDR range"
+        ))),
+        "{value}"
+      );
+    }
+  }
+
+  #[test]
+  fn malformed_rule_never_reaches_the_bridge() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = Config::load(dir.path().join("settings.json"));
+    let mock = MockBridgeRunner::default();
+    let bridge = Bridge::new(mock.clone());
+
+    let error = block_on(add(
+      &bridge,
+      &config,
+      rule("lan", SplitKind::Cidr, "192.168.0.0/40"),
+    ))
+    .unwrap_err();
+
+    assert_eq!(error.kind(), "invalidSplitRule");
+    assert!(block_on(add(&bridge, &config, rule("app", SplitKind::App, " "))).is_err());
+    assert!(mock.calls().is_empty());
+    assert!(config.get().split_tunnel.is_empty());
+  }
+
+  #[test]
+  fn add_and_remove_round_trip_through_the_saved_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("settings.json");
+    let mock = MockBridgeRunner::default().respond("setSplitTunnel", Ok(Value::Null));
+    let bridge = Bridge::new(mock.clone());
+    let lan = rule("lan", SplitKind::Cidr, "192.168.0.0/16");
+    let browser = rule("browser", SplitKind::App, "/usr/bin/firefox");
+
+    block_on(add(&bridge, &Config::load(&path), lan.clone())).unwrap();
+    block_on(add(&bridge, &Config::load(&path), browser.clone())).unwrap();
+    assert_eq!(
+      Config::load(&path).get().split_tunnel,
+      vec![lan.clone(), browser.clone()]
+    );
+
+    block_on(remove(&bridge, &Config::load(&path), "lan".to_string())).unwrap();
+    assert_eq!(
+      Config::load(&path).get().split_tunnel,
+      vec![browser.clone()]
+    );
+    assert_eq!(
+      mock.calls().last(),
+      Some(&(
+        "setSplitTunnel".to_string(),
+        json!({ "rules": [{ "id": "browser", "kind": "app", "value": "/usr/bin/firefox" }] })
+      ))
+    );
+
+    assert_eq!(
+      block_on(remove(&bridge, &Config::load(&path), "lan".to_string())),
+      Err(BridgeError::UnknownSplitRule {
+        id: "lan".to_string()
+      })
+    );
+  }
+
+  #[test]
+  fn adding_an_existing_id_replaces_the_rule() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = Config::load(dir.path().join("settings.json"));
+    let bridge =
+      Bridge::new(MockBridgeRunner::default().respond("setSplitTunnel", Ok(Value::Null)));
+
+    block_on(add(
+      &bridge,
+      &config,
+      rule("lan", SplitKind::Cidr, "10.0.0.0/8"),
+    ))
+    .unwrap();
+    block_on(add(
+      &bridge,
+      &config,
+      rule("lan", SplitKind::Cidr, "172.16.0.0/12"),
+    ))
+    .unwrap();
+
+    assert_eq!(
+      config.get().split_tunnel,
+      vec![rule("lan", SplitKind::Cidr, "172.16.0.0/12")]
+    );
+  }
+}
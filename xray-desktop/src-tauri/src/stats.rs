@@ -0,0 +1,168 @@
+use crate::bridge::Bridge;
+use crate::error::BridgeError;
+use crate::event_batch;
+use crate::quality;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::sync::Mutex;
+use std::time::Duration;
+use tauri::async_runtime::JoinHandle;
+use tauri::{AppHandle, Manager};
+
+pub const STATS_EVENT: &str = "vpn://stats";
+const DEFAULT_INTERVAL: Duration = Duration::from_secs(1);
+const MIN_INTERVAL: Duration = Duration::from_millis(250);
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct TrafficStats {
+  pub uplink_bytes: u64,
+  pub downlink_bytes: u64,
+  pub uptime_secs: u64,
+  /// Round trip through the tunnel, when the agent measures it.
+  pub rtt_ms: Option<u32>,
+  /// Share of probes lost, 0 to 100, when the agent measures it.
+  pub loss_pct: Option<f64>,
+}
+
+impl TrafficStats {
+  /// The bridge answers `null` while disconnected; that reads as all zeros.
+  pub fn from_bridge(value: Value) -> Result<Self, BridgeError> {
+    if value.is_null() {
+      return Ok(Self::default());
+    }
+    serde_json::from_value(value).map_err(|error| BridgeError::InvalidJson(error.to_string()))
+  }
+}
+
+pub async fn fetch(bridge: &Bridge) -> Result<TrafficStats, BridgeError> {
+  TrafficStats::from_bridge(bridge.call("stats", Value::Null).await?)
+}
+
+/// `fetch`, feeding the sample to the connection quality indicator.
+async fn sample(app: &AppHandle, bridge: &Bridge) -> Result<TrafficStats, BridgeError> {
+  let stats = fetch(bridge).await?;
+  quality::observe(app, &stats);
+  Ok(stats)
+}
+
+/// Opt-in loop pushing `vpn://stats` so the UI can draw a live graph. Samples go out batched
+/// with `event_batch`, so a short interval doesn't mean an event each time.
+#[derive(Default)]
+pub struct StatsEmitter {
+  task: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl StatsEmitter {
+  /// (Re)starts the loop with the given interval.
+  pub fn start(&self, app: AppHandle, interval: Duration) {
+    let interval = interval.max(MIN_INTERVAL);
+    let mut task = self.task.lock().unwrap();
+    if let Some(previous) = task.take() {
+      previous.abort();
+    }
+    *task = Some(tauri::async_runtime::spawn(emit_loop(app, interval)));
+  }
+
+  pub fn stop(&self) {
+    if let Some(task) = self.task.lock().unwrap().take() {
+      task.abort();
+    }
+  }
+}
+
+async fn emit_loop(app: AppHandle, interval: Duration) {
+  let bridge = app.state::<Bridge>().inner().clone();
+
+  loop {
+    if let Ok(stats) = sample(&app, &bridge).await {
+      event_batch::emit(&app, STATS_EVENT, stats);
+    }
+
+    tokio::time::sleep(interval).await;
+  }
+}
+
+#[allow(non_snake_case)]
+#[tauri::command]
+pub async fn getStats(
+  app: AppHandle,
+  bridge: tauri::State<'_, Bridge>,
+) -> Result<TrafficStats, BridgeError> {
+  sample(&app, &bridge).await
+}
+
+#[allow(non_snake_case)]
+#[tauri::command]
+pub fn startStatsEmitter(
+  app: AppHandle,
+  emitter: tauri::State<'_, StatsEmitter>,
+  intervalMs: Option<u64>,
+) {
+  let interval = intervalMs.map_or(DEFAULT_INTERVAL, Duration::from_millis);
+  emitter.start(app, interval);
+}
+
+#[allow(non_snake_case)]
+#[tauri::command]
+pub fn stopStatsEmitter(emitter: tauri::State<'_, StatsEmitter>) {
+  emitter.stop();
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use serde_json::json;
+
+  #[test]
+  fn deserializes_connected_payload() {
+    let stats = TrafficStats::from_bridge(json!({
+      "uplinkBytes": 1_048_576,
+      "downlinkBytes": 73_400_320,
+      "uptimeSecs": 3600,
+      "rttMs": 42,
+      "lossPct": 0.5,
+      "connected": true
+    }))
+    .unwrap();
+
+    assert_eq!(
+      stats,
+      TrafficStats {
+        uplink_bytes: 1_048_576,
+        downlink_bytes: 73_400_320,
+        uptime_secs: 3600,
+        rtt_ms: Some(42),
+        loss_pct: Some(0.5),
+      }
+    );
+  }
+
+  #[test]
+  fn disconnected_payloads_are_zeroed() {
+    assert_eq!(
+      TrafficStats::from_bridge(Value::Null).unwrap(),
+      TrafficStats::default()
+    );
+    assert_eq!(
+      TrafficStats::from_bridge(json!({})).unwrap(),
+      TrafficStats::default()
+    );
+  }
+
+  #[test]
+  fn partial_payload_defaults_missing_counters() {
+    let stats = TrafficStats::from_bridge(json!({ "uptimeSecs": 12 })).unwrap();
+
+    assert_eq!(stats.uptime_secs, 12);
+    assert_eq!(stats.uplink_bytes, 0);
+  }
+
+  #[test]
+  fn rejects_negative_counters() {
+    assert!(matches!(
+      TrafficStats::from_bridge(json!({ "uplinkBytes": -1 })),
+      Err(BridgeError::InvalidJson(_))
+    ));
+  }
+}
@@ -0,0 +1,575 @@
+use crate::bandwidth::BandwidthLimit;
+use crate::bridge::Bridge;
+use crate::commands;
+use crate::config::Config;
+use crate::disconnect_timer;
+use crate::error::BridgeError;
+use crate::event_batch;
+use crate::history::{History, SessionRecorder};
+use crate::mode::Mode;
+use crate::notifications::{self, Notifier};
+use crate::quality;
+use crate::reconnect::Watchdog;
+use crate::transport::{known_transport, Transport};
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::Value;
+use std::ops::RangeInclusive;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+use tauri::async_runtime::JoinHandle;
+use tauri::{AppHandle, Emitter, Manager};
+
+pub const STATUS_CHANGED_EVENT: &str = "vpn://status-changed";
+/// Polling while a transition is likely: one was just seen or a connect/disconnect started.
+pub const FAST_POLL: Duration = Duration::from_secs(1);
+/// Polling once the state has been stable for `SETTLE_TIME`.
+pub const SLOW_POLL: Duration = Duration::from_secs(10);
+const SETTLE_TIME: Duration = Duration::from_secs(30);
+pub const POLL_RANGE_MS: RangeInclusive<u64> = 250..=60_000;
+/// Window, tray and watcher asking within this long share one bridge call.
+const CACHE_TTL: Duration = Duration::from_millis(500);
+
+/// The bridge `status` answer. Every field is optional on the wire and unknown ones are
+/// ignored, so agent schema drift degrades to defaults instead of failing the call.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct StatusSnapshot {
+  pub connected: bool,
+  #[serde(deserialize_with = "known_mode")]
+  pub mode: Option<Mode>,
+  pub server_id: Option<String>,
+  /// Seconds since the connect, when the bridge knows it.
+  pub uptime_secs: Option<u64>,
+  pub public_ip: Option<String>,
+  pub last_error: Option<String>,
+  /// Traffic outside the tunnel is being blocked.
+  pub kill_switch_active: bool,
+  /// Resolvers in effect; empty when the system ones are used.
+  pub dns: Vec<String>,
+  /// Control panel the imported config came from.
+  pub base_url: Option<String>,
+  /// Site the traffic is disguised as (the Reality server name).
+  pub disguise: Option<String>,
+  pub logs_path: Option<String>,
+  /// The session is kept but traffic is not routed through it.
+  pub paused: bool,
+  /// IPv6 goes through the tunnel; `false` when it is blocked, `None` when the agent doesn't say.
+  pub ipv6_enabled: Option<bool>,
+  /// MTU in effect on the TUN interface, when the agent reports one.
+  pub mtu: Option<u16>,
+  /// Throughput caps in effect.
+  pub bandwidth_limit: BandwidthLimit,
+  /// Transport the tunnel is using, when the agent reports one this build knows.
+  #[serde(deserialize_with = "known_transport")]
+  pub transport: Option<Transport>,
+}
+
+/// What the watcher, watchdog, tray and notifications branch on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LinkState {
+  Connected,
+  Paused,
+  Disconnected,
+}
+
+/// A mode this build doesn't know reads as `None` rather than rejecting the whole snapshot.
+fn known_mode<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Mode>, D::Error> {
+  let mode = Option::<String>::deserialize(deserializer)?;
+  Ok(mode.and_then(|mode| mode.parse().ok()))
+}
+
+impl StatusSnapshot {
+  pub fn from_bridge(value: Value) -> Result<Self, BridgeError> {
+    serde_json::from_value(value).map_err(|error| BridgeError::InvalidJson(error.to_string()))
+  }
+
+  /// Paused wins over `connected`: agents differ in what they report for a paused session.
+  pub fn state(&self) -> LinkState {
+    if self.paused {
+      LinkState::Paused
+    } else if self.connected {
+      LinkState::Connected
+    } else {
+      LinkState::Disconnected
+    }
+  }
+
+  /// Equal apart from the ever-growing uptime.
+  fn same_state(&self, other: &Self) -> bool {
+    Self {
+      uptime_secs: None,
+      ..self.clone()
+    } == Self {
+      uptime_secs: None,
+      ..other.clone()
+    }
+  }
+}
+
+/// Remembers the last observed snapshot and reports only real transitions.
+#[derive(Debug, Default)]
+pub struct StatusTracker {
+  last: Option<StatusSnapshot>,
+}
+
+impl StatusTracker {
+  pub fn state(&self) -> LinkState {
+    self
+      .last
+      .as_ref()
+      .map_or(LinkState::Disconnected, StatusSnapshot::state)
+  }
+
+  pub fn observe(&mut self, snapshot: StatusSnapshot) -> Option<&StatusSnapshot> {
+    if self
+      .last
+      .as_ref()
+      .is_some_and(|last| last.same_state(&snapshot))
+    {
+      return None;
+    }
+
+    self.last = Some(snapshot);
+    self.last.as_ref()
+  }
+}
+
+/// The last `status` answer, shared by every caller for `CACHE_TTL`.
+pub struct StatusCache {
+  ttl: Duration,
+  /// Held across the bridge call, so callers arriving meanwhile wait for that answer instead of
+  /// asking again.
+  last: tokio::sync::Mutex<Option<(Instant, StatusSnapshot)>>,
+}
+
+impl Default for StatusCache {
+  fn default() -> Self {
+    Self::with_ttl(CACHE_TTL)
+  }
+}
+
+impl StatusCache {
+  pub fn with_ttl(ttl: Duration) -> Self {
+    Self {
+      ttl,
+      last: tokio::sync::Mutex::new(None),
+    }
+  }
+
+  /// The cached snapshot while fresh, otherwise a new one from the bridge. `force_refresh`
+  /// always asks the bridge; failures are not cached.
+  pub async fn get(
+    &self,
+    bridge: &Bridge,
+    force_refresh: bool,
+  ) -> Result<StatusSnapshot, BridgeError> {
+    let mut last = self.last.lock().await;
+    if let Some((fetched, snapshot)) = last.as_ref() {
+      if !force_refresh && fetched.elapsed() < self.ttl {
+        return Ok(snapshot.clone());
+      }
+    }
+
+    let snapshot = StatusSnapshot::from_bridge(bridge.call("status", Value::Null).await?)?;
+    *last = Some((Instant::now(), snapshot.clone()));
+    Ok(snapshot)
+  }
+}
+
+pub fn validate_poll_ms(value: u64) -> Result<u64, BridgeError> {
+  if POLL_RANGE_MS.contains(&value) {
+    Ok(value)
+  } else {
+    Err(BridgeError::InvalidPollInterval(value))
+  }
+}
+
+/// How long the watcher sleeps between polls.
+#[derive(Debug, Default)]
+pub struct PollCadence {
+  fixed: Option<Duration>,
+  active_at: Option<Instant>,
+}
+
+impl PollCadence {
+  /// `None` goes back to adapting to activity.
+  pub fn set_fixed(&mut self, fixed: Option<Duration>) {
+    self.fixed = fixed;
+  }
+
+  /// A transition was seen, or an action that causes one was started.
+  pub fn activity(&mut self, now: Instant) {
+    self.active_at = Some(now);
+  }
+
+  pub fn interval(&self, now: Instant) -> Duration {
+    if let Some(fixed) = self.fixed {
+      return fixed;
+    }
+    match self.active_at {
+      Some(active_at) if now.saturating_duration_since(active_at) < SETTLE_TIME => FAST_POLL,
+      _ => SLOW_POLL,
+    }
+  }
+}
+
+/// Shared with the loop, so a new interval or a started action cuts the current sleep short.
+#[derive(Default)]
+struct Pacer {
+  cadence: Mutex<PollCadence>,
+  wake: tokio::sync::Notify,
+}
+
+/// Background loop polling the bridge, emitting `vpn://status-changed` on transitions and
+/// posting native notifications once they settle.
+#[derive(Default)]
+pub struct StatusWatcher {
+  task: Mutex<Option<JoinHandle<()>>>,
+  pacer: Arc<Pacer>,
+}
+
+impl StatusWatcher {
+  pub fn start(&self, app: AppHandle) {
+    let mut task = self.task.lock().unwrap();
+    if task.is_none() {
+      if let Some(config) = app.try_state::<Config>() {
+        self.set_interval(config.get().status_poll_ms.map(Duration::from_millis));
+      }
+      *task = Some(tauri::async_runtime::spawn(watch(app, self.pacer.clone())));
+    }
+  }
+
+  pub fn stop(&self) {
+    if let Some(task) = self.task.lock().unwrap().take() {
+      task.abort();
+    }
+  }
+
+  /// Applies to the running loop right away.
+  pub fn set_interval(&self, fixed: Option<Duration>) {
+    self.pacer.cadence.lock().unwrap().set_fixed(fixed);
+    self.pacer.wake.notify_one();
+  }
+
+  /// Polls now and fast for a while; called as a connect or disconnect starts.
+  pub fn expect_change(&self) {
+    self.pacer.cadence.lock().unwrap().activity(Instant::now());
+    self.pacer.wake.notify_one();
+  }
+}
+
+/// `StatusWatcher::expect_change` when the watcher is running.
+pub fn expect_change(app: &AppHandle) {
+  if let Some(watcher) = app.try_state::<StatusWatcher>() {
+    watcher.expect_change();
+  }
+}
+
+async fn watch(app: AppHandle, pacer: Arc<Pacer>) {
+  let bridge = app.state::<Bridge>().inner().clone();
+  let mut tracker = StatusTracker::default();
+  let mut notifier = Notifier::default();
+  let mut recorder = SessionRecorder::default();
+
+  loop {
+    // A failed poll keeps the last known state; the next successful one re-syncs it.
+    let polled = match app.try_state::<StatusCache>() {
+      Some(cache) => cache.get(&bridge, false).await,
+      None => commands::status(&bridge).await,
+    };
+    if let Ok(snapshot) = polled {
+      if let Some(notice) = notifier.observe(&snapshot, Instant::now()) {
+        notifications::post(&app, notice);
+      }
+      if let Some(event) = recorder.observe(&snapshot, SystemTime::now()) {
+        if let Some(history) = app.try_state::<History>() {
+          if let Err(error) = history.append(&event) {
+            tracing::warn!("connection history not saved: {error}");
+          }
+        }
+      }
+      let previous = tracker.state();
+      if let Some(changed) = tracker.observe(snapshot) {
+        pacer.cadence.lock().unwrap().activity(Instant::now());
+        if let Some(watchdog) = app.try_state::<Watchdog>() {
+          watchdog.on_transition(&app, previous, changed.state());
+        }
+        if changed.state() == LinkState::Disconnected {
+          // However it went down, the session's last stats and log lines go out now.
+          event_batch::flush(&app);
+          quality::reset(&app);
+          disconnect_timer::cancel(&app);
+        }
+        let _ = app.emit(STATUS_CHANGED_EVENT, changed);
+      }
+    }
+
+    let interval = pacer.cadence.lock().unwrap().interval(Instant::now());
+    let _ = tokio::time::timeout(interval, pacer.wake.notified()).await;
+  }
+}
+
+#[allow(non_snake_case)]
+#[tauri::command]
+pub fn startStatusWatcher(app: AppHandle, watcher: tauri::State<'_, StatusWatcher>) {
+  watcher.start(app);
+}
+
+#[allow(non_snake_case)]
+#[tauri::command]
+pub fn stopStatusWatcher(watcher: tauri::State<'_, StatusWatcher>) {
+  watcher.stop();
+}
+
+/// Polls every `intervalMs` from now on and saves it; `null` goes back to the adaptive interval.
+#[allow(non_snake_case)]
+#[tauri::command]
+pub fn setStatusPollInterval(
+  watcher: tauri::State<'_, StatusWatcher>,
+  config: tauri::State<'_, Config>,
+  intervalMs: Option<u64>,
+) -> Result<(), BridgeError> {
+  let interval_ms = intervalMs.map(validate_poll_ms).transpose()?;
+  config.update(|settings| settings.status_poll_ms = interval_ms)?;
+  watcher.set_interval(interval_ms.map(Duration::from_millis));
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::bridge::BridgeRunner;
+  use serde_json::json;
+  use std::sync::atomic::{AtomicUsize, Ordering};
+  use std::sync::Arc;
+  use tauri::async_runtime::block_on;
+
+  /// Answers `status` slowly enough for callers to overlap, counting bridge calls.
+  #[derive(Clone, Default)]
+  struct SlowStatus {
+    calls: Arc<AtomicUsize>,
+  }
+
+  impl BridgeRunner for SlowStatus {
+    fn run(&self, _action: &str, _payload: Value) -> Result<Value, BridgeError> {
+      let call = self.calls.fetch_add(1, Ordering::SeqCst) + 1;
+      std::thread::sleep(Duration::from_millis(50));
+      Ok(json!({ "connected": true, "uptimeSecs": call }))
+    }
+  }
+
+  fn snapshot(value: Value) -> StatusSnapshot {
+    serde_json::from_value(value).unwrap()
+  }
+
+  #[test]
+  fn deserializes_agent_status_payload() {
+    let parsed = snapshot(json!({
+      "connected": true,
+      "running": true,
+      "pid": 4242,
+      "mode": "proxy",
+      "serverId": "srv-1",
+      "uptimeSecs": 754,
+      "publicIp": "203.0.113.7",
+      "lastError": null,
+      "logsPath": "/tmp/logs",
+      "killSwitchActive": true,
+      "dns": ["1.1.1.1", "2606:4700:4700::1111"],
+      "ipv6Enabled": true,
+      "mtu": 1420,
+      "bandwidthLimit": { "upKbps": null, "downKbps": 8000 }
+    }));
+
+    assert_eq!(
+      parsed,
+      StatusSnapshot {
+        connected: true,
+        mode: Some(Mode::Proxy),
+        server_id: Some("srv-1".to_string()),
+        uptime_secs: Some(754),
+        public_ip: Some("203.0.113.7".to_string()),
+        last_error: None,
+        kill_switch_active: true,
+        dns: vec!["1.1.1.1".to_string(), "2606:4700:4700::1111".to_string()],
+        logs_path: Some("/tmp/logs".to_string()),
+        ipv6_enabled: Some(true),
+        mtu: Some(1420),
+        bandwidth_limit: BandwidthLimit {
+          up_kbps: None,
+          down_kbps: Some(8000),
+        },
+        ..StatusSnapshot::default()
+      }
+    );
+    // An agent that doesn't report IPv6 isn't taken to be blocking it.
+    assert_eq!(snapshot(json!({ "connected": true })).ipv6_enabled, None);
+  }
+
+  #[test]
+  fn paused_is_its_own_state() {
+    assert_eq!(
+      snapshot(json!({ "connected": true })).state(),
+      LinkState::Connected
+    );
+    assert_eq!(
+      snapshot(json!({ "connected": true, "paused": true })).state(),
+      LinkState::Paused
+    );
+    assert_eq!(
+      snapshot(json!({ "connected": false, "paused": true })).state(),
+      LinkState::Paused
+    );
+    assert_eq!(snapshot(json!({})).state(), LinkState::Disconnected);
+
+    let mut tracker = StatusTracker::default();
+    tracker.observe(snapshot(json!({ "connected": true })));
+    assert!(
+      tracker
+        .observe(snapshot(json!({ "connected": true, "paused": true })))
+        .is_some(),
+      "pausing is a transition"
+    );
+    assert_eq!(tracker.state(), LinkState::Paused);
+  }
+
+  #[test]
+  fn minimal_payload_fills_in_defaults() {
+    assert_eq!(
+      snapshot(json!({ "connected": true })),
+      StatusSnapshot {
+        connected: true,
+        ..StatusSnapshot::default()
+      }
+    );
+  }
+
+  #[test]
+  fn tolerates_nulls_and_unknown_modes() {
+    let parsed = snapshot(json!({
+      "connected": false,
+      "mode": "tproxy",
+      "transport": "vless+quic",
+      "serverId": null,
+      "uptimeSecs": null,
+      "importedConfig": { "serverId": "srv-1" }
+    }));
+
+    assert_eq!(parsed, StatusSnapshot::default());
+    assert_eq!(
+      snapshot(json!({ "mode": "vpn", "publicIp": "2001:db8::7", "transport": "vless+ws" })),
+      StatusSnapshot {
+        mode: Some(Mode::Vpn),
+        public_ip: Some("2001:db8::7".to_string()),
+        transport: Some(Transport::VlessWs),
+        ..StatusSnapshot::default()
+      }
+    );
+  }
+
+  #[test]
+  fn concurrent_callers_share_one_bridge_call() {
+    let runner = SlowStatus::default();
+    let bridge = Bridge::new(runner.clone());
+    let cache = Arc::new(StatusCache::default());
+
+    let snapshots = block_on(async {
+      let callers: Vec<_> = (0..8)
+        .map(|_| {
+          let (bridge, cache) = (bridge.clone(), cache.clone());
+          tauri::async_runtime::spawn(async move { cache.get(&bridge, false).await })
+        })
+        .collect();
+      let mut snapshots = Vec::new();
+      for caller in callers {
+        snapshots.push(caller.await.unwrap().unwrap());
+      }
+      snapshots
+    });
+
+    assert_eq!(runner.calls.load(Ordering::SeqCst), 1);
+    assert!(snapshots
+      .iter()
+      .all(|snapshot| snapshot.uptime_secs == Some(1)));
+  }
+
+  #[test]
+  fn cache_expires_after_the_ttl_and_can_be_bypassed() {
+    let runner = SlowStatus::default();
+    let bridge = Bridge::new(runner.clone());
+    let cache = StatusCache::with_ttl(Duration::from_millis(100));
+
+    let uptime = |force| block_on(cache.get(&bridge, force)).unwrap().uptime_secs;
+    assert_eq!(uptime(false), Some(1));
+    assert_eq!(uptime(false), Some(1), "still fresh");
+    assert_eq!(uptime(true), Some(2), "forced refresh");
+    std::thread::sleep(Duration::from_millis(120));
+    assert_eq!(uptime(false), Some(3), "expired");
+    assert_eq!(runner.calls.load(Ordering::SeqCst), 3);
+  }
+
+  #[test]
+  fn polls_fast_around_activity_and_slowly_once_settled() {
+    let start = Instant::now();
+    let mut cadence = PollCadence::default();
+    assert_eq!(cadence.interval(start), SLOW_POLL, "nothing seen yet");
+
+    cadence.activity(start);
+    assert_eq!(cadence.interval(start), FAST_POLL);
+    assert_eq!(cadence.interval(start + Duration::from_secs(29)), FAST_POLL);
+    assert_eq!(cadence.interval(start + SETTLE_TIME), SLOW_POLL);
+
+    // Another transition restarts the fast window.
+    cadence.activity(start + Duration::from_secs(40));
+    assert_eq!(cadence.interval(start + Duration::from_secs(60)), FAST_POLL);
+    assert_eq!(cadence.interval(start + Duration::from_secs(70)), SLOW_POLL);
+  }
+
+  #[test]
+  fn a_fixed_interval_overrides_the_adaptive_one() {
+    let start = Instant::now();
+    let mut cadence = PollCadence::default();
+    cadence.set_fixed(Some(Duration::from_millis(3000)));
+    cadence.activity(start);
+    assert_eq!(cadence.interval(start), Duration::from_millis(3000));
+    assert_eq!(
+      cadence.interval(start + Duration::from_secs(60)),
+      Duration::from_millis(3000)
+    );
+
+    cadence.set_fixed(None);
+    assert_eq!(cadence.interval(start), FAST_POLL);
+
+    assert_eq!(validate_poll_ms(250), Ok(250));
+    assert_eq!(validate_poll_ms(60_000), Ok(60_000));
+    assert_eq!(
+      validate_poll_ms(0),
+      Err(BridgeError::InvalidPollInterval(0))
+    );
+    assert_eq!(
+      validate_poll_ms(60_001),
+      Err(BridgeError::InvalidPollInterval(60_001))
+    );
+  }
+
+  #[test]
+  fn emits_only_on_transitions() {
+    let script = [
+      json!({ "connected": false, "mode": "proxy" }),
+      json!({ "connected": false, "mode": "proxy" }),
+      json!({ "connected": true, "mode": "proxy", "serverId": "srv-1", "uptimeSecs": 0 }),
+      json!({ "connected": true, "mode": "proxy", "serverId": "srv-1", "uptimeSecs": 2 }),
+      json!({ "connected": true, "mode": "proxy", "serverId": "srv-1", "uptimeSecs": 4 }),
+      json!({ "connected": false, "mode": "proxy", "lastError": "STARTUP_FAILED" }),
+    ];
+    let mut tracker = StatusTracker::default();
+
+    let emitted: Vec<bool> = script
+      .into_iter()
+      .filter_map(|value| tracker.observe(snapshot(value)).map(|s| s.connected))
+      .collect();
+
+    assert_eq!(emitted, vec![false, true, false]);
+  }
+}
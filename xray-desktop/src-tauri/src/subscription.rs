@@ -0,0 +1,274 @@
+use crate::base_url::normalize_base_url;
+use crate::bridge::Bridge;
+use crate::config::Config;
+use crate::deep_link;
+use crate::error::BridgeError;
+use crate::profiles;
+use crate::secrets::Secrets;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::HashSet;
+use tauri::Url;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportSummary {
+  pub imported: usize,
+  /// Entries an existing profile (or an earlier entry) already covers.
+  pub skipped: usize,
+  /// One line per rejected entry; never contains a token.
+  pub errors: Vec<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Entry {
+  #[serde(default)]
+  name: Option<String>,
+  base_url: String,
+  token: String,
+}
+
+/// An entry is either `{ name?, baseUrl, token }` or an `xraycp://import` link.
+fn parse_entry(value: Value) -> Result<Entry, BridgeError> {
+  match value {
+    Value::String(link) => deep_link::parse_import_link(&link).map(|link| Entry {
+      name: None,
+      base_url: link.base_url,
+      token: link.token,
+    }),
+    value => {
+      serde_json::from_value(value).map_err(|error| BridgeError::InvalidJson(error.to_string()))
+    }
+  }
+}
+
+/// Identifies an account across imports: the same token on the same control server.
+fn key(base_url: &str, token: &str) -> (String, String) {
+  (base_url.to_string(), token.to_string())
+}
+
+fn subscription_url(raw: &str, allow_insecure: bool) -> Result<String, BridgeError> {
+  normalize_base_url(raw, allow_insecure).map_err(|error| match error {
+    BridgeError::InvalidBaseUrl(reason) => BridgeError::InvalidSubscriptionUrl(reason),
+    error => error,
+  })
+}
+
+/// Downloads the subscription and saves a profile (token in the keyring) per new account. One
+/// broken entry only lands in `errors`; the rest are still imported.
+pub async fn import(
+  bridge: &Bridge,
+  secrets: &Secrets,
+  config: &Config,
+  url: &str,
+) -> Result<ImportSummary, BridgeError> {
+  let allow_insecure = config.get().allow_insecure_base_url;
+  let url = subscription_url(url, allow_insecure)?;
+  let reply = bridge
+    .call("importSubscription", json!({ "url": url }))
+    .await?;
+  let entries: Vec<Value> =
+    serde_json::from_value(reply).map_err(|error| BridgeError::InvalidJson(error.to_string()))?;
+
+  let mut known: HashSet<(String, String)> = config
+    .get()
+    .profiles
+    .iter()
+    .filter_map(|profile| {
+      let stored = secrets.load_token_at(&profile.token_ref).ok()??;
+      Some(key(&profile.base_url, &stored.token))
+    })
+    .collect();
+  let mut summary = ImportSummary {
+    imported: 0,
+    skipped: 0,
+    errors: Vec::new(),
+  };
+  for (index, value) in entries.into_iter().enumerate() {
+    let entry = parse_entry(value).and_then(|entry| {
+      let base_url = normalize_base_url(&entry.base_url, allow_insecure)?;
+      Ok(Entry { base_url, ..entry })
+    });
+    let entry = match entry {
+      Ok(entry) if !entry.token.trim().is_empty() => entry,
+      Ok(_) => {
+        summary
+          .errors
+          .push(format!("entry {}: token is empty", index + 1));
+        continue;
+      }
+      Err(error) => {
+        summary.errors.push(format!("entry {}: {error}", index + 1));
+        continue;
+      }
+    };
+    if !known.insert(key(&entry.base_url, &entry.token)) {
+      summary.skipped += 1;
+      continue;
+    }
+    let name = entry
+      .name
+      .map(|name| name.trim().to_string())
+      .filter(|name| !name.is_empty())
+      .or_else(|| {
+        Url::parse(&entry.base_url)
+          .ok()?
+          .host_str()
+          .map(str::to_string)
+      })
+      .unwrap_or_else(|| entry.base_url.clone());
+    match profiles::create(secrets, config, name, entry.base_url, entry.token) {
+      Ok(_) => summary.imported += 1,
+      Err(error) => summary.errors.push(format!("entry {}: {error}", index + 1)),
+    }
+  }
+  Ok(summary)
+}
+
+#[allow(non_snake_case)]
+#[tauri::command]
+pub async fn importSubscription(
+  bridge: tauri::State<'_, Bridge>,
+  secrets: tauri::State<'_, Secrets>,
+  config: tauri::State<'_, Config>,
+  url: String,
+) -> Result<ImportSummary, BridgeError> {
+  import(&bridge, &secrets, &config, &url).await
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::bridge::mock::MockBridgeRunner;
+  use crate::secrets::mock::MemoryStore;
+  use tauri::async_runtime::block_on;
+
+  const TOKEN_A: &str = "abcDEF123_token_value";
+  const TOKEN_B: &str = "xyzUVW789_other_token";
+
+  fn subscription(entries: Value) -> (MockBridgeRunner, Bridge) {
+    let mock = MockBridgeRunner::default().respond("importSubscription", Ok(entries));
+    (mock.clone(), Bridge::new(mock))
+  }
+
+  fn names(config: &Config) -> Vec<String> {
+    config
+      .get()
+      .profiles
+      .into_iter()
+      .map(|profile| profile.name)
+      .collect()
+  }
+
+  #[test]
+  fn imports_objects_and_links_and_skips_known_accounts() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = Config::load(dir.path().join("settings.json"));
+    let secrets = Secrets::new(Box::new(MemoryStore::default()));
+    profiles::create(
+      &secrets,
+      &config,
+      "Mine".to_string(),
+      "https://cp.example.com".to_string(),
+      TOKEN_A.to_string(),
+    )
+    .unwrap();
+    let (mock, bridge) = subscription(json!([
+      // Already saved, even though the URL is written differently.
+      { "name": "Amsterdam", "baseUrl": "cp.example.com/", "token": TOKEN_A },
+      { "name": "Frankfurt", "baseUrl": "https://cp.example.com", "token": TOKEN_B },
+      format!("xraycp://import?baseUrl=https%3A%2F%2Fcp2.example.com&token={TOKEN_A}"),
+      // Repeats the entry before it.
+      format!("xraycp://import?baseUrl=https://cp2.example.com&token={TOKEN_A}")
+    ]));
+
+    let summary = block_on(import(
+      &bridge,
+      &secrets,
+      &config,
+      "https://sub.example.com/s/42?fmt=json",
+    ))
+    .unwrap();
+
+    assert_eq!(
+      summary,
+      ImportSummary {
+        imported: 2,
+        skipped: 2,
+        errors: Vec::new(),
+      }
+    );
+    assert_eq!(names(&config), ["Mine", "Frankfurt", "cp2.example.com"]);
+    let saved = &config.get().profiles[2];
+    assert_eq!(
+      secrets
+        .load_token_at(&saved.token_ref)
+        .unwrap()
+        .unwrap()
+        .token,
+      TOKEN_A
+    );
+    assert_eq!(
+      mock.calls()[0],
+      (
+        "importSubscription".to_string(),
+        json!({ "url": "https://sub.example.com/s/42?fmt=json" })
+      )
+    );
+  }
+
+  #[test]
+  fn broken_entries_are_reported_and_the_rest_imported() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = Config::load(dir.path().join("settings.json"));
+    let secrets = Secrets::new(Box::new(MemoryStore::default()));
+    let (_, bridge) = subscription(json!([
+      { "name": "No token", "baseUrl": "https://cp.example.com" },
+      { "name": "Plain http", "baseUrl": "http://cp.example.com", "token": TOKEN_A },
+      "xraycp://import?baseUrl=https://cp.example.com&token=short",
+      { "name": "Empty", "baseUrl": "https://cp.example.com", "token": " " },
+      { "name": "Good", "baseUrl": "https://cp.example.com", "token": TOKEN_B }
+    ]));
+
+    let summary = block_on(import(
+      &bridge,
+      &secrets,
+      &config,
+      "https://sub.example.com/s/42",
+    ))
+    .unwrap();
+
+    assert_eq!(summary.imported, 1);
+    assert_eq!(summary.skipped, 0);
+    assert_eq!(summary.errors.len(), 4);
+    assert!(summary.errors[0].starts_with("entry 1: "));
+    assert!(summary.errors[1].starts_with("entry 2: Invalid base URL"));
+    assert_eq!(
+      summary.errors[2],
+      "entry 3: Invalid deep link: invalid token format"
+    );
+    assert_eq!(summary.errors[3], "entry 4: token is empty");
+    assert!(summary.errors.iter().all(|error| !error.contains(TOKEN_A)));
+    assert_eq!(names(&config), ["Good"]);
+  }
+
+  #[test]
+  fn the_url_is_checked_before_the_download() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = Config::load(dir.path().join("settings.json"));
+    let secrets = Secrets::new(Box::new(MemoryStore::default()));
+    let (mock, bridge) = subscription(json!([]));
+
+    let error = block_on(import(
+      &bridge,
+      &secrets,
+      &config,
+      "http://sub.example.com/s/42",
+    ))
+    .unwrap_err();
+
+    assert_eq!(error.kind(), "invalidSubscriptionUrl");
+    assert!(mock.calls().is_empty());
+  }
+}
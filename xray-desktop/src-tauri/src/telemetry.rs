@@ -0,0 +1,362 @@
+use crate::config::{Config, Settings};
+use crate::error::BridgeError;
+use crate::persist;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tauri::{AppHandle, Manager};
+
+const TELEMETRY_FILE: &str = "telemetry.json";
+/// Upper bounds of the histogram buckets; slower calls land in a last, open-ended one.
+const BUCKETS_MS: [u64; 12] = [
+  10, 25, 50, 100, 250, 500, 1_000, 2_500, 5_000, 10_000, 30_000, 60_000,
+];
+/// The summary on disk is rewritten after this many new samples.
+const SAVE_EVERY: u32 = 50;
+/// A summary older than this is started over, so it reflects the current version.
+const ROLL_AFTER_SECS: u64 = 7 * 24 * 60 * 60;
+
+/// Counts per bucket for one bridge action.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct Histogram {
+  pub buckets: Vec<u64>,
+  pub errors: u64,
+  pub max_ms: u64,
+}
+
+impl Histogram {
+  pub fn record(&mut self, elapsed: Duration, failed: bool) {
+    let ms = u64::try_from(elapsed.as_millis()).unwrap_or(u64::MAX);
+    self.buckets.resize(BUCKETS_MS.len() + 1, 0);
+    let index = BUCKETS_MS
+      .iter()
+      .position(|&bound| ms <= bound)
+      .unwrap_or(BUCKETS_MS.len());
+    self.buckets[index] += 1;
+    self.errors += u64::from(failed);
+    self.max_ms = self.max_ms.max(ms);
+  }
+
+  pub fn count(&self) -> u64 {
+    self.buckets.iter().sum()
+  }
+
+  /// The bucket bound at or under which `percent` of the calls finished; the slowest call when
+  /// that bucket is the open-ended one, and never more than it.
+  pub fn percentile(&self, percent: u64) -> Option<u64> {
+    let count = self.count();
+    if count == 0 {
+      return None;
+    }
+    let rank = (count * percent).div_ceil(100).max(1);
+    let mut seen = 0;
+    for (index, bucket) in self.buckets.iter().enumerate() {
+      seen += bucket;
+      if seen >= rank {
+        let bound = BUCKETS_MS.get(index).copied().unwrap_or(self.max_ms);
+        return Some(bound.min(self.max_ms));
+      }
+    }
+    Some(self.max_ms)
+  }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+struct Summary {
+  /// Unix seconds of the first sample.
+  since: u64,
+  actions: BTreeMap<String, Histogram>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ActionTiming {
+  pub action: String,
+  pub count: u64,
+  pub errors: u64,
+  pub p50_ms: u64,
+  pub p95_ms: u64,
+  pub max_ms: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TelemetryReport {
+  pub enabled: bool,
+  /// Unix seconds of the oldest sample in `actions`; `0` when there are none.
+  pub since: u64,
+  /// Sorted by action.
+  pub actions: Vec<ActionTiming>,
+}
+
+fn unix_secs(now: SystemTime) -> u64 {
+  now.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs()
+}
+
+fn io_error(error: impl std::fmt::Display) -> BridgeError {
+  BridgeError::TelemetryIo(error.to_string())
+}
+
+/// Per-action call timings, only ever kept on this machine: in memory and as
+/// `telemetry.json` in the app data dir.
+pub struct Telemetry {
+  path: PathBuf,
+  state: Mutex<(Summary, u32)>,
+}
+
+impl Telemetry {
+  pub fn for_app(app: &AppHandle) -> tauri::Result<Self> {
+    Ok(Self::in_dir(&app.path().app_data_dir()?))
+  }
+
+  /// A missing or unreadable summary starts empty.
+  pub fn in_dir(data_dir: &Path) -> Self {
+    let path = data_dir.join(TELEMETRY_FILE);
+    let summary = persist::load(&path, |raw| serde_json::from_str(raw).ok()).unwrap_or_default();
+    Self {
+      path,
+      state: Mutex::new((summary, 0)),
+    }
+  }
+
+  pub fn record(&self, action: &str, elapsed: Duration, failed: bool, now: SystemTime) {
+    let mut state = self.state.lock().unwrap();
+    let (summary, unsaved) = &mut *state;
+    let now = unix_secs(now);
+    if summary.actions.is_empty() || now.saturating_sub(summary.since) > ROLL_AFTER_SECS {
+      *summary = Summary {
+        since: now,
+        actions: BTreeMap::new(),
+      };
+    }
+    summary
+      .actions
+      .entry(action.to_string())
+      .or_default()
+      .record(elapsed, failed);
+    *unsaved += 1;
+    if *unsaved >= SAVE_EVERY {
+      *unsaved = 0;
+      if let Err(error) = self.save(summary) {
+        tracing::warn!("failed to save telemetry: {error}");
+      }
+    }
+  }
+
+  pub fn flush(&self) -> Result<(), BridgeError> {
+    let mut state = self.state.lock().unwrap();
+    state.1 = 0;
+    self.save(&state.0)
+  }
+
+  /// Drops every sample and deletes the file; returns whether there was one.
+  pub fn clear(&self) -> Result<bool, BridgeError> {
+    let mut state = self.state.lock().unwrap();
+    *state = Default::default();
+    persist::remove(&self.path).map_err(io_error)
+  }
+
+  fn save(&self, summary: &Summary) -> Result<(), BridgeError> {
+    let json = serde_json::to_string_pretty(summary).map_err(io_error)?;
+    persist::atomic_write(&self.path, json).map_err(io_error)
+  }
+
+  pub fn report(&self, enabled: bool) -> TelemetryReport {
+    let state = self.state.lock().unwrap();
+    let summary = &state.0;
+    TelemetryReport {
+      enabled,
+      since: if summary.actions.is_empty() {
+        0
+      } else {
+        summary.since
+      },
+      actions: summary
+        .actions
+        .iter()
+        .map(|(action, histogram)| ActionTiming {
+          action: action.clone(),
+          count: histogram.count(),
+          errors: histogram.errors,
+          p50_ms: histogram.percentile(50).unwrap_or(0),
+          p95_ms: histogram.percentile(95).unwrap_or(0),
+          max_ms: histogram.max_ms,
+        })
+        .collect(),
+    }
+  }
+}
+
+/// Records a call `Bridge::set_timing_sink` reported, unless `telemetryLocal` is off.
+pub fn sample(
+  settings: &Settings,
+  telemetry: &Telemetry,
+  action: &str,
+  elapsed: Duration,
+  error: Option<&str>,
+) {
+  if settings.telemetry_local {
+    telemetry.record(action, elapsed, error.is_some(), SystemTime::now());
+  }
+}
+
+pub fn observe(app: &AppHandle, action: &str, elapsed: Duration, error: Option<&str>) {
+  if let (Some(config), Some(telemetry)) = (app.try_state::<Config>(), app.try_state::<Telemetry>())
+  {
+    sample(&config.get(), &telemetry, action, elapsed, error);
+  }
+}
+
+/// p50/p95 per bridge action. Also writes the summary out, so it survives a crash.
+#[allow(non_snake_case)]
+#[tauri::command]
+pub fn getTelemetry(
+  config: tauri::State<'_, Config>,
+  telemetry: tauri::State<'_, Telemetry>,
+) -> Result<TelemetryReport, BridgeError> {
+  let enabled = config.get().telemetry_local;
+  if enabled {
+    telemetry.flush()?;
+  }
+  Ok(telemetry.report(enabled))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::fs;
+
+  fn ms(ms: u64) -> Duration {
+    Duration::from_millis(ms)
+  }
+
+  fn at(secs: u64) -> SystemTime {
+    UNIX_EPOCH + Duration::from_secs(secs)
+  }
+
+  #[test]
+  fn percentiles_come_from_the_bucket_bounds() {
+    let mut histogram = Histogram::default();
+    for _ in 0..90 {
+      histogram.record(ms(40), false);
+    }
+    for _ in 0..9 {
+      histogram.record(ms(700), false);
+    }
+    histogram.record(ms(95_000), true);
+
+    assert_eq!(histogram.count(), 100);
+    assert_eq!(histogram.errors, 1);
+    assert_eq!(histogram.percentile(50), Some(50));
+    assert_eq!(histogram.percentile(95), Some(1_000));
+    assert_eq!(histogram.percentile(100), Some(95_000));
+    assert_eq!(Histogram::default().percentile(50), None);
+  }
+
+  #[test]
+  fn a_single_fast_call_reports_its_own_time() {
+    let mut histogram = Histogram::default();
+    histogram.record(ms(3), false);
+
+    assert_eq!(histogram.percentile(50), Some(3));
+    assert_eq!(histogram.percentile(95), Some(3));
+  }
+
+  #[test]
+  fn the_report_is_per_action_and_survives_a_restart() {
+    let dir = tempfile::tempdir().unwrap();
+    let telemetry = Telemetry::in_dir(dir.path());
+    telemetry.record("status", ms(20), false, at(1_000));
+    telemetry.record("status", ms(30), false, at(1_001));
+    telemetry.record("connect", ms(2_000), true, at(1_002));
+    telemetry.flush().unwrap();
+
+    let report = Telemetry::in_dir(dir.path()).report(true);
+
+    assert_eq!(report.since, 1_000);
+    assert_eq!(
+      report.actions,
+      [
+        ActionTiming {
+          action: "connect".to_string(),
+          count: 1,
+          errors: 1,
+          p50_ms: 2_000,
+          p95_ms: 2_000,
+          max_ms: 2_000,
+        },
+        ActionTiming {
+          action: "status".to_string(),
+          count: 2,
+          errors: 0,
+          p50_ms: 25,
+          p95_ms: 30,
+          max_ms: 30,
+        },
+      ]
+    );
+  }
+
+  #[test]
+  fn a_stale_summary_starts_over() {
+    let dir = tempfile::tempdir().unwrap();
+    let telemetry = Telemetry::in_dir(dir.path());
+    telemetry.record("status", ms(20), false, at(0));
+    telemetry.record("connect", ms(900), false, at(ROLL_AFTER_SECS + 1));
+
+    let report = telemetry.report(true);
+    assert_eq!(report.since, ROLL_AFTER_SECS + 1);
+    assert_eq!(report.actions.len(), 1);
+    assert_eq!(report.actions[0].action, "connect");
+  }
+
+  #[test]
+  fn nothing_is_recorded_by_default() {
+    let dir = tempfile::tempdir().unwrap();
+    let telemetry = Telemetry::in_dir(dir.path());
+    let mut settings = Settings::default();
+    assert!(!settings.telemetry_local);
+
+    sample(&settings, &telemetry, "status", ms(20), None);
+    assert!(telemetry.report(false).actions.is_empty());
+
+    settings.telemetry_local = true;
+    sample(
+      &settings,
+      &telemetry,
+      "status",
+      ms(20),
+      Some("emptyResponse"),
+    );
+    assert_eq!(telemetry.report(true).actions[0].errors, 1);
+  }
+
+  #[test]
+  fn only_action_names_and_numbers_reach_the_disk() {
+    let dir = tempfile::tempdir().unwrap();
+    let telemetry = Telemetry::in_dir(dir.path());
+    for _ in 0..SAVE_EVERY {
+      telemetry.record("importToken", ms(120), false, at(0));
+    }
+
+    let on_disk: serde_json::Value =
+      serde_json::from_str(&fs::read_to_string(dir.path().join(TELEMETRY_FILE)).unwrap()).unwrap();
+    assert_eq!(
+      on_disk,
+      serde_json::json!({
+        "since": 0,
+        "actions": {
+          "importToken": {
+            "buckets": [0, 0, 0, 0, 50, 0, 0, 0, 0, 0, 0, 0, 0],
+            "errors": 0,
+            "maxMs": 120
+          }
+        }
+      })
+    );
+  }
+}
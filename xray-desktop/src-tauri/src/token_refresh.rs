@@ -0,0 +1,222 @@
+use crate::bridge::Bridge;
+use crate::config::Config;
+use crate::error::BridgeError;
+use crate::profiles;
+use crate::secrets::{Secrets, StoredToken};
+use serde::Deserialize;
+use serde_json::json;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tauri::async_runtime::JoinHandle;
+use tauri::{AppHandle, Manager};
+
+/// A day's notice leaves room for a machine that was asleep or offline.
+pub const DEFAULT_REFRESH_WINDOW_SECS: u64 = 24 * 60 * 60;
+const CHECK_INTERVAL: Duration = Duration::from_secs(30 * 60);
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Refreshed {
+  token: String,
+  expires_at: Option<u64>,
+}
+
+fn now_secs() -> u64 {
+  SystemTime::now()
+    .duration_since(UNIX_EPOCH)
+    .map(|elapsed| elapsed.as_secs())
+    .unwrap_or_default()
+}
+
+/// Trades the token `connect` would send for a fresh one and saves it in its place. Returns the
+/// new expiry (unix seconds) if the server gave one.
+pub async fn refresh(
+  bridge: &Bridge,
+  secrets: &Secrets,
+  config: &Config,
+) -> Result<Option<u64>, BridgeError> {
+  let settings = config.get();
+  let account = profiles::token_account(&settings);
+  // Nothing to refresh with: the user has to import again, same as a rejected token.
+  let Some(stored) = secrets.load_token_at(account)? else {
+    return Err(BridgeError::AuthExpired);
+  };
+
+  let reply = bridge
+    .call(
+      "refreshToken",
+      json!({ "baseUrl": stored.base_url, "token": stored.token }),
+    )
+    .await
+    .map_err(BridgeError::with_auth_code)?;
+  let refreshed: Refreshed =
+    serde_json::from_value(reply).map_err(|error| BridgeError::InvalidJson(error.to_string()))?;
+
+  secrets.store_token_at(
+    account,
+    &StoredToken {
+      base_url: stored.base_url,
+      token: refreshed.token,
+      expires_at: refreshed.expires_at,
+    },
+  )?;
+  Ok(refreshed.expires_at)
+}
+
+/// Whether a token expiring at `expires_at` is inside the refresh window. Tokens without a known
+/// expiry are left alone.
+pub fn due(expires_at: Option<u64>, window_secs: u64, now: u64) -> bool {
+  window_secs > 0
+    && expires_at.is_some_and(|expires_at| expires_at <= now.saturating_add(window_secs))
+}
+
+/// Background check renewing the token once it is close to expiry.
+#[derive(Default)]
+pub struct TokenRefresher {
+  task: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl TokenRefresher {
+  pub fn start(&self, app: AppHandle) {
+    let mut task = self.task.lock().unwrap();
+    if task.is_none() {
+      *task = Some(tauri::async_runtime::spawn(refresh_loop(app)));
+    }
+  }
+}
+
+async fn refresh_loop(app: AppHandle) {
+  loop {
+    if let (Some(bridge), Some(secrets), Some(config)) = (
+      app.try_state::<Bridge>(),
+      app.try_state::<Secrets>(),
+      app.try_state::<Config>(),
+    ) {
+      let settings = config.get();
+      let expires_at = secrets
+        .load_token_at(profiles::token_account(&settings))
+        .ok()
+        .flatten()
+        .and_then(|stored| stored.expires_at);
+      if due(expires_at, settings.token_refresh_window_secs, now_secs()) {
+        if let Err(error) = refresh(&bridge, &secrets, &config).await {
+          tracing::warn!(
+            kind = error.kind(),
+            "background token refresh failed: {error}"
+          );
+        }
+      }
+    }
+    tokio::time::sleep(CHECK_INTERVAL).await;
+  }
+}
+
+#[allow(non_snake_case)]
+#[tauri::command]
+pub async fn refreshToken(
+  bridge: tauri::State<'_, Bridge>,
+  secrets: tauri::State<'_, Secrets>,
+  config: tauri::State<'_, Config>,
+) -> Result<Option<u64>, BridgeError> {
+  refresh(&bridge, &secrets, &config).await
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::bridge::mock::MockBridgeRunner;
+  use crate::secrets::mock::MemoryStore;
+  use serde_json::Value;
+  use tauri::async_runtime::block_on;
+
+  fn secrets_with_token() -> Secrets {
+    let secrets = Secrets::new(Box::new(MemoryStore::default()));
+    secrets
+      .store_token(&StoredToken {
+        base_url: "https://cp.example.com".to_string(),
+        token: "old_token_value".to_string(),
+        expires_at: Some(1_700_000_000),
+      })
+      .unwrap();
+    secrets
+  }
+
+  #[test]
+  fn successful_refresh_replaces_the_stored_token() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = Config::load(dir.path().join("settings.json"));
+    let secrets = secrets_with_token();
+    let mock = MockBridgeRunner::default().respond(
+      "refreshToken",
+      Ok(json!({ "token": "new_token_value", "expiresAt": 1_800_000_000 })),
+    );
+
+    let expiry = block_on(refresh(&Bridge::new(mock.clone()), &secrets, &config));
+
+    assert_eq!(expiry, Ok(Some(1_800_000_000)));
+    assert_eq!(
+      mock.calls(),
+      [(
+        "refreshToken".to_string(),
+        json!({ "baseUrl": "https://cp.example.com", "token": "old_token_value" })
+      )]
+    );
+    assert_eq!(
+      secrets.load_token().unwrap(),
+      Some(StoredToken {
+        base_url: "https://cp.example.com".to_string(),
+        token: "new_token_value".to_string(),
+        expires_at: Some(1_800_000_000),
+      })
+    );
+  }
+
+  #[test]
+  fn rejected_refresh_is_auth_expired_and_keeps_the_old_token() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = Config::load(dir.path().join("settings.json"));
+    let secrets = secrets_with_token();
+    let bridge = Bridge::new(MockBridgeRunner::default().respond(
+      "refreshToken",
+      Err(BridgeError::BridgeReported {
+        code: Some("AUTH_EXPIRED".to_string()),
+        message: "AUTH_EXPIRED: token expired".to_string(),
+      }),
+    ));
+
+    assert_eq!(
+      block_on(refresh(&bridge, &secrets, &config)),
+      Err(BridgeError::AuthExpired)
+    );
+    assert_eq!(
+      secrets.load_token().unwrap().map(|stored| stored.token),
+      Some("old_token_value".to_string())
+    );
+  }
+
+  #[test]
+  fn missing_token_is_auth_expired() {
+    let dir = tempfile::tempdir().unwrap();
+    let mock = MockBridgeRunner::default();
+
+    assert_eq!(
+      block_on(refresh(
+        &Bridge::new(mock.clone()),
+        &Secrets::new(Box::new(MemoryStore::default())),
+        &Config::load(dir.path().join("settings.json"))
+      )),
+      Err(BridgeError::AuthExpired)
+    );
+    assert_eq!(mock.calls(), Vec::<(String, Value)>::new());
+  }
+
+  #[test]
+  fn refresh_is_due_only_inside_the_window() {
+    let now = 1_700_000_000;
+    assert!(due(Some(now + 60), 3600, now));
+    assert!(due(Some(now - 60), 3600, now), "already expired");
+    assert!(!due(Some(now + 7200), 3600, now));
+    assert!(!due(None, 3600, now));
+    assert!(!due(Some(now + 60), 0, now), "disabled");
+  }
+}
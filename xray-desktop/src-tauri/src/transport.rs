@@ -0,0 +1,288 @@
+use crate::bridge::Bridge;
+use crate::config::Config;
+use crate::error::BridgeError;
+use crate::servers::ServerCatalog;
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::json;
+use std::fmt;
+use std::str::FromStr;
+
+/// How the tunnel reaches the server, by the bridge's names.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Transport {
+  /// Whatever the server's config prefers; nothing is pinned.
+  #[default]
+  #[serde(rename = "auto")]
+  Auto,
+  #[serde(rename = "reality")]
+  Reality,
+  #[serde(rename = "vless+ws")]
+  VlessWs,
+  #[serde(rename = "vless+grpc")]
+  VlessGrpc,
+}
+
+impl Transport {
+  pub const ALLOWED: &'static [&'static str] = &["auto", "reality", "vless+ws", "vless+grpc"];
+
+  pub fn as_str(self) -> &'static str {
+    match self {
+      Self::Auto => "auto",
+      Self::Reality => "reality",
+      Self::VlessWs => "vless+ws",
+      Self::VlessGrpc => "vless+grpc",
+    }
+  }
+}
+
+impl fmt::Display for Transport {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str(self.as_str())
+  }
+}
+
+impl FromStr for Transport {
+  type Err = BridgeError;
+
+  fn from_str(value: &str) -> Result<Self, Self::Err> {
+    match value {
+      "auto" => Ok(Self::Auto),
+      "reality" => Ok(Self::Reality),
+      "vless+ws" => Ok(Self::VlessWs),
+      "vless+grpc" => Ok(Self::VlessGrpc),
+      _ => Err(BridgeError::InvalidTransport {
+        got: value.to_string(),
+        allowed: Self::ALLOWED,
+      }),
+    }
+  }
+}
+
+/// A transport this build doesn't know reads as `None` rather than rejecting the whole answer.
+pub fn known_transport<'de, D: Deserializer<'de>>(
+  deserializer: D,
+) -> Result<Option<Transport>, D::Error> {
+  let transport = Option::<String>::deserialize(deserializer)?;
+  Ok(transport.and_then(|transport| transport.parse().ok()))
+}
+
+/// Like `known_transport` for a list; unknown entries are dropped.
+pub fn known_transports<'de, D: Deserializer<'de>>(
+  deserializer: D,
+) -> Result<Vec<Transport>, D::Error> {
+  let transports = Option::<Vec<String>>::deserialize(deserializer)?.unwrap_or_default();
+  Ok(
+    transports
+      .iter()
+      .filter_map(|transport| transport.parse().ok())
+      .collect(),
+  )
+}
+
+/// `Auto` always works. Otherwise the selected server must list `transport`, when the catalog
+/// has that server and it lists any at all; the bridge checks again against the live config.
+pub fn check_offered(
+  transport: Transport,
+  server_id: Option<&str>,
+  catalog: &ServerCatalog,
+) -> Result<(), BridgeError> {
+  let Some(server_id) = server_id.filter(|_| transport != Transport::Auto) else {
+    return Ok(());
+  };
+  let offered = catalog
+    .servers()
+    .into_iter()
+    .find(|server| server.id == server_id)
+    .map(|server| server.transports)
+    .unwrap_or_default();
+  if offered.is_empty() || offered.contains(&transport) {
+    Ok(())
+  } else {
+    Err(BridgeError::UnsupportedTransport {
+      transport: transport.to_string(),
+      server_id: server_id.to_string(),
+    })
+  }
+}
+
+/// Turns the bridge's `TRANSPORT_NOT_OFFERED` into `UnsupportedTransport`.
+fn with_transport_code(
+  error: BridgeError,
+  transport: Transport,
+  server_id: Option<&str>,
+) -> BridgeError {
+  match error.code() {
+    Some("TRANSPORT_NOT_OFFERED") => BridgeError::UnsupportedTransport {
+      transport: transport.to_string(),
+      server_id: server_id.unwrap_or_default().to_string(),
+    },
+    _ => error,
+  }
+}
+
+/// Forwards the transport to the bridge and saves it; `connect` re-sends a pinned one.
+pub async fn set(
+  bridge: &Bridge,
+  config: &Config,
+  catalog: &ServerCatalog,
+  transport: Transport,
+) -> Result<(), BridgeError> {
+  let server_id = config.get().selected_server_id;
+  check_offered(transport, server_id.as_deref(), catalog)?;
+  bridge
+    .call("transport", json!({ "transport": transport }))
+    .await
+    .map_err(|error| with_transport_code(error, transport, server_id.as_deref()))?;
+  config.update(|settings| settings.transport = transport)?;
+  Ok(())
+}
+
+#[allow(non_snake_case)]
+#[tauri::command]
+pub fn getTransport(config: tauri::State<'_, Config>) -> Transport {
+  config.get().transport
+}
+
+#[allow(non_snake_case)]
+#[tauri::command]
+pub async fn setTransport(
+  bridge: tauri::State<'_, Bridge>,
+  config: tauri::State<'_, Config>,
+  catalog: tauri::State<'_, ServerCatalog>,
+  transport: Transport,
+) -> Result<(), BridgeError> {
+  set(&bridge, &config, &catalog, transport).await
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::bridge::mock::MockBridgeRunner;
+  use crate::servers::{self, ServerInfo};
+  use serde_json::Value;
+  use tauri::async_runtime::block_on;
+
+  fn catalog_with(transports: Value) -> ServerCatalog {
+    let catalog = ServerCatalog::default();
+    let servers: Vec<ServerInfo> = servers::from_bridge(json!([
+      { "id": "srv-1", "name": "Amsterdam", "transports": transports }
+    ]))
+    .unwrap();
+    catalog.replace(servers);
+    catalog
+  }
+
+  fn selected(dir: &tempfile::TempDir) -> Config {
+    let config = Config::load(dir.path().join("settings.json"));
+    config
+      .update(|settings| settings.selected_server_id = Some("srv-1".to_string()))
+      .unwrap();
+    config
+  }
+
+  #[test]
+  fn round_trips_through_the_bridge_names() {
+    for name in Transport::ALLOWED {
+      let transport: Transport = serde_json::from_value(json!(name)).unwrap();
+      assert_eq!(transport, name.parse().unwrap());
+      assert_eq!(serde_json::to_value(transport).unwrap(), json!(name));
+    }
+    assert_eq!(
+      serde_json::to_value(Transport::VlessGrpc).unwrap(),
+      json!("vless+grpc")
+    );
+    assert!(serde_json::from_value::<Transport>(json!("vless+quic")).is_err());
+  }
+
+  #[test]
+  fn rejects_unknown_names_with_allowed_values() {
+    let error = "ws".parse::<Transport>().unwrap_err();
+
+    assert_eq!(
+      serde_json::to_value(&error).unwrap(),
+      json!({
+        "kind": "invalidTransport",
+        "message": "Invalid transport \"ws\", expected one of: auto, reality, vless+ws, vless+grpc"
+      })
+    );
+  }
+
+  #[test]
+  fn transport_the_server_lacks_never_reaches_the_bridge() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = selected(&dir);
+    let mock = MockBridgeRunner::default();
+    let bridge = Bridge::new(mock.clone());
+    let catalog = catalog_with(json!(["reality", "vless+ws", "vless+quic"]));
+
+    let error = block_on(set(&bridge, &config, &catalog, Transport::VlessGrpc)).unwrap_err();
+
+    assert_eq!(
+      error,
+      BridgeError::UnsupportedTransport {
+        transport: "vless+grpc".to_string(),
+        server_id: "srv-1".to_string(),
+      }
+    );
+    assert!(mock.calls().is_empty());
+    assert_eq!(config.get().transport, Transport::Auto);
+  }
+
+  #[test]
+  fn offered_transport_is_forwarded_and_saved() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = selected(&dir);
+    let mock = MockBridgeRunner::default().respond("transport", Ok(Value::Null));
+    let bridge = Bridge::new(mock.clone());
+    let catalog = catalog_with(json!(["reality", "vless+ws"]));
+
+    block_on(set(&bridge, &config, &catalog, Transport::VlessWs)).unwrap();
+    assert_eq!(
+      Config::load(dir.path().join("settings.json"))
+        .get()
+        .transport,
+      Transport::VlessWs
+    );
+    // Unpinning is always allowed.
+    block_on(set(&bridge, &config, &catalog, Transport::Auto)).unwrap();
+
+    assert_eq!(
+      mock.calls(),
+      [
+        ("transport".to_string(), json!({ "transport": "vless+ws" })),
+        ("transport".to_string(), json!({ "transport": "auto" })),
+      ]
+    );
+    assert_eq!(
+      Config::load(dir.path().join("settings.json"))
+        .get()
+        .transport,
+      Transport::Auto
+    );
+  }
+
+  #[test]
+  fn bridge_rejection_is_unsupported_transport() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = selected(&dir);
+    let bridge = Bridge::new(MockBridgeRunner::default().respond(
+      "transport",
+      Err(BridgeError::BridgeReported {
+        code: Some("TRANSPORT_NOT_OFFERED".to_string()),
+        message: "Server srv-1 does not offer vless+grpc".to_string(),
+      }),
+    ));
+
+    // An unlisted server can't be checked up front.
+    let error = block_on(set(
+      &bridge,
+      &config,
+      &ServerCatalog::default(),
+      Transport::VlessGrpc,
+    ))
+    .unwrap_err();
+
+    assert_eq!(error.kind(), "unsupportedTransport");
+    assert_eq!(config.get().transport, Transport::Auto);
+  }
+}
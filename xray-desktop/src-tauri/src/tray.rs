@@ -0,0 +1,219 @@
+use crate::bridge::Bridge;
+use crate::commands;
+use crate::config::Config;
+use crate::conflicts;
+use crate::i18n;
+use crate::last_error::{LastError, LastErrorStore, LAST_ERROR_EVENT};
+use crate::reconnect::Watchdog;
+use crate::secrets::Secrets;
+use crate::shutdown;
+use crate::status::{LinkState, StatusSnapshot, STATUS_CHANGED_EVENT};
+use std::sync::{Arc, Mutex};
+use tauri::image::Image;
+use tauri::menu::{Menu, MenuItem, PredefinedMenuItem};
+use tauri::tray::{MouseButton, MouseButtonState, TrayIcon, TrayIconBuilder, TrayIconEvent};
+use tauri::{App, AppHandle, Listener, Manager};
+
+pub const TRAY_ID: &str = "main";
+
+struct TrayIcons {
+  connected: Image<'static>,
+  disconnected: Image<'static>,
+  errored: Image<'static>,
+}
+
+/// Builds the tray icon. Returns an error on platforms without a tray; callers treat that as
+/// "no tray" rather than a fatal setup failure.
+pub fn init(app: &App) -> tauri::Result<()> {
+  let label = |key| i18n::text_for(app.handle(), key);
+  let connect_item = MenuItem::with_id(app, "connect", label("menu.connect"), true, None::<&str>)?;
+  let disconnect_item = MenuItem::with_id(
+    app,
+    "disconnect",
+    label("menu.disconnect"),
+    true,
+    None::<&str>,
+  )?;
+  let show_item = MenuItem::with_id(app, "show", label("menu.show"), true, None::<&str>)?;
+  let quit_item = MenuItem::with_id(app, "quit", label("menu.quit"), true, None::<&str>)?;
+  let separator = PredefinedMenuItem::separator(app)?;
+  let menu = Menu::with_items(
+    app,
+    &[
+      &connect_item,
+      &disconnect_item,
+      &separator,
+      &show_item,
+      &quit_item,
+    ],
+  )?;
+
+  // Left click toggles the connection; the menu is on the right button.
+  let mut builder = TrayIconBuilder::with_id(TRAY_ID)
+    .menu(&menu)
+    .show_menu_on_left_click(false)
+    .on_tray_icon_event(|tray, event| {
+      if let TrayIconEvent::Click {
+        button: MouseButton::Left,
+        button_state: MouseButtonState::Up,
+        ..
+      } = event
+      {
+        toggle_in_background(tray.app_handle());
+      }
+    })
+    .tooltip(label(tooltip(LinkState::Disconnected)))
+    .on_menu_event(|app, event| match event.id().as_ref() {
+      "connect" => connect_in_background(app),
+      "disconnect" => disconnect_in_background(app),
+      "show" => show_main_window(app),
+      "quit" => shutdown::request_quit(app),
+      _ => {}
+    });
+
+  if let Some(icon) = app.default_window_icon() {
+    let icons = TrayIcons {
+      connected: icon.clone().to_owned(),
+      disconnected: dimmed(icon),
+      errored: tinted_red(icon),
+    };
+    builder = builder.icon(icons.disconnected.clone());
+    app.manage(icons);
+  }
+
+  let tray = builder.build(app)?;
+  // An error saved before a crash shows up right away.
+  let errored = app
+    .try_state::<LastErrorStore>()
+    .is_some_and(|store| store.get().is_some());
+  let shown = Arc::new(Mutex::new((LinkState::Disconnected, errored)));
+  update(app.handle(), &tray, LinkState::Disconnected, errored);
+
+  let (handle, status_tray, status_shown) = (app.handle().clone(), tray.clone(), shown.clone());
+  app.listen_any(STATUS_CHANGED_EVENT, move |event| {
+    if let Ok(snapshot) = serde_json::from_str::<StatusSnapshot>(event.payload()) {
+      let mut shown = status_shown.lock().unwrap();
+      shown.0 = snapshot.state();
+      // A working connection supersedes the old failure; `getLastError` still has it.
+      if shown.0 == LinkState::Connected {
+        shown.1 = false;
+      }
+      update(&handle, &status_tray, shown.0, shown.1);
+    }
+  });
+  let handle = app.handle().clone();
+  app.listen_any(LAST_ERROR_EVENT, move |event| {
+    if let Ok(last) = serde_json::from_str::<Option<LastError>>(event.payload()) {
+      let mut shown = shown.lock().unwrap();
+      shown.1 = last.is_some();
+      update(&handle, &tray, shown.0, shown.1);
+    }
+  });
+
+  Ok(())
+}
+
+/// The `i18n` key of the tooltip for `state`.
+fn tooltip(state: LinkState) -> &'static str {
+  match state {
+    LinkState::Connected => "tray.connected",
+    LinkState::Paused => "tray.paused",
+    LinkState::Disconnected => "tray.disconnected",
+  }
+}
+
+/// `errored` only changes the look while disconnected, and only with `trayErrorIndicator` on.
+fn update(app: &AppHandle, tray: &TrayIcon, state: LinkState, errored: bool) {
+  let errored = errored
+    && state == LinkState::Disconnected
+    && app
+      .try_state::<Config>()
+      .is_some_and(|config| config.get().tray_error_indicator);
+  let tooltip = if errored {
+    "tray.error"
+  } else {
+    tooltip(state)
+  };
+  let _ = tray.set_tooltip(Some(i18n::text_for(app, tooltip)));
+  if let Some(icons) = app.try_state::<TrayIcons>() {
+    // No traffic goes through a paused tunnel, so it looks disconnected.
+    let icon = if state == LinkState::Connected {
+      &icons.connected
+    } else if errored {
+      &icons.errored
+    } else {
+      &icons.disconnected
+    };
+    let _ = tray.set_icon(Some(icon.clone()));
+  }
+}
+
+/// Greyed-out, semi-transparent copy of the app icon for the disconnected state.
+fn dimmed(icon: &Image<'_>) -> Image<'static> {
+  let rgba = icon
+    .rgba()
+    .chunks_exact(4)
+    .flat_map(|pixel| {
+      let grey = ((u16::from(pixel[0]) + u16::from(pixel[1]) + u16::from(pixel[2])) / 3) as u8;
+      [grey, grey, grey, pixel[3] / 2]
+    })
+    .collect();
+  Image::new_owned(rgba, icon.width(), icon.height())
+}
+
+/// Red copy of the app icon for "disconnected because something failed".
+fn tinted_red(icon: &Image<'_>) -> Image<'static> {
+  let rgba = icon
+    .rgba()
+    .chunks_exact(4)
+    .flat_map(|pixel| {
+      let grey = ((u16::from(pixel[0]) + u16::from(pixel[1]) + u16::from(pixel[2])) / 3) as u8;
+      [128 + grey / 2, grey / 3, grey / 3, pixel[3]]
+    })
+    .collect();
+  Image::new_owned(rgba, icon.width(), icon.height())
+}
+
+fn connect_in_background(app: &AppHandle) {
+  let app = app.clone();
+  tauri::async_runtime::spawn(async move {
+    conflicts::warn_before_connect(&app, &app.state::<Bridge>()).await;
+    let _ = commands::connect(
+      &app.state::<Bridge>(),
+      &app.state::<Secrets>(),
+      &app.state::<Config>(),
+      &app.state::<Watchdog>(),
+      None,
+    )
+    .await;
+  });
+}
+
+pub fn toggle_in_background(app: &AppHandle) {
+  let app = app.clone();
+  tauri::async_runtime::spawn(async move {
+    let _ = commands::toggle_connection(
+      &app.state::<Bridge>(),
+      &app.state::<Secrets>(),
+      &app.state::<Config>(),
+      &app.state::<Watchdog>(),
+      &app.state::<commands::ToggleLock>(),
+    )
+    .await;
+  });
+}
+
+fn disconnect_in_background(app: &AppHandle) {
+  let app = app.clone();
+  tauri::async_runtime::spawn(async move {
+    let _ = commands::disconnect(&app.state::<Bridge>(), &app.state::<Watchdog>()).await;
+  });
+}
+
+pub fn show_main_window(app: &AppHandle) {
+  if let Some(window) = app.get_webview_window("main") {
+    let _ = window.unminimize();
+    let _ = window.show();
+    let _ = window.set_focus();
+  }
+}
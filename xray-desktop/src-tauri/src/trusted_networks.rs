@@ -0,0 +1,261 @@
+use crate::bridge::Bridge;
+use crate::config::{Config, Settings};
+use crate::error::BridgeError;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// The bridge `networkIdentity` answer; either part is `None` when it couldn't be read.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct NetworkIdentity {
+  pub ssid: Option<String>,
+  pub gateway_mac: Option<String>,
+}
+
+/// `aa:bb:cc:dd:ee:ff` for a MAC written with `:` or `-` and one or two digits per octet, as
+/// BSD `arp` prints them.
+fn mac(raw: &str) -> Option<String> {
+  let octets: Vec<&str> = raw.split([':', '-']).collect();
+  let valid = octets.len() == 6
+    && octets
+      .iter()
+      .all(|octet| (1..=2).contains(&octet.len()) && octet.chars().all(|c| c.is_ascii_hexdigit()));
+  valid.then(|| {
+    octets
+      .iter()
+      .map(|octet| format!("{:0>2}", octet.to_ascii_lowercase()))
+      .collect::<Vec<_>>()
+      .join(":")
+  })
+}
+
+/// A gateway MAC in canonical form, otherwise the SSID as typed (SSIDs are case-sensitive).
+pub fn normalize(raw: &str) -> Result<String, BridgeError> {
+  let raw = raw.trim();
+  if raw.is_empty() {
+    return Err(BridgeError::InvalidTrustedNetwork(
+      "network name is empty".to_string(),
+    ));
+  }
+  if raw.len() > 32 && mac(raw).is_none() {
+    return Err(BridgeError::InvalidTrustedNetwork(format!(
+      "\"{raw}\" is longer than an SSID can be"
+    )));
+  }
+  Ok(mac(raw).unwrap_or_else(|| raw.to_string()))
+}
+
+pub fn is_trusted(identity: &NetworkIdentity, trusted: &[String]) -> bool {
+  let ssid = identity.ssid.as_deref();
+  let gateway = identity.gateway_mac.as_deref().and_then(mac);
+  trusted
+    .iter()
+    .any(|entry| Some(entry.as_str()) == ssid || gateway.as_ref() == Some(entry))
+}
+
+/// An unknown network (detection failed) is treated as untrusted, so the tunnel still comes up.
+pub fn should_auto_connect(settings: &Settings, identity: Option<&NetworkIdentity>) -> bool {
+  settings.auto_connect
+    && identity.is_none_or(|identity| !is_trusted(identity, &settings.trusted_networks))
+}
+
+pub async fn current(bridge: &Bridge) -> Option<NetworkIdentity> {
+  let value = bridge.call("networkIdentity", Value::Null).await.ok()?;
+  serde_json::from_value(value).ok()
+}
+
+/// The identity is only looked up when there are trusted networks to match it against.
+pub async fn auto_connect_allowed(bridge: &Bridge, settings: &Settings) -> bool {
+  if !settings.auto_connect || settings.trusted_networks.is_empty() {
+    return settings.auto_connect;
+  }
+  should_auto_connect(settings, current(bridge).await.as_ref())
+}
+
+pub fn add(config: &Config, network: &str) -> Result<Vec<String>, BridgeError> {
+  let network = normalize(network)?;
+  let settings = config.update(|settings| {
+    if !settings.trusted_networks.contains(&network) {
+      settings.trusted_networks.push(network);
+    }
+  })?;
+  Ok(settings.trusted_networks)
+}
+
+pub fn remove(config: &Config, network: &str) -> Result<Vec<String>, BridgeError> {
+  let network = normalize(network)?;
+  let settings = config.update(|settings| settings.trusted_networks.retain(|n| *n != network))?;
+  Ok(settings.trusted_networks)
+}
+
+/// Trusts the current Wi-Fi by SSID, or a wired network by its gateway MAC. Returns the entry.
+pub async fn add_current(bridge: &Bridge, config: &Config) -> Result<String, BridgeError> {
+  let identity = current(bridge).await.unwrap_or_default();
+  let entry = identity.ssid.or(identity.gateway_mac).ok_or_else(|| {
+    BridgeError::InvalidTrustedNetwork("the current network could not be identified".to_string())
+  })?;
+  let entry = normalize(&entry)?;
+  add(config, &entry)?;
+  Ok(entry)
+}
+
+#[allow(non_snake_case)]
+#[tauri::command]
+pub fn listTrustedNetworks(config: tauri::State<'_, Config>) -> Vec<String> {
+  config.get().trusted_networks
+}
+
+#[allow(non_snake_case)]
+#[tauri::command]
+pub fn addTrustedNetwork(
+  config: tauri::State<'_, Config>,
+  network: String,
+) -> Result<Vec<String>, BridgeError> {
+  add(&config, &network)
+}
+
+#[allow(non_snake_case)]
+#[tauri::command]
+pub fn removeTrustedNetwork(
+  config: tauri::State<'_, Config>,
+  network: String,
+) -> Result<Vec<String>, BridgeError> {
+  remove(&config, &network)
+}
+
+#[allow(non_snake_case)]
+#[tauri::command]
+pub async fn addCurrentNetworkAsTrusted(
+  bridge: tauri::State<'_, Bridge>,
+  config: tauri::State<'_, Config>,
+) -> Result<String, BridgeError> {
+  add_current(&bridge, &config).await
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::bridge::mock::MockBridgeRunner;
+  use serde_json::json;
+  use tauri::async_runtime::block_on;
+
+  fn on(ssid: Option<&str>, gateway_mac: Option<&str>) -> NetworkIdentity {
+    NetworkIdentity {
+      ssid: ssid.map(str::to_string),
+      gateway_mac: gateway_mac.map(str::to_string),
+    }
+  }
+
+  fn settings(trusted: &[&str]) -> Settings {
+    Settings {
+      auto_connect: true,
+      trusted_networks: trusted.iter().map(|n| n.to_string()).collect(),
+      ..Settings::default()
+    }
+  }
+
+  #[test]
+  fn macs_are_canonical_and_ssids_kept_as_typed() {
+    assert_eq!(normalize("AA-BB-CC-0D-0E-0F").unwrap(), "aa:bb:cc:0d:0e:0f");
+    assert_eq!(normalize("0:1a:2b:3c:4d:5e").unwrap(), "00:1a:2b:3c:4d:5e");
+    assert_eq!(normalize("  Home WiFi ").unwrap(), "Home WiFi");
+    assert!(matches!(
+      normalize(" "),
+      Err(BridgeError::InvalidTrustedNetwork(_))
+    ));
+    assert!(matches!(
+      normalize(&"x".repeat(33)),
+      Err(BridgeError::InvalidTrustedNetwork(_))
+    ));
+  }
+
+  #[test]
+  fn skips_auto_connect_on_a_trusted_network() {
+    let settings = settings(&["Home WiFi", "00:1a:2b:3c:4d:5e"]);
+
+    assert!(!should_auto_connect(
+      &settings,
+      Some(&on(Some("Home WiFi"), None))
+    ));
+    // Wired, matched by the router; BSD arp drops leading zeros.
+    assert!(!should_auto_connect(
+      &settings,
+      Some(&on(None, Some("0:1A:2B:3C:4D:5E")))
+    ));
+    assert!(should_auto_connect(
+      &settings,
+      Some(&on(Some("home wifi"), Some("aa:bb:cc:dd:ee:ff")))
+    ));
+    assert!(should_auto_connect(&settings, Some(&on(None, None))));
+    assert!(should_auto_connect(&settings, None));
+  }
+
+  #[test]
+  fn nothing_auto_connects_with_auto_connect_off() {
+    let settings = Settings {
+      auto_connect: false,
+      ..settings(&[])
+    };
+
+    assert!(!should_auto_connect(&settings, None));
+    assert!(!block_on(auto_connect_allowed(
+      &Bridge::new(MockBridgeRunner::default()),
+      &settings
+    )));
+  }
+
+  #[test]
+  fn the_identity_is_only_looked_up_with_trusted_networks() {
+    let mock = MockBridgeRunner::default().respond(
+      "networkIdentity",
+      Ok(json!({ "ssid": "Home WiFi", "gatewayMac": null })),
+    );
+    let bridge = Bridge::new(mock.clone());
+
+    assert!(block_on(auto_connect_allowed(&bridge, &settings(&[]))));
+    assert!(mock.calls().is_empty());
+
+    assert!(!block_on(auto_connect_allowed(
+      &bridge,
+      &settings(&["Home WiFi"])
+    )));
+    assert!(block_on(auto_connect_allowed(
+      &bridge,
+      &settings(&["Office"])
+    )));
+  }
+
+  #[test]
+  fn trusts_the_current_network_by_ssid_then_gateway() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = Config::load(dir.path().join("settings.json"));
+    let wifi = Bridge::new(MockBridgeRunner::default().respond(
+      "networkIdentity",
+      Ok(json!({ "ssid": "Cafe", "gatewayMac": "aa:bb:cc:dd:ee:ff" })),
+    ));
+    let wired = Bridge::new(MockBridgeRunner::default().respond(
+      "networkIdentity",
+      Ok(json!({ "ssid": null, "gatewayMac": "AA-BB-CC-00-11-22" })),
+    ));
+    let unknown =
+      Bridge::new(MockBridgeRunner::default().respond("networkIdentity", Ok(json!({}))));
+
+    assert_eq!(block_on(add_current(&wifi, &config)).unwrap(), "Cafe");
+    assert_eq!(
+      block_on(add_current(&wired, &config)).unwrap(),
+      "aa:bb:cc:00:11:22"
+    );
+    assert!(matches!(
+      block_on(add_current(&unknown, &config)),
+      Err(BridgeError::InvalidTrustedNetwork(_))
+    ));
+    // Adding twice keeps one entry.
+    block_on(add_current(&wifi, &config)).unwrap();
+
+    assert_eq!(config.get().trusted_networks, ["Cafe", "aa:bb:cc:00:11:22"]);
+    assert_eq!(
+      remove(&config, "AA:BB:CC:00:11:22").unwrap(),
+      ["Cafe".to_string()]
+    );
+  }
+}
@@ -0,0 +1,137 @@
+use crate::bridge::Bridge;
+use crate::config::Config;
+use crate::error::BridgeError;
+use semver::Version;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// Only reported; nothing is downloaded.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UpdateInfo {
+  pub current: String,
+  pub latest: String,
+  pub update_available: bool,
+  pub notes_url: Option<String>,
+}
+
+/// The bridge `latestRelease` answer, from the GitHub releases feed.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Release {
+  version: String,
+  #[serde(default)]
+  notes_url: Option<String>,
+}
+
+/// Accepts release tags as well, e.g. `v1.4.0`.
+fn parse(version: &str) -> Result<Version, BridgeError> {
+  let trimmed = version.trim();
+  Version::parse(trimmed.strip_prefix('v').unwrap_or(trimmed)).map_err(|error| {
+    BridgeError::InvalidJson(format!("invalid release version \"{version}\": {error}"))
+  })
+}
+
+/// Whether `latest` should be offered over `current`. Pre-releases only go to users already on
+/// one, so a stable build is never nudged onto a beta.
+pub fn newer(current: &Version, latest: &Version) -> bool {
+  latest > current && (latest.pre.is_empty() || !current.pre.is_empty())
+}
+
+/// `None` without asking anyone when the user opted out with `checkUpdates: false`.
+pub async fn check(
+  bridge: &Bridge,
+  config: &Config,
+  current: &str,
+) -> Result<Option<UpdateInfo>, BridgeError> {
+  if !config.get().check_updates {
+    return Ok(None);
+  }
+  let release: Release = serde_json::from_value(bridge.call("latestRelease", Value::Null).await?)
+    .map_err(|error| BridgeError::InvalidJson(error.to_string()))?;
+  let update_available = newer(&parse(current)?, &parse(&release.version)?);
+  Ok(Some(UpdateInfo {
+    current: current.to_string(),
+    latest: release.version,
+    update_available,
+    notes_url: release.notes_url,
+  }))
+}
+
+#[allow(non_snake_case)]
+#[tauri::command]
+pub async fn checkForUpdates(
+  bridge: tauri::State<'_, Bridge>,
+  config: tauri::State<'_, Config>,
+) -> Result<Option<UpdateInfo>, BridgeError> {
+  check(&bridge, &config, env!("CARGO_PKG_VERSION")).await
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::bridge::mock::MockBridgeRunner;
+  use serde_json::json;
+  use tauri::async_runtime::block_on;
+
+  fn newer_than(current: &str, latest: &str) -> bool {
+    newer(&parse(current).unwrap(), &parse(latest).unwrap())
+  }
+
+  #[test]
+  fn compares_versions_numerically() {
+    assert!(newer_than("0.9.0", "0.10.0"));
+    assert!(newer_than("1.2.3", "v1.2.4"));
+    assert!(!newer_than("1.2.3", "1.2.3"));
+    assert!(!newer_than("2.0.0", "1.99.99"));
+  }
+
+  #[test]
+  fn pre_releases_are_offered_only_to_pre_release_builds() {
+    assert!(!newer_than("1.2.3", "1.3.0-beta.1"));
+    assert!(newer_than("1.3.0-beta.1", "1.3.0-beta.2"));
+    assert!(newer_than("1.3.0-beta.2", "1.3.0"));
+    assert!(!newer_than("1.3.0", "1.3.0-rc.1"));
+  }
+
+  #[test]
+  fn rejects_a_tag_that_is_not_semver() {
+    assert_eq!(parse("latest").unwrap_err().kind(), "invalidJson");
+  }
+
+  #[test]
+  fn reports_an_available_update() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = Config::load(dir.path().join("settings.json"));
+    let bridge = Bridge::new(MockBridgeRunner::default().respond(
+      "latestRelease",
+      Ok(json!({ "version": "v0.2.0", "notesUrl": "https://example.com/releases/v0.2.0" })),
+    ));
+
+    assert_eq!(
+      block_on(check(&bridge, &config, "0.1.0")).unwrap(),
+      Some(UpdateInfo {
+        current: "0.1.0".to_string(),
+        latest: "v0.2.0".to_string(),
+        update_available: true,
+        notes_url: Some("https://example.com/releases/v0.2.0".to_string()),
+      })
+    );
+  }
+
+  #[test]
+  fn opted_out_users_are_never_checked() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = Config::load(dir.path().join("settings.json"));
+    config
+      .update(|settings| settings.check_updates = false)
+      .unwrap();
+    let mock = MockBridgeRunner::default();
+
+    assert_eq!(
+      block_on(check(&Bridge::new(mock.clone()), &config, "0.1.0")),
+      Ok(None)
+    );
+    assert!(mock.calls().is_empty());
+  }
+}
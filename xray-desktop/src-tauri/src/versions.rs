@@ -0,0 +1,94 @@
+use crate::bridge::{Bridge, NodeBridgeRunner};
+use crate::error::BridgeError;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// What to paste into a bug report. Every part but `app` is `None` when it couldn't be found out.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Versions {
+  pub app: String,
+  pub bridge: Option<String>,
+  pub xray: Option<String>,
+  pub node: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct BridgeVersions {
+  bridge: Option<String>,
+  xray: Option<String>,
+}
+
+impl Versions {
+  /// `bridge_reply` is the answer to the `version` action, `None` if the call failed.
+  pub fn assemble(bridge_reply: Option<Value>, node: Option<String>) -> Self {
+    let reported: BridgeVersions = bridge_reply
+      .and_then(|reply| serde_json::from_value(reply).ok())
+      .unwrap_or_default();
+    Self {
+      app: env!("CARGO_PKG_VERSION").to_string(),
+      bridge: reported.bridge,
+      xray: reported.xray,
+      node,
+    }
+  }
+}
+
+pub async fn collect(bridge: &Bridge, node: Option<String>) -> Versions {
+  Versions::assemble(bridge.call("version", Value::Null).await.ok(), node)
+}
+
+#[allow(non_snake_case)]
+#[tauri::command]
+pub async fn getVersions(
+  bridge: tauri::State<'_, Bridge>,
+  node: tauri::State<'_, NodeBridgeRunner>,
+  // Never fails; `Result` only because async commands borrowing state must return one.
+) -> Result<Versions, BridgeError> {
+  Ok(collect(&bridge, node.node_version()).await)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::bridge::mock::MockBridgeRunner;
+  use serde_json::json;
+  use tauri::async_runtime::block_on;
+
+  #[test]
+  fn assembles_versions_from_partial_data() {
+    assert_eq!(
+      Versions::assemble(
+        Some(json!({ "bridge": "0.4.2", "xray": null })),
+        Some("20.11.1".to_string())
+      ),
+      Versions {
+        app: env!("CARGO_PKG_VERSION").to_string(),
+        bridge: Some("0.4.2".to_string()),
+        xray: None,
+        node: Some("20.11.1".to_string()),
+      }
+    );
+    assert_eq!(
+      Versions::assemble(Some(json!("garbage")), None),
+      Versions::assemble(None, None)
+    );
+  }
+
+  #[test]
+  fn unavailable_bridge_leaves_its_versions_empty() {
+    let bridge = Bridge::new(MockBridgeRunner::default().respond(
+      "version",
+      Err(BridgeError::NodeNotFound("not on PATH".to_string())),
+    ));
+
+    let versions = block_on(collect(&bridge, None));
+
+    assert_eq!(versions.app, env!("CARGO_PKG_VERSION"));
+    assert_eq!(
+      (versions.bridge, versions.xray, versions.node),
+      (None, None, None)
+    );
+  }
+}
@@ -0,0 +1,240 @@
+use crate::persist;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Manager, Monitor, PhysicalPosition, PhysicalSize, WebviewWindow, Window};
+
+const STATE_FILE: &str = "window-state.json";
+/// Dragging fires move events continuously; writes are spaced out and flushed on exit.
+const SAVE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Outer position and inner size of the main window, in physical pixels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Geometry {
+  pub x: i32,
+  pub y: i32,
+  pub width: u32,
+  pub height: u32,
+}
+
+impl Geometry {
+  fn overlap(&self, other: &Geometry) -> u64 {
+    let width =
+      (self.x + self.width as i32).min(other.x + other.width as i32) - self.x.max(other.x);
+    let height =
+      (self.y + self.height as i32).min(other.y + other.height as i32) - self.y.max(other.y);
+    if width <= 0 || height <= 0 {
+      return 0;
+    }
+    width as u64 * height as u64
+  }
+}
+
+/// Moves and shrinks `saved` so it lies fully on the monitor it overlaps most. A window that
+/// overlaps no monitor (e.g. its display was unplugged) goes to `primary`, or the first
+/// monitor when the primary is unknown.
+pub fn clamp(saved: Geometry, monitors: &[Geometry], primary: Option<Geometry>) -> Geometry {
+  let target = monitors
+    .iter()
+    .copied()
+    .map(|monitor| (saved.overlap(&monitor), monitor))
+    .filter(|(overlap, _)| *overlap > 0)
+    .max_by_key(|(overlap, _)| *overlap)
+    .map(|(_, monitor)| monitor)
+    .or(primary)
+    .or_else(|| monitors.first().copied());
+  let Some(monitor) = target else {
+    return saved;
+  };
+
+  let width = saved.width.min(monitor.width);
+  let height = saved.height.min(monitor.height);
+  Geometry {
+    x: saved
+      .x
+      .clamp(monitor.x, monitor.x + (monitor.width - width) as i32),
+    y: saved
+      .y
+      .clamp(monitor.y, monitor.y + (monitor.height - height) as i32),
+    width,
+    height,
+  }
+}
+
+fn monitor_bounds(monitor: &Monitor) -> Geometry {
+  let position = monitor.position();
+  let size = monitor.size();
+  Geometry {
+    x: position.x,
+    y: position.y,
+    width: size.width,
+    height: size.height,
+  }
+}
+
+/// Saves the main window geometry as `window-state.json` in the app config dir.
+pub struct WindowState {
+  path: PathBuf,
+  pending: Mutex<Option<Geometry>>,
+  last_save: Mutex<Option<Instant>>,
+}
+
+impl WindowState {
+  pub fn for_app(app: &AppHandle) -> tauri::Result<Self> {
+    Ok(Self {
+      path: app.path().app_config_dir()?.join(STATE_FILE),
+      pending: Mutex::new(None),
+      last_save: Mutex::new(None),
+    })
+  }
+
+  fn load(&self) -> Option<Geometry> {
+    persist::load(&self.path, |raw| serde_json::from_str(raw).ok())
+  }
+
+  /// Applies the saved geometry, kept within the monitors connected right now.
+  pub fn restore(&self, window: &WebviewWindow) {
+    let Some(saved) = self.load() else {
+      return;
+    };
+    let monitors: Vec<Geometry> = window
+      .available_monitors()
+      .unwrap_or_default()
+      .iter()
+      .map(monitor_bounds)
+      .collect();
+    let primary = window
+      .primary_monitor()
+      .ok()
+      .flatten()
+      .map(|monitor| monitor_bounds(&monitor));
+
+    let geometry = clamp(saved, &monitors, primary);
+    let _ = window.set_size(PhysicalSize::new(geometry.width, geometry.height));
+    let _ = window.set_position(PhysicalPosition::new(geometry.x, geometry.y));
+  }
+
+  /// Called on every move and resize of the main window.
+  pub fn record(&self, window: &Window) {
+    // Minimized windows report bogus positions, and a maximized size is not the one to restore.
+    if window.is_minimized().unwrap_or(false) || window.is_maximized().unwrap_or(false) {
+      return;
+    }
+    let (Ok(position), Ok(size)) = (window.outer_position(), window.inner_size()) else {
+      return;
+    };
+    *self.pending.lock().unwrap() = Some(Geometry {
+      x: position.x,
+      y: position.y,
+      width: size.width,
+      height: size.height,
+    });
+
+    let mut last_save = self.last_save.lock().unwrap();
+    if last_save.is_none_or(|at| at.elapsed() >= SAVE_INTERVAL) {
+      *last_save = Some(Instant::now());
+      drop(last_save);
+      self.flush();
+    }
+  }
+
+  /// Deletes the saved geometry, including any not written yet; returns whether there was a
+  /// file. The window keeps its place until it is moved again.
+  pub fn forget(&self) -> bool {
+    self.pending.lock().unwrap().take();
+    persist::remove(&self.path).unwrap_or(false)
+  }
+
+  /// Writes the latest recorded geometry, if it hasn't been written yet.
+  pub fn flush(&self) {
+    let Some(geometry) = self.pending.lock().unwrap().take() else {
+      return;
+    };
+    let Ok(raw) = serde_json::to_string(&geometry) else {
+      return;
+    };
+    let _ = persist::atomic_write(&self.path, raw);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn rect(x: i32, y: i32, width: u32, height: u32) -> Geometry {
+    Geometry {
+      x,
+      y,
+      width,
+      height,
+    }
+  }
+
+  const LAPTOP: Geometry = Geometry {
+    x: 0,
+    y: 0,
+    width: 1920,
+    height: 1080,
+  };
+  const EXTERNAL: Geometry = Geometry {
+    x: 1920,
+    y: 0,
+    width: 2560,
+    height: 1440,
+  };
+
+  #[test]
+  fn visible_window_is_left_alone() {
+    let saved = rect(2200, 300, 760, 560);
+
+    assert_eq!(clamp(saved, &[LAPTOP, EXTERNAL], Some(LAPTOP)), saved);
+  }
+
+  #[test]
+  fn window_hanging_off_an_edge_is_pulled_back() {
+    assert_eq!(
+      clamp(rect(1500, 900, 760, 560), &[LAPTOP], Some(LAPTOP)),
+      rect(1160, 520, 760, 560)
+    );
+    assert_eq!(
+      clamp(rect(-300, -40, 760, 560), &[LAPTOP], Some(LAPTOP)),
+      rect(0, 0, 760, 560)
+    );
+  }
+
+  #[test]
+  fn straddling_window_moves_to_the_monitor_it_mostly_covers() {
+    // 160px on the laptop, 600px on the external display.
+    assert_eq!(
+      clamp(rect(1760, 100, 760, 560), &[LAPTOP, EXTERNAL], Some(LAPTOP)),
+      rect(1920, 100, 760, 560)
+    );
+  }
+
+  #[test]
+  fn window_on_an_unplugged_monitor_falls_back_to_the_primary() {
+    let saved = rect(2200, 300, 760, 560);
+
+    assert_eq!(
+      clamp(saved, &[LAPTOP], Some(LAPTOP)),
+      rect(1160, 300, 760, 560)
+    );
+    assert_eq!(clamp(saved, &[LAPTOP], None), rect(1160, 300, 760, 560));
+  }
+
+  #[test]
+  fn oversized_window_shrinks_to_the_monitor() {
+    assert_eq!(
+      clamp(rect(100, 100, 2560, 1440), &[LAPTOP], Some(LAPTOP)),
+      LAPTOP
+    );
+  }
+
+  #[test]
+  fn no_monitor_information_keeps_the_saved_geometry() {
+    let saved = rect(2200, 300, 760, 560);
+
+    assert_eq!(clamp(saved, &[], None), saved);
+  }
+}
@@ -1,41 +1,123 @@
 import { invoke } from '@tauri-apps/api/core';
 import { getVersion } from '@tauri-apps/api/app';
-import { getCurrent as getDeepLinks, onOpenUrl } from '@tauri-apps/plugin-deep-link';
+import { listen } from '@tauri-apps/api/event';
 
 import './styles.css';
 
-interface AgentStatus {
+// Mirrors `StatusSnapshot` in src-tauri/src/status.rs.
+interface StatusSnapshot {
   connected: boolean;
-  running: boolean;
-  pid: number | null;
-  supervisorPid: number | null;
+  mode: 'proxy' | 'vpn' | null;
+  serverId: string | null;
+  uptimeSecs: number | null;
+  publicIp: string | null;
   lastError: string | null;
-  imported: boolean;
-  importedConfig: {
-    baseUrl: string;
-    serverId: string;
-    server: {
-      host: string;
-      port: number;
-    };
-    reality: {
-      publicKey: string;
-      serverName: string;
-      fingerprint: string;
-      shortId: string;
-      dest: string;
-    };
-  } | null;
-  proxyEnabled: boolean;
-  mode: 'proxy' | 'vpn';
-  logsPath: string;
-  agentLogPath: string;
-  xrayLogPath: string;
+  killSwitchActive: boolean;
+  dns: string[];
+  baseUrl: string | null;
+  disguise: string | null;
+  logsPath: string | null;
+  paused: boolean;
+  ipv6Enabled: boolean | null;
+  mtu: number | null;
+  bandwidthLimit: BandwidthLimit;
+  transport: Transport | null;
 }
 
-interface DeepLinkImportPayload {
+type Transport = 'auto' | 'reality' | 'vless+ws' | 'vless+grpc';
+
+interface CommandError {
+  kind: string;
+  message: string;
+  code?: string;
+}
+
+interface Settings {
+  schemaVersion: number;
+  defaultBaseUrl: string | null;
+  lastMode: 'proxy' | 'vpn' | null;
+  autoConnect: boolean;
+  autoReconnect: boolean;
+  selectedServerId: string | null;
+  autostart: boolean;
+  bridgeSpawnRetries: number;
+  notificationsEnabled: boolean;
+  killSwitch: boolean;
+  splitTunnel: SplitRule[];
+  dnsServers: string[];
+  profiles: Profile[];
+  activeProfileId: string | null;
+  tokenRefreshWindowSecs: number;
+  bridgePoolSize: number | null;
+  allowInsecureBaseUrl: boolean;
+  closeToTray: boolean;
+  ipv6Enabled: boolean;
+  mtu: number | null;
+  checkUpdates: boolean;
+  bridgeEnv: { env: Record<string, string>; cwd: string | null };
+  localApi: boolean;
+  confirmQuit: boolean;
+  schedules: { id: string; cronLike: string; action: 'connect' | 'disconnect' }[];
+  favoriteServers: string[];
+  recentServers: string[];
+  prewarmBridge: boolean;
+  bandwidthLimit: BandwidthLimit;
+  trayErrorIndicator: boolean;
+  hotkey: string | null;
+  onboardingComplete: boolean;
+  trustedNetworks: string[];
+  telemetryLocal: boolean;
+  statusPollMs: number | null;
+  verifyBridge: boolean;
+  appRules: AppRule[];
+  transport: Transport;
+  pinnedFingerprints: Record<string, string>;
+  eventBatching: { flushMs: number; maxBatch: number };
+  locale: string;
+  debugMode: boolean;
+  importedServers: ImportedServer[];
+}
+
+// Kilobits per second; `null` is unlimited.
+interface BandwidthLimit {
+  upKbps: number | null;
+  downKbps: number | null;
+}
+
+interface Profile {
+  id: string;
+  name: string;
   baseUrl: string;
-  token: string;
+  tokenRef: string;
+  proxy: ProxyEnv;
+}
+
+// From `importExternalConfig`; the outbound with its credentials stays in the keyring.
+interface ImportedServer {
+  id: string;
+  name: string;
+  country: string | null;
+  endpoint: string | null;
+  group: string | null;
+  transports: Transport[];
+}
+
+interface ProxyEnv {
+  httpProxy?: string | null;
+  httpsProxy?: string | null;
+  noProxy?: string | null;
+  nodeExtraCaCerts?: string | null;
+}
+
+interface SplitRule {
+  id: string;
+  kind: 'app' | 'cidr';
+  value: string;
+}
+
+interface AppRule {
+  executablePath: string;
+  policy: 'forceVpn' | 'bypass';
 }
 
 const baseUrlInput = must<HTMLInputElement>('baseUrl');
@@ -54,7 +136,7 @@ const disguiseCustom = must<HTMLInputElement>('disguiseCustom');
 const adminApiKeyInput = must<HTMLInputElement>('adminApiKey');
 const applyDisguiseBtn = must<HTMLButtonElement>('applyDisguiseBtn');
 
-let latestStatus: AgentStatus | null = null;
+let latestStatus: StatusSnapshot | null = null;
 
 function must<T extends HTMLElement>(id: string): T {
   const value = document.getElementById(id);
@@ -62,25 +144,33 @@ function must<T extends HTMLElement>(id: string): T {
   return value as T;
 }
 
+function errorText(error: unknown): string {
+  if (error instanceof Error) return error.message;
+  if (error && typeof error === 'object' && 'message' in error) {
+    return String((error as CommandError).message);
+  }
+  return String(error);
+}
+
 function setMessage(text: string): void {
   message.textContent = text;
 }
 
-function renderStatus(status: AgentStatus): void {
+function renderStatus(status: StatusSnapshot): void {
   latestStatus = status;
-  modeSelect.value = status.mode;
-  disguiseLine.textContent = status.importedConfig
-    ? `Disguised as traffic from ${status.importedConfig.reality.serverName}`
-    : 'Disguised as traffic from -';
+  modeSelect.value = status.mode ?? 'proxy';
+  disguiseLine.textContent = `Disguised as traffic from ${status.disguise ?? '-'}`;
 
   statusBox.textContent = JSON.stringify(
     {
-      state: status.connected ? 'Connected' : 'Disconnected',
+      state: status.paused ? 'Paused' : status.connected ? 'Connected' : 'Disconnected',
       lastError: status.lastError,
-      pid: status.pid,
-      supervisorPid: status.supervisorPid,
-      imported: status.imported,
+      serverId: status.serverId,
       mode: status.mode,
+      uptimeSecs: status.uptimeSecs,
+      publicIp: status.publicIp,
+      killSwitchActive: status.killSwitchActive,
+      dns: status.dns,
       logsPath: status.logsPath,
     },
     null,
@@ -88,8 +178,9 @@ function renderStatus(status: AgentStatus): void {
   );
 }
 
-async function refreshStatus(): Promise<void> {
-  const status = await invoke<AgentStatus>('status');
+// `forceRefresh` skips the shell's 500ms status cache, e.g. right after an action.
+async function refreshStatus(forceRefresh = false): Promise<void> {
+  const status = await invoke<StatusSnapshot>('status', { forceRefresh });
   renderStatus(status);
 }
 
@@ -99,12 +190,11 @@ async function withAction(name: string, action: () => Promise<void>): Promise<vo
 
   try {
     await action();
-    await refreshStatus();
+    await refreshStatus(true);
     setMessage(`${name} completed`);
   } catch (error) {
-    const text = error instanceof Error ? error.message : String(error);
-    setMessage(`${name} failed: ${text}`);
-    await refreshStatus().catch(() => undefined);
+    setMessage(`${name} failed: ${errorText(error)}`);
+    await refreshStatus(true).catch(() => undefined);
   } finally {
     setDisabled(false);
   }
@@ -122,82 +212,6 @@ function setDisabled(value: boolean): void {
   adminApiKeyInput.disabled = value;
 }
 
-function parseImportDeepLink(rawUrl: string): DeepLinkImportPayload {
-  let parsed: URL;
-  try {
-    parsed = new URL(rawUrl);
-  } catch {
-    throw new Error('Malformed deep link URL');
-  }
-
-  if (parsed.protocol !== 'xraycp:') {
-    throw new Error('Unsupported deep link scheme');
-  }
-
-  const action = (parsed.hostname || parsed.pathname.replace(/^\/+/, '')).toLowerCase();
-  if (action !== 'import') {
-    throw new Error('Unsupported deep link action');
-  }
-
-  const baseUrl = (parsed.searchParams.get('baseUrl') ?? '').trim();
-  const token = (parsed.searchParams.get('token') ?? '').trim();
-
-  if (!baseUrl) {
-    throw new Error('Missing baseUrl parameter');
-  }
-
-  if (!token) {
-    throw new Error('Missing token parameter');
-  }
-
-  let parsedBaseUrl: URL;
-  try {
-    parsedBaseUrl = new URL(baseUrl);
-  } catch {
-    throw new Error('Invalid baseUrl parameter');
-  }
-
-  if (parsedBaseUrl.protocol !== 'http:' && parsedBaseUrl.protocol !== 'https:') {
-    throw new Error('baseUrl must use http or https');
-  }
-
-  if (!/^[A-Za-z0-9_-]{16,}$/.test(token)) {
-    throw new Error('Invalid token format');
-  }
-
-  return {
-    baseUrl,
-    token,
-  };
-}
-
-async function processDeepLinks(urls: string[]): Promise<void> {
-  if (urls.length === 0) return;
-
-  let payload: DeepLinkImportPayload | null = null;
-  for (const rawUrl of urls) {
-    try {
-      payload = parseImportDeepLink(rawUrl);
-      break;
-    } catch {
-      continue;
-    }
-  }
-
-  if (!payload) {
-    setMessage('Deep link ignored: invalid format. Expected xraycp://import?...');
-    return;
-  }
-
-  await withAction('Import from link', async () => {
-    await invoke('importToken', { baseUrl: payload.baseUrl, token: payload.token });
-  });
-
-  baseUrlInput.value = payload.baseUrl;
-  shareTokenInput.value = '';
-  setMessage('Import from deep link completed. Press Connect to start tunnel.');
-}
-
 importBtn.addEventListener('click', async () => {
   const baseUrl = baseUrlInput.value.trim();
   const token = shareTokenInput.value.trim();
@@ -228,13 +242,13 @@ modeSelect.addEventListener('change', async () => {
 
 applyDisguiseBtn.addEventListener('click', async () => {
   const status = latestStatus;
-  if (!status?.importedConfig) {
+  if (!status?.serverId || !status.baseUrl) {
     setMessage('Import config first');
     return;
   }
 
-  const serverId = status.importedConfig.serverId;
-  const baseUrl = status.importedConfig.baseUrl;
+  const serverId = status.serverId;
+  const baseUrl = status.baseUrl;
   const adminApiKey = adminApiKeyInput.value.trim();
   if (!adminApiKey) {
     setMessage('Admin API key is required to apply disguise');
@@ -299,23 +313,36 @@ async function initialize(): Promise<void> {
     appVersionLabel.textContent = 'Version: unknown';
   }
 
+  const settings = await invoke<Settings>('getSettings');
+  if (settings.defaultBaseUrl && !baseUrlInput.value) {
+    baseUrlInput.value = settings.defaultBaseUrl;
+  }
+
   await refreshStatus();
 
-  try {
-    const startUrls = (await getDeepLinks()) ?? [];
-    if (startUrls.length > 0) {
-      await processDeepLinks(startUrls);
-    }
+  await listen('vpn://status-changed', () => {
+    void refreshStatus().catch(() => undefined);
+  });
 
-    await onOpenUrl((urls) => {
-      void processDeepLinks(urls);
-    });
-  } catch {
-    setMessage('Deep link listener unavailable');
-  }
+  await listen<{ baseUrl: string }>('vpn://token-imported', (event) => {
+    baseUrlInput.value = event.payload.baseUrl;
+    shareTokenInput.value = '';
+    setMessage('Import from deep link completed. Press Connect to start tunnel.');
+    void refreshStatus().catch(() => undefined);
+  });
+
+  await listen<CommandError>('vpn://deep-link-error', (event) => {
+    setMessage(`Import from link failed: ${errorText(event.payload)}`);
+  });
+
+  await listen('vpn://confirm-quit', () => {
+    const disconnect = window.confirm(
+      'The VPN is connected. Disconnect before quitting?\n\nCancel quits and leaves the tunnel up.',
+    );
+    void invoke('confirmQuit', { disconnect });
+  });
 }
 
 void initialize().catch((error) => {
-  const text = error instanceof Error ? error.message : String(error);
-  statusBox.textContent = `Failed to load status: ${text}`;
+  statusBox.textContent = `Failed to load status: ${errorText(error)}`;
 });